@@ -0,0 +1,26 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use trippy_core::{parse_ipv4_icmp_probe, MultipathStrategy, PortDirection, TracerProtocol};
+
+// The first byte selects the protocol, multipath strategy and port direction, the rest is the packet received.
+fuzz_target!(|data: &[u8]| {
+    if let Some((&selector, packet)) = data.split_first() {
+        let protocol = match selector % 3 {
+            0 => TracerProtocol::Icmp,
+            1 => TracerProtocol::Udp,
+            _ => TracerProtocol::Tcp,
+        };
+        let multipath_strategy = match (selector / 3) % 3 {
+            0 => MultipathStrategy::Classic,
+            1 => MultipathStrategy::Paris,
+            _ => MultipathStrategy::Dublin,
+        };
+        let direction = match (selector / 9) % 3 {
+            0 => PortDirection::new_fixed_src(33434),
+            1 => PortDirection::new_fixed_dest(33434),
+            _ => PortDirection::None,
+        };
+        let _ = parse_ipv4_icmp_probe(packet, protocol, multipath_strategy, direction);
+    }
+});
@@ -0,0 +1,22 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use std::net::Ipv6Addr;
+use trippy_core::{parse_ipv6_icmp_probe, PortDirection, TracerProtocol};
+
+// The first byte selects the protocol and port direction, the rest is the packet received.
+fuzz_target!(|data: &[u8]| {
+    if let Some((&selector, packet)) = data.split_first() {
+        let protocol = match selector % 3 {
+            0 => TracerProtocol::Icmp,
+            1 => TracerProtocol::Udp,
+            _ => TracerProtocol::Tcp,
+        };
+        let direction = match (selector / 3) % 3 {
+            0 => PortDirection::new_fixed_src(33434),
+            1 => PortDirection::new_fixed_dest(33434),
+            _ => PortDirection::None,
+        };
+        let _ = parse_ipv6_icmp_probe(packet, protocol, direction, Ipv6Addr::LOCALHOST);
+    }
+});
@@ -0,0 +1,542 @@
+use chrono::{DateTime, Local};
+use itertools::Itertools;
+use parking_lot::RwLock;
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// A change in the state of a trace which the user may wish to be alerted to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Alert {
+    /// The target, which had responded, has not responded for `rounds` consecutive rounds.
+    Unreachable { target: IpAddr, rounds: usize },
+    /// The target has responded again after being unreachable.
+    Reachable { target: IpAddr },
+    /// The hop at `ttl` responded from `addr`, which is not one of the `previous` addresses of the recent rounds.
+    HopChanged {
+        target: IpAddr,
+        ttl: u8,
+        addr: IpAddr,
+        previous: Vec<IpAddr>,
+    },
+    /// The path to the target has breached a threshold, since the round at `since`.
+    Degraded {
+        target: IpAddr,
+        breach: Breach,
+        since: SystemTime,
+    },
+    /// The path to the target has not breached any threshold since the round at `since`.
+    Recovered { target: IpAddr, since: SystemTime },
+}
+
+impl Display for Alert {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unreachable { target, rounds } => {
+                write!(f, "target {target} unreachable for {rounds} rounds")
+            }
+            Self::Reachable { target } => write!(f, "target {target} reachable again"),
+            Self::HopChanged {
+                target,
+                ttl,
+                addr,
+                previous,
+            } => write!(
+                f,
+                "hop {ttl} towards {target} changed to {addr} from {}",
+                previous.iter().join(", ")
+            ),
+            Self::Degraded {
+                target,
+                breach,
+                since,
+            } => write!(
+                f,
+                "target {target} degraded since {}, {breach}",
+                format_time(*since)
+            ),
+            Self::Recovered { target, since } => {
+                write!(f, "target {target} recovered since {}", format_time(*since))
+            }
+        }
+    }
+}
+
+/// The local time of day of `time`, i.e. `14:05:09`.
+fn format_time(time: SystemTime) -> String {
+    DateTime::<Local>::from(time).format("%H:%M:%S").to_string()
+}
+
+/// A threshold of the path to the target which has been breached.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Breach {
+    /// The loss of the target exceeds the `threshold`, both as percentages.
+    Loss { loss_pct: f64, threshold: f64 },
+    /// The average RTT of the target exceeds the `threshold`.
+    Latency { avg: Duration, threshold: Duration },
+}
+
+impl Display for Breach {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Loss {
+                loss_pct,
+                threshold,
+            } => write!(f, "loss {loss_pct:.1}% exceeds {threshold:.1}%"),
+            Self::Latency { avg, threshold } => write!(
+                f,
+                "latency {:.1}ms exceeds {:.1}ms",
+                avg.as_secs_f64() * 1000_f64,
+                threshold.as_secs_f64() * 1000_f64
+            ),
+        }
+    }
+}
+
+/// Detects changes in the reachability of the target and in the path to it from the path of each round.
+///
+/// Changes are subject to hysteresis of `rounds` rounds such that a lossy path does not raise alerts every round: the
+/// target is only unreachable once it has not responded for `rounds` consecutive rounds, and a hop has only changed
+/// if it responds from an address from which it has not responded in any of the previous `rounds` rounds.  Hops which
+/// do not respond in a round are ignored, and so are any hops in the first `rounds` rounds.
+#[derive(Debug)]
+pub struct PathMonitor {
+    target: IpAddr,
+    rounds: usize,
+    /// Has the target responded, `None` until it first does.
+    reachable: Option<bool>,
+    /// The number of consecutive rounds in which the target has not responded.
+    missed: usize,
+    /// The paths of up to the previous `rounds` rounds, oldest first.
+    recent: VecDeque<Vec<Option<IpAddr>>>,
+}
+
+impl PathMonitor {
+    pub fn new(target: IpAddr, rounds: usize) -> Self {
+        let rounds = rounds.max(1);
+        Self {
+            target,
+            rounds,
+            reachable: None,
+            missed: 0,
+            recent: VecDeque::with_capacity(rounds),
+        }
+    }
+
+    /// Record the `path` of a round, the host which responded at each ttl from 1, and return any alerts it raises.
+    pub fn update(&mut self, path: &[Option<IpAddr>]) -> Vec<Alert> {
+        let mut alerts = self.update_reachable(path);
+        if self.recent.len() == self.rounds {
+            alerts.extend(self.changed_hops(path));
+            self.recent.pop_front();
+        }
+        self.recent.push_back(path.to_vec());
+        alerts
+    }
+
+    fn update_reachable(&mut self, path: &[Option<IpAddr>]) -> Vec<Alert> {
+        let target = self.target;
+        if path.contains(&Some(target)) {
+            self.missed = 0;
+            let recovered = self.reachable == Some(false);
+            self.reachable = Some(true);
+            recovered
+                .then_some(Alert::Reachable { target })
+                .into_iter()
+                .collect()
+        } else {
+            self.missed += 1;
+            if self.reachable == Some(true) && self.missed >= self.rounds {
+                self.reachable = Some(false);
+                vec![Alert::Unreachable {
+                    target,
+                    rounds: self.missed,
+                }]
+            } else {
+                vec![]
+            }
+        }
+    }
+
+    fn changed_hops<'a>(&'a self, path: &'a [Option<IpAddr>]) -> impl Iterator<Item = Alert> + 'a {
+        path.iter()
+            .enumerate()
+            .filter_map(|(index, addr)| addr.map(|addr| (index, addr)))
+            .filter_map(move |(index, addr)| {
+                let previous: Vec<_> = self
+                    .recent
+                    .iter()
+                    .filter_map(|path| path.get(index).copied().flatten())
+                    .unique()
+                    .sorted()
+                    .collect();
+                (!previous.is_empty() && !previous.contains(&addr)).then(|| Alert::HopChanged {
+                    target: self.target,
+                    ttl: u8::try_from(index + 1).unwrap_or(u8::MAX),
+                    addr,
+                    previous,
+                })
+            })
+    }
+}
+
+/// The loss and latency of the target beyond which the path to it is degraded.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AlertThresholds {
+    /// The loss of the target, as a percentage, above which the path is degraded, if any.
+    pub loss_pct: Option<f64>,
+    /// The average RTT of the target above which the path is degraded, if any.
+    pub latency: Option<Duration>,
+}
+
+impl AlertThresholds {
+    pub fn new(loss_pct: Option<f64>, latency: Option<Duration>) -> Self {
+        Self { loss_pct, latency }
+    }
+
+    /// Are there any thresholds to monitor?
+    pub fn is_set(&self) -> bool {
+        self.loss_pct.is_some() || self.latency.is_some()
+    }
+}
+
+/// Detects the path to the target becoming degraded, or recovering, from the response of the target in each round.
+///
+/// The loss and average RTT of the target are evaluated over the most recent `window` rounds.  Changes are subject to
+/// hysteresis of `rounds` rounds such that a path on the edge of a threshold does not flap: the path is only degraded
+/// once a threshold has been breached for `rounds` consecutive rounds, and only recovers once none has been for as
+/// many rounds.
+#[derive(Debug)]
+pub struct ThresholdMonitor {
+    target: IpAddr,
+    thresholds: AlertThresholds,
+    window: usize,
+    rounds: usize,
+    /// The RTT of the target in up to the previous `window` rounds, oldest first, `None` if it did not respond.
+    recent: VecDeque<Option<Duration>>,
+    degraded: bool,
+    /// The number of consecutive rounds which disagree with `degraded`, and the time of the first of them.
+    pending: Option<(usize, SystemTime)>,
+}
+
+impl ThresholdMonitor {
+    pub fn new(target: IpAddr, thresholds: AlertThresholds, window: usize, rounds: usize) -> Self {
+        let window = window.max(1);
+        Self {
+            target,
+            thresholds,
+            window,
+            rounds: rounds.max(1),
+            recent: VecDeque::with_capacity(window),
+            degraded: false,
+            pending: None,
+        }
+    }
+
+    /// Record the `rtt` of the target in a round at `time`, `None` if it did not respond, and return the alert it
+    /// raises, if any.
+    pub fn update(&mut self, rtt: Option<Duration>, time: SystemTime) -> Option<Alert> {
+        if self.recent.len() == self.window {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(rtt);
+        let breach = self.breach();
+        if breach.is_some() == self.degraded {
+            self.pending = None;
+            return None;
+        }
+        let (count, since) = self
+            .pending
+            .map_or((1, time), |(count, since)| (count + 1, since));
+        if count < self.rounds {
+            self.pending = Some((count, since));
+            return None;
+        }
+        self.pending = None;
+        self.degraded = !self.degraded;
+        let target = self.target;
+        Some(match breach {
+            Some(breach) => Alert::Degraded {
+                target,
+                breach,
+                since,
+            },
+            None => Alert::Recovered { target, since },
+        })
+    }
+
+    /// The threshold breached over the recent rounds, if any, loss being checked before latency.
+    fn breach(&self) -> Option<Breach> {
+        let responses: Vec<_> = self.recent.iter().flatten().collect();
+        let loss_pct =
+            (self.recent.len() - responses.len()) as f64 / self.recent.len() as f64 * 100_f64;
+        let loss = self
+            .thresholds
+            .loss_pct
+            .filter(|threshold| loss_pct > *threshold)
+            .map(|threshold| Breach::Loss {
+                loss_pct,
+                threshold,
+            });
+        let latency = || {
+            let avg = responses
+                .iter()
+                .copied()
+                .sum::<Duration>()
+                .checked_div(u32::try_from(responses.len()).ok()?)?;
+            self.thresholds
+                .latency
+                .filter(|threshold| avg > *threshold)
+                .map(|threshold| Breach::Latency { avg, threshold })
+        };
+        loss.or_else(latency)
+    }
+}
+
+/// The transitions of the path to a target between degraded and recovered, shared between the backend, which records
+/// them, and the frontends.
+pub type SharedHealth = Arc<RwLock<Health>>;
+
+/// The transitions of the path to a target between degraded and recovered, oldest first.
+#[derive(Debug, Default)]
+pub struct Health {
+    transitions: Vec<Alert>,
+}
+
+impl Health {
+    pub fn shared() -> SharedHealth {
+        Arc::new(RwLock::new(Self::default()))
+    }
+
+    /// Record an `Alert::Degraded` or `Alert::Recovered` transition.
+    pub fn push(&mut self, transition: Alert) {
+        self.transitions.push(transition);
+    }
+
+    /// The breach by which the path is degraded, and since when, if it is.
+    pub fn degraded(&self) -> Option<(Breach, SystemTime)> {
+        match self.transitions.last() {
+            Some(Alert::Degraded { breach, since, .. }) => Some((*breach, *since)),
+            _ => None,
+        }
+    }
+
+    /// The transitions recorded after the first `seen`, such that a reader can find those it has not yet seen.
+    pub fn transitions_since(&self, seen: usize) -> &[Alert] {
+        self.transitions.get(seen..).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    const TARGET: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 99));
+
+    fn addr(last: u8) -> IpAddr {
+        IpAddr::V4(Ipv4Addr::new(10, 0, 0, last))
+    }
+
+    fn update_all(monitor: &mut PathMonitor, paths: &[&[Option<IpAddr>]]) -> Vec<Alert> {
+        paths.iter().flat_map(|path| monitor.update(path)).collect()
+    }
+
+    #[test]
+    fn test_unreachable_after_rounds() {
+        let mut monitor = PathMonitor::new(TARGET, 3);
+        let reached: &[Option<IpAddr>] = &[Some(addr(1)), Some(TARGET)];
+        let lost: &[Option<IpAddr>] = &[Some(addr(1)), None];
+        assert!(update_all(&mut monitor, &[reached, lost, lost]).is_empty());
+        assert_eq!(
+            vec![Alert::Unreachable {
+                target: TARGET,
+                rounds: 3
+            }],
+            monitor.update(lost)
+        );
+        assert!(monitor.update(lost).is_empty());
+        assert_eq!(
+            vec![Alert::Reachable { target: TARGET }],
+            monitor.update(reached)
+        );
+    }
+
+    #[test]
+    fn test_not_unreachable_if_never_reached() {
+        let mut monitor = PathMonitor::new(TARGET, 2);
+        let lost: &[Option<IpAddr>] = &[Some(addr(1)), None];
+        assert!(update_all(&mut monitor, &[lost, lost, lost]).is_empty());
+    }
+
+    #[test]
+    fn test_lossy_target_is_not_unreachable() {
+        let mut monitor = PathMonitor::new(TARGET, 2);
+        let reached: &[Option<IpAddr>] = &[Some(TARGET)];
+        let lost: &[Option<IpAddr>] = &[None];
+        assert!(update_all(&mut monitor, &[reached, lost, reached, lost, reached]).is_empty());
+    }
+
+    #[test]
+    fn test_hop_changed() {
+        let mut monitor = PathMonitor::new(TARGET, 2);
+        let alerts = update_all(
+            &mut monitor,
+            &[
+                &[Some(addr(1)), Some(addr(2))],
+                &[Some(addr(1)), None],
+                &[Some(addr(1)), Some(addr(3))],
+                &[Some(addr(1)), Some(addr(3))],
+            ],
+        );
+        assert_eq!(
+            vec![Alert::HopChanged {
+                target: TARGET,
+                ttl: 2,
+                addr: addr(3),
+                previous: vec![addr(2)],
+            }],
+            alerts
+        );
+        assert_eq!(
+            "hop 2 towards 10.0.0.99 changed to 10.0.0.3 from 10.0.0.2",
+            alerts[0].to_string()
+        );
+    }
+
+    #[test]
+    fn test_alternating_hop_is_not_changed() {
+        let mut monitor = PathMonitor::new(TARGET, 2);
+        let alerts = update_all(
+            &mut monitor,
+            &[
+                &[Some(addr(1))],
+                &[Some(addr(2))],
+                &[Some(addr(1))],
+                &[Some(addr(2))],
+                &[Some(addr(1))],
+            ],
+        );
+        assert!(alerts.is_empty());
+    }
+
+    fn time(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn update_thresholds(monitor: &mut ThresholdMonitor, rtts: &[Option<u64>]) -> Vec<Alert> {
+        rtts.iter()
+            .enumerate()
+            .filter_map(|(round, rtt)| {
+                monitor.update(rtt.map(Duration::from_millis), time(round as u64))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_loss_degraded_and_recovered() {
+        let thresholds = AlertThresholds::new(Some(25_f64), None);
+        let mut monitor = ThresholdMonitor::new(TARGET, thresholds, 4, 2);
+        let rtts = [
+            Some(10),
+            Some(10),
+            Some(10),
+            Some(10),
+            None,
+            None,
+            None,
+            Some(10),
+            Some(10),
+            Some(10),
+            Some(10),
+        ];
+        assert_eq!(
+            vec![
+                Alert::Degraded {
+                    target: TARGET,
+                    breach: Breach::Loss {
+                        loss_pct: 75_f64,
+                        threshold: 25_f64
+                    },
+                    since: time(5),
+                },
+                Alert::Recovered {
+                    target: TARGET,
+                    since: time(9),
+                }
+            ],
+            update_thresholds(&mut monitor, &rtts)
+        );
+    }
+
+    #[test]
+    fn test_latency_degraded_and_recovered() {
+        let thresholds = AlertThresholds::new(None, Some(Duration::from_millis(100)));
+        let mut monitor = ThresholdMonitor::new(TARGET, thresholds, 2, 1);
+        let rtts = [Some(50), Some(50), Some(200), Some(50), None, Some(50)];
+        assert_eq!(
+            vec![
+                Alert::Degraded {
+                    target: TARGET,
+                    breach: Breach::Latency {
+                        avg: Duration::from_millis(125),
+                        threshold: Duration::from_millis(100)
+                    },
+                    since: time(2),
+                },
+                Alert::Recovered {
+                    target: TARGET,
+                    since: time(4),
+                }
+            ],
+            update_thresholds(&mut monitor, &rtts)
+        );
+    }
+
+    #[test]
+    fn test_thresholds_do_not_flap() {
+        let thresholds = AlertThresholds::new(Some(0_f64), None);
+        let mut monitor = ThresholdMonitor::new(TARGET, thresholds, 1, 2);
+        let rtts = [Some(10), None, Some(10), None, Some(10), None];
+        assert!(update_thresholds(&mut monitor, &rtts).is_empty());
+    }
+
+    #[test]
+    fn test_breach_display() {
+        let loss = Breach::Loss {
+            loss_pct: 12.5,
+            threshold: 10_f64,
+        };
+        assert_eq!("loss 12.5% exceeds 10.0%", loss.to_string());
+        let latency = Breach::Latency {
+            avg: Duration::from_micros(120_250),
+            threshold: Duration::from_millis(100),
+        };
+        assert_eq!("latency 120.2ms exceeds 100.0ms", latency.to_string());
+    }
+
+    #[test]
+    fn test_health() {
+        let mut health = Health::default();
+        assert_eq!(None, health.degraded());
+        let breach = Breach::Loss {
+            loss_pct: 50_f64,
+            threshold: 10_f64,
+        };
+        health.push(Alert::Degraded {
+            target: TARGET,
+            breach,
+            since: time(1),
+        });
+        assert_eq!(Some((breach, time(1))), health.degraded());
+        health.push(Alert::Recovered {
+            target: TARGET,
+            since: time(2),
+        });
+        assert_eq!(None, health.degraded());
+        assert_eq!(1, health.transitions_since(1).len());
+        assert!(health.transitions_since(3).is_empty());
+    }
+}
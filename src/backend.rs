@@ -1,287 +1,256 @@
-use crate::caps::drop_caps;
-use crate::config::MAX_HOPS;
-use parking_lot::RwLock;
-use std::collections::HashMap;
-use std::net::{IpAddr, Ipv4Addr};
-use std::sync::Arc;
-use std::time::Duration;
-use trippy::tracing::{
-    Probe, ProbeStatus, Tracer, TracerChannel, TracerChannelConfig, TracerConfig, TracerRound,
+use crate::alert::{PathMonitor, SharedHealth, ThresholdMonitor};
+use crate::event::{EventLevel, SharedEventLog};
+use crate::state::StateFile;
+use std::cell::{Cell, RefCell};
+use std::net::IpAddr;
+use std::time::{Duration, SystemTime};
+use tracing::debug_span;
+use trippy_core::{
+    CancellationToken, Convergence, Probe, ProbeStatus, RateLimitHeuristic, RoundParameters,
+    TraceResult, Tracer, TracerChannel, TracerConfig, TracerRound,
 };
 
-/// The state of all hops in a trace.
-#[derive(Debug, Clone)]
-pub struct Trace {
-    max_samples: usize,
-    lowest_ttl: u8,
-    highest_ttl: u8,
-    highest_ttl_for_round: u8,
-    round: Option<usize>,
-    hops: Vec<Hop>,
-    error: Option<String>,
-}
-
-impl Trace {
-    pub fn new(max_samples: usize) -> Self {
-        Self {
-            max_samples,
-            lowest_ttl: 0,
-            highest_ttl: 0,
-            highest_ttl_for_round: 0,
-            round: None,
-            hops: (0..MAX_HOPS).map(|_| Hop::default()).collect(),
-            error: None,
-        }
-    }
-
-    /// The current round of tracing.
-    pub fn round(&self) -> Option<usize> {
-        self.round
-    }
-
-    /// Information about each hop in the trace.
-    pub fn hops(&self) -> &[Hop] {
-        if self.lowest_ttl == 0 || self.highest_ttl == 0 {
-            &[]
-        } else {
-            let start = (self.lowest_ttl as usize) - 1;
-            let end = self.highest_ttl as usize;
-            &self.hops[start..end]
-        }
-    }
-
-    /// Is a given `Hop` the target hop?
-    ///
-    /// A `Hop` is considered to be the target if it has the highest `ttl` value observed.
-    ///
-    /// Note that if the target host does not respond to probes then the the highest `ttl` observed will be one greater
-    /// than the `ttl` of the last host which did respond.
-    pub fn is_target(&self, hop: &Hop) -> bool {
-        self.highest_ttl == hop.ttl
-    }
-
-    /// Is a given `Hop` in the current round?
-    pub fn is_in_round(&self, hop: &Hop) -> bool {
-        hop.ttl <= self.highest_ttl_for_round
-    }
-
-    /// Return the target `Hop`.
-    ///
-    /// TODO Do we guarantee there is always a target hop?
-    pub fn target_hop(&self) -> &Hop {
-        if self.highest_ttl > 0 {
-            &self.hops[usize::from(self.highest_ttl) - 1]
-        } else {
-            &self.hops[0]
-        }
-    }
+pub use trippy_core::{Endpoint, Flow, Hop, HopLink, SharedTrace, Trace};
 
-    pub fn error(&self) -> Option<&str> {
-        self.error.as_deref()
-    }
-
-    /// Update the tracing state from a `TracerRound`.
-    pub fn update_from_round(&mut self, round: &TracerRound<'_>) {
-        self.highest_ttl = std::cmp::max(self.highest_ttl, round.largest_ttl.0);
-        self.highest_ttl_for_round = round.largest_ttl.0;
-        for probe in round.probes {
-            self.update_from_probe(probe);
-        }
+/// Run the tracing backend.
+///
+/// The `trace_data` is updated after every round, whilst no reader is blocked for longer than it takes to apply the
+/// round to it.
+///
+/// The alerts raised by the `monitors` are recorded in the `events`, and the transitions of the path between degraded
+/// and recovered also in the `health` of the monitors.
+///
+/// If `state_file` is set then the trace is saved to it every so many rounds, a failure to do so being recorded in the
+/// `events` as a warning.
+///
+/// Tracing stops once `cancellation` is cancelled.  The range of ttl probed is read from the `parameters` at the start
+/// of each round.
+#[allow(clippy::too_many_arguments)]
+pub fn run_backend(
+    tracer_config: &TracerConfig,
+    channel: TracerChannel,
+    trace_data: SharedTrace,
+    events: &SharedEventLog,
+    monitors: Monitors,
+    state_file: Option<StateFile>,
+    cancellation: CancellationToken,
+    parameters: RoundParameters,
+) {
+    let td = trace_data.clone();
+    if let Err(err) = trace(
+        tracer_config,
+        channel,
+        trace_data,
+        events,
+        monitors,
+        state_file,
+        cancellation,
+        parameters,
+    ) {
+        let err = err.render();
+        events
+            .write()
+            .push(EventLevel::Error, format!("tracing stopped: {err}"));
+        td.update(|trace| trace.set_error(err));
     }
+}
 
-    fn update_from_probe(&mut self, probe: &Probe) {
-        self.update_lowest_ttl(probe);
-        self.update_round(probe);
-        match probe.status {
-            ProbeStatus::Complete => {
-                let index = usize::from(probe.ttl.0) - 1;
-                let hop = &mut self.hops[index];
-                hop.ttl = probe.ttl.0;
-                hop.total_sent += 1;
-                hop.total_recv += 1;
-                let dur = probe.duration();
-                let dur_ms = dur.as_secs_f64() * 1000_f64;
-                hop.total_time += dur;
-                hop.last = Some(dur);
-                hop.samples.insert(0, dur);
-                hop.best = hop.best.map_or(Some(dur), |d| Some(d.min(dur)));
-                hop.worst = hop.worst.map_or(Some(dur), |d| Some(d.max(dur)));
-                hop.mean += (dur_ms - hop.mean) / hop.total_recv as f64;
-                hop.m2 += (dur_ms - hop.mean) * (dur_ms - hop.mean);
-                if hop.samples.len() > self.max_samples {
-                    hop.samples.pop();
-                }
-                let host = probe.host.unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
-                *hop.addrs.entry(host).or_default() += 1;
-            }
-            ProbeStatus::Awaited => {
-                let index = usize::from(probe.ttl.0) - 1;
-                self.hops[index].total_sent += 1;
-                self.hops[index].ttl = probe.ttl.0;
-                self.hops[index].samples.insert(0, Duration::default());
-                if self.hops[index].samples.len() > self.max_samples {
-                    self.hops[index].samples.pop();
-                }
+/// Trace until cancelled or an error occurs.
+#[allow(clippy::too_many_arguments)]
+fn trace(
+    tracer_config: &TracerConfig,
+    channel: TracerChannel,
+    trace_data: SharedTrace,
+    events: &SharedEventLog,
+    monitors: Monitors,
+    state_file: Option<StateFile>,
+    cancellation: CancellationToken,
+    parameters: RoundParameters,
+) -> TraceResult<()> {
+    let target = tracer_config.target_addr;
+    let monitor = monitors.path.map(RefCell::new);
+    let thresholds = monitors.thresholds.map(RefCell::new);
+    let health = monitors.health;
+    let rate_limit = monitors.rate_limit;
+    let convergence = monitors.convergence;
+    let unsaved = Cell::new(0_usize);
+    let tracer = Tracer::new(tracer_config, move |round| {
+        debug_span!("backend.update_trace_data").in_scope(|| {
+            trace_data.update(|trace| {
+                trace.update_from_round(round);
+                trace.update_rate_limited(&rate_limit);
+                trace.update_converged(&convergence);
+            });
+        });
+        if let Some(monitor) = &monitor {
+            for alert in monitor.borrow_mut().update(&round.path()) {
+                events.write().push(EventLevel::Alert, alert.to_string());
             }
-            ProbeStatus::NotSent => {}
         }
-    }
-
-    /// Update `lowest_ttl` for valid probes.
-    fn update_lowest_ttl(&mut self, probe: &Probe) {
-        if matches!(probe.status, ProbeStatus::Awaited | ProbeStatus::Complete) {
-            if self.lowest_ttl == 0 {
-                self.lowest_ttl = probe.ttl.0;
-            } else {
-                self.lowest_ttl = self.lowest_ttl.min(probe.ttl.0);
+        if let Some(thresholds) = &thresholds {
+            let rtt = target_rtt(round, round.target().unwrap_or(target));
+            if let Some(alert) = thresholds.borrow_mut().update(rtt, SystemTime::now()) {
+                events.write().push(EventLevel::Alert, alert.to_string());
+                health.write().push(alert);
             }
         }
-    }
-
-    /// Update `round` for valid probes.
-    fn update_round(&mut self, probe: &Probe) {
-        if matches!(probe.status, ProbeStatus::Awaited | ProbeStatus::Complete) {
-            self.round = match self.round {
-                None => Some(probe.round.0),
-                Some(r) => Some(r.max(probe.round.0)),
+        unsaved.set(unsaved.get() + 1);
+        if let Some(state_file) = state_file
+            .as_ref()
+            .filter(|state_file| unsaved.get() >= state_file.save_rounds())
+        {
+            unsaved.set(0);
+            let saved = debug_span!("backend.save_state")
+                .in_scope(|| state_file.save(&trace_data.snapshot()));
+            if let Err(err) = saved {
+                events.write().push(EventLevel::Warn, err.to_string());
             }
         }
-    }
+    })
+    .with_cancellation(cancellation)
+    .with_parameters(parameters);
+    tracer.trace(channel)
 }
 
-/// Information about a single `Hop` within a `Trace`.
-#[derive(Debug, Clone)]
-pub struct Hop {
-    ttl: u8,
-    addrs: HashMap<IpAddr, usize>,
-    total_sent: usize,
-    total_recv: usize,
-    total_time: Duration,
-    last: Option<Duration>,
-    best: Option<Duration>,
-    worst: Option<Duration>,
-    mean: f64,
-    m2: f64,
-    samples: Vec<Duration>,
+/// The RTT of the target in a round, if it responded.
+fn target_rtt(round: &TracerRound<'_>, target: IpAddr) -> Option<Duration> {
+    round
+        .probes
+        .iter()
+        .find(|probe| probe.status == ProbeStatus::Complete && probe.host == Some(target))
+        .map(Probe::duration)
 }
 
-impl Hop {
-    /// The time-to-live of this hop.
-    pub fn ttl(&self) -> u8 {
-        self.ttl
-    }
-
-    /// The set of addresses that have responded for this time-to-live.
-    pub fn addrs(&self) -> impl Iterator<Item = &IpAddr> {
-        self.addrs.keys()
-    }
-
-    pub fn addrs_with_counts(&self) -> impl Iterator<Item = (&IpAddr, &usize)> {
-        self.addrs.iter()
-    }
-
-    /// The number of unique address observed for this time-to-live.
-    pub fn addr_count(&self) -> usize {
-        self.addrs.len()
-    }
-
-    /// The total number of probes sent.
-    pub fn total_sent(&self) -> usize {
-        self.total_sent
-    }
+/// The monitors which raise alerts from each round of a trace.
+#[derive(Debug)]
+pub struct Monitors {
+    /// Detects changes in the reachability of the target and in the path to it, if they are to be alerted.
+    pub path: Option<PathMonitor>,
+    /// Detects the path to the target becoming degraded and recovering, if any thresholds are set.
+    pub thresholds: Option<ThresholdMonitor>,
+    /// The transitions detected by the `thresholds`, shared with the frontends.
+    pub health: SharedHealth,
+    /// The heuristic by which the hops which are likely rate limiting ICMP are flagged after every round.
+    pub rate_limit: RateLimitHeuristic,
+    /// The thresholds by which the path is judged to have converged after every round.
+    pub convergence: Convergence,
+}
 
-    /// The total number of probes responses received.
-    pub fn total_recv(&self) -> usize {
-        self.total_recv
-    }
+/// Helpers for building synthetic traces in tests.
+#[cfg(test)]
+pub mod testing {
+    use super::*;
+    use crate::TraceInfo;
+    use std::net::IpAddr;
+    use std::time::{Duration, SystemTime};
+    use trippy_core::{
+        CompletionReason, IcmpPacketType, MultipathStrategy, PortDirection, Probe, ProbeStatus,
+        Round, Sequence, TimeToLive, TracerAddrFamily, TracerProtocol, TracerRound,
+    };
 
-    /// The % of packets that are lost.
-    pub fn loss_pct(&self) -> f64 {
-        if self.total_sent > 0 {
-            let lost = self.total_sent - self.total_recv;
-            lost as f64 / self.total_sent as f64 * 100f64
-        } else {
-            0_f64
+    /// Build a `Trace` from a sequence of rounds.
+    ///
+    /// Each round is a list of `(host, rtt_ms)` for consecutive `ttl` starting from 1, where a `host` of `None`
+    /// indicates that no response was received.
+    pub fn make_trace(rounds: &[&[(Option<IpAddr>, u64)]]) -> Trace {
+        let mut trace = Trace::new(256);
+        for (round, path) in rounds.iter().enumerate() {
+            let path: Vec<_> = path.iter().map(|(host, rtt)| (*host, *rtt, None)).collect();
+            add_round(&mut trace, round, &path);
         }
+        trace
     }
 
-    /// The duration of the last probe.
-    pub fn last_ms(&self) -> Option<f64> {
-        self.last.map(|last| last.as_secs_f64() * 1000_f64)
-    }
-
-    /// The duration of the best probe observed.
-    pub fn best_ms(&self) -> Option<f64> {
-        self.best.map(|last| last.as_secs_f64() * 1000_f64)
-    }
-
-    /// The duration of the worst probe observed.
-    pub fn worst_ms(&self) -> Option<f64> {
-        self.worst.map(|last| last.as_secs_f64() * 1000_f64)
-    }
-
-    /// The average duration of all probes.
-    pub fn avg_ms(&self) -> f64 {
-        if self.total_recv() > 0 {
-            (self.total_time.as_secs_f64() * 1000_f64) / self.total_recv as f64
-        } else {
-            0_f64
+    /// Build a `Trace` from a sequence of rounds which each probed the host of their last hop, as if the addresses of
+    /// the target were probed round robin.
+    pub fn make_round_robin_trace(rounds: &[&[(Option<IpAddr>, u64)]]) -> Trace {
+        let mut trace = Trace::new(256);
+        for (round, path) in rounds.iter().enumerate() {
+            let target = path.last().and_then(|(host, _)| *host);
+            let path: Vec<_> = path.iter().map(|(host, rtt)| (*host, *rtt, None)).collect();
+            add_round_to(&mut trace, round, target, &path);
         }
+        trace
     }
 
-    /// The standard deviation of all probes.
-    pub fn stddev_ms(&self) -> f64 {
-        if self.total_recv > 1 {
-            (self.m2 / (self.total_recv - 1) as f64).sqrt()
-        } else {
-            0_f64
-        }
+    /// Build the `TraceInfo` of an ICMP trace of `trace` from `10.0.0.100` to `example.com` (`10.0.0.3`).
+    pub fn make_trace_info(trace: Trace) -> TraceInfo {
+        TraceInfo::new(
+            SharedTrace::from(trace),
+            IpAddr::from([10, 0, 0, 100]),
+            String::from("example.com"),
+            IpAddr::from([10, 0, 0, 3]),
+            MultipathStrategy::Classic,
+            PortDirection::None,
+            TracerProtocol::Icmp,
+            TracerAddrFamily::Ipv4,
+            1,
+            64,
+            Duration::from_millis(100),
+            Duration::from_secs(1),
+            84,
+            None,
+            None,
+            0,
+        )
     }
 
-    /// The last N samples.
-    pub fn samples(&self) -> &[Duration] {
-        &self.samples
+    /// Add a round to a `Trace`.
+    ///
+    /// The round is a list of `(host, rtt_ms, icmp_packet_type)` for consecutive `ttl` starting from 1, where a `host`
+    /// of `None` indicates that no response was received.
+    pub fn add_round(
+        trace: &mut Trace,
+        round: usize,
+        path: &[(Option<IpAddr>, u64, Option<IcmpPacketType>)],
+    ) {
+        add_round_to(trace, round, None, path);
     }
-}
 
-impl Default for Hop {
-    fn default() -> Self {
-        Self {
-            ttl: 0,
-            addrs: HashMap::default(),
-            total_sent: 0,
-            total_recv: 0,
-            total_time: Duration::default(),
-            last: None,
-            best: None,
-            worst: None,
-            mean: 0f64,
-            m2: 0f64,
-            samples: Vec::default(),
-        }
+    /// Add a round to a `Trace` whose probes were sent to `target`, if given.
+    fn add_round_to(
+        trace: &mut Trace,
+        round: usize,
+        target: Option<IpAddr>,
+        path: &[(Option<IpAddr>, u64, Option<IcmpPacketType>)],
+    ) {
+        let sent = SystemTime::UNIX_EPOCH;
+        let probes: Vec<_> = path
+            .iter()
+            .enumerate()
+            .map(|(i, (host, rtt, icmp_packet_type))| {
+                let probe = Probe::new(
+                    Sequence(33000 + i as u16),
+                    TimeToLive(i as u8 + 1),
+                    Round(round),
+                    sent,
+                );
+                let probe = match target {
+                    Some(target) => probe.with_target(target),
+                    None => probe,
+                };
+                match host {
+                    Some(host) => {
+                        let probe = probe
+                            .with_status(ProbeStatus::Complete)
+                            .with_host(*host)
+                            .with_received(sent + Duration::from_millis(*rtt));
+                        match icmp_packet_type {
+                            Some(icmp_packet_type) => {
+                                probe.with_icmp_packet_type(*icmp_packet_type)
+                            }
+                            None => probe,
+                        }
+                    }
+                    None => probe,
+                }
+            })
+            .collect();
+        let largest_ttl = TimeToLive(path.len() as u8);
+        trace.update_from_round(&TracerRound::new(
+            &probes,
+            largest_ttl,
+            CompletionReason::TargetFound,
+        ));
     }
 }
-
-/// Run the tracing backend.
-///
-/// Note that this implementation blocks the tracer on the `RwLock` and so any delays in the the TUI will delay the
-/// next round of the started.
-pub fn run_backend(
-    tracer_config: &TracerConfig,
-    channel_config: &TracerChannelConfig,
-    trace_data: Arc<RwLock<Trace>>,
-) -> anyhow::Result<()> {
-    let td = trace_data.clone();
-    let channel = TracerChannel::connect(channel_config)?;
-    drop_caps()?;
-    let tracer = Tracer::new(tracer_config, move |round| {
-        trace_data.write().update_from_round(round);
-    });
-    match tracer.trace(channel) {
-        Ok(_) => {}
-        Err(err) => {
-            td.write().error = Some(err.to_string());
-        }
-    };
-    Ok(())
-}
@@ -0,0 +1,360 @@
+use anyhow::anyhow;
+use pcap_file::pcap::{PcapHeader, PcapPacket, PcapWriter};
+use pcap_file::{DataLink, TsResolution};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, UNIX_EPOCH};
+use trippy_core::{CapturedPacket, PacketCapture};
+
+/// The maximum number of captured packets which may be queued for writing.
+const CAPTURE_CHANNEL_CAPACITY: usize = 4096;
+
+/// The interval at which the capture thread checks for shutdown when no packets are being captured.
+const CAPTURE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The maximum number of bytes of each packet to capture.
+const CAPTURE_SNAPLEN: u32 = 65535;
+
+/// The size of the pcap file header.
+const PCAP_HEADER_SIZE: u64 = 24;
+
+/// The size of the pcap per-packet record header.
+const PCAP_RECORD_HEADER_SIZE: u64 = 16;
+
+/// Captures the packets sent and received by the tracers to a pcap file.
+///
+/// Packets are written, unbuffered, by a dedicated thread such that file I/O never delays tracing.  The packets are
+/// raw IPv4 or IPv6 packets and so the file uses the raw IP (`LINKTYPE_RAW`) link-layer type.
+///
+/// Note that `TCP` probes are sent by the operating system when connecting and so only the ICMP responses to them are
+/// captured.
+///
+/// If a maximum size is given then a new file is started whenever writing a packet would exceed it, `trace.pcap` is
+/// followed by `trace.1.pcap`, `trace.2.pcap` and so on.
+pub struct Capture {
+    sink: PacketCapture,
+    shutdown: Arc<AtomicBool>,
+    handle: JoinHandle<anyhow::Result<()>>,
+}
+
+impl Capture {
+    /// Start capturing to `path`.
+    ///
+    /// The first file is created here, before tracing begins, so that permission errors are reported immediately.
+    pub fn start(path: &Path, max_size: Option<u64>) -> anyhow::Result<Self> {
+        let mut writer = CaptureWriter::create(path, max_size)?;
+        let (sender, receiver) = sync_channel(CAPTURE_CHANNEL_CAPACITY);
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let shutdown = shutdown.clone();
+            std::thread::Builder::new()
+                .name(String::from("capture"))
+                .spawn(move || writer.run(&receiver, &shutdown))?
+        };
+        Ok(Self {
+            sink: PacketCapture::new(sender),
+            shutdown,
+            handle,
+        })
+    }
+
+    /// Start capturing to `path` once signalled by `open`, queueing any packets captured before then.
+    ///
+    /// The first file is created only once signalled, such that it is created with the privileges of the process at
+    /// that time, and so any error creating it is reported once the capture is finished.  Nothing is written if `open`
+    /// is dropped without being signalled.
+    pub fn start_after(
+        path: &Path,
+        max_size: Option<u64>,
+        open: Receiver<()>,
+    ) -> anyhow::Result<Self> {
+        let path = path.to_path_buf();
+        let (sender, receiver) = sync_channel(CAPTURE_CHANNEL_CAPACITY);
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let shutdown = shutdown.clone();
+            std::thread::Builder::new()
+                .name(String::from("capture"))
+                .spawn(move || {
+                    if open.recv().is_err() {
+                        return Ok(());
+                    }
+                    CaptureWriter::create(&path, max_size)?.run(&receiver, &shutdown)
+                })?
+        };
+        Ok(Self {
+            sink: PacketCapture::new(sender),
+            shutdown,
+            handle,
+        })
+    }
+
+    /// The sink to which the tracers send captured packets.
+    pub fn packet_capture(&self) -> PacketCapture {
+        self.sink.clone()
+    }
+
+    /// Write any queued packets and close the capture file.
+    pub fn finish(self) -> anyhow::Result<()> {
+        self.shutdown.store(true, Ordering::SeqCst);
+        self.handle
+            .join()
+            .map_err(|_| anyhow!("capture thread panicked"))?
+    }
+}
+
+/// Writes captured packets to a sequence of pcap files.
+struct CaptureWriter {
+    path: PathBuf,
+    max_size: Option<u64>,
+    index: usize,
+    size: u64,
+    writer: PcapWriter<File>,
+}
+
+impl CaptureWriter {
+    fn create(path: &Path, max_size: Option<u64>) -> anyhow::Result<Self> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_size,
+            index: 0,
+            size: PCAP_HEADER_SIZE,
+            writer: open_pcap(path)?,
+        })
+    }
+
+    /// Write packets until shutdown is requested, or all senders have gone, and then write any which remain.
+    fn run(
+        &mut self,
+        receiver: &Receiver<CapturedPacket>,
+        shutdown: &AtomicBool,
+    ) -> anyhow::Result<()> {
+        while !shutdown.load(Ordering::SeqCst) {
+            match receiver.recv_timeout(CAPTURE_POLL_INTERVAL) {
+                Ok(packet) => self.write(&packet)?,
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        for packet in receiver.try_iter() {
+            self.write(&packet)?;
+        }
+        Ok(())
+    }
+
+    /// Write a packet, starting a new file first if it would exceed the maximum size.
+    fn write(&mut self, packet: &CapturedPacket) -> anyhow::Result<()> {
+        let record_size = PCAP_RECORD_HEADER_SIZE + packet.data.len() as u64;
+        if let Some(max_size) = self.max_size {
+            if self.size > PCAP_HEADER_SIZE && self.size + record_size > max_size {
+                self.rotate()?;
+            }
+        }
+        let timestamp = packet
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        self.writer
+            .write_packet(&PcapPacket::new(
+                timestamp,
+                packet.data.len() as u32,
+                &packet.data,
+            ))
+            .map_err(|e| anyhow!("failed to write capture file: {}", e))?;
+        self.size += record_size;
+        Ok(())
+    }
+
+    /// Close the current file and start the next.
+    fn rotate(&mut self) -> anyhow::Result<()> {
+        self.index += 1;
+        self.writer = open_pcap(&rotated_path(&self.path, self.index))?;
+        self.size = PCAP_HEADER_SIZE;
+        Ok(())
+    }
+}
+
+/// Create a pcap file for raw IP packets.
+fn open_pcap(path: &Path) -> anyhow::Result<PcapWriter<File>> {
+    let file = File::create(path)
+        .map_err(|e| anyhow!("failed to create capture file {}: {}", path.display(), e))?;
+    let header = PcapHeader {
+        datalink: DataLink::RAW,
+        snaplen: CAPTURE_SNAPLEN,
+        ts_resolution: TsResolution::NanoSecond,
+        ..PcapHeader::default()
+    };
+    PcapWriter::with_header(file, header)
+        .map_err(|e| anyhow!("failed to create capture file {}: {}", path.display(), e))
+}
+
+/// The path of the capture file with the given `index`, i.e. `trace.pcap` becomes `trace.1.pcap`.
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    if index == 0 {
+        return path.to_path_buf();
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{stem}.{index}.{}", ext.to_string_lossy()),
+        None => format!("{stem}.{index}"),
+    };
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pcap_file::pcap::PcapReader;
+    use std::time::SystemTime;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("trippy-{name}-{}.pcap", std::process::id()))
+    }
+
+    /// Read the link type and packets of a pcap file.
+    fn read_pcap(path: &Path) -> (DataLink, Vec<Vec<u8>>) {
+        let mut reader = PcapReader::new(File::open(path).unwrap()).unwrap();
+        let datalink = reader.header().datalink;
+        let mut packets = vec![];
+        while let Some(packet) = reader.next_packet() {
+            packets.push(packet.unwrap().data.to_vec());
+        }
+        (datalink, packets)
+    }
+
+    #[test]
+    fn test_capture() {
+        let path = temp_path("capture");
+        let capture = Capture::start(&path, None).unwrap();
+        let packet_capture = capture.packet_capture();
+        let packets: Vec<_> = (0..10_u8).map(|i| vec![0x45, i, 0, 20]).collect();
+        for packet in &packets {
+            packet_capture.capture(SystemTime::now(), packet);
+        }
+        capture.finish().unwrap();
+        let (datalink, captured) = read_pcap(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(DataLink::RAW, datalink);
+        assert_eq!(packets, captured);
+    }
+
+    #[test]
+    fn test_capture_rotate() {
+        let path = temp_path("rotate");
+        let max_size = PCAP_HEADER_SIZE + 2 * (PCAP_RECORD_HEADER_SIZE + 100);
+        let capture = Capture::start(&path, Some(max_size)).unwrap();
+        let packet_capture = capture.packet_capture();
+        for _ in 0..5 {
+            packet_capture.capture(SystemTime::now(), &[0_u8; 100]);
+        }
+        capture.finish().unwrap();
+        let counts: Vec<_> = (0..3)
+            .map(|i| {
+                let path = rotated_path(&path, i);
+                let (_, packets) = read_pcap(&path);
+                std::fs::remove_file(&path).unwrap();
+                packets.len()
+            })
+            .collect();
+        assert_eq!(vec![2, 2, 1], counts);
+        assert!(!rotated_path(&path, 3).exists());
+    }
+
+    #[test]
+    fn test_rotated_path() {
+        assert_eq!(
+            PathBuf::from("/tmp/trace.pcap"),
+            rotated_path(Path::new("/tmp/trace.pcap"), 0)
+        );
+        assert_eq!(
+            PathBuf::from("/tmp/trace.2.pcap"),
+            rotated_path(Path::new("/tmp/trace.pcap"), 2)
+        );
+        assert_eq!(
+            PathBuf::from("trace.1"),
+            rotated_path(Path::new("trace"), 1)
+        );
+    }
+
+    /// Trace to the loopback address and validate that every probe sent and every response received is captured.
+    #[test]
+    #[ignore = "requires CAP_NET_RAW"]
+    fn test_capture_loopback() {
+        use std::net::{IpAddr, Ipv4Addr};
+        use std::sync::atomic::AtomicUsize;
+        use trippy_core::{
+            MultipathStrategy, PortDirection, ProbeStatus, Tracer, TracerAddrFamily, TracerChannel,
+            TracerChannelConfig, TracerConfig, TracerProtocol,
+        };
+        let path = temp_path("loopback");
+        let capture = Capture::start(&path, None).unwrap();
+        let addr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let channel_config = TracerChannelConfig::new(
+            TracerProtocol::Icmp,
+            TracerAddrFamily::Ipv4,
+            addr,
+            addr,
+            4242,
+            84,
+            0,
+            0,
+            33000,
+            MultipathStrategy::Classic,
+            PortDirection::None,
+            Duration::from_millis(10),
+            Duration::from_secs(1),
+            Some(capture.packet_capture()),
+            None,
+            None,
+        );
+        let tracer_config = TracerConfig::new(
+            addr,
+            TracerProtocol::Icmp,
+            Some(3),
+            4242,
+            1,
+            64,
+            Duration::from_millis(100),
+            24,
+            33000,
+            Duration::from_millis(10),
+            Duration::from_millis(10),
+            Duration::from_secs(1),
+            84,
+            0,
+        )
+        .unwrap();
+        let sent = AtomicUsize::new(0);
+        let complete = AtomicUsize::new(0);
+        let channel = TracerChannel::connect(&channel_config).unwrap();
+        Tracer::new(&tracer_config, |round| {
+            for probe in round.probes {
+                match probe.status {
+                    ProbeStatus::Complete => {
+                        sent.fetch_add(1, Ordering::SeqCst);
+                        complete.fetch_add(1, Ordering::SeqCst);
+                    }
+                    ProbeStatus::Awaited => {
+                        sent.fetch_add(1, Ordering::SeqCst);
+                    }
+                    ProbeStatus::NotSent | ProbeStatus::Failed(_) => {}
+                }
+            }
+        })
+        .trace(channel)
+        .unwrap();
+        capture.finish().unwrap();
+        let (_, packets) = read_pcap(&path);
+        std::fs::remove_file(&path).unwrap();
+        let icmp_type = |packet: &Vec<u8>| packet[usize::from(packet[0] & 0x0f) * 4];
+        let echo_requests = packets.iter().filter(|p| icmp_type(p) == 8).count();
+        let echo_replies = packets.iter().filter(|p| icmp_type(p) == 0).count();
+        // each echo request is captured both when sent and when the raw socket receives it on the loopback interface
+        assert_eq!(2 * sent.into_inner(), echo_requests);
+        assert_eq!(complete.into_inner(), echo_replies);
+    }
+}
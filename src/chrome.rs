@@ -0,0 +1,483 @@
+use anyhow::anyhow;
+use parking_lot::Mutex;
+use serde_json::{json, Map, Value};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+use trippy_core::COUNTER_TARGET;
+
+/// The process id of every event, as the log is of a single process.
+const PID: u64 = 1;
+
+/// The id of the next thread to log an event.
+static NEXT_THREAD_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    /// The id of the current thread in the log, once it has logged an event.
+    static THREAD_ID: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+/// Log the spans and counters of the session to `path` in the Chrome trace event format, such that it may be opened
+/// in `chrome://tracing` or Perfetto.
+///
+/// The subscriber is installed globally and the log is completed once the `ChromeLog` returned is dropped, though a
+/// log which is not completed may still be opened.
+pub fn start(path: &Path) -> anyhow::Result<ChromeLog> {
+    let file = File::create(path)
+        .map_err(|err| anyhow!("failed to create log file {}: {err}", path.display()))?;
+    let log = ChromeLog::new(Box::new(BufWriter::new(file)));
+    tracing::subscriber::set_global_default(log.subscriber())?;
+    Ok(log)
+}
+
+/// Log to `path` as [`start`] does, but create the file only once signalled by `open`, buffering the events logged
+/// before then, such that it is created with the privileges of the process at that time.
+///
+/// Nothing is written if `open` is dropped without being signalled.
+pub fn start_after(path: &Path, open: Receiver<()>) -> anyhow::Result<ChromeLog> {
+    let log = ChromeLog::new(Box::new(DeferredFile::new(path, open)));
+    tracing::subscriber::set_global_default(log.subscriber())?;
+    Ok(log)
+}
+
+/// A file which is created once signalled, whatever is written before then being buffered in memory.
+struct DeferredFile {
+    path: PathBuf,
+    open: Receiver<()>,
+    state: DeferredState,
+}
+
+enum DeferredState {
+    /// Not yet signalled, with what has been written so far.
+    Pending(Vec<u8>),
+    Open(BufWriter<File>),
+    /// The file could not be created, or was never to be, and so whatever is written is discarded.
+    Closed(Option<String>),
+}
+
+impl DeferredFile {
+    fn new(path: &Path, open: Receiver<()>) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            open,
+            state: DeferredState::Pending(vec![]),
+        }
+    }
+
+    /// Create the file, and write to it whatever has been buffered, if it has been signalled since last written.
+    fn poll(&mut self) {
+        if let DeferredState::Pending(buffer) = &self.state {
+            self.state = match self.open.try_recv() {
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => DeferredState::Closed(None),
+                Ok(()) => {
+                    let created = File::create(&self.path).and_then(|file| {
+                        let mut file = BufWriter::new(file);
+                        file.write_all(buffer)?;
+                        Ok(file)
+                    });
+                    match created {
+                        Ok(file) => DeferredState::Open(file),
+                        Err(err) => DeferredState::Closed(Some(format!(
+                            "failed to create log file {}: {err}",
+                            self.path.display()
+                        ))),
+                    }
+                }
+            };
+        }
+    }
+}
+
+impl Write for DeferredFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.poll();
+        match &mut self.state {
+            DeferredState::Pending(buffer) => buffer.extend_from_slice(buf),
+            DeferredState::Open(file) => file.write_all(buf)?,
+            DeferredState::Closed(_) => {}
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.poll();
+        match &mut self.state {
+            DeferredState::Open(file) => file.flush(),
+            DeferredState::Closed(Some(err)) => Err(io::Error::other(err.clone())),
+            DeferredState::Pending(_) | DeferredState::Closed(None) => Ok(()),
+        }
+    }
+}
+
+/// A log in the Chrome trace event format, a JSON array of events.
+pub struct ChromeLog {
+    writer: Arc<Mutex<Writer>>,
+}
+
+impl ChromeLog {
+    fn new(out: Box<dyn Write + Send>) -> Self {
+        Self {
+            writer: Arc::new(Mutex::new(Writer {
+                out,
+                start: Instant::now(),
+                events: 0,
+                finished: false,
+            })),
+        }
+    }
+
+    /// A subscriber which logs every span entered and event to this log.
+    fn subscriber(&self) -> ChromeSubscriber {
+        ChromeSubscriber {
+            writer: self.writer.clone(),
+            spans: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(1),
+        }
+    }
+}
+
+impl Drop for ChromeLog {
+    fn drop(&mut self) {
+        if let Err(err) = self.writer.lock().finish() {
+            eprintln!("{err}");
+        }
+    }
+}
+
+/// Writes the events of the log.
+///
+/// The log is for diagnosis only and so a failure to write it is ignored rather than failing the trace, though it is
+/// reported once the log is finished.
+struct Writer {
+    out: Box<dyn Write + Send>,
+    start: Instant,
+    events: usize,
+    finished: bool,
+}
+
+impl Writer {
+    fn write(&mut self, event: &Value) {
+        if !self.finished {
+            let separator = if self.events == 0 { "[\n" } else { ",\n" };
+            let _ = write!(self.out, "{separator}{event}");
+            self.events += 1;
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+        let open = if self.events == 0 { "[" } else { "" };
+        let _ = writeln!(self.out, "{open}\n]");
+        self.out.flush()
+    }
+
+    /// The timestamp of an event logged now, in microseconds since the log was started.
+    fn timestamp(&self) -> f64 {
+        self.start.elapsed().as_secs_f64() * 1_000_000.0
+    }
+
+    /// The id of the current thread, which is named in the log when it first logs an event.
+    fn thread_id(&mut self) -> u64 {
+        if let Some(id) = THREAD_ID.with(Cell::get) {
+            return id;
+        }
+        let id = NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed);
+        THREAD_ID.with(|thread_id| thread_id.set(Some(id)));
+        if let Some(name) = thread::current().name() {
+            self.write(&json!({
+                "ph": "M",
+                "name": "thread_name",
+                "pid": PID,
+                "tid": id,
+                "args": { "name": name },
+            }));
+        }
+        id
+    }
+
+    /// Log an event of phase `ph`, such as `B` to begin a span, now on the current thread.
+    fn event(&mut self, ph: &str, name: &str, args: &Map<String, Value>) {
+        let tid = self.thread_id();
+        let ts = self.timestamp();
+        self.write(&json!({
+            "ph": ph,
+            "name": name,
+            "pid": PID,
+            "tid": tid,
+            "ts": ts,
+            "args": args,
+        }));
+    }
+}
+
+/// A span which has been created and not yet closed.
+struct SpanData {
+    name: &'static str,
+    args: Map<String, Value>,
+    refs: usize,
+}
+
+/// A `tracing` subscriber which logs each span entered as a duration and each event as an instant, other than those
+/// of the `COUNTER_TARGET` which are logged as counters.
+struct ChromeSubscriber {
+    writer: Arc<Mutex<Writer>>,
+    spans: Mutex<HashMap<u64, SpanData>>,
+    next_id: AtomicU64,
+}
+
+impl ChromeSubscriber {
+    /// The name and fields of the span `id`, if it has not been closed.
+    fn span(&self, id: &Id) -> Option<(&'static str, Map<String, Value>)> {
+        self.spans
+            .lock()
+            .get(&id.into_u64())
+            .map(|span| (span.name, span.args.clone()))
+    }
+}
+
+impl Subscriber for ChromeSubscriber {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut args = Map::new();
+        span.record(&mut Fields(&mut args));
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.spans.lock().insert(
+            id,
+            SpanData {
+                name: span.metadata().name(),
+                args,
+                refs: 1,
+            },
+        );
+        Id::from_u64(id)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        if let Some(span) = self.spans.lock().get_mut(&span.into_u64()) {
+            values.record(&mut Fields(&mut span.args));
+        }
+    }
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut args = Map::new();
+        event.record(&mut Fields(&mut args));
+        let mut writer = self.writer.lock();
+        if event.metadata().target() == COUNTER_TARGET {
+            for (name, value) in args {
+                let counter = Map::from_iter([(name.clone(), value)]);
+                writer.event("C", &name, &counter);
+            }
+        } else {
+            let name = match args.get("message") {
+                Some(Value::String(message)) => message.clone(),
+                _ => event.metadata().name().to_string(),
+            };
+            writer.event("i", &name, &args);
+        }
+    }
+
+    fn enter(&self, span: &Id) {
+        if let Some((name, args)) = self.span(span) {
+            self.writer.lock().event("B", name, &args);
+        }
+    }
+
+    fn exit(&self, span: &Id) {
+        if let Some((name, _)) = self.span(span) {
+            self.writer.lock().event("E", name, &Map::new());
+        }
+    }
+
+    fn clone_span(&self, id: &Id) -> Id {
+        if let Some(span) = self.spans.lock().get_mut(&id.into_u64()) {
+            span.refs += 1;
+        }
+        id.clone()
+    }
+
+    fn try_close(&self, id: Id) -> bool {
+        let mut spans = self.spans.lock();
+        let closed = spans.get_mut(&id.into_u64()).is_some_and(|span| {
+            span.refs -= 1;
+            span.refs == 0
+        });
+        if closed {
+            spans.remove(&id.into_u64());
+        }
+        closed
+    }
+}
+
+/// Records the fields of a span or event as the args of a log event.
+struct Fields<'a>(&'a mut Map<String, Value>);
+
+impl Visit for Fields<'_> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0
+            .insert(field.name().to_string(), json!(format!("{value:?}")));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::mpsc;
+
+    /// A log written to memory.
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// The events logged whilst running `f`, other than the names of the threads.
+    fn logged(f: impl FnOnce()) -> Vec<Value> {
+        let buffer = Buffer::default();
+        let log = ChromeLog::new(Box::new(buffer.clone()));
+        tracing::subscriber::with_default(log.subscriber(), f);
+        drop(log);
+        let events: Vec<Value> = serde_json::from_slice(&buffer.0.lock()).unwrap();
+        events
+            .into_iter()
+            .filter(|event| event["ph"] != "M")
+            .collect()
+    }
+
+    #[test]
+    fn test_spans() {
+        let events = logged(|| {
+            let _span = tracing::debug_span!("channel.send_probe", ttl = 3_u8).entered();
+            tracing::debug_span!("channel.poll").in_scope(|| {});
+        });
+        let phases: Vec<_> = events
+            .iter()
+            .map(|event| (event["ph"].as_str(), event["name"].as_str()))
+            .collect();
+        assert_eq!(
+            vec![
+                (Some("B"), Some("channel.send_probe")),
+                (Some("B"), Some("channel.poll")),
+                (Some("E"), Some("channel.poll")),
+                (Some("E"), Some("channel.send_probe")),
+            ],
+            phases
+        );
+        assert_eq!(json!({ "ttl": 3 }), events[0]["args"]);
+        assert!(events[0]["ts"].as_f64().unwrap() <= events[3]["ts"].as_f64().unwrap());
+    }
+
+    #[test]
+    fn test_counters_and_events() {
+        let events = logged(|| {
+            tracing::trace!(target: COUNTER_TARGET, malformed_packets = 2_u64);
+            tracing::info!(addr = "10.0.0.1", "lookup failed");
+        });
+        assert_eq!(2, events.len());
+        assert_eq!("C", events[0]["ph"]);
+        assert_eq!("malformed_packets", events[0]["name"]);
+        assert_eq!(json!({ "malformed_packets": 2 }), events[0]["args"]);
+        assert_eq!("i", events[1]["ph"]);
+        assert_eq!("lookup failed", events[1]["name"]);
+        assert_eq!("10.0.0.1", events[1]["args"]["addr"]);
+    }
+
+    #[test]
+    fn test_empty_log() {
+        assert!(logged(|| {}).is_empty());
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("trippy-chrome-{name}-{}.json", std::process::id()))
+    }
+
+    #[test]
+    fn test_deferred_file() {
+        let path = temp_path("deferred");
+        let (open, gate) = mpsc::channel();
+        let mut file = DeferredFile::new(&path, gate);
+        file.write_all(b"before ").unwrap();
+        file.flush().unwrap();
+        assert!(!path.exists());
+        open.send(()).unwrap();
+        file.write_all(b"after").unwrap();
+        file.flush().unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!("before after", written);
+    }
+
+    #[test]
+    fn test_deferred_file_not_signalled() {
+        let path = temp_path("abandoned");
+        let (open, gate) = mpsc::channel::<()>();
+        let mut file = DeferredFile::new(&path, gate);
+        file.write_all(b"before").unwrap();
+        drop(open);
+        file.write_all(b"after").unwrap();
+        file.flush().unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_deferred_file_failed() {
+        let path = Path::new("/no/such/dir/trippy.json");
+        let (open, gate) = mpsc::channel();
+        let mut file = DeferredFile::new(path, gate);
+        open.send(()).unwrap();
+        file.write_all(b"event").unwrap();
+        assert!(file
+            .flush()
+            .unwrap_err()
+            .to_string()
+            .starts_with("failed to create log file /no/such/dir/trippy.json: "));
+    }
+}
@@ -1,10 +1,15 @@
 use crate::config::DnsResolveMethod;
 use crate::dns::inner::DnsResolverInner;
+use crate::event::SharedEventLog;
 use std::fmt::{Display, Formatter};
 use std::net::IpAddr;
+use std::path::PathBuf;
 use std::rc::Rc;
 use std::time::Duration;
 
+/// Reading the hosts file of the platform.
+mod hosts;
+
 /// The state of reverse DNS resolution.
 #[derive(Debug, Clone)]
 pub enum DnsEntry {
@@ -45,6 +50,71 @@ impl Display for DnsEntry {
     }
 }
 
+/// Where the answer to a forward or reverse DNS lookup came from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DnsSource {
+    /// The hosts file of the platform, which the `system` resolver consults before the network.
+    HostsFile,
+    /// The resolver of the OS.
+    System,
+    /// The DNS servers of `/etc/resolv.conf`.
+    Resolv,
+    /// The Google `8.8.8.8` DNS service.
+    Google,
+    /// The Cloudflare `1.1.1.1` DNS service.
+    Cloudflare,
+}
+
+impl From<DnsResolveMethod> for DnsSource {
+    fn from(resolve_method: DnsResolveMethod) -> Self {
+        match resolve_method {
+            DnsResolveMethod::System => Self::System,
+            DnsResolveMethod::Resolv => Self::Resolv,
+            DnsResolveMethod::Google => Self::Google,
+            DnsResolveMethod::Cloudflare => Self::Cloudflare,
+        }
+    }
+}
+
+impl Display for DnsSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::HostsFile => write!(f, "hosts-file"),
+            Self::System => write!(f, "system"),
+            Self::Resolv => write!(f, "resolv"),
+            Self::Google => write!(f, "google"),
+            Self::Cloudflare => write!(f, "cloudflare"),
+        }
+    }
+}
+
+/// A hostname which does not exist, as far as the resolver consulted knows.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct HostNotFound {
+    pub hostname: String,
+    /// The resolver consulted.
+    pub source: DnsSource,
+}
+
+impl Display for HostNotFound {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.source {
+            DnsSource::System | DnsSource::HostsFile => write!(
+                f,
+                "no such host {}, having consulted the hosts file and the system resolver",
+                self.hostname
+            ),
+            source => write!(
+                f,
+                "no such host {}, having consulted the {source} resolver",
+                self.hostname
+            ),
+        }
+    }
+}
+
+impl std::error::Error for HostNotFound {}
+
 /// Autonomous System (AS) information.
 #[derive(Debug, Clone, Default)]
 pub struct AsInfo {
@@ -64,11 +134,13 @@ pub enum IpAddrFamily {
 }
 
 /// Configuration for the `DnsResolver`.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 pub struct DnsResolverConfig {
     pub resolve_method: DnsResolveMethod,
     pub addr_family: IpAddrFamily,
     pub timeout: Duration,
+    /// The hosts file consulted before the network by the `system` resolver, that of the platform by default.
+    pub hosts_path: PathBuf,
 }
 
 impl DnsResolverConfig {
@@ -77,6 +149,7 @@ impl DnsResolverConfig {
             resolve_method,
             addr_family: IpAddrFamily::Ipv4,
             timeout,
+            hosts_path: hosts::default_path(),
         }
     }
 
@@ -85,8 +158,16 @@ impl DnsResolverConfig {
             resolve_method,
             addr_family: IpAddrFamily::Ipv6,
             timeout,
+            hosts_path: hosts::default_path(),
         }
     }
+
+    /// Consult the hosts file at `hosts_path` rather than that of the platform.
+    #[cfg(test)]
+    #[must_use]
+    pub fn with_hosts_path(self, hosts_path: PathBuf) -> Self {
+        Self { hosts_path, ..self }
+    }
 }
 
 /// A cheaply cloneable, non-blocking, caching, forward and reverse DNS resolver.
@@ -96,14 +177,17 @@ pub struct DnsResolver {
 }
 
 impl DnsResolver {
-    pub fn start(config: DnsResolverConfig) -> anyhow::Result<Self> {
+    /// Start the resolver, recording failed and timed out reverse DNS lookups in `events`.
+    pub fn start(config: DnsResolverConfig, events: SharedEventLog) -> anyhow::Result<Self> {
         Ok(Self {
-            inner: Rc::new(DnsResolverInner::start(config)?),
+            inner: Rc::new(DnsResolverInner::start(config, events)?),
         })
     }
 
-    /// Resolve a DNS hostname to IP addresses.
-    pub fn lookup(&self, hostname: &str) -> anyhow::Result<Vec<IpAddr>> {
+    /// Resolve a DNS hostname to IP addresses, along with where they came from.
+    ///
+    /// There is no source if the hostname is itself an address, as it is not resolved.
+    pub fn lookup(&self, hostname: &str) -> anyhow::Result<(Vec<IpAddr>, Option<DnsSource>)> {
         self.inner.lookup(hostname)
     }
 
@@ -128,6 +212,11 @@ impl DnsResolver {
         self.inner.reverse_lookup(addr, true)
     }
 
+    /// Where the reverse DNS resolution of `IpAddr` came from, if it has resolved.
+    pub fn reverse_lookup_source(&self, addr: IpAddr) -> Option<DnsSource> {
+        self.inner.reverse_lookup_source(addr)
+    }
+
     /// Get the `DnsResolverConfig`.
     pub fn config(&self) -> &DnsResolverConfig {
         self.inner.config()
@@ -140,21 +229,29 @@ impl DnsResolver {
 
 /// Private impl of resolver.
 mod inner {
+    use crate::dns::hosts::HostsFile;
     use crate::dns::{
-        AsInfo, DnsEntry, DnsResolveMethod, DnsResolverConfig, IpAddrFamily, Resolved,
+        AsInfo, DnsEntry, DnsResolveMethod, DnsResolverConfig, DnsSource, HostNotFound,
+        IpAddrFamily, Resolved,
     };
+    use crate::event::{EventLevel, SharedEventLog};
     use anyhow::anyhow;
     use crossbeam::channel::{bounded, Receiver, Sender};
+    use dns_lookup::{AddrInfoHints, LookupErrorKind, SockType};
     use itertools::Itertools;
     use parking_lot::RwLock;
     use std::collections::HashMap;
+    use std::io;
     use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+    use std::path::PathBuf;
     use std::str::FromStr;
     use std::sync::Arc;
     use std::thread;
     use std::time::Duration;
+    use tracing::debug_span;
     use trust_dns_resolver::config::{LookupIpStrategy, ResolverConfig, ResolverOpts};
     use trust_dns_resolver::error::ResolveErrorKind;
+    use trust_dns_resolver::proto::op::ResponseCode;
     use trust_dns_resolver::proto::rr::RecordType;
     use trust_dns_resolver::{Name, Resolver};
 
@@ -164,13 +261,14 @@ mod inner {
     /// The duration wait to enqueue a `DnsEntry::Pending` to the resolver before returning `DnsEntry::Timeout`.
     const RESOLVER_QUEUE_TIMEOUT: Duration = Duration::from_millis(10);
 
-    /// Alias for a cache of reverse DNS lookup entries.
-    type Cache = Arc<RwLock<HashMap<IpAddr, DnsEntry>>>;
+    /// Alias for a cache of reverse DNS lookup entries, each with where it came from once resolved.
+    type Cache = Arc<RwLock<HashMap<IpAddr, (DnsEntry, Option<DnsSource>)>>>;
 
     #[derive(Clone)]
     enum DnsProvider {
-        TrustDns(Arc<Resolver>),
-        DnsLookup,
+        TrustDns(Arc<Resolver>, DnsSource),
+        /// The resolver of the OS, which consults the hosts file at the path given first.
+        DnsLookup(PathBuf),
     }
 
     #[derive(Debug, Clone)]
@@ -185,15 +283,16 @@ mod inner {
         provider: DnsProvider,
         tx: Sender<DnsResolveRequest>,
         addr_cache: Cache,
+        events: SharedEventLog,
     }
 
     impl DnsResolverInner {
-        pub fn start(config: DnsResolverConfig) -> anyhow::Result<Self> {
+        pub fn start(config: DnsResolverConfig, events: SharedEventLog) -> anyhow::Result<Self> {
             let (tx, rx) = bounded(RESOLVER_MAX_QUEUE_SIZE);
             let addr_cache = Arc::new(RwLock::new(HashMap::new()));
 
             let provider = if matches!(config.resolve_method, DnsResolveMethod::System) {
-                DnsProvider::DnsLookup
+                DnsProvider::DnsLookup(config.hosts_path.clone())
             } else {
                 let mut options = ResolverOpts::default();
                 options.timeout = config.timeout;
@@ -210,20 +309,22 @@ mod inner {
                     DnsResolveMethod::System => unreachable!(),
                 }?;
                 let resolver = Arc::new(res);
-                DnsProvider::TrustDns(resolver)
+                DnsProvider::TrustDns(resolver, DnsSource::from(config.resolve_method))
             };
 
             // spawn a thread to process the resolve queue
             {
                 let cache = addr_cache.clone();
                 let provider = provider.clone();
-                thread::spawn(move || resolver_queue_processor(rx, &provider, &cache));
+                let events = events.clone();
+                thread::spawn(move || resolver_queue_processor(rx, &provider, &cache, &events));
             }
             Ok(Self {
                 config,
                 provider,
                 tx,
                 addr_cache,
+                events,
             })
         }
 
@@ -231,15 +332,44 @@ mod inner {
             &self.config
         }
 
-        pub fn lookup(&self, hostname: &str) -> anyhow::Result<Vec<IpAddr>> {
+        pub fn lookup(&self, hostname: &str) -> anyhow::Result<(Vec<IpAddr>, Option<DnsSource>)> {
+            let _span = debug_span!("dns.lookup", hostname).entered();
+            if let Ok(addr) = IpAddr::from_str(hostname) {
+                return Ok((vec![addr], None));
+            }
             match &self.provider {
-                DnsProvider::TrustDns(resolver) => {
-                    Ok(resolver.lookup_ip(hostname)?.iter().collect::<Vec<_>>())
+                DnsProvider::TrustDns(resolver, source) => match resolver.lookup_ip(hostname) {
+                    Ok(lookup) => Ok((lookup.iter().collect::<Vec<_>>(), Some(*source))),
+                    Err(err) => match err.kind() {
+                        ResolveErrorKind::NoRecordsFound {
+                            response_code: ResponseCode::NXDomain,
+                            ..
+                        } => Err(HostNotFound {
+                            hostname: hostname.to_string(),
+                            source: *source,
+                        }
+                        .into()),
+                        _ => Err(err.into()),
+                    },
+                },
+                DnsProvider::DnsLookup(hosts_path) => {
+                    let addrs = HostsFile::read(hosts_path).lookup(hostname);
+                    if addrs.is_empty() {
+                        Ok((lookup_system(hostname)?, Some(DnsSource::System)))
+                    } else {
+                        Ok((addrs, Some(DnsSource::HostsFile)))
+                    }
                 }
-                DnsProvider::DnsLookup => Ok(dns_lookup::lookup_host(hostname)?),
             }
         }
 
+        pub fn reverse_lookup_source(&self, addr: IpAddr) -> Option<DnsSource> {
+            self.addr_cache
+                .read()
+                .get(&addr)
+                .and_then(|(_, source)| *source)
+        }
+
         pub fn reverse_lookup(&self, addr: IpAddr, with_asinfo: bool) -> DnsEntry {
             let mut enqueue = false;
 
@@ -251,8 +381,9 @@ mod inner {
                 .entry(addr)
                 .or_insert_with(|| {
                     enqueue = true;
-                    DnsEntry::Pending(addr)
+                    (DnsEntry::Pending(addr), None)
                 })
+                .0
                 .clone();
 
             // If the entry exists but has timed out, then set it as DnsEntry::Pending and enqueue it again.
@@ -261,7 +392,7 @@ mod inner {
                     .addr_cache
                     .write()
                     .get_mut(&addr)
-                    .expect("addr must be in cache") = DnsEntry::Pending(addr);
+                    .expect("addr must be in cache") = (DnsEntry::Pending(addr), None);
                 dns_entry = DnsEntry::Pending(addr);
                 enqueue = true;
             }
@@ -284,7 +415,10 @@ mod inner {
                         .addr_cache
                         .write()
                         .get_mut(&addr)
-                        .expect("addr must be in cache") = DnsEntry::Timeout(addr);
+                        .expect("addr must be in cache") = (DnsEntry::Timeout(addr), None);
+                    self.events
+                        .write()
+                        .push(EventLevel::Warn, "DNS resolver queue is full");
                     DnsEntry::Timeout(addr)
                 }
             } else {
@@ -297,26 +431,59 @@ mod inner {
         }
     }
 
+    /// Resolve `hostname` with the resolver of the OS, as `dns_lookup::lookup_host` does, but telling a hostname which
+    /// does not exist apart from a failure to resolve it.
+    fn lookup_system(hostname: &str) -> anyhow::Result<Vec<IpAddr>> {
+        let hints = AddrInfoHints {
+            socktype: SockType::Stream.into(),
+            ..AddrInfoHints::default()
+        };
+        match dns_lookup::getaddrinfo(Some(hostname), None, Some(hints)) {
+            Ok(addrs) => Ok(addrs
+                .map(|addr| addr.map(|addr| addr.sockaddr.ip()))
+                .collect::<io::Result<Vec<_>>>()?),
+            Err(err) if matches!(err.kind(), LookupErrorKind::NoName) => Err(HostNotFound {
+                hostname: hostname.to_string(),
+                source: DnsSource::System,
+            }
+            .into()),
+            Err(err) => Err(io::Error::from(err).into()),
+        }
+    }
+
     /// Process each `IpAddr` from the resolver queue and perform the reverse DNS lookup.
     ///
     /// For each `IpAddr`, perform the reverse DNS lookup and update the cache with the result (`Resolved`, `NotFound`,
-    /// `Timeout` or `Failed`) for that addr.
+    /// `Timeout` or `Failed`) for that addr.  Lookups which time out or fail are recorded in `events`.
     fn resolver_queue_processor(
         rx: Receiver<DnsResolveRequest>,
         provider: &DnsProvider,
         cache: &Cache,
+        events: &SharedEventLog,
     ) {
         for DnsResolveRequest { addr, with_asinfo } in rx {
-            let entry = match &provider {
-                DnsProvider::DnsLookup => {
-                    // we can't distinguish between a failed lookup or a genuine error and so we just assume all
-                    // failures are `DnsEntry::NotFound`.
-                    match dns_lookup::lookup_addr(&addr) {
-                        Ok(dns) => DnsEntry::Resolved(Resolved::Normal(addr, vec![dns])),
-                        Err(_) => DnsEntry::NotFound(addr),
+            let _span = debug_span!("dns.reverse_lookup", %addr, with_asinfo).entered();
+            let (entry, source) = match &provider {
+                DnsProvider::DnsLookup(hosts_path) => {
+                    let hostnames = HostsFile::read(hosts_path).reverse_lookup(addr);
+                    if hostnames.is_empty() {
+                        // we can't distinguish between a failed lookup or a genuine error and so we just assume all
+                        // failures are `DnsEntry::NotFound`.
+                        match dns_lookup::lookup_addr(&addr) {
+                            Ok(dns) => (
+                                DnsEntry::Resolved(Resolved::Normal(addr, vec![dns])),
+                                Some(DnsSource::System),
+                            ),
+                            Err(_) => (DnsEntry::NotFound(addr), None),
+                        }
+                    } else {
+                        (
+                            DnsEntry::Resolved(Resolved::Normal(addr, hostnames)),
+                            Some(DnsSource::HostsFile),
+                        )
                     }
                 }
-                DnsProvider::TrustDns(resolver) => match resolver.reverse_lookup(addr) {
+                DnsProvider::TrustDns(resolver, source) => match resolver.reverse_lookup(addr) {
                     Ok(name) => {
                         let hostnames = name
                             .into_iter()
@@ -326,21 +493,33 @@ mod inner {
                             })
                             .map(|s| s.to_string())
                             .collect();
-                        if with_asinfo {
+                        let entry = if with_asinfo {
                             let as_info = lookup_asinfo(resolver, addr).unwrap_or_default();
                             DnsEntry::Resolved(Resolved::WithAsInfo(addr, hostnames, as_info))
                         } else {
                             DnsEntry::Resolved(Resolved::Normal(addr, hostnames))
-                        }
+                        };
+                        (entry, Some(*source))
                     }
                     Err(err) => match err.kind() {
-                        ResolveErrorKind::NoRecordsFound { .. } => DnsEntry::NotFound(addr),
-                        ResolveErrorKind::Timeout => DnsEntry::Timeout(addr),
-                        _ => DnsEntry::Failed(addr),
+                        ResolveErrorKind::NoRecordsFound { .. } => (DnsEntry::NotFound(addr), None),
+                        ResolveErrorKind::Timeout => (DnsEntry::Timeout(addr), None),
+                        _ => (DnsEntry::Failed(addr), None),
                     },
                 },
             };
-            cache.write().insert(addr, entry);
+            match entry {
+                DnsEntry::Timeout(addr) => events.write().push(
+                    EventLevel::Warn,
+                    format!("reverse DNS lookup of {addr} timed out"),
+                ),
+                DnsEntry::Failed(addr) => events.write().push(
+                    EventLevel::Warn,
+                    format!("reverse DNS lookup of {addr} failed"),
+                ),
+                _ => {}
+            }
+            cache.write().insert(addr, (entry, source));
         }
     }
 
@@ -454,3 +633,105 @@ mod inner {
         Ok(split.nth(4).unwrap_or_default().trim().to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::EventLog;
+    use std::fs;
+    use std::net::Ipv4Addr;
+    use std::thread;
+
+    /// A `system` resolver which consults the hosts file `hosts`, written to a temporary file named for `name`.
+    fn resolver(name: &str, hosts: &str) -> (DnsResolver, PathBuf) {
+        let path = std::env::temp_dir().join(format!("trippy-hosts-{name}-{}", std::process::id()));
+        fs::write(&path, hosts).unwrap();
+        let config = DnsResolverConfig::new_ipv4(DnsResolveMethod::System, Duration::from_secs(1))
+            .with_hosts_path(path.clone());
+        (
+            DnsResolver::start(config, EventLog::shared()).unwrap(),
+            path,
+        )
+    }
+
+    #[test]
+    fn test_lookup_hosts_file_first() {
+        let (resolver, path) = resolver("lookup", "10.1.2.3 localhost lab-target.test\n");
+        let lookup = resolver.lookup("localhost");
+        let lab_target = resolver.lookup("lab-target.test");
+        let addr = resolver.lookup("192.0.2.1");
+        fs::remove_file(&path).unwrap();
+        let hosts_addr = IpAddr::V4(Ipv4Addr::new(10, 1, 2, 3));
+        assert_eq!(
+            (vec![hosts_addr], Some(DnsSource::HostsFile)),
+            lookup.unwrap()
+        );
+        assert_eq!(
+            (vec![hosts_addr], Some(DnsSource::HostsFile)),
+            lab_target.unwrap()
+        );
+        assert_eq!(
+            (vec![IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))], None),
+            addr.unwrap()
+        );
+    }
+
+    #[test]
+    fn test_lookup_without_hosts_file() {
+        let (resolver, path) = resolver("missing", "");
+        fs::remove_file(&path).unwrap();
+        let (addrs, source) = resolver.lookup("localhost").unwrap();
+        assert_eq!(Some(DnsSource::System), source);
+        assert!(addrs.iter().all(IpAddr::is_loopback));
+    }
+
+    #[test]
+    fn test_reverse_lookup_hosts_file_first() {
+        let (resolver, path) = resolver("reverse", "127.0.0.1 lab-loopback.test loopback\n");
+        let addr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let mut entry = resolver.reverse_lookup(addr);
+        for _ in 0..100 {
+            if !matches!(entry, DnsEntry::Pending(_)) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+            entry = resolver.reverse_lookup(addr);
+        }
+        fs::remove_file(&path).unwrap();
+        assert_eq!("lab-loopback.test loopback", entry.to_string());
+        assert_eq!(
+            Some(DnsSource::HostsFile),
+            resolver.reverse_lookup_source(addr)
+        );
+        resolver.flush();
+        assert_eq!(None, resolver.reverse_lookup_source(addr));
+    }
+
+    #[test]
+    fn test_host_not_found_display() {
+        let not_found = |source| {
+            HostNotFound {
+                hostname: String::from("gogle.com"),
+                source,
+            }
+            .to_string()
+        };
+        assert_eq!(
+            "no such host gogle.com, having consulted the hosts file and the system resolver",
+            not_found(DnsSource::System)
+        );
+        assert_eq!(
+            "no such host gogle.com, having consulted the google resolver",
+            not_found(DnsSource::Google)
+        );
+    }
+
+    #[test]
+    fn test_source_display() {
+        assert_eq!("hosts-file", DnsSource::HostsFile.to_string());
+        assert_eq!(
+            DnsSource::Cloudflare,
+            DnsSource::from(DnsResolveMethod::Cloudflare)
+        );
+    }
+}
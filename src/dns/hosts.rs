@@ -0,0 +1,136 @@
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+/// The path of the hosts file of the platform.
+#[cfg(not(windows))]
+pub fn default_path() -> PathBuf {
+    PathBuf::from("/etc/hosts")
+}
+
+/// The path of the hosts file of the platform, within the `System32` directory of the Windows installation.
+#[cfg(windows)]
+pub fn default_path() -> PathBuf {
+    let system_root = std::env::var_os("SystemRoot").unwrap_or_else(|| "C:\\Windows".into());
+    PathBuf::from(system_root).join(r"System32\drivers\etc\hosts")
+}
+
+/// The entries of a hosts file, each an address followed by its canonical hostname and any aliases.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct HostsFile {
+    entries: Vec<(IpAddr, Vec<String>)>,
+}
+
+impl HostsFile {
+    /// Read the hosts file at `path`, which is read as having no entries if it cannot be read, as a missing hosts file
+    /// is not an error for the platform resolver either.
+    pub fn read(path: &Path) -> Self {
+        std::fs::read_to_string(path).map_or_else(|_| Self::default(), |hosts| Self::parse(&hosts))
+    }
+
+    /// Parse the `hosts` file, skipping comments and any line which does not start with an address.
+    ///
+    /// The zone of an address, such as `fe80::1%lo0`, is ignored.
+    pub fn parse(hosts: &str) -> Self {
+        let entries = hosts
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split('#').next()?.split_whitespace();
+                let addr = fields.next()?.split('%').next()?.parse().ok()?;
+                let hostnames: Vec<_> = fields.map(String::from).collect();
+                (!hostnames.is_empty()).then_some((addr, hostnames))
+            })
+            .collect();
+        Self { entries }
+    }
+
+    /// The addresses of `hostname`, in the order they are listed, matching the hostname regardless of case.
+    pub fn lookup(&self, hostname: &str) -> Vec<IpAddr> {
+        let hostname = hostname.trim_end_matches('.');
+        let mut addrs = vec![];
+        for (addr, _) in self.entries.iter().filter(|(_, hostnames)| {
+            hostnames
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(hostname))
+        }) {
+            if !addrs.contains(addr) {
+                addrs.push(*addr);
+            }
+        }
+        addrs
+    }
+
+    /// The hostnames of `addr`, those of the first line it is listed on, the canonical hostname first.
+    pub fn reverse_lookup(&self, addr: IpAddr) -> Vec<String> {
+        self.entries
+            .iter()
+            .find(|(entry, _)| *entry == addr)
+            .map(|(_, hostnames)| hostnames.clone())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    const HOSTS: &str = "\
+# lab targets
+127.0.0.1   localhost
+10.1.2.3    Lab-Target.test lab-target   # the edge router
+10.1.2.4\tlab-target.test
+fe80::1%lo0 link.test
+::1         localhost ip6-localhost
+not-an-addr bogus.test
+10.9.9.9
+";
+
+    #[test]
+    fn test_lookup() {
+        let hosts = HostsFile::parse(HOSTS);
+        assert_eq!(
+            vec![
+                IpAddr::V4(Ipv4Addr::new(10, 1, 2, 3)),
+                IpAddr::V4(Ipv4Addr::new(10, 1, 2, 4))
+            ],
+            hosts.lookup("lab-target.test")
+        );
+        assert_eq!(
+            vec![IpAddr::V4(Ipv4Addr::new(10, 1, 2, 3))],
+            hosts.lookup("LAB-TARGET.")
+        );
+        assert_eq!(
+            vec![
+                IpAddr::V4(Ipv4Addr::LOCALHOST),
+                IpAddr::V6(Ipv6Addr::LOCALHOST)
+            ],
+            hosts.lookup("localhost")
+        );
+        assert_eq!(
+            vec![IpAddr::V6("fe80::1".parse().unwrap())],
+            hosts.lookup("link.test")
+        );
+        assert!(hosts.lookup("bogus.test").is_empty());
+        assert!(hosts.lookup("edge").is_empty());
+    }
+
+    #[test]
+    fn test_reverse_lookup() {
+        let hosts = HostsFile::parse(HOSTS);
+        assert_eq!(
+            vec![String::from("Lab-Target.test"), String::from("lab-target")],
+            hosts.reverse_lookup(IpAddr::V4(Ipv4Addr::new(10, 1, 2, 3)))
+        );
+        assert!(hosts
+            .reverse_lookup(IpAddr::V4(Ipv4Addr::new(10, 9, 9, 9)))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_read_missing() {
+        assert_eq!(
+            HostsFile::default(),
+            HostsFile::read(Path::new("/no/such/hosts"))
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use chrono::{DateTime, Local};
+use parking_lot::RwLock;
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+
+/// The maximum number of events retained by the `EventLog`.
+pub const MAX_EVENTS: usize = 100;
+
+/// An `EventLog` shared between the tracers, the DNS resolver and the frontend.
+pub type SharedEventLog = Arc<RwLock<EventLog>>;
+
+/// The severity of an event.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EventLevel {
+    /// A change in the state of the trace, such as the target becoming unreachable.
+    Alert,
+    /// A recoverable problem, tracing continues.
+    Warn,
+    /// A fatal problem, tracing has stopped.
+    Error,
+}
+
+impl Display for EventLevel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Alert => write!(f, "alert"),
+            Self::Warn => write!(f, "warn"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A warning or error which occurred whilst tracing.
+#[derive(Debug, Clone)]
+pub struct Event {
+    /// The time the event last occurred.
+    pub time: DateTime<Local>,
+    pub level: EventLevel,
+    pub message: String,
+    /// The number of times the event occurred again immediately after it first occurred.
+    pub repeats: usize,
+}
+
+/// A bounded log of the most recent events, oldest first.
+///
+/// An event which repeats the latest event is not recorded again, rather the repeat is counted against the latest
+/// event, such that a problem which occurs every round does not push every other event out of the log.
+#[derive(Debug)]
+pub struct EventLog {
+    events: VecDeque<Event>,
+    capacity: usize,
+    /// The number of alerts ever recorded, including repeats and any which have been discarded.
+    alerts: usize,
+}
+
+impl EventLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            events: VecDeque::with_capacity(capacity),
+            capacity,
+            alerts: 0,
+        }
+    }
+
+    /// Create an empty `EventLog`, of `MAX_EVENTS` capacity, which may be shared between threads.
+    pub fn shared() -> SharedEventLog {
+        Arc::new(RwLock::new(Self::new(MAX_EVENTS)))
+    }
+
+    /// Record an event, discarding the oldest event if the log is full.
+    pub fn push(&mut self, level: EventLevel, message: impl Into<String>) {
+        let message = message.into();
+        let time = Local::now();
+        if level == EventLevel::Alert {
+            self.alerts += 1;
+        }
+        match self.events.back_mut() {
+            Some(latest) if latest.level == level && latest.message == message => {
+                latest.time = time;
+                latest.repeats += 1;
+            }
+            _ => {
+                if self.events.len() == self.capacity {
+                    self.events.pop_front();
+                }
+                if self.capacity > 0 {
+                    self.events.push_back(Event {
+                        time,
+                        level,
+                        message,
+                        repeats: 0,
+                    });
+                }
+            }
+        }
+    }
+
+    /// The most recent event, if any.
+    pub fn latest(&self) -> Option<&Event> {
+        self.events.back()
+    }
+
+    /// The events, oldest first.
+    pub fn events(&self) -> impl DoubleEndedIterator<Item = &Event> {
+        self.events.iter()
+    }
+
+    /// The number of alerts ever recorded, such that a reader can tell whether any have been recorded since it last
+    /// looked.
+    pub fn alerts(&self) -> usize {
+        self.alerts
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(log: &EventLog) -> Vec<(&str, usize)> {
+        log.events()
+            .map(|event| (event.message.as_str(), event.repeats))
+            .collect()
+    }
+
+    #[test]
+    fn test_push() {
+        let mut log = EventLog::new(10);
+        assert!(log.latest().is_none());
+        log.push(EventLevel::Warn, "first");
+        log.push(EventLevel::Error, "second");
+        assert_eq!(vec![("first", 0), ("second", 0)], messages(&log));
+        assert_eq!(EventLevel::Error, log.latest().unwrap().level);
+    }
+
+    #[test]
+    fn test_repeats_are_counted() {
+        let mut log = EventLog::new(10);
+        log.push(EventLevel::Warn, "timeout");
+        log.push(EventLevel::Warn, "timeout");
+        log.push(EventLevel::Warn, "timeout");
+        log.push(EventLevel::Error, "timeout");
+        log.push(EventLevel::Warn, "other");
+        log.push(EventLevel::Warn, "timeout");
+        assert_eq!(
+            vec![("timeout", 2), ("timeout", 0), ("other", 0), ("timeout", 0)],
+            messages(&log)
+        );
+    }
+
+    #[test]
+    fn test_bounded() {
+        let mut log = EventLog::new(2);
+        log.push(EventLevel::Warn, "a");
+        log.push(EventLevel::Warn, "b");
+        log.push(EventLevel::Warn, "c");
+        assert_eq!(vec![("b", 0), ("c", 0)], messages(&log));
+        assert_eq!(2, log.len());
+        let mut log = EventLog::new(0);
+        log.push(EventLevel::Warn, "a");
+        assert!(log.is_empty());
+    }
+
+    #[test]
+    fn test_alerts_are_counted() {
+        let mut log = EventLog::new(1);
+        log.push(EventLevel::Alert, "unreachable");
+        log.push(EventLevel::Alert, "unreachable");
+        log.push(EventLevel::Warn, "timeout");
+        log.push(EventLevel::Alert, "reachable");
+        assert_eq!(3, log.alerts());
+        assert_eq!(1, log.len());
+    }
+}
@@ -1,26 +1,72 @@
-use crate::backend::Hop;
-use crate::config::{AddressMode, DnsResolveMethod};
+use crate::alert::Breach;
+use crate::backend::{Endpoint, Flow, Hop};
+use crate::config::{
+    AddressMode, Columns, DnsResolveMethod, TuiClipboard, TuiColumn, TuiColumns, TuiTheme,
+    TuiThemeItem,
+};
 use crate::dns::{DnsEntry, Resolved};
+use crate::event::{Event as TraceEvent, EventLevel, SharedEventLog};
+pub use crate::frontend::binding::KeyBindings;
+use crate::frontend::binding::TuiCommand;
+use crate::frontend::chart::{y_labels, ChartPoints};
+use crate::frontend::clipboard::Clipboard;
+use crate::frontend::collapse::{hop_rows, HopRow};
+use crate::frontend::columns::{allocate_widths, format_age, header, COLUMN_SPACING};
+pub use crate::frontend::compact::CompactThresholds;
+use crate::frontend::compact::{compact_columns, compact_header, Compact};
+use crate::frontend::compare::{Compared, ComparisonPoints, Toggled};
+use crate::frontend::header::{fit, Field};
+use crate::frontend::heatmap::{heatmap, CELL};
+use crate::frontend::histogram::{Histogram, DEFAULT_BINS, MAX_BINS, MIN_BINS};
+use crate::frontend::hosts::{format_host, ranked_addrs, share_pct, with_hidden};
+use crate::frontend::map::{spread, Pan, Viewport};
+use crate::frontend::pinned::{section_height, Pins, TABLE_CHROME};
+use crate::frontend::rdap::{Lookup, Rdap};
+use crate::frontend::selection::HopSelection;
+pub use crate::frontend::session::Session;
+use crate::frontend::session::{value_name, SessionState};
+use crate::frontend::settings::{faster, slower, RefreshRate, Setting};
+use crate::frontend::sort::HopSort;
+use crate::frontend::sparkline::{sparkline, Glyph};
+use crate::frontend::startup::{spinner, SPINNER_FRAME};
+pub use crate::frontend::startup::{SharedStartupStatus, Startup, StartupStatus};
+use crate::frontend::summary::{Reachability, TraceSummary};
+use crate::frontend::target::{InputOutcome, TargetAction, TargetInput};
+pub use crate::frontend::theme::Theme;
+pub use crate::frontend::threshold::Thresholds;
+use crate::frontend::threshold::{severity_style, Severity};
+use crate::geoip::GeoIpDb;
+use crate::report::ExportFormat;
+use crate::segment::{segments, Segment};
+use crate::zone;
 use crate::{DnsResolver, Trace, TraceInfo};
 use chrono::SecondsFormat;
-use crossterm::event::KeyModifiers;
+use clap::ValueEnum;
 use crossterm::{
     event::{self, Event, KeyCode},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use itertools::Itertools;
+use std::cmp::Reverse;
 use std::collections::BTreeMap;
 use std::io;
+use std::io::Write;
 use std::net::IpAddr;
-use std::time::{Duration, SystemTime};
-use trippy::tracing::{PortDirection, TracerProtocol};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::debug_span;
+use trippy_core::{IcmpQuote, PortDirection, TimeToLive, TracerProtocol};
 use tui::layout::{Alignment, Direction, Rect};
 use tui::symbols::Marker;
-use tui::text::{Span, Spans};
+use tui::text::{Span, Spans, Text};
+use tui::widgets::canvas::{Canvas, Line, Map, MapResolution, Points};
 use tui::widgets::{
     Axis, BarChart, BorderType, Chart, Clear, Dataset, GraphType, Paragraph, Sparkline, TableState,
-    Tabs,
+    Tabs, Wrap,
 };
 use tui::{
     backend::{Backend, CrosstermBackend},
@@ -30,58 +76,86 @@ use tui::{
     Frame, Terminal,
 };
 
-const TABLE_HEADER: [&str; 11] = [
-    "#", "Host", "Loss%", "Snt", "Recv", "Last", "Avg", "Best", "Wrst", "StDev", "Sts",
-];
-
-const TABLE_WIDTH: [Constraint; 11] = [
-    Constraint::Percentage(3),
-    Constraint::Percentage(42),
-    Constraint::Percentage(5),
-    Constraint::Percentage(5),
-    Constraint::Percentage(5),
-    Constraint::Percentage(5),
-    Constraint::Percentage(5),
-    Constraint::Percentage(5),
-    Constraint::Percentage(5),
-    Constraint::Percentage(5),
-    Constraint::Percentage(5),
-];
-
-const LAYOUT_WITHOUT_TABS: [Constraint; 3] = [
+mod binding;
+mod chart;
+mod clipboard;
+mod collapse;
+mod columns;
+mod compact;
+mod compare;
+mod export;
+mod header;
+mod heatmap;
+mod histogram;
+mod hosts;
+mod map;
+mod pinned;
+mod rdap;
+mod selection;
+mod session;
+mod settings;
+mod sort;
+mod sparkline;
+mod startup;
+mod summary;
+mod target;
+mod theme;
+mod threshold;
+
+const LAYOUT_WITHOUT_TABS: [Constraint; 4] = [
     Constraint::Length(5),
     Constraint::Min(10),
     Constraint::Length(6),
+    Constraint::Length(1),
 ];
 
-const LAYOUT_WITH_TABS: [Constraint; 4] = [
+const LAYOUT_WITH_TABS: [Constraint; 5] = [
     Constraint::Length(5),
     Constraint::Length(3),
     Constraint::Min(10),
     Constraint::Length(6),
+    Constraint::Length(1),
 ];
 
-const MAX_ZOOM_FACTOR: usize = 16;
+const COMPACT_LAYOUT_WITHOUT_TABS: [Constraint; 2] = [Constraint::Min(0), Constraint::Length(1)];
 
-const HELP_LINES: [&str; 16] = [
-    "[up] & [down]    - select hop",
-    "[left] & [right] - select trace",
-    "[esc]            - clear selection",
-    "c                - toggle chart",
-    "f                - toggle freeze display",
-    "Ctrl+r           - reset statistics",
-    "Ctrl+k           - flush DNS cache",
-    "i                - show IP only",
-    "n                - show hostname only",
-    "b                - show both IP and hostname",
-    "[ & ]            - expand & collapse hosts",
-    "{ & }            - expand & collapse hosts to max and min",
-    "+ & -            - zoom chart in and out",
-    "z                - toggle AS information (if available)",
-    "h                - toggle help",
-    "q                - quit",
+const COMPACT_LAYOUT_WITH_TABS: [Constraint; 3] = [
+    Constraint::Length(3),
+    Constraint::Min(0),
+    Constraint::Length(1),
 ];
 
+const MAX_ZOOM_FACTOR: usize = 16;
+
+/// The width reserved for the labels of the y axis of the chart.
+const CHART_Y_LABEL_WIDTH: u16 = 8;
+
+/// The number of lines the help dialog is scrolled by a page up or page down.
+const HELP_PAGE_LINES: usize = 10;
+
+/// The width of the labels of the header lines, i.e. `Config: `.
+const HEADER_LABEL_WIDTH: usize = 8;
+
+/// The width of the list of hops without a location beside the world map.
+const MAP_GUTTER_WIDTH: u16 = 30;
+
+/// The caveat of the segment column, shown below the hop table when the column is shown.
+const SEGMENT_NOTE: &str =
+    "Seg: avg less that of the previous responding hop, an estimate only as return paths \
+                            differ, ≈0* where negative";
+
+/// How long a note is shown on the status bar.
+const STATUS_NOTE_DURATION: Duration = Duration::from_secs(3);
+
+/// How long the header flashes after an alert.
+const ALERT_FLASH_DURATION: Duration = Duration::from_secs(3);
+
+/// How long a value which could not be copied to the clipboard is shown on the status bar, to be selected manually.
+const CLIPBOARD_FALLBACK_DURATION: Duration = Duration::from_secs(30);
+
+/// The width of the help hint on the right of the second line of the header, and the space before it.
+const HEADER_HELP_WIDTH: usize = 10;
+
 /// Tui configuration.
 #[derive(Debug)]
 pub struct TuiConfig {
@@ -97,9 +171,44 @@ pub struct TuiConfig {
     max_addrs: Option<u8>,
     /// The maximum number of samples to record per hop.
     max_samples: usize,
+    /// The columns of the hop table.
+    columns: TuiColumns,
+    /// The colors of the TUI.
+    theme: Theme,
+    /// The built-in theme of the TUI.
+    theme_name: TuiTheme,
+    /// The colors which override those of the built-in theme.
+    theme_colors: Vec<(TuiThemeItem, Color)>,
+    /// The thresholds at which latency and packet loss are highlighted.
+    thresholds: Thresholds,
+    /// The key bindings of the TUI.
+    key_bindings: KeyBindings,
+    /// Collapse runs of hops which have not responded into a single row.
+    hide_unresponsive: bool,
+    /// How to copy to the clipboard.
+    clipboard: TuiClipboard,
+    /// The directory to which the trace is exported.
+    export_dir: PathBuf,
+    /// The columns of an exported table.
+    export_columns: Columns,
+    /// Ring the terminal bell on an alert.
+    bell: bool,
+    /// Flash the header on an alert.
+    notify: bool,
+    /// Color the heatmap by the latency of each reply, rather than by response alone.
+    heatmap_latency: bool,
+    /// Show the historic hops beyond the current path, which were of a longer path to the target.
+    historic_hops: bool,
+    /// The locations of hops shown on the world map.
+    geoip: Option<GeoIpDb>,
+    /// The size of the terminal below which the compact layout is used.
+    compact_thresholds: CompactThresholds,
+    /// Look up the registration of hop addresses with RDAP.
+    rdap: bool,
 }
 
 impl TuiConfig {
+    #[allow(clippy::too_many_arguments, clippy::fn_params_excessive_bools)]
     pub fn new(
         refresh_rate: Duration,
         preserve_screen: bool,
@@ -107,6 +216,20 @@ impl TuiConfig {
         lookup_as_info: bool,
         max_addrs: Option<u8>,
         max_samples: usize,
+        columns: TuiColumns,
+        theme_name: TuiTheme,
+        theme_colors: Vec<(TuiThemeItem, Color)>,
+        thresholds: Thresholds,
+        key_bindings: KeyBindings,
+        hide_unresponsive: bool,
+        clipboard: TuiClipboard,
+        export_dir: PathBuf,
+        export_columns: Columns,
+        bell: bool,
+        notify: bool,
+        geoip: Option<GeoIpDb>,
+        compact_thresholds: CompactThresholds,
+        rdap: bool,
     ) -> Self {
         Self {
             refresh_rate,
@@ -115,120 +238,632 @@ impl TuiConfig {
             lookup_as_info,
             max_addrs,
             max_samples,
+            columns,
+            theme: Theme::new(theme_name, &theme_colors),
+            theme_name,
+            theme_colors,
+            thresholds,
+            key_bindings,
+            hide_unresponsive,
+            clipboard,
+            export_dir,
+            export_columns,
+            bell,
+            notify,
+            heatmap_latency: false,
+            historic_hops: false,
+            geoip,
+            compact_thresholds,
+            rdap,
+        }
+    }
+
+    /// Restore the configuration saved in a previous session.
+    fn restore(&mut self, state: &SessionState) {
+        if let Some(columns) = state.columns() {
+            self.columns = columns;
+        }
+        if let Some(theme_name) = state.theme() {
+            self.theme_name = theme_name;
+            self.theme = Theme::new(theme_name, &self.theme_colors);
+        }
+        if let Some(address_mode) = state.address_mode() {
+            self.address_mode = address_mode;
+        }
+        if let Some(lookup_as_info) = state.lookup_as_info {
+            self.lookup_as_info = lookup_as_info;
+        }
+        if let Some(max_addrs) = state.max_addrs {
+            self.max_addrs = (max_addrs > 0).then_some(max_addrs);
+        }
+        if let Some(hide_unresponsive) = state.hide_unresponsive {
+            self.hide_unresponsive = hide_unresponsive;
+        }
+        if let Some(refresh_rate) = state.refresh_rate() {
+            self.refresh_rate = refresh_rate;
+        }
+        if let Some(heatmap_latency) = state.heatmap_latency {
+            self.heatmap_latency = heatmap_latency;
         }
     }
 }
 
+/// Resolves a target and starts tracing it, returning the information of the new trace.
+///
+/// The launcher blocks whilst the target is resolved and so is only called off the TUI thread.
+pub type TraceLauncher = Arc<dyn Fn(&str) -> anyhow::Result<TraceInfo> + Send + Sync>;
+
+/// The state of the view of a trace which is kept whilst another trace is shown.
+#[derive(Debug, Default)]
+struct TabState {
+    selection: HopSelection,
+    flow_selected: usize,
+    show_chart: bool,
+    show_histogram: bool,
+    show_compare: bool,
+    compared: Compared,
+    show_detail: bool,
+    pins: Pins,
+}
+
 struct TuiApp {
-    selected_tracer_data: Trace,
+    selected_tracer_data: Arc<Trace>,
     trace_info: Vec<TraceInfo>,
+    /// The saved view of each trace other than the selected trace.
+    tabs: Vec<TabState>,
+    /// The summary of each trace, as of when its data could last be read without waiting.
+    summaries: Vec<TraceSummary>,
+    /// Is the summary of all traces shown in place of the selected trace?
+    show_summary: bool,
+    launcher: TraceLauncher,
+    /// The dialog in which a target to trace is entered, if shown.
+    target_input: Option<TargetInput>,
+    /// The trace of the target submitted in the dialog, once its tracer has been started.
+    launched: Option<(TargetAction, Receiver<anyhow::Result<TraceInfo>>)>,
     tui_config: TuiConfig,
-    table_state: TableState,
+    selection: HopSelection,
+    /// The hops of the selected trace pinned to the top of the hop table.
+    pins: Pins,
+    /// The column by which the hops other than the pinned hops are sorted, if not in ttl order.
+    sort: Option<HopSort>,
+    /// The compact layout for the size of the terminal when last drawn.
+    compact: Compact,
+    /// Is the compact layout forced on or off regardless of the size of the terminal?
+    compact_forced: Option<bool>,
+    /// The clipboards to which addresses are copied.
+    clipboard: Clipboard,
+    /// The RDAP lookups of the session.
+    rdap: Rdap,
+    /// The address whose RDAP registration is shown, and when it was first shown, if any.
+    rdap_shown: Option<(IpAddr, Instant)>,
+    /// Is the user asked whether to unpin the pinned hops after the statistics were reset?
+    confirm_unpin: bool,
     trace_selected: usize,
+    /// The selected flow of the selected trace, from 1, or 0 for all flows.
+    flow_selected: usize,
     resolver: DnsResolver,
+    events: SharedEventLog,
     show_help: bool,
+    help_scroll: usize,
+    show_events: bool,
+    events_scroll: usize,
+    show_column_settings: bool,
+    column_cursor: usize,
+    show_settings: bool,
+    settings_cursor: usize,
+    show_export: bool,
+    export_cursor: usize,
+    /// A transient note shown on the status bar, and when it expires.
+    status_note: Option<(String, Instant)>,
+    /// The number of alerts recorded in the events when they were last checked.
+    alerts_seen: usize,
+    /// When the header stops flashing, if it is flashing.
+    flash_until: Option<Instant>,
     show_chart: bool,
+    show_histogram: bool,
+    /// Is the chart of the selected hop compared with other hops shown in place of the RTT chart?
+    show_compare: bool,
+    /// The hops compared with the selected hop.
+    compared: Compared,
+    histogram_bins: usize,
+    show_detail: bool,
+    show_map: bool,
+    map_viewport: Viewport,
     frozen_start: Option<SystemTime>,
     zoom_factor: usize,
 }
 
 impl TuiApp {
-    fn new(tui_config: TuiConfig, resolver: DnsResolver, trace_info: Vec<TraceInfo>) -> Self {
+    fn new(
+        tui_config: TuiConfig,
+        resolver: DnsResolver,
+        events: SharedEventLog,
+        trace_info: Vec<TraceInfo>,
+        launcher: TraceLauncher,
+    ) -> Self {
         Self {
-            selected_tracer_data: Trace::new(tui_config.max_samples),
+            selected_tracer_data: Arc::new(Trace::new(tui_config.max_samples)),
+            tabs: trace_info.iter().map(|_| TabState::default()).collect(),
+            summaries: vec![TraceSummary::default(); trace_info.len()],
+            show_summary: false,
+            launcher,
+            target_input: None,
+            launched: None,
             trace_info,
             tui_config,
-            table_state: TableState::default(),
+            selection: HopSelection::default(),
+            pins: Pins::default(),
+            sort: None,
+            compact: Compact::default(),
+            compact_forced: None,
+            clipboard: Clipboard::default(),
+            rdap: Rdap::default(),
+            rdap_shown: None,
+            confirm_unpin: false,
             trace_selected: 0,
+            flow_selected: 0,
             resolver,
+            events,
             show_help: false,
+            show_events: false,
+            events_scroll: 0,
+            show_column_settings: false,
+            column_cursor: 0,
+            show_settings: false,
+            settings_cursor: 0,
+            show_export: false,
+            export_cursor: 0,
+            status_note: None,
+            alerts_seen: 0,
+            flash_until: None,
+            help_scroll: 0,
             show_chart: false,
+            show_histogram: false,
+            show_compare: false,
+            compared: Compared::default(),
+            histogram_bins: DEFAULT_BINS,
+            show_detail: false,
+            show_map: false,
+            map_viewport: Viewport::default(),
             frozen_start: None,
             zoom_factor: 1,
         }
     }
 
+    /// The data of the selected flow of the selected trace, or of all of its flows if no flow is selected.
+    ///
+    /// The flows of a trace whose target addresses are probed round robin are those of each address.
     fn tracer_data(&self) -> &Trace {
-        &self.selected_tracer_data
+        match (self.selected_endpoint(), self.selected_flow()) {
+            (Some(endpoint), _) => endpoint.trace(),
+            (None, Some(flow)) => flow.trace(),
+            (None, None) => &self.selected_tracer_data,
+        }
+    }
+
+    /// The hops of the hop table, which are those of the current path and, if shown, the historic hops beyond it.
+    fn hops(&self) -> &[Hop] {
+        if self.tui_config.historic_hops {
+            self.tracer_data().all_hops()
+        } else {
+            self.tracer_data().hops()
+        }
+    }
+
+    /// The selected flow, if any, unless the target addresses of the trace are probed round robin.
+    fn selected_flow(&self) -> Option<&Flow> {
+        if !self.selected_tracer_data.endpoints().is_empty() {
+            return None;
+        }
+        self.flow_selected
+            .checked_sub(1)
+            .and_then(|index| self.selected_tracer_data.flows().get(index))
+    }
+
+    /// The selected target address, if any, if the target addresses of the trace are probed round robin.
+    fn selected_endpoint(&self) -> Option<&Endpoint> {
+        self.flow_selected
+            .checked_sub(1)
+            .and_then(|index| self.selected_tracer_data.endpoints().get(index))
+    }
+
+    /// The number of flows of the selected trace, or of target addresses if they are probed round robin.
+    fn flow_count(&self) -> usize {
+        match self.selected_tracer_data.endpoints().len() {
+            0 => self.selected_tracer_data.flows().len(),
+            endpoints => endpoints,
+        }
+    }
+
+    /// Select the next flow, or all flows after the last flow, keeping the same hop selected if possible.
+    fn next_flow(&mut self) {
+        let selected = self.selected_ttl();
+        self.flow_selected = (self.flow_selected + 1) % (self.flow_count() + 1);
+        self.select_ttl(selected);
     }
 
+    /// Select the previous flow, or all flows before the first flow, keeping the same hop selected if possible.
+    fn previous_flow(&mut self) {
+        let selected = self.selected_ttl();
+        let flows = self.flow_count() + 1;
+        self.flow_selected = (self.flow_selected + flows - 1) % flows;
+        self.select_ttl(selected);
+    }
+
+    /// Snapshot the data of the selected trace, keeping the same hop selected even if the rows of the table change.
+    ///
+    /// Only the selected trace is waited for, the summaries of the other traces are updated only if they can be read
+    /// without waiting.
     fn snapshot_trace_data(&mut self) {
-        self.selected_tracer_data = self.trace_info[self.trace_selected].data.read().clone();
+        let selected = self.selected_ttl();
+        self.selected_tracer_data = self.trace_info[self.trace_selected].data.snapshot();
+        self.select_ttl(selected);
+        for (index, info) in self.trace_info.iter().enumerate() {
+            if index == self.trace_selected {
+                self.summaries[index] =
+                    TraceSummary::new(&self.selected_tracer_data, info.target_addr);
+            } else if let Some(trace) = info.data.try_snapshot() {
+                self.summaries[index] = TraceSummary::new(&trace, info.target_addr);
+            }
+        }
+    }
+
+    /// Reset the statistics of the selected trace, asking whether to also unpin its pinned hops, if any.
+    fn reset_statistics(&mut self) {
+        self.clear();
+        self.clear_trace_data();
+        self.confirm_unpin = !self.pins.is_empty();
+    }
+
+    /// Unpin the pinned hops if the user confirms with `y`, any other key keeps them pinned.
+    fn confirm_unpin_key(&mut self, key: KeyCode) {
+        self.confirm_unpin = false;
+        if matches!(key, KeyCode::Char('y' | 'Y')) {
+            let selected = self.selected_ttl();
+            self.pins.clear();
+            self.select_ttl(selected);
+            self.note(String::from("unpinned all hops"));
+        }
     }
 
     fn clear_trace_data(&mut self) {
-        *self.trace_info[self.trace_selected].data.write() =
-            Trace::new(self.tui_config.max_samples);
+        self.trace_info[self.trace_selected]
+            .data
+            .replace(Trace::new(self.tui_config.max_samples));
     }
 
     fn tracer_config(&self) -> &TraceInfo {
         &self.trace_info[self.trace_selected]
     }
 
-    fn clamp_selected_hop(&mut self) {
-        let hop_count = self.tracer_data().hops().len();
-        if let Some(selected) = self.table_state.selected() {
-            if selected > hop_count - 1 {
-                self.table_state.select(Some(hop_count - 1));
-            }
-        }
+    /// The first and max ttl probed by the selected trace, which are those configured unless changed whilst it runs.
+    fn ttl_range(&self) -> (u8, u8) {
+        let info = self.tracer_config();
+        info.parameters
+            .as_ref()
+            .map_or((info.first_ttl, info.max_ttl), |parameters| {
+                let (first_ttl, max_ttl) = parameters.ttl_range();
+                (first_ttl.0, max_ttl.0)
+            })
     }
 
-    fn next_hop(&mut self) {
-        let hop_count = self.tracer_data().hops().len();
-        if hop_count == 0 {
-            return;
-        }
-        let max_index = 0.max(hop_count.saturating_sub(1));
-        let i = match self.table_state.selected() {
-            Some(i) => {
-                if i < max_index {
-                    i + 1
-                } else {
-                    i
+    /// Narrow or widen the range of ttl probed by the selected trace, from its next round, by moving the first and
+    /// max ttl by `first` and `max`.
+    ///
+    /// The hops outside the range keep their statistics, which are no longer updated until it is widened again.
+    fn shift_ttl_range(&mut self, first: i8, max: i8) {
+        let (first_ttl, max_ttl) = self.ttl_range();
+        self.set_ttl_range(
+            first_ttl.saturating_add_signed(first),
+            max_ttl.saturating_add_signed(max),
+        );
+    }
+
+    /// Probe the range of ttl configured for the selected trace from its next round.
+    fn reset_ttl_range(&mut self) {
+        let info = self.tracer_config();
+        self.set_ttl_range(info.first_ttl, info.max_ttl);
+    }
+
+    fn set_ttl_range(&mut self, first_ttl: u8, max_ttl: u8) {
+        let info = self.tracer_config();
+        let note = match &info.parameters {
+            _ if info.fixed_ttl => String::from("the ttl of this trace is fixed"),
+            None => String::from("the ttl range of this trace cannot be changed"),
+            Some(parameters) => {
+                match parameters.set_ttl_range(TimeToLive(first_ttl), TimeToLive(max_ttl)) {
+                    Ok(()) => format!("probing ttl {first_ttl} to {max_ttl} from the next round"),
+                    Err(err) => format!("ttl range unchanged: {err}"),
                 }
             }
-            None => 0,
         };
-        self.table_state.select(Some(i));
+        self.note(note);
     }
 
-    fn previous_hop(&mut self) {
-        let hop_count = self.tracer_data().hops().len();
-        if hop_count == 0 {
-            return;
+    /// The threshold by which the path to the target of the selected trace is degraded, if it is.
+    fn degraded(&self) -> Option<Breach> {
+        self.tracer_config()
+            .health
+            .read()
+            .degraded()
+            .map(|(breach, _)| breach)
+    }
+
+    /// The rows of the hop table, those of the pinned hops first and then the others in the order of the sort.
+    fn rows(&self) -> Vec<HopRow> {
+        let hops = self.hops();
+        let mut rows = hop_rows(hops, self.tui_config.hide_unresponsive, &self.pins);
+        if let Some(sort) = self.sort {
+            sort.sort(&mut rows[self.pinned_count()..], hops);
         }
-        let i = match self.table_state.selected() {
-            Some(i) => {
-                if i > 0 {
-                    i - 1
-                } else {
-                    i
-                }
+        rows
+    }
+
+    /// The number of rows of the hop table which are of pinned hops.
+    fn pinned_count(&self) -> usize {
+        self.hops()
+            .iter()
+            .filter(|hop| self.pins.contains(hop.ttl()))
+            .count()
+    }
+
+    /// The number of rows of the hop table, which are what is selected.
+    fn hop_count(&self) -> usize {
+        self.rows().len()
+    }
+
+    /// The selected hop, or the target hop if no hop is selected.
+    ///
+    /// If the selected row is a run of unresponsive hops then the first hop of the run is selected.
+    fn selected_hop(&self) -> &Hop {
+        self.selection
+            .selected()
+            .and_then(|selected| self.rows().get(selected).map(HopRow::first))
+            .map_or_else(
+                || self.tracer_data().target_hop(),
+                |index| &self.hops()[index],
+            )
+    }
+
+    /// The ttl of the selected hop, if a hop is selected.
+    fn selected_ttl(&self) -> Option<u8> {
+        self.selection.selected().map(|_| self.selected_hop().ttl())
+    }
+
+    /// Select the row which shows the hop with `ttl`, if any.
+    fn select_ttl(&mut self, ttl: Option<u8>) {
+        let hops = self.hops();
+        if let Some(index) = ttl.and_then(|ttl| hops.iter().position(|hop| hop.ttl() == ttl)) {
+            let rows = self.rows();
+            if let Some(row) = rows.iter().position(|row| row.contains(index)) {
+                self.selection.select(row, rows.len());
             }
-            None => 0.max(hop_count.saturating_sub(1)),
+        }
+    }
+
+    /// Pin the selected hop to the top of the hop table, or return it to its place if it is pinned, keeping it selected.
+    fn toggle_pin(&mut self) {
+        let Some(ttl) = self.selected_ttl() else {
+            self.note(String::from("select a hop to pin"));
+            return;
         };
-        self.table_state.select(Some(i));
+        let pinned = self.pins.toggle(ttl);
+        self.select_ttl(Some(ttl));
+        if pinned {
+            self.note(format!("pinned hop {ttl}"));
+        } else {
+            self.note(format!("unpinned hop {ttl}"));
+        }
+    }
+
+    /// Restore the view of the selected trace saved in a previous session.
+    fn restore(&mut self, state: &SessionState) {
+        self.show_chart = state.show_chart.unwrap_or(self.show_chart);
+        self.show_histogram = state.show_histogram.unwrap_or(self.show_histogram);
+        self.show_map = state.show_map.unwrap_or(self.show_map);
+    }
+
+    /// The state to save for the next session, that of the configuration and of the view of the selected trace.
+    fn session_state(&self) -> SessionState {
+        let config = &self.tui_config;
+        SessionState {
+            columns: Some(
+                config
+                    .columns
+                    .0
+                    .iter()
+                    .map(|column| column.to_char())
+                    .collect(),
+            ),
+            theme: value_name(&config.theme_name),
+            address_mode: value_name(&config.address_mode),
+            lookup_as_info: Some(config.lookup_as_info),
+            max_addrs: Some(config.max_addrs.unwrap_or_default()),
+            hide_unresponsive: Some(config.hide_unresponsive),
+            refresh_rate_ms: u64::try_from(config.refresh_rate.as_millis()).ok(),
+            heatmap_latency: Some(config.heatmap_latency),
+            show_chart: Some(self.show_chart),
+            show_histogram: Some(self.show_histogram),
+            show_map: Some(self.show_map),
+            ..SessionState::new()
+        }
+    }
+
+    fn toggle_unresponsive(&mut self) {
+        let selected = self.selected_ttl();
+        self.tui_config.hide_unresponsive = !self.tui_config.hide_unresponsive;
+        self.select_ttl(selected);
+    }
+
+    fn clamp_selected_hop(&mut self) {
+        self.selection.clamp(self.hop_count());
+    }
+
+    fn next_hop(&mut self) {
+        self.selection.next(self.hop_count());
+    }
+
+    fn previous_hop(&mut self) {
+        self.selection.previous(self.hop_count());
+    }
+
+    fn next_hop_page(&mut self) {
+        self.selection.page_down(self.hop_count());
     }
 
+    fn previous_hop_page(&mut self) {
+        self.selection.page_up(self.hop_count());
+    }
+
+    fn first_hop(&mut self) {
+        self.selection.first(self.hop_count());
+    }
+
+    fn last_hop(&mut self) {
+        self.selection.last(self.hop_count());
+    }
+
+    fn toggle_follow(&mut self) {
+        self.selection.toggle_follow();
+    }
+
+    /// Select the next trace, or the summary after the last trace.
     fn next_trace(&mut self) {
         if self.trace_selected < self.trace_info.len() - 1 {
-            self.trace_selected += 1;
+            self.select_trace(self.trace_selected + 1);
+        } else if self.trace_info.len() > 1 {
+            self.show_summary = true;
         }
     }
 
+    /// Select the previous trace, or the last trace from the summary.
     fn previous_trace(&mut self) {
-        if self.trace_selected > 0 {
-            self.trace_selected -= 1;
+        if self.show_summary {
+            self.show_summary = false;
+        } else if self.trace_selected > 0 {
+            self.select_trace(self.trace_selected - 1);
+        }
+    }
+
+    /// Select the trace at `index`, saving the view of the selected trace and restoring that of the trace at `index`.
+    fn select_trace(&mut self, index: usize) {
+        self.show_summary = false;
+        if index == self.trace_selected || index >= self.trace_info.len() {
+            return;
+        }
+        self.tabs[self.trace_selected] = TabState {
+            selection: std::mem::take(&mut self.selection),
+            flow_selected: self.flow_selected,
+            show_chart: self.show_chart,
+            show_histogram: self.show_histogram,
+            show_compare: self.show_compare,
+            compared: std::mem::take(&mut self.compared),
+            show_detail: self.show_detail,
+            pins: std::mem::take(&mut self.pins),
+        };
+        let tab = std::mem::take(&mut self.tabs[index]);
+        self.selection = tab.selection;
+        self.flow_selected = tab.flow_selected;
+        self.show_chart = tab.show_chart;
+        self.show_histogram = tab.show_histogram;
+        self.show_compare = tab.show_compare;
+        self.compared = tab.compared;
+        self.show_detail = tab.show_detail;
+        self.pins = tab.pins;
+        self.confirm_unpin = false;
+        self.trace_selected = index;
+        self.selected_tracer_data = self.trace_info[index].data.snapshot();
+        self.clamp_selected_hop();
+    }
+
+    /// Select the trace of a number key, from `1`, or the summary with `0`, if there is more than one trace.
+    fn select_tab(&mut self, key: &event::KeyEvent) {
+        if self.trace_info.len() < 2 || !key.modifiers.is_empty() {
+            return;
+        }
+        match key.code {
+            KeyCode::Char('0') => self.show_summary = true,
+            KeyCode::Char(c @ '1'..='9') => self.select_trace(c as usize - '1' as usize),
+            _ => {}
+        }
+    }
+
+    /// Sort the hops by the column of a function key, from `F1`, or reverse the sort if already sorted by it, keeping
+    /// the same hop selected.
+    fn sort_key(&mut self, key: &event::KeyEvent) {
+        let KeyCode::F(number @ 1..=9) = key.code else {
+            return;
+        };
+        let Some(column) = self
+            .tui_config
+            .columns
+            .0
+            .get(usize::from(number) - 1)
+            .copied()
+        else {
+            return;
         };
+        let selected = self.selected_ttl();
+        match HopSort::toggle(self.sort, column) {
+            Some(sort) => {
+                self.sort = Some(sort);
+                self.note(format!("sorted by {} {}", header(column), sort.indicator()));
+            }
+            None => self.note(format!("cannot sort by {}", header(column))),
+        }
+        self.select_ttl(selected);
+    }
+
+    /// Restore the ttl order of the hops, keeping the same hop selected.
+    fn reset_sort(&mut self) {
+        if self.sort.take().is_some() {
+            let selected = self.selected_ttl();
+            self.select_ttl(selected);
+            self.note(String::from("sorted by ttl"));
+        }
+    }
+
+    /// The layout in use, compact for the size of the terminal unless forced on or off.
+    fn layout(&self) -> Compact {
+        self.compact.forced(self.compact_forced)
+    }
+
+    fn toggle_compact(&mut self) {
+        self.compact_forced = self.compact.toggle(self.compact_forced);
+        self.note(String::from(if self.layout().is_compact() {
+            "compact layout"
+        } else {
+            "full layout"
+        }));
     }
 
     fn clear(&mut self) {
-        self.table_state.select(None);
+        self.selection.clear();
+    }
+
+    fn toggle_detail(&mut self) {
+        self.show_detail = !self.show_detail;
     }
 
     fn toggle_help(&mut self) {
         self.show_help = !self.show_help;
+        self.help_scroll = 0;
+    }
+
+    /// Scroll the help dialog, the scroll offset is clamped to the length of the help when rendered.
+    fn scroll_help(&mut self, key: KeyCode) {
+        self.help_scroll = scroll_lines(self.help_scroll, key);
+    }
+
+    /// Show or hide the event history, which is scrolled to the most recent event when shown.
+    fn toggle_events(&mut self) {
+        self.show_events = !self.show_events;
+        self.events_scroll = usize::MAX;
+    }
+
+    /// Scroll the event history, the scroll offset is clamped to the number of events when rendered.
+    fn scroll_events(&mut self, key: KeyCode) {
+        self.events_scroll = scroll_lines(self.events_scroll, key);
     }
 
     fn toggle_freeze(&mut self) {
@@ -242,137 +877,657 @@ impl TuiApp {
         self.show_chart = !self.show_chart;
     }
 
+    /// Toggle between the RTT chart and the RTT histogram, showing the histogram if neither is shown.
+    fn toggle_histogram(&mut self) {
+        self.show_histogram = !self.show_chart || !self.show_histogram;
+        self.show_compare = false;
+        self.show_chart = true;
+    }
+
+    /// Toggle between the RTT chart and the hop comparison chart, showing the comparison if neither is shown.
+    fn toggle_compare(&mut self) {
+        self.show_compare = !self.show_chart || !self.show_compare;
+        self.show_histogram = false;
+        self.show_chart = true;
+    }
+
+    /// Add the selected hop to the hops compared on the comparison chart, or remove it.
+    fn compare_hop(&mut self) {
+        let Some(ttl) = self.selected_ttl() else {
+            self.note(String::from("select a hop to compare"));
+            return;
+        };
+        let note = match self.compared.toggle(ttl) {
+            Toggled::Added => format!("comparing hop {ttl}"),
+            Toggled::Removed => format!("no longer comparing hop {ttl}"),
+            Toggled::Evicted(evicted) => {
+                format!("comparing hop {ttl} in place of hop {evicted}")
+            }
+        };
+        self.note(note);
+    }
+
+    fn more_bins(&mut self) {
+        self.histogram_bins = (self.histogram_bins + 1).min(MAX_BINS);
+    }
+
+    fn fewer_bins(&mut self) {
+        self.histogram_bins = self.histogram_bins.saturating_sub(1).max(MIN_BINS);
+    }
+
+    fn toggle_map(&mut self) {
+        self.show_map = !self.show_map;
+    }
+
+    /// The command for a key which moves the world map, or the selected hop on it, if the map is shown.
+    ///
+    /// Whilst the map is shown the arrow keys pan the map and page up and page down select the next and previous hop.
+    fn map_command(&self, key: &event::KeyEvent) -> Option<TuiCommand> {
+        self.tui_config.key_bindings.command(key).filter(|command| {
+            self.show_map
+                && matches!(
+                    command,
+                    TuiCommand::NextHop
+                        | TuiCommand::PreviousHop
+                        | TuiCommand::NextTrace
+                        | TuiCommand::PreviousTrace
+                        | TuiCommand::NextHopPage
+                        | TuiCommand::PreviousHopPage
+                        | TuiCommand::ZoomIn
+                        | TuiCommand::ZoomOut
+                )
+        })
+    }
+
+    fn move_map(&mut self, command: TuiCommand) {
+        match command {
+            TuiCommand::PreviousHop => self.map_viewport.pan(Pan::Up),
+            TuiCommand::NextHop => self.map_viewport.pan(Pan::Down),
+            TuiCommand::PreviousTrace => self.map_viewport.pan(Pan::Left),
+            TuiCommand::NextTrace => self.map_viewport.pan(Pan::Right),
+            TuiCommand::NextHopPage => self.next_hop(),
+            TuiCommand::PreviousHopPage => self.previous_hop(),
+            TuiCommand::ZoomIn => self.map_viewport.zoom_in(),
+            TuiCommand::ZoomOut => self.map_viewport.zoom_out(),
+            _ => {}
+        }
+    }
+
     fn toggle_asinfo(&mut self) {
         self.tui_config.lookup_as_info = !self.tui_config.lookup_as_info;
     }
 
-    fn expand_hosts(&mut self) {
-        self.tui_config.max_addrs = match self.tui_config.max_addrs {
-            None => Some(1),
-            Some(i) if i < self.max_hosts() => Some(i + 1),
-            Some(i) => Some(i),
-        }
+    fn toggle_sparkline(&mut self) {
+        self.tui_config.columns.toggle(TuiColumn::Sparkline);
     }
 
-    fn contract_hosts(&mut self) {
-        self.tui_config.max_addrs = match self.tui_config.max_addrs {
-            Some(i) if i > 1 => Some(i - 1),
-            _ => None,
-        }
+    fn toggle_heatmap(&mut self) {
+        self.tui_config.columns.toggle(TuiColumn::Heatmap);
     }
 
-    fn zoom_in(&mut self) {
-        if self.zoom_factor < MAX_ZOOM_FACTOR {
-            self.zoom_factor += 1;
-        }
+    fn toggle_heatmap_latency(&mut self) {
+        self.tui_config.heatmap_latency = !self.tui_config.heatmap_latency;
     }
 
-    fn zoom_out(&mut self) {
-        if self.zoom_factor > 1 {
-            self.zoom_factor -= 1;
-        }
+    fn toggle_historic_hops(&mut self) {
+        let selected = self.selected_ttl();
+        self.tui_config.historic_hops = !self.tui_config.historic_hops;
+        self.select_ttl(selected);
+        self.clamp_selected_hop();
     }
 
-    fn expand_hosts_max(&mut self) {
-        self.tui_config.max_addrs = Some(self.max_hosts());
+    fn toggle_column_settings(&mut self) {
+        self.show_column_settings = !self.show_column_settings;
     }
 
-    fn contract_hosts_min(&mut self) {
-        self.tui_config.max_addrs = Some(1);
+    /// Move the cursor of the column settings, or show or hide the column under it.
+    fn column_settings_key(&mut self, key: KeyCode) {
+        match key {
+            KeyCode::Up => self.column_cursor = self.column_cursor.saturating_sub(1),
+            KeyCode::Down => {
+                self.column_cursor = (self.column_cursor + 1).min(TuiColumn::ALL.len() - 1);
+            }
+            KeyCode::Char(' ') | KeyCode::Enter => {
+                self.tui_config
+                    .columns
+                    .toggle(TuiColumn::ALL[self.column_cursor]);
+            }
+            _ => self.toggle_column_settings(),
+        }
     }
 
-    /// The maximum number of hosts per hop for the currently selected trace.
-    fn max_hosts(&self) -> u8 {
-        self.selected_tracer_data
-            .hops()
-            .iter()
-            .map(|h| h.addrs().count())
-            .max()
-            .and_then(|i| u8::try_from(i).ok())
-            .unwrap_or_default()
+    fn toggle_settings(&mut self) {
+        self.show_settings = !self.show_settings;
     }
-}
 
-/// Run the frontend TUI.
-pub fn run_frontend(
-    traces: Vec<TraceInfo>,
-    tui_config: TuiConfig,
-    resolver: DnsResolver,
-) -> anyhow::Result<()> {
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
-    let preserve_screen = tui_config.preserve_screen;
-    let res = run_app(&mut terminal, traces, tui_config, resolver);
-    disable_raw_mode()?;
-    if !preserve_screen {
-        execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    /// Move the cursor of the settings, or change the setting under it.
+    fn settings_key(&mut self, key: KeyCode) {
+        let setting = Setting::ALL[self.settings_cursor];
+        match (key, setting) {
+            (KeyCode::Up, _) => self.settings_cursor = self.settings_cursor.saturating_sub(1),
+            (KeyCode::Down, _) => {
+                self.settings_cursor = (self.settings_cursor + 1).min(Setting::ALL.len() - 1);
+            }
+            (KeyCode::Left, Setting::RefreshRate) => self.faster_refresh(),
+            (KeyCode::Right, Setting::RefreshRate) => self.slower_refresh(),
+            (KeyCode::Left, Setting::AddressMode) => {
+                self.tui_config.address_mode = self.tui_config.address_mode.previous();
+            }
+            (KeyCode::Right, Setting::AddressMode) => {
+                self.tui_config.address_mode = self.tui_config.address_mode.next();
+            }
+            (KeyCode::Left, Setting::MaxAddrs) => self.contract_hosts(),
+            (KeyCode::Right, Setting::MaxAddrs) => self.expand_hosts(),
+            (KeyCode::Left | KeyCode::Right, Setting::Theme) => {
+                let config = &mut self.tui_config;
+                config.theme_name = config.theme_name.next();
+                config.theme = Theme::new(config.theme_name, &config.theme_colors);
+            }
+            (KeyCode::Enter | KeyCode::Right, Setting::Columns) => {
+                self.show_settings = false;
+                self.show_column_settings = true;
+            }
+            (KeyCode::Left | KeyCode::Enter, _) => {}
+            _ => self.toggle_settings(),
+        }
     }
-    terminal.show_cursor()?;
-    if let Err(err) = res {
-        println!("{err:?}");
+
+    fn toggle_export(&mut self) {
+        self.show_export = !self.show_export;
     }
-    Ok(())
-}
 
-fn run_app<B: Backend>(
-    terminal: &mut Terminal<B>,
-    trace_info: Vec<TraceInfo>,
-    tui_config: TuiConfig,
-    resolver: DnsResolver,
-) -> io::Result<()> {
-    let mut app = TuiApp::new(tui_config, resolver, trace_info);
-    loop {
-        if app.frozen_start.is_none() {
-            app.snapshot_trace_data();
-            app.clamp_selected_hop();
+    fn add_target(&mut self) {
+        self.target_input = Some(TargetInput::new(TargetAction::Add));
+    }
+
+    /// Edit the target in the dialog, or start tracing it on a thread of its own such that the TUI does not wait for
+    /// it to be resolved.
+    fn target_key(&mut self, key: KeyCode) {
+        let Some(input) = &mut self.target_input else {
+            return;
         };
-        terminal.draw(|f| render_app(f, &mut app))?;
-        if event::poll(app.tui_config.refresh_rate)? {
-            if let Event::Key(key) = event::read()? {
-                if app.show_help {
-                    match key.code {
-                        KeyCode::Char('q' | 'h') | KeyCode::Esc => app.toggle_help(),
-                        _ => {}
-                    }
-                } else {
-                    match (key.code, key.modifiers) {
-                        (KeyCode::Char('h'), _) => app.toggle_help(),
-                        (KeyCode::Char('q'), _) | (KeyCode::Char('c'), KeyModifiers::CONTROL) => {
-                            return Ok(())
-                        }
-                        (KeyCode::Char('f'), _) => app.toggle_freeze(),
-                        (KeyCode::Char('c'), _) => app.toggle_chart(),
-                        (KeyCode::Char('r'), KeyModifiers::CONTROL) => {
-                            app.clear();
-                            app.clear_trace_data();
-                        }
-                        (KeyCode::Char('k'), KeyModifiers::CONTROL) => {
-                            app.resolver.flush();
-                        }
-                        (KeyCode::Down, _) => app.next_hop(),
-                        (KeyCode::Up, _) => app.previous_hop(),
-                        (KeyCode::Esc, _) => app.clear(),
-                        (KeyCode::Left, _) => {
-                            app.previous_trace();
-                            app.clear();
-                        }
-                        (KeyCode::Right, _) => {
-                            app.next_trace();
-                            app.clear();
-                        }
-                        (KeyCode::Char('i'), _) => {
+        match input.key(key) {
+            InputOutcome::Editing => {}
+            InputOutcome::Cancel => {
+                self.target_input = None;
+                self.launched = None;
+            }
+            InputOutcome::Submit(_, TargetAction::Add)
+                if !matches!(
+                    self.trace_info[self.trace_selected].protocol,
+                    TracerProtocol::Icmp
+                ) =>
+            {
+                input.fail(String::from(
+                    "only icmp tracing may trace more than one target, replace the trace instead",
+                ));
+            }
+            InputOutcome::Submit(target, action) => {
+                let (tx, rx) = mpsc::channel();
+                let launcher = self.launcher.clone();
+                let spawned =
+                    thread::Builder::new()
+                        .name(String::from("launcher"))
+                        .spawn(move || {
+                            // If the dialog has been closed whilst the target was resolved then nobody wants the trace.
+                            if let Err(mpsc::SendError(Ok(info))) = tx.send(launcher(&target)) {
+                                info.cancellation.cancel();
+                            }
+                        });
+                match spawned {
+                    Ok(_) => self.launched = Some((action, rx)),
+                    Err(err) => input.fail(err.to_string()),
+                }
+            }
+        }
+    }
+
+    /// Show the trace of the target submitted in the dialog once its tracer has started, or why it failed to start.
+    fn check_launched(&mut self) {
+        let Some((action, rx)) = &self.launched else {
+            return;
+        };
+        let action = *action;
+        let launched = match rx.try_recv() {
+            Ok(launched) => launched,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => Err(anyhow::anyhow!("failed to start tracing")),
+        };
+        self.launched = None;
+        match launched {
+            Ok(info) => {
+                self.target_input = None;
+                match action {
+                    TargetAction::Add => self.add_trace(info),
+                    TargetAction::Replace => self.replace_trace(info),
+                }
+            }
+            Err(err) => {
+                if let Some(input) = &mut self.target_input {
+                    input.fail(err.to_string());
+                }
+            }
+        }
+    }
+
+    /// Show a new trace in a tab of its own.
+    fn add_trace(&mut self, info: TraceInfo) {
+        self.note(format!("tracing {}", info.target_hostname));
+        self.trace_info.push(info);
+        self.tabs.push(TabState::default());
+        self.summaries.push(TraceSummary::default());
+        self.select_trace(self.trace_info.len() - 1);
+    }
+
+    /// Show a new trace in place of the selected trace, the tracer of which is stopped.
+    fn replace_trace(&mut self, info: TraceInfo) {
+        self.note(format!(
+            "tracing {} in place of {}",
+            info.target_hostname, self.trace_info[self.trace_selected].target_hostname
+        ));
+        let replaced = std::mem::replace(&mut self.trace_info[self.trace_selected], info);
+        replaced.cancellation.cancel();
+        self.selection = HopSelection::default();
+        self.pins = Pins::default();
+        self.confirm_unpin = false;
+        self.compared = Compared::default();
+        self.flow_selected = 0;
+        self.summaries[self.trace_selected] = TraceSummary::default();
+        self.selected_tracer_data = self.trace_info[self.trace_selected].data.snapshot();
+    }
+
+    /// Move the cursor of the export formats, or export in the format under it.
+    fn export_key(&mut self, key: KeyCode) {
+        match key {
+            KeyCode::Up => self.export_cursor = self.export_cursor.saturating_sub(1),
+            KeyCode::Down => {
+                self.export_cursor = (self.export_cursor + 1).min(ExportFormat::ALL.len() - 1);
+            }
+            KeyCode::Enter => {
+                self.show_export = false;
+                self.export(ExportFormat::ALL[self.export_cursor]);
+            }
+            _ => self.toggle_export(),
+        }
+    }
+
+    /// Export the trace as shown, including any selected flow, to a file and note the path written or the error.
+    ///
+    /// The data shown is a snapshot taken under a single read lock and so the file is internally consistent.
+    fn export(&mut self, format: ExportFormat) {
+        let config = &self.tui_config;
+        let exported = export::export(
+            &config.export_dir,
+            &self.trace_info[self.trace_selected],
+            self.tracer_data(),
+            &self.resolver,
+            &config.export_columns,
+            format,
+        );
+        match exported {
+            Ok(path) => self.note(format!("exported to {}", path.display())),
+            Err(err) => self.note(format!("export failed: {err}")),
+        }
+    }
+
+    fn faster_refresh(&mut self) {
+        self.set_refresh_rate(faster(self.tui_config.refresh_rate));
+    }
+
+    fn slower_refresh(&mut self) {
+        self.set_refresh_rate(slower(self.tui_config.refresh_rate));
+    }
+
+    fn set_refresh_rate(&mut self, refresh_rate: RefreshRate) {
+        self.tui_config.refresh_rate = refresh_rate.rate;
+        if refresh_rate.clamped {
+            self.note(format!(
+                "refresh rate limited to {}",
+                humantime::format_duration(refresh_rate.rate)
+            ));
+        }
+    }
+
+    /// Show a note on the status bar for `STATUS_NOTE_DURATION`.
+    fn note(&mut self, note: String) {
+        self.note_for(note, STATUS_NOTE_DURATION);
+    }
+
+    /// Show a note on the status bar for `duration`.
+    fn note_for(&mut self, note: String, duration: Duration) {
+        self.status_note = Some((note, Instant::now() + duration));
+    }
+
+    /// The note shown on the status bar, if it has not expired.
+    fn status_note(&self) -> Option<&str> {
+        self.status_note
+            .as_ref()
+            .filter(|(_, expires)| Instant::now() < *expires)
+            .map(|(note, _)| note.as_str())
+    }
+
+    /// Ring the bell and flash the header, as configured, if any alerts have been recorded since last checked.
+    fn check_alerts(&mut self) {
+        let alerts = self.events.read().alerts();
+        if alerts > self.alerts_seen {
+            self.alerts_seen = alerts;
+            if self.tui_config.bell {
+                let mut stdout = io::stdout();
+                stdout
+                    .write_all(b"\x07")
+                    .and_then(|()| stdout.flush())
+                    .unwrap_or_default();
+            }
+            if self.tui_config.notify {
+                self.flash_until = Some(Instant::now() + ALERT_FLASH_DURATION);
+            }
+        }
+    }
+
+    /// Is the header flashing, and if so is it highlighted, i.e. on, rather than off?
+    fn flash_on(&self) -> bool {
+        let now = Instant::now();
+        self.flash_until
+            .is_some_and(|until| now < until && (until - now).as_millis() / 500 % 2 == 1)
+    }
+
+    /// Copy the most frequent address of the selected hop to the clipboard.
+    /// Show the RDAP registration of the network of the most frequent address of the selected hop, looking it up in
+    /// the background if it has not been already.
+    fn show_rdap(&mut self) {
+        if !self.tui_config.rdap {
+            self.note(String::from("rdap lookups are off, see --tui-rdap"));
+            return;
+        }
+        let Some((addr, _)) = ranked_addrs(self.selected_hop()).first().copied() else {
+            self.note(String::from("no address to look up"));
+            return;
+        };
+        self.rdap.lookup(addr);
+        self.rdap_shown = Some((addr, Instant::now()));
+    }
+
+    fn copy_addr(&mut self) {
+        let addr = ranked_addrs(self.selected_hop())
+            .first()
+            .map(|(addr, _)| addr.to_string());
+        self.copy(addr);
+    }
+
+    /// Copy every address of the selected hop to the clipboard, as a list of `hostname (ip)`.
+    fn copy_hosts(&mut self) {
+        let hosts = ranked_addrs(self.selected_hop())
+            .iter()
+            .map(|(addr, _)| {
+                let hostname = format_dns_entry(self.resolver.reverse_lookup(*addr), false);
+                if hostname == addr.to_string() {
+                    hostname
+                } else {
+                    format_host(AddressMode::Both, &hostname, *addr, None)
+                }
+            })
+            .join(", ");
+        self.copy(Some(hosts).filter(|hosts| !hosts.is_empty()));
+    }
+
+    /// Copy `text` to the clipboard, or show it on the status bar such that it can be selected manually if it
+    /// cannot be copied.
+    fn copy(&mut self, text: Option<String>) {
+        match text {
+            None => self.note(String::from("no address to copy")),
+            Some(text) => match self.clipboard.copy(self.tui_config.clipboard, &text) {
+                Ok(()) => self.note(format!("copied {text}")),
+                Err(err) => self.note_for(format!("{err}: {text}"), CLIPBOARD_FALLBACK_DURATION),
+            },
+        }
+    }
+
+    fn expand_hosts(&mut self) {
+        self.tui_config.max_addrs = match self.tui_config.max_addrs {
+            None => Some(1),
+            Some(i) if i < self.max_hosts() => Some(i + 1),
+            Some(i) => Some(i),
+        }
+    }
+
+    fn contract_hosts(&mut self) {
+        self.tui_config.max_addrs = match self.tui_config.max_addrs {
+            Some(i) if i > 1 => Some(i - 1),
+            _ => None,
+        }
+    }
+
+    fn zoom_in(&mut self) {
+        if self.zoom_factor < MAX_ZOOM_FACTOR {
+            self.zoom_factor += 1;
+        }
+    }
+
+    fn zoom_out(&mut self) {
+        if self.zoom_factor > 1 {
+            self.zoom_factor -= 1;
+        }
+    }
+
+    fn expand_hosts_max(&mut self) {
+        self.tui_config.max_addrs = Some(self.max_hosts());
+    }
+
+    fn contract_hosts_min(&mut self) {
+        self.tui_config.max_addrs = Some(1);
+    }
+
+    /// The maximum number of hosts per hop for the currently selected trace.
+    fn max_hosts(&self) -> u8 {
+        self.selected_tracer_data
+            .hops()
+            .iter()
+            .map(|h| h.addrs().count())
+            .max()
+            .and_then(|i| u8::try_from(i).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// Run the frontend TUI.
+///
+/// The progress of starting the traces is shown until they have started.  If they fail to start the error is shown
+/// until a key is pressed and then returned once the terminal has been restored.
+pub fn run_frontend(
+    startup: &Startup,
+    mut tui_config: TuiConfig,
+    resolver: DnsResolver,
+    events: SharedEventLog,
+    launcher: TraceLauncher,
+    session: &Session,
+) -> anyhow::Result<()> {
+    tui_config.restore(session.restored());
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+    let preserve_screen = tui_config.preserve_screen;
+    let (res, failed) = match run_startup(&mut terminal, startup, &tui_config) {
+        Ok(Some(Ok(traces))) => {
+            let res = run_app(
+                &mut terminal,
+                traces,
+                tui_config,
+                resolver,
+                events,
+                launcher,
+                session,
+            );
+            (res, None)
+        }
+        Ok(Some(Err(err))) => (Ok(()), Some(err)),
+        Ok(None) => (Ok(()), None),
+        Err(err) => (Err(err), None),
+    };
+    disable_raw_mode()?;
+    if !preserve_screen {
+        execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    }
+    terminal.show_cursor()?;
+    if let Err(err) = res {
+        println!("{err:?}");
+    }
+    failed.map_or(Ok(()), Err)
+}
+
+/// Show the progress of starting the traces until they have started, or failed to start, returning `None` if the user
+/// quit first.
+///
+/// If the traces failed to start the error is shown until a key is pressed.
+fn run_startup<B: Backend>(
+    terminal: &mut Terminal<B>,
+    startup: &Startup,
+    tui_config: &TuiConfig,
+) -> io::Result<Option<anyhow::Result<Vec<TraceInfo>>>> {
+    let start = Instant::now();
+    loop {
+        let started = startup.try_started();
+        if let Some(Ok(traces)) = started {
+            return Ok(Some(Ok(traces)));
+        }
+        let error = started.and_then(Result::err);
+        terminal.draw(|f| {
+            let message = match &error {
+                Some(err) => err.to_string(),
+                None => format!(
+                    "{} {}",
+                    spinner(start.elapsed()),
+                    startup.status().message()
+                ),
+            };
+            render_startup(f, &message, error.is_some(), &tui_config.theme);
+        })?;
+        if let Some(err) = error {
+            loop {
+                if let Event::Key(_) = event::read()? {
+                    return Ok(Some(Err(err)));
+                }
+            }
+        }
+        if event::poll(tui_config.refresh_rate.min(SPINNER_FRAME))? {
+            if let Event::Key(key) = event::read()? {
+                if tui_config.key_bindings.command(&key) == Some(TuiCommand::Quit) {
+                    return Ok(None);
+                }
+            }
+        }
+    }
+}
+
+///
+/// The state of the TUI is restored from the `session` at the start and saved to it on quitting.
+#[allow(clippy::too_many_lines)]
+fn run_app<B: Backend>(
+    terminal: &mut Terminal<B>,
+    trace_info: Vec<TraceInfo>,
+    tui_config: TuiConfig,
+    resolver: DnsResolver,
+    events: SharedEventLog,
+    launcher: TraceLauncher,
+    session: &Session,
+) -> io::Result<()> {
+    let mut app = TuiApp::new(tui_config, resolver, events, trace_info, launcher);
+    app.restore(session.restored());
+    if let Some(err) = session.error() {
+        app.note(err.to_string());
+    }
+    loop {
+        if app.frozen_start.is_none() {
+            debug_span!("frontend.snapshot_trace_data").in_scope(|| app.snapshot_trace_data());
+            app.clamp_selected_hop();
+        };
+        app.check_alerts();
+        app.check_launched();
+        app.rdap.check();
+        debug_span!("frontend.draw").in_scope(|| terminal.draw(|f| render_app(f, &mut app)))?;
+        if event::poll(app.tui_config.refresh_rate)? {
+            if let Event::Key(key) = event::read()? {
+                if app.show_help {
+                    match key.code {
+                        KeyCode::Up
+                        | KeyCode::Down
+                        | KeyCode::PageUp
+                        | KeyCode::PageDown
+                        | KeyCode::Home
+                        | KeyCode::End => app.scroll_help(key.code),
+                        _ => app.toggle_help(),
+                    }
+                } else if app.show_events {
+                    match key.code {
+                        KeyCode::Up
+                        | KeyCode::Down
+                        | KeyCode::PageUp
+                        | KeyCode::PageDown
+                        | KeyCode::Home
+                        | KeyCode::End => app.scroll_events(key.code),
+                        _ => app.toggle_events(),
+                    }
+                } else if app.show_column_settings {
+                    app.column_settings_key(key.code);
+                } else if app.show_settings {
+                    app.settings_key(key.code);
+                } else if app.show_export {
+                    app.export_key(key.code);
+                } else if app.target_input.is_some() {
+                    app.target_key(key.code);
+                } else if app.confirm_unpin {
+                    app.confirm_unpin_key(key.code);
+                } else if app.rdap_shown.is_some() {
+                    app.rdap_shown = None;
+                } else if let Some(command) = app.map_command(&key) {
+                    app.move_map(command);
+                } else if let Some(command) = app.tui_config.key_bindings.command(&key) {
+                    match command {
+                        TuiCommand::ToggleHelp => app.toggle_help(),
+                        TuiCommand::ToggleEvents => app.toggle_events(),
+                        TuiCommand::Quit => return session.save(&app.session_state()),
+                        TuiCommand::ToggleFreeze => app.toggle_freeze(),
+                        TuiCommand::ToggleChart => app.toggle_chart(),
+                        TuiCommand::ToggleHistogram => app.toggle_histogram(),
+                        TuiCommand::ToggleCompare => app.toggle_compare(),
+                        TuiCommand::CompareHop => app.compare_hop(),
+                        TuiCommand::MoreBins => app.more_bins(),
+                        TuiCommand::FewerBins => app.fewer_bins(),
+                        TuiCommand::ToggleMap => app.toggle_map(),
+                        TuiCommand::ResetStatistics => app.reset_statistics(),
+                        TuiCommand::RaiseFirstTtl => app.shift_ttl_range(1, 0),
+                        TuiCommand::LowerFirstTtl => app.shift_ttl_range(-1, 0),
+                        TuiCommand::LowerMaxTtl => app.shift_ttl_range(0, -1),
+                        TuiCommand::RaiseMaxTtl => app.shift_ttl_range(0, 1),
+                        TuiCommand::ResetTtlRange => app.reset_ttl_range(),
+                        TuiCommand::FlushDnsCache => app.resolver.flush(),
+                        TuiCommand::NextHop => app.next_hop(),
+                        TuiCommand::PreviousHop => app.previous_hop(),
+                        TuiCommand::NextHopPage => app.next_hop_page(),
+                        TuiCommand::PreviousHopPage => app.previous_hop_page(),
+                        TuiCommand::FirstHop => app.first_hop(),
+                        TuiCommand::LastHop => app.last_hop(),
+                        TuiCommand::ToggleFollow => app.toggle_follow(),
+                        TuiCommand::ToggleDetail => app.toggle_detail(),
+                        TuiCommand::ClearSelection => app.clear(),
+                        TuiCommand::PreviousTrace => app.previous_trace(),
+                        TuiCommand::NextTrace => app.next_trace(),
+                        TuiCommand::NextFlow => app.next_flow(),
+                        TuiCommand::PreviousFlow => app.previous_flow(),
+                        TuiCommand::AddressModeIp => {
                             app.tui_config.address_mode = AddressMode::IP;
                         }
-                        (KeyCode::Char('n'), _) => {
+                        TuiCommand::AddressModeHost => {
                             app.tui_config.address_mode = AddressMode::Host;
                         }
-                        (KeyCode::Char('b'), _) => {
+                        TuiCommand::AddressModeBoth => {
                             app.tui_config.address_mode = AddressMode::Both;
                         }
-                        (KeyCode::Char('z'), _) => match app.resolver.config().resolve_method {
+                        TuiCommand::CycleAddressMode => {
+                            app.tui_config.address_mode = app.tui_config.address_mode.next();
+                        }
+                        TuiCommand::ToggleAsInfo => match app.resolver.config().resolve_method {
                             DnsResolveMethod::Resolv
                             | DnsResolveMethod::Google
                             | DnsResolveMethod::Cloudflare => {
@@ -381,14 +1536,33 @@ fn run_app<B: Backend>(
                             }
                             DnsResolveMethod::System => {}
                         },
-                        (KeyCode::Char('{'), _) => app.contract_hosts_min(),
-                        (KeyCode::Char('}'), _) => app.expand_hosts_max(),
-                        (KeyCode::Char('['), _) => app.contract_hosts(),
-                        (KeyCode::Char(']'), _) => app.expand_hosts(),
-                        (KeyCode::Char('+' | '='), _) => app.zoom_in(),
-                        (KeyCode::Char('-'), _) => app.zoom_out(),
-                        _ => {}
+                        TuiCommand::ToggleSparkline => app.toggle_sparkline(),
+                        TuiCommand::ToggleHeatmap => app.toggle_heatmap(),
+                        TuiCommand::ToggleHeatmapLatency => app.toggle_heatmap_latency(),
+                        TuiCommand::TogglePin => app.toggle_pin(),
+                        TuiCommand::ToggleUnresponsive => app.toggle_unresponsive(),
+                        TuiCommand::ToggleHistoricHops => app.toggle_historic_hops(),
+                        TuiCommand::ToggleColumnSettings => app.toggle_column_settings(),
+                        TuiCommand::ResetSort => app.reset_sort(),
+                        TuiCommand::ToggleCompact => app.toggle_compact(),
+                        TuiCommand::ShowRdap => app.show_rdap(),
+                        TuiCommand::ToggleSettings => app.toggle_settings(),
+                        TuiCommand::ToggleExport => app.toggle_export(),
+                        TuiCommand::AddTarget => app.add_target(),
+                        TuiCommand::FasterRefresh => app.faster_refresh(),
+                        TuiCommand::CopyAddr => app.copy_addr(),
+                        TuiCommand::CopyHosts => app.copy_hosts(),
+                        TuiCommand::SlowerRefresh => app.slower_refresh(),
+                        TuiCommand::ContractHostsMin => app.contract_hosts_min(),
+                        TuiCommand::ExpandHostsMax => app.expand_hosts_max(),
+                        TuiCommand::ContractHosts => app.contract_hosts(),
+                        TuiCommand::ExpandHosts => app.expand_hosts(),
+                        TuiCommand::ZoomIn => app.zoom_in(),
+                        TuiCommand::ZoomOut => app.zoom_out(),
                     }
+                } else {
+                    app.select_tab(&key);
+                    app.sort_key(&key);
                 }
             }
         }
@@ -417,31 +1591,43 @@ fn run_app<B: Backend>(
 ///  ------------------------------------
 ///
 /// Header - the title, configuration, destination, clock and keyboard controls
-/// Tab - a tab for each target being traced (only shown if > 1 target requested)
+/// Tab - a tab for each target being traced and one for a summary of all targets (only shown if > 1 target requested)
 /// Hops - a table where each row represents a single hop (time-to-live) in the trace
 /// History - a graph of historic round-trip ping samples for the target host
 /// Frequency - a histogram of sample frequencies by round-trip time for the target host
 ///
 /// On startup a splash screen is shown in place of the hops table, until the completion of the first round.
+///
+/// The compact layout is chosen afresh for the size of the terminal each time the TUI is drawn, such that it follows
+/// the terminal as it is resized.  In the compact layout of a short terminal the header and footer are hidden.
 fn render_app<B: Backend>(f: &mut Frame<'_, B>, app: &mut TuiApp) {
-    let constraints = if app.trace_info.len() > 1 {
-        LAYOUT_WITH_TABS.as_slice()
-    } else {
-        LAYOUT_WITHOUT_TABS.as_slice()
+    let size = f.size();
+    app.compact = Compact::new(size.width, size.height, app.tui_config.compact_thresholds);
+    let short = app.layout().short;
+    let tabs = app.trace_info.len() > 1;
+    let constraints = match (tabs, short) {
+        (true, false) => LAYOUT_WITH_TABS.as_slice(),
+        (false, false) => LAYOUT_WITHOUT_TABS.as_slice(),
+        (true, true) => COMPACT_LAYOUT_WITH_TABS.as_slice(),
+        (false, true) => COMPACT_LAYOUT_WITHOUT_TABS.as_slice(),
     };
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints(constraints.as_ref())
-        .split(f.size());
-    render_header(f, app, chunks[0]);
-    if app.trace_info.len() > 1 {
-        render_tabs(f, app, chunks[1]);
-        render_body(f, chunks[2], app);
-        render_footer(f, chunks[3], app);
-    } else {
-        render_body(f, chunks[1], app);
-        render_footer(f, chunks[2], app);
+        .split(size);
+    let mut chunks = chunks.into_iter();
+    if !short {
+        render_header(f, app, chunks.next().unwrap_or_default());
+    }
+    if tabs {
+        render_tabs(f, app, chunks.next().unwrap_or_default());
+    }
+    render_body(f, chunks.next().unwrap_or_default(), app);
+    if !short {
+        render_footer(f, chunks.next().unwrap_or_default(), app);
     }
+    render_status_bar(f, app, chunks.next().unwrap_or_default());
+    render_popups(f, app);
 }
 
 /// Render the title, config, target, clock and keyboard controls.
@@ -451,7 +1637,14 @@ fn render_header<B: Backend>(f: &mut Frame<'_, B>, app: &mut TuiApp, rect: Rect)
         .title_alignment(Alignment::Center)
         .borders(Borders::ALL)
         .border_type(BorderType::Rounded)
-        .style(Style::default());
+        .border_style(if app.flash_on() || app.degraded().is_some() {
+            Style::default()
+                .fg(app.tui_config.theme.crit)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            app.tui_config.theme.border()
+        })
+        .style(app.tui_config.theme.text());
     let now = chrono::Local::now().to_rfc3339_opts(SecondsFormat::Secs, true);
     let clock_span = Spans::from(Span::raw(now));
     let help_span = Spans::from(vec![
@@ -462,9 +1655,54 @@ fn render_header<B: Backend>(f: &mut Frame<'_, B>, app: &mut TuiApp, rect: Rect)
     ]);
     let right_spans = vec![clock_span, help_span];
     let right = Paragraph::new(right_spans)
-        .style(Style::default())
+        .style(app.tui_config.theme.text())
         .block(header_block.clone())
         .alignment(Alignment::Right);
+    let source = render_source(app);
+    let dest = render_destination(app);
+    let target = format!("{source} -> {dest}");
+    let port_mismatch = render_port_mismatch(app).map_or_else(
+        || Span::raw(""),
+        |mismatch| {
+            Span::styled(
+                format!(" ({mismatch})"),
+                Style::default().fg(app.tui_config.theme.warn),
+            )
+        },
+    );
+    let config_fields = header_config_fields(app);
+    let status_fields = header_status_fields(app);
+    let width = usize::from(rect.width.saturating_sub(2)).saturating_sub(HEADER_LABEL_WIDTH);
+    let left_spans = vec![
+        Spans::from(vec![
+            Span::styled("Target: ", Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(target),
+            port_mismatch,
+        ]),
+        Spans::from(vec![
+            Span::styled("Config: ", Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(fit(
+                &config_fields,
+                " ",
+                width.saturating_sub(HEADER_HELP_WIDTH),
+            )),
+        ]),
+        Spans::from(vec![
+            Span::styled("Status: ", Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(fit(&status_fields, ", ", width)),
+        ]),
+    ];
+
+    let left = Paragraph::new(left_spans)
+        .style(app.tui_config.theme.text())
+        .block(header_block)
+        .alignment(Alignment::Left);
+    f.render_widget(right, rect);
+    f.render_widget(left, rect);
+}
+
+/// The fields of the config line of the header, in order of display.
+fn header_config_fields(app: &TuiApp) -> Vec<Field> {
     let protocol = match app.tracer_config().protocol {
         TracerProtocol::Icmp => format!("icmp({})", app.tracer_config().addr_family),
         TracerProtocol::Udp => format!(
@@ -487,39 +1725,131 @@ fn render_header<B: Backend>(f: &mut Frame<'_, B>, app: &mut TuiApp, rect: Rect)
     };
     let interval = humantime::format_duration(app.tracer_config().min_round_duration);
     let grace = humantime::format_duration(app.tracer_config().grace_duration);
-    let first_ttl = app.tracer_config().first_ttl;
-    let max_ttl = app.tracer_config().max_ttl;
+    let (first_ttl, max_ttl) = app.ttl_range();
+    let packet_size = app.tracer_config().packet_size;
+    let address_mode = format_address_mode(app.tui_config.address_mode);
     let max_hosts = app
         .tui_config
         .max_addrs
-        .map_or_else(|| String::from("auto"), |m| m.to_string());
-    let source = render_source(app);
-    let dest = render_destination(app);
-    let target = format!("{source} -> {dest}");
-    let left_spans = vec![
-        Spans::from(vec![
-            Span::styled("Target: ", Style::default().add_modifier(Modifier::BOLD)),
-            Span::raw(target),
-        ]),
-        Spans::from(vec![
-            Span::styled("Config: ", Style::default().add_modifier(Modifier::BOLD)),
-            Span::raw(format!("protocol={protocol} dns={dns} as-info={as_info} interval={interval} grace={grace} start-ttl={first_ttl} max-ttl={max_ttl} max-hosts={max_hosts}"))]),
-        Spans::from(vec![
-            Span::styled("Status: ", Style::default().add_modifier(Modifier::BOLD)),
-            Span::raw(render_status(app)),
-            Span::raw(format!(
-                ", discovered {} hops",
-                app.tracer_data().hops().len()
-            )),
-        ]),
+        .map_or_else(|| String::from("all"), |m| m.to_string());
+    let mut fields = vec![
+        Field::new(9, format!("protocol={protocol}")),
+        Field::new(5, format!("size={packet_size}")),
+        Field::new(4, format!("dns={dns}")),
+        Field::new(2, format!("as-info={as_info}")),
+        Field::new(6, format!("interval={interval}")),
+        Field::new(3, format!("grace={grace}")),
     ];
+    if app.tracer_config().fixed_ttl {
+        fields.push(Field::new(7, format!("fixed-ttl={max_ttl}")));
+    } else {
+        fields.extend([
+            Field::new(2, format!("start-ttl={first_ttl}")),
+            Field::new(7, format!("max-ttl={max_ttl}")),
+        ]);
+    }
+    fields.extend([
+        Field::new(1, format!("address-mode={address_mode}")),
+        Field::new(1, format!("max-hosts={max_hosts}")),
+        Field::new(1, format!("seed={}", app.tracer_config().rng_seed)),
+    ]);
+    fields
+}
 
-    let left = Paragraph::new(left_spans)
-        .style(Style::default())
-        .block(header_block)
-        .alignment(Alignment::Left);
-    f.render_widget(right, rect);
-    f.render_widget(left, rect);
+/// The fields of the status line of the header, in order of display.
+fn header_status_fields(app: &TuiApp) -> Vec<Field> {
+    let trace = &app.selected_tracer_data;
+    let round = trace.round().map_or(0, |round| round + 1);
+    let elapsed = humantime::format_duration(Duration::from_secs(trace.elapsed().as_secs()));
+    let mut fields = vec![Field::new(9, render_status(app))];
+    if let Some(breach) = app.degraded() {
+        fields.push(Field::new(9, format!("degraded, {breach}")));
+    }
+    if let Some(change) = app.tracer_data().path_change() {
+        let ago = humantime::format_duration(Duration::from_secs(change.elapsed().as_secs()));
+        fields.push(Field::new(
+            7,
+            format!("path {} to {} hops {ago} ago", change.from(), change.to()),
+        ));
+    }
+    if let Some(stability) = render_path_stability(app.tracer_data()) {
+        fields.push(Field::new(6, stability));
+    }
+    fields.extend([
+        Field::new(6, format!("round {round}")),
+        Field::new(7, format!("elapsed {elapsed}")),
+        Field::new(3, format!("{} in flight", trace.in_flight())),
+    ]);
+    if let Some(window) = trace.inflight_window() {
+        fields.push(Field::new(4, format!("window {window}")));
+    }
+    fields.extend([
+        Field::new(
+            8,
+            format!("discovered {} hops", app.tracer_data().hops().len()),
+        ),
+        Field::new(1, "user timestamps"),
+        Field::new(2, app.tracer_config().priority.to_string()),
+    ]);
+    if let Some(metrics) = trace.channel_metrics() {
+        fields.push(Field::new(1, format!("packets {metrics}")));
+    }
+    if let Some(flow_key) = trace.flow_key() {
+        fields.push(Field::new(4, format!("flow key {flow_key}")));
+    }
+    let endpoints = trace.endpoints();
+    match app.selected_endpoint() {
+        Some(endpoint) => fields.push(Field::new(
+            5,
+            format!(
+                "target {}/{} {} ({} rounds)",
+                app.flow_selected,
+                endpoints.len(),
+                endpoint.addr(),
+                endpoint.rounds()
+            ),
+        )),
+        None if endpoints.len() > 1 => {
+            fields.push(Field::new(5, format!("all {} targets", endpoints.len())));
+        }
+        None => {}
+    }
+    match app.selected_flow() {
+        Some(flow) => fields.push(Field::new(
+            5,
+            format!(
+                "flow {}/{} ({} rounds)",
+                flow.id(),
+                trace.flows().len(),
+                flow.rounds()
+            ),
+        )),
+        None if trace.flows().len() > 1 => {
+            fields.push(Field::new(5, format!("all {} flows", trace.flows().len())));
+        }
+        None => {}
+    }
+    fields
+}
+
+/// Render the stability of the path of `trace`, the distinct paths observed and when it last changed.
+fn render_path_stability(trace: &Trace) -> Option<String> {
+    let stability = trace.path_stability();
+    let score = stability.score()?;
+    let paths = match stability.distinct_paths() {
+        1 => String::from("1 path"),
+        paths => format!("{paths} paths"),
+    };
+    Some(match stability.changed_at() {
+        Some(changed_at) => {
+            let ago = SystemTime::now()
+                .duration_since(changed_at)
+                .unwrap_or_default();
+            let ago = humantime::format_duration(Duration::from_secs(ago.as_secs()));
+            format!("stability {score:.0}% ({paths}, changed {ago} ago)")
+        }
+        None => format!("stability {score:.0}% ({paths})"),
+    })
 }
 
 /// Render the source address of the trace.
@@ -542,7 +1872,10 @@ fn render_source(app: &mut TuiApp) -> String {
 /// Render the destination address.
 fn render_destination(app: &mut TuiApp) -> String {
     let dest_hostname = &app.tracer_config().target_hostname;
-    let dest_addr = app.tracer_config().target_addr;
+    let dest_addr = zone::format_addr(
+        app.tracer_config().target_addr,
+        app.tracer_config().target_zone.as_deref(),
+    );
     match app.tracer_config().port_direction {
         PortDirection::None => {
             format!("{dest_hostname} ({dest_addr})")
@@ -556,10 +1889,27 @@ fn render_destination(app: &mut TuiApp) -> String {
     }
 }
 
+/// Render the ports from which the target replied other than those probed, as if something answered on its behalf,
+/// if it did.
+fn render_port_mismatch(app: &TuiApp) -> Option<String> {
+    let mismatches = app
+        .selected_tracer_data
+        .target_hop()
+        .responder_ports()
+        .into_iter()
+        .filter(|(ports, _)| ports.is_mismatch())
+        .map(|(ports, _)| format!("port {} replied from {}", ports.probed, ports.responder))
+        .join(", ");
+    (!mismatches.is_empty()).then_some(mismatches)
+}
+
 /// Render the headline status of the tracing.
 fn render_status(app: &TuiApp) -> String {
-    if app.selected_tracer_data.error().is_some() {
+    let trace = &app.selected_tracer_data;
+    if trace.error().is_some() {
         String::from("Failed")
+    } else if app.frozen_start.is_none() && trace.hops().iter().all(|hop| hop.total_recv() == 0) {
+        format!("{} Waiting for first response…", spinner(trace.elapsed()))
     } else if let Some(start) = app.frozen_start {
         format!(
             "Frozen ({})",
@@ -582,145 +1932,598 @@ fn format_dns_method(resolve_method: DnsResolveMethod) -> String {
     }
 }
 
-/// Render the tabs, one per trace.
+/// Render the tabs, one per trace followed by the summary.
+///
+/// Each trace is shown with its number key, target and the end-to-end packet loss or why there is none, i.e.
+/// `1 example.com 0.0%`.
 fn render_tabs<B: Backend>(f: &mut Frame<'_, B>, app: &mut TuiApp, rect: Rect) {
     let tabs_block = Block::default()
         .title("Traces")
         .title_alignment(Alignment::Left)
         .borders(Borders::ALL)
         .border_type(BorderType::Rounded)
-        .style(Style::default());
-    let titles: Vec<_> = app
+        .border_style(app.tui_config.theme.border())
+        .style(app.tui_config.theme.text());
+    let theme = &app.tui_config.theme;
+    let mut titles: Vec<_> = app
         .trace_info
         .iter()
-        .map(|trace| {
-            Spans::from(Span::styled(
-                &trace.target_hostname,
-                Style::default().fg(Color::Green),
-            ))
+        .zip(&app.summaries)
+        .enumerate()
+        .map(|(index, (trace, summary))| {
+            let status = match summary.reachability {
+                Reachability::Reachable => Span::raw(format!("{:.1}%", summary.loss_pct)),
+                Reachability::Waiting => Span::styled("-", theme.text_dim()),
+                Reachability::Unreachable | Reachability::Failed => Span::styled(
+                    summary.reachability.label(),
+                    Style::default().fg(theme.crit),
+                ),
+            };
+            Spans::from(vec![
+                Span::raw(format!("{} ", index + 1)),
+                Span::styled(
+                    trace.target_hostname.clone(),
+                    Style::default().fg(theme.target),
+                ),
+                Span::raw(" "),
+                status,
+            ])
         })
         .collect();
+    titles.push(Spans::from("0 Summary"));
+    let selected = if app.show_summary {
+        app.trace_info.len()
+    } else {
+        app.trace_selected
+    };
     let tabs = Tabs::new(titles)
         .block(tabs_block)
-        .select(app.trace_selected)
-        .style(Style::default())
+        .select(selected)
+        .style(app.tui_config.theme.text())
         .highlight_style(
             Style::default()
                 .add_modifier(Modifier::BOLD)
-                .bg(Color::Black),
+                .bg(app.tui_config.theme.tab_selected_bg),
         );
     f.render_widget(tabs, rect);
 }
 
-/// Render the body.
-///
-/// This is either an BSOD if there wa san error or the table of hop data or, if there is no data, the splash screen.
+/// Render the body, below the flows if more than one flow has been observed, or below the target addresses if they
+/// are probed round robin.
 fn render_body<B: Backend>(f: &mut Frame<'_, B>, rec: Rect, app: &mut TuiApp) {
-    if let Some(err) = app.selected_tracer_data.error() {
-        render_bsod(f, rec, err);
-    } else if app.tracer_data().hops().is_empty() {
-        render_splash(f, rec);
+    if app.show_summary {
+        render_summary(f, app, rec);
+    } else if app.flow_count() > 1 {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Min(0)].as_ref())
+            .split(rec);
+        if app.selected_tracer_data.endpoints().is_empty() {
+            render_flows(f, app, chunks[0]);
+        } else {
+            render_endpoints(f, app, chunks[0]);
+        }
+        render_view(f, chunks[1], app);
+    } else {
+        render_view(f, rec, app);
+    }
+}
+
+/// Render the summary of every trace, one row per target with the end-to-end statistics from its target hop.
+fn render_summary<B: Backend>(f: &mut Frame<'_, B>, app: &TuiApp, rect: Rect) {
+    let theme = &app.tui_config.theme;
+    let header = Row::new(
+        [
+            "#", "Target", "Status", "Hops", "Snt", "Loss%", "Last", "Avg", "Best", "Wrst",
+        ]
+        .map(Cell::from),
+    )
+    .style(theme.header());
+    let rows =
+        app.trace_info
+            .iter()
+            .zip(&app.summaries)
+            .enumerate()
+            .map(|(index, (trace, summary))| {
+                let status_style = match summary.reachability {
+                    Reachability::Reachable => Style::default().fg(theme.target),
+                    Reachability::Waiting => theme.text_dim(),
+                    Reachability::Unreachable | Reachability::Failed => {
+                        Style::default().fg(theme.crit)
+                    }
+                };
+                Row::new(vec![
+                    Cell::from(format!("{}", index + 1)),
+                    Cell::from(format!(
+                        "{} ({})",
+                        trace.target_hostname,
+                        zone::format_addr(trace.target_addr, trace.target_zone.as_deref())
+                    )),
+                    Cell::from(summary.reachability.label()).style(status_style),
+                    Cell::from(format!("{}", summary.hops)),
+                    Cell::from(format!("{}", summary.sent)),
+                    Cell::from(format!("{:.1}%", summary.loss_pct)),
+                    Cell::from(format_ms(summary.last_ms)),
+                    Cell::from(format_ms(summary.avg_ms)),
+                    Cell::from(format_ms(summary.best_ms)),
+                    Cell::from(format_ms(summary.worst_ms)),
+                ])
+            });
+    let table = Table::new(rows)
+        .header(header)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .border_style(theme.border())
+                .title("Summary"),
+        )
+        .style(theme.text())
+        .column_spacing(COLUMN_SPACING)
+        .widths(&[
+            Constraint::Length(2),
+            Constraint::Min(20),
+            Constraint::Length(11),
+            Constraint::Length(4),
+            Constraint::Length(5),
+            Constraint::Length(6),
+            Constraint::Length(6),
+            Constraint::Length(6),
+            Constraint::Length(6),
+            Constraint::Length(6),
+        ]);
+    f.render_widget(table, rect);
+}
+
+/// Render the flows of the selected trace, each as its number and rounds, i.e. `2:15`.
+///
+/// A flow whose path differs from that of the first flow is highlighted along with the ttl from which it differs,
+/// i.e. `2:15≠5`.
+fn render_flows<B: Backend>(f: &mut Frame<'_, B>, app: &TuiApp, rect: Rect) {
+    let theme = &app.tui_config.theme;
+    let flows = app.selected_tracer_data.flows();
+    let style = |selected: bool, style: Style| {
+        if selected {
+            theme.selected()
+        } else {
+            style
+        }
+    };
+    let mut spans = vec![
+        Span::styled("Flows: ", Style::default().add_modifier(Modifier::BOLD)),
+        Span::styled(" all ", style(app.flow_selected == 0, theme.text())),
+    ];
+    for flow in flows {
+        let selected = app.flow_selected == flow.id();
+        let (label, flow_style) = match flow.diverges_from(&flows[0]) {
+            Some(ttl) => (
+                format!(" {}:{}≠{ttl} ", flow.id(), flow.rounds()),
+                Style::default().fg(theme.warn),
+            ),
+            None => (format!(" {}:{} ", flow.id(), flow.rounds()), theme.text()),
+        };
+        spans.push(Span::styled(label, style(selected, flow_style)));
+    }
+    f.render_widget(Paragraph::new(Spans::from(spans)), rect);
+}
+
+/// Render the target addresses of the selected trace, which are probed round robin, each as its address and rounds,
+/// i.e. `10.0.0.1:15`.
+fn render_endpoints<B: Backend>(f: &mut Frame<'_, B>, app: &TuiApp, rect: Rect) {
+    let theme = &app.tui_config.theme;
+    let style = |selected: bool| {
+        if selected {
+            theme.selected()
+        } else {
+            theme.text()
+        }
+    };
+    let mut spans = vec![
+        Span::styled("Targets: ", Style::default().add_modifier(Modifier::BOLD)),
+        Span::styled(" all ", style(app.flow_selected == 0)),
+    ];
+    for (i, endpoint) in app.selected_tracer_data.endpoints().iter().enumerate() {
+        spans.push(Span::styled(
+            format!(" {}:{} ", endpoint.addr(), endpoint.rounds()),
+            style(app.flow_selected == i + 1),
+        ));
+    }
+    f.render_widget(Paragraph::new(Spans::from(spans)), rect);
+}
+
+/// Render the table of hop data or, if there is no data, the splash screen.
+fn render_view<B: Backend>(f: &mut Frame<'_, B>, rec: Rect, app: &mut TuiApp) {
+    if app.tracer_data().hops().is_empty() {
+        render_splash(f, rec, &app.tui_config.theme);
+    } else if app.show_map {
+        render_map(f, app, rec);
+    } else if app.show_detail {
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)].as_ref())
+            .split(rec);
+        if app.show_chart {
+            render_chart_or_histogram(f, app, chunks[0]);
+        } else {
+            render_table(f, app, chunks[0]);
+        }
+        render_detail(f, app, chunks[1]);
     } else if app.show_chart {
-        render_chart(f, app, rec);
+        render_chart_or_histogram(f, app, rec);
     } else {
         render_table(f, app, rec);
     }
 }
 
-/// Render the ping history for all hops as a chart.
-fn render_chart<B: Backend>(f: &mut Frame<'_, B>, app: &mut TuiApp, rect: Rect) {
-    let target_hop = app.table_state.selected().map_or_else(
-        || app.tracer_data().target_hop(),
-        |s| &app.tracer_data().hops()[s],
-    );
-    let samples = app.tui_config.max_samples / app.zoom_factor;
-    let series_data = app
-        .selected_tracer_data
+/// Render the world map, with the hops which have a location plotted on it and those without listed beside it.
+///
+/// The hops are located by their most frequent address.  A line is drawn between each located hop and the next, and
+/// the selected hop is highlighted and described below the map.
+fn render_map<B: Backend>(f: &mut Frame<'_, B>, app: &TuiApp, rect: Rect) {
+    let theme = &app.tui_config.theme;
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(3), Constraint::Length(1)].as_ref())
+        .split(rect);
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Min(20), Constraint::Length(MAP_GUTTER_WIDTH)].as_ref())
+        .split(rows[0]);
+    let selected_ttl = app.selected_hop().ttl();
+    let (located, unlocated): (Vec<_>, Vec<_>) = app
+        .tracer_data()
         .hops()
         .iter()
-        .map(|hop| {
-            hop.samples()
-                .iter()
-                .enumerate()
-                .take(samples)
-                .map(|(i, s)| (i as f64, (s.as_secs_f64() * 1000_f64)))
-                .collect::<Vec<_>>()
+        .map(|hop| (hop, hop_location(hop, app.tui_config.geoip.as_ref())))
+        .partition(|(_, location)| location.is_some());
+    let points: Vec<_> = located
+        .iter()
+        .filter_map(|(_, location)| *location)
+        .collect();
+    let points = spread(&points, app.map_viewport.spread_radius());
+    let selected: Vec<_> = located
+        .iter()
+        .zip(&points)
+        .filter(|((hop, _), _)| hop.ttl() == selected_ttl)
+        .map(|(_, point)| *point)
+        .collect();
+    let viewport = app.map_viewport;
+    let canvas = Canvas::default()
+        .block(
+            Block::default()
+                .title("World map")
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .border_style(theme.border()),
+        )
+        .marker(Marker::Braille)
+        .x_bounds(viewport.x_bounds())
+        .y_bounds(viewport.y_bounds())
+        .paint(|ctx| {
+            ctx.draw(&Map {
+                resolution: MapResolution::High,
+                color: theme.text_dim,
+            });
+            ctx.layer();
+            for pair in points.windows(2) {
+                ctx.draw(&Line {
+                    x1: pair[0].0,
+                    y1: pair[0].1,
+                    x2: pair[1].0,
+                    y2: pair[1].1,
+                    color: theme.chart,
+                });
+            }
+            ctx.draw(&Points {
+                coords: &points,
+                color: theme.history,
+            });
+            ctx.draw(&Points {
+                coords: &selected,
+                color: theme.target,
+            });
+        });
+    f.render_widget(canvas, columns[0]);
+    let gutter: Vec<_> = unlocated
+        .iter()
+        .map(|(hop, _)| {
+            let style = if hop.ttl() == selected_ttl {
+                theme.selected()
+            } else {
+                theme.text()
+            };
+            let host = hop
+                .primary_addr()
+                .map_or_else(|| String::from("No response"), |addr| addr.to_string());
+            Spans::from(Span::styled(format!("{:>3} {host}", hop.ttl()), style))
         })
-        .collect::<Vec<_>>();
-    let max_sample = series_data
+        .collect();
+    let gutter = Paragraph::new(gutter).block(
+        Block::default()
+            .title("Not located")
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(theme.border()),
+    );
+    f.render_widget(gutter, columns[1]);
+    let info = Paragraph::new(map_info(app)).style(theme.text());
+    f.render_widget(info, rows[1]);
+}
+
+/// The location, as `(longitude, latitude)`, of the most frequent address of a hop, if known.
+fn hop_location(hop: &Hop, geoip: Option<&GeoIpDb>) -> Option<(f64, f64)> {
+    let location = geoip?.lookup(hop.primary_addr()?)?;
+    Some((location.longitude, location.latitude))
+}
+
+/// Describe the location and AS of the selected hop, i.e. `#5 10.0.0.1 London, GB AS64500 EXAMPLE`.
+fn map_info(app: &TuiApp) -> String {
+    let hop = app.selected_hop();
+    if let Some(addr) = hop.primary_addr() {
+        format!("#{} {addr} {}", hop.ttl(), map_location(app, addr))
+    } else {
+        format!("#{} No response", hop.ttl())
+    }
+}
+
+/// Describe the location and AS of an address, i.e. `London, GB AS64500 EXAMPLE`.
+fn map_location(app: &TuiApp, addr: IpAddr) -> String {
+    let location = app
+        .tui_config
+        .geoip
+        .as_ref()
+        .and_then(|geoip| geoip.lookup(addr))
+        .map_or_else(
+            || String::from("unknown location"),
+            |location| format!("{}, {}", location.city, location.country),
+        );
+    let as_info = if app.tui_config.lookup_as_info {
+        match app.resolver.reverse_lookup_with_asinfo(addr) {
+            DnsEntry::Resolved(Resolved::WithAsInfo(_, _, asinfo)) => {
+                format!(" AS{} {}", asinfo.asn, asinfo.name)
+            }
+            _ => String::new(),
+        }
+    } else {
+        String::new()
+    };
+    format!("{location}{as_info}")
+}
+
+fn render_chart_or_histogram<B: Backend>(f: &mut Frame<'_, B>, app: &mut TuiApp, rect: Rect) {
+    if app.show_histogram {
+        render_histogram(f, app, rect);
+    } else if app.show_compare {
+        render_comparison(f, app, rect);
+    } else {
+        render_chart(f, app, rect);
+    }
+}
+
+/// Render a histogram of the RTT of the replies of the selected hop, with the lower bound of each bin below it.
+fn render_histogram<B: Backend>(f: &mut Frame<'_, B>, app: &TuiApp, rect: Rect) {
+    let hop = app.selected_hop();
+    let theme = &app.tui_config.theme;
+    let histogram = Histogram::new(hop.samples(), app.histogram_bins);
+    let labels = histogram.labels();
+    let data: Vec<_> = labels
         .iter()
-        .flatten()
-        .map(|&(_, s)| s)
-        .max_by_key(|&c| c as u64)
-        .unwrap_or_default();
-    let sets = series_data
+        .map(String::as_str)
+        .zip(histogram.counts.iter().copied())
+        .collect();
+    let bins = data.len().max(1) as u16;
+    let bar_width = (rect.width.saturating_sub(2) / bins)
+        .saturating_sub(1)
+        .max(1);
+    let barchart = BarChart::default()
+        .block(
+            Block::default()
+                .title(format!(
+                    "RTT histogram #{} (ms, {} bins, {} lost)",
+                    hop.ttl(),
+                    app.histogram_bins,
+                    histogram.lost
+                ))
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .border_style(theme.border()),
+        )
+        .data(&data)
+        .bar_width(bar_width)
+        .bar_gap(1)
+        .bar_style(Style::default().fg(theme.chart))
+        .value_style(
+            Style::default()
+                .bg(theme.chart)
+                .add_modifier(Modifier::BOLD),
+        )
+        .label_style(theme.text());
+    f.render_widget(barchart, rect);
+}
+
+/// Render the ping history for all hops as a chart.
+fn render_chart<B: Backend>(f: &mut Frame<'_, B>, app: &mut TuiApp, rect: Rect) {
+    let hop = app.selected_hop();
+    let theme = &app.tui_config.theme;
+    // One sample per column, less the borders and the labels of the y axis.
+    let width = usize::from(rect.width.saturating_sub(CHART_Y_LABEL_WIDTH + 2));
+    let samples = (app.tui_config.max_samples / app.zoom_factor).min(width);
+    let points = ChartPoints::new(hop.samples(), samples);
+    let mut sets = points
+        .runs
         .iter()
-        .enumerate()
-        .map(|(i, s)| {
+        .map(|run| {
             Dataset::default()
-                .name(format!("Hop {}", i + 1))
-                .data(s)
+                .data(run)
                 .graph_type(GraphType::Line)
                 .marker(Marker::Braille)
-                .style(Style::default().fg({
-                    match i {
-                        i if i + 1 == target_hop.ttl() as usize => Color::Green,
-                        _ => Color::Gray,
-                    }
-                }))
+                .style(Style::default().fg(theme.chart))
         })
         .collect::<Vec<_>>();
-    let constraints = (Constraint::Ratio(1, 1), Constraint::Ratio(1, 1));
+    sets.push(
+        Dataset::default()
+            .data(&points.lost)
+            .graph_type(GraphType::Scatter)
+            .marker(Marker::Dot)
+            .style(Style::default().fg(theme.loss)),
+    );
     let chart = Chart::new(sets)
         .x_axis(
             Axis::default()
                 .title("Samples")
-                .bounds([0_f64, samples as f64])
+                .bounds(points.x_bounds)
                 .labels_alignment(Alignment::Right)
                 .labels(
-                    ["0".to_string(), format!("{samples} ({}x)", app.zoom_factor)]
-                        .into_iter()
-                        .map(Span::from)
-                        .collect(),
+                    [
+                        format!("{samples} ({}x)", app.zoom_factor),
+                        "now".to_string(),
+                    ]
+                    .into_iter()
+                    .map(Span::from)
+                    .collect(),
                 )
-                .style(Style::default().fg(Color::DarkGray)),
+                .style(theme.text_dim()),
         )
         .y_axis(
             Axis::default()
-                .title("RTT")
-                .bounds([0_f64, max_sample])
+                .title("RTT (ms)")
+                .bounds(points.y_bounds)
+                .labels(points.y_labels().into_iter().map(Span::from).collect())
+                .style(theme.text_dim()),
+        )
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .border_style(theme.border())
+                .title(format!("Hop #{}", hop.ttl())),
+        )
+        .style(theme.text());
+    f.render_widget(chart, rect);
+}
+
+/// Render the RTT of the selected hop and the compared hops on a common axis, each in its own color.
+///
+/// The chart is titled with a legend of the ttl and address of the hop of each color.
+fn render_comparison<B: Backend>(f: &mut Frame<'_, B>, app: &TuiApp, rect: Rect) {
+    let theme = &app.tui_config.theme;
+    let colors = theme.series();
+    let hops: Vec<_> = app
+        .compared
+        .series(app.selected_hop().ttl())
+        .into_iter()
+        .filter_map(|ttl| app.hops().iter().find(|hop| hop.ttl() == ttl))
+        .collect();
+    let width = usize::from(rect.width.saturating_sub(CHART_Y_LABEL_WIDTH + 2));
+    let samples = (app.tui_config.max_samples / app.zoom_factor).min(width);
+    let series: Vec<_> = hops.iter().map(|hop| hop.samples()).collect();
+    let points = ComparisonPoints::new(&series, samples);
+    let sets: Vec<_> = points
+        .series
+        .iter()
+        .enumerate()
+        .flat_map(|(i, runs)| {
+            runs.iter().map(move |run| {
+                Dataset::default()
+                    .data(run)
+                    .graph_type(GraphType::Line)
+                    .marker(Marker::Braille)
+                    .style(Style::default().fg(colors[i % colors.len()]))
+            })
+        })
+        .collect();
+    let legend: Vec<_> = hops
+        .iter()
+        .enumerate()
+        .map(|(i, hop)| {
+            let addr = hop
+                .primary_addr()
+                .map_or_else(|| String::from("No response"), |addr| addr.to_string());
+            Span::styled(
+                format!(" #{} {addr} ", hop.ttl()),
+                Style::default().fg(colors[i % colors.len()]),
+            )
+        })
+        .collect();
+    let chart = Chart::new(sets)
+        .x_axis(
+            Axis::default()
+                .title("Samples")
+                .bounds(points.x_bounds)
+                .labels_alignment(Alignment::Right)
                 .labels(
                     [
-                        String::from("0.0"),
-                        format!("{:.1}", max_sample / 2_f64),
-                        format!("{max_sample:.1}"),
+                        format!("{samples} ({}x)", app.zoom_factor),
+                        "now".to_string(),
                     ]
                     .into_iter()
                     .map(Span::from)
                     .collect(),
                 )
-                .style(Style::default().fg(Color::DarkGray)),
+                .style(theme.text_dim()),
+        )
+        .y_axis(
+            Axis::default()
+                .title("RTT (ms)")
+                .bounds(points.y_bounds)
+                .labels(
+                    y_labels(points.y_bounds)
+                        .into_iter()
+                        .map(Span::from)
+                        .collect(),
+                )
+                .style(theme.text_dim()),
         )
-        .hidden_legend_constraints(constraints)
         .block(
             Block::default()
                 .borders(Borders::ALL)
                 .border_type(BorderType::Rounded)
-                .title("Hops"),
-        );
+                .border_style(theme.border())
+                .title(Spans::from(legend)),
+        )
+        .style(theme.text());
     f.render_widget(chart, rect);
 }
 
-/// Render a blue screen of death.
-fn render_bsod<B: Backend>(f: &mut Frame<'_, B>, rect: Rect, error: &str) {
-    let chunks = Layout::default()
-        .constraints([Constraint::Percentage(35), Constraint::Percentage(65)].as_ref())
-        .split(rect);
+/// Render the dialog telling the user that tracing has stopped, with the error which stopped it.
+/// Render the progress of starting the traces, or the `message` of the error if they failed to start.
+fn render_startup<B: Backend>(f: &mut Frame<'_, B>, message: &str, failed: bool, theme: &Theme) {
     let block = Block::default()
-        .title("Hops")
+        .title(" Trippy ")
+        .title_alignment(Alignment::Center)
         .borders(Borders::ALL)
-        .border_type(BorderType::Rounded)
-        .style(Style::default().bg(Color::Blue));
+        .border_type(BorderType::Double)
+        .style(theme.popup());
+    let spans = if failed {
+        vec![
+            Spans::from(Span::styled(
+                "Trippy Failed :(",
+                Style::default().add_modifier(Modifier::REVERSED),
+            )),
+            Spans::from(""),
+            Spans::from(message),
+            Spans::from(""),
+            Spans::from("Press any key to quit"),
+        ]
+    } else {
+        vec![Spans::from(""), Spans::from(message)]
+    };
+    let area = centered_rect(60, 30, f.size());
+    let paragraph = Paragraph::new(spans)
+        .block(block)
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true });
+    f.render_widget(Clear, area);
+    f.render_widget(paragraph, area);
+}
+
+fn render_bsod<B: Backend>(f: &mut Frame<'_, B>, error: &str, theme: &Theme) {
+    let block = Block::default()
+        .title(" Trace stopped ")
+        .title_alignment(Alignment::Center)
+        .borders(Borders::ALL)
+        .border_type(BorderType::Double)
+        .style(theme.popup());
     let spans = vec![
         Spans::from(Span::styled(
             "Trippy Failed :(",
@@ -729,17 +2532,21 @@ fn render_bsod<B: Backend>(f: &mut Frame<'_, B>, rect: Rect, error: &str) {
         Spans::from(""),
         Spans::from(error),
         Spans::from(""),
-        Spans::from("Press q to quit "),
+        Spans::from("Press e to show events or q to quit"),
     ];
-    let paragraph = Paragraph::new(spans).alignment(Alignment::Center);
-    f.render_widget(block, rect);
-    f.render_widget(paragraph, chunks[1]);
+    let area = centered_rect(60, 30, f.size());
+    let paragraph = Paragraph::new(spans)
+        .block(block)
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true });
+    f.render_widget(Clear, area);
+    f.render_widget(paragraph, area);
 }
 
 /// Render the splash screen.
 ///
 /// This is shown on startup whilst we await the first round of data to be available.
-fn render_splash<B: Backend>(f: &mut Frame<'_, B>, rect: Rect) {
+fn render_splash<B: Backend>(f: &mut Frame<'_, B>, rect: Rect, theme: &Theme) {
     let chunks = Layout::default()
         .constraints([Constraint::Percentage(35), Constraint::Percentage(65)].as_ref())
         .split(rect);
@@ -747,7 +2554,8 @@ fn render_splash<B: Backend>(f: &mut Frame<'_, B>, rect: Rect) {
         .title("Hops")
         .borders(Borders::ALL)
         .border_type(BorderType::Rounded)
-        .style(Style::default());
+        .border_style(theme.border())
+        .style(theme.text());
     let splash = vec![
         r#" _____    _                "#,
         r#"|_   _| _(_)_ __ _ __ _  _ "#,
@@ -759,7 +2567,7 @@ fn render_splash<B: Backend>(f: &mut Frame<'_, B>, rect: Rect) {
     ];
     let spans: Vec<_> = splash
         .into_iter()
-        .map(|line| Spans::from(Span::styled(line, Style::default())))
+        .map(|line| Spans::from(Span::styled(line, theme.text())))
         .collect();
     let paragraph = Paragraph::new(spans).alignment(Alignment::Center);
     f.render_widget(block, rect);
@@ -781,98 +2589,343 @@ fn render_splash<B: Backend>(f: &mut Frame<'_, B>, rect: Rect) {
 /// - The worst round-trip time for all probes at this hop (`Wrst`)
 /// - The standard deviation round-trip time for all probes at this hop (`StDev`)
 /// - The status of this hop (`Sts`)
+///
+/// Any pinned hops are shown in a section above the table which does not scroll, always with their ttl.
 fn render_table<B: Backend>(f: &mut Frame<'_, B>, app: &mut TuiApp, rect: Rect) {
-    let header = render_table_header();
-    let selected_style = Style::default().add_modifier(Modifier::REVERSED);
-    let rows = app.tracer_data().hops().iter().map(|hop| {
-        render_table_row(
-            hop,
-            &app.resolver,
-            app.tracer_data().is_target(hop),
-            app.tracer_data().is_in_round(hop),
-            app.tui_config.address_mode,
-            app.tui_config.lookup_as_info,
-            app.tui_config.max_addrs,
-        )
+    let hops = app.hops();
+    let hop_rows = app.rows();
+    let heights: Vec<_> = hop_rows
+        .iter()
+        .map(|row| match row {
+            HopRow::Hop(index) => row_height(&hops[*index], app.tui_config.max_addrs),
+            HopRow::Unresponsive(_) => 1,
+        })
+        .collect();
+    let pinned = app.pinned_count();
+    let pinned_height = section_height(heights[..pinned].iter().sum(), rect.height);
+    // If there is no room for the pinned section the pinned hops are shown, and scrolled, at the top of the table.
+    let rect = if app.tui_config.columns.contains(TuiColumn::Segment)
+        && rect.height > pinned_height + TABLE_CHROME + 1
+    {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(1)].as_ref())
+            .split(rect);
+        let note = Paragraph::new(SEGMENT_NOTE).style(app.tui_config.theme.text_dim());
+        f.render_widget(note, chunks[1]);
+        chunks[0]
+    } else {
+        rect
+    };
+    let (pinned, rect) = if pinned_height > 0 {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(pinned_height), Constraint::Min(0)].as_ref())
+            .split(rect);
+        let widths = table_widths(app, true, chunks[0].width.saturating_sub(2));
+        let selected = app
+            .selection
+            .selected()
+            .filter(|selected| *selected < pinned);
+        let rows = hop_rows[..pinned].iter();
+        render_hop_rows(f, app, rows, &widths, "Pinned", None, selected, chunks[0]);
+        (pinned, chunks[1])
+    } else {
+        (0, rect)
+    };
+    let viewport = rect.height.saturating_sub(TABLE_CHROME);
+    app.selection.scroll(&heights, pinned, viewport);
+    let offset = app.selection.offset();
+    // The ttl is always shown whilst sorted such that the order of the path can still be seen.  The borders.
+    let widths = table_widths(app, app.sort.is_some(), rect.width.saturating_sub(2));
+    let selected = app
+        .selection
+        .selected()
+        .and_then(|selected| selected.checked_sub(pinned + offset));
+    let rows = hop_rows[pinned..].iter().skip(offset);
+    render_hop_rows(f, app, rows, &widths, "Hops", app.sort, selected, rect);
+}
+
+/// The widths of the columns of a hop table `table_width` wide, always including the ttl column if `ttl`.
+///
+/// In the compact layout of a narrow terminal the less important columns are dropped first.
+fn table_widths(app: &TuiApp, ttl: bool, table_width: u16) -> Vec<(TuiColumn, u16)> {
+    let columns = if ttl {
+        with_ttl(&app.tui_config.columns.0)
+    } else {
+        app.tui_config.columns.0.clone()
+    };
+    if app.layout().narrow {
+        allocate_widths(&compact_columns(&columns, table_width), table_width)
+    } else {
+        allocate_widths(&columns, table_width)
+    }
+}
+
+/// The `columns` with the ttl column first if it is not already shown.
+fn with_ttl(columns: &[TuiColumn]) -> Vec<TuiColumn> {
+    let mut columns = columns.to_vec();
+    if !columns.contains(&TuiColumn::Ttl) {
+        columns.insert(0, TuiColumn::Ttl);
+    }
+    columns
+}
+
+/// Render `rows` of the hop table in a bordered table with `title`, with the row at `selected` highlighted and the
+/// header of the column of the `sort`, if any, marked.
+#[allow(clippy::too_many_arguments)]
+fn render_hop_rows<'a, B: Backend>(
+    f: &mut Frame<'_, B>,
+    app: &TuiApp,
+    rows: impl Iterator<Item = &'a HopRow>,
+    widths: &[(TuiColumn, u16)],
+    title: &str,
+    sort: Option<HopSort>,
+    selected: Option<usize>,
+    rect: Rect,
+) {
+    let hops = app.hops();
+    let segments = segments(hops);
+    let (first_ttl, max_ttl) = app.ttl_range();
+    // Hostnames are not shown in the compact layout of a narrow terminal as there is no room for them.
+    let address_mode = if app.layout().narrow {
+        AddressMode::IP
+    } else {
+        app.tui_config.address_mode
+    };
+    let rows = rows.map(|row| match row {
+        HopRow::Hop(index) => {
+            let hop = &hops[*index];
+            render_table_row(
+                hop,
+                segments[*index],
+                &app.resolver,
+                app.tracer_data().is_target(hop),
+                app.tracer_data().is_in_round(hop) && (first_ttl..=max_ttl).contains(&hop.ttl()),
+                &app.tui_config,
+                address_mode,
+                widths,
+            )
+        }
+        HopRow::Unresponsive(range) => {
+            render_unresponsive_row(&hops[range.clone()], widths, &app.tui_config.theme)
+        }
     });
+    let constraints: Vec<_> = widths
+        .iter()
+        .map(|(_, width)| Constraint::Length(*width))
+        .collect();
     let table = Table::new(rows)
-        .header(header)
+        .header(render_table_header(
+            widths,
+            sort,
+            app.layout().narrow,
+            &app.tui_config.theme,
+        ))
         .block(
             Block::default()
                 .borders(Borders::ALL)
                 .border_type(BorderType::Rounded)
-                .title("Hops"),
+                .border_style(app.tui_config.theme.border())
+                .title(title.to_string()),
         )
-        .highlight_style(selected_style)
-        .widths(&TABLE_WIDTH);
-    f.render_stateful_widget(table, rect, &mut app.table_state);
+        .highlight_style(app.tui_config.theme.selected())
+        .column_spacing(COLUMN_SPACING)
+        .widths(&constraints);
+    let mut table_state = TableState::default();
+    table_state.select(selected);
+    f.render_stateful_widget(table, rect, &mut table_state);
 }
 
 /// Render the table header.
-fn render_table_header() -> Row<'static> {
-    let header_cells = TABLE_HEADER
-        .iter()
-        .map(|h| Cell::from(*h).style(Style::default().fg(Color::Black)));
+///
+/// The headers are shortened in the `compact` layout.
+fn render_table_header(
+    widths: &[(TuiColumn, u16)],
+    sort: Option<HopSort>,
+    compact: bool,
+    theme: &Theme,
+) -> Row<'static> {
+    let header = |column| {
+        if compact {
+            compact_header(column)
+        } else {
+            header(column)
+        }
+    };
+    let header_cells = widths.iter().map(|(column, _)| match sort {
+        Some(sort) if sort.column == *column => {
+            Cell::from(format!("{}{}", header(*column), sort.indicator()))
+        }
+        _ => Cell::from(header(*column)),
+    });
     Row::new(header_cells)
-        .style(Style::default().bg(Color::White))
+        .style(theme.header())
         .height(1)
         .bottom_margin(0)
 }
 
-/// Render a single row in the table of hops.
+/// Render a single row in the table of hops, with a cell for each of the columns shown and the hosts shown as per
+/// `address_mode`.
+#[allow(clippy::too_many_arguments)]
 fn render_table_row(
     hop: &Hop,
+    segment: Option<Segment>,
     dns: &DnsResolver,
     is_target: bool,
     is_in_round: bool,
+    config: &TuiConfig,
     address_mode: AddressMode,
-    lookup_as_info: bool,
-    max_addr: Option<u8>,
+    widths: &[(TuiColumn, u16)],
 ) -> Row<'static> {
-    let ttl_cell = render_ttl_cell(hop);
-    let hostname_cell = render_hostname_cell(hop, dns, address_mode, lookup_as_info, max_addr);
-    let loss_pct_cell = render_loss_pct_cell(hop);
-    let total_sent_cell = render_total_sent_cell(hop);
-    let total_recv_cell = render_total_recv_cell(hop);
-    let last_cell = render_last_cell(hop);
-    let avg_cell = render_avg_cell(hop);
-    let best_cell = render_best_cell(hop);
-    let worst_cell = render_worst_cell(hop);
-    let stddev_cell = render_stddev_cell(hop);
-    let status_cell = render_status_cell(hop, is_target);
-    let cells = [
-        ttl_cell,
-        hostname_cell,
-        loss_pct_cell,
-        total_sent_cell,
-        total_recv_cell,
-        last_cell,
-        avg_cell,
-        best_cell,
-        worst_cell,
-        stddev_cell,
-        status_cell,
-    ];
-    let row_height = hop
-        .addr_count()
-        .clamp(1, max_addr.unwrap_or(u8::MAX) as usize) as u16;
-    let row_color = if is_in_round {
-        Color::Gray
-    } else {
-        Color::DarkGray
+    let cells = widths.iter().map(|(column, width)| match column {
+        TuiColumn::Ttl => render_ttl_cell(hop),
+        TuiColumn::Host => render_hostname_cell(hop, dns, config, address_mode),
+        TuiColumn::LossPct => render_loss_pct_cell(hop, config),
+        TuiColumn::Sent => render_total_sent_cell(hop),
+        TuiColumn::Received => render_total_recv_cell(hop),
+        TuiColumn::Last => render_latency_cell(hop.last_ms(), config),
+        TuiColumn::Average => {
+            render_latency_cell((hop.total_recv() > 0).then(|| hop.avg_ms()), config)
+        }
+        TuiColumn::Best => render_best_cell(hop),
+        TuiColumn::Worst => render_latency_cell(hop.worst_ms(), config),
+        TuiColumn::StdDev => render_stddev_cell(hop),
+        TuiColumn::Jitter => render_jitter_cell(hop),
+        TuiColumn::Status => render_status_cell(hop, is_target),
+        TuiColumn::Sparkline => render_sparkline_cell(hop, *width, &config.theme),
+        TuiColumn::Heatmap => render_heatmap_cell(hop, *width, config),
+        TuiColumn::Icmp => render_icmp_cell(hop, &config.theme),
+        TuiColumn::Segment => render_segment_cell(segment, &config.theme),
+        TuiColumn::Asymmetry => render_asymmetry_cell(hop),
+        TuiColumn::LastSeen => render_last_seen_cell(hop),
+    });
+    let row_height = row_height(hop, config.max_addrs);
+    let row_style = match (is_in_round, is_target) {
+        (true, true) => Style::default().fg(config.theme.target),
+        (true, false) => config.theme.text(),
+        (false, _) => config.theme.text_dim(),
     };
     Row::new(cells)
         .height(row_height)
         .bottom_margin(0)
-        .style(Style::default().fg(row_color))
+        .style(row_style)
+}
+
+/// Render a run of hops which have not responded as a single row, i.e. `5-9` and `No response (5 hops)`.
+fn render_unresponsive_row(
+    hops: &[Hop],
+    widths: &[(TuiColumn, u16)],
+    theme: &Theme,
+) -> Row<'static> {
+    let first = hops.first().map_or(0, Hop::ttl);
+    let last = hops.last().map_or(0, Hop::ttl);
+    let cells = widths.iter().map(|(column, _)| match column {
+        TuiColumn::Ttl => Cell::from(format!("{first}-{last}")),
+        TuiColumn::Host => Cell::from(format!("No response ({} hops)", hops.len())),
+        _ => Cell::from(""),
+    });
+    Row::new(cells)
+        .height(1)
+        .bottom_margin(0)
+        .style(theme.text_dim())
+}
+
+/// Render the annotations of the destination unreachable responses to the recent probes of a hop, i.e. `!H !X`.
+fn render_icmp_cell(hop: &Hop, theme: &Theme) -> Cell<'static> {
+    let annotations = hop
+        .recent_unreachable()
+        .iter()
+        .filter_map(|(unreachable, _)| unreachable.annotation())
+        .unique()
+        .join(" ");
+    Cell::from(Span::styled(annotations, Style::default().fg(theme.warn)))
+}
+
+/// Render the estimated latency of the segment ending at a hop, or `≈0*` if the hop is faster than the one before it.
+fn render_segment_cell(segment: Option<Segment>, theme: &Theme) -> Cell<'static> {
+    match segment {
+        Some(segment) if segment.is_negative() => {
+            Cell::from(Span::styled("≈0*", Style::default().fg(theme.warn)))
+        }
+        Some(segment) => Cell::from(format!("{:.1}", segment.latency_ms())),
+        None => Cell::from(""),
+    }
+}
+
+/// Render the hops by which the return path from a hop is longer than the forward path, such as `+3`, if known.
+fn render_last_seen_cell(hop: &Hop) -> Cell<'static> {
+    Cell::from(
+        hop.since_last_recv(SystemTime::now())
+            .map(format_age)
+            .unwrap_or_default(),
+    )
+}
+
+fn render_asymmetry_cell(hop: &Hop) -> Cell<'static> {
+    Cell::from(match hop.asymmetry() {
+        Some(0) => String::from("0"),
+        Some(asymmetry) => format!("{asymmetry:+}"),
+        None => String::new(),
+    })
+}
+
+/// Render a sparkline of the newest `width` samples of a hop, with lost probes in the loss color.
+fn render_sparkline_cell(hop: &Hop, width: u16, theme: &Theme) -> Cell<'static> {
+    let spans: Vec<_> = sparkline(hop.samples(), usize::from(width))
+        .into_iter()
+        .map(|glyph| match glyph {
+            Glyph::Reply(_) => Span::raw(glyph.char().to_string()),
+            Glyph::Lost => Span::styled(glyph.char().to_string(), Style::default().fg(theme.loss)),
+        })
+        .collect();
+    Cell::from(Spans::from(spans))
+}
+
+/// Render a heatmap of the newest `width` rounds of a hop, one colored cell per round.
+fn render_heatmap_cell(hop: &Hop, width: u16, config: &TuiConfig) -> Cell<'static> {
+    let spans: Vec<_> = heatmap(hop.samples(), usize::from(width))
+        .map(|cell| {
+            let color = cell.color(config.heatmap_latency, &config.thresholds, &config.theme);
+            Span::styled(CELL, Style::default().fg(color))
+        })
+        .collect();
+    Cell::from(Spans::from(spans))
+}
+
+/// The number of lines of the row of a hop, one per address shown.
+fn row_height(hop: &Hop, max_addr: Option<u8>) -> u16 {
+    hop.addr_count()
+        .clamp(1, max_addr.unwrap_or(u8::MAX) as usize) as u16
 }
 
 fn render_ttl_cell(hop: &Hop) -> Cell<'static> {
     Cell::from(format!("{}", hop.ttl()))
 }
 
-fn render_loss_pct_cell(hop: &Hop) -> Cell<'static> {
-    Cell::from(format!("{:.1}%", hop.loss_pct()))
+/// Render the packet loss of a hop, colored if there is any loss, or dimmed if the hop is likely rate limiting ICMP and
+/// so the loss is likely not loss of the path.
+fn render_loss_pct_cell(hop: &Hop, config: &TuiConfig) -> Cell<'static> {
+    let severity = config.thresholds.loss(hop.loss_pct());
+    let cell = Cell::from(format!("{:.1}%", hop.loss_pct()));
+    if hop.rate_limited_likely() {
+        cell.style(Style::default().add_modifier(Modifier::DIM))
+    } else if severity == Severity::Good {
+        cell
+    } else {
+        cell.style(severity_style(severity, &config.theme))
+    }
+}
+
+/// Render a latency, colored by its severity.
+fn render_latency_cell(ms: Option<f64>, config: &TuiConfig) -> Cell<'static> {
+    ms.map_or_else(
+        || Cell::from(String::default()),
+        |ms| {
+            let severity = config.thresholds.latency(ms);
+            Cell::from(format!("{ms:.1}")).style(severity_style(severity, &config.theme))
+        },
+    )
 }
 
 fn render_total_sent_cell(hop: &Hop) -> Cell<'static> {
@@ -883,109 +2936,77 @@ fn render_total_recv_cell(hop: &Hop) -> Cell<'static> {
     Cell::from(format!("{}", hop.total_recv()))
 }
 
-fn render_avg_cell(hop: &Hop) -> Cell<'static> {
-    Cell::from(if hop.total_recv() > 0 {
-        format!("{:.1}", hop.avg_ms())
+/// Format a `DnsEntry` with or without `AS` information (if available)
+fn format_dns_entry(dns_entry: DnsEntry, lookup_as_info: bool) -> String {
+    match dns_entry {
+        DnsEntry::Resolved(Resolved::Normal(_, hosts)) => hosts.join(" "),
+        DnsEntry::Resolved(Resolved::WithAsInfo(_, hosts, asinfo)) => {
+            if lookup_as_info {
+                format!("AS{} {}", asinfo.asn, hosts.join(" "))
+            } else {
+                hosts.join(" ")
+            }
+        }
+        DnsEntry::Pending(ip) | DnsEntry::NotFound(ip) => format!("{ip}"),
+        DnsEntry::Failed(ip) => format!("Failed: {ip}"),
+        DnsEntry::Timeout(ip) => format!("Timeout: {ip}"),
+    }
+}
+
+/// Perform a reverse DNS lookup for an address and format the result.
+fn lookup_hostname(addr: IpAddr, dns: &DnsResolver, lookup_as_info: bool) -> String {
+    if lookup_as_info {
+        format_dns_entry(dns.reverse_lookup_with_asinfo(addr), true)
     } else {
-        String::default()
-    })
+        format_dns_entry(dns.reverse_lookup(addr), false)
+    }
 }
 
+/// Render the hosts of a hop, one per line and most frequent first, after a marker of the severity of the hop.
 fn render_hostname_cell(
     hop: &Hop,
     dns: &DnsResolver,
+    config: &TuiConfig,
     address_mode: AddressMode,
-    lookup_as_info: bool,
-    max_addr: Option<u8>,
 ) -> Cell<'static> {
-    /// Format a `DnsEntry` with or without `AS` information (if available)
-    fn format_dns_entry(dns_entry: DnsEntry, lookup_as_info: bool) -> String {
-        match dns_entry {
-            DnsEntry::Resolved(Resolved::Normal(_, hosts)) => hosts.join(" "),
-            DnsEntry::Resolved(Resolved::WithAsInfo(_, hosts, asinfo)) => {
-                if lookup_as_info {
-                    format!("AS{} {}", asinfo.asn, hosts.join(" "))
-                } else {
-                    hosts.join(" ")
-                }
-            }
-            DnsEntry::Pending(ip) | DnsEntry::NotFound(ip) => format!("{ip}"),
-            DnsEntry::Failed(ip) => format!("Failed: {ip}"),
-            DnsEntry::Timeout(ip) => format!("Timeout: {ip}"),
-        }
-    }
-    /// Perform a reverse DNS lookup for an address and format the result.
-    fn format_address(
-        addr: &IpAddr,
-        freq: usize,
-        hop: &Hop,
-        dns: &DnsResolver,
-        address_mode: AddressMode,
-        lookup_as_info: bool,
-    ) -> String {
-        let addr_fmt = match address_mode {
-            AddressMode::IP => addr.to_string(),
-            AddressMode::Host => {
-                if lookup_as_info {
-                    let entry = dns.reverse_lookup_with_asinfo(*addr);
-                    format_dns_entry(entry, true)
-                } else {
-                    let entry = dns.reverse_lookup(*addr);
-                    format_dns_entry(entry, false)
-                }
-            }
-            AddressMode::Both => {
-                let hostname = if lookup_as_info {
-                    let entry = dns.reverse_lookup_with_asinfo(*addr);
-                    format_dns_entry(entry, true)
-                } else {
-                    let entry = dns.reverse_lookup(*addr);
-                    format_dns_entry(entry, false)
+    let hosts = if hop.total_recv() > 0 {
+        let addrs = ranked_addrs(hop);
+        let shown = config.max_addrs.map_or(addrs.len(), |max_addrs| {
+            usize::from(max_addrs).min(addrs.len())
+        });
+        let hosts = addrs[..shown]
+            .iter()
+            .map(|(addr, count)| {
+                let hostname = match address_mode {
+                    AddressMode::IP => String::new(),
+                    AddressMode::Host | AddressMode::Both => {
+                        lookup_hostname(*addr, dns, config.lookup_as_info)
+                    }
                 };
-                format!("{hostname} ({addr})")
-            }
-        };
-
-        if hop.addr_count() > 1 {
-            format!(
-                "{} [{:.1}%]",
-                addr_fmt,
-                (freq as f64 / hop.total_recv() as f64) * 100_f64
-            )
-        } else {
-            addr_fmt
-        }
-    }
-
-    Cell::from(if hop.total_recv() > 0 {
-        match max_addr {
-            None => hop
-                .addrs_with_counts()
-                .map(|(addr, &freq)| {
-                    format_address(addr, freq, hop, dns, address_mode, lookup_as_info)
-                })
-                .join("\n"),
-            Some(max_addr) => hop
-                .addrs_with_counts()
-                .sorted_unstable_by_key(|(_, &cnt)| cnt)
-                .rev()
-                .take(max_addr as usize)
-                .map(|(addr, &freq)| {
-                    format_address(addr, freq, hop, dns, address_mode, lookup_as_info)
-                })
-                .join("\n"),
-        }
+                let share = (addrs.len() > 1).then(|| share_pct(*count, hop.total_recv()));
+                format_host(address_mode, &hostname, *addr, share)
+            })
+            .collect();
+        with_hidden(hosts, addrs.len() - shown).join("\n")
     } else {
         String::from("No response")
-    })
-}
-
-fn render_last_cell(hop: &Hop) -> Cell<'static> {
-    Cell::from(
-        hop.last_ms()
-            .map(|last| format!("{last:.1}"))
-            .unwrap_or_default(),
-    )
+    };
+    let severity = config.thresholds.hop(hop);
+    let marker = config.thresholds.marker(severity);
+    let indent = " ".repeat(marker.chars().count());
+    let lines: Vec<_> = hosts
+        .lines()
+        .enumerate()
+        .map(|(i, host)| {
+            let prefix = if i == 0 {
+                Span::styled(marker, severity_style(severity, &config.theme))
+            } else {
+                Span::raw(indent.clone())
+            };
+            Spans::from(vec![prefix, Span::raw(host.to_string())])
+        })
+        .collect();
+    Cell::from(Text::from(lines))
 }
 
 fn render_best_cell(hop: &Hop) -> Cell<'static> {
@@ -996,14 +3017,6 @@ fn render_best_cell(hop: &Hop) -> Cell<'static> {
     )
 }
 
-fn render_worst_cell(hop: &Hop) -> Cell<'static> {
-    Cell::from(
-        hop.worst_ms()
-            .map(|worst| format!("{worst:.1}"))
-            .unwrap_or_default(),
-    )
-}
-
 fn render_stddev_cell(hop: &Hop) -> Cell<'static> {
     Cell::from(if hop.total_recv() > 1 {
         format!("{:.1}", hop.stddev_ms())
@@ -1012,6 +3025,14 @@ fn render_stddev_cell(hop: &Hop) -> Cell<'static> {
     })
 }
 
+fn render_jitter_cell(hop: &Hop) -> Cell<'static> {
+    Cell::from(if hop.total_recv() > 1 {
+        format!("{:.1}", hop.jitter_ms())
+    } else {
+        String::default()
+    })
+}
+
 fn render_status_cell(hop: &Hop, is_target: bool) -> Cell<'static> {
     let lost = hop.total_sent() - hop.total_recv();
     Cell::from(match (lost, is_target) {
@@ -1023,6 +3044,164 @@ fn render_status_cell(hop: &Hop, is_target: bool) -> Cell<'static> {
     })
 }
 
+/// The lines of the sent, received and lost probes of a hop and of its round trip times.
+fn render_detail_stats(hop: &Hop) -> Vec<Spans<'static>> {
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let mut lines = vec![Spans::from(vec![
+        Span::styled("Sent: ", bold),
+        Span::raw(format!(
+            "{}  Recv: {}  Loss: {:.1}%",
+            hop.total_sent(),
+            hop.total_recv(),
+            hop.loss_pct()
+        )),
+    ])];
+    if let Some(failure) = hop.last_failure() {
+        lines.push(Spans::from(vec![
+            Span::styled("Failed: ", bold),
+            Span::raw(format!(
+                "{} not sent, not counted as loss, last: {failure}",
+                hop.total_failed()
+            )),
+        ]));
+    }
+    if hop.rate_limited_likely() {
+        lines.push(Spans::from(vec![
+            Span::styled("Loss: ", bold),
+            Span::styled(
+                "likely rate-limited, not path loss",
+                Style::default().add_modifier(Modifier::DIM),
+            ),
+        ]));
+    }
+    lines.push(Spans::from(vec![
+        Span::styled("RTT: ", bold),
+        Span::raw(format!(
+            "last {}  avg {:.1}  best {}  worst {}  stddev {:.1} ms",
+            format_ms(hop.last_ms()),
+            hop.avg_ms(),
+            format_ms(hop.best_ms()),
+            format_ms(hop.worst_ms()),
+            hop.stddev_ms()
+        )),
+    ]));
+    lines
+}
+
+/// Render the details of the selected hop, or of the target hop if no hop is selected.
+fn render_detail<B: Backend>(f: &mut Frame<'_, B>, app: &TuiApp, rect: Rect) {
+    let hop = app.selected_hop();
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let mut lines = render_detail_stats(hop);
+    if hop.total_sent() > 0 {
+        lines.push(Spans::from(vec![
+            Span::styled("Last seen: ", bold),
+            Span::raw(hop.since_last_recv(SystemTime::now()).map_or_else(
+                || String::from("never"),
+                |age| format!("{} ago", format_age(age)),
+            )),
+        ]));
+    }
+    if let Some(offset) = hop.clock_offset_ms() {
+        lines.push(Spans::from(vec![
+            Span::styled("Clock offset: ", bold),
+            Span::raw(format!("{offset:+} ms (remote clock, experimental)")),
+        ]));
+    }
+    let recent = hop.recent_unreachable();
+    lines.extend(hop.unreachable().into_iter().map(|(unreachable, total)| {
+        let count = recent
+            .iter()
+            .find(|(other, _)| *other == unreachable)
+            .map_or(0, |(_, count)| *count);
+        Spans::from(vec![
+            Span::styled("ICMP: ", bold),
+            Span::styled(
+                unreachable
+                    .annotation()
+                    .map(|annotation| format!("{annotation} "))
+                    .unwrap_or_default(),
+                Style::default().fg(app.tui_config.theme.warn),
+            ),
+            Span::raw(format!("{unreachable} count={total} recent={count}")),
+        ])
+    }));
+    lines.extend([
+        Spans::from(""),
+        Spans::from(Span::styled("Addresses:", bold)),
+    ]);
+    if hop.addr_count() == 0 {
+        lines.push(Spans::from("  No response"));
+    }
+    lines.extend(
+        hop.addrs_with_details()
+            .sorted_unstable_by_key(|(addr, details)| (Reverse(details.count()), **addr))
+            .map(|(addr, details)| {
+                let hostname = format_dns_entry(
+                    app.resolver.reverse_lookup(*addr),
+                    app.tui_config.lookup_as_info,
+                );
+                let source = app
+                    .resolver
+                    .reverse_lookup_source(*addr)
+                    .map(|source| format!(" ({source})"))
+                    .unwrap_or_default();
+                let count = details.count();
+                let pct = share_pct(count, hop.total_recv());
+                Spans::from(format!(
+                    "  {addr} {hostname}{source} count={count} ({pct}%)  last {}  best {}  worst {} ms{}",
+                    format_ms(details.last_ms()),
+                    format_ms(details.best_ms()),
+                    format_ms(details.worst_ms()),
+                    format_quote(details.quote())
+                ))
+            }),
+    );
+    let samples = hop
+        .samples()
+        .iter()
+        .map(|sample| {
+            if *sample == Duration::ZERO {
+                String::from("*")
+            } else {
+                format!("{:.1}", sample.as_secs_f64() * 1000_f64)
+            }
+        })
+        .join(" ");
+    lines.extend([
+        Spans::from(""),
+        Spans::from(Span::styled("Samples (ms, newest first):", bold)),
+        Spans::from(format!("  {samples}")),
+    ]);
+    let detail = Paragraph::new(lines)
+        .style(app.tui_config.theme.text())
+        .wrap(Wrap { trim: false })
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .border_style(app.tui_config.theme.border())
+                .title(format!("Hop #{}", hop.ttl())),
+        );
+    f.render_widget(detail, rect);
+}
+
+/// Format an optional latency, in milliseconds.
+fn format_ms(ms: Option<f64>) -> String {
+    ms.map_or_else(|| String::from("-"), |ms| format!("{ms:.1}"))
+}
+
+/// Format the size of the last ICMP error from an address and of the probe quoted in it, if any.
+fn format_quote(quote: Option<IcmpQuote>) -> String {
+    quote.map_or_else(String::new, |quote| {
+        let kind = if quote.full { "full" } else { "partial" };
+        format!(
+            "  quote {kind} {}/{} bytes",
+            quote.quoted_len, quote.payload_len
+        )
+    })
+}
+
 /// Render the footer.
 ///
 /// This contains the history and frequency charts.
@@ -1033,17 +3212,170 @@ fn render_footer<B: Backend>(f: &mut Frame<'_, B>, rec: Rect, app: &mut TuiApp)
         .split(rec);
     render_history(f, app, bottom_chunks[0]);
     render_ping_frequency(f, app, bottom_chunks[1]);
+}
+
+/// Render the status bar, a recent note or the most recent warning or error, if any, and the refresh rate.
+fn render_status_bar<B: Backend>(f: &mut Frame<'_, B>, app: &TuiApp, rect: Rect) {
+    let theme = &app.tui_config.theme;
+    // Indicate that data is hidden in the compact layout.
+    let refresh = format!(
+        "{}{} refresh {}",
+        if app.layout().is_compact() {
+            " compact"
+        } else {
+            ""
+        },
+        if app.selection.is_following() {
+            " follow"
+        } else {
+            ""
+        },
+        humantime::format_duration(app.tui_config.refresh_rate)
+    );
+    // Warn that responses were discarded as the wall clock was adjusted whilst they were in flight, or as their ttl
+    // was not that of any hop.
+    let clock_anomalies = match app.selected_tracer_data.clock_anomalies() {
+        0 => String::new(),
+        1 => String::from(" 1 clock anomaly"),
+        count => format!(" {count} clock anomalies"),
+    };
+    let invalid_ttls = match app.selected_tracer_data.invalid_ttls() {
+        0 => String::new(),
+        1 => String::from(" 1 invalid ttl"),
+        count => format!(" {count} invalid ttls"),
+    };
+    // Warn that probes could not be sent, a local failure which is not counted as loss, with the reason of the last.
+    let hops = app.selected_tracer_data.hops();
+    let send_failures = match hops.iter().map(Hop::total_failed).sum::<usize>() {
+        0 => String::new(),
+        count => {
+            let reason = hops
+                .iter()
+                .rev()
+                .find_map(Hop::last_failure)
+                .map_or_else(String::new, |failure| format!(": {failure}"));
+            if count == 1 {
+                format!(" 1 send failure{reason}")
+            } else {
+                format!(" {count} send failures{reason}")
+            }
+        }
+    };
+    let anomalies = format!("{clock_anomalies}{invalid_ttls}{send_failures}");
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            [
+                Constraint::Min(0),
+                Constraint::Length((anomalies.len() + refresh.len()) as u16),
+            ]
+            .as_ref(),
+        )
+        .split(rect);
+    let events = app.events.read();
+    let line = match (app.status_note(), events.latest()) {
+        (Some(note), _) => Spans::from(Span::styled(note.to_string(), theme.text())),
+        (None, Some(event)) => {
+            let style = Style::default().fg(match event.level {
+                EventLevel::Alert => theme.target,
+                EventLevel::Warn => theme.warn,
+                EventLevel::Error => theme.crit,
+            });
+            Spans::from(vec![
+                Span::styled(format_event(event), style),
+                Span::styled(
+                    format!(" ({} events, e to show)", events.len()),
+                    theme.text_dim(),
+                ),
+            ])
+        }
+        (None, None) => Spans::from(Span::styled("No warnings", theme.text_dim())),
+    };
+    f.render_widget(Paragraph::new(line), chunks[0]);
+    let status = Spans::from(vec![
+        Span::styled(anomalies, Style::default().fg(theme.warn)),
+        Span::styled(refresh, theme.text_dim()),
+    ]);
+    f.render_widget(Paragraph::new(status), chunks[1]);
+}
+
+/// Render the history of events, oldest first.
+fn render_events<B: Backend>(f: &mut Frame<'_, B>, app: &mut TuiApp) {
+    let theme = app.tui_config.theme;
+    let block = Block::default()
+        .title(" Events ")
+        .title_alignment(Alignment::Center)
+        .borders(Borders::ALL)
+        .style(theme.popup())
+        .border_type(BorderType::Double);
+    let area = centered_rect(80, 60, f.size());
+    let lines: Vec<_> = {
+        let events = app.events.read();
+        if events.is_empty() {
+            vec![Spans::from("No events")]
+        } else {
+            events
+                .events()
+                .map(|event| Spans::from(format_event(event)))
+                .collect()
+        }
+    };
+    let height = usize::from(block.inner(area).height);
+    app.events_scroll = app.events_scroll.min(lines.len().saturating_sub(height));
+    let scroll = u16::try_from(app.events_scroll).unwrap_or(u16::MAX);
+    let paragraph = Paragraph::new(lines)
+        .block(block)
+        .alignment(Alignment::Left)
+        .scroll((scroll, 0));
+    f.render_widget(Clear, area);
+    f.render_widget(paragraph, area);
+}
+
+/// Format an event, i.e. `12:01:02 warn: reverse DNS lookup of 10.0.0.1 timed out (repeated 2 times)`.
+fn format_event(event: &TraceEvent) -> String {
+    let time = event.time.format("%H:%M:%S");
+    let repeats = match event.repeats {
+        0 => String::new(),
+        1 => String::from(" (repeated once)"),
+        n => format!(" (repeated {n} times)"),
+    };
+    format!("{time} {}: {}{repeats}", event.level, event.message)
+}
+
+/// Render the popups, above the rest of the TUI.
+///
+/// The dialog telling the user that tracing has stopped is shown unless another popup has been opened over it.
+fn render_popups<B: Backend>(f: &mut Frame<'_, B>, app: &mut TuiApp) {
     if app.show_help {
-        render_help(f);
+        render_help(f, app);
+    } else if app.show_events {
+        render_events(f, app);
+    } else if app.show_column_settings {
+        render_column_settings(f, app);
+    } else if app.show_settings {
+        render_settings(f, app);
+    } else if app.show_export {
+        render_export(f, app);
+    } else if let Some(input) = &app.target_input {
+        render_target_input(f, input, &app.tui_config.theme);
+    } else if app.confirm_unpin {
+        render_confirm_unpin(f, app);
+    } else if let Some((addr, shown)) = app.rdap_shown {
+        render_rdap(
+            f,
+            addr,
+            app.rdap.get(addr),
+            shown.elapsed(),
+            &app.tui_config.theme,
+        );
+    } else if let Some(err) = app.selected_tracer_data.error() {
+        render_bsod(f, err, &app.tui_config.theme);
     }
 }
 
 /// Render the ping history for the final hop which is typically the target.
 fn render_history<B: Backend>(f: &mut Frame<'_, B>, app: &mut TuiApp, rect: Rect) {
-    let target_hop = app.table_state.selected().map_or_else(
-        || app.tracer_data().target_hop(),
-        |s| &app.tracer_data().hops()[s],
-    );
+    let target_hop = app.selected_hop();
     let data = target_hop
         .samples()
         .iter()
@@ -1055,19 +3387,17 @@ fn render_history<B: Backend>(f: &mut Frame<'_, B>, app: &mut TuiApp, rect: Rect
             Block::default()
                 .title(format!("Samples #{}", target_hop.ttl()))
                 .borders(Borders::ALL)
-                .border_type(BorderType::Rounded),
+                .border_type(BorderType::Rounded)
+                .border_style(app.tui_config.theme.border()),
         )
         .data(&data)
-        .style(Style::default().fg(Color::Yellow));
+        .style(Style::default().fg(app.tui_config.theme.history));
     f.render_widget(history, rect);
 }
 
 /// Render a histogram of ping frequencies.
 fn render_ping_frequency<B: Backend>(f: &mut Frame<'_, B>, app: &mut TuiApp, rect: Rect) {
-    let target_hop = app.table_state.selected().map_or_else(
-        || app.tracer_data().target_hop(),
-        |s| &app.tracer_data().hops()[s],
-    );
+    let target_hop = app.selected_hop();
     let freq_data = sample_frequency(target_hop.samples());
     let freq_data_ref: Vec<_> = freq_data.iter().map(|(b, c)| (b.as_str(), *c)).collect();
     let barchart = BarChart::default()
@@ -1075,37 +3405,346 @@ fn render_ping_frequency<B: Backend>(f: &mut Frame<'_, B>, app: &mut TuiApp, rec
             Block::default()
                 .title(format!("Frequency #{}", target_hop.ttl()))
                 .borders(Borders::ALL)
-                .border_type(BorderType::Rounded),
+                .border_type(BorderType::Rounded)
+                .border_style(app.tui_config.theme.border()),
         )
         .data(freq_data_ref.as_slice())
         .bar_width(4)
         .bar_gap(1)
-        .bar_style(Style::default().fg(Color::Green))
+        .bar_style(Style::default().fg(app.tui_config.theme.chart))
         .value_style(
             Style::default()
-                .bg(Color::Green)
+                .bg(app.tui_config.theme.chart)
                 .add_modifier(Modifier::BOLD),
-        );
+        )
+        .label_style(app.tui_config.theme.text());
     f.render_widget(barchart, rect);
 }
 
-/// Render help
-fn render_help<B: Backend>(f: &mut Frame<'_, B>) {
+/// Render the settings, the value of each setting with the cursor on one of them.
+fn render_settings<B: Backend>(f: &mut Frame<'_, B>, app: &TuiApp) {
+    let block = Block::default()
+        .title(" Settings ")
+        .title_alignment(Alignment::Center)
+        .borders(Borders::ALL)
+        .style(app.tui_config.theme.popup())
+        .border_type(BorderType::Double);
+    let mut lines: Vec<_> = Setting::ALL
+        .iter()
+        .enumerate()
+        .map(|(i, setting)| {
+            let line = format!(
+                "{:<14} < {} >",
+                setting.label(),
+                setting_value(app, *setting)
+            );
+            if i == app.settings_cursor {
+                Spans::from(Span::styled(
+                    line,
+                    Style::default().add_modifier(Modifier::REVERSED),
+                ))
+            } else {
+                Spans::from(line)
+            }
+        })
+        .collect();
+    lines.extend([
+        Spans::from(""),
+        Spans::from("up/down to select, left/right to change, any other key to close"),
+    ]);
+    let area = centered_rect(50, 40, f.size());
+    let settings = Paragraph::new(lines)
+        .block(block)
+        .wrap(Wrap { trim: false });
+    f.render_widget(Clear, area);
+    f.render_widget(settings, area);
+}
+
+/// Render the formats in which the trace may be exported, with the cursor on one of them.
+fn render_export<B: Backend>(f: &mut Frame<'_, B>, app: &TuiApp) {
+    let block = Block::default()
+        .title(" Export ")
+        .title_alignment(Alignment::Center)
+        .borders(Borders::ALL)
+        .style(app.tui_config.theme.popup())
+        .border_type(BorderType::Double);
+    let mut lines: Vec<_> = ExportFormat::ALL
+        .iter()
+        .enumerate()
+        .map(|(i, format)| {
+            if i == app.export_cursor {
+                Spans::from(Span::styled(
+                    format.label(),
+                    Style::default().add_modifier(Modifier::REVERSED),
+                ))
+            } else {
+                Spans::from(format.label())
+            }
+        })
+        .collect();
+    lines.extend([
+        Spans::from(""),
+        Spans::from(format!("to {}", app.tui_config.export_dir.display())),
+        Spans::from("up/down to select, enter to export, any other key to close"),
+    ]);
+    let area = centered_rect(50, 40, f.size());
+    let export = Paragraph::new(lines)
+        .block(block)
+        .wrap(Wrap { trim: false });
+    f.render_widget(Clear, area);
+    f.render_widget(export, area);
+}
+
+/// Render the question of whether to unpin the pinned hops after the statistics were reset.
+fn render_confirm_unpin<B: Backend>(f: &mut Frame<'_, B>, app: &TuiApp) {
+    let block = Block::default()
+        .title(" Statistics reset ")
+        .title_alignment(Alignment::Center)
+        .borders(Borders::ALL)
+        .style(app.tui_config.theme.popup())
+        .border_type(BorderType::Double);
+    let lines = vec![
+        Spans::from(match app.pins.len() {
+            1 => String::from("Also unpin the pinned hop?"),
+            pins => format!("Also unpin the {pins} pinned hops?"),
+        }),
+        Spans::from(""),
+        Spans::from("y to unpin, any other key to keep them pinned"),
+    ];
+    let area = centered_rect(50, 20, f.size());
+    let confirm = Paragraph::new(lines)
+        .block(block)
+        .wrap(Wrap { trim: false });
+    f.render_widget(Clear, area);
+    f.render_widget(confirm, area);
+}
+
+/// Render the RDAP registration of the network of `addr`, a spinner whilst it is looked up or the error if it failed.
+fn render_rdap<B: Backend>(
+    f: &mut Frame<'_, B>,
+    addr: IpAddr,
+    lookup: Lookup<'_>,
+    elapsed: Duration,
+    theme: &Theme,
+) {
+    let block = Block::default()
+        .title(format!(" RDAP {addr} "))
+        .title_alignment(Alignment::Center)
+        .borders(Borders::ALL)
+        .style(theme.popup())
+        .border_type(BorderType::Double);
+    let field = |label: &str, value: &Option<String>| {
+        Spans::from(vec![
+            Span::styled(format!("{label:<14}"), theme.header()),
+            Span::raw(value.clone().unwrap_or_else(|| String::from("-"))),
+        ])
+    };
+    let mut lines = match lookup {
+        Lookup::Pending => vec![Spans::from(format!("{} looking up…", spinner(elapsed)))],
+        Lookup::Found(info) => vec![
+            field("Network", &info.netname),
+            field("Organisation", &info.org),
+            field("Abuse contact", &info.abuse),
+            field("Prefix", &info.prefix),
+        ],
+        Lookup::Failed(err) => vec![Spans::from(Span::styled(
+            err.to_string(),
+            Style::default().fg(theme.crit),
+        ))],
+    };
+    lines.push(Spans::from(""));
+    lines.push(Spans::from("any key to close"));
+    let area = centered_rect(60, 30, f.size());
+    let rdap = Paragraph::new(lines)
+        .block(block)
+        .wrap(Wrap { trim: false });
+    f.render_widget(Clear, area);
+    f.render_widget(rdap, area);
+}
+
+/// Render the dialog in which a target to trace is entered.
+fn render_target_input<B: Backend>(f: &mut Frame<'_, B>, input: &TargetInput, theme: &Theme) {
+    let block = Block::default()
+        .title(" Trace target ")
+        .title_alignment(Alignment::Center)
+        .borders(Borders::ALL)
+        .style(theme.popup())
+        .border_type(BorderType::Double);
+    let status = if input.is_resolving() {
+        Spans::from(format!("resolving {}...", input.text()))
+    } else if let Some(err) = input.error() {
+        Spans::from(Span::styled(
+            err.to_string(),
+            Style::default().fg(theme.crit),
+        ))
+    } else {
+        Spans::from("")
+    };
+    let lines = vec![
+        Spans::from(format!("> {}_", input.text())),
+        status,
+        Spans::from(""),
+        Spans::from(format!(
+            "{} (tab to change), enter to trace, esc to close",
+            input.action().label()
+        )),
+    ];
+    let area = centered_rect(50, 25, f.size());
+    let dialog = Paragraph::new(lines)
+        .block(block)
+        .wrap(Wrap { trim: false });
+    f.render_widget(Clear, area);
+    f.render_widget(dialog, area);
+}
+
+/// The current value of a setting.
+fn setting_value(app: &TuiApp, setting: Setting) -> String {
+    let config = &app.tui_config;
+    match setting {
+        Setting::RefreshRate => humantime::format_duration(config.refresh_rate).to_string(),
+        Setting::AddressMode => format_address_mode(config.address_mode),
+        Setting::MaxAddrs => config
+            .max_addrs
+            .map_or_else(|| String::from("all"), |max| max.to_string()),
+        Setting::Theme => config
+            .theme_name
+            .to_possible_value()
+            .map_or_else(String::new, |value| value.get_name().to_string()),
+        Setting::Columns => config
+            .columns
+            .0
+            .iter()
+            .map(|column| column.to_char())
+            .collect(),
+    }
+}
+
+/// Render the column settings, a checkbox per column.
+fn render_column_settings<B: Backend>(f: &mut Frame<'_, B>, app: &TuiApp) {
+    let block = Block::default()
+        .title(" Columns ")
+        .title_alignment(Alignment::Center)
+        .borders(Borders::ALL)
+        .style(app.tui_config.theme.popup())
+        .border_type(BorderType::Double);
+    let lines: Vec<_> = TuiColumn::ALL
+        .iter()
+        .enumerate()
+        .map(|(i, column)| {
+            let checkbox = if app.tui_config.columns.contains(*column) {
+                "[x]"
+            } else {
+                "[ ]"
+            };
+            let line = format!("{checkbox} {} ({})", column_name(*column), column.to_char());
+            if i == app.column_cursor {
+                Spans::from(Span::styled(
+                    line,
+                    Style::default().add_modifier(Modifier::REVERSED),
+                ))
+            } else {
+                Spans::from(line)
+            }
+        })
+        .collect();
+    let area = centered_rect(40, 60, f.size());
+    let settings = Paragraph::new(lines).block(block);
+    f.render_widget(Clear, area);
+    f.render_widget(settings, area);
+}
+
+/// The name of a column, as accepted by `--tui-custom-columns`.
+fn column_name(column: TuiColumn) -> String {
+    column
+        .to_possible_value()
+        .map_or_else(String::new, |value| value.get_name().to_string())
+}
+
+/// Render the help dialog.
+///
+/// The key bindings are listed by category and scroll if they do not fit, the version and basic configuration are
+/// always shown at the bottom.
+fn render_help<B: Backend>(f: &mut Frame<'_, B>, app: &mut TuiApp) {
     let block = Block::default()
         .title(" Controls ")
         .title_alignment(Alignment::Center)
         .borders(Borders::ALL)
-        .style(Style::default().bg(Color::Blue))
+        .style(app.tui_config.theme.popup())
         .border_type(BorderType::Double);
-    let control_spans: Vec<_> = HELP_LINES.iter().map(|&line| Spans::from(line)).collect();
-    let control = Paragraph::new(control_spans)
-        .style(Style::default())
-        .block(block.clone())
-        .alignment(Alignment::Left);
-    let area = centered_rect(50, 50, f.size());
+    let area = centered_rect(60, 80, f.size());
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(4)].as_ref())
+        .split(block.inner(area));
+    let lines = help_lines(&app.tui_config.key_bindings);
+    let max_scroll = lines.len().saturating_sub(usize::from(chunks[0].height));
+    app.help_scroll = app.help_scroll.min(max_scroll);
+    let scroll = u16::try_from(app.help_scroll).unwrap_or(u16::MAX);
+    let controls = Paragraph::new(lines)
+        .style(app.tui_config.theme.popup())
+        .alignment(Alignment::Left)
+        .scroll((scroll, 0));
+    let footer = Paragraph::new(vec![
+        Spans::from(Span::styled(
+            format!("Trippy v{}", clap::crate_version!()),
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+        Spans::from(format!(
+            "refresh-rate={} address-mode={}",
+            humantime::format_duration(app.tui_config.refresh_rate),
+            format_address_mode(app.tui_config.address_mode)
+        )),
+        Spans::from(trippy_core::capabilities().to_string()),
+        Spans::from(trippy_core::capabilities().features()),
+    ])
+    .alignment(Alignment::Left);
     f.render_widget(Clear, area);
     f.render_widget(block, area);
-    f.render_widget(control, area);
+    f.render_widget(controls, chunks[0]);
+    f.render_widget(footer, chunks[1]);
+}
+
+/// The lines of the help dialog, the effective key bindings grouped by category.
+fn help_lines(key_bindings: &KeyBindings) -> Vec<Spans<'static>> {
+    let mut lines = Vec::new();
+    for (category, bindings) in key_bindings.by_category() {
+        if !lines.is_empty() {
+            lines.push(Spans::from(""));
+        }
+        lines.push(Spans::from(Span::styled(
+            category.to_string(),
+            Style::default().add_modifier(Modifier::BOLD),
+        )));
+        lines.extend(
+            bindings
+                .into_iter()
+                .map(|(keys, description)| Spans::from(format!("  {keys:<16} - {description}"))),
+        );
+    }
+    lines
+}
+
+/// Scroll a dialog of lines by a line or a page for the up, down, page up and page down keys, or to the start or end
+/// of the dialog for the home and end keys.
+fn scroll_lines(scroll: usize, key: KeyCode) -> usize {
+    match key {
+        KeyCode::Up => scroll.saturating_sub(1),
+        KeyCode::Down => scroll.saturating_add(1),
+        KeyCode::PageUp => scroll.saturating_sub(HELP_PAGE_LINES),
+        KeyCode::PageDown => scroll.saturating_add(HELP_PAGE_LINES),
+        KeyCode::Home => 0,
+        KeyCode::End => usize::MAX,
+        _ => scroll,
+    }
+}
+
+/// Format the `AddressMode`.
+fn format_address_mode(address_mode: AddressMode) -> String {
+    match address_mode {
+        AddressMode::IP => String::from("ip"),
+        AddressMode::Host => String::from("host"),
+        AddressMode::Both => String::from("both"),
+    }
 }
 
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
@@ -0,0 +1,866 @@
+use anyhow::anyhow;
+use clap::ValueEnum;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use itertools::Itertools;
+use std::fmt::{Display, Formatter};
+
+/// A command which may be performed from the TUI.
+///
+/// The kebab case name of a command, i.e. `toggle-chart`, is the action which is bound to a key with
+/// `--tui-key-binding`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum TuiCommand {
+    NextHop,
+    PreviousHop,
+    NextHopPage,
+    PreviousHopPage,
+    FirstHop,
+    LastHop,
+    ToggleFollow,
+    NextTrace,
+    PreviousTrace,
+    NextFlow,
+    PreviousFlow,
+    ClearSelection,
+    ToggleDetail,
+    ToggleChart,
+    ToggleHistogram,
+    ToggleCompare,
+    CompareHop,
+    MoreBins,
+    FewerBins,
+    ToggleFreeze,
+    ToggleMap,
+    AddressModeIp,
+    AddressModeHost,
+    AddressModeBoth,
+    CycleAddressMode,
+    ToggleAsInfo,
+    ToggleSparkline,
+    ToggleHeatmap,
+    ToggleHeatmapLatency,
+    ToggleUnresponsive,
+    ToggleHistoricHops,
+    TogglePin,
+    ToggleColumnSettings,
+    ResetSort,
+    ToggleCompact,
+    ShowRdap,
+    ToggleSettings,
+    FasterRefresh,
+    SlowerRefresh,
+    ExpandHosts,
+    ContractHosts,
+    ExpandHostsMax,
+    ContractHostsMin,
+    ZoomIn,
+    ZoomOut,
+    ResetStatistics,
+    RaiseFirstTtl,
+    LowerFirstTtl,
+    LowerMaxTtl,
+    RaiseMaxTtl,
+    ResetTtlRange,
+    FlushDnsCache,
+    CopyAddr,
+    CopyHosts,
+    ToggleExport,
+    AddTarget,
+    ToggleHelp,
+    ToggleEvents,
+    Quit,
+}
+
+/// The category of a key binding, used to group the bindings in the help dialog.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum KeyCategory {
+    Navigation,
+    Display,
+    Actions,
+}
+
+impl KeyCategory {
+    const ALL: [Self; 3] = [Self::Navigation, Self::Display, Self::Actions];
+}
+
+impl Display for KeyCategory {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Navigation => write!(f, "Navigation"),
+            Self::Display => write!(f, "Display"),
+            Self::Actions => write!(f, "Actions"),
+        }
+    }
+}
+
+/// A key, with or without the control modifier.
+///
+/// Other modifiers are ignored such that, for example, `{` matches regardless of whether shift was reported.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Key {
+    code: KeyCode,
+    ctrl: bool,
+}
+
+impl Key {
+    const fn plain(code: KeyCode) -> Self {
+        Self { code, ctrl: false }
+    }
+
+    const fn ctrl(c: char) -> Self {
+        Self {
+            code: KeyCode::Char(c),
+            ctrl: true,
+        }
+    }
+
+    const fn char(c: char) -> Self {
+        Self::plain(KeyCode::Char(c))
+    }
+
+    fn matches(&self, event: &KeyEvent) -> bool {
+        self.code == event.code && self.ctrl == event.modifiers.contains(KeyModifiers::CONTROL)
+    }
+}
+
+impl TryFrom<&str> for Key {
+    type Error = anyhow::Error;
+
+    /// Parse a key spec such as `q`, `ctrl+r`, `f5` or `shift+tab`.
+    ///
+    /// A spec is any number of `ctrl` and `shift` modifiers followed by either a single character or the name of a
+    /// key, separated by `+`.  Shift is folded into the key, i.e. `shift+z` is `Z`, as the terminal reports it so.
+    fn try_from(spec: &str) -> Result<Self, Self::Error> {
+        let (modifiers, name) = match spec.strip_suffix("++") {
+            Some(modifiers) => (modifiers, "+"),
+            None => match spec.rsplit_once('+') {
+                Some((modifiers, name)) if !name.is_empty() => (modifiers, name),
+                _ => ("", spec),
+            },
+        };
+        let mut ctrl = false;
+        let mut shift = false;
+        for modifier in modifiers.split('+').filter(|modifier| !modifier.is_empty()) {
+            match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => ctrl = true,
+                "shift" => shift = true,
+                _ => return Err(anyhow!("unknown modifier ({modifier}) in key {spec}")),
+            }
+        }
+        let mut chars = name.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match name.to_ascii_lowercase().as_str() {
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "pgup" | "pageup" => KeyCode::PageUp,
+                "pgdn" | "pagedown" => KeyCode::PageDown,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "enter" => KeyCode::Enter,
+                "esc" => KeyCode::Esc,
+                "tab" => KeyCode::Tab,
+                "backspace" => KeyCode::Backspace,
+                "delete" => KeyCode::Delete,
+                "insert" => KeyCode::Insert,
+                "space" => KeyCode::Char(' '),
+                "comma" => KeyCode::Char(','),
+                other => match other.strip_prefix('f').map(str::parse::<u8>) {
+                    Some(Ok(n)) if (1..=12).contains(&n) => KeyCode::F(n),
+                    _ => return Err(anyhow!("unknown key ({name}) in key {spec}")),
+                },
+            },
+        };
+        let code = match (shift, code) {
+            (false, code) => code,
+            (true, KeyCode::Tab) => KeyCode::BackTab,
+            (true, KeyCode::Char(c)) if c.is_alphabetic() => {
+                KeyCode::Char(c.to_uppercase().next().unwrap_or(c))
+            }
+            (true, _) => {
+                return Err(anyhow!(
+                    "shift is only supported with letters and tab in key {spec}"
+                ))
+            }
+        };
+        Ok(Self { code, ctrl })
+    }
+}
+
+impl Display for Key {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        match self.code {
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::Up => write!(f, "[up]"),
+            KeyCode::Down => write!(f, "[down]"),
+            KeyCode::Left => write!(f, "[left]"),
+            KeyCode::Right => write!(f, "[right]"),
+            KeyCode::PageUp => write!(f, "[pgup]"),
+            KeyCode::PageDown => write!(f, "[pgdn]"),
+            KeyCode::Home => write!(f, "[home]"),
+            KeyCode::End => write!(f, "[end]"),
+            KeyCode::Enter => write!(f, "[enter]"),
+            KeyCode::Esc => write!(f, "[esc]"),
+            KeyCode::Tab => write!(f, "[tab]"),
+            KeyCode::BackTab => write!(f, "[shift+tab]"),
+            KeyCode::Backspace => write!(f, "[backspace]"),
+            KeyCode::Delete => write!(f, "[delete]"),
+            KeyCode::Insert => write!(f, "[insert]"),
+            KeyCode::F(n) => write!(f, "[f{n}]"),
+            code => write!(f, "{code:?}"),
+        }
+    }
+}
+
+/// The keys which perform a `TuiCommand`.
+#[derive(Debug)]
+pub struct KeyBinding {
+    pub keys: &'static [Key],
+    pub command: TuiCommand,
+    pub category: KeyCategory,
+    pub description: &'static str,
+}
+
+/// The default key bindings of the TUI.
+///
+/// This table, with any keys overridden by `KeyBindings`, drives both the dispatch of key events and the help dialog.
+/// Bindings are matched in order and so a binding with the control modifier never shadows, nor is shadowed by, a
+/// binding of the same key without it.
+pub const KEY_BINDINGS: [KeyBinding; 59] = [
+    KeyBinding {
+        keys: &[Key::plain(KeyCode::Down)],
+        command: TuiCommand::NextHop,
+        category: KeyCategory::Navigation,
+        description: "select next hop",
+    },
+    KeyBinding {
+        keys: &[Key::plain(KeyCode::Up)],
+        command: TuiCommand::PreviousHop,
+        category: KeyCategory::Navigation,
+        description: "select previous hop",
+    },
+    KeyBinding {
+        keys: &[Key::plain(KeyCode::PageDown)],
+        command: TuiCommand::NextHopPage,
+        category: KeyCategory::Navigation,
+        description: "select hop a page down",
+    },
+    KeyBinding {
+        keys: &[Key::plain(KeyCode::PageUp)],
+        command: TuiCommand::PreviousHopPage,
+        category: KeyCategory::Navigation,
+        description: "select hop a page up",
+    },
+    KeyBinding {
+        keys: &[Key::plain(KeyCode::Home)],
+        command: TuiCommand::FirstHop,
+        category: KeyCategory::Navigation,
+        description: "select first hop",
+    },
+    KeyBinding {
+        keys: &[Key::plain(KeyCode::End)],
+        command: TuiCommand::LastHop,
+        category: KeyCategory::Navigation,
+        description: "select last hop",
+    },
+    KeyBinding {
+        keys: &[Key::plain(KeyCode::Char('g'))],
+        command: TuiCommand::ToggleFollow,
+        category: KeyCategory::Navigation,
+        description: "toggle following the target hop",
+    },
+    KeyBinding {
+        keys: &[Key::plain(KeyCode::Right)],
+        command: TuiCommand::NextTrace,
+        category: KeyCategory::Navigation,
+        description: "select next trace",
+    },
+    KeyBinding {
+        keys: &[Key::plain(KeyCode::Left)],
+        command: TuiCommand::PreviousTrace,
+        category: KeyCategory::Navigation,
+        description: "select previous trace",
+    },
+    KeyBinding {
+        keys: &[Key::plain(KeyCode::Tab)],
+        command: TuiCommand::NextFlow,
+        category: KeyCategory::Navigation,
+        description: "select next flow or target address",
+    },
+    KeyBinding {
+        keys: &[Key::plain(KeyCode::BackTab)],
+        command: TuiCommand::PreviousFlow,
+        category: KeyCategory::Navigation,
+        description: "select previous flow or target address",
+    },
+    KeyBinding {
+        keys: &[Key::plain(KeyCode::Esc)],
+        command: TuiCommand::ClearSelection,
+        category: KeyCategory::Navigation,
+        description: "clear selection",
+    },
+    KeyBinding {
+        keys: &[Key::plain(KeyCode::Enter)],
+        command: TuiCommand::ToggleDetail,
+        category: KeyCategory::Display,
+        description: "toggle hop detail panel",
+    },
+    KeyBinding {
+        keys: &[Key::char('c')],
+        command: TuiCommand::ToggleChart,
+        category: KeyCategory::Display,
+        description: "toggle chart",
+    },
+    KeyBinding {
+        keys: &[Key::char('f')],
+        command: TuiCommand::ToggleFreeze,
+        category: KeyCategory::Display,
+        description: "toggle freeze display",
+    },
+    KeyBinding {
+        keys: &[Key::char('F')],
+        command: TuiCommand::ToggleHistogram,
+        category: KeyCategory::Display,
+        description: "toggle chart or RTT histogram",
+    },
+    KeyBinding {
+        keys: &[Key::char('C')],
+        command: TuiCommand::ToggleCompare,
+        category: KeyCategory::Display,
+        description: "toggle chart or hop comparison chart",
+    },
+    KeyBinding {
+        keys: &[Key::char('a')],
+        command: TuiCommand::CompareHop,
+        category: KeyCategory::Display,
+        description: "add or remove selected hop from comparison",
+    },
+    KeyBinding {
+        keys: &[Key::char('>')],
+        command: TuiCommand::MoreBins,
+        category: KeyCategory::Display,
+        description: "more histogram bins",
+    },
+    KeyBinding {
+        keys: &[Key::char('<')],
+        command: TuiCommand::FewerBins,
+        category: KeyCategory::Display,
+        description: "fewer histogram bins",
+    },
+    KeyBinding {
+        keys: &[Key::char('m')],
+        command: TuiCommand::ToggleMap,
+        category: KeyCategory::Display,
+        description: "toggle world map (arrows pan, PgUp/PgDn select)",
+    },
+    KeyBinding {
+        keys: &[Key::char('i')],
+        command: TuiCommand::AddressModeIp,
+        category: KeyCategory::Display,
+        description: "show IP only",
+    },
+    KeyBinding {
+        keys: &[Key::char('n')],
+        command: TuiCommand::AddressModeHost,
+        category: KeyCategory::Display,
+        description: "show hostname only",
+    },
+    KeyBinding {
+        keys: &[Key::char('b')],
+        command: TuiCommand::AddressModeBoth,
+        category: KeyCategory::Display,
+        description: "show both IP and hostname",
+    },
+    KeyBinding {
+        keys: &[Key::char('z')],
+        command: TuiCommand::CycleAddressMode,
+        category: KeyCategory::Display,
+        description: "cycle IP, hostname or both",
+    },
+    KeyBinding {
+        keys: &[Key::char('Z')],
+        command: TuiCommand::ToggleAsInfo,
+        category: KeyCategory::Display,
+        description: "toggle AS information (if available)",
+    },
+    KeyBinding {
+        keys: &[Key::char('k')],
+        command: TuiCommand::ToggleSparkline,
+        category: KeyCategory::Display,
+        description: "toggle sparkline column",
+    },
+    KeyBinding {
+        keys: &[Key::char('p')],
+        command: TuiCommand::ToggleHeatmap,
+        category: KeyCategory::Display,
+        description: "toggle heatmap column",
+    },
+    KeyBinding {
+        keys: &[Key::char('L')],
+        command: TuiCommand::ToggleHeatmapLatency,
+        category: KeyCategory::Display,
+        description: "color heatmap by response or latency",
+    },
+    KeyBinding {
+        keys: &[Key::char('x')],
+        command: TuiCommand::ToggleUnresponsive,
+        category: KeyCategory::Display,
+        description: "hide or show unresponsive hops",
+    },
+    KeyBinding {
+        keys: &[Key::char('H')],
+        command: TuiCommand::ToggleHistoricHops,
+        category: KeyCategory::Display,
+        description: "show or hide hops beyond a shortened path",
+    },
+    KeyBinding {
+        keys: &[Key::char('P')],
+        command: TuiCommand::TogglePin,
+        category: KeyCategory::Display,
+        description: "pin or unpin selected hop",
+    },
+    KeyBinding {
+        keys: &[Key::char('O')],
+        command: TuiCommand::ToggleColumnSettings,
+        category: KeyCategory::Display,
+        description: "show or hide columns",
+    },
+    KeyBinding {
+        keys: &[Key::char('o')],
+        command: TuiCommand::ResetSort,
+        category: KeyCategory::Display,
+        description: "sort hops by ttl (f1-f9 to sort by a column)",
+    },
+    KeyBinding {
+        keys: &[Key::char('W')],
+        command: TuiCommand::ToggleCompact,
+        category: KeyCategory::Display,
+        description: "toggle compact layout",
+    },
+    KeyBinding {
+        keys: &[Key::char('w')],
+        command: TuiCommand::ShowRdap,
+        category: KeyCategory::Display,
+        description: "show rdap registration of selected hop",
+    },
+    KeyBinding {
+        keys: &[Key::char('s')],
+        command: TuiCommand::ToggleSettings,
+        category: KeyCategory::Display,
+        description: "show settings",
+    },
+    KeyBinding {
+        keys: &[Key::char('(')],
+        command: TuiCommand::FasterRefresh,
+        category: KeyCategory::Display,
+        description: "refresh twice as often",
+    },
+    KeyBinding {
+        keys: &[Key::char(')')],
+        command: TuiCommand::SlowerRefresh,
+        category: KeyCategory::Display,
+        description: "refresh half as often",
+    },
+    KeyBinding {
+        keys: &[Key::char(']')],
+        command: TuiCommand::ExpandHosts,
+        category: KeyCategory::Display,
+        description: "expand hosts",
+    },
+    KeyBinding {
+        keys: &[Key::char('[')],
+        command: TuiCommand::ContractHosts,
+        category: KeyCategory::Display,
+        description: "collapse hosts",
+    },
+    KeyBinding {
+        keys: &[Key::char('}')],
+        command: TuiCommand::ExpandHostsMax,
+        category: KeyCategory::Display,
+        description: "expand hosts to max",
+    },
+    KeyBinding {
+        keys: &[Key::char('{')],
+        command: TuiCommand::ContractHostsMin,
+        category: KeyCategory::Display,
+        description: "collapse hosts to min",
+    },
+    KeyBinding {
+        keys: &[Key::char('+'), Key::char('=')],
+        command: TuiCommand::ZoomIn,
+        category: KeyCategory::Display,
+        description: "zoom chart in",
+    },
+    KeyBinding {
+        keys: &[Key::char('-')],
+        command: TuiCommand::ZoomOut,
+        category: KeyCategory::Display,
+        description: "zoom chart out",
+    },
+    KeyBinding {
+        keys: &[Key::ctrl('r')],
+        command: TuiCommand::ResetStatistics,
+        category: KeyCategory::Actions,
+        description: "reset statistics",
+    },
+    KeyBinding {
+        keys: &[Key::char('t')],
+        command: TuiCommand::RaiseFirstTtl,
+        category: KeyCategory::Actions,
+        description: "probe from the next ttl",
+    },
+    KeyBinding {
+        keys: &[Key::char('T')],
+        command: TuiCommand::LowerFirstTtl,
+        category: KeyCategory::Actions,
+        description: "probe from the previous ttl",
+    },
+    KeyBinding {
+        keys: &[Key::char('d')],
+        command: TuiCommand::LowerMaxTtl,
+        category: KeyCategory::Actions,
+        description: "probe up to the previous ttl",
+    },
+    KeyBinding {
+        keys: &[Key::char('D')],
+        command: TuiCommand::RaiseMaxTtl,
+        category: KeyCategory::Actions,
+        description: "probe up to the next ttl",
+    },
+    KeyBinding {
+        keys: &[Key::char('R')],
+        command: TuiCommand::ResetTtlRange,
+        category: KeyCategory::Actions,
+        description: "probe the configured ttl range",
+    },
+    KeyBinding {
+        keys: &[Key::ctrl('k')],
+        command: TuiCommand::FlushDnsCache,
+        category: KeyCategory::Actions,
+        description: "flush DNS cache",
+    },
+    KeyBinding {
+        keys: &[Key::char('y')],
+        command: TuiCommand::CopyAddr,
+        category: KeyCategory::Actions,
+        description: "copy address of selected hop",
+    },
+    KeyBinding {
+        keys: &[Key::char('Y')],
+        command: TuiCommand::CopyHosts,
+        category: KeyCategory::Actions,
+        description: "copy all hosts of selected hop",
+    },
+    KeyBinding {
+        keys: &[Key::char('E')],
+        command: TuiCommand::ToggleExport,
+        category: KeyCategory::Actions,
+        description: "export trace to a file",
+    },
+    KeyBinding {
+        keys: &[Key::char('/')],
+        command: TuiCommand::AddTarget,
+        category: KeyCategory::Actions,
+        description: "trace a new target",
+    },
+    KeyBinding {
+        keys: &[Key::char('e')],
+        command: TuiCommand::ToggleEvents,
+        category: KeyCategory::Actions,
+        description: "show event history",
+    },
+    KeyBinding {
+        keys: &[Key::char('h'), Key::char('?')],
+        command: TuiCommand::ToggleHelp,
+        category: KeyCategory::Actions,
+        description: "toggle help",
+    },
+    KeyBinding {
+        keys: &[Key::char('q'), Key::ctrl('c')],
+        command: TuiCommand::Quit,
+        category: KeyCategory::Actions,
+        description: "quit",
+    },
+];
+
+/// The effective key bindings of the TUI, the `KEY_BINDINGS` with the keys of any actions overridden.
+#[derive(Debug, Clone)]
+pub struct KeyBindings {
+    /// The keys of each of the `KEY_BINDINGS`, in the same order.
+    keys: Vec<Vec<Key>>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            keys: KEY_BINDINGS
+                .iter()
+                .map(|binding| binding.keys.to_vec())
+                .collect(),
+        }
+    }
+}
+
+impl KeyBindings {
+    /// Override the default bindings with `specs` of the form `action=key`.
+    ///
+    /// The keys of all the specs of an action replace the default keys of that action.  A key may not be bound to
+    /// more than one action.
+    pub fn new(specs: &[String]) -> anyhow::Result<Self> {
+        let mut bindings = Self::default();
+        let mut overridden = vec![false; KEY_BINDINGS.len()];
+        for spec in specs {
+            let (action, key) = spec
+                .split_once('=')
+                .ok_or_else(|| anyhow!("invalid key binding ({spec}), expected action=key"))?;
+            let command = TuiCommand::from_str(action.trim(), true).map_err(|_| {
+                anyhow!(
+                    "unknown action ({}), must be one of {}",
+                    action.trim(),
+                    TuiCommand::value_variants()
+                        .iter()
+                        .map(|command| action_name(*command))
+                        .join(", ")
+                )
+            })?;
+            let key = Key::try_from(key.trim())?;
+            let index = KEY_BINDINGS
+                .iter()
+                .position(|binding| binding.command == command)
+                .ok_or_else(|| anyhow!("action ({}) cannot be bound", action.trim()))?;
+            if !std::mem::replace(&mut overridden[index], true) {
+                bindings.keys[index].clear();
+            }
+            if !bindings.keys[index].contains(&key) {
+                bindings.keys[index].push(key);
+            }
+        }
+        bindings.validate()?;
+        Ok(bindings)
+    }
+
+    /// Check that no key is bound to more than one action.
+    fn validate(&self) -> anyhow::Result<()> {
+        for (i, keys) in self.keys.iter().enumerate() {
+            for (j, other) in self.keys.iter().enumerate().skip(i + 1) {
+                if let Some(key) = keys.iter().find(|key| other.contains(key)) {
+                    return Err(anyhow!(
+                        "key ({key}) is bound to both {} and {}",
+                        action_name(KEY_BINDINGS[i].command),
+                        action_name(KEY_BINDINGS[j].command)
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The command bound to a key event, if any.
+    pub fn command(&self, event: &KeyEvent) -> Option<TuiCommand> {
+        KEY_BINDINGS
+            .iter()
+            .zip(&self.keys)
+            .find(|(_, keys)| keys.iter().any(|key| key.matches(event)))
+            .map(|(binding, _)| binding.command)
+    }
+
+    /// The keys and description of each binding grouped by category, in the order they are shown in the help dialog.
+    ///
+    /// The keys are labelled as a list, i.e. `h, ?`.
+    pub fn by_category(&self) -> Vec<(KeyCategory, Vec<(String, &'static str)>)> {
+        KeyCategory::ALL
+            .iter()
+            .map(|category| {
+                let bindings = KEY_BINDINGS
+                    .iter()
+                    .zip(&self.keys)
+                    .filter(|(binding, _)| binding.category == *category)
+                    .map(|(binding, keys)| (keys.iter().join(", "), binding.description))
+                    .collect();
+                (*category, bindings)
+            })
+            .collect()
+    }
+}
+
+/// The name of the action of a command, i.e. `toggle-chart`.
+fn action_name(command: TuiCommand) -> String {
+    command
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    fn bindings(specs: &[&str]) -> anyhow::Result<KeyBindings> {
+        let specs: Vec<_> = specs.iter().map(ToString::to_string).collect();
+        KeyBindings::new(&specs)
+    }
+
+    fn key(spec: &str) -> Key {
+        Key::try_from(spec).unwrap()
+    }
+
+    #[test]
+    fn test_command() {
+        let bindings = KeyBindings::default();
+        let none = KeyModifiers::NONE;
+        let ctrl = KeyModifiers::CONTROL;
+        assert_eq!(
+            Some(TuiCommand::ToggleChart),
+            bindings.command(&event(KeyCode::Char('c'), none))
+        );
+        assert_eq!(
+            Some(TuiCommand::Quit),
+            bindings.command(&event(KeyCode::Char('c'), ctrl))
+        );
+        assert_eq!(
+            Some(TuiCommand::ToggleHelp),
+            bindings.command(&event(KeyCode::Char('?'), KeyModifiers::SHIFT))
+        );
+        assert_eq!(
+            Some(TuiCommand::CycleAddressMode),
+            bindings.command(&event(KeyCode::Char('z'), none))
+        );
+        assert_eq!(
+            Some(TuiCommand::ToggleAsInfo),
+            bindings.command(&event(KeyCode::Char('Z'), KeyModifiers::SHIFT))
+        );
+        assert_eq!(None, bindings.command(&event(KeyCode::Char('r'), none)));
+        assert_eq!(
+            Some(TuiCommand::LowerMaxTtl),
+            bindings.command(&event(KeyCode::Char('d'), none))
+        );
+        assert_eq!(
+            Some(TuiCommand::ResetTtlRange),
+            bindings.command(&event(KeyCode::Char('R'), KeyModifiers::SHIFT))
+        );
+        assert_eq!(
+            Some(TuiCommand::ResetStatistics),
+            bindings.command(&event(KeyCode::Char('r'), ctrl))
+        );
+    }
+
+    #[test]
+    fn test_bindings_are_reachable() {
+        let bindings = KeyBindings::default();
+        for binding in &KEY_BINDINGS {
+            for key in binding.keys {
+                let modifiers = if key.ctrl {
+                    KeyModifiers::CONTROL
+                } else {
+                    KeyModifiers::NONE
+                };
+                assert_eq!(
+                    Some(binding.command),
+                    bindings.command(&event(key.code, modifiers)),
+                    "{key} is shadowed"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_bindings_by_category() {
+        let grouped = KeyBindings::default().by_category();
+        assert_eq!(
+            KEY_BINDINGS.len(),
+            grouped
+                .iter()
+                .map(|(_, bindings)| bindings.len())
+                .sum::<usize>()
+        );
+        assert_eq!(KeyCategory::Navigation, grouped[0].0);
+        assert_eq!("[down]", grouped[0].1[0].0);
+        let (_, actions) = &grouped[2];
+        assert_eq!(
+            &(String::from("q, Ctrl+c"), "quit"),
+            actions.last().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(Key::char('q'), key("q"));
+        assert_eq!(Key::ctrl('r'), key("ctrl+r"));
+        assert_eq!(Key::ctrl('r'), key("Control+r"));
+        assert_eq!(Key::plain(KeyCode::F(5)), key("f5"));
+        assert_eq!(Key::plain(KeyCode::F(12)), key("F12"));
+        assert_eq!(Key::plain(KeyCode::BackTab), key("shift+tab"));
+        assert_eq!(Key::plain(KeyCode::Tab), key("tab"));
+        assert_eq!(Key::char('Z'), key("shift+z"));
+        assert_eq!(Key::plain(KeyCode::PageDown), key("pgdn"));
+        assert_eq!(Key::char(' '), key("space"));
+        assert_eq!(Key::char(','), key("comma"));
+        assert_eq!(Key::char('+'), key("+"));
+        assert_eq!(Key::ctrl('+'), key("ctrl++"));
+        assert_eq!(Key::char('F'), key("F"));
+    }
+
+    #[test]
+    fn test_parse_key_invalid() {
+        let error = |spec: &str| Key::try_from(spec).unwrap_err().to_string();
+        assert_eq!("unknown key (f13) in key f13", error("f13"));
+        assert_eq!("unknown key (foo) in key ctrl+foo", error("ctrl+foo"));
+        assert_eq!("unknown key () in key ", error(""));
+        assert_eq!("unknown modifier (alt) in key alt+x", error("alt+x"));
+        assert_eq!(
+            "shift is only supported with letters and tab in key shift+f5",
+            error("shift+f5")
+        );
+    }
+
+    #[test]
+    fn test_override_bindings() {
+        let bindings = bindings(&["toggle-chart=X", "quit=ctrl+q", "quit=f10"]).unwrap();
+        let none = KeyModifiers::NONE;
+        assert_eq!(
+            Some(TuiCommand::ToggleChart),
+            bindings.command(&event(KeyCode::Char('X'), none))
+        );
+        assert_eq!(None, bindings.command(&event(KeyCode::Char('c'), none)));
+        assert_eq!(None, bindings.command(&event(KeyCode::Char('q'), none)));
+        assert_eq!(
+            Some(TuiCommand::Quit),
+            bindings.command(&event(KeyCode::F(10), none))
+        );
+        let (_, actions) = &bindings.by_category()[2];
+        assert_eq!(
+            &(String::from("Ctrl+q, [f10]"), "quit"),
+            actions.last().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_override_bindings_invalid() {
+        let error = |specs: &[&str]| bindings(specs).unwrap_err().to_string();
+        assert_eq!(
+            "invalid key binding (quit), expected action=key",
+            error(&["quit"])
+        );
+        assert!(error(&["pause=p"])
+            .starts_with("unknown action (pause), must be one of next-hop, previous-hop"));
+        assert_eq!("unknown key (foo) in key foo", error(&["quit=foo"]));
+        assert_eq!(
+            "key (c) is bound to both toggle-chart and quit",
+            error(&["quit=c"])
+        );
+        assert_eq!(
+            "key (X) is bound to both toggle-freeze and toggle-help",
+            error(&["toggle-help=X", "toggle-freeze=X"])
+        );
+        assert!(bindings(&["quit=c", "toggle-chart=j"]).is_ok());
+    }
+}
@@ -0,0 +1,141 @@
+use std::time::Duration;
+
+/// The points of the RTT chart of a hop.
+///
+/// The x coordinate of each point is the position of the sample such that the newest sample is on the right.  The
+/// replies are split into runs of consecutive replies such that each run may be drawn as a line with a gap wherever
+/// a probe was lost, the lost probes are marked on the baseline of the chart.
+#[derive(Debug, Default, PartialEq)]
+pub struct ChartPoints {
+    pub runs: Vec<Vec<(f64, f64)>>,
+    pub lost: Vec<(f64, f64)>,
+    /// The x bounds of the chart.
+    pub x_bounds: [f64; 2],
+    /// The y bounds of the chart, in milliseconds.
+    pub y_bounds: [f64; 2],
+}
+
+impl ChartPoints {
+    /// Chart the newest `count` of `samples`, which are ordered newest first.
+    ///
+    /// A zero duration sample is a lost probe.  The y axis is scaled to the range of the replies, with a margin of
+    /// one millisecond if all replies have the same RTT.
+    pub fn new(samples: &[Duration], count: usize) -> Self {
+        let samples = &samples[..count.min(samples.len())];
+        let newest = samples.len().saturating_sub(1);
+        let y_bounds = y_bounds(samples);
+        let mut runs = vec![];
+        let mut run = vec![];
+        let mut lost = vec![];
+        // Walk from the oldest sample to the newest such that the points of each run are in order of x.
+        for (i, sample) in samples.iter().enumerate().rev() {
+            let x = (newest - i) as f64;
+            if *sample == Duration::ZERO {
+                lost.push((x, y_bounds[0]));
+                if !run.is_empty() {
+                    runs.push(std::mem::take(&mut run));
+                }
+            } else {
+                run.push((x, sample.as_secs_f64() * 1000_f64));
+            }
+        }
+        if !run.is_empty() {
+            runs.push(run);
+        }
+        Self {
+            runs,
+            lost,
+            x_bounds: [0_f64, newest.max(1) as f64],
+            y_bounds,
+        }
+    }
+
+    /// The labels of the y axis, in milliseconds.
+    pub fn y_labels(&self) -> [String; 3] {
+        y_labels(self.y_bounds)
+    }
+}
+
+/// The labels of a y axis with `bounds` in milliseconds: the bounds and the midpoint between them.
+pub fn y_labels(bounds: [f64; 2]) -> [String; 3] {
+    let [min, max] = bounds;
+    [
+        format!("{min:.1}"),
+        format!("{:.1}", min + (max - min) / 2_f64),
+        format!("{max:.1}"),
+    ]
+}
+
+fn y_bounds(samples: &[Duration]) -> [f64; 2] {
+    let (min, max) = samples
+        .iter()
+        .filter(|sample| **sample != Duration::ZERO)
+        .map(|sample| sample.as_secs_f64() * 1000_f64)
+        .fold((f64::MAX, f64::MIN), |(min, max), rtt| {
+            (min.min(rtt), max.max(rtt))
+        });
+    if min > max {
+        [0_f64, 1_f64]
+    } else if (max - min).abs() < f64::EPSILON {
+        [(min - 1_f64).max(0_f64), max + 1_f64]
+    } else {
+        [min, max]
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: &[u64]) -> Vec<Duration> {
+        millis.iter().map(|ms| Duration::from_millis(*ms)).collect()
+    }
+
+    #[test]
+    fn test_chart_newest_on_right() {
+        let points = ChartPoints::new(&ms(&[30, 20, 10]), 10);
+        assert_eq!(
+            vec![vec![(0_f64, 10_f64), (1_f64, 20_f64), (2_f64, 30_f64)]],
+            points.runs
+        );
+        assert!(points.lost.is_empty());
+        assert_eq!([0_f64, 2_f64], points.x_bounds);
+        assert_eq!([10_f64, 30_f64], points.y_bounds);
+        assert_eq!(["10.0", "20.0", "30.0"], points.y_labels());
+    }
+
+    #[test]
+    fn test_chart_lost_probes_are_gaps() {
+        let points = ChartPoints::new(&ms(&[40, 0, 20, 10, 0]), 10);
+        assert_eq!(
+            vec![
+                vec![(1_f64, 10_f64), (2_f64, 20_f64)],
+                vec![(4_f64, 40_f64)]
+            ],
+            points.runs
+        );
+        assert_eq!(vec![(0_f64, 10_f64), (3_f64, 10_f64)], points.lost);
+    }
+
+    #[test]
+    fn test_chart_limited_to_count() {
+        let points = ChartPoints::new(&ms(&[30, 20, 10]), 2);
+        assert_eq!(vec![vec![(0_f64, 20_f64), (1_f64, 30_f64)]], points.runs);
+    }
+
+    #[test]
+    fn test_chart_few_samples() {
+        let points = ChartPoints::new(&[], 10);
+        assert_eq!(ChartPoints::new(&[], 0), points);
+        assert!(points.runs.is_empty());
+        assert_eq!([0_f64, 1_f64], points.x_bounds);
+        assert_eq!([0_f64, 1_f64], points.y_bounds);
+        let points = ChartPoints::new(&ms(&[5]), 10);
+        assert_eq!(vec![vec![(0_f64, 5_f64)]], points.runs);
+        assert_eq!([0_f64, 1_f64], points.x_bounds);
+        assert_eq!([4_f64, 6_f64], points.y_bounds);
+        let points = ChartPoints::new(&ms(&[0]), 10);
+        assert_eq!(vec![(0_f64, 0_f64)], points.lost);
+    }
+}
@@ -0,0 +1,125 @@
+use crate::config::TuiClipboard;
+use anyhow::anyhow;
+use std::io::Write;
+
+/// The base64 alphabet of RFC 4648.
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// The clipboards to which the TUI copies.
+///
+/// The system clipboard is opened when first copied to, and held for the rest of the session, as on X11 and Wayland
+/// what was copied is only available for as long as it is held.
+#[derive(Default)]
+pub struct Clipboard {
+    system: Option<arboard::Clipboard>,
+}
+
+impl Clipboard {
+    /// Copy `text` to the clipboard of `mode`.
+    ///
+    /// The `System` clipboard falls back to OSC 52 in an SSH session, where the local clipboard is of the remote host
+    /// and is rarely reachable.
+    pub fn copy(&mut self, mode: TuiClipboard, text: &str) -> anyhow::Result<()> {
+        match mode {
+            TuiClipboard::Osc52 => copy_osc52(text),
+            TuiClipboard::System => self.copy_system(text).or_else(|err| {
+                if is_ssh_session() {
+                    copy_osc52(text)
+                } else {
+                    Err(err)
+                }
+            }),
+            TuiClipboard::Off => Err(anyhow!("clipboard is off")),
+        }
+    }
+
+    /// Copy `text` to the clipboard of the platform.
+    fn copy_system(&mut self, text: &str) -> anyhow::Result<()> {
+        let clipboard = match &mut self.system {
+            Some(clipboard) => clipboard,
+            None => self.system.insert(
+                arboard::Clipboard::new().map_err(|err| anyhow!("no system clipboard ({err})"))?,
+            ),
+        };
+        clipboard
+            .set_text(text)
+            .map_err(|err| anyhow!("failed to copy ({err})"))
+    }
+}
+
+/// Copy `text` with an OSC 52 escape sequence, which the terminal copies to the clipboard of its own host.
+fn copy_osc52(text: &str) -> anyhow::Result<()> {
+    let mut stdout = std::io::stdout();
+    stdout.write_all(osc52(text).as_bytes())?;
+    stdout.flush()?;
+    Ok(())
+}
+
+fn is_ssh_session() -> bool {
+    std::env::var_os("SSH_TTY").is_some() || std::env::var_os("SSH_CONNECTION").is_some()
+}
+
+/// The OSC 52 escape sequence which sets the clipboard to `text`.
+fn osc52(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", encode(text.as_bytes()))
+}
+
+/// Encode `bytes` as padded base64, as of RFC 4648.
+pub fn encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = u32::from(b[0]) << 16 | u32::from(b[1]) << 8 | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(char::from(BASE64[(n >> (18 - 6 * i) & 0x3f) as usize]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_rfc4648() {
+        assert_eq!("", encode(b""));
+        assert_eq!("Zg==", encode(b"f"));
+        assert_eq!("Zm8=", encode(b"fo"));
+        assert_eq!("Zm9v", encode(b"foo"));
+        assert_eq!("Zm9vYg==", encode(b"foob"));
+        assert_eq!("Zm9vYmE=", encode(b"fooba"));
+        assert_eq!("Zm9vYmFy", encode(b"foobar"));
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!("MTAuMS4yLjM=", encode(b"10.1.2.3"));
+        assert_eq!("AP/+", encode(&[0x00, 0xff, 0xfe]));
+        assert_eq!("+/8=", encode(&[0xfb, 0xff]));
+    }
+
+    #[test]
+    fn test_osc52() {
+        assert_eq!("\x1b]52;c;MTAuMS4yLjM=\x07", osc52("10.1.2.3"));
+    }
+
+    #[test]
+    fn test_copy_off() {
+        assert_eq!(
+            "clipboard is off",
+            Clipboard::default()
+                .copy(TuiClipboard::Off, "10.1.2.3")
+                .unwrap_err()
+                .to_string()
+        );
+    }
+}
@@ -0,0 +1,173 @@
+use crate::backend::Hop;
+use crate::frontend::pinned::Pins;
+use itertools::Itertools;
+use std::ops::Range;
+
+/// A row of the hop table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HopRow {
+    /// A hop, by index into the hops of the trace.
+    Hop(usize),
+    /// A run of consecutive hops which have never responded, by range of index into the hops of the trace.
+    Unresponsive(Range<usize>),
+}
+
+impl HopRow {
+    /// The index of the first hop of the row.
+    pub fn first(&self) -> usize {
+        match self {
+            Self::Hop(index) => *index,
+            Self::Unresponsive(range) => range.start,
+        }
+    }
+
+    /// Is the hop at `index` shown in this row?
+    pub fn contains(&self, index: usize) -> bool {
+        match self {
+            Self::Hop(i) => *i == index,
+            Self::Unresponsive(range) => range.contains(&index),
+        }
+    }
+}
+
+/// The rows of the hop table for `hops`.
+///
+/// The hops with a ttl which is `pinned` come first, in ttl order, each in a row of its own, followed by the rows of the
+/// other hops.
+///
+/// If `collapse` is set then every run of two or more consecutive hops which have not received any response, and are
+/// not pinned, is collapsed into a single row.  The rows are recomputed from the hops every time and so a hop which
+/// starts to respond is shown in its own row again.
+pub fn hop_rows(hops: &[Hop], collapse: bool, pinned: &Pins) -> Vec<HopRow> {
+    let is_pinned = |hop: &Hop| pinned.contains(hop.ttl());
+    let mut rows: Vec<_> = hops.iter().positions(is_pinned).map(HopRow::Hop).collect();
+    let mut index = 0;
+    while index < hops.len() {
+        if is_pinned(&hops[index]) {
+            index += 1;
+            continue;
+        }
+        let silent = if collapse {
+            hops[index..]
+                .iter()
+                .take_while(|hop| hop.total_recv() == 0 && !is_pinned(hop))
+                .count()
+        } else {
+            0
+        };
+        if silent > 1 {
+            rows.push(HopRow::Unresponsive(index..index + silent));
+            index += silent;
+        } else {
+            rows.push(HopRow::Hop(index));
+            index += 1;
+        }
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::testing::make_trace;
+    use crate::backend::Trace;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    /// A trace of one round where `path` is `x` for a hop which responded and `.` for a hop which did not.
+    fn trace(path: &str) -> Trace {
+        let addr = Some(IpAddr::V4(Ipv4Addr::LOCALHOST));
+        let path: Vec<_> = path
+            .chars()
+            .map(|c| if c == 'x' { (addr, 10) } else { (None, 0) })
+            .collect();
+        make_trace(&[&path])
+    }
+
+    fn rows(path: &str) -> Vec<HopRow> {
+        hop_rows(trace(path).hops(), true, &Pins::default())
+    }
+
+    #[test]
+    fn test_no_collapse() {
+        assert_eq!(
+            vec![HopRow::Hop(0), HopRow::Hop(1), HopRow::Hop(2)],
+            hop_rows(trace("x..").hops(), false, &Pins::default())
+        );
+    }
+
+    #[test]
+    fn test_collapse_interior() {
+        assert_eq!(
+            vec![HopRow::Hop(0), HopRow::Unresponsive(1..4), HopRow::Hop(4)],
+            rows("x...x")
+        );
+    }
+
+    #[test]
+    fn test_collapse_leading_and_trailing() {
+        assert_eq!(
+            vec![
+                HopRow::Unresponsive(0..2),
+                HopRow::Hop(2),
+                HopRow::Unresponsive(3..6)
+            ],
+            rows("..x...")
+        );
+        assert_eq!(vec![HopRow::Unresponsive(0..3)], rows("..."));
+    }
+
+    #[test]
+    fn test_single_silent_hop_not_collapsed() {
+        assert_eq!(
+            vec![HopRow::Hop(0), HopRow::Hop(1), HopRow::Hop(2)],
+            rows("x.x")
+        );
+    }
+
+    #[test]
+    fn test_responding_hop_expands() {
+        let addr = Some(IpAddr::V4(Ipv4Addr::LOCALHOST));
+        let trace = make_trace(&[
+            &[(addr, 10), (None, 0), (None, 0), (None, 0), (addr, 10)],
+            &[(addr, 10), (None, 0), (addr, 10), (None, 0), (addr, 10)],
+        ]);
+        assert_eq!(
+            vec![
+                HopRow::Hop(0),
+                HopRow::Hop(1),
+                HopRow::Hop(2),
+                HopRow::Hop(3),
+                HopRow::Hop(4)
+            ],
+            hop_rows(trace.hops(), true, &Pins::default())
+        );
+    }
+
+    #[test]
+    fn test_pinned_first() {
+        let mut pinned = Pins::default();
+        pinned.toggle(5);
+        pinned.toggle(3);
+        pinned.toggle(9);
+        assert_eq!(
+            vec![
+                HopRow::Hop(2),
+                HopRow::Hop(4),
+                HopRow::Hop(0),
+                HopRow::Hop(1),
+                HopRow::Hop(3),
+                HopRow::Unresponsive(5..7)
+            ],
+            hop_rows(trace("xx.x...").hops(), true, &pinned)
+        );
+    }
+
+    #[test]
+    fn test_row_contains() {
+        let row = HopRow::Unresponsive(2..5);
+        assert_eq!(2, row.first());
+        assert!(row.contains(4));
+        assert!(!row.contains(5));
+        assert!(HopRow::Hop(1).contains(1));
+    }
+}
@@ -0,0 +1,284 @@
+use crate::config::TuiColumn;
+use std::time::Duration;
+
+/// The space between the columns of the hop table.
+pub const COLUMN_SPACING: u16 = 1;
+
+/// The narrowest the host column may be before other columns are dropped.
+const HOST_MIN_WIDTH: u16 = 16;
+
+/// The most samples shown in each of the sparkline and heatmap columns.
+const FLEXIBLE_MAX_WIDTH: u16 = 20;
+
+/// The fewest samples worth showing in each of the sparkline and heatmap columns.
+const FLEXIBLE_MIN_WIDTH: u16 = 5;
+
+/// The header of a column.
+pub fn header(column: TuiColumn) -> &'static str {
+    match column {
+        TuiColumn::Ttl => "#",
+        TuiColumn::Host => "Host",
+        TuiColumn::LossPct => "Loss%",
+        TuiColumn::Sent => "Snt",
+        TuiColumn::Received => "Recv",
+        TuiColumn::Last => "Last",
+        TuiColumn::Average => "Avg",
+        TuiColumn::Best => "Best",
+        TuiColumn::Worst => "Wrst",
+        TuiColumn::StdDev => "StDev",
+        TuiColumn::Jitter => "Jttr",
+        TuiColumn::Status => "Sts",
+        TuiColumn::Sparkline => "Recent",
+        TuiColumn::Heatmap => "Rounds",
+        TuiColumn::Icmp => "Icmp",
+        TuiColumn::Segment => "Seg",
+        TuiColumn::Asymmetry => "Asym",
+        TuiColumn::LastSeen => "Seen",
+    }
+}
+
+/// Is the width of a column, other than the host column, one sample per character of whatever width is spare?
+fn is_flexible(column: TuiColumn) -> bool {
+    matches!(column, TuiColumn::Sparkline | TuiColumn::Heatmap)
+}
+
+/// The width of a column, zero for the host, sparkline and heatmap columns whose width depends on the width of the
+/// table.
+fn fixed_width(column: TuiColumn) -> u16 {
+    match column {
+        TuiColumn::Host | TuiColumn::Sparkline | TuiColumn::Heatmap => 0,
+        TuiColumn::Ttl | TuiColumn::Status => 3,
+        TuiColumn::Sent | TuiColumn::Received | TuiColumn::Asymmetry => 5,
+        TuiColumn::LossPct
+        | TuiColumn::Last
+        | TuiColumn::Average
+        | TuiColumn::Best
+        | TuiColumn::Worst
+        | TuiColumn::StdDev
+        | TuiColumn::Jitter
+        | TuiColumn::Segment => 6,
+        TuiColumn::LastSeen => 7,
+        TuiColumn::Icmp => 8,
+    }
+}
+
+/// Format the `age` of the last response of a hop in at most its two largest units, such as `4m12s`, to fit the last
+/// seen column.
+pub fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    match (secs / 86400, secs / 3600 % 24, secs / 60 % 60, secs % 60) {
+        (0, 0, 0, s) => format!("{s}s"),
+        (0, 0, m, s) => format!("{m}m{s:02}s"),
+        (0, h, m, _) => format!("{h}h{m:02}m"),
+        (d, h, _, _) => format!("{d}d{h:02}h"),
+    }
+}
+
+/// The width used by the fixed width columns and the spacing between all columns.
+pub fn used_width(columns: &[TuiColumn]) -> u16 {
+    let spacing = COLUMN_SPACING * columns.len().saturating_sub(1) as u16;
+    columns
+        .iter()
+        .map(|column| fixed_width(*column))
+        .sum::<u16>()
+        + spacing
+}
+
+/// Allocate the width of a table `table_width` wide to the columns which fit in it.
+///
+/// The host column takes whatever width the other columns leave.  If that is less than `HOST_MIN_WIDTH` then the
+/// sparkline and heatmap columns are dropped first, followed by the other columns from the last, such that the host
+/// column always remains.  The sparkline and heatmap columns share the spare width, up to `FLEXIBLE_MAX_WIDTH` each.
+pub fn allocate_widths(columns: &[TuiColumn], table_width: u16) -> Vec<(TuiColumn, u16)> {
+    let mut shown = columns.to_vec();
+    while used_width(&shown) + HOST_MIN_WIDTH > table_width {
+        let victim = if shown.iter().any(|column| is_flexible(*column)) {
+            shown.iter().rposition(|column| is_flexible(*column))
+        } else {
+            shown.iter().rposition(|column| *column != TuiColumn::Host)
+        };
+        match victim {
+            Some(index) => shown.remove(index),
+            None => break,
+        };
+    }
+    let mut flexible_width = 0;
+    loop {
+        let flexible = shown.iter().filter(|column| is_flexible(**column)).count() as u16;
+        if flexible == 0 {
+            break;
+        }
+        let spare = table_width - used_width(&shown) - HOST_MIN_WIDTH;
+        flexible_width = (spare / flexible).min(FLEXIBLE_MAX_WIDTH);
+        if flexible_width >= FLEXIBLE_MIN_WIDTH {
+            break;
+        }
+        flexible_width = 0;
+        if let Some(index) = shown.iter().rposition(|column| is_flexible(*column)) {
+            shown.remove(index);
+        }
+    }
+    let flexible = shown.iter().filter(|column| is_flexible(**column)).count() as u16;
+    let host_width = table_width
+        .saturating_sub(used_width(&shown))
+        .saturating_sub(flexible_width * flexible);
+    shown
+        .into_iter()
+        .map(|column| match column {
+            TuiColumn::Host => (column, host_width),
+            TuiColumn::Sparkline | TuiColumn::Heatmap => (column, flexible_width),
+            _ => (column, fixed_width(column)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TuiColumns;
+
+    fn columns(spec: &str) -> Vec<TuiColumn> {
+        TuiColumns::try_from(spec).unwrap().0
+    }
+
+    fn allocate(spec: &str, table_width: u16) -> (String, u16) {
+        let widths = allocate_widths(&columns(spec), table_width);
+        let spec = widths.iter().map(|(column, _)| column.to_char()).collect();
+        let total = widths.iter().map(|(_, width)| width).sum::<u16>()
+            + COLUMN_SPACING * (widths.len() as u16 - 1);
+        assert!(total <= table_width.max(HOST_MIN_WIDTH));
+        let host = widths
+            .iter()
+            .find(|(column, _)| *column == TuiColumn::Host)
+            .unwrap()
+            .1;
+        (spec, host)
+    }
+
+    #[test]
+    fn test_parse_columns() {
+        assert_eq!(
+            TuiColumn::ALL[..11].to_vec(),
+            columns(TuiColumn::DEFAULT_SPEC)
+        );
+        assert_eq!(
+            vec![TuiColumn::Host, TuiColumn::Sparkline, TuiColumn::Ttl],
+            columns("okh")
+        );
+        assert_eq!(
+            vec![TuiColumn::Ttl, TuiColumn::Host, TuiColumn::LossPct],
+            columns("ttl, host,loss-pct")
+        );
+        assert_eq!(
+            vec![TuiColumn::Host, TuiColumn::StdDev],
+            columns("Host,std-dev")
+        );
+    }
+
+    #[test]
+    fn test_parse_columns_invalid() {
+        let error = |spec: &str| TuiColumns::try_from(spec).unwrap_err().to_string();
+        assert_eq!("unknown column (x) in hox", error("hox"));
+        assert_eq!("duplicate column (h) in hoh", error("hoh"));
+        assert_eq!("unknown column (foo) in host,foo", error("host,foo"));
+        assert_eq!(
+            "duplicate column (ttl) in ttl,host,ttl",
+            error("ttl,host,ttl")
+        );
+        assert_eq!("the host column (o) must be included in hl", error("hl"));
+        assert_eq!("the host column (o) must be included in ", error(""));
+    }
+
+    #[test]
+    fn test_every_column_has_a_char() {
+        let spec: String = TuiColumn::ALL
+            .iter()
+            .map(|column| column.to_char())
+            .collect();
+        assert_eq!(TuiColumn::ALL.to_vec(), columns(&spec));
+    }
+
+    #[test]
+    fn test_toggle_columns() {
+        let mut tui_columns = TuiColumns::try_from("hol").unwrap();
+        tui_columns.toggle(TuiColumn::Ttl);
+        tui_columns.toggle(TuiColumn::Host);
+        tui_columns.toggle(TuiColumn::Sparkline);
+        assert_eq!(columns("olk"), tui_columns.0);
+        tui_columns.toggle(TuiColumn::Ttl);
+        tui_columns.add(TuiColumn::LossPct);
+        assert_eq!(columns("olkh"), tui_columns.0);
+    }
+
+    #[test]
+    fn test_allocate_widths_default() {
+        assert_eq!(
+            (String::from(TuiColumn::DEFAULT_SPEC), 58),
+            allocate(TuiColumn::DEFAULT_SPEC, 120)
+        );
+        assert_eq!(
+            (String::from(TuiColumn::DEFAULT_SPEC), 16),
+            allocate(TuiColumn::DEFAULT_SPEC, 78)
+        );
+    }
+
+    #[test]
+    fn test_allocate_widths_drops_last_columns() {
+        assert_eq!(
+            (String::from("holsravb"), 16),
+            allocate(TuiColumn::DEFAULT_SPEC, 60)
+        );
+        assert_eq!((String::from("oh"), 16), allocate("ohl", 20));
+        assert_eq!((String::from("o"), 5), allocate("ohl", 5));
+    }
+
+    #[test]
+    fn test_allocate_widths_sparkline_and_heatmap() {
+        let spec = "holsravbwdtkm";
+        let widths = allocate_widths(&columns(spec), 140);
+        assert_eq!((TuiColumn::Sparkline, 20), widths[11]);
+        assert_eq!((TuiColumn::Heatmap, 20), widths[12]);
+        assert_eq!((String::from(spec), 16), allocate(spec, 120));
+        let widths = allocate_widths(&columns(spec), 100);
+        assert_eq!((TuiColumn::Sparkline, 10), widths[11]);
+        assert_eq!((TuiColumn::Heatmap, 10), widths[12]);
+        assert_eq!((String::from("holsravbwdtk"), 16), allocate(spec, 89));
+        assert_eq!(
+            (String::from(TuiColumn::DEFAULT_SPEC), 16),
+            allocate(spec, 78)
+        );
+    }
+
+    #[test]
+    fn test_allocate_widths_sparkline() {
+        let spec = "holsravbwdtk";
+        let widths = allocate_widths(&columns(spec), 120);
+        assert_eq!((TuiColumn::Sparkline, 20), widths[11]);
+        assert_eq!((String::from(spec), 37), allocate(spec, 120));
+        assert_eq!((String::from(spec), 16), allocate(spec, 90));
+        assert_eq!(
+            (String::from(TuiColumn::DEFAULT_SPEC), 20),
+            allocate(spec, 82)
+        );
+        assert_eq!(
+            (String::from(TuiColumn::DEFAULT_SPEC), 16),
+            allocate(spec, 78)
+        );
+    }
+
+    #[test]
+    fn test_format_age() {
+        assert_eq!("0s", format_age(Duration::ZERO));
+        assert_eq!("59s", format_age(Duration::from_millis(59_900)));
+        assert_eq!("4m12s", format_age(Duration::from_secs(252)));
+        assert_eq!(
+            "2h05m",
+            format_age(Duration::from_secs(2 * 3600 + 5 * 60 + 30))
+        );
+        assert_eq!(
+            "3d07h",
+            format_age(Duration::from_secs(3 * 86400 + 7 * 3600 + 59))
+        );
+        assert!(format_age(Duration::from_secs(999 * 86400 + 23 * 3600 + 1)).len() <= 7);
+    }
+}
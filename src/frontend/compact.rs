@@ -0,0 +1,171 @@
+use crate::config::TuiColumn;
+use crate::frontend::columns::{header, used_width};
+
+/// The narrowest the host column may be in the compact layout before columns are dropped.
+const COMPACT_HOST_MIN_WIDTH: u16 = 24;
+
+/// The columns dropped from the hop table in the compact layout, in the order they are dropped.
+///
+/// The ttl, host, loss, last and average columns are never dropped.
+const DROP_ORDER: [TuiColumn; 13] = [
+    TuiColumn::Sparkline,
+    TuiColumn::Heatmap,
+    TuiColumn::StdDev,
+    TuiColumn::Best,
+    TuiColumn::Worst,
+    TuiColumn::Jitter,
+    TuiColumn::Segment,
+    TuiColumn::Asymmetry,
+    TuiColumn::LastSeen,
+    TuiColumn::Icmp,
+    TuiColumn::Status,
+    TuiColumn::Received,
+    TuiColumn::Sent,
+];
+
+/// The size of the terminal below which the TUI uses the compact layout.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CompactThresholds {
+    width: u16,
+    height: u16,
+}
+
+impl CompactThresholds {
+    pub fn new(width: u16, height: u16) -> Self {
+        Self { width, height }
+    }
+}
+
+/// Which of the compact layouts of a narrow and of a short terminal are used.
+///
+/// In the narrow layout columns of the hop table are dropped, headers are shortened and addresses are shown without
+/// their hostnames.  In the short layout the header and the charts below the hop table are hidden.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Compact {
+    pub narrow: bool,
+    pub short: bool,
+}
+
+impl Compact {
+    /// The compact layout of a terminal `width` by `height`.
+    pub fn new(width: u16, height: u16, thresholds: CompactThresholds) -> Self {
+        Self {
+            narrow: width < thresholds.width,
+            short: height < thresholds.height,
+        }
+    }
+
+    /// The layout used when the compact layout is `forced` on or off, if it is.
+    pub fn forced(self, forced: Option<bool>) -> Self {
+        forced.map_or(self, |forced| Self {
+            narrow: forced,
+            short: forced,
+        })
+    }
+
+    /// Is any data hidden?
+    pub fn is_compact(self) -> bool {
+        self.narrow || self.short
+    }
+
+    /// The forcing of the compact layout after toggling it, where it is otherwise this layout.
+    ///
+    /// The compact layout is forced off if it is in use and on if not.  Once forced to the layout the size of the
+    /// terminal calls for it is no longer forced, such that it again follows the size of the terminal.
+    pub fn toggle(self, forced: Option<bool>) -> Option<bool> {
+        let compact = !self.forced(forced).is_compact();
+        (compact != self.is_compact()).then_some(compact)
+    }
+}
+
+/// The `columns` which fit in a table `table_width` wide in the compact layout.
+///
+/// Columns are dropped in the order of `DROP_ORDER` until the host column is left at least `COMPACT_HOST_MIN_WIDTH`.
+pub fn compact_columns(columns: &[TuiColumn], table_width: u16) -> Vec<TuiColumn> {
+    let mut shown = columns.to_vec();
+    for victim in DROP_ORDER {
+        if used_width(&shown) + COMPACT_HOST_MIN_WIDTH <= table_width {
+            break;
+        }
+        shown.retain(|column| *column != victim);
+    }
+    shown
+}
+
+/// The header of a column in the compact layout.
+pub fn compact_header(column: TuiColumn) -> &'static str {
+    match column {
+        TuiColumn::LossPct => "L%",
+        TuiColumn::Received => "Rcv",
+        TuiColumn::StdDev => "SD",
+        TuiColumn::Jitter => "Jt",
+        TuiColumn::Status => "St",
+        _ => header(column),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TuiColumns;
+
+    const THRESHOLDS: CompactThresholds = CompactThresholds {
+        width: 100,
+        height: 30,
+    };
+
+    fn columns(spec: &str) -> Vec<TuiColumn> {
+        TuiColumns::try_from(spec).unwrap().0
+    }
+
+    #[test]
+    fn test_thresholds() {
+        assert_eq!(Compact::default(), Compact::new(100, 30, THRESHOLDS));
+        assert_eq!(
+            Compact {
+                narrow: true,
+                short: false
+            },
+            Compact::new(99, 30, THRESHOLDS)
+        );
+        assert_eq!(
+            Compact {
+                narrow: false,
+                short: true
+            },
+            Compact::new(100, 29, THRESHOLDS)
+        );
+        assert!(Compact::new(80, 24, THRESHOLDS).is_compact());
+    }
+
+    #[test]
+    fn test_toggle() {
+        let roomy = Compact::new(120, 40, THRESHOLDS);
+        assert_eq!(Some(true), roomy.toggle(None));
+        assert_eq!(None, roomy.toggle(Some(true)));
+        let cramped = Compact::new(80, 24, THRESHOLDS);
+        assert_eq!(Some(false), cramped.toggle(None));
+        assert_eq!(None, cramped.toggle(Some(false)));
+        let narrow = Compact::new(80, 40, THRESHOLDS);
+        assert!(narrow.forced(Some(true)).short);
+        assert!(!narrow.forced(None).short);
+    }
+
+    #[test]
+    fn test_compact_columns() {
+        let default = columns(TuiColumn::DEFAULT_SPEC);
+        assert_eq!(default, compact_columns(&default, 86));
+        assert_eq!(columns("holsravbwt"), compact_columns(&default, 85));
+        assert_eq!(columns("holsravwt"), compact_columns(&default, 78));
+        assert_eq!(columns("holsravt"), compact_columns(&default, 71));
+        assert_eq!(columns("holsav"), compact_columns(&default, 60));
+        assert_eq!(columns("holav"), compact_columns(&default, 10));
+        assert_eq!(columns("hloav"), compact_columns(&columns("hlskmoav"), 40));
+    }
+
+    #[test]
+    fn test_compact_header() {
+        assert_eq!("L%", compact_header(TuiColumn::LossPct));
+        assert_eq!("Avg", compact_header(TuiColumn::Average));
+    }
+}
@@ -0,0 +1,207 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// The most hops charted on the comparison chart, including the selected hop.
+pub const MAX_COMPARED: usize = 4;
+
+/// The outcome of toggling a hop in the comparison.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Toggled {
+    Added,
+    Removed,
+    /// The hop was added and the hop at this ttl, which had been compared the longest, was removed to make room.
+    Evicted(u8),
+}
+
+/// The hops, by ttl, compared with the selected hop on the comparison chart, in the order they were added.
+///
+/// There is room for all but one of `MAX_COMPARED` hops such that the selected hop can always be charted too.
+#[derive(Debug, Default, Clone)]
+pub struct Compared(VecDeque<u8>);
+
+impl Compared {
+    /// Add the hop at `ttl` to the comparison, evicting the oldest hop if there is no room, or remove it if present.
+    pub fn toggle(&mut self, ttl: u8) -> Toggled {
+        if let Some(index) = self.0.iter().position(|compared| *compared == ttl) {
+            self.0.remove(index);
+            return Toggled::Removed;
+        }
+        let evicted = if self.0.len() == MAX_COMPARED - 1 {
+            self.0.pop_front()
+        } else {
+            None
+        };
+        self.0.push_back(ttl);
+        evicted.map_or(Toggled::Added, Toggled::Evicted)
+    }
+
+    /// The ttls of the hops charted: the `selected` hop, then the compared hops in the order they were added.
+    pub fn series(&self, selected: u8) -> Vec<u8> {
+        std::iter::once(selected)
+            .chain(self.0.iter().copied().filter(|ttl| *ttl != selected))
+            .collect()
+    }
+}
+
+/// Align the newest `count` samples of each series, which are ordered newest first, onto a common axis.
+///
+/// The newest sample of every series is at the last index, the axis being as long as the longest series, such that
+/// samples at the same index were probed in the same round.  A lost probe, a zero duration sample, and an index before
+/// the oldest sample of a shorter series are `None`, otherwise the RTT is in milliseconds.
+pub fn align(series: &[&[Duration]], count: usize) -> Vec<Vec<Option<f64>>> {
+    let len = series
+        .iter()
+        .map(|samples| samples.len().min(count))
+        .max()
+        .unwrap_or_default();
+    series
+        .iter()
+        .map(|samples| {
+            let mut aligned = vec![None; len];
+            for (age, sample) in samples.iter().take(len).enumerate() {
+                if *sample != Duration::ZERO {
+                    aligned[len - 1 - age] = Some(sample.as_secs_f64() * 1000_f64);
+                }
+            }
+            aligned
+        })
+        .collect()
+}
+
+/// The points of the comparison chart of several hops.
+///
+/// Each series is split into runs of consecutive replies such that each run may be drawn as a line with a gap wherever
+/// a probe was lost.
+#[derive(Debug, Default, PartialEq)]
+pub struct ComparisonPoints {
+    /// The runs of each series, in the order of the series.
+    pub series: Vec<Vec<Vec<(f64, f64)>>>,
+    /// The x bounds of the chart.
+    pub x_bounds: [f64; 2],
+    /// The y bounds of the chart, in milliseconds, from zero to the greatest RTT of any series.
+    pub y_bounds: [f64; 2],
+}
+
+impl ComparisonPoints {
+    /// Chart the newest `count` samples of each series, which are ordered newest first.
+    pub fn new(series: &[&[Duration]], count: usize) -> Self {
+        let aligned = align(series, count);
+        let len = aligned.first().map_or(0, Vec::len);
+        let max = aligned
+            .iter()
+            .flatten()
+            .flatten()
+            .fold(0_f64, |max, rtt| max.max(*rtt));
+        let series = aligned
+            .iter()
+            .map(|aligned| {
+                let mut runs = vec![];
+                let mut run = vec![];
+                for (x, rtt) in aligned.iter().enumerate() {
+                    if let Some(rtt) = rtt {
+                        run.push((x as f64, *rtt));
+                    } else if !run.is_empty() {
+                        runs.push(std::mem::take(&mut run));
+                    }
+                }
+                if !run.is_empty() {
+                    runs.push(run);
+                }
+                runs
+            })
+            .collect();
+        Self {
+            series,
+            x_bounds: [0_f64, len.saturating_sub(1).max(1) as f64],
+            y_bounds: [0_f64, if max > 0_f64 { max } else { 1_f64 }],
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: &[u64]) -> Vec<Duration> {
+        millis.iter().map(|ms| Duration::from_millis(*ms)).collect()
+    }
+
+    #[test]
+    fn test_toggle_evicts_oldest() {
+        let mut compared = Compared::default();
+        assert_eq!(Toggled::Added, compared.toggle(2));
+        assert_eq!(Toggled::Added, compared.toggle(5));
+        assert_eq!(Toggled::Added, compared.toggle(3));
+        assert_eq!(vec![7, 2, 5, 3], compared.series(7));
+        assert_eq!(Toggled::Evicted(2), compared.toggle(9));
+        assert_eq!(vec![7, 5, 3, 9], compared.series(7));
+        assert_eq!(Toggled::Removed, compared.toggle(3));
+        assert_eq!(vec![5, 9], compared.series(5));
+    }
+
+    #[test]
+    fn test_align_newest_last() {
+        let hop1 = ms(&[10, 11, 12]);
+        let hop2 = ms(&[20, 21, 22]);
+        assert_eq!(
+            vec![
+                vec![Some(12_f64), Some(11_f64), Some(10_f64)],
+                vec![Some(22_f64), Some(21_f64), Some(20_f64)],
+            ],
+            align(&[&hop1, &hop2], 10)
+        );
+    }
+
+    #[test]
+    fn test_align_shorter_series_and_lost() {
+        let hop1 = ms(&[10, 0, 12, 13]);
+        let hop2 = ms(&[20, 21]);
+        assert_eq!(
+            vec![
+                vec![Some(13_f64), Some(12_f64), None, Some(10_f64)],
+                vec![None, None, Some(21_f64), Some(20_f64)],
+            ],
+            align(&[&hop1, &hop2], 10)
+        );
+    }
+
+    #[test]
+    fn test_align_limited_to_count() {
+        let hop1 = ms(&[10, 11, 12]);
+        let hop2 = ms(&[20]);
+        assert_eq!(
+            vec![vec![Some(11_f64), Some(10_f64)], vec![None, Some(20_f64)]],
+            align(&[&hop1, &hop2], 2)
+        );
+        assert!(align(&[], 2).is_empty());
+    }
+
+    #[test]
+    fn test_points_gaps_and_bounds() {
+        let hop1 = ms(&[10, 0, 12, 13]);
+        let hop2 = ms(&[40, 21]);
+        let points = ComparisonPoints::new(&[&hop1, &hop2], 10);
+        assert_eq!(
+            vec![
+                vec![
+                    vec![(0_f64, 13_f64), (1_f64, 12_f64)],
+                    vec![(3_f64, 10_f64)]
+                ],
+                vec![vec![(2_f64, 21_f64), (3_f64, 40_f64)]],
+            ],
+            points.series
+        );
+        assert_eq!([0_f64, 3_f64], points.x_bounds);
+        assert_eq!([0_f64, 40_f64], points.y_bounds);
+    }
+
+    #[test]
+    fn test_points_no_replies() {
+        let lost = ms(&[0]);
+        let points = ComparisonPoints::new(&[&lost], 10);
+        assert_eq!(vec![Vec::<Vec<(f64, f64)>>::new()], points.series);
+        assert_eq!([0_f64, 1_f64], points.x_bounds);
+        assert_eq!([0_f64, 1_f64], points.y_bounds);
+    }
+}
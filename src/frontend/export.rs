@@ -0,0 +1,75 @@
+use crate::backend::Trace;
+use crate::config::{Columns, ReportOutput};
+use crate::report::{run_export, ExportFormat, ReportMetadata, ReportWriter};
+use crate::{DnsResolver, TraceInfo};
+use chrono::{DateTime, Local};
+use std::path::{Path, PathBuf};
+
+/// Export `trace`, the data of the trace of `info`, in `format` to a new timestamped file in `dir`, returning the path
+/// written.
+pub fn export(
+    dir: &Path,
+    info: &TraceInfo,
+    trace: &Trace,
+    resolver: &DnsResolver,
+    columns: &Columns,
+    format: ExportFormat,
+) -> anyhow::Result<PathBuf> {
+    let path = export_path(dir, &info.target_hostname, format, Local::now());
+    let mut writer = ReportWriter::open(&ReportOutput::File(path.clone()), format.mode())?;
+    run_export(
+        &ReportMetadata::new(info),
+        trace,
+        resolver,
+        columns,
+        format,
+        &mut writer,
+    )?;
+    writer.finish()?;
+    Ok(path)
+}
+
+/// The path of a file exported in `format` at `now`, i.e. `trippy-example.com-20230101-120000.json` in `dir`.
+///
+/// Any character of the `target_hostname` which may not be used in a file name is replaced with `_`.
+fn export_path(
+    dir: &Path,
+    target_hostname: &str,
+    format: ExportFormat,
+    now: DateTime<Local>,
+) -> PathBuf {
+    let target: String = target_hostname
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    dir.join(format!(
+        "trippy-{target}-{}.{}",
+        now.format("%Y%m%d-%H%M%S"),
+        format.extension()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_export_path() {
+        let now = Local.with_ymd_and_hms(2023, 1, 2, 3, 4, 5).unwrap();
+        assert_eq!(
+            PathBuf::from("exports/trippy-example.com-20230102-030405.json"),
+            export_path(Path::new("exports"), "example.com", ExportFormat::Json, now)
+        );
+        assert_eq!(
+            PathBuf::from("trippy-fe80__1-20230102-030405.md"),
+            export_path(Path::new(""), "fe80::1", ExportFormat::Markdown, now)
+        );
+    }
+}
@@ -0,0 +1,87 @@
+/// A field of the header, with the priority used to choose which fields to drop if the header is too narrow.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    /// The higher the priority the longer the field is kept.
+    priority: u8,
+    text: String,
+}
+
+impl Field {
+    pub fn new(priority: u8, text: impl Into<String>) -> Self {
+        Self {
+            priority,
+            text: text.into(),
+        }
+    }
+}
+
+/// Join the `fields` with `separator` such that the result is at most `width` characters.
+///
+/// If all fields do not fit then the lowest priority fields are dropped first, the latest first amongst fields of
+/// the same priority, until the rest fit.  The fields are otherwise kept in order.  At least one field is always kept
+/// even if it alone is too wide.
+pub fn fit(fields: &[Field], separator: &str, width: usize) -> String {
+    let mut shown: Vec<&Field> = fields.iter().collect();
+    let len = |shown: &[&Field]| {
+        shown
+            .iter()
+            .map(|field| field.text.chars().count())
+            .sum::<usize>()
+            + separator.chars().count() * shown.len().saturating_sub(1)
+    };
+    while shown.len() > 1 && len(&shown) > width {
+        let victim = shown
+            .iter()
+            .enumerate()
+            .rev()
+            .min_by_key(|(_, field)| field.priority)
+            .map(|(i, _)| i);
+        if let Some(i) = victim {
+            shown.remove(i);
+        }
+    }
+    shown
+        .iter()
+        .map(|field| field.text.as_str())
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields() -> Vec<Field> {
+        vec![
+            Field::new(3, "protocol=icmp"),
+            Field::new(1, "dns=resolv"),
+            Field::new(2, "size=84"),
+            Field::new(1, "grace=100ms"),
+        ]
+    }
+
+    #[test]
+    fn test_fit_all() {
+        assert_eq!(
+            "protocol=icmp dns=resolv size=84 grace=100ms",
+            fit(&fields(), " ", 100)
+        );
+        assert_eq!(
+            "protocol=icmp dns=resolv size=84 grace=100ms",
+            fit(&fields(), " ", 44)
+        );
+    }
+
+    #[test]
+    fn test_fit_drops_lowest_priority_latest_first() {
+        assert_eq!("protocol=icmp dns=resolv size=84", fit(&fields(), " ", 43));
+        assert_eq!("protocol=icmp size=84", fit(&fields(), " ", 31));
+        assert_eq!("protocol=icmp", fit(&fields(), " ", 20));
+    }
+
+    #[test]
+    fn test_fit_keeps_one_field() {
+        assert_eq!("protocol=icmp", fit(&fields(), " ", 0));
+        assert_eq!("", fit(&[], ", ", 10));
+    }
+}
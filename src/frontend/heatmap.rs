@@ -0,0 +1,122 @@
+use crate::frontend::theme::Theme;
+use crate::frontend::threshold::{severity_style, Thresholds};
+use std::time::Duration;
+use tui::style::Color;
+
+/// The glyph of every cell of a heatmap, such that a cell is drawn without allocation.
+pub const CELL: &str = "█";
+
+/// A cell of a heatmap, one per round.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HeatCell {
+    /// A reply, with its RTT.
+    Reply(Duration),
+    /// A lost probe.
+    Lost,
+    /// A round for which the hop has no sample, i.e. before it was first probed.
+    NotProbed,
+}
+
+impl HeatCell {
+    /// The color of the cell.
+    ///
+    /// If `latency` is set then a reply is colored by the severity of its RTT, otherwise every reply is good.
+    pub fn color(self, latency: bool, thresholds: &Thresholds, theme: &Theme) -> Color {
+        match self {
+            Self::Reply(rtt) if latency => {
+                let severity = thresholds.latency(rtt.as_secs_f64() * 1000_f64);
+                severity_style(severity, theme).fg.unwrap_or(theme.good)
+            }
+            Self::Reply(_) => theme.good,
+            Self::Lost => theme.loss,
+            Self::NotProbed => theme.text_dim,
+        }
+    }
+}
+
+/// The heatmap of the newest `width` of `samples`, which are ordered newest first.
+///
+/// The heatmap is ordered oldest first, with the newest round on the right, and is always `width` cells wide such
+/// that the heatmaps of every hop line up; the rounds older than the oldest sample are not probed.  A zero duration
+/// sample is a lost probe.
+pub fn heatmap(samples: &[Duration], width: usize) -> impl Iterator<Item = HeatCell> + '_ {
+    let samples = &samples[..width.min(samples.len())];
+    let padding = width - samples.len();
+    (0..padding)
+        .map(|_| HeatCell::NotProbed)
+        .chain(samples.iter().rev().map(|sample| {
+            if *sample == Duration::ZERO {
+                HeatCell::Lost
+            } else {
+                HeatCell::Reply(*sample)
+            }
+        }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TuiTheme;
+
+    /// The heatmap of `samples`, in milliseconds, as `x` for a reply, `.` for a loss and ` ` for not probed.
+    fn cells(samples: &[u64], width: usize) -> String {
+        let samples: Vec<_> = samples
+            .iter()
+            .map(|ms| Duration::from_millis(*ms))
+            .collect();
+        heatmap(&samples, width)
+            .map(|cell| match cell {
+                HeatCell::Reply(_) => 'x',
+                HeatCell::Lost => '.',
+                HeatCell::NotProbed => ' ',
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_heatmap_newest_on_the_right() {
+        assert_eq!(".xx.x", cells(&[10, 0, 10, 10, 0], 5));
+        let samples = [Duration::from_millis(30), Duration::from_millis(20)];
+        assert_eq!(
+            vec![
+                HeatCell::Reply(Duration::from_millis(20)),
+                HeatCell::Reply(Duration::from_millis(30))
+            ],
+            heatmap(&samples, 2).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_heatmap_truncated_to_width() {
+        assert_eq!("x.", cells(&[0, 10, 10, 0, 0], 2));
+        assert_eq!("", cells(&[10], 0));
+    }
+
+    #[test]
+    fn test_heatmap_padded_when_not_probed() {
+        assert_eq!("   .x", cells(&[10, 0], 5));
+        assert_eq!("    ", cells(&[], 4));
+    }
+
+    #[test]
+    fn test_heatmap_colors() {
+        let theme = Theme::new(TuiTheme::Dark, &[]);
+        let thresholds = Thresholds::new(
+            Duration::from_millis(50),
+            Duration::from_millis(150),
+            10_f64,
+            false,
+        );
+        let color = |cell: HeatCell, latency: bool| cell.color(latency, &thresholds, &theme);
+        let slow = HeatCell::Reply(Duration::from_millis(200));
+        let fair = HeatCell::Reply(Duration::from_millis(60));
+        let fast = HeatCell::Reply(Duration::from_millis(10));
+        assert_eq!(theme.good, color(slow, false));
+        assert_eq!(theme.crit, color(slow, true));
+        assert_eq!(theme.warn, color(fair, true));
+        assert_eq!(theme.good, color(fast, true));
+        assert_eq!(theme.loss, color(HeatCell::Lost, true));
+        assert_eq!(theme.loss, color(HeatCell::Lost, false));
+        assert_eq!(theme.text_dim, color(HeatCell::NotProbed, true));
+    }
+}
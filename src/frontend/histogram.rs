@@ -0,0 +1,125 @@
+use std::time::Duration;
+
+/// The default number of bins of the histogram.
+pub const DEFAULT_BINS: usize = 10;
+
+/// The fewest bins of the histogram.
+pub const MIN_BINS: usize = 2;
+
+/// The most bins of the histogram.
+pub const MAX_BINS: usize = 50;
+
+/// The histogram of the RTT of the replies of a hop.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Histogram {
+    /// The number of replies in each bin, from the best RTT to the worst.
+    pub counts: Vec<u64>,
+    /// The lower bound of each bin.
+    pub lower: Vec<Duration>,
+    /// The number of lost probes, which are excluded from the bins.
+    pub lost: usize,
+}
+
+impl Histogram {
+    /// Bucket `samples` into `bins` bins of equal width between the best and worst RTT.
+    ///
+    /// A zero duration sample is a lost probe.  The worst RTT is counted in the last bin.  If every reply has the same
+    /// RTT then there is a single bin, and if there are no replies then there are no bins.  The bucketing is in whole
+    /// nanoseconds such that the same samples always give the same histogram.
+    pub fn new(samples: &[Duration], bins: usize) -> Self {
+        let bins = bins.max(1);
+        let lost = samples.iter().filter(|s| **s == Duration::ZERO).count();
+        let replies: Vec<_> = samples
+            .iter()
+            .filter(|s| **s != Duration::ZERO)
+            .map(Duration::as_nanos)
+            .collect();
+        let (best, worst) = match (replies.iter().min(), replies.iter().max()) {
+            (Some(best), Some(worst)) => (*best, *worst),
+            _ => {
+                return Self {
+                    lost,
+                    ..Self::default()
+                }
+            }
+        };
+        let range = worst - best;
+        let bins = if range == 0 { 1 } else { bins };
+        let mut counts = vec![0; bins];
+        for reply in &replies {
+            let bin = ((reply - best) * bins as u128)
+                .checked_div(range)
+                .unwrap_or(0);
+            counts[(bin as usize).min(bins - 1)] += 1;
+        }
+        let lower = (0..bins)
+            .map(|bin| nanos(best + range * bin as u128 / bins as u128))
+            .collect();
+        Self {
+            counts,
+            lower,
+            lost,
+        }
+    }
+
+    /// The label of each bin, the lower bound of the bin in milliseconds.
+    pub fn labels(&self) -> Vec<String> {
+        self.lower
+            .iter()
+            .map(|lower| format!("{:.1}", lower.as_secs_f64() * 1000_f64))
+            .collect()
+    }
+}
+
+fn nanos(nanos: u128) -> Duration {
+    Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(ms: &[u64]) -> Vec<Duration> {
+        ms.iter().map(|ms| Duration::from_millis(*ms)).collect()
+    }
+
+    #[test]
+    fn test_histogram_bins() {
+        let histogram = Histogram::new(&samples(&[10, 20, 11, 0, 19, 15, 20, 0, 10]), 2);
+        assert_eq!(vec![3, 4], histogram.counts);
+        assert_eq!(samples(&[10, 15]), histogram.lower);
+        assert_eq!(2, histogram.lost);
+        assert_eq!(vec!["10.0", "15.0"], histogram.labels());
+    }
+
+    #[test]
+    fn test_histogram_bimodal() {
+        let histogram = Histogram::new(&samples(&[10, 11, 90, 12, 90, 10, 89, 90]), 8);
+        assert_eq!(vec![4, 0, 0, 0, 0, 0, 0, 4], histogram.counts);
+        assert_eq!(samples(&[10, 20, 30, 40, 50, 60, 70, 80]), histogram.lower);
+        assert_eq!(0, histogram.lost);
+    }
+
+    #[test]
+    fn test_histogram_identical_samples() {
+        let histogram = Histogram::new(&samples(&[25, 25, 0, 25]), 10);
+        assert_eq!(vec![3], histogram.counts);
+        assert_eq!(samples(&[25]), histogram.lower);
+        assert_eq!(1, histogram.lost);
+    }
+
+    #[test]
+    fn test_histogram_no_replies() {
+        let histogram = Histogram::new(&samples(&[0, 0]), 10);
+        assert!(histogram.counts.is_empty());
+        assert_eq!(2, histogram.lost);
+        assert_eq!(Histogram::default(), Histogram::new(&[], 10));
+    }
+
+    #[test]
+    fn test_histogram_deterministic() {
+        let samples = samples(&[3, 7, 1, 9, 4, 4, 8, 2]);
+        assert_eq!(Histogram::new(&samples, 3), Histogram::new(&samples, 3));
+        assert_eq!(vec![3, 2, 3], Histogram::new(&samples, 3).counts);
+    }
+}
@@ -0,0 +1,137 @@
+use crate::backend::Hop;
+use crate::config::AddressMode;
+use std::cmp::Reverse;
+use std::net::IpAddr;
+
+/// The addresses which have responded for a hop and the number of responses from each, most frequent first.
+///
+/// Addresses with the same number of responses are ordered by address such that the order is stable from one frame
+/// to the next.
+pub fn ranked_addrs(hop: &Hop) -> Vec<(IpAddr, usize)> {
+    let mut addrs: Vec<_> = hop
+        .addrs_with_counts()
+        .map(|(addr, count)| (*addr, *count))
+        .collect();
+    addrs.sort_unstable_by_key(|(addr, count)| (Reverse(*count), *addr));
+    addrs
+}
+
+/// The share of `total` responses accounted for by `count`, as a whole percentage, or zero if there are none.
+pub fn share_pct(count: usize, total: usize) -> usize {
+    (count * 100 + total / 2).checked_div(total).unwrap_or(0)
+}
+
+/// Format a host of a hop for `address_mode`, i.e. `10.0.0.1 (63%)`, `host (63%)` or `host (10.0.0.1) 63%`.
+///
+/// The `hostname` is ignored in `IP` mode and the share is omitted if `None`, i.e. if the hop has a single address.
+pub fn format_host(
+    address_mode: AddressMode,
+    hostname: &str,
+    addr: IpAddr,
+    share: Option<usize>,
+) -> String {
+    match (address_mode, share) {
+        (AddressMode::IP, None) => addr.to_string(),
+        (AddressMode::IP, Some(pct)) => format!("{addr} ({pct}%)"),
+        (AddressMode::Host, None) => hostname.to_string(),
+        (AddressMode::Host, Some(pct)) => format!("{hostname} ({pct}%)"),
+        (AddressMode::Both, None) => format!("{hostname} ({addr})"),
+        (AddressMode::Both, Some(pct)) => format!("{hostname} ({addr}) {pct}%"),
+    }
+}
+
+/// Append the number of `hidden` hosts, if any, to the last of the `hosts` shown, i.e. `10.0.0.2 (20%) (+3)`.
+pub fn with_hidden(mut hosts: Vec<String>, hidden: usize) -> Vec<String> {
+    if hidden > 0 {
+        if let Some(last) = hosts.last_mut() {
+            *last = format!("{last} (+{hidden})");
+        }
+    }
+    hosts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::testing::make_trace;
+    use std::net::Ipv4Addr;
+
+    fn addr(last: u8) -> IpAddr {
+        IpAddr::V4(Ipv4Addr::new(10, 0, 0, last))
+    }
+
+    #[test]
+    fn test_ranked_addrs_by_frequency() {
+        let trace = make_trace(&[
+            &[(Some(addr(1)), 10)],
+            &[(Some(addr(2)), 10)],
+            &[(Some(addr(2)), 10)],
+            &[(None, 0)],
+            &[(Some(addr(3)), 10)],
+            &[(Some(addr(2)), 10)],
+            &[(Some(addr(3)), 10)],
+        ]);
+        assert_eq!(
+            vec![(addr(2), 3), (addr(3), 2), (addr(1), 1)],
+            ranked_addrs(&trace.hops()[0])
+        );
+    }
+
+    #[test]
+    fn test_ranked_addrs_ties_ordered_by_address() {
+        let trace = make_trace(&[
+            &[(Some(addr(9)), 10)],
+            &[(Some(addr(3)), 10)],
+            &[(Some(addr(5)), 10)],
+        ]);
+        assert_eq!(
+            vec![(addr(3), 1), (addr(5), 1), (addr(9), 1)],
+            ranked_addrs(&trace.hops()[0])
+        );
+        assert!(ranked_addrs(&make_trace(&[&[(None, 0)]]).hops()[0]).is_empty());
+    }
+
+    #[test]
+    fn test_share_pct() {
+        assert_eq!(63, share_pct(5, 8));
+        assert_eq!(38, share_pct(3, 8));
+        assert_eq!(33, share_pct(1, 3));
+        assert_eq!(67, share_pct(2, 3));
+        assert_eq!(100, share_pct(4, 4));
+        assert_eq!(0, share_pct(0, 0));
+    }
+
+    #[test]
+    fn test_format_host() {
+        let ip = addr(1);
+        assert_eq!("10.0.0.1", format_host(AddressMode::IP, "host", ip, None));
+        assert_eq!(
+            "10.0.0.1 (63%)",
+            format_host(AddressMode::IP, "host", ip, Some(63))
+        );
+        assert_eq!("host", format_host(AddressMode::Host, "host", ip, None));
+        assert_eq!(
+            "host (63%)",
+            format_host(AddressMode::Host, "host", ip, Some(63))
+        );
+        assert_eq!(
+            "host (10.0.0.1)",
+            format_host(AddressMode::Both, "host", ip, None)
+        );
+        assert_eq!(
+            "host (10.0.0.1) 63%",
+            format_host(AddressMode::Both, "host", ip, Some(63))
+        );
+    }
+
+    #[test]
+    fn test_with_hidden() {
+        let hosts = vec![String::from("a (50%)"), String::from("b (30%)")];
+        assert_eq!(
+            vec![String::from("a (50%)"), String::from("b (30%) (+2)")],
+            with_hidden(hosts.clone(), 2)
+        );
+        assert_eq!(hosts, with_hidden(hosts.clone(), 0));
+        assert!(with_hidden(vec![], 2).is_empty());
+    }
+}
@@ -0,0 +1,228 @@
+use std::f64::consts::TAU;
+
+/// The most the map may be zoomed in.
+const MAX_ZOOM: f64 = 64_f64;
+
+/// The fraction of the visible map moved by each pan.
+const PAN_FRACTION: f64 = 0.25;
+
+/// The precision, in degrees, to which markers are considered to be at the same place.
+const CLUSTER_PRECISION: f64 = 0.01;
+
+/// The direction in which to pan the map.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Pan {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// The part of the world map which is visible, an equirectangular projection of longitude (x) and latitude (y).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Viewport {
+    /// The longitude of the center of the map.
+    lon: f64,
+    /// The latitude of the center of the map.
+    lat: f64,
+    /// The zoom factor, where 1 shows the whole world.
+    zoom: f64,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self {
+            lon: 0_f64,
+            lat: 0_f64,
+            zoom: 1_f64,
+        }
+    }
+}
+
+impl Viewport {
+    /// The longitude either side of the center which is visible.
+    fn half_width(&self) -> f64 {
+        180_f64 / self.zoom
+    }
+
+    /// The latitude either side of the center which is visible.
+    fn half_height(&self) -> f64 {
+        90_f64 / self.zoom
+    }
+
+    /// The bounds of the canvas on the x axis, in degrees of longitude.
+    pub fn x_bounds(&self) -> [f64; 2] {
+        [self.lon - self.half_width(), self.lon + self.half_width()]
+    }
+
+    /// The bounds of the canvas on the y axis, in degrees of latitude.
+    pub fn y_bounds(&self) -> [f64; 2] {
+        [self.lat - self.half_height(), self.lat + self.half_height()]
+    }
+
+    /// Pan the map by a fraction of the visible map, stopping at the edges of the world.
+    pub fn pan(&mut self, pan: Pan) {
+        let (dx, dy) = match pan {
+            Pan::Up => (0_f64, 1_f64),
+            Pan::Down => (0_f64, -1_f64),
+            Pan::Left => (-1_f64, 0_f64),
+            Pan::Right => (1_f64, 0_f64),
+        };
+        self.lon = (dx * 2_f64 * PAN_FRACTION).mul_add(self.half_width(), self.lon);
+        self.lat = (dy * 2_f64 * PAN_FRACTION).mul_add(self.half_height(), self.lat);
+        self.clamp();
+    }
+
+    pub fn zoom_in(&mut self) {
+        self.zoom = (self.zoom * 2_f64).min(MAX_ZOOM);
+        self.clamp();
+    }
+
+    pub fn zoom_out(&mut self) {
+        self.zoom = (self.zoom / 2_f64).max(1_f64);
+        self.clamp();
+    }
+
+    /// Keep the visible map within the world.
+    fn clamp(&mut self) {
+        let lon_limit = 180_f64 - self.half_width();
+        let lat_limit = 90_f64 - self.half_height();
+        self.lon = self.lon.clamp(-lon_limit, lon_limit);
+        self.lat = self.lat.clamp(-lat_limit, lat_limit);
+    }
+
+    /// The radius, in degrees of longitude, by which markers at the same place are spread such that each is visible.
+    pub fn spread_radius(&self) -> f64 {
+        self.half_width() / 40_f64
+    }
+}
+
+/// Spread the markers at `points` which are at the same place onto a circle of `radius` around it.
+///
+/// The first marker at a place is at the top of the circle and the others follow clockwise, in order, such that the
+/// markers do not move from one frame to the next.  A marker which is alone at its place is not moved.
+pub fn spread(points: &[(f64, f64)], radius: f64) -> Vec<(f64, f64)> {
+    let place = |(lon, lat): (f64, f64)| {
+        (
+            (lon / CLUSTER_PRECISION).round() as i64,
+            (lat / CLUSTER_PRECISION).round() as i64,
+        )
+    };
+    points
+        .iter()
+        .enumerate()
+        .map(|(index, point)| {
+            let same: Vec<_> = points
+                .iter()
+                .enumerate()
+                .filter(|(_, other)| place(**other) == place(*point))
+                .map(|(i, _)| i)
+                .collect();
+            if same.len() > 1 {
+                let position = same.iter().position(|i| *i == index).unwrap_or_default();
+                let angle = TAU * position as f64 / same.len() as f64;
+                (
+                    radius.mul_add(angle.sin(), point.0),
+                    radius.mul_add(angle.cos(), point.1),
+                )
+            } else {
+                *point
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod tests {
+    use super::*;
+
+    fn approx(expected: (f64, f64), actual: (f64, f64)) -> bool {
+        (expected.0 - actual.0).abs() < 1e-9 && (expected.1 - actual.1).abs() < 1e-9
+    }
+
+    #[test]
+    fn test_default_shows_the_world() {
+        let viewport = Viewport::default();
+        assert_eq!([-180_f64, 180_f64], viewport.x_bounds());
+        assert_eq!([-90_f64, 90_f64], viewport.y_bounds());
+    }
+
+    #[test]
+    fn test_zoom() {
+        let mut viewport = Viewport::default();
+        viewport.zoom_in();
+        assert_eq!([-90_f64, 90_f64], viewport.x_bounds());
+        assert_eq!([-45_f64, 45_f64], viewport.y_bounds());
+        for _ in 0..10 {
+            viewport.zoom_in();
+        }
+        assert_eq!(
+            [-180_f64 / MAX_ZOOM, 180_f64 / MAX_ZOOM],
+            viewport.x_bounds()
+        );
+        for _ in 0..10 {
+            viewport.zoom_out();
+        }
+        assert_eq!(Viewport::default(), viewport);
+    }
+
+    #[test]
+    fn test_pan_within_the_world() {
+        let mut viewport = Viewport::default();
+        viewport.pan(Pan::Right);
+        assert_eq!(Viewport::default(), viewport);
+        viewport.zoom_in();
+        viewport.pan(Pan::Right);
+        assert_eq!([-45_f64, 135_f64], viewport.x_bounds());
+        viewport.pan(Pan::Up);
+        assert_eq!([-22.5, 67.5], viewport.y_bounds());
+        viewport.pan(Pan::Right);
+        viewport.pan(Pan::Right);
+        assert_eq!([0_f64, 180_f64], viewport.x_bounds());
+        viewport.pan(Pan::Down);
+        viewport.pan(Pan::Down);
+        viewport.pan(Pan::Down);
+        assert_eq!([-90_f64, 0_f64], viewport.y_bounds());
+        viewport.pan(Pan::Left);
+        assert_eq!([-45_f64, 135_f64], viewport.x_bounds());
+        viewport.zoom_out();
+        assert_eq!(Viewport::default(), viewport);
+    }
+
+    #[test]
+    fn test_spread_distinct_points_unmoved() {
+        let points = [(2.35, 48.85), (-0.12, 51.5)];
+        assert_eq!(points.to_vec(), spread(&points, 1_f64));
+        assert!(spread(&[], 1_f64).is_empty());
+    }
+
+    #[test]
+    fn test_spread_same_place() {
+        let points = [
+            (10_f64, 20_f64),
+            (50_f64, 0_f64),
+            (10_f64, 20.001),
+            (10_f64, 20_f64),
+        ];
+        let spread = spread(&points, 2_f64);
+        assert_eq!(4, spread.len());
+        assert_eq!((50_f64, 0_f64), spread[1]);
+        let third = TAU / 3_f64;
+        assert!(approx((10_f64, 22_f64), spread[0]));
+        assert!(approx(
+            (
+                2_f64.mul_add(third.sin(), 10_f64),
+                2_f64.mul_add(third.cos(), 20.001)
+            ),
+            spread[2]
+        ));
+        assert!(approx(
+            (
+                2_f64.mul_add((2_f64 * third).sin(), 10_f64),
+                2_f64.mul_add((2_f64 * third).cos(), 20_f64)
+            ),
+            spread[3]
+        ));
+    }
+}
@@ -0,0 +1,82 @@
+use std::collections::BTreeSet;
+
+/// The lines of a table other than its rows, i.e. the borders and the header row.
+pub const TABLE_CHROME: u16 = 3;
+
+/// The fewest lines the scrollable hop table is left with, enough for a single row.
+const MIN_TABLE_HEIGHT: u16 = TABLE_CHROME + 1;
+
+/// The hops pinned to the top of the hop table, by ttl.
+///
+/// Hops are pinned by ttl rather than by row such that a pin survives the path growing or shrinking, a pinned ttl which
+/// the path does not currently reach is simply not shown.
+#[derive(Debug, Default, Clone)]
+pub struct Pins(BTreeSet<u8>);
+
+impl Pins {
+    pub fn contains(&self, ttl: u8) -> bool {
+        self.0.contains(&ttl)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    /// Pin the hop at `ttl`, or unpin it if it is pinned, returning whether it is now pinned.
+    pub fn toggle(&mut self, ttl: u8) -> bool {
+        if self.0.remove(&ttl) {
+            false
+        } else {
+            self.0.insert(ttl)
+        }
+    }
+}
+
+/// The height of the section of pinned hops, which need `lines` lines, above the hop table in an area of `height` lines.
+///
+/// The pinned section is only as tall as it needs to be, but never so tall that the hop table is left without room for
+/// a row.  If there is not room for both a pinned row and a row of the hop table then no section is shown, i.e. `0`,
+/// and the pinned hops are instead shown at the top of the hop table.
+pub fn section_height(lines: u16, height: u16) -> u16 {
+    let available = height.saturating_sub(MIN_TABLE_HEIGHT);
+    if lines == 0 || available <= TABLE_CHROME {
+        0
+    } else {
+        (lines + TABLE_CHROME).min(available)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toggle() {
+        let mut pins = Pins::default();
+        assert!(pins.toggle(7));
+        assert!(pins.toggle(3));
+        assert!(pins.contains(7));
+        assert_eq!(2, pins.len());
+        assert!(!pins.toggle(7));
+        assert!(!pins.contains(7));
+        assert!(pins.contains(3));
+    }
+
+    #[test]
+    fn test_section_height() {
+        assert_eq!(0, section_height(0, 40));
+        assert_eq!(5, section_height(2, 40));
+        assert_eq!(6, section_height(20, 10));
+        assert_eq!(4, section_height(2, 8));
+        assert_eq!(0, section_height(2, 7));
+        assert_eq!(0, section_height(2, 2));
+    }
+}
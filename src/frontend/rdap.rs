@@ -0,0 +1,399 @@
+use anyhow::anyhow;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+use ureq::{Agent, AgentBuilder};
+
+/// The service which redirects an RDAP query to the registry of the address.
+const RDAP_BOOTSTRAP_URL: &str = "https://rdap.org/ip";
+
+/// The least time between the start of consecutive lookups, such that the registries are not flooded.
+const MIN_LOOKUP_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long a lookup may take before it is abandoned.
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The registration of the network of an address, as much of it as is shown.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RdapInfo {
+    /// The name of the network, i.e. the netname of WHOIS.
+    pub netname: Option<String>,
+    /// The name of the organisation which registered the network.
+    pub org: Option<String>,
+    /// The email address to which to report abuse from the network.
+    pub abuse: Option<String>,
+    /// The prefix, or else the range, of the addresses of the network.
+    pub prefix: Option<String>,
+    /// The first and last address of the network.
+    range: Option<(IpAddr, IpAddr)>,
+}
+
+impl RdapInfo {
+    /// Is `addr` in the network?
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        self.range
+            .is_some_and(|(start, end)| start <= addr && addr <= end)
+    }
+}
+
+/// Parse the fields shown from the json of an RDAP ip network response.
+///
+/// The organisation is that of the registrant and the abuse contact the email address of the entity with the abuse
+/// role, either of which may be nested within other entities.
+pub fn parse(json: &str) -> anyhow::Result<RdapInfo> {
+    let value: Value = serde_json::from_str(json)?;
+    let text = |key| value.get(key).and_then(Value::as_str).map(String::from);
+    if value.get("errorCode").is_some() {
+        return Err(anyhow!(
+            "{}",
+            text("title").unwrap_or_else(|| String::from("rdap error"))
+        ));
+    }
+    let range = match (text("startAddress"), text("endAddress")) {
+        (Some(start), Some(end)) => Some((start.parse()?, end.parse()?)),
+        _ => None,
+    };
+    let mut entities = vec![];
+    collect_entities(&value, &mut entities);
+    Ok(RdapInfo {
+        netname: text("name"),
+        org: find_vcard(&entities, "registrant", "fn"),
+        abuse: find_vcard(&entities, "abuse", "email"),
+        prefix: cidr(&value).or_else(|| range.map(|(start, end)| format!("{start} - {end}"))),
+        range,
+    })
+}
+
+/// Collect the entities of `value`, and theirs, depth first.
+fn collect_entities<'a>(value: &'a Value, entities: &mut Vec<&'a Value>) {
+    for entity in value
+        .get("entities")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        entities.push(entity);
+        collect_entities(entity, entities);
+    }
+}
+
+/// The text of the `property` of the vCard of the first of `entities` with `role`.
+fn find_vcard(entities: &[&Value], role: &str, property: &str) -> Option<String> {
+    entities
+        .iter()
+        .filter(|entity| {
+            entity
+                .get("roles")
+                .and_then(Value::as_array)
+                .is_some_and(|roles| roles.iter().any(|r| r.as_str() == Some(role)))
+        })
+        .find_map(|entity| {
+            entity
+                .pointer("/vcardArray/1")
+                .and_then(Value::as_array)?
+                .iter()
+                .find(|item| item.get(0).and_then(Value::as_str) == Some(property))
+                .and_then(|item| item.get(3))
+                .and_then(Value::as_str)
+                .map(String::from)
+        })
+}
+
+/// The first prefix of the cidr0 extension, if present.
+fn cidr(value: &Value) -> Option<String> {
+    let cidr = value.pointer("/cidr0_cidrs/0")?;
+    let prefix = cidr
+        .get("v4prefix")
+        .or_else(|| cidr.get("v6prefix"))
+        .and_then(Value::as_str)?;
+    let length = cidr.get("length").and_then(Value::as_u64)?;
+    Some(format!("{prefix}/{length}"))
+}
+
+/// How long to wait before starting a lookup at `now` if the last started at `last`, if at all.
+pub fn rate_limit_wait(last: Option<Instant>, now: Instant) -> Option<Duration> {
+    let next = last? + MIN_LOOKUP_INTERVAL;
+    (next > now).then(|| next - now)
+}
+
+/// Make the HTTP agent with which the lookups are made, which follows the redirect to the registry of each address.
+fn make_agent() -> Agent {
+    AgentBuilder::new().timeout(LOOKUP_TIMEOUT).build()
+}
+
+/// Fetch the RDAP registration of the network of `addr`.
+fn fetch(agent: &Agent, addr: IpAddr) -> anyhow::Result<RdapInfo> {
+    fetch_url(agent, &format!("{RDAP_BOOTSTRAP_URL}/{addr}"))
+}
+
+/// Fetch the RDAP registration at `url`.
+///
+/// A registry which answers with an error status fails the lookup with the title of its RDAP error, if it has one.
+fn fetch_url(agent: &Agent, url: &str) -> anyhow::Result<RdapInfo> {
+    match agent.get(url).set("Accept", "application/rdap+json").call() {
+        Ok(response) => parse(&response.into_string()?),
+        Err(ureq::Error::Status(status, response)) => {
+            let body = response.into_string().unwrap_or_default();
+            let title = serde_json::from_str::<Value>(&body)
+                .ok()
+                .and_then(|value| value.get("title")?.as_str().map(String::from));
+            Err(anyhow!(
+                "{}",
+                title.unwrap_or_else(|| format!("http status {status}"))
+            ))
+        }
+        Err(err) => Err(anyhow!("{}", err)),
+    }
+}
+
+/// The channels to and from the lookup thread.
+type Worker = (Sender<IpAddr>, Receiver<(IpAddr, anyhow::Result<RdapInfo>)>);
+
+/// The outcome of the lookup of an address.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Lookup<'a> {
+    Pending,
+    Found(&'a RdapInfo),
+    Failed(&'a str),
+}
+
+/// The RDAP lookups of the session, made in turn on a thread of their own and cached by network.
+#[derive(Debug, Default)]
+pub struct Rdap {
+    /// The networks found.
+    networks: Vec<RdapInfo>,
+    /// The lookups pending, `None`, or which failed, with the error, by address.
+    lookups: HashMap<IpAddr, Option<String>>,
+    /// The channels to and from the lookup thread, once started.
+    worker: Option<Worker>,
+}
+
+impl Rdap {
+    /// Look up `addr`, unless its network has been found or a lookup of it is pending.  A failed lookup is retried.
+    pub fn lookup(&mut self, addr: IpAddr) {
+        if self.networks.iter().any(|info| info.contains(addr))
+            || matches!(self.lookups.get(&addr), Some(None))
+        {
+            return;
+        }
+        let (sender, _) = self.worker.get_or_insert_with(spawn_worker);
+        if sender.send(addr).is_ok() {
+            self.lookups.insert(addr, None);
+        } else {
+            self.lookups
+                .insert(addr, Some(String::from("rdap lookup thread stopped")));
+        }
+    }
+
+    /// The outcome of the lookup of `addr`.
+    pub fn get(&self, addr: IpAddr) -> Lookup<'_> {
+        if let Some(info) = self.networks.iter().find(|info| info.contains(addr)) {
+            return Lookup::Found(info);
+        }
+        match self.lookups.get(&addr) {
+            Some(Some(err)) => Lookup::Failed(err),
+            _ => Lookup::Pending,
+        }
+    }
+
+    /// Record the outcome of any lookups which have completed.
+    pub fn check(&mut self) {
+        while let Some(Ok((addr, result))) =
+            self.worker.as_ref().map(|(_, results)| results.try_recv())
+        {
+            self.record(addr, result);
+        }
+    }
+
+    fn record(&mut self, addr: IpAddr, result: anyhow::Result<RdapInfo>) {
+        match result {
+            Ok(mut info) => {
+                // Without a range the network is cached for the address looked up alone.
+                info.range.get_or_insert((addr, addr));
+                self.lookups.remove(&addr);
+                self.networks.push(info);
+            }
+            Err(err) => {
+                self.lookups.insert(addr, Some(err.to_string()));
+            }
+        }
+    }
+}
+
+/// Start the thread which looks up each address sent to it in turn, no more often than `MIN_LOOKUP_INTERVAL`.
+fn spawn_worker() -> Worker {
+    let (request_sender, requests) = channel::<IpAddr>();
+    let (result_sender, results) = channel();
+    let spawned = thread::Builder::new()
+        .name(String::from("rdap"))
+        .spawn(move || {
+            let agent = make_agent();
+            let mut last = None;
+            for addr in requests {
+                if let Some(wait) = rate_limit_wait(last, Instant::now()) {
+                    thread::sleep(wait);
+                }
+                last = Some(Instant::now());
+                if result_sender.send((addr, fetch(&agent, addr))).is_err() {
+                    break;
+                }
+            }
+        });
+    // If the thread could not be started the requests are refused and so each lookup fails.
+    drop(spawned);
+    (request_sender, results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::{Ipv4Addr, SocketAddr, TcpListener};
+
+    const RESPONSE: &str = r#"{
+        "objectClassName": "ip network",
+        "startAddress": "1.1.1.0",
+        "endAddress": "1.1.1.255",
+        "name": "APNIC-LABS",
+        "cidr0_cidrs": [{"v4prefix": "1.1.1.0", "length": 24}],
+        "entities": [
+            {
+                "roles": ["registrant"],
+                "vcardArray": ["vcard", [["version", {}, "text", "4.0"], ["fn", {}, "text", "APNIC Research and Development"]]],
+                "entities": [
+                    {
+                        "roles": ["abuse"],
+                        "vcardArray": ["vcard", [["fn", {}, "text", "IRT-APNICRANDNET-AU"], ["email", {}, "text", "helpdesk@apnic.net"]]]
+                    }
+                ]
+            }
+        ]
+    }"#;
+
+    const ADDR: IpAddr = IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1));
+
+    #[test]
+    fn test_parse() {
+        let info = parse(RESPONSE).unwrap();
+        assert_eq!(Some(String::from("APNIC-LABS")), info.netname);
+        assert_eq!(
+            Some(String::from("APNIC Research and Development")),
+            info.org
+        );
+        assert_eq!(Some(String::from("helpdesk@apnic.net")), info.abuse);
+        assert_eq!(Some(String::from("1.1.1.0/24")), info.prefix);
+        assert!(info.contains(ADDR));
+        assert!(!info.contains(IpAddr::V4(Ipv4Addr::new(1, 1, 2, 1))));
+    }
+
+    #[test]
+    fn test_parse_missing_fields() {
+        let info =
+            parse(r#"{"startAddress": "10.0.0.0", "endAddress": "10.255.255.255"}"#).unwrap();
+        assert_eq!(None, info.netname);
+        assert_eq!(None, info.org);
+        assert_eq!(Some(String::from("10.0.0.0 - 10.255.255.255")), info.prefix);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            "Not Found",
+            parse(r#"{"errorCode": 404, "title": "Not Found"}"#)
+                .unwrap_err()
+                .to_string()
+        );
+        assert!(parse("<html>").is_err());
+        assert!(parse(r#"{"startAddress": "x", "endAddress": "y"}"#).is_err());
+    }
+
+    /// Serve each of `responses` in turn, each to a connection of its own, on the address returned.
+    fn serve(responses: Vec<String>) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for (response, stream) in responses.into_iter().zip(listener.incoming()) {
+                let mut stream = stream.unwrap();
+                let mut request = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while request.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        addr
+    }
+
+    fn response(status: &str, headers: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {status}\r\n{headers}Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+    }
+
+    #[test]
+    fn test_fetch() {
+        let addr = serve(vec![response("200 OK", "", RESPONSE)]);
+        let info = fetch_url(&make_agent(), &format!("http://{addr}/ip/1.1.1.1")).unwrap();
+        assert_eq!(Some(String::from("APNIC-LABS")), info.netname);
+    }
+
+    #[test]
+    fn test_fetch_redirect() {
+        let addr = serve(vec![
+            response("302 Found", "Location: /registry/ip/1.1.1.1\r\n", ""),
+            response("200 OK", "", RESPONSE),
+        ]);
+        let info = fetch_url(&make_agent(), &format!("http://{addr}/ip/1.1.1.1")).unwrap();
+        assert_eq!(Some(String::from("1.1.1.0/24")), info.prefix);
+    }
+
+    #[test]
+    fn test_fetch_errors() {
+        let addr = serve(vec![
+            response(
+                "404 Not Found",
+                "",
+                r#"{"errorCode": 404, "title": "Not Found"}"#,
+            ),
+            response("503 Service Unavailable", "", "<html>"),
+        ]);
+        let agent = make_agent();
+        let url = format!("http://{addr}/ip/1.1.1.1");
+        assert_eq!(
+            "Not Found",
+            fetch_url(&agent, &url).unwrap_err().to_string()
+        );
+        assert_eq!(
+            "http status 503",
+            fetch_url(&agent, &url).unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_rate_limit_wait() {
+        let now = Instant::now();
+        assert_eq!(None, rate_limit_wait(None, now));
+        assert_eq!(
+            Some(Duration::from_millis(1500)),
+            rate_limit_wait(Some(now), now + Duration::from_millis(500))
+        );
+        assert_eq!(None, rate_limit_wait(Some(now), now + MIN_LOOKUP_INTERVAL));
+    }
+
+    #[test]
+    fn test_cached_by_network() {
+        let mut rdap = Rdap::default();
+        assert_eq!(Lookup::Pending, rdap.get(ADDR));
+        rdap.record(ADDR, parse(RESPONSE));
+        let other = IpAddr::V4(Ipv4Addr::new(1, 1, 1, 200));
+        assert!(matches!(rdap.get(other), Lookup::Found(_)));
+        let failed = IpAddr::V4(Ipv4Addr::new(192, 168, 0, 1));
+        rdap.record(failed, Err(anyhow!("not found")));
+        assert_eq!(Lookup::Failed("not found"), rdap.get(failed));
+    }
+}
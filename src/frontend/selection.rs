@@ -0,0 +1,349 @@
+/// The selected hop of the hop table and the scroll position of the table.
+///
+/// The selection is an index into the hops of the trace and so is preserved as new hops are discovered, it is only
+/// ever moved to select a hop which exists.
+///
+/// In follow mode the table is instead scrolled to keep the last row, the target hop, in view as the path grows or
+/// shrinks.  Follow mode is disengaged by moving the selection.
+#[derive(Debug, Default)]
+pub struct HopSelection {
+    selected: Option<usize>,
+    /// Keep the last row of the table in view.
+    follow: bool,
+    /// The index of the first row shown in the table, after any pinned rows.
+    offset: usize,
+    /// The number of hops which fitted in the table when it was last rendered.
+    page_size: usize,
+}
+
+impl HopSelection {
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn clear(&mut self) {
+        self.selected = None;
+    }
+
+    pub fn is_following(&self) -> bool {
+        self.follow
+    }
+
+    /// Engage follow mode, clearing the selection, or disengage it.
+    pub fn toggle_follow(&mut self) {
+        self.follow = !self.follow;
+        if self.follow {
+            self.selected = None;
+        }
+    }
+
+    /// Select the next hop, or the first hop if none is selected.
+    pub fn next(&mut self, hop_count: usize) {
+        self.follow = false;
+        let index = self
+            .selected
+            .map_or(0, |selected| selected.saturating_add(1));
+        self.select(index, hop_count);
+    }
+
+    /// Select the previous hop, or the last hop if none is selected.
+    pub fn previous(&mut self, hop_count: usize) {
+        self.follow = false;
+        let index = self
+            .selected
+            .map_or(usize::MAX, |selected| selected.saturating_sub(1));
+        self.select(index, hop_count);
+    }
+
+    pub fn page_down(&mut self, hop_count: usize) {
+        self.follow = false;
+        let index = self
+            .selected
+            .map_or(0, |selected| selected.saturating_add(self.page_size.max(1)));
+        self.select(index, hop_count);
+    }
+
+    pub fn page_up(&mut self, hop_count: usize) {
+        self.follow = false;
+        let index = self
+            .selected
+            .map_or(0, |selected| selected.saturating_sub(self.page_size.max(1)));
+        self.select(index, hop_count);
+    }
+
+    pub fn first(&mut self, hop_count: usize) {
+        self.follow = false;
+        self.select(0, hop_count);
+    }
+
+    pub fn last(&mut self, hop_count: usize) {
+        self.follow = false;
+        self.select(usize::MAX, hop_count);
+    }
+
+    /// Ensure the selection is of one of the `hop_count` hops.
+    pub fn clamp(&mut self, hop_count: usize) {
+        if let Some(selected) = self.selected {
+            self.select(selected, hop_count);
+        }
+    }
+
+    /// Select the hop at `index`, or the last hop if there are fewer hops.
+    pub fn select(&mut self, index: usize, hop_count: usize) {
+        self.selected = hop_count.checked_sub(1).map(|last| index.min(last));
+    }
+
+    /// Scroll the table such that the selected hop, or the last hop in follow mode, is visible.
+    ///
+    /// The `heights` are the number of lines of each row of the table, the first `pinned` of which are shown in a
+    /// section of their own which does not scroll, and `viewport` is the number of lines available to show the rest.
+    /// The offset is of the first row shown after the pinned rows.
+    pub fn scroll(&mut self, heights: &[u16], pinned: usize, viewport: u16) {
+        let viewport = usize::from(viewport);
+        let heights = &heights[pinned.min(heights.len())..];
+        if heights.is_empty() {
+            self.offset = 0;
+            self.page_size = 0;
+            return;
+        }
+        let visible = if self.follow {
+            Some(heights.len() - 1)
+        } else {
+            self.selected
+                .and_then(|selected| selected.checked_sub(pinned))
+        };
+        self.offset = scroll_offset(heights, viewport, self.offset, visible);
+        let mut used = 0;
+        self.page_size = heights[self.offset..]
+            .iter()
+            .take_while(|height| {
+                used += usize::from(**height);
+                used <= viewport
+            })
+            .count()
+            .max(1);
+    }
+}
+
+/// The index of the first row shown such that the row at `visible`, if any, is shown in full.
+///
+/// The `heights` are the number of lines of each row of the table, of which there must be at least one, and
+/// `viewport` is the number of lines available to show them.  The table is scrolled from `offset` by as little as
+/// possible and never leaves empty lines at the bottom whilst earlier rows are hidden.  A row is only clipped if it
+/// alone is taller than the viewport.
+fn scroll_offset(heights: &[u16], viewport: usize, offset: usize, visible: Option<usize>) -> usize {
+    let lines = |rows: &[u16]| {
+        rows.iter()
+            .map(|height| usize::from(*height))
+            .sum::<usize>()
+    };
+    let mut offset = offset.min(heights.len() - 1);
+    if let Some(visible) = visible {
+        if visible < offset {
+            offset = visible;
+        }
+        while offset < visible && lines(&heights[offset..=visible]) > viewport {
+            offset += 1;
+        }
+    }
+    while offset > 0 && lines(&heights[offset - 1..]) <= viewport {
+        offset -= 1;
+    }
+    offset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_and_previous() {
+        let mut selection = HopSelection::default();
+        selection.next(0);
+        assert_eq!(None, selection.selected());
+        selection.next(3);
+        assert_eq!(Some(0), selection.selected());
+        selection.next(3);
+        selection.next(3);
+        selection.next(3);
+        assert_eq!(Some(2), selection.selected());
+        selection.previous(3);
+        assert_eq!(Some(1), selection.selected());
+        selection.clear();
+        selection.previous(3);
+        assert_eq!(Some(2), selection.selected());
+    }
+
+    #[test]
+    fn test_first_and_last() {
+        let mut selection = HopSelection::default();
+        selection.last(5);
+        assert_eq!(Some(4), selection.selected());
+        selection.first(5);
+        assert_eq!(Some(0), selection.selected());
+    }
+
+    #[test]
+    fn test_clamp() {
+        let mut selection = HopSelection::default();
+        selection.clamp(4);
+        assert_eq!(None, selection.selected());
+        selection.last(8);
+        selection.clamp(4);
+        assert_eq!(Some(3), selection.selected());
+        selection.clamp(10);
+        assert_eq!(Some(3), selection.selected());
+        selection.clamp(0);
+        assert_eq!(None, selection.selected());
+    }
+
+    #[test]
+    fn test_scroll_down() {
+        let mut selection = HopSelection::default();
+        let heights = [1; 20];
+        selection.scroll(&heights, 0, 5);
+        assert_eq!(0, selection.offset());
+        selection.select(4, 20);
+        selection.scroll(&heights, 0, 5);
+        assert_eq!(0, selection.offset());
+        selection.select(5, 20);
+        selection.scroll(&heights, 0, 5);
+        assert_eq!(1, selection.offset());
+        selection.last(20);
+        selection.scroll(&heights, 0, 5);
+        assert_eq!(15, selection.offset());
+    }
+
+    #[test]
+    fn test_scroll_up() {
+        let mut selection = HopSelection::default();
+        let heights = [1; 20];
+        selection.last(20);
+        selection.scroll(&heights, 0, 5);
+        selection.select(12, 20);
+        selection.scroll(&heights, 0, 5);
+        assert_eq!(12, selection.offset());
+        selection.select(14, 20);
+        selection.scroll(&heights, 0, 5);
+        assert_eq!(12, selection.offset());
+    }
+
+    #[test]
+    fn test_scroll_variable_heights() {
+        let mut selection = HopSelection::default();
+        let heights = [1, 3, 1, 2, 1];
+        selection.select(3, 5);
+        selection.scroll(&heights, 0, 4);
+        assert_eq!(2, selection.offset());
+        assert_eq!(3, selection.page_size);
+    }
+
+    #[test]
+    fn test_scroll_rows_grow_and_shrink() {
+        let mut selection = HopSelection::default();
+        selection.select(6, 10);
+        selection.scroll(&[1; 10], 0, 5);
+        assert_eq!(2, selection.offset());
+        selection.scroll(&[3; 10], 0, 5);
+        assert_eq!(Some(6), selection.selected());
+        assert_eq!(6, selection.offset());
+        selection.scroll(&[1; 10], 0, 5);
+        assert_eq!(Some(6), selection.selected());
+        assert_eq!(5, selection.offset());
+    }
+
+    #[test]
+    fn test_scroll_fills_viewport() {
+        let mut selection = HopSelection::default();
+        selection.last(20);
+        selection.scroll(&[1; 20], 0, 5);
+        assert_eq!(15, selection.offset());
+        selection.scroll(&[1; 20], 0, 10);
+        assert_eq!(10, selection.offset());
+        selection.scroll(&[1; 20], 0, 30);
+        assert_eq!(0, selection.offset());
+    }
+
+    #[test]
+    fn test_scroll_offset_target_grows() {
+        let heights = [1; 10];
+        assert_eq!(5, scroll_offset(&heights[..10], 5, 0, Some(9)));
+        let heights = [1; 12];
+        assert_eq!(7, scroll_offset(&heights, 5, 5, Some(11)));
+    }
+
+    #[test]
+    fn test_scroll_offset_target_shrinks() {
+        let heights = [1; 12];
+        assert_eq!(2, scroll_offset(&heights[..7], 5, 7, Some(6)));
+        assert_eq!(0, scroll_offset(&heights[..3], 5, 7, Some(2)));
+    }
+
+    #[test]
+    fn test_scroll_offset_tall_target() {
+        let heights = [1, 1, 1, 1, 3];
+        assert_eq!(2, scroll_offset(&heights, 5, 0, Some(4)));
+        assert_eq!(4, scroll_offset(&heights, 2, 0, Some(4)));
+    }
+
+    #[test]
+    fn test_follow() {
+        let mut selection = HopSelection::default();
+        selection.select(2, 20);
+        selection.toggle_follow();
+        assert!(selection.is_following());
+        assert_eq!(None, selection.selected());
+        selection.scroll(&[1; 20], 0, 5);
+        assert_eq!(15, selection.offset());
+        selection.scroll(&[2; 25], 0, 5);
+        assert_eq!(23, selection.offset());
+        selection.scroll(&[1; 8], 0, 5);
+        assert_eq!(3, selection.offset());
+        selection.previous(8);
+        assert!(!selection.is_following());
+        assert_eq!(Some(7), selection.selected());
+    }
+
+    #[test]
+    fn test_scroll_pinned() {
+        let mut selection = HopSelection::default();
+        let heights = [1; 20];
+        selection.last(20);
+        selection.scroll(&heights, 2, 5);
+        assert_eq!(13, selection.offset());
+        selection.first(20);
+        selection.scroll(&heights, 2, 5);
+        assert_eq!(13, selection.offset());
+        selection.select(2, 20);
+        selection.scroll(&heights, 2, 5);
+        assert_eq!(0, selection.offset());
+        selection.scroll(&heights, 20, 5);
+        assert_eq!(0, selection.offset());
+    }
+
+    #[test]
+    fn test_page_down_and_up() {
+        let mut selection = HopSelection::default();
+        let heights = [1; 20];
+        selection.page_down(20);
+        assert_eq!(Some(0), selection.selected());
+        selection.scroll(&heights, 0, 5);
+        selection.page_down(20);
+        assert_eq!(Some(5), selection.selected());
+        selection.scroll(&heights, 0, 5);
+        selection.page_down(20);
+        selection.page_down(20);
+        selection.page_down(20);
+        assert_eq!(Some(19), selection.selected());
+        selection.page_up(20);
+        assert_eq!(Some(14), selection.selected());
+        selection.page_up(20);
+        selection.page_up(20);
+        selection.page_up(20);
+        assert_eq!(Some(0), selection.selected());
+    }
+}
@@ -0,0 +1,288 @@
+use crate::config::{
+    AddressMode, TuiColumns, TuiTheme, TUI_MAX_REFRESH_RATE_MS, TUI_MIN_REFRESH_RATE_MS,
+};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::{env, fs, io};
+
+/// The version of the session state written, to be incremented should the meaning of an existing field change.
+const VERSION: u32 = 1;
+
+/// The state of the TUI saved on exit and restored on the next run against the same target.
+///
+/// Every field is optional such that a file written by an older version, which lacks a field, leaves it as configured.
+/// Fields unknown to this version, written by a newer one, are ignored, as are values which cannot be parsed.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionState {
+    pub version: u32,
+    pub columns: Option<String>,
+    pub theme: Option<String>,
+    pub address_mode: Option<String>,
+    pub lookup_as_info: Option<bool>,
+    /// The most addresses shown per hop, zero for no limit.
+    pub max_addrs: Option<u8>,
+    pub hide_unresponsive: Option<bool>,
+    pub refresh_rate_ms: Option<u64>,
+    pub heatmap_latency: Option<bool>,
+    pub show_chart: Option<bool>,
+    pub show_histogram: Option<bool>,
+    pub show_map: Option<bool>,
+}
+
+impl SessionState {
+    pub fn new() -> Self {
+        Self {
+            version: VERSION,
+            ..Self::default()
+        }
+    }
+
+    /// Parse the state from the json of a state file.
+    pub fn parse(json: &str) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// The state less anything given `explicit`ly on the command line, by the id of its argument, which always wins.
+    pub fn without_args(mut self, explicit: &[String]) -> Self {
+        let explicit = |id: &str| explicit.iter().any(|arg| arg == id);
+        if explicit("tui_custom_columns") {
+            self.columns = None;
+        }
+        if explicit("tui_theme") {
+            self.theme = None;
+        }
+        if explicit("tui_address_mode") {
+            self.address_mode = None;
+        }
+        if explicit("dns_lookup_as_info") {
+            self.lookup_as_info = None;
+        }
+        if explicit("tui_max_addrs") {
+            self.max_addrs = None;
+        }
+        if explicit("tui_hide_unresponsive") {
+            self.hide_unresponsive = None;
+        }
+        if explicit("tui_refresh_rate") {
+            self.refresh_rate_ms = None;
+        }
+        self
+    }
+
+    pub fn columns(&self) -> Option<TuiColumns> {
+        self.columns
+            .as_deref()
+            .and_then(|spec| TuiColumns::try_from(spec).ok())
+    }
+
+    pub fn theme(&self) -> Option<TuiTheme> {
+        self.theme
+            .as_deref()
+            .and_then(|theme| TuiTheme::from_str(theme, true).ok())
+    }
+
+    pub fn address_mode(&self) -> Option<AddressMode> {
+        self.address_mode
+            .as_deref()
+            .and_then(|mode| AddressMode::from_str(mode, true).ok())
+    }
+
+    /// The refresh rate, clamped to the range allowed on the command line.
+    pub fn refresh_rate(&self) -> Option<Duration> {
+        self.refresh_rate_ms.map(|ms| {
+            Duration::from_millis(ms).clamp(TUI_MIN_REFRESH_RATE_MS, TUI_MAX_REFRESH_RATE_MS)
+        })
+    }
+}
+
+/// The name of a value enum as given on the command line.
+pub fn value_name<T: ValueEnum>(value: &T) -> Option<String> {
+    value
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+}
+
+/// The session state of a target, restored at startup and saved on exit.
+#[derive(Debug, Default)]
+pub struct Session {
+    /// The file to which the state is saved, if the data directory of the user is known.
+    path: Option<PathBuf>,
+    /// The state restored, less anything given on the command line.
+    restored: SessionState,
+    /// Why the state could not be restored, if it could not.
+    error: Option<String>,
+}
+
+impl Session {
+    /// Open the session of `target`, restoring its state, less the arguments given `explicit`ly, if `restore`.
+    ///
+    /// A state file which cannot be read is ignored, such that it is replaced on exit, and the error recorded.
+    pub fn open(target: &str, explicit: &[String], restore: bool) -> Self {
+        let path = session_path(target);
+        let (restored, error) = match path.as_deref().filter(|_| restore).map(load) {
+            Some(Ok(state)) => (state.without_args(explicit), None),
+            Some(Err(err)) => (
+                SessionState::default(),
+                Some(format!("ignored saved TUI state: {err}")),
+            ),
+            None => (SessionState::default(), None),
+        };
+        Self {
+            path,
+            restored,
+            error,
+        }
+    }
+
+    pub fn restored(&self) -> &SessionState {
+        &self.restored
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Save `state`, creating the directory of the state file if need be.
+    pub fn save(&self, state: &SessionState) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(state)?)
+    }
+}
+
+/// Load the state from the file at `path`, the default state if there is no such file.
+fn load(path: &Path) -> anyhow::Result<SessionState> {
+    match fs::read_to_string(path) {
+        Ok(json) => SessionState::parse(&json),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(SessionState::default()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// The state file of `target` in the data directory of the user, if known.
+fn session_path(target: &str) -> Option<PathBuf> {
+    data_dir().map(|dir| {
+        dir.join("trippy")
+            .join("sessions")
+            .join(format!("{}.json", file_name(target)))
+    })
+}
+
+/// The data directory of the user, following the conventions of the platform.
+fn data_dir() -> Option<PathBuf> {
+    let var = |name| {
+        env::var_os(name)
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from)
+    };
+    if cfg!(windows) {
+        var("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        var("HOME").map(|home| home.join("Library").join("Application Support"))
+    } else {
+        var("XDG_DATA_HOME").or_else(|| var("HOME").map(|home| home.join(".local").join("share")))
+    }
+}
+
+/// The name of the state file of `target`, with any character which may not be valid in a file name replaced.
+fn file_name(target: &str) -> String {
+    target
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state() -> SessionState {
+        SessionState {
+            columns: Some(String::from("holv")),
+            theme: Some(String::from("light")),
+            address_mode: Some(String::from("both")),
+            hide_unresponsive: Some(true),
+            refresh_rate_ms: Some(500),
+            show_chart: Some(true),
+            ..SessionState::new()
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let json = serde_json::to_string(&state()).unwrap();
+        assert_eq!(state(), SessionState::parse(&json).unwrap());
+    }
+
+    #[test]
+    fn test_args_win() {
+        let explicit = [
+            String::from("tui_theme"),
+            String::from("tui_hide_unresponsive"),
+        ];
+        let restored = state().without_args(&explicit);
+        assert!(restored.theme().is_none());
+        assert_eq!(None, restored.hide_unresponsive);
+        assert_eq!(Some(String::from("holv")), restored.columns);
+        assert_eq!(Some(true), restored.show_chart);
+        assert_eq!(state(), state().without_args(&[]));
+    }
+
+    #[test]
+    fn test_older_version() {
+        let restored = SessionState::parse(r#"{"show_chart":true}"#).unwrap();
+        assert_eq!(0, restored.version);
+        assert_eq!(Some(true), restored.show_chart);
+        assert_eq!(None, restored.columns());
+        assert_eq!(None, restored.refresh_rate());
+    }
+
+    #[test]
+    fn test_newer_version() {
+        let restored = SessionState::parse(
+            r#"{"version":7,"theme":"solarized","address_mode":"IP","zoom":3,"show_map":true}"#,
+        )
+        .unwrap();
+        assert_eq!(7, restored.version);
+        assert!(restored.theme().is_none());
+        assert!(matches!(restored.address_mode(), Some(AddressMode::IP)));
+        assert_eq!(Some(true), restored.show_map);
+    }
+
+    #[test]
+    fn test_corrupt() {
+        assert!(SessionState::parse("{\"show_chart\":").is_err());
+        assert!(SessionState::parse(r#"{"show_chart":"yes"}"#).is_err());
+    }
+
+    #[test]
+    fn test_values() {
+        assert!(matches!(state().theme(), Some(TuiTheme::Light)));
+        assert_eq!(Some(Duration::from_millis(500)), state().refresh_rate());
+        let fast = SessionState {
+            refresh_rate_ms: Some(1),
+            ..SessionState::new()
+        };
+        assert_eq!(Some(TUI_MIN_REFRESH_RATE_MS), fast.refresh_rate());
+        assert_eq!(Some(String::from("both")), value_name(&AddressMode::Both));
+    }
+
+    #[test]
+    fn test_file_name() {
+        assert_eq!("example.com", file_name("Example.com"));
+        assert_eq!("__1", file_name("::1"));
+    }
+}
@@ -0,0 +1,86 @@
+use crate::config::{TUI_MAX_REFRESH_RATE_MS, TUI_MIN_REFRESH_RATE_MS};
+use std::time::Duration;
+
+/// A setting of the TUI which may be changed from the settings popup.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Setting {
+    RefreshRate,
+    AddressMode,
+    MaxAddrs,
+    Theme,
+    Columns,
+}
+
+impl Setting {
+    /// The settings, in the order shown.
+    pub const ALL: [Self; 5] = [
+        Self::RefreshRate,
+        Self::AddressMode,
+        Self::MaxAddrs,
+        Self::Theme,
+        Self::Columns,
+    ];
+
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::RefreshRate => "Refresh rate",
+            Self::AddressMode => "Address mode",
+            Self::MaxAddrs => "Max hosts",
+            Self::Theme => "Theme",
+            Self::Columns => "Columns",
+        }
+    }
+}
+
+/// A refresh rate after a change, which may have been clamped.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RefreshRate {
+    pub rate: Duration,
+    /// Was the rate clamped to `TUI_MIN_REFRESH_RATE_MS` or `TUI_MAX_REFRESH_RATE_MS`?
+    pub clamped: bool,
+}
+
+/// Halve the refresh interval of `rate`, such that the TUI refreshes twice as often.
+pub fn faster(rate: Duration) -> RefreshRate {
+    clamp(rate / 2)
+}
+
+/// Double the refresh interval of `rate`, such that the TUI refreshes half as often.
+pub fn slower(rate: Duration) -> RefreshRate {
+    clamp(rate.saturating_mul(2))
+}
+
+fn clamp(rate: Duration) -> RefreshRate {
+    let clamped = rate.clamp(TUI_MIN_REFRESH_RATE_MS, TUI_MAX_REFRESH_RATE_MS);
+    RefreshRate {
+        rate: clamped,
+        clamped: clamped != rate,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rate(ms: u64, clamped: bool) -> RefreshRate {
+        RefreshRate {
+            rate: Duration::from_millis(ms),
+            clamped,
+        }
+    }
+
+    #[test]
+    fn test_faster() {
+        assert_eq!(rate(50, false), faster(Duration::from_millis(100)));
+        assert_eq!(rate(50, true), faster(Duration::from_millis(80)));
+        assert_eq!(rate(50, true), faster(Duration::from_millis(50)));
+    }
+
+    #[test]
+    fn test_slower() {
+        assert_eq!(rate(200, false), slower(Duration::from_millis(100)));
+        assert_eq!(rate(10_000, false), slower(Duration::from_secs(5)));
+        assert_eq!(rate(10_000, true), slower(Duration::from_secs(6)));
+        assert_eq!(rate(10_000, true), slower(Duration::MAX));
+    }
+}
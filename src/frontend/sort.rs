@@ -0,0 +1,225 @@
+use crate::backend::Hop;
+use crate::config::TuiColumn;
+use crate::frontend::collapse::HopRow;
+use std::cmp::Ordering;
+use std::time::SystemTime;
+
+/// The direction of the sort of the hop table.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SortOrder {
+    Ascending,
+    Descending,
+}
+
+/// The column by which the hop table is sorted, in place of the default ttl order.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct HopSort {
+    pub column: TuiColumn,
+    pub order: SortOrder,
+}
+
+impl HopSort {
+    /// The sort after sorting by `column`: descending, i.e. the worst hops first, or reversed if already sorted by it.
+    ///
+    /// Returns `None` if `column` cannot be sorted by.
+    pub fn toggle(sort: Option<Self>, column: TuiColumn) -> Option<Self> {
+        if !is_sortable(column) {
+            return None;
+        }
+        let order = match sort {
+            Some(sort) if sort.column == column && sort.order == SortOrder::Descending => {
+                SortOrder::Ascending
+            }
+            _ => SortOrder::Descending,
+        };
+        Some(Self { column, order })
+    }
+
+    /// The indicator of the sort shown after the header of the sorted column.
+    pub fn indicator(self) -> &'static str {
+        match self.order {
+            SortOrder::Ascending => "▲",
+            SortOrder::Descending => "▼",
+        }
+    }
+
+    /// Sort `rows` of `hops` by the sorted column.
+    ///
+    /// Hops without a value for the column, such as the latency of a hop which has not responded, are sorted last in
+    /// either order.  A run of unresponsive hops is sorted by whichever of its hops sorts first.  The sort is stable
+    /// and so hops with equal values remain in ttl order.
+    pub fn sort(self, rows: &mut [HopRow], hops: &[Hop]) {
+        let key = |row: &HopRow| match row {
+            HopRow::Hop(index) => sort_value(&hops[*index], self.column),
+            HopRow::Unresponsive(range) => hops[range.clone()]
+                .iter()
+                .filter_map(|hop| sort_value(hop, self.column))
+                .min_by(|a, b| self.compare(*a, *b)),
+        };
+        rows.sort_by(|a, b| match (key(a), key(b)) {
+            (Some(a), Some(b)) => self.compare(a, b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        });
+    }
+
+    fn compare(self, a: f64, b: f64) -> Ordering {
+        match self.order {
+            SortOrder::Ascending => a.total_cmp(&b),
+            SortOrder::Descending => b.total_cmp(&a),
+        }
+    }
+}
+
+/// Can the hop table be sorted by `column`?
+pub fn is_sortable(column: TuiColumn) -> bool {
+    matches!(
+        column,
+        TuiColumn::Ttl
+            | TuiColumn::LossPct
+            | TuiColumn::Sent
+            | TuiColumn::Received
+            | TuiColumn::Last
+            | TuiColumn::Average
+            | TuiColumn::Best
+            | TuiColumn::Worst
+            | TuiColumn::StdDev
+            | TuiColumn::Jitter
+            | TuiColumn::Asymmetry
+            | TuiColumn::LastSeen
+    )
+}
+
+/// The value of `hop` in `column`, `None` if the hop has no value, as shown in the hop table.
+fn sort_value(hop: &Hop, column: TuiColumn) -> Option<f64> {
+    let recv = hop.total_recv();
+    match column {
+        TuiColumn::Ttl => Some(f64::from(hop.ttl())),
+        TuiColumn::LossPct => (hop.total_sent() > 0).then(|| hop.loss_pct()),
+        TuiColumn::Sent => Some(hop.total_sent() as f64),
+        TuiColumn::Received => Some(recv as f64),
+        TuiColumn::Last => hop.last_ms(),
+        TuiColumn::Average => (recv > 0).then(|| hop.avg_ms()),
+        TuiColumn::Best => hop.best_ms(),
+        TuiColumn::Worst => hop.worst_ms(),
+        TuiColumn::StdDev => (recv > 1).then(|| hop.stddev_ms()),
+        TuiColumn::Jitter => (recv > 1).then(|| hop.jitter_ms()),
+        TuiColumn::Asymmetry => hop.asymmetry().map(f64::from),
+        TuiColumn::LastSeen => hop
+            .since_last_recv(SystemTime::now())
+            .map(|age| age.as_secs_f64()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::testing::make_trace;
+    use crate::frontend::collapse::hop_rows;
+    use crate::frontend::pinned::Pins;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    const ADDR: Option<IpAddr> = Some(IpAddr::V4(Ipv4Addr::LOCALHOST));
+
+    fn sorted(rounds: &[&[(Option<IpAddr>, u64)]], sort: HopSort, collapse: bool) -> Vec<HopRow> {
+        let trace = make_trace(rounds);
+        let mut rows = hop_rows(trace.hops(), collapse, &Pins::default());
+        sort.sort(&mut rows, trace.hops());
+        rows
+    }
+
+    const AVG_DESC: HopSort = HopSort {
+        column: TuiColumn::Average,
+        order: SortOrder::Descending,
+    };
+
+    const AVG_ASC: HopSort = HopSort {
+        column: TuiColumn::Average,
+        order: SortOrder::Ascending,
+    };
+
+    #[test]
+    fn test_toggle() {
+        let sort = HopSort::toggle(None, TuiColumn::Worst);
+        assert_eq!(
+            Some(HopSort {
+                column: TuiColumn::Worst,
+                order: SortOrder::Descending
+            }),
+            sort
+        );
+        let sort = HopSort::toggle(sort, TuiColumn::Worst);
+        assert_eq!(Some(SortOrder::Ascending), sort.map(|sort| sort.order));
+        let sort = HopSort::toggle(sort, TuiColumn::Worst);
+        assert_eq!(Some(SortOrder::Descending), sort.map(|sort| sort.order));
+        let sort = HopSort::toggle(sort, TuiColumn::LossPct);
+        assert_eq!(Some(TuiColumn::LossPct), sort.map(|sort| sort.column));
+        assert_eq!(None, HopSort::toggle(sort, TuiColumn::Host));
+    }
+
+    #[test]
+    fn test_sort_no_data_last() {
+        let rounds: &[&[(Option<IpAddr>, u64)]] =
+            &[&[(ADDR, 20), (None, 0), (ADDR, 30), (ADDR, 10)]];
+        assert_eq!(
+            vec![
+                HopRow::Hop(2),
+                HopRow::Hop(0),
+                HopRow::Hop(3),
+                HopRow::Hop(1)
+            ],
+            sorted(rounds, AVG_DESC, false)
+        );
+        assert_eq!(
+            vec![
+                HopRow::Hop(3),
+                HopRow::Hop(0),
+                HopRow::Hop(2),
+                HopRow::Hop(1)
+            ],
+            sorted(rounds, AVG_ASC, false)
+        );
+    }
+
+    #[test]
+    fn test_sort_stable_ties() {
+        let rounds: &[&[(Option<IpAddr>, u64)]] = &[&[(ADDR, 10), (ADDR, 20), (ADDR, 10)]];
+        assert_eq!(
+            vec![HopRow::Hop(1), HopRow::Hop(0), HopRow::Hop(2)],
+            sorted(rounds, AVG_DESC, false)
+        );
+    }
+
+    #[test]
+    fn test_sort_unresponsive_runs() {
+        let rounds: &[&[(Option<IpAddr>, u64)]] =
+            &[&[(ADDR, 20), (None, 0), (None, 0), (ADDR, 30)]];
+        assert_eq!(
+            vec![HopRow::Hop(3), HopRow::Hop(0), HopRow::Unresponsive(1..3)],
+            sorted(rounds, AVG_DESC, true)
+        );
+        let loss = HopSort {
+            column: TuiColumn::LossPct,
+            order: SortOrder::Descending,
+        };
+        assert_eq!(
+            vec![HopRow::Unresponsive(1..3), HopRow::Hop(0), HopRow::Hop(3)],
+            sorted(rounds, loss, true)
+        );
+    }
+
+    #[test]
+    fn test_sort_by_ttl() {
+        let rounds: &[&[(Option<IpAddr>, u64)]] = &[&[(ADDR, 20), (ADDR, 10), (ADDR, 30)]];
+        let ttl = HopSort {
+            column: TuiColumn::Ttl,
+            order: SortOrder::Descending,
+        };
+        assert_eq!(
+            vec![HopRow::Hop(2), HopRow::Hop(1), HopRow::Hop(0)],
+            sorted(rounds, ttl, false)
+        );
+    }
+}
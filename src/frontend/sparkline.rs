@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+/// The glyphs of a sparkline, from lowest to highest.
+const GLYPHS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// A glyph of a sparkline.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Glyph {
+    /// A reply, with the glyph of its RTT.
+    Reply(char),
+    /// A lost probe, shown as the lowest glyph.
+    Lost,
+}
+
+impl Glyph {
+    pub fn char(self) -> char {
+        match self {
+            Self::Reply(c) => c,
+            Self::Lost => GLYPHS[0],
+        }
+    }
+}
+
+/// The sparkline of the newest `width` of `samples`, which are ordered newest first.
+///
+/// The sparkline is ordered oldest first such that it reads left to right.  A zero duration sample is a lost probe.
+/// The glyphs are scaled to the best and worst RTT of the samples shown such that the shape of the latency of every
+/// hop is visible, whatever its RTT.
+pub fn sparkline(samples: &[Duration], width: usize) -> Vec<Glyph> {
+    let samples = &samples[..width.min(samples.len())];
+    let (best, worst) = samples
+        .iter()
+        .filter(|sample| **sample != Duration::ZERO)
+        .fold((Duration::MAX, Duration::ZERO), |(best, worst), sample| {
+            (best.min(*sample), worst.max(*sample))
+        });
+    samples
+        .iter()
+        .rev()
+        .map(|sample| {
+            if *sample == Duration::ZERO {
+                Glyph::Lost
+            } else if worst <= best {
+                Glyph::Reply(GLYPHS[GLYPHS.len() / 2 - 1])
+            } else {
+                let range = worst.saturating_sub(best).as_secs_f64();
+                let scaled = sample.saturating_sub(best).as_secs_f64() / range;
+                let level = (scaled * (GLYPHS.len() - 1) as f64).round() as usize;
+                Glyph::Reply(GLYPHS[level.min(GLYPHS.len() - 1)])
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glyphs(samples: &[u64], width: usize) -> String {
+        let samples: Vec<_> = samples
+            .iter()
+            .map(|ms| Duration::from_millis(*ms))
+            .collect();
+        sparkline(&samples, width)
+            .into_iter()
+            .map(Glyph::char)
+            .collect()
+    }
+
+    #[test]
+    fn test_sparkline_scaled_to_samples() {
+        assert_eq!("▁▂▃▄▅▆▇█", glyphs(&[17, 16, 15, 14, 13, 12, 11, 10], 20));
+        assert_eq!(
+            "▁▂▃▄▅▆▇█",
+            glyphs(&[170, 160, 150, 140, 130, 120, 110, 100], 20)
+        );
+        assert_eq!("█▁▅", glyphs(&[15, 10, 20], 20));
+    }
+
+    #[test]
+    fn test_sparkline_newest_width_samples() {
+        assert_eq!("▁█", glyphs(&[20, 10, 30, 40], 2));
+        assert_eq!("", glyphs(&[20, 10], 0));
+        assert_eq!("", glyphs(&[], 20));
+    }
+
+    #[test]
+    fn test_sparkline_flat() {
+        assert_eq!("▄▄▄", glyphs(&[10, 10, 10], 20));
+    }
+
+    #[test]
+    fn test_sparkline_lost() {
+        let samples = [10, 0, 20, 0].map(Duration::from_millis);
+        assert_eq!(
+            vec![
+                Glyph::Lost,
+                Glyph::Reply('█'),
+                Glyph::Lost,
+                Glyph::Reply('▁')
+            ],
+            sparkline(&samples, 20)
+        );
+        assert_eq!("▁▁", glyphs(&[0, 0], 20));
+    }
+}
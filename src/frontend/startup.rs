@@ -0,0 +1,130 @@
+use crate::TraceInfo;
+use parking_lot::RwLock;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The frames of the spinner shown whilst waiting.
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// How long each frame of the spinner is shown.
+pub const SPINNER_FRAME: Duration = Duration::from_millis(100);
+
+/// The step of starting the traces which is in progress.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StartupStatus {
+    /// Nothing has been started yet.
+    Starting,
+    /// Resolving the address of the target host.
+    Resolving(String),
+    /// Discovering the source address from which to trace the target host.
+    Discovering(String),
+}
+
+impl StartupStatus {
+    pub fn message(&self) -> String {
+        match self {
+            Self::Starting => String::from("starting…"),
+            Self::Resolving(host) => format!("resolving {host}…"),
+            Self::Discovering(host) => format!("discovering source address for {host}…"),
+        }
+    }
+}
+
+/// The status of the traces being started, shared with the thread which starts them.
+pub type SharedStartupStatus = Arc<RwLock<StartupStatus>>;
+
+/// The traces being started on a thread of their own.
+///
+/// Resolving the targets and discovering the source addresses may take several seconds and so the traces are started
+/// off the TUI thread such that their progress, or the error if they fail to start, can be shown in the TUI.
+pub struct Startup {
+    status: SharedStartupStatus,
+    traces: Receiver<anyhow::Result<Vec<TraceInfo>>>,
+}
+
+impl Startup {
+    pub fn new(
+        status: SharedStartupStatus,
+        traces: Receiver<anyhow::Result<Vec<TraceInfo>>>,
+    ) -> Self {
+        Self { status, traces }
+    }
+
+    pub fn status(&self) -> StartupStatus {
+        self.status.read().clone()
+    }
+
+    /// The traces, or the error if they failed to start, if they have finished starting.
+    pub fn try_started(&self) -> Option<anyhow::Result<Vec<TraceInfo>>> {
+        match self.traces.try_recv() {
+            Ok(traces) => Some(traces),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                Some(Err(anyhow::anyhow!("failed to start tracing")))
+            }
+        }
+    }
+
+    /// Wait for the traces to finish starting.
+    pub fn wait(self) -> anyhow::Result<Vec<TraceInfo>> {
+        self.traces
+            .recv()
+            .map_err(|_| anyhow::anyhow!("failed to start tracing"))?
+    }
+}
+
+/// The frame of the spinner to show after waiting for `elapsed`.
+pub fn spinner(elapsed: Duration) -> char {
+    let frame = elapsed.as_millis() / SPINNER_FRAME.as_millis();
+    SPINNER[(frame % SPINNER.len() as u128) as usize]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_spinner() {
+        assert_eq!('⠋', spinner(Duration::ZERO));
+        assert_eq!('⠋', spinner(Duration::from_millis(99)));
+        assert_eq!('⠙', spinner(Duration::from_millis(100)));
+        assert_eq!('⠏', spinner(Duration::from_millis(950)));
+        assert_eq!('⠋', spinner(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn test_message() {
+        assert_eq!(
+            "resolving example.com…",
+            StartupStatus::Resolving(String::from("example.com")).message()
+        );
+        assert_eq!(
+            "discovering source address for example.com…",
+            StartupStatus::Discovering(String::from("example.com")).message()
+        );
+    }
+
+    #[test]
+    fn test_started() {
+        let status = Arc::new(RwLock::new(StartupStatus::Starting));
+        let (sender, receiver) = mpsc::channel();
+        let startup = Startup::new(status.clone(), receiver);
+        assert!(startup.try_started().is_none());
+        *status.write() = StartupStatus::Resolving(String::from("example.com"));
+        assert_eq!(
+            StartupStatus::Resolving(String::from("example.com")),
+            startup.status()
+        );
+        sender
+            .send(Err(anyhow::anyhow!("failed to resolve target")))
+            .unwrap();
+        let err = startup.try_started().and_then(Result::err).unwrap();
+        assert_eq!("failed to resolve target", err.to_string());
+        drop(sender);
+        assert!(startup
+            .try_started()
+            .is_some_and(|started| started.is_err()));
+    }
+}
@@ -0,0 +1,126 @@
+use crate::backend::Trace;
+use std::net::IpAddr;
+
+/// The reachability of the target of a trace.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Reachability {
+    /// No round has completed yet.
+    Waiting,
+    /// The target has responded.
+    Reachable,
+    /// The target has never responded.
+    Unreachable,
+    /// The trace has failed.
+    Failed,
+}
+
+impl Reachability {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Waiting => "waiting",
+            Self::Reachable => "reachable",
+            Self::Unreachable => "unreachable",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+/// The end-to-end statistics of a trace, from the target hop, as shown on its tab and in the summary.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceSummary {
+    pub reachability: Reachability,
+    /// The number of hops discovered.
+    pub hops: usize,
+    pub sent: usize,
+    pub loss_pct: f64,
+    pub last_ms: Option<f64>,
+    pub avg_ms: Option<f64>,
+    pub best_ms: Option<f64>,
+    pub worst_ms: Option<f64>,
+}
+
+impl TraceSummary {
+    /// Summarise `trace` of `target_addr`.
+    pub fn new(trace: &Trace, target_addr: IpAddr) -> Self {
+        let target = trace.target_hop();
+        let reachability = if trace.error().is_some() {
+            Reachability::Failed
+        } else if trace.round().is_none() {
+            Reachability::Waiting
+        } else if target.addrs().any(|addr| *addr == target_addr) {
+            Reachability::Reachable
+        } else {
+            Reachability::Unreachable
+        };
+        let responded = target.total_recv() > 0;
+        Self {
+            reachability,
+            hops: trace.hops().len(),
+            sent: target.total_sent(),
+            loss_pct: target.loss_pct(),
+            last_ms: target.last_ms(),
+            avg_ms: responded.then(|| target.avg_ms()),
+            best_ms: target.best_ms(),
+            worst_ms: target.worst_ms(),
+        }
+    }
+}
+
+impl Default for TraceSummary {
+    fn default() -> Self {
+        Self {
+            reachability: Reachability::Waiting,
+            hops: 0,
+            sent: 0,
+            loss_pct: 0_f64,
+            last_ms: None,
+            avg_ms: None,
+            best_ms: None,
+            worst_ms: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::testing::make_trace;
+    use std::net::Ipv4Addr;
+
+    const TARGET: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 99));
+
+    fn addr(last: u8) -> IpAddr {
+        IpAddr::V4(Ipv4Addr::new(10, 0, 0, last))
+    }
+
+    #[test]
+    fn test_waiting() {
+        let summary = TraceSummary::new(&Trace::new(8), TARGET);
+        assert_eq!(Reachability::Waiting, summary.reachability);
+        assert_eq!(0, summary.hops);
+    }
+
+    #[test]
+    fn test_reachable() {
+        let trace = make_trace(&[
+            &[(Some(addr(1)), 10), (Some(TARGET), 20)],
+            &[(Some(addr(1)), 10), (None, 0)],
+        ]);
+        let summary = TraceSummary::new(&trace, TARGET);
+        assert_eq!(Reachability::Reachable, summary.reachability);
+        assert_eq!(2, summary.hops);
+        assert_eq!(2, summary.sent);
+        assert!((summary.loss_pct - 50_f64).abs() < f64::EPSILON);
+        assert_eq!(Some(20_f64), summary.best_ms);
+        assert_eq!(Some(20_f64), summary.avg_ms);
+    }
+
+    #[test]
+    fn test_unreachable() {
+        let trace = make_trace(&[&[(Some(addr(1)), 10), (None, 0)]]);
+        let summary = TraceSummary::new(&trace, TARGET);
+        assert_eq!(Reachability::Unreachable, summary.reachability);
+        assert_eq!(None, summary.avg_ms);
+        assert!((summary.loss_pct - 100_f64).abs() < f64::EPSILON);
+    }
+}
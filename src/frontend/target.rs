@@ -0,0 +1,166 @@
+use crossterm::event::KeyCode;
+
+/// What to do with the trace of a target entered in the TUI.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TargetAction {
+    /// Trace the target in a new tab.
+    Add,
+    /// Trace the target in place of the selected trace.
+    Replace,
+}
+
+impl TargetAction {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Add => "add tab",
+            Self::Replace => "replace trace",
+        }
+    }
+
+    fn toggle(self) -> Self {
+        match self {
+            Self::Add => Self::Replace,
+            Self::Replace => Self::Add,
+        }
+    }
+}
+
+/// The outcome of a key pressed in the target input dialog.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputOutcome {
+    /// Keep editing.
+    Editing,
+    /// Close the dialog.
+    Cancel,
+    /// Trace the target entered.
+    Submit(String, TargetAction),
+}
+
+/// The state of the dialog in which a target to trace is entered.
+///
+/// Whilst the target is being resolved the input is kept, but not editable, and if it fails to resolve the error is
+/// shown until the input is next edited.
+#[derive(Debug)]
+pub struct TargetInput {
+    text: String,
+    action: TargetAction,
+    resolving: bool,
+    error: Option<String>,
+}
+
+impl TargetInput {
+    pub fn new(action: TargetAction) -> Self {
+        Self {
+            text: String::new(),
+            action,
+            resolving: false,
+            error: None,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn action(&self) -> TargetAction {
+        self.action
+    }
+
+    pub fn is_resolving(&self) -> bool {
+        self.resolving
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Record that the target submitted failed to resolve or to start tracing.
+    pub fn fail(&mut self, error: String) {
+        self.resolving = false;
+        self.error = Some(error);
+    }
+
+    /// Handle a key: `Enter` submits the target, `Tab` switches between adding a tab and replacing the selected trace
+    /// and `Esc` cancels.  Keys other than `Esc` are ignored whilst the target is being resolved.
+    pub fn key(&mut self, code: KeyCode) -> InputOutcome {
+        if self.resolving {
+            return if code == KeyCode::Esc {
+                InputOutcome::Cancel
+            } else {
+                InputOutcome::Editing
+            };
+        }
+        match code {
+            KeyCode::Esc => return InputOutcome::Cancel,
+            KeyCode::Enter => {
+                let target = self.text.trim();
+                if target.is_empty() {
+                    return InputOutcome::Editing;
+                }
+                self.resolving = true;
+                self.error = None;
+                return InputOutcome::Submit(target.to_string(), self.action);
+            }
+            KeyCode::Tab | KeyCode::BackTab => self.action = self.action.toggle(),
+            KeyCode::Backspace => {
+                self.text.pop();
+                self.error = None;
+            }
+            KeyCode::Char(c) if !c.is_whitespace() => {
+                self.text.push(c);
+                self.error = None;
+            }
+            _ => {}
+        }
+        InputOutcome::Editing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_text(input: &mut TargetInput, text: &str) {
+        for c in text.chars() {
+            assert_eq!(InputOutcome::Editing, input.key(KeyCode::Char(c)));
+        }
+    }
+
+    #[test]
+    fn test_edit_and_submit() {
+        let mut input = TargetInput::new(TargetAction::Add);
+        assert_eq!(InputOutcome::Editing, input.key(KeyCode::Enter));
+        type_text(&mut input, "example.comm");
+        input.key(KeyCode::Backspace);
+        input.key(KeyCode::Char(' '));
+        input.key(KeyCode::Tab);
+        assert_eq!("example.com", input.text());
+        assert_eq!(
+            InputOutcome::Submit(String::from("example.com"), TargetAction::Replace),
+            input.key(KeyCode::Enter)
+        );
+        assert!(input.is_resolving());
+    }
+
+    #[test]
+    fn test_resolving_ignores_keys() {
+        let mut input = TargetInput::new(TargetAction::Add);
+        type_text(&mut input, "example.com");
+        input.key(KeyCode::Enter);
+        assert_eq!(InputOutcome::Editing, input.key(KeyCode::Char('x')));
+        assert_eq!("example.com", input.text());
+        assert_eq!(InputOutcome::Cancel, input.key(KeyCode::Esc));
+    }
+
+    #[test]
+    fn test_failure_is_cleared_by_editing() {
+        let mut input = TargetInput::new(TargetAction::Add);
+        type_text(&mut input, "nosuchhost");
+        input.key(KeyCode::Enter);
+        input.fail(String::from("failed to resolve target"));
+        assert!(!input.is_resolving());
+        assert_eq!(Some("failed to resolve target"), input.error());
+        input.key(KeyCode::Backspace);
+        assert_eq!(None, input.error());
+    }
+}
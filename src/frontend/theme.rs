@@ -0,0 +1,208 @@
+use crate::config::{TuiTheme, TuiThemeItem};
+use tui::style::{Color, Style};
+
+/// The colors of the TUI, one for each `TuiThemeItem`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Theme {
+    pub text: Color,
+    pub text_dim: Color,
+    pub header_fg: Color,
+    pub header_bg: Color,
+    pub selected_fg: Color,
+    pub selected_bg: Color,
+    pub target: Color,
+    pub loss: Color,
+    pub chart: Color,
+    pub history: Color,
+    pub border: Color,
+    pub popup_fg: Color,
+    pub popup_bg: Color,
+    pub tab_selected_bg: Color,
+    pub good: Color,
+    pub warn: Color,
+    pub crit: Color,
+}
+
+impl Theme {
+    const DARK: Self = Self {
+        text: Color::Gray,
+        text_dim: Color::DarkGray,
+        header_fg: Color::Black,
+        header_bg: Color::White,
+        selected_fg: Color::Black,
+        selected_bg: Color::Gray,
+        target: Color::Green,
+        loss: Color::Red,
+        chart: Color::Green,
+        history: Color::Yellow,
+        border: Color::Reset,
+        popup_fg: Color::White,
+        popup_bg: Color::Blue,
+        tab_selected_bg: Color::Black,
+        good: Color::Green,
+        warn: Color::Yellow,
+        crit: Color::Red,
+    };
+
+    const LIGHT: Self = Self {
+        text: Color::Black,
+        text_dim: Color::DarkGray,
+        header_fg: Color::White,
+        header_bg: Color::DarkGray,
+        selected_fg: Color::White,
+        selected_bg: Color::Blue,
+        target: Color::Blue,
+        loss: Color::Red,
+        chart: Color::Blue,
+        history: Color::Magenta,
+        border: Color::DarkGray,
+        popup_fg: Color::Black,
+        popup_bg: Color::LightCyan,
+        tab_selected_bg: Color::Gray,
+        good: Color::Green,
+        warn: Color::Rgb(0xaf, 0x87, 0x00),
+        crit: Color::Red,
+    };
+
+    /// The built-in `theme` with the `overrides` applied, in order.
+    pub fn new(theme: TuiTheme, overrides: &[(TuiThemeItem, Color)]) -> Self {
+        let mut colors = match theme {
+            TuiTheme::Dark => Self::DARK,
+            TuiTheme::Light => Self::LIGHT,
+        };
+        for (item, color) in overrides {
+            *colors.color_mut(*item) = *color;
+        }
+        colors
+    }
+
+    fn color_mut(&mut self, item: TuiThemeItem) -> &mut Color {
+        match item {
+            TuiThemeItem::Text => &mut self.text,
+            TuiThemeItem::TextDim => &mut self.text_dim,
+            TuiThemeItem::HeaderFg => &mut self.header_fg,
+            TuiThemeItem::HeaderBg => &mut self.header_bg,
+            TuiThemeItem::SelectedFg => &mut self.selected_fg,
+            TuiThemeItem::SelectedBg => &mut self.selected_bg,
+            TuiThemeItem::Target => &mut self.target,
+            TuiThemeItem::Loss => &mut self.loss,
+            TuiThemeItem::Chart => &mut self.chart,
+            TuiThemeItem::History => &mut self.history,
+            TuiThemeItem::Border => &mut self.border,
+            TuiThemeItem::PopupFg => &mut self.popup_fg,
+            TuiThemeItem::PopupBg => &mut self.popup_bg,
+            TuiThemeItem::TabSelectedBg => &mut self.tab_selected_bg,
+            TuiThemeItem::Good => &mut self.good,
+            TuiThemeItem::Warn => &mut self.warn,
+            TuiThemeItem::Crit => &mut self.crit,
+        }
+    }
+
+    pub fn text(&self) -> Style {
+        Style::default().fg(self.text)
+    }
+
+    pub fn text_dim(&self) -> Style {
+        Style::default().fg(self.text_dim)
+    }
+
+    pub fn header(&self) -> Style {
+        Style::default().fg(self.header_fg).bg(self.header_bg)
+    }
+
+    pub fn selected(&self) -> Style {
+        Style::default().fg(self.selected_fg).bg(self.selected_bg)
+    }
+
+    pub fn border(&self) -> Style {
+        Style::default().fg(self.border)
+    }
+
+    pub fn popup(&self) -> Style {
+        Style::default().fg(self.popup_fg).bg(self.popup_bg)
+    }
+
+    /// The colors of the series of the hop comparison chart, in the order of the series.
+    pub fn series(&self) -> [Color; 4] {
+        [self.chart, self.history, self.popup_bg, self.crit]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{parse_tui_color, parse_tui_theme_color, TUI_COLORS};
+    use clap::ValueEnum;
+
+    #[test]
+    fn test_parse_color() {
+        for (name, color) in TUI_COLORS {
+            assert_eq!(color, parse_tui_color(name).unwrap());
+        }
+        assert_eq!(Color::DarkGray, parse_tui_color("Dark-Gray").unwrap());
+        assert_eq!(
+            Color::Rgb(0x1a, 0x2b, 0xff),
+            parse_tui_color("#1a2bFF").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_color_invalid() {
+        let error = |color: &str| parse_tui_color(color).unwrap_err().to_string();
+        assert_eq!(
+            "invalid color (#12345g), expected #rrggbb",
+            error("#12345g")
+        );
+        assert!(error("#12345")
+            .starts_with("unknown color (#12345), must be #rrggbb or one of default, black"));
+        assert!(error("pink").ends_with("light-cyan, white"));
+    }
+
+    #[test]
+    fn test_parse_theme_color() {
+        assert_eq!(
+            (TuiThemeItem::HeaderBg, Color::Rgb(0, 0, 0x80)),
+            parse_tui_theme_color("header-bg=#000080").unwrap()
+        );
+        assert_eq!(
+            (TuiThemeItem::Loss, Color::LightRed),
+            parse_tui_theme_color(" loss = light-red").unwrap()
+        );
+        let error = |spec: &str| parse_tui_theme_color(spec).unwrap_err().to_string();
+        assert_eq!(
+            "invalid theme color (loss), expected item=color",
+            error("loss")
+        );
+        assert!(
+            error("foo=red").starts_with("unknown theme item (foo), must be one of text, text-dim")
+        );
+        assert!(error("loss=pink").starts_with("unknown color (pink)"));
+    }
+
+    #[test]
+    fn test_override_every_item() {
+        let color = Color::Rgb(1, 2, 3);
+        for item in TuiThemeItem::value_variants() {
+            let name = item.to_possible_value().unwrap().get_name().to_string();
+            let spec = format!("{name}=#010203");
+            let mut theme = Theme::new(TuiTheme::Dark, &[parse_tui_theme_color(&spec).unwrap()]);
+            assert_eq!(color, *theme.color_mut(*item), "{name}");
+            let mut dark = Theme::DARK;
+            *theme.color_mut(*item) = *dark.color_mut(*item);
+            assert_eq!(Theme::DARK, theme, "{name} overrode another item");
+        }
+    }
+
+    #[test]
+    fn test_overrides_apply_in_order() {
+        let theme = Theme::new(
+            TuiTheme::Light,
+            &[
+                (TuiThemeItem::Text, Color::Red),
+                (TuiThemeItem::Text, Color::Cyan),
+            ],
+        );
+        assert_eq!(Color::Cyan, theme.text);
+        assert_eq!(Theme::LIGHT.border, theme.border);
+    }
+}
@@ -0,0 +1,159 @@
+use crate::backend::Hop;
+use crate::frontend::theme::Theme;
+use std::time::Duration;
+use tui::style::Style;
+
+/// How concerning a latency or packet loss is.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Good,
+    Warn,
+    Crit,
+}
+
+/// The thresholds at which latency and packet loss become concerning.
+#[derive(Debug, Copy, Clone)]
+pub struct Thresholds {
+    latency_warn_ms: f64,
+    latency_crit_ms: f64,
+    loss_crit_pct: f64,
+    /// Mark hops with `!` and `!!` glyphs rather than by color alone.
+    glyphs: bool,
+}
+
+impl Thresholds {
+    pub fn new(
+        latency_warn: Duration,
+        latency_crit: Duration,
+        loss_crit_pct: f64,
+        glyphs: bool,
+    ) -> Self {
+        Self {
+            latency_warn_ms: latency_warn.as_secs_f64() * 1000_f64,
+            latency_crit_ms: latency_crit.as_secs_f64() * 1000_f64,
+            loss_crit_pct,
+            glyphs,
+        }
+    }
+
+    /// The severity of a latency, in milliseconds, which is a warning from `latency_warn` and critical from
+    /// `latency_crit`.
+    pub fn latency(&self, ms: f64) -> Severity {
+        if ms >= self.latency_crit_ms {
+            Severity::Crit
+        } else if ms >= self.latency_warn_ms {
+            Severity::Warn
+        } else {
+            Severity::Good
+        }
+    }
+
+    /// The severity of a packet loss percentage, which is a warning if there is any loss and critical above
+    /// `loss_crit_pct`.
+    pub fn loss(&self, pct: f64) -> Severity {
+        if pct > self.loss_crit_pct {
+            Severity::Crit
+        } else if pct > 0_f64 {
+            Severity::Warn
+        } else {
+            Severity::Good
+        }
+    }
+
+    /// The severity of a hop, the worse of its average latency and its packet loss.
+    pub fn hop(&self, hop: &Hop) -> Severity {
+        let loss = self.loss(hop.loss_pct());
+        if hop.total_recv() > 0 {
+            loss.max(self.latency(hop.avg_ms()))
+        } else {
+            loss
+        }
+    }
+
+    /// The marker of a severity shown before the address of a hop, two characters wide.
+    pub fn marker(&self, severity: Severity) -> &'static str {
+        match (self.glyphs, severity) {
+            (false, _) => "● ",
+            (true, Severity::Good) => "  ",
+            (true, Severity::Warn) => "! ",
+            (true, Severity::Crit) => "!!",
+        }
+    }
+}
+
+/// The style of a severity.
+pub fn severity_style(severity: Severity, theme: &Theme) -> Style {
+    Style::default().fg(match severity {
+        Severity::Good => theme.good,
+        Severity::Warn => theme.warn,
+        Severity::Crit => theme.crit,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::testing::make_trace;
+    use crate::config::TuiTheme;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    fn thresholds(glyphs: bool) -> Thresholds {
+        Thresholds::new(
+            Duration::from_millis(50),
+            Duration::from_millis(150),
+            10_f64,
+            glyphs,
+        )
+    }
+
+    #[test]
+    fn test_latency_boundaries() {
+        let thresholds = thresholds(false);
+        assert_eq!(Severity::Good, thresholds.latency(0_f64));
+        assert_eq!(Severity::Good, thresholds.latency(49.9));
+        assert_eq!(Severity::Warn, thresholds.latency(50_f64));
+        assert_eq!(Severity::Warn, thresholds.latency(149.9));
+        assert_eq!(Severity::Crit, thresholds.latency(150_f64));
+    }
+
+    #[test]
+    fn test_loss_boundaries() {
+        let thresholds = thresholds(false);
+        assert_eq!(Severity::Good, thresholds.loss(0_f64));
+        assert_eq!(Severity::Warn, thresholds.loss(0.1));
+        assert_eq!(Severity::Warn, thresholds.loss(10_f64));
+        assert_eq!(Severity::Crit, thresholds.loss(10.1));
+        assert_eq!(Severity::Crit, thresholds.loss(100_f64));
+    }
+
+    #[test]
+    fn test_hop_severity() {
+        let addr = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+        let thresholds = thresholds(false);
+        let slow = make_trace(&[&[(addr, 100)], &[(addr, 120)]]);
+        assert_eq!(Severity::Warn, thresholds.hop(&slow.hops()[0]));
+        let lossy = make_trace(&[&[(addr, 10)], &[(None, 0)]]);
+        assert_eq!(Severity::Crit, thresholds.hop(&lossy.hops()[0]));
+        let silent = make_trace(&[&[(None, 0)]]);
+        assert_eq!(Severity::Crit, thresholds.hop(&silent.hops()[0]));
+        let good = make_trace(&[&[(addr, 10)], &[(addr, 20)]]);
+        assert_eq!(Severity::Good, thresholds.hop(&good.hops()[0]));
+    }
+
+    #[test]
+    fn test_markers() {
+        assert_eq!("● ", thresholds(false).marker(Severity::Crit));
+        let glyphs = thresholds(true);
+        assert_eq!("  ", glyphs.marker(Severity::Good));
+        assert_eq!("! ", glyphs.marker(Severity::Warn));
+        assert_eq!("!!", glyphs.marker(Severity::Crit));
+    }
+
+    #[test]
+    fn test_severity_style() {
+        let theme = Theme::new(TuiTheme::Dark, &[]);
+        assert_eq!(Some(theme.good), severity_style(Severity::Good, &theme).fg);
+        assert_eq!(Some(theme.warn), severity_style(Severity::Warn, &theme).fg);
+        assert_eq!(Some(theme.crit), severity_style(Severity::Crit, &theme).fg);
+    }
+}
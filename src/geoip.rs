@@ -0,0 +1,198 @@
+use anyhow::anyhow;
+use std::fs::File;
+use std::io::Read;
+use std::net::IpAddr;
+use std::path::Path;
+
+/// The location of an address.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeoLocation {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub city: String,
+    pub country: String,
+}
+
+/// A network and its location.
+#[derive(Debug, Clone)]
+struct GeoIpEntry {
+    network: IpAddr,
+    prefix_len: u8,
+    location: GeoLocation,
+}
+
+/// A database of the locations of networks.
+///
+/// The database is read from a CSV file with one `network,latitude,longitude,city,country` record per line, such as
+/// `203.0.113.0/24,51.5072,-0.1276,London,GB`, where lines starting with `#` are ignored.  The most specific network
+/// which contains an address is its location.
+#[derive(Debug, Default)]
+pub struct GeoIpDb {
+    /// The entries, the longest prefix first.
+    entries: Vec<GeoIpEntry>,
+}
+
+impl GeoIpDb {
+    /// Read a database from the CSV file at `path`.
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let file = File::open(path)
+            .map_err(|err| anyhow!("failed to open GeoIP file {}: {err}", path.display()))?;
+        Self::from_reader(file)
+            .map_err(|err| anyhow!("invalid GeoIP file {}: {err}", path.display()))
+    }
+
+    /// Read a database from CSV records.
+    pub fn from_reader<R: Read>(reader: R) -> anyhow::Result<Self> {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .comment(Some(b'#'))
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+        let mut entries = vec![];
+        for record in reader.records() {
+            let record = record?;
+            let line = record.position().map_or(0, csv::Position::line);
+            let field = |index: usize| {
+                record
+                    .get(index)
+                    .ok_or_else(|| anyhow!("missing field {} on line {line}", index + 1))
+            };
+            let (network, prefix_len) = parse_network(field(0)?).ok_or_else(|| {
+                anyhow!(
+                    "invalid network ({}) on line {line}",
+                    field(0).unwrap_or("")
+                )
+            })?;
+            let latitude = parse_coordinate(field(1)?, 90_f64)
+                .ok_or_else(|| anyhow!("invalid latitude on line {line}"))?;
+            let longitude = parse_coordinate(field(2)?, 180_f64)
+                .ok_or_else(|| anyhow!("invalid longitude on line {line}"))?;
+            entries.push(GeoIpEntry {
+                network,
+                prefix_len,
+                location: GeoLocation {
+                    latitude,
+                    longitude,
+                    city: field(3)?.to_string(),
+                    country: field(4)?.to_string(),
+                },
+            });
+        }
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.prefix_len));
+        Ok(Self { entries })
+    }
+
+    /// The location of `addr`, if known.
+    pub fn lookup(&self, addr: IpAddr) -> Option<&GeoLocation> {
+        self.entries
+            .iter()
+            .find(|entry| contains(entry.network, entry.prefix_len, addr))
+            .map(|entry| &entry.location)
+    }
+}
+
+/// Parse a network, i.e. `10.0.0.0/8`, or a single address.
+fn parse_network(network: &str) -> Option<(IpAddr, u8)> {
+    let (addr, prefix_len) = match network.split_once('/') {
+        Some((addr, prefix_len)) => (addr.parse::<IpAddr>().ok()?, Some(prefix_len.parse().ok()?)),
+        None => (network.parse::<IpAddr>().ok()?, None),
+    };
+    let max_len = if addr.is_ipv4() { 32 } else { 128 };
+    let prefix_len = prefix_len.unwrap_or(max_len);
+    (prefix_len <= max_len).then_some((addr, prefix_len))
+}
+
+/// Parse a latitude or longitude of at most `limit` degrees either side of zero.
+fn parse_coordinate(coordinate: &str, limit: f64) -> Option<f64> {
+    coordinate
+        .parse::<f64>()
+        .ok()
+        .filter(|degrees| degrees.abs() <= limit)
+}
+
+/// Is `addr` within the network `network/prefix_len`?
+fn contains(network: IpAddr, prefix_len: u8, addr: IpAddr) -> bool {
+    match (network, addr) {
+        (IpAddr::V4(network), IpAddr::V4(addr)) => {
+            let mask = u32::MAX
+                .checked_shl(32 - u32::from(prefix_len))
+                .unwrap_or(0);
+            u32::from(network) & mask == u32::from(addr) & mask
+        }
+        (IpAddr::V6(network), IpAddr::V6(addr)) => {
+            let mask = u128::MAX
+                .checked_shl(128 - u32::from(prefix_len))
+                .unwrap_or(0);
+            u128::from(network) & mask == u128::from(addr) & mask
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DB: &str = "\
+# network,latitude,longitude,city,country
+10.0.0.0/8, 51.5072, -0.1276, London, GB
+10.1.0.0/16, 48.8566, 2.3522, Paris, FR
+192.0.2.1, 40.7128, -74.006, New York, US
+2001:db8::/32, 35.6762, 139.6503, Tokyo, JP
+";
+
+    fn city(db: &GeoIpDb, addr: &str) -> Option<String> {
+        db.lookup(addr.parse().unwrap())
+            .map(|location| location.city.clone())
+    }
+
+    #[test]
+    fn test_lookup_most_specific_network() {
+        let db = GeoIpDb::from_reader(DB.as_bytes()).unwrap();
+        assert_eq!(Some(String::from("London")), city(&db, "10.2.3.4"));
+        assert_eq!(Some(String::from("Paris")), city(&db, "10.1.3.4"));
+        assert_eq!(Some(String::from("New York")), city(&db, "192.0.2.1"));
+        assert_eq!(None, city(&db, "192.0.2.2"));
+        assert_eq!(Some(String::from("Tokyo")), city(&db, "2001:db8::1"));
+        assert_eq!(None, city(&db, "2001:db9::1"));
+        let location = db.lookup("10.1.0.1".parse().unwrap()).unwrap();
+        assert_eq!(
+            &GeoLocation {
+                latitude: 48.8566,
+                longitude: 2.3522,
+                city: String::from("Paris"),
+                country: String::from("FR"),
+            },
+            location
+        );
+    }
+
+    #[test]
+    fn test_lookup_any_network() {
+        let db = GeoIpDb::from_reader(&b"0.0.0.0/0,1,2,Anywhere,ZZ"[..]).unwrap();
+        assert_eq!(Some(String::from("Anywhere")), city(&db, "198.51.100.1"));
+        assert_eq!(None, city(&db, "::1"));
+    }
+
+    #[test]
+    fn test_invalid_records() {
+        let error = |db: &str| GeoIpDb::from_reader(db.as_bytes()).unwrap_err().to_string();
+        assert_eq!(
+            "invalid network (10.0.0.0/33) on line 1",
+            error("10.0.0.0/33,1,2,City,ZZ")
+        );
+        assert_eq!(
+            "invalid network (foo) on line 2",
+            error("10.0.0.0/8,1,2,City,ZZ\nfoo,1,2,City,ZZ")
+        );
+        assert_eq!(
+            "invalid latitude on line 1",
+            error("10.0.0.0/8,91,2,City,ZZ")
+        );
+        assert_eq!(
+            "invalid longitude on line 1",
+            error("10.0.0.0/8,1,east,City,ZZ")
+        );
+        assert!(GeoIpDb::from_reader(&b""[..]).unwrap().entries.is_empty());
+    }
+}
@@ -10,126 +10,690 @@
     clippy::struct_excessive_bools
 )]
 #![forbid(unsafe_code)]
-use crate::backend::Trace;
+use crate::alert::{AlertThresholds, Health, PathMonitor, SharedHealth, ThresholdMonitor};
+use crate::backend::{Monitors, SharedTrace, Trace};
 use crate::caps::{drop_caps, ensure_caps};
-use crate::config::{Mode, TrippyConfig};
-use crate::dns::{DnsResolver, DnsResolverConfig};
-use crate::frontend::TuiConfig;
+use crate::capture::Capture;
+use crate::chrome::ChromeLog;
+use crate::config::{tcp_probes_warning, Command, LogFormat, Mode, TrippyConfig};
+use crate::dns::{DnsResolver, DnsResolverConfig, DnsSource, HostNotFound};
+use crate::event::{EventLevel, EventLog, SharedEventLog};
+use crate::frontend::{
+    CompactThresholds, Session, SharedStartupStatus, Startup, StartupStatus, Thresholds,
+    TraceLauncher, TuiConfig,
+};
+use crate::geoip::GeoIpDb;
+use crate::privileges::{DropTarget, System};
+use crate::report::{
+    CsvConfig, DiffConfig, InfluxConfig, ReportConfig, ReportMetadata, ReportWriter, StreamConfig,
+};
+use crate::signal::Interrupt;
+use crate::state::StateFile;
 use anyhow::{anyhow, Error};
-use clap::Parser;
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches};
 use config::Args;
+use itertools::Itertools;
 use parking_lot::RwLock;
+use std::fmt::{Display, Formatter};
 use std::net::IpAddr;
-use std::sync::Arc;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::{mpsc, Arc};
 use std::thread;
 use std::time::Duration;
-use trippy::tracing::SourceAddr;
-use trippy::tracing::{
-    MultipathStrategy, PortDirection, TracerAddrFamily, TracerChannelConfig, TracerConfig,
-    TracerProtocol,
+use trippy_core::SourceAddr;
+use trippy_core::{
+    set_thread_priority, CancellationToken, MultipathStrategy, PacketCapture, PortDirection,
+    RateLimitHeuristic, RoundParameters, TracerAddrFamily, TracerChannel, TracerChannelConfig,
+    TracerConfig, TracerPriority, TracerProtocol,
 };
 
+mod alert;
 mod backend;
 mod caps;
+mod capture;
+mod chrome;
 mod config;
 mod dns;
+mod event;
 mod frontend;
+mod geoip;
+mod privileges;
 mod report;
+mod rng;
+mod segment;
+mod signal;
+mod state;
+mod zone;
 
-fn main() -> anyhow::Result<()> {
-    let pid = u16::try_from(std::process::id() % u32::from(u16::MAX))?;
-    let cfg = TrippyConfig::try_from((Args::parse(), pid))?;
-    let resolver = start_dns_resolver(&cfg)?;
+fn main() -> anyhow::Result<ExitCode> {
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    if let Some(command) = args.command.take() {
+        return run_command(command).map(|()| ExitCode::SUCCESS);
+    }
+    // The arguments given on the command line, rather than defaulted, take precedence over any restored TUI state.
+    let explicit: Vec<_> = matches
+        .ids()
+        .filter(|id| matches.value_source(id.as_str()) == Some(ValueSource::CommandLine))
+        .map(|id| id.as_str().to_string())
+        .collect();
+    let cfg = Arc::new(TrippyConfig::try_from(args)?);
+    for warning in &cfg.target_warnings {
+        eprintln!("warning: {warning}");
+    }
+    if let Some(warning) = tcp_probes_warning(cfg.protocol, cfg.max_inflight, cfg.max_tcp_probes) {
+        eprintln!("warning: {warning}");
+    }
+    if cfg.print_config {
+        return print_config(&cfg).map(|()| ExitCode::SUCCESS);
+    }
+    let drop_target = make_drop_target(&cfg)?;
+    // If privileges are to be dropped then the output files are created once they are, such that the user owns them.
+    let (_log, log_open) = start_log(&cfg, drop_target.is_some())?;
+    let writer = match drop_target {
+        None => Some(ReportWriter::open(&cfg.output, cfg.mode)?),
+        Some(_) => None,
+    };
+    let (capture, capture_open) = start_capture(&cfg, drop_target.is_some())?;
+    let geoip = load_geoip(&cfg)?;
+    let events = EventLog::shared();
+    let resolver = start_dns_resolver(&cfg, &events)?;
+    let state = load_state(&cfg)?;
+    let packet_capture = capture.as_ref().map(Capture::packet_capture);
     ensure_caps()?;
-    let traces: Vec<_> = cfg
-        .targets
-        .iter()
-        .enumerate()
-        .map(|(i, target_host)| start_tracer(&cfg, target_host, pid + i as u16, &resolver))
-        .collect::<anyhow::Result<Vec<_>>>()?;
+    let drops_privileges = drop_target.is_some();
+    let identifiers = Arc::new(AtomicU16::new(cfg.trace_identifier));
+    let startup = start_tracers(
+        cfg.clone(),
+        identifiers.clone(),
+        packet_capture.clone(),
+        events.clone(),
+        drop_target,
+        log_open.into_iter().chain(capture_open).collect(),
+        state.clone(),
+    )?;
     drop_caps()?;
-    run_frontend(&cfg, resolver, traces)?;
+    let launcher = make_trace_launcher(
+        cfg.clone(),
+        identifiers,
+        packet_capture,
+        events.clone(),
+        drops_privileges,
+    );
+    let session = Session::open(&cfg.targets[0], &explicit, !cfg.tui_no_restore);
+    let unreachable = run_frontend(
+        &cfg, resolver, startup, events, geoip, writer, launcher, &session,
+    )?;
+    if let Some((state_file, trace_data)) = state {
+        state_file.save(&trace_data.snapshot())?;
+    }
+    if let Some(capture) = capture {
+        capture.finish()?;
+    }
+    Ok(if unreachable {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+/// Print the configuration of the run, and the addresses of the targets along with where they came from.
+fn print_config(cfg: &TrippyConfig) -> anyhow::Result<()> {
+    for (name, value) in cfg.settings() {
+        println!("{name}: {value}");
+    }
+    let resolver = start_dns_resolver(cfg, &EventLog::shared())?;
+    for target_host in &cfg.targets {
+        let (target_addrs, target_source) = resolve_target(cfg, target_host, &resolver)?;
+        for (name, value) in config::target_settings(target_host, &target_addrs, target_source) {
+            println!("{name}: {value}");
+        }
+    }
     Ok(())
 }
 
+/// Run a command which operates on existing reports.
+fn run_command(command: Command) -> anyhow::Result<()> {
+    match command {
+        #[cfg(feature = "cbor")]
+        Command::Decode { file } => report::run_decode(&file, &mut std::io::stdout().lock()),
+        Command::Diff {
+            before,
+            after,
+            rtt_threshold,
+            loss_threshold,
+            exit_code,
+        } => {
+            let config = DiffConfig::new(rtt_threshold, loss_threshold);
+            let regressed = report::run_diff(&before, &after, &config, &mut std::io::stdout())?;
+            if regressed && exit_code {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Start logging the spans of the session to the log file, if any, which is completed once the log returned is dropped.
+///
+/// No subscriber is installed without a log file, such that the spans cost next to nothing.
+///
+/// If `deferred` then the log file is only created once signalled by the sender returned, i.e. once privileges have
+/// been dropped.
+fn start_log(
+    cfg: &TrippyConfig,
+    deferred: bool,
+) -> anyhow::Result<(Option<ChromeLog>, Option<mpsc::Sender<()>>)> {
+    match (&cfg.log_file, cfg.log_format) {
+        (Some(path), LogFormat::Chrome) if deferred => {
+            let (open, gate) = mpsc::channel();
+            Ok((Some(chrome::start_after(path, gate)?), Some(open)))
+        }
+        (Some(path), LogFormat::Chrome) => Ok((Some(chrome::start(path)?), None)),
+        (None, _) => Ok((None, None)),
+    }
+}
+
+/// The user to drop privileges to once the raw sockets are created, if any.
+fn make_drop_target(cfg: &TrippyConfig) -> anyhow::Result<Option<DropTarget>> {
+    privileges::drop_request(
+        cfg.drop_privileges.as_deref(),
+        cfg.keep_privileges,
+        privileges::sudo_ids(),
+        privileges::DROP_BY_DEFAULT,
+    )?
+    .map(|request| privileges::drop_target(&System, &request))
+    .transpose()
+}
+
+/// Start capturing packets, if enabled.
+///
+/// If `deferred` then the capture file is only created once signalled by the sender returned, i.e. once privileges
+/// have been dropped.
+fn start_capture(
+    cfg: &TrippyConfig,
+    deferred: bool,
+) -> anyhow::Result<(Option<Capture>, Option<mpsc::Sender<()>>)> {
+    let Some(path) = &cfg.capture_file else {
+        return Ok((None, None));
+    };
+    if deferred {
+        let (open, gate) = mpsc::channel();
+        let capture = Capture::start_after(path, cfg.capture_max_size, gate)?;
+        Ok((Some(capture), Some(open)))
+    } else {
+        Ok((Some(Capture::start(path, cfg.capture_max_size)?), None))
+    }
+}
+
+/// Load the geolocation database, if any.
+fn load_geoip(cfg: &TrippyConfig) -> anyhow::Result<Option<GeoIpDb>> {
+    cfg.geoip_file
+        .as_deref()
+        .map(GeoIpDb::from_file)
+        .transpose()
+}
+
+/// Load the trace of the target from the state file, if any, to be resumed by its tracer and saved on exit.
+///
+/// A new trace is started if there is no such file.
+fn load_state(cfg: &TrippyConfig) -> anyhow::Result<Option<(StateFile, SharedTrace)>> {
+    let Some(path) = &cfg.state_file else {
+        return Ok(None);
+    };
+    let state_file = StateFile::new(
+        path.clone(),
+        cfg.targets[0].clone(),
+        cfg.protocol,
+        cfg.state_save_rounds,
+    );
+    let trace_data = match state_file.load(cfg.tui_max_samples)? {
+        Some(trace) => SharedTrace::from(trace),
+        None => SharedTrace::new(cfg.tui_max_samples),
+    };
+    Ok(Some((state_file, trace_data)))
+}
+
 /// Start the DNS resolver.
-fn start_dns_resolver(cfg: &TrippyConfig) -> anyhow::Result<DnsResolver> {
-    Ok(match cfg.addr_family {
-        TracerAddrFamily::Ipv4 => DnsResolver::start(DnsResolverConfig::new_ipv4(
-            cfg.dns_resolve_method,
-            cfg.dns_timeout,
-        ))?,
-        TracerAddrFamily::Ipv6 => DnsResolver::start(DnsResolverConfig::new_ipv6(
-            cfg.dns_resolve_method,
-            cfg.dns_timeout,
-        ))?,
+fn start_dns_resolver(cfg: &TrippyConfig, events: &SharedEventLog) -> anyhow::Result<DnsResolver> {
+    DnsResolver::start(make_dns_config(cfg), events.clone())
+}
+
+/// Make the DNS resolver configuration.
+fn make_dns_config(cfg: &TrippyConfig) -> DnsResolverConfig {
+    match cfg.addr_family {
+        TracerAddrFamily::Ipv4 => {
+            DnsResolverConfig::new_ipv4(cfg.dns_resolve_method, cfg.dns_timeout)
+        }
+        TracerAddrFamily::Ipv6 => {
+            DnsResolverConfig::new_ipv6(cfg.dns_resolve_method, cfg.dns_timeout)
+        }
+    }
+}
+
+/// Make the launcher of tracers to targets given in the TUI, each of which uses the next of the `identifiers`.
+///
+/// The launcher is called off the TUI thread and so resolves each target with a resolver of its own.  No targets can
+/// be added once privileges are dropped, as the raw sockets can then no longer be created.
+///
+/// A target is traced at its first resolved address only, unless its addresses are probed round robin, as a target
+/// given in the TUI is shown as a single trace.
+fn make_trace_launcher(
+    cfg: Arc<TrippyConfig>,
+    identifiers: Arc<AtomicU16>,
+    capture: Option<PacketCapture>,
+    events: SharedEventLog,
+    drops_privileges: bool,
+) -> TraceLauncher {
+    Arc::new(move |target_host: &str| {
+        if drops_privileges {
+            return Err(anyhow!(
+                "targets cannot be added once privileges have been dropped"
+            ));
+        }
+        let target = config::parse_target(target_host)?;
+        if let Some(warning) = target.warning {
+            events.write().push(EventLevel::Warn, warning);
+        }
+        let target_host = target.host.as_str();
+        let resolver = DnsResolver::start(make_dns_config(&cfg), events.clone())?;
+        let (mut target_addrs, target_source) = resolve_target(&cfg, target_host, &resolver)?;
+        if !cfg.round_robin {
+            target_addrs.truncate(1);
+        }
+        let trace_identifier = identifiers.fetch_add(1, Ordering::Relaxed);
+        start_tracer(
+            &cfg,
+            target_host,
+            &target_addrs,
+            target_source,
+            trace_identifier,
+            capture.as_ref(),
+            &events,
+            None,
+            None,
+        )
     })
 }
 
-/// Start a tracer to a given target.
-fn start_tracer(
+/// Start the tracers to the targets, each of which uses the next of the `identifiers`, on a thread of their own.
+///
+/// The step in progress is written to the status of the `Startup` such that the TUI can show it whilst waiting, which
+/// also resolves the targets with a resolver of its own.
+///
+/// Once every tracer has started, and so every raw socket has been created, privileges are dropped to the
+/// `drop_target`, if any, and then the log and capture files are created by signalling each of `deferred_opens`.  The
+/// traces fail to start if privileges cannot be dropped.
+///
+/// Every resolved address of a target is traced as a target of its own if `resolve_all` is set, unless its addresses
+/// are probed round robin in a single trace.
+///
+/// The tracer to the first target resumes the trace of the `state` file, if any, and saves to it.
+fn start_tracers(
+    cfg: Arc<TrippyConfig>,
+    identifiers: Arc<AtomicU16>,
+    capture: Option<PacketCapture>,
+    events: SharedEventLog,
+    drop_target: Option<DropTarget>,
+    deferred_opens: Vec<mpsc::Sender<()>>,
+    state: Option<(StateFile, SharedTrace)>,
+) -> anyhow::Result<Startup> {
+    let status = Arc::new(RwLock::new(StartupStatus::Starting));
+    let (sender, receiver) = mpsc::channel();
+    {
+        let status = status.clone();
+        thread::Builder::new()
+            .name(String::from("startup"))
+            .spawn(move || {
+                let traces = DnsResolver::start(make_dns_config(&cfg), events.clone()).and_then(
+                    |resolver| {
+                        let mut traces = vec![];
+                        for target_host in &cfg.targets {
+                            *status.write() = StartupStatus::Resolving(target_host.clone());
+                            let (target_addrs, target_source) =
+                                resolve_target(&cfg, target_host, &resolver)?;
+                            let target_addrs = if cfg.round_robin {
+                                vec![target_addrs]
+                            } else {
+                                target_addrs.into_iter().map(|addr| vec![addr]).collect()
+                            };
+                            for target_addrs in target_addrs {
+                                traces.push(start_tracer(
+                                    &cfg,
+                                    target_host,
+                                    &target_addrs,
+                                    target_source,
+                                    identifiers.fetch_add(1, Ordering::Relaxed),
+                                    capture.as_ref(),
+                                    &events,
+                                    Some(&status),
+                                    state.clone().filter(|_| traces.is_empty()),
+                                )?);
+                            }
+                        }
+                        Ok(traces)
+                    },
+                );
+                let traces = traces.and_then(|traces| {
+                    if let Some(target) = &drop_target {
+                        privileges::drop_privileges(&System, target)?;
+                    }
+                    for open in deferred_opens {
+                        // The capture thread is only gone if it failed, and the log is never gone.
+                        let _ = open.send(());
+                    }
+                    Ok(traces)
+                });
+                // The receiver is only gone if the user quit whilst waiting.
+                let _ = sender.send(traces);
+            })?;
+    }
+    Ok(Startup::new(status, receiver))
+}
+
+/// The addresses of the family of the trace to which `target_host` resolves, all of them if `resolve_all` is set and
+/// otherwise only the first, along with where they came from unless the target is an address.
+fn resolve_target(
     cfg: &TrippyConfig,
     target_host: &str,
-    trace_identifier: u16,
     resolver: &DnsResolver,
-) -> Result<TraceInfo, Error> {
-    let target_addr: IpAddr = resolver
-        .lookup(target_host)
-        .map_err(|e| anyhow!("failed to resolve target: {} ({})", target_host, e))?
-        .into_iter()
-        .find(|addr| {
+) -> anyhow::Result<(Vec<IpAddr>, Option<DnsSource>)> {
+    let (target_host, _) = zone::split_zone(target_host)?;
+    let (lookup_addrs, source) = resolver.lookup(target_host).map_err(|e| {
+        if e.is::<HostNotFound>() {
+            anyhow!(
+                "failed to resolve target: {target_host} ({e}), check the spelling of the hostname"
+            )
+        } else {
+            anyhow!("failed to resolve target: {} ({})", target_host, e)
+        }
+    })?;
+    let mut target_addrs: Vec<IpAddr> = lookup_addrs
+        .iter()
+        .copied()
+        .filter(|addr| {
             matches!(
                 (cfg.addr_family, addr),
                 (TracerAddrFamily::Ipv4, IpAddr::V4(_)) | (TracerAddrFamily::Ipv6, IpAddr::V6(_))
             )
         })
-        .ok_or_else(|| {
-            anyhow!(
-                "failed to find an {:?} address for target: {}",
-                cfg.addr_family,
-                target_host
-            )
-        })?;
+        .unique()
+        .collect();
+    if target_addrs.is_empty() {
+        let (traced, _) = config::addr_family_name(cfg.addr_family);
+        return Err(match lookup_addrs.first() {
+            Some(addr) => {
+                let (name, flag) = config::addr_family_name(config::addr_family_of(*addr));
+                anyhow!(
+                    "target {target_host} resolves only to {name} addresses, such as {addr}, but {traced} is traced, use {flag} to trace {name}"
+                )
+            }
+            None => anyhow!("failed to find an {traced} address for target: {target_host}"),
+        });
+    }
+    if !cfg.resolve_all {
+        target_addrs.truncate(1);
+    }
+    Ok((target_addrs, source))
+}
+
+/// Start a tracer to the `target_addrs` of a given target, writing each step to the startup `status`, if any.
+///
+/// The addresses are probed in turn, one each round, if there are several.  The tracer updates the trace of the
+/// `state` file, if any, and saves to it, rather than starting a new trace.
+#[allow(clippy::too_many_arguments)]
+fn start_tracer(
+    cfg: &TrippyConfig,
+    target_host: &str,
+    target_addrs: &[IpAddr],
+    target_source: Option<DnsSource>,
+    trace_identifier: u16,
+    capture: Option<&PacketCapture>,
+    events: &SharedEventLog,
+    status: Option<&SharedStartupStatus>,
+    state: Option<(StateFile, SharedTrace)>,
+) -> Result<TraceInfo, Error> {
+    let progress = |step: StartupStatus| {
+        if let Some(status) = status {
+            *status.write() = step;
+        }
+    };
+    let target_addr = target_addrs[0];
+    let (_, zone) = zone::split_zone(target_host)?;
+    let target_zone = zone::target_zone(target_addrs, zone, cfg.interface.as_deref())?;
+    let scope_id = target_zone.as_deref().map_or(Ok(0), zone::scope_id)?;
+    progress(StartupStatus::Discovering(target_host.to_string()));
     let source_addr = match cfg.source_addr {
-        None => SourceAddr::discover(target_addr, cfg.port_direction, cfg.interface.as_deref())?,
+        None => SourceAddr::discover(
+            target_addr,
+            cfg.port_direction,
+            cfg.interface.as_deref(),
+            scope_id,
+        )?,
         Some(addr) => SourceAddr::validate(addr)?,
     };
-    let trace_data = Arc::new(RwLock::new(Trace::new(cfg.tui_max_samples)));
-    let channel_config = make_channel_config(cfg, source_addr, target_addr, trace_identifier);
-    let tracer_config = make_tracer_config(cfg, target_addr, trace_identifier)?;
+    let (state_file, trace_data) = match state {
+        Some((state_file, trace_data)) => (Some(state_file), trace_data),
+        None => (None, SharedTrace::new(cfg.tui_max_samples)),
+    };
+    let channel_config =
+        make_channel_config(cfg, source_addr, target_addr, trace_identifier, capture)?
+            .with_scope_id(scope_id);
+    let mut tracer_config = make_tracer_config(cfg, target_addr, trace_identifier)?;
+    if target_addrs.len() > 1 {
+        tracer_config = tracer_config.with_round_robin(target_addrs.to_vec())?;
+    }
+    // The capabilities needed to connect the channel are raised first as a tracer started from the TUI runs on a
+    // thread of a thread which has already dropped them.
+    ensure_caps()?;
+    let channel = TracerChannel::connect(&channel_config).map_err(|err| anyhow!(err.render()))?;
+    drop_caps()?;
+    let mut trace_info = make_trace_info(
+        cfg,
+        trace_data.clone(),
+        source_addr,
+        target_host.to_string(),
+        target_addr,
+        target_zone,
+        target_source,
+    );
+    let parameters = RoundParameters::new(tracer_config.first_ttl, tracer_config.max_ttl)?;
+    trace_info.parameters = Some(parameters.clone());
+    let (raised_sender, raised) = mpsc::channel();
     {
-        let trace_data = trace_data.clone();
+        let events = events.clone();
+        let monitors = make_monitors(cfg, target_addr, trace_info.health.clone());
+        let cancellation = trace_info.cancellation.clone();
+        let priority = cfg.backend_priority;
         thread::Builder::new()
             .name(format!("tracer-{}", tracer_config.trace_identifier.0))
             .spawn(move || {
-                backend::run_backend(&tracer_config, &channel_config, trace_data)
-                    .expect("failed to run tracer backend");
+                // The receiver waits for the priority to be raised and so is never gone.
+                let _ = raised_sender.send(set_thread_priority(priority));
+                backend::run_backend(
+                    &tracer_config,
+                    channel,
+                    trace_data,
+                    &events,
+                    monitors,
+                    state_file,
+                    cancellation,
+                    parameters,
+                );
             })?;
     }
-    Ok(make_trace_info(
-        cfg,
-        trace_data,
-        source_addr,
-        target_host.to_string(),
-        target_addr,
-    ))
+    trace_info.priority = Priority {
+        requested: cfg.backend_priority,
+        raised: match raised.recv()? {
+            Ok(()) => true,
+            Err(err) => {
+                events.write().push(EventLevel::Warn, err.to_string());
+                false
+            }
+        },
+    };
+    Ok(trace_info)
+}
+
+/// Make the monitors of the trace to `target_addr`, which record the transitions between degraded and recovered in
+/// the `health` of the trace.
+///
+/// Changes in the path are only monitored if they are to be alerted in the TUI, and not if the addresses of the
+/// target are probed round robin as the path then changes with the address probed.  The path is only monitored for
+/// degradation if any thresholds are set, over the last `tui_max_samples` rounds.  Both are subject to
+/// `tui_alert_rounds` rounds of hysteresis.  The hops likely rate limiting ICMP are judged over the same samples.
+fn make_monitors(cfg: &TrippyConfig, target_addr: IpAddr, health: SharedHealth) -> Monitors {
+    let thresholds = AlertThresholds::new(cfg.alert_target_loss, cfg.alert_target_latency);
+    Monitors {
+        path: ((cfg.tui_bell || cfg.tui_notify) && !cfg.round_robin)
+            .then(|| PathMonitor::new(target_addr, cfg.tui_alert_rounds)),
+        thresholds: thresholds.is_set().then(|| {
+            ThresholdMonitor::new(
+                target_addr,
+                thresholds,
+                cfg.tui_max_samples,
+                cfg.tui_alert_rounds,
+            )
+        }),
+        health,
+        rate_limit: RateLimitHeuristic {
+            gap_pct: cfg.rate_limit_gap,
+            healthy_pct: cfg.rate_limit_healthy,
+            min_samples: RateLimitHeuristic::default()
+                .min_samples
+                .min(cfg.tui_max_samples),
+        },
+        convergence: cfg.convergence,
+    }
 }
 
-/// Run the TUI, stream or report.
+/// Run the TUI, stream or report, returning whether the target was unreachable, for the modes which exit with 1 if so.
+#[allow(clippy::too_many_lines, clippy::too_many_arguments)]
 fn run_frontend(
     args: &TrippyConfig,
     resolver: DnsResolver,
-    traces: Vec<TraceInfo>,
-) -> anyhow::Result<()> {
+    startup: Startup,
+    events: SharedEventLog,
+    geoip: Option<GeoIpDb>,
+    writer: Option<ReportWriter>,
+    launcher: TraceLauncher,
+    session: &Session,
+) -> anyhow::Result<bool> {
+    if matches!(args.mode, Mode::Tui) {
+        let tui_config = make_tui_config(args, geoip);
+        frontend::run_frontend(&startup, tui_config, resolver, events, launcher, session)?;
+        writer.map_or(Ok(()), ReportWriter::finish)?;
+        return Ok(false);
+    }
+    let traces = startup.wait()?;
+    let mut writer = match writer {
+        Some(writer) => writer,
+        None => ReportWriter::open(&args.output, args.mode)?,
+    };
+    let report_config = make_report_config(args, writer.is_tty());
+    let out = &mut writer;
+    let metadata = &ReportMetadata::new(&traces[0]);
     match args.mode {
-        Mode::Tui => frontend::run_frontend(traces, make_tui_config(args), resolver)?,
-        Mode::Stream => report::run_report_stream(&traces[0])?,
-        Mode::Csv => report::run_report_csv(&traces[0], args.report_cycles, &resolver)?,
-        Mode::Json => report::run_report_json(&traces[0], args.report_cycles, &resolver)?,
-        Mode::Pretty => report::run_report_table_pretty(&traces[0], args.report_cycles, &resolver)?,
-        Mode::Markdown => report::run_report_table_md(&traces[0], args.report_cycles, &resolver)?,
+        Mode::Tui => unreachable!("the tui is run before the traces have started"),
+        Mode::Stream => {
+            let stream_config = make_stream_config(args);
+            let interrupt = Interrupt::install()?;
+            report::run_report_stream(
+                &traces[0],
+                metadata,
+                &report_config,
+                &stream_config,
+                &resolver,
+                &interrupt,
+                out,
+            )?;
+        }
+        Mode::Csv => {
+            let csv_config = make_csv_config(args);
+            report::run_report_csv(
+                &traces[0],
+                metadata,
+                &report_config,
+                &resolver,
+                csv_config,
+                out,
+            )?;
+        }
+        Mode::Json => {
+            let include_samples = args.report_include_samples;
+            report::run_report_json(
+                &traces[0],
+                metadata,
+                &report_config,
+                &resolver,
+                include_samples,
+                args.report_include_segments,
+                out,
+            )?;
+        }
+        Mode::Pretty => {
+            let columns = &args.report_columns;
+            report::run_report_table_pretty(
+                &traces[0],
+                metadata,
+                &report_config,
+                &resolver,
+                columns,
+                out,
+            )?;
+        }
+        Mode::Markdown => {
+            let columns = &args.report_columns;
+            report::run_report_table_md(
+                &traces[0],
+                metadata,
+                &report_config,
+                &resolver,
+                columns,
+                out,
+            )?;
+        }
+        Mode::Dot => report::run_report_dot(&traces[0], metadata, &report_config, &resolver, out)?,
+        Mode::Mtr => report::run_report_mtr(&traces[0], metadata, &report_config, &resolver, out)?,
+        Mode::Classic => {
+            report::run_report_classic(&traces[0], metadata, &report_config, &resolver, out)?;
+        }
+        #[cfg(feature = "cbor")]
+        Mode::Cbor => {
+            let include_samples = args.report_include_samples;
+            report::run_report_cbor(
+                &traces[0],
+                metadata,
+                &report_config,
+                &resolver,
+                include_samples,
+                args.report_include_segments,
+                out,
+            )?;
+        }
+        Mode::Warts => report::run_report_warts(&traces[0], metadata, &report_config, out)?,
+        Mode::OneshotJson => {
+            let reached = report::run_report_oneshot_json(&traces[0], metadata, args.timeout, out)?;
+            writer.finish()?;
+            return Ok(!reached);
+        }
+        Mode::Prometheus => {
+            let listen = args.listen.expect("listen address must be set");
+            report::run_report_prometheus(&traces, listen, &Interrupt::install()?)?;
+        }
+        Mode::Influx => {
+            let influx_config = make_influx_config(args);
+            report::run_report_influx(&traces[0], &influx_config, &Interrupt::install()?, out)?;
+        }
+        Mode::Sqlite => {
+            let path = args.output.file_path().expect("output file must be set");
+            report::run_report_sqlite(&traces[0], path, &Interrupt::install()?)?;
+        }
     }
-    Ok(())
+    writer.finish()?;
+    Ok(false)
 }
 
 /// Make the tracer configuration.
@@ -138,7 +702,7 @@ fn make_tracer_config(
     target_addr: IpAddr,
     trace_identifier: u16,
 ) -> anyhow::Result<TracerConfig> {
-    Ok(TracerConfig::new(
+    let config = TracerConfig::new(
         target_addr,
         args.protocol,
         args.max_rounds,
@@ -153,7 +717,22 @@ fn make_tracer_config(
         args.max_round_duration,
         args.packet_size,
         args.payload_pattern,
-    )?)
+    )?
+    .with_ttl_margin(args.ttl_margin)
+    // Each trace draws its own offsets, such that the rounds of several targets are not offset in step.
+    .with_round_jitter(
+        args.round_jitter,
+        args.round_jitter_seed ^ u64::from(trace_identifier),
+    )?;
+    let config = match args.fixed_ttl {
+        Some(fixed_ttl) => config.with_fixed_ttl(fixed_ttl)?,
+        None => config,
+    };
+    Ok(if args.adaptive_inflight {
+        config.with_adaptive_inflight()
+    } else {
+        config
+    })
 }
 
 /// Make the tracer configuration.
@@ -162,8 +741,9 @@ fn make_channel_config(
     source_addr: IpAddr,
     target_addr: IpAddr,
     trace_identifier: u16,
-) -> TracerChannelConfig {
-    TracerChannelConfig::new(
+    capture: Option<&PacketCapture>,
+) -> anyhow::Result<TracerChannelConfig> {
+    Ok(TracerChannelConfig::new(
         args.protocol,
         args.addr_family,
         source_addr,
@@ -176,36 +756,53 @@ fn make_channel_config(
         args.multipath_strategy,
         args.port_direction,
         args.read_timeout,
-        args.min_round_duration,
+        args.tcp_connect_timeout,
+        capture.cloned(),
+        args.interface.clone(),
+        args.fwmark,
     )
+    .with_max_tcp_probes(args.max_tcp_probes)?
+    .with_icmp_probe_type(args.icmp_probe_type)?
+    .with_payload_stamp(args.payload_stamp)?)
 }
 
 /// Make the per-trace information.
 fn make_trace_info(
     args: &TrippyConfig,
-    trace_data: Arc<RwLock<Trace>>,
+    trace_data: SharedTrace,
     source_addr: IpAddr,
     target: String,
     target_addr: IpAddr,
+    target_zone: Option<String>,
+    target_source: Option<DnsSource>,
 ) -> TraceInfo {
-    TraceInfo::new(
-        trace_data,
-        source_addr,
-        target,
-        target_addr,
-        args.multipath_strategy,
-        args.port_direction,
-        args.protocol,
-        args.addr_family,
-        args.first_ttl,
-        args.max_ttl,
-        args.grace_duration,
-        args.min_round_duration,
-    )
+    TraceInfo {
+        fixed_ttl: args.fixed_ttl.is_some(),
+        target_zone,
+        target_source,
+        ..TraceInfo::new(
+            trace_data,
+            source_addr,
+            target,
+            target_addr,
+            args.multipath_strategy,
+            args.port_direction,
+            args.protocol,
+            args.addr_family,
+            args.first_ttl,
+            args.max_ttl,
+            args.grace_duration,
+            args.min_round_duration,
+            args.packet_size,
+            args.interface.clone(),
+            args.fwmark,
+            args.rng_seed,
+        )
+    }
 }
 
 /// Make the TUI configuration.
-fn make_tui_config(args: &TrippyConfig) -> TuiConfig {
+fn make_tui_config(args: &TrippyConfig, geoip: Option<GeoIpDb>) -> TuiConfig {
     TuiConfig::new(
         args.tui_refresh_rate,
         args.tui_preserve_screen,
@@ -213,31 +810,102 @@ fn make_tui_config(args: &TrippyConfig) -> TuiConfig {
         args.dns_lookup_as_info,
         args.tui_max_addrs,
         args.tui_max_samples,
+        args.tui_columns.clone(),
+        args.tui_theme,
+        args.tui_theme_colors.clone(),
+        Thresholds::new(
+            args.tui_latency_warn,
+            args.tui_latency_crit,
+            args.tui_loss_crit,
+            args.tui_severity_glyphs,
+        ),
+        args.tui_key_bindings.clone(),
+        args.tui_hide_unresponsive,
+        args.tui_clipboard,
+        args.tui_export_dir.clone(),
+        args.report_columns.clone(),
+        args.tui_bell,
+        args.tui_notify,
+        geoip,
+        CompactThresholds::new(args.tui_compact_width, args.tui_compact_height),
+        args.tui_rdap,
+    )
+}
+
+/// Make the report configuration.
+///
+/// A progressive report is only redrawn in place when written to a terminal.
+fn make_report_config(args: &TrippyConfig, tty: bool) -> ReportConfig {
+    ReportConfig::new(
+        args.report_cycles,
+        args.dns_lookup,
+        args.dns_timeout,
+        !args.quiet,
+        args.report_until_converged,
+        args.report_progressive && tty,
+    )
+}
+
+/// Make the stream report configuration.
+fn make_stream_config(args: &TrippyConfig) -> StreamConfig {
+    StreamConfig::new(args.stream_time_format.clone(), args.stream_hop_timestamps)
+}
+
+/// Make the CSV report configuration.
+fn make_csv_config(args: &TrippyConfig) -> CsvConfig {
+    CsvConfig::new(args.csv_delimiter, args.csv_explode_addrs, args.csv_header)
+}
+
+/// Make the influx line protocol report configuration.
+fn make_influx_config(args: &TrippyConfig) -> InfluxConfig {
+    InfluxConfig::new(
+        args.influx_measurement.clone(),
+        args.influx_tags.clone(),
+        args.influx_precision,
     )
 }
 
 /// Information about a `Trace` needed for the Tui, stream and reports.
 #[derive(Debug, Clone)]
 pub struct TraceInfo {
-    pub data: Arc<RwLock<Trace>>,
+    pub data: SharedTrace,
     pub source_addr: IpAddr,
     pub target_hostname: String,
     pub target_addr: IpAddr,
+    /// The zone of a link-local IPv6 target, the interface through which it is reached, if any.
+    pub target_zone: Option<String>,
+    /// Where the address of the target came from, unless the target was given as an address.
+    pub target_source: Option<DnsSource>,
     pub multipath_strategy: MultipathStrategy,
     pub port_direction: PortDirection,
     pub protocol: TracerProtocol,
     pub addr_family: TracerAddrFamily,
     pub first_ttl: u8,
     pub max_ttl: u8,
+    /// Whether every probe is sent with the one ttl, which is both `first_ttl` and `max_ttl`.
+    pub fixed_ttl: bool,
     pub grace_duration: Duration,
     pub min_round_duration: Duration,
+    pub packet_size: u16,
+    pub interface: Option<String>,
+    pub fwmark: Option<u32>,
+    /// The seed of the random number generator of the run, with which it may be repeated.
+    pub rng_seed: u64,
+    /// Stops the tracer of the trace.
+    pub cancellation: CancellationToken,
+    /// The range of ttl probed by the tracer of the trace, which may be changed whilst it runs, if it is running.
+    pub parameters: Option<RoundParameters>,
+    /// The transitions of the path to the target between degraded and recovered.
+    pub health: SharedHealth,
+    /// The scheduling priority of the tracer of the trace.
+    pub priority: Priority,
 }
 
 impl TraceInfo {
     #[allow(clippy::too_many_arguments)]
     #[must_use]
     pub fn new(
-        data: Arc<RwLock<Trace>>,
+        data: SharedTrace,
         source_addr: IpAddr,
         target_hostname: String,
         target_addr: IpAddr,
@@ -249,20 +917,61 @@ impl TraceInfo {
         max_ttl: u8,
         grace_duration: Duration,
         min_round_duration: Duration,
+        packet_size: u16,
+        interface: Option<String>,
+        fwmark: Option<u32>,
+        rng_seed: u64,
     ) -> Self {
         Self {
             data,
             source_addr,
             target_hostname,
             target_addr,
+            target_zone: None,
+            target_source: None,
             multipath_strategy,
             port_direction,
             protocol,
             addr_family,
             first_ttl,
             max_ttl,
+            fixed_ttl: false,
             grace_duration,
             min_round_duration,
+            packet_size,
+            interface,
+            fwmark,
+            rng_seed,
+            cancellation: CancellationToken::new(),
+            parameters: None,
+            health: Health::shared(),
+            priority: Priority::default(),
+        }
+    }
+}
+
+/// The scheduling priority requested for a tracer and whether its thread could be raised to it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Priority {
+    pub requested: TracerPriority,
+    pub raised: bool,
+}
+
+impl Default for Priority {
+    fn default() -> Self {
+        Self {
+            requested: TracerPriority::Normal,
+            raised: true,
+        }
+    }
+}
+
+impl Display for Priority {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.raised {
+            write!(f, "{} priority", self.requested)
+        } else {
+            write!(f, "normal priority ({} denied)", self.requested)
         }
     }
 }
@@ -0,0 +1,399 @@
+use anyhow::anyhow;
+use std::io;
+
+/// The user to which privileges are dropped once the raw sockets have been created.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DropTarget {
+    pub name: String,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+/// The user, if any, whose privileges to run with once the raw sockets have been created.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DropRequest {
+    /// The user given by name with `--drop-privileges <user>`.
+    User(String),
+    /// The user who invoked trippy with `sudo`, by the `SUDO_UID` and `SUDO_GID` it sets.
+    Invoker { uid: u32, gid: u32 },
+}
+
+/// Whether privileges are dropped to the invoking user when launched with `sudo` without `--drop-privileges`.
+///
+/// Not on macOS, where the supplementary groups of the user cannot be set, and so a drop could not complete.
+pub const DROP_BY_DEFAULT: bool = cfg!(not(target_os = "macos"));
+
+/// The user to drop privileges to, if any.
+///
+/// Privileges are dropped to the `user` given with `--drop-privileges`, or if given without a user to the user who
+/// invoked trippy with `sudo`, by the `sudo_ids` it sets.  When launched with `sudo` privileges are dropped to the
+/// invoking user if `by_default`, unless `keep` is set.
+pub fn drop_request(
+    drop_privileges: Option<&str>,
+    keep: bool,
+    sudo_ids: Option<(u32, u32)>,
+    by_default: bool,
+) -> anyhow::Result<Option<DropRequest>> {
+    let invoker = sudo_ids.map(|(uid, gid)| DropRequest::Invoker { uid, gid });
+    match drop_privileges {
+        Some("") => invoker
+            .map(Some)
+            .ok_or_else(|| anyhow!("--drop-privileges requires a user unless launched with sudo")),
+        Some(user) => Ok(Some(DropRequest::User(user.to_string()))),
+        None if keep || !by_default => Ok(None),
+        None => Ok(invoker),
+    }
+}
+
+/// The calls to the operating system with which privileges are dropped.
+pub trait Syscalls {
+    /// The effective user id of the process.
+    fn effective_uid(&self) -> u32;
+    /// The user and group ids of the user `name`, if there is such a user.
+    fn lookup_user(&self, name: &str) -> io::Result<Option<(u32, u32)>>;
+    /// The name of the user `uid`, if there is such a user.
+    fn user_name(&self, uid: u32) -> io::Result<Option<String>>;
+    /// Set the supplementary groups of the process to those of the user `name` in the group database, and `gid`.
+    fn init_groups(&self, name: &str, gid: u32) -> io::Result<()>;
+    fn set_gid(&self, gid: u32) -> io::Result<()>;
+    fn set_uid(&self, uid: u32) -> io::Result<()>;
+}
+
+/// The user to which privileges are dropped for `request`.
+pub fn drop_target(sys: &impl Syscalls, request: &DropRequest) -> anyhow::Result<DropTarget> {
+    match request {
+        DropRequest::User(name) => {
+            let (uid, gid) = sys
+                .lookup_user(name)?
+                .ok_or_else(|| anyhow!("unknown user to drop privileges to: {}", name))?;
+            Ok(DropTarget {
+                name: name.clone(),
+                uid,
+                gid,
+            })
+        }
+        DropRequest::Invoker { uid, gid } => Ok(DropTarget {
+            name: sys
+                .user_name(*uid)?
+                .ok_or_else(|| anyhow!("unknown user to drop privileges to: uid {}", uid))?,
+            uid: *uid,
+            gid: *gid,
+        }),
+    }
+}
+
+/// Drop the privileges of the process to those of `target`, with the supplementary groups of the user.
+///
+/// The drop is verified by attempting to regain root, which must fail, and an error is returned if any step fails
+/// such that trippy refuses to continue with privileges it was asked to give up.  Nothing is done if the process
+/// already runs as the user, such as when its capabilities were granted to the binary.
+pub fn drop_privileges(sys: &impl Syscalls, target: &DropTarget) -> anyhow::Result<()> {
+    if sys.effective_uid() == target.uid {
+        return Ok(());
+    }
+    let failed = |call: &str, err: io::Error| {
+        anyhow!(
+            "failed to drop privileges to {} ({}: {}), refusing to continue",
+            target.name,
+            call,
+            err
+        )
+    };
+    sys.init_groups(&target.name, target.gid)
+        .map_err(|err| failed("initgroups", err))?;
+    sys.set_gid(target.gid)
+        .map_err(|err| failed("setgid", err))?;
+    sys.set_uid(target.uid)
+        .map_err(|err| failed("setuid", err))?;
+    if sys.set_uid(0).is_ok() || sys.effective_uid() != target.uid {
+        return Err(anyhow!(
+            "failed to drop privileges to {} (root could be regained), refusing to continue",
+            target.name
+        ));
+    }
+    Ok(())
+}
+
+/// The `SUDO_UID` and `SUDO_GID` set by `sudo`, if launched with it.
+pub fn sudo_ids() -> Option<(u32, u32)> {
+    let id = |name| std::env::var(name).ok()?.parse().ok();
+    Some((id("SUDO_UID")?, id("SUDO_GID")?))
+}
+
+/// The calls of the operating system.
+pub struct System;
+
+#[cfg(unix)]
+impl Syscalls for System {
+    fn effective_uid(&self) -> u32 {
+        nix::unistd::geteuid().as_raw()
+    }
+
+    fn lookup_user(&self, name: &str) -> io::Result<Option<(u32, u32)>> {
+        Ok(nix::unistd::User::from_name(name)?.map(|user| (user.uid.as_raw(), user.gid.as_raw())))
+    }
+
+    fn user_name(&self, uid: u32) -> io::Result<Option<String>> {
+        Ok(nix::unistd::User::from_uid(nix::unistd::Uid::from_raw(uid))?.map(|user| user.name))
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    fn init_groups(&self, name: &str, gid: u32) -> io::Result<()> {
+        let name = std::ffi::CString::new(name)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        Ok(nix::unistd::initgroups(
+            &name,
+            nix::unistd::Gid::from_raw(gid),
+        )?)
+    }
+
+    /// The supplementary groups cannot be set on macOS without `unsafe` code, as `nix` has no `initgroups` for it, and
+    /// so the drop fails rather than retaining those of root.
+    #[cfg(target_os = "macos")]
+    fn init_groups(&self, _name: &str, _gid: u32) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "the supplementary groups of root cannot be dropped on macOS",
+        ))
+    }
+
+    fn set_gid(&self, gid: u32) -> io::Result<()> {
+        Ok(nix::unistd::setgid(nix::unistd::Gid::from_raw(gid))?)
+    }
+
+    fn set_uid(&self, uid: u32) -> io::Result<()> {
+        Ok(nix::unistd::setuid(nix::unistd::Uid::from_raw(uid))?)
+    }
+}
+
+/// Privileges are not dropped on Windows, where raw sockets require running as an administrator throughout.
+#[cfg(not(unix))]
+impl Syscalls for System {
+    fn effective_uid(&self) -> u32 {
+        0
+    }
+
+    fn lookup_user(&self, _name: &str) -> io::Result<Option<(u32, u32)>> {
+        Ok(Some((0, 0)))
+    }
+
+    fn user_name(&self, _uid: u32) -> io::Result<Option<String>> {
+        Ok(Some(String::new()))
+    }
+
+    fn init_groups(&self, _name: &str, _gid: u32) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn set_gid(&self, _gid: u32) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn set_uid(&self, _uid: u32) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+
+    /// Syscalls which record each call and fail the call named `fail`, if any.
+    struct MockSyscalls {
+        euid: Cell<u32>,
+        fail: Option<&'static str>,
+        /// Can root be regained after setting the user id?
+        regain: bool,
+        calls: RefCell<Vec<String>>,
+    }
+
+    impl MockSyscalls {
+        fn root() -> Self {
+            Self {
+                euid: Cell::new(0),
+                fail: None,
+                regain: false,
+                calls: RefCell::new(vec![]),
+            }
+        }
+
+        fn call(&self, call: &'static str, id: u32) -> io::Result<()> {
+            self.calls.borrow_mut().push(format!("{call}({id})"));
+            if self.fail == Some(call) {
+                Err(io::Error::from(io::ErrorKind::PermissionDenied))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    impl Syscalls for MockSyscalls {
+        fn effective_uid(&self) -> u32 {
+            self.euid.get()
+        }
+
+        fn lookup_user(&self, name: &str) -> io::Result<Option<(u32, u32)>> {
+            Ok((name == "alice").then_some((1000, 100)))
+        }
+
+        fn user_name(&self, uid: u32) -> io::Result<Option<String>> {
+            Ok((uid == 1000).then(|| String::from("alice")))
+        }
+
+        fn init_groups(&self, _name: &str, gid: u32) -> io::Result<()> {
+            self.call("initgroups", gid)
+        }
+
+        fn set_gid(&self, gid: u32) -> io::Result<()> {
+            self.call("setgid", gid)
+        }
+
+        fn set_uid(&self, uid: u32) -> io::Result<()> {
+            if uid == 0 && !self.regain && self.euid.get() != 0 {
+                return Err(io::Error::from(io::ErrorKind::PermissionDenied));
+            }
+            self.call("setuid", uid)?;
+            self.euid.set(uid);
+            Ok(())
+        }
+    }
+
+    const ALICE: DropTarget = DropTarget {
+        name: String::new(),
+        uid: 1000,
+        gid: 100,
+    };
+
+    #[test]
+    fn test_drop_request() {
+        let sudo = Some((1000, 100));
+        let invoker = Some(DropRequest::Invoker {
+            uid: 1000,
+            gid: 100,
+        });
+        assert_eq!(invoker, drop_request(None, false, sudo, true).unwrap());
+        assert_eq!(None, drop_request(None, true, sudo, true).unwrap());
+        assert_eq!(None, drop_request(None, false, None, true).unwrap());
+        assert_eq!(invoker, drop_request(Some(""), false, sudo, true).unwrap());
+        assert!(drop_request(Some(""), false, None, true).is_err());
+        assert_eq!(
+            Some(DropRequest::User(String::from("bob"))),
+            drop_request(Some("bob"), false, sudo, true).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_drop_request_not_by_default() {
+        let sudo = Some((1000, 100));
+        assert_eq!(None, drop_request(None, false, sudo, false).unwrap());
+        assert_eq!(
+            Some(DropRequest::Invoker {
+                uid: 1000,
+                gid: 100
+            }),
+            drop_request(Some(""), false, sudo, false).unwrap()
+        );
+        assert_eq!(
+            Some(DropRequest::User(String::from("bob"))),
+            drop_request(Some("bob"), false, sudo, false).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_drop_by_default() {
+        assert_eq!(!cfg!(target_os = "macos"), DROP_BY_DEFAULT);
+        let sudo = Some((1000, 100));
+        assert_eq!(
+            DROP_BY_DEFAULT,
+            drop_request(None, false, sudo, DROP_BY_DEFAULT)
+                .unwrap()
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn test_drop_target() {
+        let sys = MockSyscalls::root();
+        let target = drop_target(&sys, &DropRequest::User(String::from("alice"))).unwrap();
+        assert_eq!((1000, 100), (target.uid, target.gid));
+        assert!(drop_target(&sys, &DropRequest::User(String::from("mallory"))).is_err());
+    }
+
+    #[test]
+    fn test_drop_target_invoker() {
+        let sys = MockSyscalls::root();
+        let target = drop_target(
+            &sys,
+            &DropRequest::Invoker {
+                uid: 1000,
+                gid: 100,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            DropTarget {
+                name: String::from("alice"),
+                uid: 1000,
+                gid: 100
+            },
+            target
+        );
+        let err = drop_target(
+            &sys,
+            &DropRequest::Invoker {
+                uid: 1001,
+                gid: 100,
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            "unknown user to drop privileges to: uid 1001",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_drop_privileges() {
+        let sys = MockSyscalls::root();
+        drop_privileges(&sys, &ALICE).unwrap();
+        assert_eq!(
+            vec!["initgroups(100)", "setgid(100)", "setuid(1000)"],
+            *sys.calls.borrow()
+        );
+        assert_eq!(1000, sys.effective_uid());
+    }
+
+    #[test]
+    fn test_already_dropped() {
+        let sys = MockSyscalls {
+            euid: Cell::new(1000),
+            ..MockSyscalls::root()
+        };
+        drop_privileges(&sys, &ALICE).unwrap();
+        assert!(sys.calls.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_refuse_if_call_fails() {
+        for call in ["initgroups", "setgid", "setuid"] {
+            let sys = MockSyscalls {
+                fail: Some(call),
+                ..MockSyscalls::root()
+            };
+            let err = drop_privileges(&sys, &ALICE).unwrap_err().to_string();
+            assert!(err.contains(call), "{err}");
+            assert!(err.ends_with("refusing to continue"), "{err}");
+        }
+    }
+
+    #[test]
+    fn test_refuse_if_root_regained() {
+        let sys = MockSyscalls {
+            regain: true,
+            ..MockSyscalls::root()
+        };
+        let err = drop_privileges(&sys, &ALICE).unwrap_err().to_string();
+        assert!(err.contains("root could be regained"), "{err}");
+    }
+}
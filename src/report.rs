@@ -1,100 +1,774 @@
+use crate::alert::{Alert, Breach};
+use crate::backend::Hop;
+use crate::config::{Column, Columns, Mode, ReportOutput};
+use crate::dns::{DnsEntry, DnsSource, Resolved};
+use crate::segment::segments;
+use crate::signal::Interrupt;
+use crate::zone;
 use crate::{DnsResolver, Trace, TraceInfo};
 use anyhow::anyhow;
+use chrono::{DateTime, SecondsFormat, Utc};
 use comfy_table::presets::{ASCII_MARKDOWN, UTF8_FULL};
-use comfy_table::{ContentArrangement, Table};
+use comfy_table::{Attribute, Cell, ContentArrangement, Table};
+use crossterm::tty::IsTty;
 use itertools::Itertools;
-use parking_lot::RwLock;
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::thread::sleep;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
+use std::{fs, io};
+use trippy_core::{ChannelMetrics, FlowKey};
+
+#[cfg(feature = "cbor")]
+mod cbor;
+mod classic;
+mod diff;
+mod dot;
+mod influx;
+mod mtr;
+mod oneshot;
+mod prometheus;
+mod sqlite;
+mod warts;
+
+#[cfg(feature = "cbor")]
+pub use cbor::{run_decode, run_report_cbor};
+pub use classic::run_report_classic;
+pub use diff::{run_diff, DiffConfig};
+pub use dot::run_report_dot;
+pub use influx::{run_report_influx, InfluxConfig};
+pub use mtr::run_report_mtr;
+pub use oneshot::run_report_oneshot_json;
+pub use prometheus::run_report_prometheus;
+pub use sqlite::run_report_sqlite;
+pub use warts::run_report_warts;
+
+/// The destination of report output.
+///
+/// When writing a report to a file (other than in append mode) the data is written to a temporary file in the same
+/// directory which is renamed into place by `finish` so that an incomplete report never replaces the target file.
+pub struct ReportWriter {
+    sink: Sink,
+}
+
+enum Sink {
+    Stdout(io::Stdout),
+    File(BufWriter<File>),
+    Atomic {
+        file: BufWriter<File>,
+        tmp_path: PathBuf,
+        path: PathBuf,
+    },
+}
+
+impl ReportWriter {
+    /// Open the report output.
+    ///
+    /// Any file is created here, before tracing begins, so that permission errors are reported immediately.
+    ///
+    /// The `Stream` and `Influx` modes never complete, and are followed as they are written, and so are written to the
+    /// target file directly.  The `Sqlite` mode manages the database file itself and so nothing is opened here.
+    pub fn open(output: &ReportOutput, mode: Mode) -> anyhow::Result<Self> {
+        let sink = match output {
+            _ if matches!(mode, Mode::Sqlite) => Sink::Stdout(io::stdout()),
+            ReportOutput::Stdout => Sink::Stdout(io::stdout()),
+            ReportOutput::Append(path) => Sink::File(BufWriter::new(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|e| output_error(path, &e))?,
+            )),
+            ReportOutput::File(path) if matches!(mode, Mode::Stream | Mode::Influx) => Sink::File(
+                BufWriter::new(File::create(path).map_err(|e| output_error(path, &e))?),
+            ),
+            ReportOutput::File(path) => {
+                let tmp_path = temp_path(path);
+                let file = File::create(&tmp_path).map_err(|e| output_error(path, &e))?;
+                Sink::Atomic {
+                    file: BufWriter::new(file),
+                    tmp_path,
+                    path: path.clone(),
+                }
+            }
+        };
+        Ok(Self { sink })
+    }
+
+    /// Is the report written to a terminal?
+    pub fn is_tty(&self) -> bool {
+        matches!(self.sink, Sink::Stdout(_)) && io::stdout().is_tty()
+    }
+
+    /// Flush and complete the report output.
+    pub fn finish(mut self) -> anyhow::Result<()> {
+        self.flush()?;
+        if let Sink::Atomic { tmp_path, path, .. } = &self.sink {
+            fs::rename(tmp_path, path).map_err(|e| output_error(path, &e))?;
+            self.sink = Sink::Stdout(io::stdout());
+        }
+        Ok(())
+    }
+}
+
+impl Write for ReportWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.sink {
+            Sink::Stdout(stdout) => stdout.write(buf),
+            Sink::File(file) | Sink::Atomic { file, .. } => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.sink {
+            Sink::Stdout(stdout) => stdout.flush(),
+            Sink::File(file) | Sink::Atomic { file, .. } => file.flush(),
+        }
+    }
+}
+
+impl Drop for ReportWriter {
+    /// Remove the temporary file of any report which did not complete.
+    fn drop(&mut self) {
+        if let Sink::Atomic { tmp_path, .. } = &self.sink {
+            fs::remove_file(tmp_path).unwrap_or_default();
+        }
+    }
+}
+
+/// The temporary path used whilst writing a report to `path`.
+fn temp_path(path: &Path) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()))
+}
+
+fn output_error(path: &Path, err: &io::Error) -> anyhow::Error {
+    anyhow!("failed to write output file: {} ({})", path.display(), err)
+}
+
+/// Configuration for the reports.
+#[derive(Debug, Copy, Clone)]
+pub struct ReportConfig {
+    /// The number of rounds of tracing to run before reporting.
+    cycles: usize,
+    /// Whether to lookup the hostnames of all hop addresses.
+    dns_lookup: bool,
+    /// The maximum total time to wait for hostname lookups to complete.
+    dns_timeout: Duration,
+    /// Whether to report the progress of every round on stderr.
+    progress: bool,
+    /// Whether to report as soon as the path has converged, should it do so within `cycles` rounds.
+    until_converged: bool,
+    /// Whether to redraw a table report in place after every round, rather than writing it once tracing completes.
+    progressive: bool,
+}
+
+impl ReportConfig {
+    #[allow(clippy::fn_params_excessive_bools)]
+    pub fn new(
+        cycles: usize,
+        dns_lookup: bool,
+        dns_timeout: Duration,
+        progress: bool,
+        until_converged: bool,
+        progressive: bool,
+    ) -> Self {
+        Self {
+            cycles,
+            dns_lookup,
+            dns_timeout,
+            progress,
+            until_converged,
+            progressive,
+        }
+    }
+
+    /// Why the tracing of `trace` stopped to be reported, if it could have stopped for more than one reason.
+    fn stopped_by(&self, trace: &Trace) -> Option<StoppedBy> {
+        self.until_converged.then(|| match trace.converged() {
+            Some(_) => StoppedBy::Converged,
+            None => StoppedBy::Cycles,
+        })
+    }
+}
+
+/// The metadata of a trace which is included in the header of every report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportMetadata {
+    /// The version of trippy which produced the report.
+    version: String,
+    /// The hostname of the local host.
+    local_hostname: String,
+    /// The source address of the probes.
+    source_addr: IpAddr,
+    /// The network interface, only known if one was given with `--interface`.
+    interface: Option<String>,
+    /// The firewall mark of the probes, only known if one was given with `--fwmark`.
+    fwmark: Option<u32>,
+    target_hostname: String,
+    target_addr: IpAddr,
+    /// The zone of a link-local IPv6 target, the interface through which it is reached, if any.
+    target_zone: Option<String>,
+    /// Where the address of the target came from, unless the target was given as an address.
+    target_source: Option<DnsSource>,
+    protocol: String,
+    packet_size: u16,
+    /// The seed of the random number generator of the run, with which it may be repeated with `--rng-seed`.
+    rng_seed: u64,
+}
+
+impl ReportMetadata {
+    pub fn new(info: &TraceInfo) -> Self {
+        Self {
+            version: String::from(env!("CARGO_PKG_VERSION")),
+            local_hostname: dns_lookup::get_hostname()
+                .unwrap_or_else(|_| String::from("localhost")),
+            source_addr: info.source_addr,
+            interface: info.interface.clone(),
+            fwmark: info.fwmark,
+            target_hostname: info.target_hostname.clone(),
+            target_addr: info.target_addr,
+            target_zone: info.target_zone.clone(),
+            target_source: info.target_source,
+            protocol: info.protocol.to_string(),
+            packet_size: info.packet_size,
+            rng_seed: info.rng_seed,
+        }
+    }
+
+    /// The address of the target, with its zone if it has one, such as `fe80::1%eth0`.
+    pub fn target_ip(&self) -> String {
+        zone::format_addr(self.target_addr, self.target_zone.as_deref())
+    }
+
+    /// The metadata as `(name, value)` pairs, as written in the header of the CSV and table reports, along with the
+    /// `flow_key` of the latest round, if recorded.
+    fn fields(&self, flow_key: Option<FlowKey>) -> Vec<(&'static str, String)> {
+        let mut fields = vec![
+            ("version", self.version.clone()),
+            ("local_hostname", self.local_hostname.clone()),
+            ("source", self.source_addr.to_string()),
+        ];
+        if let Some(interface) = &self.interface {
+            fields.push(("interface", interface.clone()));
+        }
+        if let Some(fwmark) = self.fwmark {
+            fields.push(("fwmark", fwmark.to_string()));
+        }
+        fields.extend([(
+            "target",
+            format!("{} ({})", self.target_hostname, self.target_ip()),
+        )]);
+        if let Some(target_source) = self.target_source {
+            fields.push(("target_source", target_source.to_string()));
+        }
+        fields.extend([
+            ("protocol", self.protocol.clone()),
+            ("packet_size", self.packet_size.to_string()),
+            ("rng_seed", self.rng_seed.to_string()),
+        ]);
+        if let Some(flow_key) = flow_key {
+            fields.push(("flow_key", flow_key.to_string()));
+        }
+        fields
+    }
+}
+
+impl Display for ReportMetadata {
+    /// Describe the trace, i.e. `example.com (10.0.0.3) from myhost (10.0.0.1 via eth0) using icmp with 84 byte
+    /// packets, trippy 0.7.0-dev`.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({}) from {} ({}",
+            self.target_hostname,
+            self.target_ip(),
+            self.local_hostname,
+            self.source_addr
+        )?;
+        if let Some(interface) = &self.interface {
+            write!(f, " via {interface}")?;
+        }
+        write!(
+            f,
+            ") using {} with {} byte packets, trippy {}",
+            self.protocol, self.packet_size, self.version
+        )
+    }
+}
+
+/// The maximum width of a hostname in the table reports.
+const MAX_HOSTNAME_WIDTH: usize = 48;
+
+/// The poll interval whilst waiting for hostname lookups to complete.
+const HOSTNAME_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The hostnames of all hop addresses in a `Trace`.
+///
+/// Lookups are only performed once tracing has complete so that they do not perturb the trace timings.
+#[derive(Debug, Default)]
+struct Hostnames(HashMap<IpAddr, String>);
+
+impl Hostnames {
+    /// Lookup the hostnames for all addresses in the `Trace`.
+    ///
+    /// This blocks for at most `dns_timeout` in total, any address which has not resolved by then is reported without a
+    /// hostname.
+    fn lookup(trace: &Trace, resolver: &DnsResolver, config: &ReportConfig) -> Self {
+        let mut hostnames = Self::default();
+        hostnames.resolve_all(
+            trace.hops().iter().flat_map(Hop::addrs).copied(),
+            resolver,
+            config,
+        );
+        hostnames
+    }
+
+    /// Record the hostnames of all `addrs`, blocking for at most `dns_timeout` in total.
+    fn resolve_all(
+        &mut self,
+        addrs: impl Iterator<Item = IpAddr>,
+        resolver: &DnsResolver,
+        config: &ReportConfig,
+    ) {
+        if !config.dns_lookup {
+            return;
+        }
+        let start = SystemTime::now();
+        let mut pending: Vec<_> = addrs.collect();
+        loop {
+            pending.retain(|addr| !self.resolve(*addr, resolver));
+            let elapsed = start.elapsed().unwrap_or_default();
+            if pending.is_empty() || elapsed >= config.dns_timeout {
+                break;
+            }
+            sleep(HOSTNAME_POLL_INTERVAL.min(config.dns_timeout.saturating_sub(elapsed)));
+        }
+    }
+
+    /// The hostnames of the addresses in the `Trace` which have already been resolved.
+    ///
+    /// This never blocks, unlike `lookup`, and so may be used whilst tracing.
+    fn resolved(trace: &Trace, resolver: &DnsResolver) -> Self {
+        let mut hostnames = Self::default();
+        for addr in trace.hops().iter().flat_map(Hop::addrs) {
+            hostnames.resolve(*addr, resolver);
+        }
+        hostnames
+    }
+
+    /// Record the hostname of `addr` if the lookup has completed, returning `false` if it is still pending.
+    ///
+    /// This never blocks, a lookup is started in the background for any address not yet known to the resolver.
+    fn resolve(&mut self, addr: IpAddr, resolver: &DnsResolver) -> bool {
+        if self.0.contains_key(&addr) {
+            return true;
+        }
+        match resolver.reverse_lookup(addr) {
+            DnsEntry::Resolved(Resolved::Normal(_, hosts) | Resolved::WithAsInfo(_, hosts, _)) => {
+                // The system resolver returns the address itself when no hostname exists.
+                if hosts.iter().any(|host| *host != addr.to_string()) {
+                    self.0.insert(addr, hosts.join(" "));
+                }
+                true
+            }
+            DnsEntry::NotFound(_) | DnsEntry::Failed(_) => true,
+            DnsEntry::Pending(_) | DnsEntry::Timeout(_) => false,
+        }
+    }
+
+    /// The hostname of `addr`, if known.
+    fn get(&self, addr: &IpAddr) -> Option<&str> {
+        self.0.get(addr).map(String::as_str)
+    }
+
+    /// Format `addr` as `hostname (ip)` if the hostname is known or as `ip` otherwise.
+    fn format(&self, addr: &IpAddr) -> String {
+        match self.get(addr) {
+            Some(hostname) => format!("{} ({})", truncate(hostname, MAX_HOSTNAME_WIDTH), addr),
+            None => addr.to_string(),
+        }
+    }
+}
+
+/// Truncate `s` to at most `width` characters, marking truncation with an ellipsis.
+fn truncate(s: &str, width: usize) -> String {
+    if s.chars().count() > width {
+        format!(
+            "{}...",
+            s.chars().take(width.saturating_sub(3)).collect::<String>()
+        )
+    } else {
+        s.to_string()
+    }
+}
+
+/// Configuration for the CSV report.
+#[derive(Debug, Copy, Clone)]
+pub struct CsvConfig {
+    /// The field delimiter.
+    delimiter: u8,
+    /// Whether to write a row per address rather than a row per hop.
+    explode_addrs: bool,
+    /// Whether to write a header row.
+    header: bool,
+}
+
+impl CsvConfig {
+    pub fn new(delimiter: u8, explode_addrs: bool, header: bool) -> Self {
+        Self {
+            delimiter,
+            explode_addrs,
+            header,
+        }
+    }
+}
+
+/// The delimiter used to join multiple addresses within a single CSV field.
+const CSV_ADDR_DELIMITER: &str = "|";
+
+/// A single row of the CSV report.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct CsvRow {
+    #[serde(rename = "Target")]
+    target: String,
+    #[serde(rename = "TargetIp")]
+    target_ip: String,
+    #[serde(rename = "Hop")]
+    ttl: u8,
+    #[serde(rename = "Addrs")]
+    addrs: String,
+    #[serde(rename = "Hosts")]
+    hosts: String,
+    #[serde(rename = "Loss%")]
+    loss_pct: String,
+    #[serde(rename = "Snt")]
+    sent: usize,
+    #[serde(rename = "Recv")]
+    recv: usize,
+    #[serde(rename = "Last")]
+    last: String,
+    #[serde(rename = "Avg")]
+    avg: String,
+    #[serde(rename = "Best")]
+    best: String,
+    #[serde(rename = "Wrst")]
+    worst: String,
+    #[serde(rename = "StdDev")]
+    stddev: String,
+    #[serde(rename = "Jitter")]
+    jitter: String,
+}
 
 /// Generate a CSV report of trace data.
+///
+/// If the header row is enabled then it is preceded by a block of `#` comment lines describing the trace.
 pub fn run_report_csv(
     info: &TraceInfo,
-    report_cycles: usize,
+    metadata: &ReportMetadata,
+    config: &ReportConfig,
     resolver: &DnsResolver,
+    csv_config: CsvConfig,
+    out: &mut impl Write,
 ) -> anyhow::Result<()> {
-    let trace = wait_for_round(&info.data, report_cycles)?;
-    println!("Target,TargetIp,Hop,Addrs,Loss%,Snt,Recv,Last,Avg,Best,Wrst,StdDev,");
-    for hop in trace.hops().iter() {
-        let ttl = hop.ttl();
-        let hosts = hop.addrs().map(|ip| resolver.reverse_lookup(*ip)).join(":");
-        let host = if hosts.is_empty() {
-            String::from("???")
+    let trace = wait_for_round(info, config)?;
+    let hostnames = Hostnames::lookup(&trace, resolver, config);
+    write_csv_report(metadata, &trace, &hostnames, csv_config, out)
+}
+
+fn write_csv_report(
+    metadata: &ReportMetadata,
+    trace: &Trace,
+    hostnames: &Hostnames,
+    csv_config: CsvConfig,
+    out: &mut impl Write,
+) -> anyhow::Result<()> {
+    let rows = trace.hops().iter().flat_map(|hop| {
+        let addrs: Vec<_> = hop
+            .addrs()
+            .map(|ip| {
+                (
+                    ip.to_string(),
+                    hostnames.get(ip).unwrap_or_default().to_string(),
+                )
+            })
+            .collect();
+        let addrs = if addrs.is_empty() {
+            vec![(String::from("???"), String::new())]
+        } else if csv_config.explode_addrs {
+            addrs
         } else {
-            hosts
+            let (ips, hosts): (Vec<_>, Vec<_>) = addrs.into_iter().unzip();
+            vec![(ips.join(CSV_ADDR_DELIMITER), hosts.join(CSV_ADDR_DELIMITER))]
         };
-        let sent = hop.total_sent();
-        let recv = hop.total_recv();
-        let last = hop
-            .last_ms()
-            .map_or_else(|| String::from("???"), |last| format!("{last:.1}"));
-        let best = hop
-            .best_ms()
-            .map_or_else(|| String::from("???"), |best| format!("{best:.1}"));
-        let worst = hop
-            .worst_ms()
-            .map_or_else(|| String::from("???"), |worst| format!("{worst:.1}"));
-        let stddev = hop.stddev_ms();
-        let avg = hop.avg_ms();
-        let loss_pct = hop.loss_pct();
-        println!(
-            "{},{},{},{},{:.1}%,{},{},{},{:.1},{},{},{:.1}",
-            info.target_hostname,
-            info.target_addr,
-            ttl,
-            host,
-            loss_pct,
-            sent,
-            recv,
-            last,
-            avg,
-            best,
-            worst,
-            stddev
-        );
+        addrs.into_iter().map(|(addrs, hosts)| CsvRow {
+            target: metadata.target_hostname.clone(),
+            target_ip: metadata.target_ip(),
+            ttl: hop.ttl(),
+            addrs,
+            hosts,
+            loss_pct: format!("{:.1}%", hop.loss_pct()),
+            sent: hop.total_sent(),
+            recv: hop.total_recv(),
+            last: hop
+                .last_ms()
+                .map_or_else(|| String::from("???"), |last| format!("{last:.1}")),
+            avg: format!("{:.1}", hop.avg_ms()),
+            best: hop
+                .best_ms()
+                .map_or_else(|| String::from("???"), |best| format!("{best:.1}")),
+            worst: hop
+                .worst_ms()
+                .map_or_else(|| String::from("???"), |worst| format!("{worst:.1}")),
+            stddev: format!("{:.1}", hop.stddev_ms()),
+            jitter: format!("{:.1}", hop.jitter_ms()),
+        })
+    });
+    if csv_config.header {
+        write_csv_comments(metadata, trace.flow_key(), out)?;
+    }
+    write_csv(csv_config, rows, out)
+}
+
+/// Write the metadata as a block of CSV comment lines, i.e. `# protocol: icmp`.
+fn write_csv_comments(
+    metadata: &ReportMetadata,
+    flow_key: Option<FlowKey>,
+    out: &mut impl Write,
+) -> anyhow::Result<()> {
+    for (name, value) in metadata.fields(flow_key) {
+        writeln!(out, "# {name}: {value}")?;
+    }
+    Ok(())
+}
+
+/// Write the CSV rows, quoting and escaping fields as required.
+fn write_csv(
+    config: CsvConfig,
+    rows: impl IntoIterator<Item = CsvRow>,
+    out: &mut impl Write,
+) -> anyhow::Result<()> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(config.delimiter)
+        .has_headers(config.header)
+        .from_writer(out);
+    for row in rows {
+        writer.serialize(row)?;
     }
+    writer.flush()?;
     Ok(())
 }
 
-#[derive(Serialize)]
+/// The version of the JSON report schema.
+///
+/// This must be incremented whenever the structure of the JSON report changes.
+pub const REPORT_SCHEMA_VERSION: u16 = 1;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Report {
     pub info: ReportInfo,
     pub hops: Vec<ReportHop>,
+    /// The hops of each address of the target, if its addresses were probed round robin.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub targets: Vec<ReportTarget>,
+}
+
+/// The hops of the rounds which probed one of the addresses of a target probed round robin.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReportTarget {
+    pub ip: String,
+    pub rounds: usize,
+    pub hops: Vec<ReportHop>,
 }
 
-#[derive(Serialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct ReportInfo {
+    pub version: String,
+    pub schema: u16,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
     pub target: Host,
+    /// Where the address of the target came from, such as `hosts-file`, unless the target was given as an address.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_source: Option<String>,
+    pub source: String,
+    #[serde(default)]
+    pub local_hostname: String,
+    #[serde(default)]
+    pub interface: Option<String>,
+    #[serde(default)]
+    pub fwmark: Option<u32>,
+    pub protocol: String,
+    pub packet_size: u16,
+    #[serde(default)]
+    pub rng_seed: Option<u64>,
+    pub rounds: usize,
+    /// The number of responses discarded as the wall clock was adjusted whilst they were in flight.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub clock_anomalies: usize,
+    /// The number of probes discarded as their ttl was not that of any hop.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub invalid_ttls: usize,
+    /// The counts of the packets received by the channel, by what became of them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel_metrics: Option<ChannelMetrics>,
+    /// The stability of the path to the target, and the changes of path observed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path_stability: Option<ReportPathStability>,
+    /// The round at which the path converged, if it has.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub converged_round: Option<usize>,
+    /// Why tracing stopped, if the report was to stop once the path converged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stopped_by: Option<StoppedBy>,
+    /// The flow key of the first probe of the latest round, the ports, checksum and identifiers it was sent with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flow_key: Option<FlowKey>,
+}
+
+/// Why the tracing of a report stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StoppedBy {
+    /// The path converged before `--report-cycles` rounds were traced.
+    Converged,
+    /// The `--report-cycles` rounds were traced before the path converged.
+    Cycles,
+}
+
+/// The stability of the path to the target over the latest rounds, and the changes of path observed.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReportPathStability {
+    /// The stability of the path, from 0 to 100.
+    #[serde(serialize_with = "fixed_width", deserialize_with = "parse_fixed_width")]
+    pub score: f64,
+    pub distinct_paths: usize,
+    pub changes: usize,
+    /// When the path last changed, if it has.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_change: Option<DateTime<Utc>>,
 }
 
-#[derive(Serialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct ReportHop {
     ttl: u8,
-    hosts: Vec<Host>,
-    #[serde(serialize_with = "fixed_width")]
+    addrs: Vec<ReportHopAddr>,
+    #[serde(serialize_with = "fixed_width", deserialize_with = "parse_fixed_width")]
     loss_pct: f64,
     sent: usize,
-    #[serde(serialize_with = "fixed_width")]
+    #[serde(serialize_with = "fixed_width", deserialize_with = "parse_fixed_width")]
     last: f64,
     recv: usize,
-    #[serde(serialize_with = "fixed_width")]
+    #[serde(serialize_with = "fixed_width", deserialize_with = "parse_fixed_width")]
     avg: f64,
-    #[serde(serialize_with = "fixed_width")]
+    #[serde(serialize_with = "fixed_width", deserialize_with = "parse_fixed_width")]
     best: f64,
-    #[serde(serialize_with = "fixed_width")]
+    #[serde(serialize_with = "fixed_width", deserialize_with = "parse_fixed_width")]
     worst: f64,
-    #[serde(serialize_with = "fixed_width")]
+    #[serde(serialize_with = "fixed_width", deserialize_with = "parse_fixed_width")]
     stddev: f64,
+    #[serde(serialize_with = "fixed_width", deserialize_with = "parse_fixed_width")]
+    jitter: f64,
+    /// Whether the hop is likely rate limiting ICMP, such that its loss is likely not loss of the path.
+    #[serde(default)]
+    rate_limited_likely: bool,
+    /// The hops by which the return path from the hop is longer than the forward path, if it could be inferred.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    asymmetry: Option<i16>,
+    /// The ports probed and the ports from which the hop, being the target, replied at the transport layer.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    responder_ports: Vec<ReportResponderPorts>,
+    /// Whether the hop replied from a port other than the one probed, as if something answered on its behalf.
+    #[serde(default, skip_serializing_if = "is_false")]
+    port_mismatch: bool,
+    /// The time at which the last response of the hop was received, if any was.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_recv_time: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    samples: Option<Vec<ReportSample>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    segment: Option<ReportSegment>,
+}
+
+/// The estimated latency of the segment from the previous hop which responded, `negative` if the hop was faster than
+/// that hop, in which case `latency_ms` is zero.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReportSegment {
+    #[serde(serialize_with = "fixed_width", deserialize_with = "parse_fixed_width")]
+    latency_ms: f64,
+    negative: bool,
+}
+
+/// A single retained sample of a hop, `seq_offset` counts back from the most recent probe and `rtt_ms` is `null` for
+/// lost probes.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReportSample {
+    seq_offset: usize,
+    #[serde(
+        serialize_with = "fixed_width_opt",
+        deserialize_with = "parse_fixed_width_opt"
+    )]
+    rtt_ms: Option<f64>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReportHopAddr {
+    ip: String,
+    hostname: Option<String>,
+    count: usize,
+    #[serde(serialize_with = "fixed_width", deserialize_with = "parse_fixed_width")]
+    last: f64,
+    #[serde(serialize_with = "fixed_width", deserialize_with = "parse_fixed_width")]
+    best: f64,
+    #[serde(serialize_with = "fixed_width", deserialize_with = "parse_fixed_width")]
+    worst: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    quote: Option<ReportQuote>,
+}
+
+/// The size, in bytes, of the payload of the last ICMP error from an address and of the probe quoted in it, `full` if
+/// the probe was quoted in full.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportQuote {
+    payload_len: u16,
+    quoted_len: u16,
+    full: bool,
+}
+
+/// The number of replies from the `responder` port to probes of the `probed` port.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportResponderPorts {
+    probed: u16,
+    responder: u16,
+    count: usize,
 }
 
-#[derive(Serialize)]
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Host {
     pub ip: String,
     pub hostname: String,
 }
 
+#[allow(clippy::trivially_copy_pass_by_ref)]
+fn is_zero(val: &usize) -> bool {
+    *val == 0
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)]
+fn is_false(val: &bool) -> bool {
+    !*val
+}
+
 #[allow(clippy::trivially_copy_pass_by_ref)]
 fn fixed_width<S>(val: &f64, serializer: S) -> Result<S::Ok, S::Error>
 where
@@ -103,27 +777,209 @@ where
     serializer.serialize_str(&format!("{val:.2}"))
 }
 
-/// Generate a CSV report of trace data.
+fn parse_fixed_width<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    String::deserialize(deserializer)?
+        .parse()
+        .map_err(serde::de::Error::custom)
+}
+
+#[allow(clippy::ref_option)]
+fn fixed_width_opt<S>(val: &Option<f64>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match val {
+        Some(val) => fixed_width(val, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn parse_fixed_width_opt<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|val| val.parse().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+/// The retained samples of a hop, newest first.
+///
+/// Lost probes are recorded by the backend as a zero duration sample and are reported as `None`.
+fn report_samples(hop: &Hop) -> Vec<ReportSample> {
+    hop.samples()
+        .iter()
+        .enumerate()
+        .map(|(seq_offset, sample)| ReportSample {
+            seq_offset,
+            rtt_ms: (*sample != Duration::ZERO).then_some(sample.as_secs_f64() * 1000_f64),
+        })
+        .collect()
+}
+
+/// Generate a JSON report of trace data.
+///
+/// If `include_samples` is set then the retained samples of every hop (up to `--tui-max-samples` per hop), and the
+/// size of the last ICMP error from every address and of the probe quoted in it, are included, which may make the
+/// report very large, and so the report is streamed to `out` as it is serialized.  If `include_segments` is set then
+/// the estimated latency of the segment ending at every hop which responded is included.
 pub fn run_report_json(
     info: &TraceInfo,
-    report_cycles: usize,
+    metadata: &ReportMetadata,
+    config: &ReportConfig,
     resolver: &DnsResolver,
+    include_samples: bool,
+    include_segments: bool,
+    out: &mut impl Write,
 ) -> anyhow::Result<()> {
-    let trace = wait_for_round(&info.data, report_cycles)?;
-    let hops: Vec<ReportHop> = trace
+    let report = trace_report(
+        info,
+        metadata,
+        config,
+        resolver,
+        include_samples,
+        include_segments,
+    )?;
+    write_json_report(&report, out)
+}
+
+fn write_json_report(report: &Report, out: &mut impl Write) -> anyhow::Result<()> {
+    serde_json::to_writer_pretty(&mut *out, report)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+/// Trace for `cycles` rounds and build the `Report` shared by the JSON and binary reports.
+fn trace_report(
+    info: &TraceInfo,
+    metadata: &ReportMetadata,
+    config: &ReportConfig,
+    resolver: &DnsResolver,
+    include_samples: bool,
+    include_segments: bool,
+) -> anyhow::Result<Report> {
+    let start = Utc::now();
+    let trace = wait_for_round(info, config)?;
+    let end = Utc::now();
+    let hostnames = Hostnames::lookup(&trace, resolver, config);
+    let mut report = make_report(
+        metadata,
+        &trace,
+        &hostnames,
+        start,
+        end,
+        include_samples,
+        include_segments,
+    );
+    report.info.stopped_by = config.stopped_by(&trace);
+    Ok(report)
+}
+
+/// The stability of the path of `trace`, if any round has been traced.
+fn report_path_stability(trace: &Trace) -> Option<ReportPathStability> {
+    let stability = trace.path_stability();
+    stability.score().map(|score| ReportPathStability {
+        score,
+        distinct_paths: stability.distinct_paths(),
+        changes: stability.changes(),
+        last_change: stability.changed_at().map(DateTime::from),
+    })
+}
+
+/// Build the `Report` of a `Trace` which ran from `start` to `end`.
+fn make_report(
+    metadata: &ReportMetadata,
+    trace: &Trace,
+    hostnames: &Hostnames,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    include_samples: bool,
+    include_segments: bool,
+) -> Report {
+    Report {
+        info: ReportInfo {
+            version: metadata.version.clone(),
+            schema: REPORT_SCHEMA_VERSION,
+            start,
+            end,
+            target: Host {
+                ip: metadata.target_ip(),
+                hostname: metadata.target_hostname.clone(),
+            },
+            target_source: metadata.target_source.map(|source| source.to_string()),
+            source: metadata.source_addr.to_string(),
+            local_hostname: metadata.local_hostname.clone(),
+            interface: metadata.interface.clone(),
+            fwmark: metadata.fwmark,
+            protocol: metadata.protocol.clone(),
+            packet_size: metadata.packet_size,
+            rng_seed: Some(metadata.rng_seed),
+            rounds: trace.round().map_or(0, |round| round + 1),
+            clock_anomalies: trace.clock_anomalies(),
+            invalid_ttls: trace.invalid_ttls(),
+            channel_metrics: trace.channel_metrics(),
+            path_stability: report_path_stability(trace),
+            converged_round: trace.converged(),
+            stopped_by: None,
+            flow_key: trace.flow_key(),
+        },
+        hops: report_hops(trace, hostnames, include_samples, include_segments),
+        targets: trace
+            .endpoints()
+            .iter()
+            .map(|endpoint| ReportTarget {
+                ip: endpoint.addr().to_string(),
+                rounds: endpoint.rounds(),
+                hops: report_hops(
+                    endpoint.trace(),
+                    hostnames,
+                    include_samples,
+                    include_segments,
+                ),
+            })
+            .collect(),
+    }
+}
+
+/// The `ReportHop` of every hop in the `Trace`.
+fn report_hops(
+    trace: &Trace,
+    hostnames: &Hostnames,
+    include_samples: bool,
+    include_segments: bool,
+) -> Vec<ReportHop> {
+    let segments = segments(trace.hops());
+    trace
         .hops()
         .iter()
-        .map(|hop| {
-            let hosts: Vec<_> = hop
-                .addrs()
-                .map(|ip| Host {
+        .zip(segments)
+        .map(|(hop, segment)| {
+            let addrs: Vec<_> = hop
+                .addrs_with_details()
+                .sorted_by_key(|(ip, addr)| (Reverse(addr.count()), **ip))
+                .map(|(ip, addr)| ReportHopAddr {
                     ip: ip.to_string(),
-                    hostname: resolver.reverse_lookup(*ip).to_string(),
+                    hostname: hostnames.get(ip).map(String::from),
+                    count: addr.count(),
+                    last: addr.last_ms().unwrap_or_default(),
+                    best: addr.best_ms().unwrap_or_default(),
+                    worst: addr.worst_ms().unwrap_or_default(),
+                    quote: addr
+                        .quote()
+                        .filter(|_| include_samples)
+                        .map(|quote| ReportQuote {
+                            payload_len: quote.payload_len,
+                            quoted_len: quote.quoted_len,
+                            full: quote.full,
+                        }),
                 })
                 .collect();
             ReportHop {
                 ttl: hop.ttl(),
-                hosts,
+                addrs,
                 loss_pct: hop.loss_pct(),
                 sent: hop.total_sent(),
                 last: hop.last_ms().unwrap_or_default(),
@@ -132,134 +988,1877 @@ pub fn run_report_json(
                 best: hop.best_ms().unwrap_or_default(),
                 worst: hop.worst_ms().unwrap_or_default(),
                 stddev: hop.stddev_ms(),
+                jitter: hop.jitter_ms(),
+                rate_limited_likely: hop.rate_limited_likely(),
+                asymmetry: hop.asymmetry(),
+                responder_ports: hop
+                    .responder_ports()
+                    .into_iter()
+                    .map(|(ports, count)| ReportResponderPorts {
+                        probed: ports.probed,
+                        responder: ports.responder,
+                        count,
+                    })
+                    .collect(),
+                port_mismatch: hop.port_mismatch(),
+                last_recv_time: hop.last_recv_time().map(DateTime::from),
+                samples: include_samples.then(|| report_samples(hop)),
+                segment: segment
+                    .filter(|_| include_segments)
+                    .map(|segment| ReportSegment {
+                        latency_ms: segment.latency_ms(),
+                        negative: segment.is_negative(),
+                    }),
             }
         })
-        .collect();
-
-    let report = Report {
-        info: ReportInfo {
-            target: Host {
-                ip: info.target_addr.to_string(),
-                hostname: info.target_hostname.to_string(),
-            },
-        },
-        hops,
-    };
-    println!("{}", serde_json::to_string_pretty(&report).unwrap());
-    Ok(())
+        .collect()
 }
 
 /// Generate a markdown table report of trace data.
 pub fn run_report_table_md(
     info: &TraceInfo,
-    report_cycles: usize,
+    metadata: &ReportMetadata,
+    config: &ReportConfig,
     resolver: &DnsResolver,
+    columns: &Columns,
+    out: &mut impl Write,
 ) -> anyhow::Result<()> {
-    run_report_table(info, report_cycles, resolver, ASCII_MARKDOWN)
+    run_report_table(
+        info,
+        metadata,
+        config,
+        resolver,
+        columns,
+        TableStyle::Markdown,
+        out,
+    )
 }
 
 /// Generate a pretty table report of trace data.
 pub fn run_report_table_pretty(
     info: &TraceInfo,
-    report_cycles: usize,
+    metadata: &ReportMetadata,
+    config: &ReportConfig,
     resolver: &DnsResolver,
+    columns: &Columns,
+    out: &mut impl Write,
 ) -> anyhow::Result<()> {
-    run_report_table(info, report_cycles, resolver, UTF8_FULL)
+    run_report_table(
+        info,
+        metadata,
+        config,
+        resolver,
+        columns,
+        TableStyle::Pretty,
+        out,
+    )
+}
+
+/// The style of a table report.
+#[derive(Debug, Copy, Clone)]
+enum TableStyle {
+    Pretty,
+    Markdown,
 }
 
 fn run_report_table(
     info: &TraceInfo,
-    report_cycles: usize,
+    metadata: &ReportMetadata,
+    config: &ReportConfig,
     resolver: &DnsResolver,
-    preset: &str,
+    columns: &Columns,
+    style: TableStyle,
+    out: &mut impl Write,
+) -> anyhow::Result<()> {
+    if !config.progressive {
+        let trace = wait_for_round(info, config)?;
+        let hostnames = Hostnames::lookup(&trace, resolver, config);
+        return write_table_report(metadata, &trace, &hostnames, columns, style, out);
+    }
+    let mut redraw = Redraw::default();
+    let trace = wait_for_round_with(info, config, |trace| {
+        let hostnames = Hostnames::resolved(trace, resolver);
+        redraw.draw(
+            &render_table_report(metadata, trace, &hostnames, columns, style)?,
+            out,
+        )
+    })?;
+    let hostnames = Hostnames::lookup(&trace, resolver, config);
+    redraw.draw(
+        &render_table_report(metadata, &trace, &hostnames, columns, style)?,
+        out,
+    )
+}
+
+/// Render the table report of a `Trace`, exactly as written by `write_table_report`.
+fn render_table_report(
+    metadata: &ReportMetadata,
+    trace: &Trace,
+    hostnames: &Hostnames,
+    columns: &Columns,
+    style: TableStyle,
+) -> anyhow::Result<String> {
+    let mut buf = vec![];
+    write_table_report(metadata, trace, hostnames, columns, style, &mut buf)?;
+    Ok(String::from_utf8(buf)?)
+}
+
+/// Redraws a report in place on a terminal.
+///
+/// The lines previously drawn are kept such that only those which have changed are redrawn, the remainder of any line
+/// narrower than before being cleared, as are any lines left over from a taller report.
+#[derive(Debug, Default)]
+struct Redraw {
+    lines: Vec<String>,
+}
+
+impl Redraw {
+    /// Draw `report` over the report previously drawn, if any.
+    fn draw(&mut self, report: &str, out: &mut impl Write) -> anyhow::Result<()> {
+        let lines: Vec<_> = report.lines().map(String::from).collect();
+        if !self.lines.is_empty() {
+            write!(out, "\r\x1b[{}A", self.lines.len())?;
+        }
+        for (i, line) in lines.iter().enumerate() {
+            if self.lines.get(i) == Some(line) {
+                writeln!(out)?;
+            } else {
+                writeln!(out, "{line}\x1b[K")?;
+            }
+        }
+        if lines.len() < self.lines.len() {
+            write!(out, "\x1b[J")?;
+        }
+        out.flush()?;
+        self.lines = lines;
+        Ok(())
+    }
+}
+
+fn write_table_report(
+    metadata: &ReportMetadata,
+    trace: &Trace,
+    hostnames: &Hostnames,
+    columns: &Columns,
+    style: TableStyle,
+    out: &mut impl Write,
+) -> anyhow::Result<()> {
+    let table = make_table(trace, hostnames, columns, style);
+    write_table_header(metadata, trace.flow_key(), style, out)?;
+    writeln!(out, "{table}")?;
+    Ok(())
+}
+
+/// Write the metadata rows which precede a table report, as a list for markdown.
+fn write_table_header(
+    metadata: &ReportMetadata,
+    flow_key: Option<FlowKey>,
+    style: TableStyle,
+    out: &mut impl Write,
 ) -> anyhow::Result<()> {
-    let trace = wait_for_round(&info.data, report_cycles)?;
-    let columns = vec![
-        "Hop", "Addrs", "Loss%", "Snt", "Recv", "Last", "Avg", "Best", "Wrst", "StdDev",
-    ];
+    for (name, value) in metadata.fields(flow_key) {
+        match style {
+            TableStyle::Pretty => writeln!(out, "{name}: {value}")?,
+            TableStyle::Markdown => writeln!(out, "- {name}: {value}")?,
+        }
+    }
+    writeln!(out)?;
+    Ok(())
+}
+
+/// Build the table report of a `Trace`.
+///
+/// The target hop is shown in bold, for markdown only the hop number is emboldened as cells may span several lines.
+fn make_table(trace: &Trace, hostnames: &Hostnames, columns: &Columns, style: TableStyle) -> Table {
     let mut table = Table::new();
+    let preset = match style {
+        TableStyle::Pretty => UTF8_FULL,
+        TableStyle::Markdown => ASCII_MARKDOWN,
+    };
     table
         .load_preset(preset)
         .set_content_arrangement(ContentArrangement::Dynamic)
-        .set_header(columns);
-    for hop in trace.hops().iter() {
-        let ttl = hop.ttl().to_string();
-        let hosts = hop
-            .addrs()
-            .map(|ip| resolver.reverse_lookup(*ip).to_string())
-            .join("\n");
-        let host = if hosts.is_empty() {
-            String::from("???")
-        } else {
-            hosts
-        };
-        let sent = hop.total_sent().to_string();
-        let recv = hop.total_recv().to_string();
-        let last = hop
-            .last_ms()
-            .map_or_else(|| String::from("???"), |last| format!("{last:.1}"));
-        let best = hop
-            .best_ms()
-            .map_or_else(|| String::from("???"), |best| format!("{best:.1}"));
-        let worst = hop
-            .worst_ms()
-            .map_or_else(|| String::from("???"), |worst| format!("{worst:.1}"));
-        let stddev = format!("{:.1}", hop.stddev_ms());
-        let avg = format!("{:.1}", hop.avg_ms());
-        let loss_pct = format!("{:.1}", hop.loss_pct());
-        table.add_row(vec![
-            &ttl, &host, &loss_pct, &sent, &recv, &last, &avg, &best, &worst, &stddev,
-        ]);
+        .set_header(columns.0.iter().map(|column| column_header(*column)));
+    for hop in trace.hops() {
+        let is_target = trace.is_target(hop);
+        let cells = columns.0.iter().map(|column| {
+            let content = column_cell(*column, hop, hostnames);
+            match (style, is_target) {
+                (TableStyle::Pretty, true) => Cell::new(content).add_attribute(Attribute::Bold),
+                (TableStyle::Markdown, true) if *column == Column::Ttl => {
+                    Cell::new(format!("**{content}**"))
+                }
+                _ => Cell::new(content),
+            }
+        });
+        table.add_row(cells.collect::<Vec<_>>());
     }
-    println!("{table}");
-    Ok(())
+    table
 }
 
-/// Display a continuous stream of trace data.
-pub fn run_report_stream(info: &TraceInfo) -> anyhow::Result<()> {
-    println!("Tracing to {} ({})", info.target_hostname, info.target_addr);
-    loop {
-        let trace_data = &info.data.read().clone();
-        if let Some(err) = trace_data.error() {
-            return Err(anyhow!("error: {}", err));
-        }
-        for hop in trace_data.hops() {
-            let ttl = hop.ttl();
-            let addrs = hop.addrs().collect::<Vec<_>>();
-            let sent = hop.total_sent();
-            let recv = hop.total_recv();
-            let last = hop
-                .last_ms()
-                .map(|last| format!("{last:.1}"))
-                .unwrap_or_default();
-            let best = hop
-                .best_ms()
-                .map(|best| format!("{best:.1}"))
-                .unwrap_or_default();
-            let worst = hop
-                .worst_ms()
-                .map(|worst| format!("{worst:.1}"))
-                .unwrap_or_default();
-            let stddev = hop.stddev_ms();
-            let avg = hop.avg_ms();
-            let loss_pct = hop.loss_pct();
-            println!(
-                "ttl={ttl} addrs={addrs:?} loss_pct={loss_pct:.1}, sent={sent} recv={recv} last={last} best={best} worst={worst} avg={avg:.1} stddev={stddev:.1}"
-            );
-        }
-        sleep(info.min_round_duration);
+/// The header of a table report column.
+fn column_header(column: Column) -> &'static str {
+    match column {
+        Column::Ttl => "Hop",
+        Column::Host => "Addrs",
+        Column::LossPct => "Loss%",
+        Column::Sent => "Snt",
+        Column::Received => "Recv",
+        Column::Last => "Last",
+        Column::Average => "Avg",
+        Column::Best => "Best",
+        Column::Worst => "Wrst",
+        Column::StdDev => "StdDev",
+        Column::Jitter => "Jitter",
     }
 }
 
-/// Block until trace data for round `round` is available.
-fn wait_for_round(trace_data: &Arc<RwLock<Trace>>, report_cycles: usize) -> anyhow::Result<Trace> {
-    let mut trace = trace_data.read().clone();
-    while trace.round().is_none() || trace.round() < Some(report_cycles - 1) {
-        trace = trace_data.read().clone();
-        if let Some(err) = trace.error() {
-            return Err(anyhow!("error: {}", err));
+/// The content of a table report cell, `-` is shown for any latency of a hop which has not responded.
+fn column_cell(column: Column, hop: &Hop, hostnames: &Hostnames) -> String {
+    let format_ms = |ms: Option<f64>| ms.map_or_else(|| String::from("-"), |ms| format!("{ms:.1}"));
+    let responded = hop.total_recv() > 0;
+    match column {
+        Column::Ttl => hop.ttl().to_string(),
+        Column::Host => {
+            let hosts = hop
+                .addrs_with_counts()
+                .sorted_by_key(|(ip, count)| (Reverse(**count), **ip))
+                .map(|(ip, _)| hostnames.format(ip))
+                .join("\n");
+            if hosts.is_empty() {
+                String::from("???")
+            } else {
+                hosts
+            }
+        }
+        Column::LossPct => format!("{:.1}", hop.loss_pct()),
+        Column::Sent => hop.total_sent().to_string(),
+        Column::Received => hop.total_recv().to_string(),
+        Column::Last => format_ms(hop.last_ms()),
+        Column::Average => format_ms(responded.then(|| hop.avg_ms())),
+        Column::Best => format_ms(hop.best_ms()),
+        Column::Worst => format_ms(hop.worst_ms()),
+        Column::StdDev => format_ms(responded.then(|| hop.stddev_ms())),
+        Column::Jitter => format_ms(responded.then(|| hop.jitter_ms())),
+    }
+}
+
+/// The formats in which a trace may be exported from the TUI.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ExportFormat {
+    Pretty,
+    Markdown,
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    /// The formats, in the order shown.
+    pub const ALL: [Self; 4] = [Self::Pretty, Self::Markdown, Self::Csv, Self::Json];
+
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Pretty => "Pretty table",
+            Self::Markdown => "Markdown table",
+            Self::Csv => "CSV",
+            Self::Json => "JSON",
+        }
+    }
+
+    /// The extension of the file exported in this format.
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Pretty => "txt",
+            Self::Markdown => "md",
+            Self::Csv => "csv",
+            Self::Json => "json",
+        }
+    }
+
+    /// The report mode which writes this format.
+    pub const fn mode(self) -> Mode {
+        match self {
+            Self::Pretty => Mode::Pretty,
+            Self::Markdown => Mode::Markdown,
+            Self::Csv => Mode::Csv,
+            Self::Json => Mode::Json,
+        }
+    }
+}
+
+/// Export a `Trace` in `format`, as the report of that mode would have written it.
+///
+/// Only hostnames which have already been resolved are included such that exporting never blocks.  The trace is taken
+/// to have run for `trace.elapsed()` until now.
+pub fn run_export(
+    metadata: &ReportMetadata,
+    trace: &Trace,
+    resolver: &DnsResolver,
+    columns: &Columns,
+    format: ExportFormat,
+    out: &mut impl Write,
+) -> anyhow::Result<()> {
+    let hostnames = Hostnames::resolved(trace, resolver);
+    match format {
+        ExportFormat::Pretty => write_table_report(
+            metadata,
+            trace,
+            &hostnames,
+            columns,
+            TableStyle::Pretty,
+            out,
+        ),
+        ExportFormat::Markdown => write_table_report(
+            metadata,
+            trace,
+            &hostnames,
+            columns,
+            TableStyle::Markdown,
+            out,
+        ),
+        ExportFormat::Csv => write_csv_report(
+            metadata,
+            trace,
+            &hostnames,
+            CsvConfig::new(b',', false, true),
+            out,
+        ),
+        ExportFormat::Json => {
+            let end = Utc::now();
+            let start = end
+                - chrono::Duration::from_std(trace.elapsed())
+                    .unwrap_or_else(|_| chrono::Duration::zero());
+            let report = make_report(metadata, trace, &hostnames, start, end, false, false);
+            write_json_report(&report, out)
+        }
+    }
+}
+
+/// Configuration for the stream report.
+#[derive(Debug, Clone)]
+pub struct StreamConfig {
+    /// The `strftime` format of timestamps, or RFC 3339 if not set.
+    time_format: Option<String>,
+    /// Whether to prefix every hop line with a timestamp.
+    hop_timestamps: bool,
+}
+
+impl StreamConfig {
+    pub fn new(time_format: Option<String>, hop_timestamps: bool) -> Self {
+        Self {
+            time_format,
+            hop_timestamps,
+        }
+    }
+
+    /// Format a timestamp.
+    fn format_time(&self, time: DateTime<Utc>) -> String {
+        match &self.time_format {
+            Some(format) => time.format(format).to_string(),
+            None => time.to_rfc3339_opts(SecondsFormat::Millis, true),
+        }
+    }
+}
+
+/// Display a continuous stream of trace data.
+///
+/// Every round is prefixed with a timestamped header and the output is flushed after every round.  Hostnames are
+/// shown for any hop addresses which have been resolved by the time the round is written, lookups never delay the
+/// output.  A summary is written when interrupted.
+pub fn run_report_stream(
+    info: &TraceInfo,
+    metadata: &ReportMetadata,
+    report_config: &ReportConfig,
+    config: &StreamConfig,
+    resolver: &DnsResolver,
+    interrupt: &Interrupt,
+    out: &mut impl Write,
+) -> anyhow::Result<()> {
+    writeln!(out, "Tracing to {metadata}")?;
+    let start = SystemTime::now();
+    let mut hostnames = Hostnames::default();
+    let mut trace_data = info.data.snapshot();
+    let mut alerts_seen = 0;
+    let mut path_changes_seen = trace_data.path_stability().changes();
+    let mut converged_seen = trace_data.converged().is_some();
+    while !interrupt.is_set() {
+        trace_data = info.data.snapshot();
+        if let Some(err) = trace_data.error() {
+            return Err(anyhow!("error: {}", err));
+        }
+        if report_config.dns_lookup {
+            for addr in trace_data.hops().iter().flat_map(Hop::addrs) {
+                hostnames.resolve(*addr, resolver);
+            }
+        }
+        if trace_data.round().is_some() {
+            write_stream_round(&trace_data, &hostnames, config, Utc::now(), out)?;
+            if trace_data.path_stability().changes() > path_changes_seen {
+                write_stream_path_change(&trace_data, &hostnames, config, Utc::now(), out)?;
+                path_changes_seen = trace_data.path_stability().changes();
+            }
+            if let Some(round) = trace_data.converged().filter(|_| !converged_seen) {
+                write_stream_converged(round, config, Utc::now(), out)?;
+                converged_seen = true;
+            }
+            out.flush()?;
+        }
+        let health = info.health.read();
+        let alerts = health.transitions_since(alerts_seen);
+        for alert in alerts {
+            write_stream_alert(alert, config, Utc::now(), out)?;
+        }
+        out.flush()?;
+        alerts_seen += alerts.len();
+        drop(health);
+        sleep(info.min_round_duration);
+    }
+    let elapsed = start.elapsed().unwrap_or_default();
+    write_stream_summary(metadata, &trace_data, elapsed, out)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Write the header and hop lines for the current round of a stream.
+fn write_stream_round(
+    trace_data: &Trace,
+    hostnames: &Hostnames,
+    config: &StreamConfig,
+    now: DateTime<Utc>,
+    out: &mut impl Write,
+) -> anyhow::Result<()> {
+    let timestamp = config.format_time(now);
+    let round = trace_data
+        .round()
+        .map(|round| round.to_string())
+        .unwrap_or_default();
+    write!(out, "{timestamp} round={round}")?;
+    if let Some(started) = trace_data.round_started() {
+        write!(
+            out,
+            " started={}",
+            config.format_time(DateTime::from(started))
+        )?;
+    }
+    if let Some(window) = trace_data.inflight_window() {
+        write!(out, " inflight_window={window}")?;
+    }
+    if let Some(flow_key) = trace_data.flow_key() {
+        write!(out, " {flow_key}")?;
+    }
+    writeln!(out)?;
+    for hop in trace_data.hops() {
+        let ttl = hop.ttl();
+        let addrs = hop.addrs().map(|addr| hostnames.format(addr)).join(", ");
+        let sent = hop.total_sent();
+        let recv = hop.total_recv();
+        let last = hop
+            .last_ms()
+            .map(|last| format!("{last:.1}"))
+            .unwrap_or_default();
+        let best = hop
+            .best_ms()
+            .map(|best| format!("{best:.1}"))
+            .unwrap_or_default();
+        let worst = hop
+            .worst_ms()
+            .map(|worst| format!("{worst:.1}"))
+            .unwrap_or_default();
+        let stddev = hop.stddev_ms();
+        let avg = hop.avg_ms();
+        let loss_pct = hop.loss_pct();
+        if config.hop_timestamps {
+            write!(out, "{timestamp} ")?;
+        }
+        writeln!(
+            out,
+            "ttl={ttl} addrs=[{addrs}] loss_pct={loss_pct:.1}, sent={sent} recv={recv} last={last} best={best} worst={worst} avg={avg:.1} stddev={stddev:.1}"
+        )?;
+    }
+    Ok(())
+}
+
+/// Write the line of the convergence of the path to the target at `round`.
+fn write_stream_converged(
+    round: usize,
+    config: &StreamConfig,
+    now: DateTime<Utc>,
+    out: &mut impl Write,
+) -> anyhow::Result<()> {
+    let timestamp = config.format_time(now);
+    writeln!(out, "{timestamp} converged round={round}")?;
+    Ok(())
+}
+
+/// Write the line of the latest change of the path to the target, listing the hops which differ.
+fn write_stream_path_change(
+    trace_data: &Trace,
+    hostnames: &Hostnames,
+    config: &StreamConfig,
+    now: DateTime<Utc>,
+    out: &mut impl Write,
+) -> anyhow::Result<()> {
+    let stability = trace_data.path_stability();
+    let Some(change) = stability.last_change() else {
+        return Ok(());
+    };
+    let timestamp = config.format_time(now);
+    let format_host = |host: Option<IpAddr>| {
+        host.map_or_else(|| String::from("*"), |host| hostnames.format(&host))
+    };
+    let hops = change
+        .hops()
+        .iter()
+        .map(|hop| {
+            format!(
+                "ttl={} {}->{}",
+                hop.ttl,
+                format_host(hop.from),
+                format_host(hop.to)
+            )
+        })
+        .join(", ");
+    let score = stability.score().unwrap_or_default();
+    writeln!(
+        out,
+        "{timestamp} path_change round={} distinct_paths={} stability={score:.1} hops=[{hops}]",
+        change.round(),
+        stability.distinct_paths()
+    )?;
+    Ok(())
+}
+
+/// Write the line of a transition of the path to the target between degraded and recovered.
+fn write_stream_alert(
+    alert: &Alert,
+    config: &StreamConfig,
+    now: DateTime<Utc>,
+    out: &mut impl Write,
+) -> anyhow::Result<()> {
+    let timestamp = config.format_time(now);
+    match alert {
+        Alert::Degraded {
+            target,
+            breach,
+            since,
+        } => {
+            let since = config.format_time(DateTime::from(*since));
+            let breach = match breach {
+                Breach::Loss {
+                    loss_pct,
+                    threshold,
+                } => format!("target_loss_pct={loss_pct:.1} alert_target_loss={threshold:.1}"),
+                Breach::Latency { avg, threshold } => format!(
+                    "target_avg={:.1} alert_target_latency={:.1}",
+                    avg.as_secs_f64() * 1000_f64,
+                    threshold.as_secs_f64() * 1000_f64
+                ),
+            };
+            writeln!(
+                out,
+                "{timestamp} alert=degraded target={target} since={since} {breach}"
+            )?;
+        }
+        Alert::Recovered { target, since } => {
+            let since = config.format_time(DateTime::from(*since));
+            writeln!(
+                out,
+                "{timestamp} alert=recovered target={target} since={since}"
+            )?;
+        }
+        Alert::Unreachable { .. } | Alert::Reachable { .. } | Alert::HopChanged { .. } => {}
+    }
+    Ok(())
+}
+
+/// Write the summary of a stream.
+fn write_stream_summary(
+    metadata: &ReportMetadata,
+    trace_data: &Trace,
+    elapsed: Duration,
+    out: &mut impl Write,
+) -> anyhow::Result<()> {
+    let rounds = trace_data.round().map_or(0, |round| round + 1);
+    let elapsed = elapsed.as_secs_f64();
+    let loss_pct = trace_data.target_hop().loss_pct();
+    writeln!(
+        out,
+        "--- {} ({}) trace summary ---",
+        metadata.target_hostname,
+        metadata.target_ip()
+    )?;
+    writeln!(
+        out,
+        "rounds={rounds} elapsed={elapsed:.1}s target_loss_pct={loss_pct:.1}"
+    )?;
+    Ok(())
+}
+
+/// The hops of the latest round of `trace` along with the address which responded for each, if any.
+///
+/// The `Trace` only records the aggregate state of each hop and so the response for the latest round is found by
+/// comparing against the `previous` state, a hop responded in the round if the count of one of its addresses increased.
+fn round_responses<'a>(
+    previous: Option<&Trace>,
+    trace: &'a Trace,
+) -> Vec<(&'a Hop, Option<IpAddr>)> {
+    trace
+        .hops()
+        .iter()
+        .filter(|hop| trace.is_in_round(hop))
+        .map(|hop| {
+            let previous_hop = previous
+                .and_then(|previous| previous.hops().iter().find(|prev| prev.ttl() == hop.ttl()));
+            let responded = hop.addrs_with_details().find(|(addr, details)| {
+                let previous_count = previous_hop
+                    .and_then(|prev| prev.addrs_with_details().find(|(prev, _)| prev == addr))
+                    .map_or(0, |(_, prev)| prev.count());
+                details.count() > previous_count
+            });
+            (hop, responded.map(|(addr, _)| *addr))
+        })
+        .collect()
+}
+
+/// Block until trace data for round `cycles` is available, or until the path has converged if the report is to stop
+/// once it does.
+///
+/// Unless disabled, the progress of every round is written to stderr such that it is never mixed with the report.
+fn wait_for_round(info: &TraceInfo, config: &ReportConfig) -> anyhow::Result<Arc<Trace>> {
+    wait_for_round_with(info, config, |_| Ok(()))
+}
+
+/// Wait for the rounds to be reported, calling `on_round` with the trace as every round completes.
+///
+/// The progress is not reported for a progressive report, which itself shows the progress of the trace.
+fn wait_for_round_with(
+    info: &TraceInfo,
+    config: &ReportConfig,
+    mut on_round: impl FnMut(&Trace) -> anyhow::Result<()>,
+) -> anyhow::Result<Arc<Trace>> {
+    let mut trace = info.data.snapshot();
+    // The cycles of a trace resumed from a state file are traced in addition to the rounds it was resumed with.
+    let cycles = trace.resumed_rounds() + config.cycles;
+    let mut round = None;
+    let mut progress = (config.progress && !config.progressive)
+        .then(|| Progress::new(cycles, info.min_round_duration));
+    let converged = |trace: &Trace| config.until_converged && trace.converged().is_some();
+    while trace.round().is_none() || (trace.round() < Some(cycles - 1) && !converged(&trace)) {
+        trace = info.data.snapshot();
+        if let Some(err) = trace.error() {
+            return Err(anyhow!("error: {}", err));
+        }
+        if let Some(progress) = &mut progress {
+            progress.update(&trace, info.target_addr)?;
+        }
+        if trace.round().is_some() && trace.round() != round {
+            round = trace.round();
+            on_round(&trace)?;
         }
         sleep(Duration::from_millis(100));
     }
+    if let Some(progress) = &mut progress {
+        progress.update(&trace, info.target_addr)?;
+        progress.finish()?;
+    }
     Ok(trace)
 }
+
+/// Reports the progress of the rounds of a trace on stderr.
+///
+/// If stderr is a terminal the progress is shown on a single line which is updated after every round, otherwise a
+/// line is written for every round.
+struct Progress {
+    cycles: usize,
+    min_round_duration: Duration,
+    tty: bool,
+    round: Option<usize>,
+}
+
+impl Progress {
+    fn new(cycles: usize, min_round_duration: Duration) -> Self {
+        Self {
+            cycles,
+            min_round_duration,
+            tty: io::stderr().is_tty(),
+            round: None,
+        }
+    }
+
+    /// Report the progress if a new round has completed since the last update.
+    fn update(&mut self, trace: &Trace, target_addr: IpAddr) -> io::Result<()> {
+        if trace.round().is_none() || trace.round() == self.round {
+            return Ok(());
+        }
+        self.round = trace.round();
+        let line = progress_line(trace, target_addr, self.cycles);
+        let mut stderr = io::stderr().lock();
+        if self.tty {
+            let remaining = self
+                .cycles
+                .saturating_sub(trace.round().map_or(0, |round| round + 1));
+            let eta = self.min_round_duration * remaining as u32;
+            write!(stderr, "\r\x1b[K{line}, eta {}s", eta.as_secs())?;
+        } else {
+            writeln!(stderr, "{line}")?;
+        }
+        stderr.flush()
+    }
+
+    /// Complete the progress line, if stderr is a terminal.
+    fn finish(&self) -> io::Result<()> {
+        if self.tty && self.round.is_some() {
+            writeln!(io::stderr())?;
+        }
+        Ok(())
+    }
+}
+
+/// Describe the progress of a trace, i.e. `round 13/50, target responded, 14 hops`.
+fn progress_line(trace: &Trace, target_addr: IpAddr, cycles: usize) -> String {
+    let round = trace.round().map_or(0, |round| round + 1);
+    let target = if trace
+        .hops()
+        .iter()
+        .any(|hop| hop.addrs().any(|addr| *addr == target_addr))
+    {
+        "target responded"
+    } else {
+        "target not responded"
+    };
+    format!(
+        "round {round}/{cycles}, {target}, {} hops",
+        trace.hops().len()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::testing::{make_round_robin_trace, make_trace, make_trace_info};
+    use crate::config::DnsResolveMethod;
+    use crate::dns::DnsResolverConfig;
+    use crate::event::EventLog;
+    use chrono::TimeZone;
+    use std::net::Ipv4Addr;
+
+    pub fn make_metadata(target_addr: IpAddr, interface: Option<&str>) -> ReportMetadata {
+        ReportMetadata {
+            version: String::from("0.7.0-dev"),
+            local_hostname: String::from("myhost"),
+            source_addr: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+            interface: interface.map(String::from),
+            fwmark: None,
+            target_hostname: String::from("example.com"),
+            target_addr,
+            target_zone: None,
+            target_source: None,
+            protocol: String::from("icmp"),
+            packet_size: 84,
+            rng_seed: 42,
+        }
+    }
+
+    fn writer_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("trippy-report-{name}-{}", std::process::id()))
+    }
+
+    #[test]
+    fn test_writer_influx_written_directly() {
+        let path = writer_path("influx");
+        let mut writer =
+            ReportWriter::open(&ReportOutput::File(path.clone()), Mode::Influx).unwrap();
+        writer.write_all(b"trippy,ttl=1 rtt=1.2 0\n").unwrap();
+        writer.flush().unwrap();
+        assert_eq!(
+            "trippy,ttl=1 rtt=1.2 0\n",
+            fs::read_to_string(&path).unwrap()
+        );
+        writer.finish().unwrap();
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_writer_json_written_atomically() {
+        let path = writer_path("json");
+        let mut writer = ReportWriter::open(&ReportOutput::File(path.clone()), Mode::Json).unwrap();
+        writer.write_all(b"{}").unwrap();
+        writer.flush().unwrap();
+        assert!(!path.exists());
+        writer.finish().unwrap();
+        assert_eq!("{}", fs::read_to_string(&path).unwrap());
+        fs::remove_file(path).unwrap();
+    }
+
+    fn make_csv_row(ttl: u8, addrs: &str) -> CsvRow {
+        CsvRow {
+            target: String::from("example.com"),
+            target_ip: String::from("10.0.0.3"),
+            ttl,
+            addrs: String::from(addrs),
+            hosts: String::new(),
+            loss_pct: String::from("0.0%"),
+            sent: 10,
+            recv: 10,
+            last: String::from("1.2"),
+            avg: String::from("1.5"),
+            best: String::from("0.5"),
+            worst: String::from("2.7"),
+            stddev: String::from("0.2"),
+            jitter: String::from("0.3"),
+        }
+    }
+
+    fn write_csv_rows(config: CsvConfig, rows: Vec<CsvRow>) -> String {
+        let mut out = Vec::new();
+        write_csv(config, rows, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!("example.com", truncate("example.com", 11));
+        assert_eq!("examp...", truncate("example.com", 8));
+        assert_eq!("", truncate("", 8));
+    }
+
+    #[test]
+    fn test_hostnames_format() {
+        let addr = IpAddr::from([10, 0, 0, 1]);
+        let long_hostname = "a".repeat(MAX_HOSTNAME_WIDTH + 10);
+        assert_eq!("10.0.0.1", Hostnames::default().format(&addr));
+        let hostnames = Hostnames(HashMap::from([(addr, String::from("gateway"))]));
+        assert_eq!("gateway (10.0.0.1)", hostnames.format(&addr));
+        let hostnames = Hostnames(HashMap::from([(addr, long_hostname)]));
+        assert_eq!(
+            format!("{}... (10.0.0.1)", "a".repeat(MAX_HOSTNAME_WIDTH - 3)),
+            hostnames.format(&addr)
+        );
+    }
+
+    #[test]
+    fn test_csv_report_roundtrip() {
+        let rows = vec![
+            make_csv_row(1, "gateway, inc."),
+            make_csv_row(2, "10.0.0.2|10.0.0.3"),
+        ];
+        let csv = write_csv_rows(CsvConfig::new(b',', false, true), rows);
+        let parsed: Vec<CsvRow> = csv::Reader::from_reader(csv.as_bytes())
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            vec![
+                make_csv_row(1, "gateway, inc."),
+                make_csv_row(2, "10.0.0.2|10.0.0.3")
+            ],
+            parsed
+        );
+    }
+
+    #[test]
+    fn test_csv_report_header() {
+        let csv = write_csv_rows(CsvConfig::new(b',', false, true), vec![]);
+        assert_eq!("", csv);
+        let csv = write_csv_rows(
+            CsvConfig::new(b',', false, true),
+            vec![make_csv_row(1, "a")],
+        );
+        assert_eq!(
+            "Target,TargetIp,Hop,Addrs,Hosts,Loss%,Snt,Recv,Last,Avg,Best,Wrst,StdDev,Jitter",
+            csv.lines().next().unwrap()
+        );
+        let csv = write_csv_rows(
+            CsvConfig::new(b',', false, false),
+            vec![make_csv_row(1, "a")],
+        );
+        assert_eq!(
+            "example.com,10.0.0.3,1,a,,0.0%,10,10,1.2,1.5,0.5,2.7,0.2,0.3",
+            csv.lines().next().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_csv_report_comments() {
+        let target = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3));
+        let mut out = Vec::new();
+        write_csv_comments(&make_metadata(target, Some("eth0")), None, &mut out).unwrap();
+        assert_eq!(
+            "# version: 0.7.0-dev\n\
+             # local_hostname: myhost\n\
+             # source: 10.0.0.1\n\
+             # interface: eth0\n\
+             # target: example.com (10.0.0.3)\n\
+             # protocol: icmp\n\
+             # packet_size: 84\n\
+             # rng_seed: 42\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_report_metadata_fwmark() {
+        let metadata = ReportMetadata {
+            fwmark: Some(42),
+            ..make_metadata(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3)), None)
+        };
+        assert!(metadata
+            .fields(None)
+            .contains(&("fwmark", String::from("42"))));
+    }
+
+    #[test]
+    fn test_report_metadata_display() {
+        let target = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3));
+        assert_eq!(
+            "example.com (10.0.0.3) from myhost (10.0.0.1) using icmp with 84 byte packets, trippy 0.7.0-dev",
+            make_metadata(target, None).to_string()
+        );
+        assert_eq!(
+            "example.com (10.0.0.3) from myhost (10.0.0.1 via eth0) using icmp with 84 byte packets, trippy \
+             0.7.0-dev",
+            make_metadata(target, Some("eth0")).to_string()
+        );
+    }
+
+    #[test]
+    fn test_report_metadata_target_zone() {
+        let metadata = ReportMetadata {
+            target_zone: Some(String::from("eth0")),
+            ..make_metadata(IpAddr::V6("fe80::1".parse().unwrap()), None)
+        };
+        assert_eq!("fe80::1%eth0", metadata.target_ip());
+        assert!(metadata
+            .to_string()
+            .starts_with("example.com (fe80::1%eth0) from myhost"));
+    }
+
+    #[test]
+    fn test_report_metadata_target_source() {
+        let target = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3));
+        let fields = make_metadata(target, None).fields(None);
+        assert!(!fields.iter().any(|(name, _)| *name == "target_source"));
+        let metadata = ReportMetadata {
+            target_source: Some(DnsSource::HostsFile),
+            ..make_metadata(target, None)
+        };
+        let fields = metadata.fields(None);
+        assert_eq!(
+            Some(&("target_source", String::from("hosts-file"))),
+            fields.iter().find(|(name, _)| *name == "target_source")
+        );
+        let report = super::make_report(
+            &metadata,
+            &Trace::new(16),
+            &Hostnames::default(),
+            Utc::now(),
+            Utc::now(),
+            false,
+            false,
+        );
+        assert_eq!(Some(String::from("hosts-file")), report.info.target_source);
+    }
+
+    #[test]
+    fn test_table_header() {
+        let target = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3));
+        let mut out = Vec::new();
+        write_table_header(
+            &make_metadata(target, None),
+            Some(FlowKey::udp(33000, 33434, 0xbeef)),
+            TableStyle::Markdown,
+            &mut out,
+        )
+        .unwrap();
+        let header = String::from_utf8(out).unwrap();
+        assert!(header.starts_with("- version: 0.7.0-dev\n- local_hostname: myhost\n"));
+        assert!(header.ends_with(
+            "- rng_seed: 42\n- flow_key: src_port=33000 dest_port=33434 checksum=0xbeef\n\n"
+        ));
+        assert!(!header.contains("interface"));
+    }
+
+    #[test]
+    fn test_csv_report_delimiter() {
+        let csv = write_csv_rows(
+            CsvConfig::new(b';', false, false),
+            vec![make_csv_row(1, "a;b")],
+        );
+        let parsed: Vec<CsvRow> = csv::ReaderBuilder::new()
+            .delimiter(b';')
+            .has_headers(false)
+            .from_reader(csv.as_bytes())
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(vec![make_csv_row(1, "a;b")], parsed);
+        assert!(csv.starts_with("example.com;10.0.0.3;1;\"a;b\";;0.0%;"));
+    }
+
+    const GOLDEN_JSON_REPORT: &str = include_str!("../tests/resources/json_report.json");
+    const GOLDEN_TABLE_REPORT_PRETTY: &str =
+        include_str!("../tests/resources/table_report_pretty.txt");
+    const GOLDEN_TABLE_REPORT_MARKDOWN: &str =
+        include_str!("../tests/resources/table_report_markdown.txt");
+
+    pub fn make_report() -> Report {
+        Report {
+            info: ReportInfo {
+                version: String::from("0.7.0-dev"),
+                schema: REPORT_SCHEMA_VERSION,
+                start: Utc.timestamp_opt(1_672_531_200, 0).unwrap(),
+                end: Utc.timestamp_opt(1_672_531_210, 0).unwrap(),
+                target: Host {
+                    ip: String::from("10.0.0.3"),
+                    hostname: String::from("example.com"),
+                },
+                target_source: None,
+                source: String::from("10.0.0.1"),
+                local_hostname: String::from("myhost"),
+                interface: Some(String::from("eth0")),
+                fwmark: Some(42),
+                protocol: String::from("icmp"),
+                packet_size: 84,
+                rng_seed: Some(42),
+                rounds: 10,
+                clock_anomalies: 0,
+                invalid_ttls: 0,
+                channel_metrics: None,
+                path_stability: None,
+                converged_round: None,
+                stopped_by: None,
+                flow_key: None,
+            },
+            hops: vec![
+                ReportHop {
+                    ttl: 1,
+                    addrs: vec![ReportHopAddr {
+                        ip: String::from("10.0.0.2"),
+                        hostname: Some(String::from("gateway")),
+                        count: 10,
+                        last: 1.25,
+                        best: 0.5,
+                        worst: 2.75,
+                        quote: None,
+                    }],
+                    loss_pct: 0_f64,
+                    sent: 10,
+                    last: 1.25,
+                    recv: 10,
+                    avg: 1.5,
+                    best: 0.5,
+                    worst: 2.75,
+                    stddev: 0.25,
+                    jitter: 0.5,
+                    rate_limited_likely: false,
+                    asymmetry: None,
+                    responder_ports: vec![],
+                    port_mismatch: false,
+                    last_recv_time: None,
+                    samples: None,
+                    segment: None,
+                },
+                ReportHop {
+                    ttl: 2,
+                    addrs: vec![ReportHopAddr {
+                        ip: String::from("10.0.0.3"),
+                        hostname: None,
+                        count: 5,
+                        last: 10.5,
+                        best: 9.25,
+                        worst: 12.0,
+                        quote: None,
+                    }],
+                    loss_pct: 50_f64,
+                    sent: 10,
+                    last: 10.5,
+                    recv: 5,
+                    avg: 10.75,
+                    best: 9.25,
+                    worst: 12.0,
+                    stddev: 1.0,
+                    jitter: 1.75,
+                    rate_limited_likely: true,
+                    asymmetry: Some(3),
+                    responder_ports: vec![],
+                    port_mismatch: false,
+                    last_recv_time: None,
+                    samples: None,
+                    segment: None,
+                },
+            ],
+            targets: vec![],
+        }
+    }
+
+    /// The line of a JSON report with the time of the run it holds, if any, replaced by that of the golden file.
+    fn fix_time(line: &str) -> Option<String> {
+        let (key, _) = line.split_once(": ")?;
+        let time = match key.trim_start() {
+            "\"start\"" => "2023-01-01T00:00:00Z",
+            "\"end\"" => "2023-01-01T00:00:10Z",
+            "\"last_change\"" => "2023-01-01T00:00:05Z",
+            _ => return None,
+        };
+        let comma = if line.ends_with(',') { "," } else { "" };
+        Some(format!("{key}: \"{time}\"{comma}"))
+    }
+
+    /// The report written by `run_report_json` matches the golden file, once the times of the run are fixed.
+    #[test]
+    fn test_json_report_golden() {
+        let info = make_trace_info(make_table_trace());
+        let metadata = ReportMetadata {
+            fwmark: Some(42),
+            ..make_metadata(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3)), Some("eth0"))
+        };
+        let config = ReportConfig::new(3, false, Duration::from_secs(1), false, false, false);
+        let resolver = DnsResolver::start(
+            DnsResolverConfig::new_ipv4(DnsResolveMethod::System, Duration::from_secs(1)),
+            EventLog::shared(),
+        )
+        .unwrap();
+        let mut out = Vec::new();
+        run_report_json(&info, &metadata, &config, &resolver, false, false, &mut out).unwrap();
+        let json = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| fix_time(line).unwrap_or_else(|| line.to_string()))
+            .join("\n");
+        assert_eq!(GOLDEN_JSON_REPORT.trim_end(), json);
+    }
+
+    fn make_table_trace() -> Trace {
+        let router = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+        let alternate = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 11)));
+        let target = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3)));
+        make_trace(&[
+            &[(router, 10), (None, 0), (target, 30)],
+            &[(alternate, 14), (None, 0), (target, 34)],
+            &[(router, 12), (None, 0), (None, 0)],
+        ])
+    }
+
+    fn render_table(columns: &str, style: TableStyle) -> String {
+        let mut hostnames = Hostnames::default();
+        hostnames.0.insert(
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+            String::from("router.example.com"),
+        );
+        let columns = Columns::try_from(columns).unwrap();
+        make_table(&make_table_trace(), &hostnames, &columns, style).to_string()
+    }
+
+    #[test]
+    fn test_table_pretty_golden() {
+        assert_eq!(
+            GOLDEN_TABLE_REPORT_PRETTY.trim_end(),
+            render_table(Column::DEFAULT_SPEC, TableStyle::Pretty)
+        );
+    }
+
+    #[test]
+    fn test_table_markdown_golden() {
+        assert_eq!(
+            GOLDEN_TABLE_REPORT_MARKDOWN.trim_end(),
+            render_table(Column::DEFAULT_SPEC, TableStyle::Markdown)
+        );
+    }
+
+    #[test]
+    fn test_table_columns() {
+        assert_eq!(
+            "| Hop   | Jitter | Loss% |\n\
+             |-------|--------|-------|\n\
+             | 1     | 3.0    | 0.0   |\n\
+             | 2     | -      | 100.0 |\n\
+             | **3** | 4.0    | 33.3  |",
+            render_table("hjl", TableStyle::Markdown)
+        );
+    }
+
+    #[test]
+    fn test_table_report() {
+        let target = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3));
+        let columns = Columns::try_from("hl").unwrap();
+        let mut out = Vec::new();
+        write_table_report(
+            &make_metadata(target, None),
+            &make_table_trace(),
+            &Hostnames::default(),
+            &columns,
+            TableStyle::Markdown,
+            &mut out,
+        )
+        .unwrap();
+        let report = String::from_utf8(out).unwrap();
+        assert!(report.starts_with("- version: 0.7.0-dev\n"));
+        assert!(report.ends_with("- packet_size: 84\n- rng_seed: 42\n\n| Hop   | Loss% |\n|-------|-------|\n| 1     | 0.0   |\n| 2     | 100.0 |\n| **3** | 33.3  |\n"));
+    }
+
+    #[test]
+    fn test_redraw() {
+        let mut redraw = Redraw::default();
+        let mut out = Vec::new();
+        redraw.draw("hop 1\nhop 2 pending\n", &mut out).unwrap();
+        assert_eq!(
+            "hop 1\x1b[K\nhop 2 pending\x1b[K\n",
+            String::from_utf8(out).unwrap()
+        );
+        let mut out = Vec::new();
+        redraw.draw("hop 1\nhop 2\nhop 3\n", &mut out).unwrap();
+        assert_eq!(
+            "\r\x1b[2A\nhop 2\x1b[K\nhop 3\x1b[K\n",
+            String::from_utf8(out).unwrap()
+        );
+        let mut out = Vec::new();
+        redraw.draw("hop 1\n", &mut out).unwrap();
+        assert_eq!("\r\x1b[3A\n\x1b[J", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn test_render_table_report() {
+        let target = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3));
+        let metadata = make_metadata(target, None);
+        let columns = Columns::try_from(Column::DEFAULT_SPEC).unwrap();
+        let mut out = Vec::new();
+        write_table_report(
+            &metadata,
+            &make_table_trace(),
+            &Hostnames::default(),
+            &columns,
+            TableStyle::Pretty,
+            &mut out,
+        )
+        .unwrap();
+        let report = render_table_report(
+            &metadata,
+            &make_table_trace(),
+            &Hostnames::default(),
+            &columns,
+            TableStyle::Pretty,
+        )
+        .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), report);
+    }
+
+    #[test]
+    fn test_make_report() {
+        let target = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3));
+        let start = Utc.timestamp_opt(1_672_531_200, 0).unwrap();
+        let end = Utc.timestamp_opt(1_672_531_210, 0).unwrap();
+        let report = super::make_report(
+            &make_metadata(target, None),
+            &make_table_trace(),
+            &Hostnames::default(),
+            start,
+            end,
+            false,
+            false,
+        );
+        assert_eq!(3, report.info.rounds);
+        assert_eq!(0, report.info.clock_anomalies);
+        assert_eq!(0, report.info.invalid_ttls);
+        assert_eq!(None, report.info.channel_metrics);
+        assert_eq!(start, report.info.start);
+        assert_eq!(
+            vec![1, 2, 3],
+            report.hops.iter().map(|hop| hop.ttl).collect::<Vec<_>>()
+        );
+        assert!(report.targets.is_empty());
+    }
+
+    #[test]
+    fn test_make_report_invalid_ttls() {
+        use trippy_core::{
+            CompletionReason, Probe, ProbeStatus, Round, Sequence, TimeToLive, TracerRound,
+        };
+        let target = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3));
+        let sent = std::time::SystemTime::UNIX_EPOCH;
+        let probe = Probe::new(Sequence(33000), TimeToLive(0), Round(0), sent)
+            .with_status(ProbeStatus::Complete)
+            .with_host(target)
+            .with_received(sent + Duration::from_millis(10));
+        let mut trace = Trace::new(256);
+        trace.update_from_round(&TracerRound::new(
+            &[probe],
+            TimeToLive(1),
+            CompletionReason::TargetFound,
+        ));
+        let now = Utc.timestamp_opt(1_672_531_200, 0).unwrap();
+        let report = super::make_report(
+            &make_metadata(target, None),
+            &trace,
+            &Hostnames::default(),
+            now,
+            now,
+            false,
+            false,
+        );
+        assert_eq!(1, report.info.invalid_ttls);
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(1, json["info"]["invalid_ttls"]);
+    }
+
+    #[test]
+    fn test_make_report_clock_anomalies() {
+        use trippy_core::{
+            CompletionReason, Probe, ProbeStatus, Round, Sequence, TimeToLive, TracerRound,
+        };
+        let target = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3));
+        let sent = std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+        let probe = Probe::new(Sequence(33000), TimeToLive(1), Round(0), sent)
+            .with_status(ProbeStatus::Complete)
+            .with_host(target)
+            .with_received(sent - Duration::from_millis(10));
+        let mut trace = Trace::new(256);
+        trace.update_from_round(&TracerRound::new(
+            &[probe],
+            TimeToLive(1),
+            CompletionReason::TargetFound,
+        ));
+        let now = Utc.timestamp_opt(1_672_531_200, 0).unwrap();
+        let report = super::make_report(
+            &make_metadata(target, None),
+            &trace,
+            &Hostnames::default(),
+            now,
+            now,
+            false,
+            false,
+        );
+        assert_eq!(1, report.info.clock_anomalies);
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(1, json["info"]["clock_anomalies"]);
+    }
+
+    #[test]
+    fn test_make_report_port_mismatch() {
+        use trippy_core::{
+            CompletionReason, Probe, ProbeStatus, ResponderPorts, Round, Sequence, TimeToLive,
+            TracerRound,
+        };
+        let target = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3));
+        let sent = std::time::SystemTime::UNIX_EPOCH;
+        let mut trace = Trace::new(256);
+        for (round, responder) in [(0, 443), (1, 3128)] {
+            let probe = Probe::new(Sequence(33000), TimeToLive(1), Round(round), sent)
+                .with_status(ProbeStatus::Complete)
+                .with_host(target)
+                .with_received(sent + Duration::from_millis(10))
+                .with_responder_ports(Some(ResponderPorts::new(443, responder)));
+            trace.update_from_round(&TracerRound::new(
+                &[probe],
+                TimeToLive(1),
+                CompletionReason::TargetFound,
+            ));
+        }
+        let now = Utc.timestamp_opt(1_672_531_200, 0).unwrap();
+        let report = super::make_report(
+            &make_metadata(target, None),
+            &trace,
+            &Hostnames::default(),
+            now,
+            now,
+            false,
+            false,
+        );
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(true, json["hops"][0]["port_mismatch"]);
+        assert_eq!(
+            serde_json::json!([
+                {"probed": 443, "responder": 443, "count": 1},
+                {"probed": 443, "responder": 3128, "count": 1},
+            ]),
+            json["hops"][0]["responder_ports"]
+        );
+        let json = serde_json::to_value(make_report()).unwrap();
+        assert!(json["hops"][0].get("port_mismatch").is_none());
+        assert!(json["hops"][0].get("responder_ports").is_none());
+    }
+
+    #[test]
+    fn test_make_report_last_recv_time() {
+        let target = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3));
+        let trace = make_trace(&[&[(Some(target), 10), (None, 0)]]);
+        let now = Utc.timestamp_opt(1_672_531_200, 0).unwrap();
+        let report = super::make_report(
+            &make_metadata(target, None),
+            &trace,
+            &Hostnames::default(),
+            now,
+            now,
+            false,
+            false,
+        );
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(
+            "1970-01-01T00:00:00.010Z",
+            json["hops"][0]["last_recv_time"]
+        );
+        assert!(json["hops"][1].get("last_recv_time").is_none());
+    }
+
+    #[test]
+    fn test_make_report_converged() {
+        use trippy_core::Convergence;
+        let hop1 = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+        let target = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3));
+        let round = [(hop1, 10), (Some(target), 10)];
+        let mut trace = make_trace(&[&round, &round, &round]);
+        let config = |until_converged| {
+            ReportConfig::new(10, false, Duration::ZERO, false, until_converged, false)
+        };
+        assert_eq!(Some(StoppedBy::Cycles), config(true).stopped_by(&trace));
+        trace.update_converged(&Convergence {
+            rounds: 3,
+            samples: 3,
+        });
+        assert_eq!(Some(StoppedBy::Converged), config(true).stopped_by(&trace));
+        assert_eq!(None, config(false).stopped_by(&trace));
+        let now = Utc.timestamp_opt(1_672_531_200, 0).unwrap();
+        let mut report = super::make_report(
+            &make_metadata(target, None),
+            &trace,
+            &Hostnames::default(),
+            now,
+            now,
+            false,
+            false,
+        );
+        report.info.stopped_by = config(true).stopped_by(&trace);
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(2, json["info"]["converged_round"]);
+        assert_eq!("converged", json["info"]["stopped_by"]);
+        let parsed: Report = serde_json::from_value(json).unwrap();
+        assert_eq!(Some(StoppedBy::Converged), parsed.info.stopped_by);
+    }
+
+    #[test]
+    fn test_make_report_path_stability() {
+        let hop1 = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+        let other = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)));
+        let target = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3));
+        let now = Utc.timestamp_opt(1_672_531_200, 0).unwrap();
+        let report = |trace: &Trace| {
+            super::make_report(
+                &make_metadata(target, None),
+                trace,
+                &Hostnames::default(),
+                now,
+                now,
+                false,
+                false,
+            )
+        };
+        let stable = report(&make_trace(&[
+            &[(hop1, 10), (Some(target), 10)],
+            &[(hop1, 10), (Some(target), 10)],
+        ]));
+        let stability = stable.info.path_stability.unwrap();
+        assert_eq!(
+            (100_f64, 1, 0),
+            (stability.score, stability.distinct_paths, stability.changes)
+        );
+        assert_eq!(None, stability.last_change);
+        let changed = report(&make_trace(&[
+            &[(hop1, 10), (Some(target), 10)],
+            &[(other, 10), (Some(target), 10)],
+        ]));
+        let json = serde_json::to_value(&changed).unwrap();
+        assert_eq!("66.67", json["info"]["path_stability"]["score"]);
+        assert_eq!(2, json["info"]["path_stability"]["distinct_paths"]);
+        assert_eq!(1, json["info"]["path_stability"]["changes"]);
+        assert!(json["info"]["path_stability"]["last_change"].is_string());
+        let empty = report(&Trace::new(256));
+        assert_eq!(None, empty.info.path_stability);
+    }
+
+    #[test]
+    fn test_make_report_channel_metrics() {
+        use trippy_core::{ChannelMetrics, CompletionReason, TimeToLive, TracerRound};
+        let target = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3));
+        let metrics = ChannelMetrics {
+            received: 5,
+            matched: 2,
+            ignored: 1,
+            malformed: 1,
+            duplicates: 1,
+            late: 0,
+            tcp_evicted: 0,
+        };
+        let mut trace = Trace::new(256);
+        trace.update_from_round(
+            &TracerRound::new(&[], TimeToLive(1), CompletionReason::RoundTimeLimitExceeded)
+                .with_channel_metrics(metrics),
+        );
+        let now = Utc.timestamp_opt(1_672_531_200, 0).unwrap();
+        let report = super::make_report(
+            &make_metadata(target, None),
+            &trace,
+            &Hostnames::default(),
+            now,
+            now,
+            false,
+            false,
+        );
+        assert_eq!(Some(metrics), report.info.channel_metrics);
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(1, json["info"]["channel_metrics"]["malformed"]);
+    }
+
+    #[test]
+    fn test_make_report_round_robin() {
+        let hop1 = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+        let first = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3));
+        let second = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 4));
+        let trace = make_round_robin_trace(&[
+            &[(hop1, 10), (Some(first), 20)],
+            &[(hop1, 10), (None, 0), (Some(second), 30)],
+            &[(hop1, 10), (Some(first), 20)],
+        ]);
+        let now = Utc.timestamp_opt(1_672_531_200, 0).unwrap();
+        let report = super::make_report(
+            &make_metadata(first, None),
+            &trace,
+            &Hostnames::default(),
+            now,
+            now,
+            false,
+            false,
+        );
+        let targets: Vec<_> = report
+            .targets
+            .iter()
+            .map(|target| (target.ip.as_str(), target.rounds, target.hops.len()))
+            .collect();
+        assert_eq!(vec![("10.0.0.3", 2, 2), ("10.0.0.4", 1, 3)], targets);
+        let json = serde_json::to_string(&report).unwrap();
+        let parsed: Report = serde_json::from_str(&json).unwrap();
+        assert_eq!(report.targets[1].hops, parsed.targets[1].hops);
+    }
+
+    #[test]
+    fn test_stream_round() {
+        let hop1 = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let target = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+        let trace = make_trace(&[&[(Some(hop1), 10), (Some(target), 20)]]);
+        let mut hostnames = Hostnames::default();
+        hostnames.0.insert(hop1, String::from("router.example.com"));
+        let now = Utc.timestamp_opt(1_672_531_200, 123_000_000).unwrap();
+        let render = |config: &StreamConfig| {
+            let mut out = Vec::new();
+            write_stream_round(&trace, &hostnames, config, now, &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(
+            "2023-01-01T00:00:00.123Z round=0\n\
+             ttl=1 addrs=[router.example.com (10.0.0.1)] loss_pct=0.0, sent=1 recv=1 last=10.0 best=10.0 worst=10.0 avg=10.0 stddev=0.0\n\
+             ttl=2 addrs=[10.0.0.2] loss_pct=0.0, sent=1 recv=1 last=20.0 best=20.0 worst=20.0 avg=20.0 stddev=0.0\n",
+            render(&StreamConfig::new(None, false))
+        );
+        assert_eq!(
+            "00:00:00 round=0\n\
+             00:00:00 ttl=1 addrs=[router.example.com (10.0.0.1)] loss_pct=0.0, sent=1 recv=1 last=10.0 best=10.0 worst=10.0 avg=10.0 stddev=0.0\n\
+             00:00:00 ttl=2 addrs=[10.0.0.2] loss_pct=0.0, sent=1 recv=1 last=20.0 best=20.0 worst=20.0 avg=20.0 stddev=0.0\n",
+            render(&StreamConfig::new(Some(String::from("%H:%M:%S")), true))
+        );
+    }
+
+    #[test]
+    fn test_stream_converged() {
+        let now = Utc.timestamp_opt(1_672_531_200, 123_000_000).unwrap();
+        let mut out = Vec::new();
+        write_stream_converged(4, &StreamConfig::new(None, false), now, &mut out).unwrap();
+        assert_eq!(
+            "2023-01-01T00:00:00.123Z converged round=4\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_stream_path_change() {
+        let hop1 = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+        let hop2 = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+        let other = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 4)));
+        let target = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3)));
+        let mut hostnames = Hostnames::default();
+        hostnames.0.insert(hop2, String::from("router.example.com"));
+        let now = Utc.timestamp_opt(1_672_531_200, 123_000_000).unwrap();
+        let render = |trace: &Trace| {
+            let mut out = Vec::new();
+            write_stream_path_change(
+                trace,
+                &hostnames,
+                &StreamConfig::new(None, false),
+                now,
+                &mut out,
+            )
+            .unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let stable = make_trace(&[
+            &[(hop1, 10), (Some(hop2), 10), (target, 10)],
+            &[(hop1, 10), (Some(hop2), 10), (target, 10)],
+        ]);
+        assert_eq!("", render(&stable));
+        let changed = make_trace(&[
+            &[(hop1, 10), (Some(hop2), 10), (target, 10)],
+            &[(hop1, 10), (other, 10), (target, 10)],
+        ]);
+        assert_eq!(
+            "2023-01-01T00:00:00.123Z path_change round=1 distinct_paths=2 stability=72.2 \
+             hops=[ttl=2 router.example.com (10.0.0.2)->10.0.0.4]\n",
+            render(&changed)
+        );
+        let shortened = make_trace(&[
+            &[(hop1, 10), (Some(hop2), 10), (target, 10)],
+            &[(hop1, 10), (Some(hop2), 10)],
+        ]);
+        assert_eq!(
+            "2023-01-01T00:00:00.123Z path_change round=1 distinct_paths=2 stability=65.6 \
+             hops=[ttl=3 10.0.0.3->*]\n",
+            render(&shortened)
+        );
+    }
+
+    #[test]
+    fn test_stream_round_started() {
+        use trippy_core::{
+            CompletionReason, Probe, ProbeStatus, Round, Sequence, TimeToLive, TracerRound,
+        };
+        let target = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+        let started = SystemTime::from(Utc.timestamp_opt(1_672_531_200, 456_000_000).unwrap());
+        let probe = Probe::new(Sequence(33000), TimeToLive(1), Round(0), started)
+            .with_status(ProbeStatus::Complete)
+            .with_host(target)
+            .with_received(started + Duration::from_millis(10));
+        let mut trace = Trace::new(256);
+        trace.update_from_round(
+            &TracerRound::new(&[probe], TimeToLive(1), CompletionReason::TargetFound)
+                .with_started(started),
+        );
+        let now = Utc.timestamp_opt(1_672_531_201, 0).unwrap();
+        let mut out = Vec::new();
+        write_stream_round(
+            &trace,
+            &Hostnames::default(),
+            &StreamConfig::new(None, false),
+            now,
+            &mut out,
+        )
+        .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(
+            out.starts_with("2023-01-01T00:00:01.000Z round=0 started=2023-01-01T00:00:00.456Z\n")
+        );
+    }
+
+    #[test]
+    fn test_flow_key_reported() {
+        use trippy_core::{CompletionReason, TimeToLive, TracerRound};
+        let mut trace = Trace::new(256);
+        trace.update_from_round(
+            &TracerRound::new(&[], TimeToLive(1), CompletionReason::RoundTimeLimitExceeded)
+                .with_flow_key(FlowKey::udp(33000, 33434, 0xbeef).with_ip_id(33000)),
+        );
+        let now = Utc.timestamp_opt(1_672_531_200, 0).unwrap();
+        let mut out = Vec::new();
+        write_stream_round(
+            &trace,
+            &Hostnames::default(),
+            &StreamConfig::new(None, false),
+            now,
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!(
+            "2023-01-01T00:00:00.000Z round= src_port=33000 dest_port=33434 checksum=0xbeef ip_id=33000\n",
+            String::from_utf8(out).unwrap()
+        );
+        let target = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3));
+        let report = super::make_report(
+            &make_metadata(target, None),
+            &trace,
+            &Hostnames::default(),
+            now,
+            now,
+            false,
+            false,
+        );
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(33434, json["info"]["flow_key"]["dest_port"]);
+        assert!(json["info"]["flow_key"]["icmp_identifier"].is_null());
+    }
+
+    #[test]
+    fn test_stream_alert() {
+        let target = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+        let now = Utc.timestamp_opt(1_672_531_260, 0).unwrap();
+        let since = SystemTime::from(Utc.timestamp_opt(1_672_531_200, 0).unwrap());
+        let render = |alert: &Alert| {
+            let mut out = Vec::new();
+            write_stream_alert(alert, &StreamConfig::new(None, false), now, &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let loss = Alert::Degraded {
+            target,
+            breach: Breach::Loss {
+                loss_pct: 12.5,
+                threshold: 10_f64,
+            },
+            since,
+        };
+        assert_eq!(
+            "2023-01-01T00:01:00.000Z alert=degraded target=10.0.0.2 since=2023-01-01T00:00:00.000Z target_loss_pct=12.5 alert_target_loss=10.0\n",
+            render(&loss)
+        );
+        let latency = Alert::Degraded {
+            target,
+            breach: Breach::Latency {
+                avg: Duration::from_millis(150),
+                threshold: Duration::from_millis(100),
+            },
+            since,
+        };
+        assert_eq!(
+            "2023-01-01T00:01:00.000Z alert=degraded target=10.0.0.2 since=2023-01-01T00:00:00.000Z target_avg=150.0 alert_target_latency=100.0\n",
+            render(&latency)
+        );
+        assert_eq!(
+            "2023-01-01T00:01:00.000Z alert=recovered target=10.0.0.2 since=2023-01-01T00:00:00.000Z\n",
+            render(&Alert::Recovered { target, since })
+        );
+    }
+
+    #[test]
+    fn test_stream_summary() {
+        let target = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+        let trace = make_trace(&[&[(Some(target), 20)], &[(None, 0)]]);
+        let mut out = Vec::new();
+        write_stream_summary(
+            &make_metadata(target, None),
+            &trace,
+            Duration::from_millis(2100),
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!(
+            "--- example.com (10.0.0.2) trace summary ---\n\
+             rounds=2 elapsed=2.1s target_loss_pct=50.0\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_json_report_roundtrip() {
+        let report: Report = serde_json::from_str(GOLDEN_JSON_REPORT).unwrap();
+        let json = serde_json::to_string_pretty(&report).unwrap();
+        assert_eq!(GOLDEN_JSON_REPORT.trim_end(), json);
+    }
+
+    #[test]
+    fn test_json_report_samples_roundtrip() {
+        let target = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3)));
+        let trace = make_trace(&[
+            &[(target, 10)],
+            &[(None, 0)],
+            &[(target, 30)],
+            &[(target, 40)],
+        ]);
+        let hop = ReportHop {
+            samples: Some(report_samples(&trace.hops()[0])),
+            ..make_report().hops.remove(0)
+        };
+        let json = serde_json::to_string(&hop).unwrap();
+        let hop: ReportHop = serde_json::from_str(&json).unwrap();
+        let samples: Vec<_> = hop
+            .samples
+            .unwrap()
+            .into_iter()
+            .map(|sample| (sample.seq_offset, sample.rtt_ms))
+            .collect();
+        assert_eq!(
+            vec![
+                (0, Some(40_f64)),
+                (1, Some(30_f64)),
+                (2, None),
+                (3, Some(10_f64))
+            ],
+            samples
+        );
+    }
+
+    #[test]
+    fn test_json_report_samples_omitted() {
+        let json = serde_json::to_value(make_report()).unwrap();
+        assert!(json["hops"][0].get("samples").is_none());
+        assert!(json["hops"][0].get("segment").is_none());
+    }
+
+    #[test]
+    fn test_json_report_quote() {
+        use trippy_core::{
+            CompletionReason, IcmpQuote, Probe, ProbeStatus, Round, Sequence, TimeToLive,
+            TracerRound,
+        };
+        let sent = std::time::SystemTime::UNIX_EPOCH;
+        let probe = Probe::new(Sequence(33000), TimeToLive(1), Round(0), sent)
+            .with_status(ProbeStatus::Complete)
+            .with_host(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)))
+            .with_received(sent + Duration::from_millis(10))
+            .with_quote(Some(IcmpQuote::new(100, 56, true)));
+        let mut trace = Trace::new(256);
+        trace.update_from_round(&TracerRound::new(
+            &[probe],
+            TimeToLive(1),
+            CompletionReason::TargetFound,
+        ));
+        let hops = report_hops(&trace, &Hostnames::default(), true, false);
+        let json = serde_json::to_value(hops).unwrap();
+        assert_eq!(
+            serde_json::json!({"payload_len": 100, "quoted_len": 56, "full": true}),
+            json[0]["addrs"][0]["quote"]
+        );
+        let hops = report_hops(&trace, &Hostnames::default(), false, false);
+        let json = serde_json::to_value(hops).unwrap();
+        assert!(json[0]["addrs"][0].get("quote").is_none());
+    }
+
+    #[test]
+    fn test_json_report_segments() {
+        let router = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+        let target = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3)));
+        let trace = make_trace(&[&[(router, 30), (None, 0), (target, 20)]]);
+        let hops = report_hops(&trace, &Hostnames::default(), false, true);
+        let json = serde_json::to_value(hops).unwrap();
+        assert_eq!(
+            serde_json::json!({"latency_ms": "30.00", "negative": false}),
+            json[0]["segment"]
+        );
+        assert!(json[1].get("segment").is_none());
+        assert_eq!(
+            serde_json::json!({"latency_ms": "0.00", "negative": true}),
+            json[2]["segment"]
+        );
+    }
+
+    #[test]
+    fn test_progress_line() {
+        let router = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+        let target = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3));
+        let unresponsive = make_trace(&[&[(router, 10), (None, 0)]]);
+        assert_eq!(
+            "round 1/50, target not responded, 2 hops",
+            progress_line(&unresponsive, target, 50)
+        );
+        let responsive = make_trace(&[
+            &[(router, 10), (None, 0)],
+            &[(router, 10), (Some(target), 20)],
+        ]);
+        assert_eq!(
+            "round 2/50, target responded, 2 hops",
+            progress_line(&responsive, target, 50)
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use crate::report::{trace_report, Report, ReportConfig, ReportMetadata};
+use crate::{DnsResolver, TraceInfo};
+use anyhow::anyhow;
+use std::fs::File;
+use std::io::{BufReader, Read, Write};
+use std::path::Path;
+
+/// Generate a CBOR report of trace data.
+///
+/// The report has exactly the same schema as the JSON report and is considerably more compact, particularly when the
+/// samples of every hop are included.
+pub fn run_report_cbor(
+    info: &TraceInfo,
+    metadata: &ReportMetadata,
+    config: &ReportConfig,
+    resolver: &DnsResolver,
+    include_samples: bool,
+    include_segments: bool,
+    out: &mut impl Write,
+) -> anyhow::Result<()> {
+    let report = trace_report(
+        info,
+        metadata,
+        config,
+        resolver,
+        include_samples,
+        include_segments,
+    )?;
+    write_cbor(&report, out)
+}
+
+/// Decode the CBOR report at `path` and write it as a JSON report.
+pub fn run_decode(path: &Path, out: &mut impl Write) -> anyhow::Result<()> {
+    let file =
+        File::open(path).map_err(|e| anyhow!("failed to open report {}: {}", path.display(), e))?;
+    decode(BufReader::new(file), out)
+        .map_err(|e| anyhow!("failed to decode report {}: {}", path.display(), e))
+}
+
+fn write_cbor(report: &Report, out: &mut impl Write) -> anyhow::Result<()> {
+    serde_cbor::to_writer(&mut *out, report)?;
+    Ok(())
+}
+
+fn decode(input: impl Read, out: &mut impl Write) -> anyhow::Result<()> {
+    let report: Report = serde_cbor::from_reader(input)?;
+    serde_json::to_writer_pretty(&mut *out, &report)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::testing::make_trace;
+    use crate::report::{report_hops, Host, Hostnames, ReportInfo, REPORT_SCHEMA_VERSION};
+    use chrono::{TimeZone, Utc};
+    use std::net::{IpAddr, Ipv4Addr};
+
+    fn make_report() -> Report {
+        let router = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+        let target = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3)));
+        let trace = make_trace(&[&[(router, 10), (target, 30)], &[(router, 12), (None, 0)]]);
+        Report {
+            info: ReportInfo {
+                version: String::from("0.7.0-dev"),
+                schema: REPORT_SCHEMA_VERSION,
+                start: Utc.timestamp_opt(1_672_531_200, 0).unwrap(),
+                end: Utc.timestamp_opt(1_672_531_210, 0).unwrap(),
+                target: Host {
+                    ip: String::from("10.0.0.3"),
+                    hostname: String::from("example.com"),
+                },
+                target_source: None,
+                source: String::from("10.0.0.1"),
+                local_hostname: String::from("myhost"),
+                interface: None,
+                fwmark: None,
+                protocol: String::from("icmp"),
+                packet_size: 84,
+                rng_seed: None,
+                rounds: 2,
+                clock_anomalies: 0,
+                invalid_ttls: 0,
+                channel_metrics: None,
+                path_stability: None,
+                converged_round: None,
+                stopped_by: None,
+                flow_key: None,
+            },
+            hops: report_hops(&trace, &Hostnames::default(), true, true),
+            targets: vec![],
+        }
+    }
+
+    #[test]
+    fn test_cbor_report_roundtrip() {
+        let report = make_report();
+        let mut cbor = vec![];
+        write_cbor(&report, &mut cbor).unwrap();
+        let mut json = vec![];
+        decode(cbor.as_slice(), &mut json).unwrap();
+        let expected = format!("{}\n", serde_json::to_string_pretty(&report).unwrap());
+        assert_eq!(expected, String::from_utf8(json).unwrap());
+        let decoded: Report = serde_cbor::from_slice(&cbor).unwrap();
+        assert_eq!(report, decoded);
+    }
+
+    #[test]
+    fn test_cbor_report_is_compact() {
+        let report = make_report();
+        let mut cbor = vec![];
+        write_cbor(&report, &mut cbor).unwrap();
+        assert!(cbor.len() < serde_json::to_vec(&report).unwrap().len());
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        let mut json = vec![];
+        assert!(decode(&b"{\"not\": \"cbor\"}"[..], &mut json).is_err());
+    }
+}
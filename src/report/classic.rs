@@ -0,0 +1,366 @@
+use crate::backend::{Hop, Trace};
+use crate::config::CLASSIC_ROUNDS;
+use crate::report::{round_responses, Hostnames, ReportConfig, ReportMetadata};
+use crate::{DnsResolver, TraceInfo};
+use anyhow::anyhow;
+use std::io::Write;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::thread::sleep;
+use std::time::Duration;
+use trippy_core::IcmpPacketType;
+use trippy_core::Unreachable;
+
+/// The interval at which the trace is polled for newly completed rounds.
+const ROUND_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Generate a classic `traceroute` style report.
+///
+/// Every hop is traced `CLASSIC_ROUNDS` times and is printed on a single line as soon as the results of all of its
+/// probes are known, an `*` is shown for every probe which timed out and the address is shown again whenever it
+/// differs from that of the previous probe of the hop.  Probes which were answered with an ICMP destination
+/// unreachable message are annotated as `traceroute` does, i.e. `!H` for host unreachable.
+pub fn run_report_classic(
+    info: &TraceInfo,
+    metadata: &ReportMetadata,
+    config: &ReportConfig,
+    resolver: &DnsResolver,
+    out: &mut impl Write,
+) -> anyhow::Result<()> {
+    writeln!(
+        out,
+        "traceroute to {} ({}), {} hops max, {} byte packets",
+        metadata.target_hostname,
+        metadata.target_ip(),
+        info.max_ttl,
+        metadata.packet_size
+    )?;
+    out.flush()?;
+    let mut report = ClassicReport::new(CLASSIC_ROUNDS);
+    let mut hostnames = Hostnames::default();
+    loop {
+        let trace = info.data.snapshot();
+        if let Some(err) = trace.error() {
+            return Err(anyhow!("error: {}", err));
+        }
+        report.update(trace);
+        for (ttl, probes) in report.complete_hops() {
+            let addrs = probes.iter().filter_map(|probe| probe.addr);
+            hostnames.resolve_all(addrs, resolver, config);
+            write_hop(ttl, &probes, &hostnames, metadata.target_addr, out)?;
+            out.flush()?;
+        }
+        if report.is_finished() {
+            return Ok(());
+        }
+        sleep(ROUND_POLL_INTERVAL);
+    }
+}
+
+/// The result of a single probe of a hop.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ClassicProbe {
+    addr: Option<IpAddr>,
+    rtt_ms: Option<f64>,
+    icmp_packet_type: Option<IcmpPacketType>,
+}
+
+/// Records the result of every probe of every hop as each round of the trace completes.
+struct ClassicReport {
+    rounds: usize,
+    probes: Vec<Vec<ClassicProbe>>,
+    next_ttl: u8,
+    previous: Option<Arc<Trace>>,
+}
+
+impl ClassicReport {
+    fn new(rounds: usize) -> Self {
+        Self {
+            rounds,
+            probes: Vec::new(),
+            next_ttl: 1,
+            previous: None,
+        }
+    }
+
+    /// Record the results of the latest round of `trace`, if it has not already been recorded.
+    fn update(&mut self, trace: Arc<Trace>) {
+        if trace.round().is_none()
+            || trace.round() == self.previous.as_deref().and_then(Trace::round)
+        {
+            return;
+        }
+        for (hop, addr) in round_responses(self.previous.as_deref(), &trace) {
+            let Some(index) = usize::from(hop.ttl()).checked_sub(1) else {
+                continue;
+            };
+            if self.probes.len() <= index {
+                self.probes.resize(index + 1, Vec::new());
+            }
+            self.probes[index].push(ClassicProbe {
+                addr,
+                rtt_ms: addr.and_then(|_| hop.last_ms()),
+                icmp_packet_type: addr.and_then(|_| hop.last_icmp_packet_type()),
+            });
+        }
+        self.previous = Some(trace);
+    }
+
+    /// Have all rounds been recorded?
+    fn is_finished(&self) -> bool {
+        let recorded = self
+            .previous
+            .as_deref()
+            .and_then(Trace::round)
+            .map_or(0, |round| round + 1);
+        recorded >= self.rounds
+    }
+
+    /// The hops which are now complete and have not yet been returned, in `ttl` order.
+    ///
+    /// A hop is complete once every round has probed it and all lower hops are complete.  Once all rounds have been
+    /// recorded the remaining hops up to the target are complete.
+    fn complete_hops(&mut self) -> Vec<(u8, Vec<ClassicProbe>)> {
+        let hops = self.previous.as_ref().map_or(&[][..], |trace| trace.hops());
+        let target_ttl = hops.last().map_or(0, Hop::ttl);
+        // The hops short of the first traced, such as every hop but one if a fixed ttl is traced, are never probed.
+        if let Some(first) = hops.first() {
+            self.next_ttl = self.next_ttl.max(first.ttl());
+        }
+        let finished = self.is_finished();
+        let mut complete = Vec::new();
+        while self.next_ttl <= target_ttl {
+            let probes = self
+                .probes
+                .get(usize::from(self.next_ttl) - 1)
+                .cloned()
+                .unwrap_or_default();
+            if probes.len() < self.rounds && !finished {
+                break;
+            }
+            complete.push((self.next_ttl, probes));
+            self.next_ttl += 1;
+        }
+        complete
+    }
+}
+
+/// Write the line for a hop, i.e. ` 3  router (10.0.0.1)  1.234 ms *  10.0.0.11 (10.0.0.11)  1.456 ms`.
+fn write_hop(
+    ttl: u8,
+    probes: &[ClassicProbe],
+    hostnames: &Hostnames,
+    target_addr: IpAddr,
+    out: &mut impl Write,
+) -> anyhow::Result<()> {
+    write!(out, "{ttl:2} ")?;
+    let mut last_addr = None;
+    for probe in probes {
+        match (probe.addr, probe.rtt_ms) {
+            (Some(addr), Some(rtt_ms)) => {
+                if last_addr != Some(addr) {
+                    write!(out, " {}", hostnames.format(&addr))?;
+                    last_addr = Some(addr);
+                }
+                write!(out, "  {rtt_ms:.3} ms")?;
+                if let Some(annotation) = annotation(probe.icmp_packet_type, target_addr) {
+                    write!(out, " {annotation}")?;
+                }
+            }
+            _ => write!(out, " *")?,
+        }
+    }
+    writeln!(out)?;
+    Ok(())
+}
+
+/// The `traceroute` annotation for a probe which was answered with an ICMP destination unreachable message.
+///
+/// No annotation is given for port unreachable, which is the expected response from the target for `UDP` probes.
+fn annotation(icmp_packet_type: Option<IcmpPacketType>, target_addr: IpAddr) -> Option<String> {
+    match icmp_packet_type {
+        Some(IcmpPacketType::Unreachable(code)) => {
+            Unreachable::new(target_addr, code, None).annotation()
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::testing::add_round;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    const ROUTER: Option<IpAddr> = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+    const ALTERNATE: Option<IpAddr> = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 11)));
+    const TARGET: Option<IpAddr> = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3)));
+    const TARGET_ADDR: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3));
+    const TIME_EXCEEDED: Option<IcmpPacketType> = Some(IcmpPacketType::TimeExceeded);
+    const ECHO_REPLY: Option<IcmpPacketType> = Some(IcmpPacketType::EchoReply);
+
+    type Round<'a> = &'a [(Option<IpAddr>, u64, Option<IcmpPacketType>)];
+
+    /// Record `rounds` one at a time and render every hop as it completes, returning the rendered hops after each.
+    fn render(rounds: &[Round<'_>], hostnames: &Hostnames) -> Vec<String> {
+        let mut trace = Trace::new(256);
+        let mut report = ClassicReport::new(rounds.len());
+        rounds
+            .iter()
+            .enumerate()
+            .map(|(round, path)| {
+                add_round(&mut trace, round, path);
+                report.update(Arc::new(trace.clone()));
+                let mut out = vec![];
+                for (ttl, probes) in report.complete_hops() {
+                    write_hop(ttl, &probes, hostnames, TARGET_ADDR, &mut out).unwrap();
+                }
+                String::from_utf8(out).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_classic_timeouts() {
+        let rendered = render(
+            &[
+                &[
+                    (ROUTER, 1, TIME_EXCEEDED),
+                    (None, 0, None),
+                    (TARGET, 3, ECHO_REPLY),
+                ],
+                &[(ROUTER, 2, TIME_EXCEEDED), (None, 0, None), (None, 0, None)],
+                &[(None, 0, None), (None, 0, None), (TARGET, 5, ECHO_REPLY)],
+            ],
+            &Hostnames::default(),
+        );
+        assert_eq!(
+            vec![
+                "",
+                "",
+                " 1  10.0.0.1  1.000 ms  2.000 ms *\n \
+                 2  * * *\n \
+                 3  10.0.0.3  3.000 ms *  5.000 ms\n"
+            ],
+            rendered
+        );
+    }
+
+    #[test]
+    fn test_classic_address_change() {
+        let mut hostnames = Hostnames::default();
+        hostnames.0.insert(ROUTER.unwrap(), String::from("router"));
+        let rendered = render(
+            &[
+                &[(ROUTER, 1, TIME_EXCEEDED), (TARGET, 3, ECHO_REPLY)],
+                &[(ALTERNATE, 2, TIME_EXCEEDED), (TARGET, 4, ECHO_REPLY)],
+                &[(ROUTER, 1, TIME_EXCEEDED), (TARGET, 5, ECHO_REPLY)],
+            ],
+            &hostnames,
+        );
+        assert_eq!(
+            " 1  router (10.0.0.1)  1.000 ms 10.0.0.11  2.000 ms router (10.0.0.1)  1.000 ms\n \
+             2  10.0.0.3  3.000 ms  4.000 ms  5.000 ms\n",
+            rendered[2]
+        );
+    }
+
+    #[test]
+    fn test_classic_complete_incrementally() {
+        let mut trace = Trace::new(256);
+        let mut report = ClassicReport::new(2);
+        add_round(&mut trace, 0, &[(ROUTER, 1, TIME_EXCEEDED)]);
+        report.update(Arc::new(trace.clone()));
+        assert!(report.complete_hops().is_empty());
+        report.update(Arc::new(trace.clone()));
+        assert!(!report.is_finished());
+        add_round(
+            &mut trace,
+            1,
+            &[(ROUTER, 1, TIME_EXCEEDED), (TARGET, 2, ECHO_REPLY)],
+        );
+        report.update(Arc::new(trace.clone()));
+        assert!(report.is_finished());
+        let ttls: Vec<_> = report
+            .complete_hops()
+            .into_iter()
+            .map(|(ttl, _)| ttl)
+            .collect();
+        assert_eq!(vec![1, 2], ttls);
+        assert!(report.complete_hops().is_empty());
+    }
+
+    #[test]
+    fn test_classic_fixed_ttl() {
+        use std::time::SystemTime;
+        use trippy_core::{
+            CompletionReason, Probe, ProbeStatus, Round, Sequence, TimeToLive, TracerRound,
+        };
+        let mut trace = Trace::new(256);
+        let mut report = ClassicReport::new(2);
+        for (round, rtt) in [(0, 1), (1, 2)] {
+            let sent = SystemTime::UNIX_EPOCH;
+            let probe = Probe::new(Sequence(33000), TimeToLive(3), Round(round), sent)
+                .with_status(ProbeStatus::Complete)
+                .with_host(ROUTER.unwrap())
+                .with_received(sent + Duration::from_millis(rtt))
+                .with_icmp_packet_type(IcmpPacketType::TimeExceeded);
+            trace.update_from_round(&TracerRound::new(
+                &[probe],
+                TimeToLive(3),
+                CompletionReason::FixedTtlAnswered,
+            ));
+            report.update(Arc::new(trace.clone()));
+        }
+        let mut out = vec![];
+        for (ttl, probes) in report.complete_hops() {
+            write_hop(ttl, &probes, &Hostnames::default(), TARGET_ADDR, &mut out).unwrap();
+        }
+        assert_eq!(
+            " 3  10.0.0.1  1.000 ms  2.000 ms\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_classic_annotation() {
+        let v6 = IpAddr::V6(Ipv6Addr::LOCALHOST);
+        let unreachable = |code| Some(IcmpPacketType::Unreachable(code));
+        assert_eq!(None, annotation(TIME_EXCEEDED, TARGET_ADDR));
+        assert_eq!(None, annotation(unreachable(3), TARGET_ADDR));
+        assert_eq!(
+            Some(String::from("!N")),
+            annotation(unreachable(0), TARGET_ADDR)
+        );
+        assert_eq!(
+            Some(String::from("!H")),
+            annotation(unreachable(1), TARGET_ADDR)
+        );
+        assert_eq!(
+            Some(String::from("!X")),
+            annotation(unreachable(13), TARGET_ADDR)
+        );
+        assert_eq!(
+            Some(String::from("!<11>")),
+            annotation(unreachable(11), TARGET_ADDR)
+        );
+        assert_eq!(None, annotation(unreachable(4), v6));
+        assert_eq!(Some(String::from("!X")), annotation(unreachable(1), v6));
+        assert_eq!(Some(String::from("!H")), annotation(unreachable(3), v6));
+    }
+
+    #[test]
+    fn test_classic_annotated_hop() {
+        let probes = [ClassicProbe {
+            addr: TARGET,
+            rtt_ms: Some(1.5),
+            icmp_packet_type: Some(IcmpPacketType::Unreachable(1)),
+        }];
+        let mut out = vec![];
+        write_hop(7, &probes, &Hostnames::default(), TARGET_ADDR, &mut out).unwrap();
+        assert_eq!(
+            " 7  10.0.0.3  1.500 ms !H\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+}
@@ -0,0 +1,359 @@
+use crate::report::{Report, ReportHop, REPORT_SCHEMA_VERSION};
+use anyhow::anyhow;
+use chrono::SecondsFormat;
+use itertools::{EitherOrBoth, Itertools};
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufReader, Write};
+use std::path::Path;
+
+/// Configuration for comparing two JSON reports.
+#[derive(Debug, Copy, Clone)]
+pub struct DiffConfig {
+    /// The change in average round trip time, in milliseconds, above which a hop has changed.
+    rtt_threshold_ms: f64,
+    /// The change in loss, in percent, above which a hop has changed.
+    loss_threshold_pct: f64,
+}
+
+impl DiffConfig {
+    pub fn new(rtt_threshold_ms: f64, loss_threshold_pct: f64) -> Self {
+        Self {
+            rtt_threshold_ms,
+            loss_threshold_pct,
+        }
+    }
+}
+
+/// Compare the JSON reports at `before` and `after` and write the differences.
+///
+/// Hops are aligned by `ttl`.  Loss is compared as a percentage and so reports of different numbers of rounds may be
+/// compared.  Returns `true` if the average round trip time or loss of any hop increased by more than the configured
+/// thresholds.
+pub fn run_diff(
+    before: &Path,
+    after: &Path,
+    config: &DiffConfig,
+    out: &mut impl Write,
+) -> anyhow::Result<bool> {
+    let before_report = read_report(before)?;
+    let after_report = read_report(after)?;
+    for (path, report) in [(before, &before_report), (after, &after_report)] {
+        if report.info.schema != REPORT_SCHEMA_VERSION {
+            writeln!(
+                out,
+                "warning: {} has schema version {}, expected {}",
+                path.display(),
+                report.info.schema,
+                REPORT_SCHEMA_VERSION
+            )?;
+        }
+    }
+    writeln!(out, "--- {}", describe(before, &before_report))?;
+    writeln!(out, "+++ {}", describe(after, &after_report))?;
+    let changes = diff_reports(&before_report, &after_report, config);
+    if changes.is_empty() {
+        writeln!(out, "no differences")?;
+    }
+    for change in &changes {
+        writeln!(out, "{change}")?;
+    }
+    Ok(changes.iter().any(Change::is_regression))
+}
+
+fn read_report(path: &Path) -> anyhow::Result<Report> {
+    let file =
+        File::open(path).map_err(|e| anyhow!("failed to open report {}: {}", path.display(), e))?;
+    serde_json::from_reader(BufReader::new(file))
+        .map_err(|e| anyhow!("failed to parse report {}: {}", path.display(), e))
+}
+
+/// Describe a report, i.e. `before.json (example.com 10.0.0.3, 10 rounds, 2023-01-01T00:00:00Z)`.
+fn describe(path: &Path, report: &Report) -> String {
+    format!(
+        "{} ({} {}, {} rounds, {})",
+        path.display(),
+        report.info.target.hostname,
+        report.info.target.ip,
+        report.info.rounds,
+        report.info.start.to_rfc3339_opts(SecondsFormat::Secs, true)
+    )
+}
+
+/// A difference between two reports.
+#[derive(Debug, PartialEq)]
+enum Change {
+    /// The number of hops changed.
+    PathLength { before: usize, after: usize },
+    /// The set of addresses which responded for a hop changed.
+    Addrs {
+        ttl: u8,
+        before: Vec<String>,
+        after: Vec<String>,
+    },
+    /// A hop is only present in the later report.
+    Added { ttl: u8, addrs: Vec<String> },
+    /// A hop is only present in the earlier report.
+    Removed { ttl: u8, addrs: Vec<String> },
+    /// The average round trip time of a hop changed by more than the threshold.
+    Rtt { ttl: u8, before: f64, after: f64 },
+    /// The loss of a hop increased by more than the threshold.
+    Loss { ttl: u8, before: f64, after: f64 },
+}
+
+impl Change {
+    /// Is this change a regression, i.e. an increase in latency or loss?
+    fn is_regression(&self) -> bool {
+        match self {
+            Self::Rtt { before, after, .. } => after > before,
+            Self::Loss { .. } => true,
+            Self::PathLength { .. }
+            | Self::Addrs { .. }
+            | Self::Added { .. }
+            | Self::Removed { .. } => false,
+        }
+    }
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PathLength { before, after } => {
+                write!(f, "path length: {before} -> {after} hops")
+            }
+            Self::Addrs { ttl, before, after } => write!(
+                f,
+                "hop {ttl}: addresses changed [{}] -> [{}]",
+                before.join(", "),
+                after.join(", ")
+            ),
+            Self::Added { ttl, addrs } => write!(f, "hop {ttl}: added [{}]", addrs.join(", ")),
+            Self::Removed { ttl, addrs } => write!(f, "hop {ttl}: removed [{}]", addrs.join(", ")),
+            Self::Rtt { ttl, before, after } => {
+                write!(
+                    f,
+                    "hop {ttl}: avg rtt {before:.2} -> {after:.2} ms ({:+.2} ms)",
+                    after - before
+                )?;
+                if self.is_regression() {
+                    write!(f, " REGRESSION")?;
+                }
+                Ok(())
+            }
+            Self::Loss { ttl, before, after } => write!(
+                f,
+                "hop {ttl}: loss {before:.1}% -> {after:.1}% ({:+.1}%) REGRESSION",
+                after - before
+            ),
+        }
+    }
+}
+
+/// The differences between two reports, in `ttl` order.
+fn diff_reports(before: &Report, after: &Report, config: &DiffConfig) -> Vec<Change> {
+    let mut changes = vec![];
+    if before.hops.len() != after.hops.len() {
+        changes.push(Change::PathLength {
+            before: before.hops.len(),
+            after: after.hops.len(),
+        });
+    }
+    let before_hops = before.hops.iter().sorted_by_key(|hop| hop.ttl);
+    let after_hops = after.hops.iter().sorted_by_key(|hop| hop.ttl);
+    for pair in before_hops.merge_join_by(after_hops, |before, after| before.ttl.cmp(&after.ttl)) {
+        match pair {
+            EitherOrBoth::Both(before, after) => diff_hops(before, after, config, &mut changes),
+            EitherOrBoth::Left(before) => changes.push(Change::Removed {
+                ttl: before.ttl,
+                addrs: hop_addrs(before),
+            }),
+            EitherOrBoth::Right(after) => changes.push(Change::Added {
+                ttl: after.ttl,
+                addrs: hop_addrs(after),
+            }),
+        }
+    }
+    changes
+}
+
+/// The differences between two hops with the same `ttl`.
+fn diff_hops(
+    before: &ReportHop,
+    after: &ReportHop,
+    config: &DiffConfig,
+    changes: &mut Vec<Change>,
+) {
+    let ttl = before.ttl;
+    let (before_addrs, after_addrs) = (hop_addrs(before), hop_addrs(after));
+    if before_addrs != after_addrs {
+        changes.push(Change::Addrs {
+            ttl,
+            before: before_addrs,
+            after: after_addrs,
+        });
+    }
+    // The average is meaningless for a hop which never responded.
+    let responded = before.recv > 0 && after.recv > 0;
+    if responded && (after.avg - before.avg).abs() > config.rtt_threshold_ms {
+        changes.push(Change::Rtt {
+            ttl,
+            before: before.avg,
+            after: after.avg,
+        });
+    }
+    if after.loss_pct - before.loss_pct > config.loss_threshold_pct {
+        changes.push(Change::Loss {
+            ttl,
+            before: before.loss_pct,
+            after: after.loss_pct,
+        });
+    }
+}
+
+/// The sorted addresses of a hop.
+fn hop_addrs(hop: &ReportHop) -> Vec<String> {
+    hop.addrs
+        .iter()
+        .map(|addr| addr.ip.clone())
+        .sorted()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::testing::make_trace;
+    use crate::report::{report_hops, Host, Hostnames, ReportInfo};
+    use chrono::{TimeZone, Utc};
+    use std::net::{IpAddr, Ipv4Addr};
+
+    const ROUTER: Option<IpAddr> = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+    const ALTERNATE: Option<IpAddr> = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 11)));
+    const MIDDLE: Option<IpAddr> = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)));
+    const TARGET: Option<IpAddr> = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3)));
+
+    fn make_report(rounds: &[&[(Option<IpAddr>, u64)]]) -> Report {
+        let trace = make_trace(rounds);
+        Report {
+            info: ReportInfo {
+                version: String::from("0.7.0-dev"),
+                schema: REPORT_SCHEMA_VERSION,
+                start: Utc.timestamp_opt(1_672_531_200, 0).unwrap(),
+                end: Utc.timestamp_opt(1_672_531_210, 0).unwrap(),
+                target: Host {
+                    ip: String::from("10.0.0.3"),
+                    hostname: String::from("example.com"),
+                },
+                target_source: None,
+                source: String::from("10.0.0.1"),
+                local_hostname: String::from("myhost"),
+                interface: None,
+                fwmark: None,
+                protocol: String::from("icmp"),
+                packet_size: 84,
+                rng_seed: None,
+                rounds: rounds.len(),
+                clock_anomalies: 0,
+                invalid_ttls: 0,
+                channel_metrics: None,
+                path_stability: None,
+                converged_round: None,
+                stopped_by: None,
+                flow_key: None,
+            },
+            hops: report_hops(&trace, &Hostnames::default(), false, false),
+            targets: vec![],
+        }
+    }
+
+    fn diff(before: &Report, after: &Report) -> Vec<Change> {
+        diff_reports(before, after, &DiffConfig::new(10_f64, 5_f64))
+    }
+
+    #[test]
+    fn test_diff_unchanged() {
+        let report = make_report(&[&[(ROUTER, 10), (TARGET, 20)]]);
+        assert!(diff(&report, &report).is_empty());
+    }
+
+    #[test]
+    fn test_diff_addrs_changed() {
+        let before = make_report(&[&[(ROUTER, 10), (TARGET, 20)]]);
+        let after = make_report(&[
+            &[(ALTERNATE, 10), (TARGET, 20)],
+            &[(ROUTER, 10), (TARGET, 20)],
+        ]);
+        let changes = diff(&before, &after);
+        assert_eq!(
+            vec![Change::Addrs {
+                ttl: 1,
+                before: vec![String::from("10.0.0.1")],
+                after: vec![String::from("10.0.0.1"), String::from("10.0.0.11")],
+            }],
+            changes
+        );
+        assert!(!changes.iter().any(Change::is_regression));
+    }
+
+    #[test]
+    fn test_diff_tail_hops() {
+        let short = make_report(&[&[(ROUTER, 10), (TARGET, 20)]]);
+        let long = make_report(&[&[(ROUTER, 10), (MIDDLE, 15), (TARGET, 20)]]);
+        let added = diff(&short, &long);
+        assert_eq!(
+            Change::PathLength {
+                before: 2,
+                after: 3
+            },
+            added[0]
+        );
+        assert!(added.contains(&Change::Added {
+            ttl: 3,
+            addrs: vec![String::from("10.0.0.3")]
+        }));
+        let removed = diff(&long, &short);
+        assert!(removed.contains(&Change::Removed {
+            ttl: 3,
+            addrs: vec![String::from("10.0.0.3")]
+        }));
+        assert!(!removed.iter().any(Change::is_regression));
+    }
+
+    #[test]
+    fn test_diff_latency_regression() {
+        let before = make_report(&[&[(ROUTER, 10), (TARGET, 20)]]);
+        let after = make_report(&[&[(ROUTER, 15), (TARGET, 45)]]);
+        let changes = diff(&before, &after);
+        assert_eq!(
+            vec![Change::Rtt {
+                ttl: 2,
+                before: 20_f64,
+                after: 45_f64
+            }],
+            changes
+        );
+        assert!(changes[0].is_regression());
+        assert_eq!(
+            "hop 2: avg rtt 20.00 -> 45.00 ms (+25.00 ms) REGRESSION",
+            changes[0].to_string()
+        );
+        let improved = diff(&after, &before);
+        assert!(!improved[0].is_regression());
+    }
+
+    #[test]
+    fn test_diff_loss_regression() {
+        let before = make_report(&[&[(ROUTER, 10), (TARGET, 20)], &[(ROUTER, 10), (TARGET, 20)]]);
+        let after = make_report(&[&[(ROUTER, 10), (TARGET, 20)], &[(ROUTER, 10), (None, 0)]]);
+        let changes = diff(&before, &after);
+        assert_eq!(
+            vec![Change::Loss {
+                ttl: 2,
+                before: 0_f64,
+                after: 50_f64
+            }],
+            changes
+        );
+        assert!(changes[0].is_regression());
+    }
+}
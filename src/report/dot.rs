@@ -0,0 +1,164 @@
+use crate::backend::{Hop, HopLink, Trace};
+use crate::report::{wait_for_round, Hostnames, ReportConfig, ReportMetadata};
+use crate::{DnsResolver, TraceInfo};
+use itertools::Itertools;
+use std::io::Write;
+use std::net::IpAddr;
+
+/// Generate a Graphviz `dot` digraph of the traced path.
+///
+/// Every unique `(ttl, address)` is a node and every link observed between the hosts of consecutive hops is an edge,
+/// weighted by the number of rounds in which it was observed.  Hops which did not respond are shown as placeholder
+/// nodes.
+pub fn run_report_dot(
+    info: &TraceInfo,
+    metadata: &ReportMetadata,
+    config: &ReportConfig,
+    resolver: &DnsResolver,
+    out: &mut impl Write,
+) -> anyhow::Result<()> {
+    let trace = wait_for_round(info, config)?;
+    let hostnames = Hostnames::lookup(&trace, resolver, config);
+    write_dot(metadata, &trace, &hostnames, out)
+}
+
+fn write_dot(
+    metadata: &ReportMetadata,
+    trace: &Trace,
+    hostnames: &Hostnames,
+    out: &mut impl Write,
+) -> anyhow::Result<()> {
+    writeln!(out, "digraph trippy {{")?;
+    writeln!(
+        out,
+        "    label=\"{} ({})\";",
+        escape(&metadata.target_hostname),
+        metadata.target_ip()
+    )?;
+    writeln!(out, "    node [shape=box];")?;
+    for hop in trace.hops() {
+        let ttl = hop.ttl();
+        for (addr, details) in hop
+            .addrs_with_details()
+            .sorted_unstable_by_key(|(addr, _)| **addr)
+        {
+            let label = match hostnames.get(addr) {
+                Some(hostname) => format!("{}\\n{}", addr, escape(hostname)),
+                None => addr.to_string(),
+            };
+            let style = if *addr == metadata.target_addr {
+                ", style=bold, peripheries=2"
+            } else {
+                ""
+            };
+            writeln!(
+                out,
+                "    {} [label=\"{}\\n{:.1} ms\"{}];",
+                node_id(ttl, Some(*addr)),
+                label,
+                details.avg_ms(),
+                style
+            )?;
+        }
+    }
+    let links: Vec<(&HopLink, &usize)> = trace
+        .links()
+        .sorted_unstable_by_key(|(link, _)| (link.ttl, link.from, link.to))
+        .collect();
+    let unresponsive = trace
+        .hops()
+        .iter()
+        .filter(|hop| hop.addr_count() == 0)
+        .map(Hop::ttl)
+        .chain(links.iter().filter_map(|(link, _)| match link {
+            HopLink {
+                ttl, from: None, ..
+            } => Some(*ttl),
+            HopLink { ttl, to: None, .. } => Some(*ttl + 1),
+            _ => None,
+        }))
+        .sorted_unstable()
+        .dedup();
+    for ttl in unresponsive {
+        writeln!(
+            out,
+            "    {} [label=\"{}: ???\", style=dashed];",
+            node_id(ttl, None),
+            ttl
+        )?;
+    }
+    let max_count = links.iter().map(|(_, &count)| count).max().unwrap_or(1);
+    for (link, &count) in links {
+        writeln!(
+            out,
+            "    {} -> {} [label=\"{}\", weight={}, penwidth={:.1}];",
+            node_id(link.ttl, link.from),
+            node_id(link.ttl + 1, link.to),
+            count,
+            count,
+            1_f64 + 3_f64 * count as f64 / max_count as f64
+        )?;
+    }
+    writeln!(out, "}}")?;
+    Ok(())
+}
+
+/// The `dot` identifier of the node for `addr` at `ttl`, or for the placeholder of an unresponsive `ttl`.
+fn node_id(ttl: u8, addr: Option<IpAddr>) -> String {
+    match addr {
+        Some(addr) => format!("\"{ttl}:{addr}\""),
+        None => format!("\"{ttl}:???\""),
+    }
+}
+
+/// Escape a `dot` quoted string.
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::testing::make_trace;
+    use crate::report::tests::make_metadata;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_dot_multipath() {
+        let a = IpAddr::from([10, 0, 0, 1]);
+        let b = IpAddr::from([10, 0, 1, 1]);
+        let c = IpAddr::from([10, 0, 2, 1]);
+        let t = IpAddr::from([10, 0, 3, 1]);
+        let trace = make_trace(&[
+            &[(Some(a), 1), (Some(b), 5), (Some(t), 10)],
+            &[(Some(a), 3), (Some(c), 7), (Some(t), 10)],
+            &[(Some(a), 2), (None, 0), (Some(t), 10)],
+            &[(Some(a), 2), (Some(b), 5), (Some(t), 10)],
+        ]);
+        let hostnames = Hostnames(HashMap::from([(a, String::from("gateway \"1\""))]));
+        let mut out = Vec::new();
+        write_dot(&make_metadata(t, None), &trace, &hostnames, &mut out).unwrap();
+        let expected = r#"digraph trippy {
+    label="example.com (10.0.3.1)";
+    node [shape=box];
+    "1:10.0.0.1" [label="10.0.0.1\ngateway \"1\"\n2.0 ms"];
+    "2:10.0.1.1" [label="10.0.1.1\n5.0 ms"];
+    "2:10.0.2.1" [label="10.0.2.1\n7.0 ms"];
+    "3:10.0.3.1" [label="10.0.3.1\n10.0 ms", style=bold, peripheries=2];
+    "2:???" [label="2: ???", style=dashed];
+    "1:10.0.0.1" -> "2:???" [label="1", weight=1, penwidth=2.5];
+    "1:10.0.0.1" -> "2:10.0.1.1" [label="2", weight=2, penwidth=4.0];
+    "1:10.0.0.1" -> "2:10.0.2.1" [label="1", weight=1, penwidth=2.5];
+    "2:???" -> "3:10.0.3.1" [label="1", weight=1, penwidth=2.5];
+    "2:10.0.1.1" -> "3:10.0.3.1" [label="2", weight=2, penwidth=4.0];
+    "2:10.0.2.1" -> "3:10.0.3.1" [label="1", weight=1, penwidth=2.5];
+}
+"#;
+        assert_eq!(expected, String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!("a\\\\b\\\"c", escape("a\\b\"c"));
+    }
+}
@@ -0,0 +1,184 @@
+use crate::backend::Trace;
+use crate::config::InfluxPrecision;
+use crate::signal::Interrupt;
+use crate::TraceInfo;
+use anyhow::anyhow;
+use std::fmt::Write as _;
+use std::io::Write;
+use std::thread::sleep;
+use std::time::{Duration, SystemTime};
+
+/// The interval at which the trace is polled for newly completed rounds.
+const ROUND_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Configuration for the influx line protocol report.
+#[derive(Debug, Clone)]
+pub struct InfluxConfig {
+    /// The measurement name.
+    measurement: String,
+    /// Additional static tags added to every line.
+    tags: Vec<(String, String)>,
+    /// The precision of the timestamp.
+    precision: InfluxPrecision,
+}
+
+impl InfluxConfig {
+    pub fn new(
+        measurement: String,
+        tags: Vec<(String, String)>,
+        precision: InfluxPrecision,
+    ) -> Self {
+        Self {
+            measurement,
+            tags,
+            precision,
+        }
+    }
+}
+
+/// Write influx line protocol for every hop after each round until interrupted.
+///
+/// Each batch of lines is written for a completed round and is flushed before the next round is awaited.  An
+/// interruption takes effect between rounds and so the batch of the current round is always written in full.
+pub fn run_report_influx(
+    info: &TraceInfo,
+    config: &InfluxConfig,
+    interrupt: &Interrupt,
+    out: &mut impl Write,
+) -> anyhow::Result<()> {
+    let mut last_round = None;
+    while !interrupt.is_set() {
+        let trace = info.data.snapshot();
+        if let Some(err) = trace.error() {
+            return Err(anyhow!("error: {}", err));
+        }
+        if trace.round().is_some() && trace.round() != last_round {
+            last_round = trace.round();
+            let batch = render_lines(&info.target_hostname, &trace, config, SystemTime::now());
+            out.write_all(batch.as_bytes())?;
+            out.flush()?;
+        }
+        sleep(ROUND_POLL_INTERVAL);
+    }
+    Ok(())
+}
+
+/// Render a line for every hop of the trace.
+///
+/// Lines are tagged with the address which responded most frequently for the hop, the `addr` tag and the latency fields
+/// are omitted for hops which have not responded.
+fn render_lines(
+    target: &str,
+    trace: &Trace,
+    config: &InfluxConfig,
+    timestamp: SystemTime,
+) -> String {
+    let timestamp = format_timestamp(timestamp, config.precision);
+    let mut out = String::new();
+    for hop in trace.hops() {
+        let mut tags = vec![
+            (String::from("target"), target.to_string()),
+            (String::from("ttl"), hop.ttl().to_string()),
+        ];
+        if let Some(addr) = hop.primary_addr() {
+            tags.push((String::from("addr"), addr.to_string()));
+        }
+        tags.extend(config.tags.iter().cloned());
+        let mut fields = vec![];
+        if let Some(last) = hop.last_ms() {
+            fields.push(format!("last_ms={last}"));
+        }
+        if hop.total_recv() > 0 {
+            fields.push(format!("avg_ms={}", hop.avg_ms()));
+        }
+        fields.push(format!("loss={}", hop.loss_pct() / 100_f64));
+        fields.push(format!("sent={}i", hop.total_sent()));
+        fields.push(format!("recv={}i", hop.total_recv()));
+        out.push_str(&escape_measurement(&config.measurement));
+        for (key, value) in &tags {
+            write!(out, ",{}={}", escape_tag(key), escape_tag(value)).unwrap_or_default();
+        }
+        writeln!(out, " {} {}", fields.join(","), timestamp).unwrap_or_default();
+    }
+    out
+}
+
+/// Format `timestamp` as an integer count of `precision` units since the Unix epoch.
+fn format_timestamp(timestamp: SystemTime, precision: InfluxPrecision) -> u128 {
+    let since_epoch = timestamp
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    match precision {
+        InfluxPrecision::Ns => since_epoch.as_nanos(),
+        InfluxPrecision::Us => since_epoch.as_micros(),
+        InfluxPrecision::Ms => since_epoch.as_millis(),
+        InfluxPrecision::S => u128::from(since_epoch.as_secs()),
+    }
+}
+
+/// Escape a measurement name, commas and spaces must be escaped.
+fn escape_measurement(s: &str) -> String {
+    s.replace(',', "\\,").replace(' ', "\\ ")
+}
+
+/// Escape a tag key or value, commas, equals signs and spaces must be escaped.
+fn escape_tag(s: &str) -> String {
+    s.replace(',', "\\,")
+        .replace('=', "\\=")
+        .replace(' ', "\\ ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::testing::make_trace;
+    use std::net::IpAddr;
+
+    #[test]
+    fn test_render_lines() {
+        let a = IpAddr::from([10, 0, 0, 1]);
+        let trace = make_trace(&[&[(Some(a), 10), (None, 0)], &[(Some(a), 20), (None, 0)]]);
+        let config = InfluxConfig::new(
+            String::from("trippy"),
+            vec![(String::from("site"), String::from("dc 1"))],
+            InfluxPrecision::Ms,
+        );
+        let timestamp = SystemTime::UNIX_EPOCH + Duration::from_millis(1_672_531_200_123);
+        let lines = render_lines("example.com", &trace, &config, timestamp);
+        assert_eq!(
+            "trippy,target=example.com,ttl=1,addr=10.0.0.1,site=dc\\ 1 last_ms=20,avg_ms=15,loss=0,sent=2i,recv=2i 1672531200123\n\
+             trippy,target=example.com,ttl=2,site=dc\\ 1 loss=1,sent=2i,recv=0i 1672531200123\n",
+            lines
+        );
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        let timestamp = SystemTime::UNIX_EPOCH + Duration::from_nanos(1_672_531_200_123_456_789);
+        assert_eq!(
+            1_672_531_200_123_456_789,
+            format_timestamp(timestamp, InfluxPrecision::Ns)
+        );
+        assert_eq!(
+            1_672_531_200_123_456,
+            format_timestamp(timestamp, InfluxPrecision::Us)
+        );
+        assert_eq!(
+            1_672_531_200_123,
+            format_timestamp(timestamp, InfluxPrecision::Ms)
+        );
+        assert_eq!(
+            1_672_531_200,
+            format_timestamp(timestamp, InfluxPrecision::S)
+        );
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            "my\\,measurement\\ name",
+            escape_measurement("my,measurement name")
+        );
+        assert_eq!("host\\ a\\,b\\=c", escape_tag("host a,b=c"));
+    }
+}
@@ -0,0 +1,169 @@
+use crate::backend::{Hop, Trace};
+use crate::report::{wait_for_round, Hostnames, ReportConfig, ReportMetadata};
+use crate::{DnsResolver, TraceInfo};
+use chrono::{DateTime, FixedOffset, Local};
+use itertools::Itertools;
+use std::cmp::Reverse;
+use std::io::Write;
+use std::net::IpAddr;
+
+/// The width of the host column of `mtr`, including the `HOST: ` and ` N.|-- ` prefixes.
+const MTR_HOST_WIDTH: usize = 33;
+
+/// The headers and widths of the default `mtr` report data columns, which includes a blank separator column.
+const MTR_FIELDS: [(&str, usize); 8] = [
+    ("Loss%", 6),
+    ("Snt", 6),
+    ("", 1),
+    ("Last", 6),
+    ("Avg", 6),
+    ("Best", 6),
+    ("Wrst", 6),
+    ("StDev", 6),
+];
+
+/// Generate a report in the format of `mtr --report`.
+///
+/// The column widths and number formatting match those of `mtr` such that tools which parse `mtr` reports can parse
+/// this report.  As with `mtr`, hostnames which do not fit in the host column are truncated, as are values which do
+/// not fit in their column (such that a loss of 100% is shown as `100.0`).
+pub fn run_report_mtr(
+    info: &TraceInfo,
+    metadata: &ReportMetadata,
+    config: &ReportConfig,
+    resolver: &DnsResolver,
+    out: &mut impl Write,
+) -> anyhow::Result<()> {
+    let start = Local::now();
+    let trace = wait_for_round(info, config)?;
+    let hostnames = Hostnames::lookup(&trace, resolver, config);
+    write_mtr(
+        &start.into(),
+        &metadata.local_hostname,
+        &trace,
+        &hostnames,
+        out,
+    )
+}
+
+fn write_mtr(
+    start: &DateTime<FixedOffset>,
+    local_hostname: &str,
+    trace: &Trace,
+    hostnames: &Hostnames,
+    out: &mut impl Write,
+) -> anyhow::Result<()> {
+    writeln!(out, "Start: {}", start.format("%Y-%m-%dT%H:%M:%S%z"))?;
+    let headers = MTR_FIELDS.map(|(header, width)| format!("{header:>width$}"));
+    writeln!(
+        out,
+        "{}{}",
+        host_column(&format!("HOST: {local_hostname}")),
+        fields(&headers)
+    )?;
+    for hop in trace.hops() {
+        let mut addrs = hop
+            .addrs_with_counts()
+            .sorted_by_key(|(addr, count)| (Reverse(**count), **addr))
+            .map(|(addr, _)| host_name(addr, hostnames));
+        let name = addrs.next().unwrap_or_else(|| String::from("???"));
+        writeln!(
+            out,
+            "{}{}",
+            host_column(&format!(" {:2}.|-- {}", hop.ttl(), name)),
+            fields(&hop_fields(hop))
+        )?;
+        for name in addrs {
+            writeln!(out, "    |  `|-- {name}")?;
+        }
+    }
+    Ok(())
+}
+
+/// Pad, or truncate, to the width of the host column.
+fn host_column(text: &str) -> String {
+    let text: String = text.chars().take(MTR_HOST_WIDTH).collect();
+    format!("{text:<MTR_HOST_WIDTH$}")
+}
+
+/// Join the data columns, truncating each to the width of its column.
+fn fields(values: &[String; 8]) -> String {
+    MTR_FIELDS
+        .iter()
+        .zip(values)
+        .map(|((_, width), value)| value.chars().take(*width).collect::<String>())
+        .join("")
+}
+
+/// The name of an address, the hostname if known or the address otherwise.
+fn host_name(addr: &IpAddr, hostnames: &Hostnames) -> String {
+    hostnames
+        .get(addr)
+        .map_or_else(|| addr.to_string(), String::from)
+}
+
+/// Format the data columns of a hop as `mtr` does, i.e. ` %4.1f%%` for the loss, ` %5d` for the number sent and
+/// ` %5.1f` for the latencies.
+fn hop_fields(hop: &Hop) -> [String; 8] {
+    [
+        format!(" {:4.1}%", hop.loss_pct()),
+        format!(" {:5}", hop.total_sent()),
+        String::from(" "),
+        format!(" {:5.1}", hop.last_ms().unwrap_or_default()),
+        format!(" {:5.1}", hop.avg_ms()),
+        format!(" {:5.1}", hop.best_ms().unwrap_or_default()),
+        format!(" {:5.1}", hop.worst_ms().unwrap_or_default()),
+        format!(" {:5.1}", hop.stddev_ms()),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::testing::make_trace;
+    use chrono::TimeZone;
+    use std::net::Ipv4Addr;
+
+    const GOLDEN_MTR_REPORT: &str = include_str!("../../tests/resources/mtr_report.txt");
+
+    #[test]
+    fn test_mtr_report_golden() {
+        let router = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+        let alternate = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 11)));
+        let target = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3)));
+        let trace = make_trace(&[
+            &[(router, 10), (None, 0), (target, 30)],
+            &[(alternate, 14), (None, 0), (target, 34)],
+            &[(router, 12), (None, 0), (None, 0)],
+            &[(router, 120), (None, 0), (target, 138)],
+        ]);
+        let mut hostnames = Hostnames::default();
+        hostnames.0.insert(
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+            String::from("router.example.com"),
+        );
+        hostnames.0.insert(
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3)),
+            String::from("a-very-long-hostname.example.com"),
+        );
+        let start = FixedOffset::east_opt(3600)
+            .unwrap()
+            .timestamp_opt(1_672_531_200, 0)
+            .unwrap();
+        let mut out = vec![];
+        write_mtr(&start, "myhost", &trace, &hostnames, &mut out).unwrap();
+        assert_eq!(GOLDEN_MTR_REPORT, String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn test_mtr_host_column() {
+        assert_eq!(
+            "HOST: myhost                     ",
+            host_column("HOST: myhost")
+        );
+        assert_eq!(
+            "  1.|-- a-very-long-hostname.exam",
+            host_column("  1.|-- a-very-long-hostname.example.com")
+        );
+    }
+}
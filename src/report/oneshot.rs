@@ -0,0 +1,185 @@
+use crate::backend::{Hop, Trace};
+use crate::report::{fixed_width, Host, ReportMetadata};
+use crate::TraceInfo;
+use anyhow::anyhow;
+use serde::Serialize;
+use std::io::Write;
+use std::net::IpAddr;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// The interval at which the trace is polled for the completion of its round.
+const ROUND_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Generate a single line `json` report of the first round of a trace, returning whether the target responded.
+///
+/// The round ends once the target responds, or else after the maximum round duration, but should it not have ended
+/// after `timeout` the report is of the hops of no round, and so the target is not reached.
+///
+/// The fields of every hop are a subset of those of the hops of the `json` report, such that they may be parsed alike.
+pub fn run_report_oneshot_json(
+    info: &TraceInfo,
+    metadata: &ReportMetadata,
+    timeout: Duration,
+    out: &mut impl Write,
+) -> anyhow::Result<bool> {
+    let start = Instant::now();
+    let mut trace = info.data.snapshot();
+    while trace.round().is_none() && start.elapsed() < timeout {
+        sleep(ROUND_POLL_INTERVAL.min(timeout.saturating_sub(start.elapsed())));
+        trace = info.data.snapshot();
+        if let Some(err) = trace.error() {
+            return Err(anyhow!("error: {}", err));
+        }
+    }
+    let report = OneshotReport::new(metadata, &trace);
+    serde_json::to_writer(&mut *out, &report)?;
+    writeln!(out)?;
+    Ok(report.reached)
+}
+
+/// The single line `json` report of a round.
+#[derive(Debug, PartialEq, Serialize)]
+struct OneshotReport {
+    target: Host,
+    /// Whether the target responded in the round.
+    reached: bool,
+    hops: Vec<OneshotHop>,
+}
+
+impl OneshotReport {
+    fn new(metadata: &ReportMetadata, trace: &Trace) -> Self {
+        let hops: Vec<_> = if trace.round().is_some() {
+            trace.hops().iter().map(OneshotHop::new).collect()
+        } else {
+            vec![]
+        };
+        let reached = hops.iter().any(|hop| hop.is_from(metadata.target_addr));
+        Self {
+            target: Host {
+                ip: metadata.target_ip(),
+                hostname: metadata.target_hostname.clone(),
+            },
+            reached,
+            hops,
+        }
+    }
+}
+
+/// A hop of a round, which has no addresses, and a `recv` of zero, if it did not respond.
+#[derive(Debug, PartialEq, Serialize)]
+struct OneshotHop {
+    ttl: u8,
+    addrs: Vec<OneshotHopAddr>,
+    sent: usize,
+    recv: usize,
+    #[serde(serialize_with = "fixed_width")]
+    last: f64,
+}
+
+impl OneshotHop {
+    fn new(hop: &Hop) -> Self {
+        Self {
+            ttl: hop.ttl(),
+            addrs: hop
+                .addrs()
+                .map(|addr| OneshotHopAddr {
+                    ip: addr.to_string(),
+                })
+                .collect(),
+            sent: hop.total_sent(),
+            recv: hop.total_recv(),
+            last: hop.last_ms().unwrap_or_default(),
+        }
+    }
+
+    fn is_from(&self, addr: IpAddr) -> bool {
+        self.addrs
+            .iter()
+            .any(|hop_addr| hop_addr.ip == addr.to_string())
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct OneshotHopAddr {
+    ip: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::testing::{make_trace, make_trace_info};
+    use crate::report::tests::make_metadata;
+    use std::collections::BTreeSet;
+    use std::net::Ipv4Addr;
+
+    const TARGET: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3));
+
+    fn run(trace: Trace, timeout: Duration) -> (bool, String) {
+        let mut out = Vec::new();
+        let reached = run_report_oneshot_json(
+            &make_trace_info(trace),
+            &make_metadata(TARGET, None),
+            timeout,
+            &mut out,
+        )
+        .unwrap();
+        (reached, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_oneshot_reachable() {
+        let router = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+        let trace = make_trace(&[&[(router, 10), (None, 0), (Some(TARGET), 30)]]);
+        let (reached, report) = run(trace, Duration::from_secs(1));
+        assert!(reached);
+        assert_eq!(
+            r#"{"target":{"ip":"10.0.0.3","hostname":"example.com"},"reached":true,"hops":[{"ttl":1,"addrs":[{"ip":"10.0.0.1"}],"sent":1,"recv":1,"last":"10.00"},{"ttl":2,"addrs":[],"sent":1,"recv":0,"last":"0.00"},{"ttl":3,"addrs":[{"ip":"10.0.0.3"}],"sent":1,"recv":1,"last":"30.00"}]}
+"#,
+            report
+        );
+    }
+
+    #[test]
+    fn test_oneshot_unreachable() {
+        let router = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+        let trace = make_trace(&[&[(router, 10), (None, 0), (None, 0)]]);
+        let (reached, report) = run(trace, Duration::from_secs(1));
+        assert!(!reached);
+        assert!(report.contains(r#""reached":false"#));
+        assert!(report.contains(r#"{"ttl":3,"addrs":[],"sent":1,"recv":0,"last":"0.00"}"#));
+    }
+
+    #[test]
+    fn test_oneshot_timeout() {
+        let start = Instant::now();
+        let (reached, report) = run(Trace::new(256), Duration::from_millis(100));
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert!(!reached);
+        assert_eq!(
+            r#"{"target":{"ip":"10.0.0.3","hostname":"example.com"},"reached":false,"hops":[]}
+"#,
+            report
+        );
+    }
+
+    /// The fields of a hop of the report are a subset of those of a hop of the `json` report.
+    #[test]
+    fn test_oneshot_hop_fields() {
+        let trace = make_trace(&[&[(Some(TARGET), 30)]]);
+        let keys = |value: &serde_json::Value| -> BTreeSet<String> {
+            value.as_object().unwrap().keys().cloned().collect()
+        };
+        let full = serde_json::to_value(crate::report::tests::make_report()).unwrap();
+        let oneshot =
+            serde_json::to_value(OneshotReport::new(&make_metadata(TARGET, None), &trace)).unwrap();
+        let full_hop = &full["hops"][0];
+        let oneshot_hop = &oneshot["hops"][0];
+        assert!(keys(oneshot_hop).is_subset(&keys(full_hop)));
+        assert!(keys(&oneshot_hop["addrs"][0]).is_subset(&keys(&full_hop["addrs"][0])));
+        assert_eq!(
+            full_hop["last"].is_string(),
+            oneshot_hop["last"].is_string()
+        );
+    }
+}
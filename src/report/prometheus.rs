@@ -0,0 +1,252 @@
+use crate::backend::{Hop, Trace};
+use crate::signal::Interrupt;
+use crate::TraceInfo;
+use anyhow::anyhow;
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread::sleep;
+use std::time::Duration;
+
+/// The interval at which the listener is polled for new connections and interruption.
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The maximum time to wait for a client to send a request.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A metric exported for every hop.
+struct Metric {
+    name: &'static str,
+    kind: &'static str,
+    help: &'static str,
+    value: fn(&Hop) -> Option<f64>,
+}
+
+/// The metrics exported for every hop.
+const METRICS: [Metric; 5] = [
+    Metric {
+        name: "trippy_hop_sent_total",
+        kind: "counter",
+        help: "The number of probes sent for the hop",
+        value: |hop| Some(hop.total_sent() as f64),
+    },
+    Metric {
+        name: "trippy_hop_recv_total",
+        kind: "counter",
+        help: "The number of probe responses received for the hop",
+        value: |hop| Some(hop.total_recv() as f64),
+    },
+    Metric {
+        name: "trippy_hop_last_rtt_seconds",
+        kind: "gauge",
+        help: "The round trip time of the last probe response for the hop",
+        value: |hop| hop.last_ms().map(|rtt| rtt / 1000_f64),
+    },
+    Metric {
+        name: "trippy_hop_best_rtt_seconds",
+        kind: "gauge",
+        help: "The best round trip time observed for the hop",
+        value: |hop| hop.best_ms().map(|rtt| rtt / 1000_f64),
+    },
+    Metric {
+        name: "trippy_hop_loss_ratio",
+        kind: "gauge",
+        help: "The ratio of probes sent for the hop for which no response was received",
+        value: |hop| Some(hop.loss_pct() / 100_f64),
+    },
+];
+
+/// Serve Prometheus metrics for all traces on `/metrics` until interrupted.
+pub fn run_report_prometheus(
+    traces: &[TraceInfo],
+    listen: SocketAddr,
+    interrupt: &Interrupt,
+) -> anyhow::Result<()> {
+    let listener =
+        TcpListener::bind(listen).map_err(|e| anyhow!("failed to listen on {} ({})", listen, e))?;
+    serve(&listener, traces, interrupt)
+}
+
+/// Handle requests on `listener` until `interrupt` is set or a trace fails.
+fn serve(
+    listener: &TcpListener,
+    traces: &[TraceInfo],
+    interrupt: &Interrupt,
+) -> anyhow::Result<()> {
+    listener.set_nonblocking(true)?;
+    while !interrupt.is_set() {
+        if let Some(err) = traces
+            .iter()
+            .find_map(|info| info.data.snapshot().error().map(String::from))
+        {
+            return Err(anyhow!("error: {}", err));
+        }
+        match listener.accept() {
+            Ok((stream, _)) => {
+                if let Err(err) = handle_request(stream, traces) {
+                    eprintln!("failed to handle metrics request: {err}");
+                }
+            }
+            Err(err) if err.kind() == ErrorKind::WouldBlock => sleep(ACCEPT_POLL_INTERVAL),
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(())
+}
+
+/// Respond to a single HTTP request.
+fn handle_request(stream: TcpStream, traces: &[TraceInfo]) -> anyhow::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+    let mut stream = reader.into_inner();
+    match request_line
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["GET", "/metrics", ..] => {
+            let body = traces
+                .iter()
+                .map(|info| (info.target_hostname.as_str(), info.data.snapshot()))
+                .collect::<Vec<_>>();
+            respond(&mut stream, "200 OK", &render_metrics(&body))
+        }
+        ["GET", ..] => respond(&mut stream, "404 Not Found", "not found\n"),
+        _ => respond(
+            &mut stream,
+            "405 Method Not Allowed",
+            "method not allowed\n",
+        ),
+    }
+}
+
+fn respond(stream: &mut TcpStream, status: &str, body: &str) -> anyhow::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// Render the metrics for all hops of all traces in the Prometheus text exposition format.
+///
+/// Each hop is labelled with the address which responded most frequently, or an empty `addr` for hops which have not
+/// responded.
+fn render_metrics(traces: &[(&str, Arc<Trace>)]) -> String {
+    let mut out = String::new();
+    for metric in &METRICS {
+        writeln!(out, "# HELP {} {}", metric.name, metric.help).unwrap_or_default();
+        writeln!(out, "# TYPE {} {}", metric.name, metric.kind).unwrap_or_default();
+        for (target, trace) in traces {
+            for hop in trace.hops() {
+                let addr = hop
+                    .primary_addr()
+                    .map(|addr| addr.to_string())
+                    .unwrap_or_default();
+                if let Some(value) = (metric.value)(hop) {
+                    writeln!(
+                        out,
+                        "{}{{target=\"{}\",ttl=\"{}\",addr=\"{}\"}} {}",
+                        metric.name,
+                        escape_label(target),
+                        hop.ttl(),
+                        addr,
+                        value
+                    )
+                    .unwrap_or_default();
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Escape a Prometheus label value.
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::testing::{make_trace, make_trace_info};
+    use std::collections::HashMap;
+    use std::io::Read;
+    use std::net::IpAddr;
+    use std::thread;
+
+    fn scrape(addr: SocketAddr, path: &str) -> (String, String) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        (head.lines().next().unwrap().to_string(), body.to_string())
+    }
+
+    /// Parse the samples of the text exposition format into a map of `name{labels}` to value.
+    fn parse_exposition(body: &str) -> HashMap<String, f64> {
+        body.lines()
+            .filter(|line| !line.starts_with('#'))
+            .map(|line| {
+                let (series, value) = line.rsplit_once(' ').unwrap();
+                (series.to_string(), value.parse().unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_scrape_metrics() {
+        let a = IpAddr::from([10, 0, 0, 1]);
+        let t = IpAddr::from([10, 0, 0, 3]);
+        let trace = make_trace(&[
+            &[(Some(a), 10), (None, 0), (Some(t), 30)],
+            &[(Some(a), 20), (None, 0), (Some(t), 40)],
+        ]);
+        let traces = vec![make_trace_info(trace)];
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let interrupt = Interrupt::default();
+        let ((status, body), (not_found_status, _)) = thread::scope(|scope| {
+            let server = scope.spawn(|| serve(&listener, &traces, &interrupt));
+            let responses = (scrape(addr, "/metrics"), scrape(addr, "/other"));
+            interrupt.set();
+            server.join().unwrap().unwrap();
+            responses
+        });
+        assert_eq!("HTTP/1.1 200 OK", status);
+        assert_eq!("HTTP/1.1 404 Not Found", not_found_status);
+        let samples = parse_exposition(&body);
+        let sample = |name: &str, ttl: u8, addr: &str| {
+            samples[&format!("{name}{{target=\"example.com\",ttl=\"{ttl}\",addr=\"{addr}\"}}")]
+        };
+        assert_eq!(13, samples.len());
+        assert!((sample("trippy_hop_sent_total", 1, "10.0.0.1") - 2_f64).abs() < f64::EPSILON);
+        assert!((sample("trippy_hop_recv_total", 1, "10.0.0.1") - 2_f64).abs() < f64::EPSILON);
+        assert!((sample("trippy_hop_last_rtt_seconds", 1, "10.0.0.1") - 0.02).abs() < 1e-9);
+        assert!((sample("trippy_hop_best_rtt_seconds", 3, "10.0.0.3") - 0.03).abs() < 1e-9);
+        assert!((sample("trippy_hop_sent_total", 2, "") - 2_f64).abs() < f64::EPSILON);
+        assert!(sample("trippy_hop_recv_total", 2, "").abs() < f64::EPSILON);
+        assert!((sample("trippy_hop_loss_ratio", 2, "") - 1_f64).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_escape_label() {
+        assert_eq!("a\\\\b\\\"c\\n", escape_label("a\\b\"c\n"));
+    }
+}
@@ -0,0 +1,314 @@
+use crate::backend::Trace;
+use crate::report::round_responses;
+use crate::signal::Interrupt;
+use crate::zone;
+use crate::TraceInfo;
+use anyhow::anyhow;
+use chrono::Utc;
+use rusqlite::{params, Connection, Transaction};
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Arc;
+use std::thread::sleep;
+use std::time::Duration;
+
+/// The interval at which the trace is polled for newly completed rounds.
+const ROUND_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The schema migrations, the version of the schema is the number of migrations which have been applied.
+///
+/// The version is stored in the `user_version` pragma, which `SQLite` reserves for use by applications, and so
+/// existing migrations must never be modified, new migrations must be appended.
+const MIGRATIONS: &[&str] = &["
+    CREATE TABLE runs (
+        id INTEGER PRIMARY KEY,
+        target TEXT NOT NULL,
+        target_ip TEXT NOT NULL,
+        source_ip TEXT NOT NULL,
+        protocol TEXT NOT NULL,
+        packet_size INTEGER NOT NULL,
+        version TEXT NOT NULL,
+        started_at TEXT NOT NULL
+    );
+    CREATE TABLE rounds (
+        id INTEGER PRIMARY KEY,
+        run_id INTEGER NOT NULL REFERENCES runs(id),
+        round INTEGER NOT NULL,
+        completed_at TEXT NOT NULL
+    );
+    CREATE TABLE hops (
+        round_id INTEGER NOT NULL REFERENCES rounds(id),
+        ttl INTEGER NOT NULL,
+        addr TEXT,
+        rtt_ms REAL,
+        status TEXT NOT NULL
+    );
+    CREATE INDEX rounds_run_id ON rounds(run_id);
+    CREATE INDEX hops_round_id ON hops(round_id);
+"];
+
+/// The status of a hop which responded in a round.
+const STATUS_COMPLETE: &str = "complete";
+
+/// The status of a hop which did not respond in a round.
+const STATUS_AWAITED: &str = "awaited";
+
+/// Append the result of every round to a `SQLite` database until interrupted.
+///
+/// The schema is created or migrated as needed and a new run is recorded for every invocation, the hops of each
+/// round are inserted in a single transaction.
+pub fn run_report_sqlite(
+    info: &TraceInfo,
+    path: &Path,
+    interrupt: &Interrupt,
+) -> anyhow::Result<()> {
+    let mut conn = open_database(path)?;
+    let run_id = insert_run(&conn, info)?;
+    let mut previous: Option<Arc<Trace>> = None;
+    while !interrupt.is_set() {
+        let trace = info.data.snapshot();
+        if let Some(err) = trace.error() {
+            return Err(anyhow!("error: {}", err));
+        }
+        if trace.round().is_some() && trace.round() != previous.as_deref().and_then(Trace::round) {
+            insert_round(&mut conn, run_id, previous.as_deref(), &trace)?;
+            previous = Some(trace);
+        }
+        sleep(ROUND_POLL_INTERVAL);
+    }
+    Ok(())
+}
+
+/// Open the database at `path` and migrate the schema to the latest version.
+fn open_database(path: &Path) -> anyhow::Result<Connection> {
+    let mut conn = Connection::open(path)
+        .map_err(|e| anyhow!("failed to open database {}: {}", path.display(), e))?;
+    migrate(&mut conn)?;
+    Ok(conn)
+}
+
+/// Apply any schema migrations which have not yet been applied.
+fn migrate(conn: &mut Connection) -> anyhow::Result<()> {
+    let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version > MIGRATIONS.len() {
+        return Err(anyhow!(
+            "database schema version {} is newer than the supported version {}",
+            version,
+            MIGRATIONS.len()
+        ));
+    }
+    let tx = conn.transaction()?;
+    for migration in &MIGRATIONS[version..] {
+        tx.execute_batch(migration)?;
+    }
+    tx.pragma_update(None, "user_version", MIGRATIONS.len())?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Record a new run and return its id.
+fn insert_run(conn: &Connection, info: &TraceInfo) -> anyhow::Result<i64> {
+    conn.execute(
+        "INSERT INTO runs (target, target_ip, source_ip, protocol, packet_size, version, started_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            info.target_hostname,
+            zone::format_addr(info.target_addr, info.target_zone.as_deref()),
+            info.source_addr.to_string(),
+            info.protocol.to_string(),
+            info.packet_size,
+            env!("CARGO_PKG_VERSION"),
+            Utc::now().to_rfc3339(),
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Record the latest round of `trace` and its hops within a single transaction.
+fn insert_round(
+    conn: &mut Connection,
+    run_id: i64,
+    previous: Option<&Trace>,
+    trace: &Trace,
+) -> anyhow::Result<()> {
+    let tx = conn.transaction()?;
+    tx.execute(
+        "INSERT INTO rounds (run_id, round, completed_at) VALUES (?1, ?2, ?3)",
+        params![run_id, trace.round(), Utc::now().to_rfc3339()],
+    )?;
+    let round_id = tx.last_insert_rowid();
+    insert_hops(&tx, round_id, previous, trace)?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Record the hops of the latest round of `trace`.
+fn insert_hops(
+    tx: &Transaction<'_>,
+    round_id: i64,
+    previous: Option<&Trace>,
+    trace: &Trace,
+) -> anyhow::Result<()> {
+    let mut stmt = tx.prepare_cached(
+        "INSERT INTO hops (round_id, ttl, addr, rtt_ms, status) VALUES (?1, ?2, ?3, ?4, ?5)",
+    )?;
+    for (ttl, addr, rtt_ms) in round_hops(previous, trace) {
+        let status = if addr.is_some() {
+            STATUS_COMPLETE
+        } else {
+            STATUS_AWAITED
+        };
+        stmt.execute(params![
+            round_id,
+            ttl,
+            addr.map(|addr| addr.to_string()),
+            rtt_ms,
+            status
+        ])?;
+    }
+    Ok(())
+}
+
+/// The `(ttl, addr, rtt_ms)` of a hop in a round, the `addr` and `rtt_ms` are `None` if the hop did not respond.
+type RoundHop = (u8, Option<IpAddr>, Option<f64>);
+
+/// The `RoundHop` of each hop in the latest round of `trace`.
+fn round_hops(previous: Option<&Trace>, trace: &Trace) -> Vec<RoundHop> {
+    round_responses(previous, trace)
+        .into_iter()
+        .map(|(hop, addr)| (hop.ttl(), addr, addr.and_then(|_| hop.last_ms())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::testing::{make_trace, make_trace_info};
+    use std::net::Ipv4Addr;
+
+    const HOP1: Option<IpAddr> = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+    const HOP2: Option<IpAddr> = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)));
+    const TARGET: Option<IpAddr> = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3)));
+
+    /// The `(round, ttl, addr, rtt_ms, status)` of a recorded hop.
+    type RecordedHop = (usize, u8, Option<String>, Option<f64>, String);
+
+    /// Record every round of the simulated trace as a new run.
+    fn record(conn: &mut Connection, rounds: &[&[(Option<IpAddr>, u64)]]) -> i64 {
+        let run_id = insert_run(conn, &make_trace_info(Trace::new(256))).unwrap();
+        let mut previous = None;
+        for i in 1..=rounds.len() {
+            let trace = make_trace(&rounds[..i]);
+            insert_round(conn, run_id, previous.as_ref(), &trace).unwrap();
+            previous = Some(trace);
+        }
+        run_id
+    }
+
+    fn count(conn: &Connection, table: &str) -> usize {
+        conn.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+            row.get(0)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_record_rounds() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+        let run_id = record(
+            &mut conn,
+            &[
+                &[(HOP1, 10), (HOP2, 20), (TARGET, 30)],
+                &[(HOP1, 12), (None, 0), (TARGET, 34)],
+            ],
+        );
+        assert_eq!(1, count(&conn, "runs"));
+        assert_eq!(2, count(&conn, "rounds"));
+        assert_eq!(6, count(&conn, "hops"));
+        let (target, target_ip, protocol, packet_size): (String, String, String, u16) = conn
+            .query_row(
+                "SELECT target, target_ip, protocol, packet_size FROM runs WHERE id = ?1",
+                [run_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .unwrap();
+        assert_eq!("example.com", target);
+        assert_eq!("10.0.0.3", target_ip);
+        assert_eq!("icmp", protocol);
+        assert_eq!(84, packet_size);
+        let mut stmt = conn
+            .prepare(
+                "SELECT r.round, h.ttl, h.addr, h.rtt_ms, h.status FROM hops h
+                 JOIN rounds r ON r.id = h.round_id ORDER BY r.round, h.ttl",
+            )
+            .unwrap();
+        let hops: Vec<RecordedHop> = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                ))
+            })
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let hop =
+            |round, ttl, addr: Option<&str>, rtt_ms: Option<f64>, status: &str| -> RecordedHop {
+                (
+                    round,
+                    ttl,
+                    addr.map(String::from),
+                    rtt_ms,
+                    String::from(status),
+                )
+            };
+        assert_eq!(
+            vec![
+                hop(0, 1, Some("10.0.0.1"), Some(10.0), "complete"),
+                hop(0, 2, Some("10.0.0.2"), Some(20.0), "complete"),
+                hop(0, 3, Some("10.0.0.3"), Some(30.0), "complete"),
+                hop(1, 1, Some("10.0.0.1"), Some(12.0), "complete"),
+                hop(1, 2, None, None, "awaited"),
+                hop(1, 3, Some("10.0.0.3"), Some(34.0), "complete"),
+            ],
+            hops
+        );
+    }
+
+    #[test]
+    fn test_existing_database_appends_run() {
+        let path = std::env::temp_dir().join(format!("trippy-test-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let rounds: &[&[(Option<IpAddr>, u64)]] = &[&[(HOP1, 10), (TARGET, 30)]];
+        let first = record(&mut open_database(&path).unwrap(), rounds);
+        let mut conn = open_database(&path).unwrap();
+        let second = record(&mut conn, rounds);
+        let runs = count(&conn, "runs");
+        let rounds = count(&conn, "rounds");
+        let hops = count(&conn, "hops");
+        drop(conn);
+        std::fs::remove_file(&path).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(2, runs);
+        assert_eq!(2, rounds);
+        assert_eq!(4, hops);
+    }
+
+    #[test]
+    fn test_migrate() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+        migrate(&mut conn).unwrap();
+        let version: usize = conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(MIGRATIONS.len(), version);
+        conn.pragma_update(None, "user_version", MIGRATIONS.len() + 1)
+            .unwrap();
+        assert!(migrate(&mut conn).is_err());
+    }
+}
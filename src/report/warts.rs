@@ -0,0 +1,695 @@
+use crate::backend::{Hop, Trace};
+use crate::report::{round_responses, ReportConfig, ReportMetadata};
+use crate::TraceInfo;
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use std::io::Write;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::thread::sleep;
+use std::time::Duration;
+use trippy_core::{IcmpPacketType, MultipathStrategy, TracerProtocol};
+
+/// The interval at which the trace is polled for newly completed rounds.
+const ROUND_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The magic number which begins the header of every warts record.
+const WARTS_MAGIC: u16 = 0x1205;
+
+/// The warts record types.
+const WARTS_TYPE_LIST: u16 = 0x0001;
+const WARTS_TYPE_CYCLE_START: u16 = 0x0002;
+const WARTS_TYPE_CYCLE_STOP: u16 = 0x0004;
+const WARTS_TYPE_TRACE: u16 = 0x0006;
+
+/// The warts id of the single list and cycle of a report, id `0` is reserved by `scamper` for the null list and cycle.
+const WARTS_ID: u32 = 1;
+
+/// The list parameters.
+const WARTS_LIST_DESCR: u8 = 1;
+const WARTS_LIST_MONITOR: u8 = 2;
+
+/// The cycle parameters.
+const WARTS_CYCLE_HOSTNAME: u8 = 2;
+
+/// The trace parameters.
+const WARTS_TRACE_LIST_ID: u8 = 1;
+const WARTS_TRACE_CYCLE_ID: u8 = 2;
+const WARTS_TRACE_START: u8 = 5;
+const WARTS_TRACE_STOP_R: u8 = 6;
+const WARTS_TRACE_ATTEMPTS: u8 = 9;
+const WARTS_TRACE_HOPLIMIT: u8 = 10;
+const WARTS_TRACE_TYPE: u8 = 11;
+const WARTS_TRACE_PROBE_S: u8 = 12;
+const WARTS_TRACE_FIRSTHOP: u8 = 15;
+const WARTS_TRACE_HOPCOUNT: u8 = 19;
+const WARTS_TRACE_PROBEC: u8 = 23;
+const WARTS_TRACE_ADDR_SRC: u8 = 26;
+const WARTS_TRACE_ADDR_DST: u8 = 27;
+
+/// The trace hop parameters.
+const WARTS_TRACE_HOP_PROBE_TTL: u8 = 2;
+const WARTS_TRACE_HOP_FLAGS: u8 = 4;
+const WARTS_TRACE_HOP_PROBE_ID: u8 = 5;
+const WARTS_TRACE_HOP_RTT: u8 = 6;
+const WARTS_TRACE_HOP_ICMP_TC: u8 = 7;
+const WARTS_TRACE_HOP_PROBE_SIZE: u8 = 8;
+const WARTS_TRACE_HOP_ADDR: u8 = 18;
+
+/// The marker which ends the optional attributes (i.e. PMTUD) which follow the hops of a trace.
+const WARTS_TRACE_ATTR_EOF: u16 = 0x0000;
+
+/// The `scamper` trace methods.
+const SCAMPER_TRACE_TYPE_ICMP_ECHO: u8 = 0x01;
+const SCAMPER_TRACE_TYPE_UDP: u8 = 0x02;
+const SCAMPER_TRACE_TYPE_TCP: u8 = 0x03;
+const SCAMPER_TRACE_TYPE_UDP_PARIS: u8 = 0x05;
+
+/// The `scamper` trace stop reasons.
+const SCAMPER_TRACE_STOP_NONE: u8 = 0x00;
+const SCAMPER_TRACE_STOP_COMPLETED: u8 = 0x01;
+const SCAMPER_TRACE_STOP_UNREACH: u8 = 0x02;
+
+/// The `scamper` hop flag which marks a TCP (rather than ICMP) response.
+const SCAMPER_TRACE_HOP_FLAG_TCP: u8 = 0x20;
+
+/// The `scamper` address types.
+const SCAMPER_ADDR_TYPE_IPV4: u8 = 0x01;
+const SCAMPER_ADDR_TYPE_IPV6: u8 = 0x02;
+
+/// Generate a `scamper` warts report of trace data.
+///
+/// The report is a single cycle of a single list containing a single trace object with a hop record for every probe
+/// which received a response, timeouts are not recorded as is the case for `scamper`.  Only the fields which trippy
+/// measures are written, notably the reply TTL is not known and so is never written.
+pub fn run_report_warts(
+    info: &TraceInfo,
+    metadata: &ReportMetadata,
+    config: &ReportConfig,
+    out: &mut impl Write,
+) -> anyhow::Result<()> {
+    let start = Utc::now();
+    let mut probes = WartsProbes::new(config.cycles, metadata.packet_size);
+    let trace = loop {
+        let trace = info.data.snapshot();
+        if let Some(err) = trace.error() {
+            return Err(anyhow!("error: {}", err));
+        }
+        probes.update(trace);
+        if probes.is_finished() {
+            break probes.previous.take().expect("finished trace");
+        }
+        sleep(ROUND_POLL_INTERVAL);
+    };
+    let report = WartsTrace {
+        start,
+        src: metadata.source_addr,
+        dst: metadata.target_addr,
+        method: trace_method(info.protocol, info.multipath_strategy),
+        stop_reason: stop_reason(&trace, metadata.target_addr),
+        attempts: saturating_u8(probes.rounds),
+        hoplimit: info.max_ttl,
+        firsthop: info.first_ttl,
+        probe_size: metadata.packet_size,
+        hop_count: u16::try_from(trace.hops().len()).unwrap_or(u16::MAX),
+        probe_count: trace.hops().iter().map(Hop::total_sent).sum(),
+        hops: probes.hops,
+    };
+    write_warts(metadata, &report, Utc::now(), out)
+}
+
+/// The result of a probe which received a response.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct WartsHop {
+    addr: IpAddr,
+    probe_ttl: u8,
+    /// The attempt which received the response, counting from `1`.
+    probe_id: u8,
+    rtt: Duration,
+    icmp_packet_type: Option<IcmpPacketType>,
+    probe_size: u16,
+}
+
+/// A trace object, the fields of which mirror those of the `scamper` trace.
+#[derive(Debug)]
+struct WartsTrace {
+    start: DateTime<Utc>,
+    src: IpAddr,
+    dst: IpAddr,
+    method: u8,
+    stop_reason: u8,
+    attempts: u8,
+    hoplimit: u8,
+    firsthop: u8,
+    probe_size: u16,
+    hop_count: u16,
+    probe_count: usize,
+    hops: Vec<WartsHop>,
+}
+
+/// Records a `WartsHop` for every response as each round of the trace completes.
+struct WartsProbes {
+    rounds: usize,
+    probe_size: u16,
+    hops: Vec<WartsHop>,
+    attempts: Vec<u8>,
+    previous: Option<Arc<Trace>>,
+}
+
+impl WartsProbes {
+    fn new(rounds: usize, probe_size: u16) -> Self {
+        Self {
+            rounds,
+            probe_size,
+            hops: Vec::new(),
+            attempts: Vec::new(),
+            previous: None,
+        }
+    }
+
+    /// Record the responses of the latest round of `trace`, if it has not already been recorded.
+    fn update(&mut self, trace: Arc<Trace>) {
+        if trace.round().is_none()
+            || trace.round() == self.previous.as_deref().and_then(Trace::round)
+        {
+            return;
+        }
+        for (hop, addr) in round_responses(self.previous.as_deref(), &trace) {
+            let Some(index) = usize::from(hop.ttl()).checked_sub(1) else {
+                continue;
+            };
+            if self.attempts.len() <= index {
+                self.attempts.resize(index + 1, 0);
+            }
+            self.attempts[index] = self.attempts[index].saturating_add(1);
+            if let (Some(addr), Some(rtt_ms)) = (addr, hop.last_ms()) {
+                self.hops.push(WartsHop {
+                    addr,
+                    probe_ttl: hop.ttl(),
+                    probe_id: self.attempts[index],
+                    rtt: Duration::from_secs_f64(rtt_ms / 1000_f64),
+                    icmp_packet_type: hop.last_icmp_packet_type(),
+                    probe_size: self.probe_size,
+                });
+            }
+        }
+        self.previous = Some(trace);
+    }
+
+    /// Have all rounds been recorded?
+    fn is_finished(&self) -> bool {
+        let recorded = self
+            .previous
+            .as_deref()
+            .and_then(Trace::round)
+            .map_or(0, |round| round + 1);
+        recorded >= self.rounds
+    }
+}
+
+/// The `scamper` trace method of the trippy protocol and strategy.
+fn trace_method(protocol: TracerProtocol, multipath_strategy: MultipathStrategy) -> u8 {
+    match (protocol, multipath_strategy) {
+        (TracerProtocol::Icmp, _) => SCAMPER_TRACE_TYPE_ICMP_ECHO,
+        (TracerProtocol::Udp, MultipathStrategy::Classic) => SCAMPER_TRACE_TYPE_UDP,
+        (TracerProtocol::Udp, MultipathStrategy::Paris | MultipathStrategy::Dublin) => {
+            SCAMPER_TRACE_TYPE_UDP_PARIS
+        }
+        (TracerProtocol::Tcp, _) => SCAMPER_TRACE_TYPE_TCP,
+    }
+}
+
+/// The `scamper` stop reason, the trace completed if the target responded.
+fn stop_reason(trace: &Trace, target_addr: IpAddr) -> u8 {
+    let target_hop = trace.target_hop();
+    if target_hop.addrs().any(|addr| *addr == target_addr) {
+        SCAMPER_TRACE_STOP_COMPLETED
+    } else if matches!(
+        target_hop.last_icmp_packet_type(),
+        Some(IcmpPacketType::Unreachable(_))
+    ) {
+        SCAMPER_TRACE_STOP_UNREACH
+    } else {
+        SCAMPER_TRACE_STOP_NONE
+    }
+}
+
+/// The ICMP type and code of a response, which depend on the address family.
+fn icmp_type_code(icmp_packet_type: IcmpPacketType, addr: IpAddr) -> Option<(u8, u8)> {
+    match (icmp_packet_type, addr) {
+        (IcmpPacketType::TimeExceeded, IpAddr::V4(_)) => Some((11, 0)),
+        (IcmpPacketType::TimeExceeded, IpAddr::V6(_)) => Some((3, 0)),
+        (IcmpPacketType::EchoReply, IpAddr::V4(_)) => Some((0, 0)),
+        (IcmpPacketType::EchoReply, IpAddr::V6(_)) => Some((129, 0)),
+        (IcmpPacketType::TimestampReply, IpAddr::V4(_)) => Some((14, 0)),
+        (IcmpPacketType::Unreachable(code), IpAddr::V4(_)) => Some((3, code)),
+        (IcmpPacketType::Unreachable(code), IpAddr::V6(_)) => Some((1, code)),
+        (IcmpPacketType::TimestampReply, IpAddr::V6(_)) | (IcmpPacketType::NotApplicable, _) => {
+            None
+        }
+    }
+}
+
+fn saturating_u8(value: usize) -> u8 {
+    u8::try_from(value).unwrap_or(u8::MAX)
+}
+
+/// Write the list, cycle start, trace and cycle stop records of a report.
+fn write_warts(
+    metadata: &ReportMetadata,
+    trace: &WartsTrace,
+    stop: DateTime<Utc>,
+    out: &mut impl Write,
+) -> anyhow::Result<()> {
+    write_record(out, WARTS_TYPE_LIST, &list_record(metadata))?;
+    write_record(
+        out,
+        WARTS_TYPE_CYCLE_START,
+        &cycle_start_record(metadata, trace.start),
+    )?;
+    write_record(out, WARTS_TYPE_TRACE, &trace_record(trace))?;
+    write_record(out, WARTS_TYPE_CYCLE_STOP, &cycle_stop_record(stop))?;
+    Ok(())
+}
+
+/// Write a record, i.e. the magic, type and length header followed by the record itself.
+fn write_record(out: &mut impl Write, record_type: u16, record: &[u8]) -> anyhow::Result<()> {
+    let len = u32::try_from(record.len()).map_err(|_| anyhow!("warts record too large"))?;
+    out.write_all(&WARTS_MAGIC.to_be_bytes())?;
+    out.write_all(&record_type.to_be_bytes())?;
+    out.write_all(&len.to_be_bytes())?;
+    out.write_all(record)?;
+    Ok(())
+}
+
+fn list_record(metadata: &ReportMetadata) -> Vec<u8> {
+    let mut buf = Vec::new();
+    buf.extend(WARTS_ID.to_be_bytes());
+    buf.extend(0_u32.to_be_bytes());
+    put_string(&mut buf, "trippy");
+    let mut params = Params::default();
+    params.string(WARTS_LIST_DESCR, &format!("trippy {}", metadata.version));
+    params.string(WARTS_LIST_MONITOR, &metadata.local_hostname);
+    params.write(&mut buf);
+    buf
+}
+
+fn cycle_start_record(metadata: &ReportMetadata, start: DateTime<Utc>) -> Vec<u8> {
+    let mut buf = Vec::new();
+    buf.extend(WARTS_ID.to_be_bytes());
+    buf.extend(WARTS_ID.to_be_bytes());
+    buf.extend(0_u32.to_be_bytes());
+    buf.extend(timestamp_secs(start).to_be_bytes());
+    let mut params = Params::default();
+    params.string(WARTS_CYCLE_HOSTNAME, &metadata.local_hostname);
+    params.write(&mut buf);
+    buf
+}
+
+fn cycle_stop_record(stop: DateTime<Utc>) -> Vec<u8> {
+    let mut buf = Vec::new();
+    buf.extend(WARTS_ID.to_be_bytes());
+    buf.extend(timestamp_secs(stop).to_be_bytes());
+    Params::default().write(&mut buf);
+    buf
+}
+
+/// The trace parameters followed by the count of hop records, the hop records and the end of the trace attributes.
+///
+/// Addresses are numbered in the order they are written and each address after the first occurrence in a record is
+/// written as a reference to that number.
+fn trace_record(trace: &WartsTrace) -> Vec<u8> {
+    let mut buf = Vec::new();
+    let mut addrs = AddrTable::default();
+    let mut params = Params::default();
+    params.u32(WARTS_TRACE_LIST_ID, WARTS_ID);
+    params.u32(WARTS_TRACE_CYCLE_ID, WARTS_ID);
+    params.timeval(WARTS_TRACE_START, trace.start);
+    params.u8(WARTS_TRACE_STOP_R, trace.stop_reason);
+    params.u8(WARTS_TRACE_ATTEMPTS, trace.attempts);
+    params.u8(WARTS_TRACE_HOPLIMIT, trace.hoplimit);
+    params.u8(WARTS_TRACE_TYPE, trace.method);
+    params.u16(WARTS_TRACE_PROBE_S, trace.probe_size);
+    params.u8(WARTS_TRACE_FIRSTHOP, trace.firsthop);
+    params.u16(WARTS_TRACE_HOPCOUNT, trace.hop_count);
+    params.u16(
+        WARTS_TRACE_PROBEC,
+        u16::try_from(trace.probe_count).unwrap_or(u16::MAX),
+    );
+    params.addr(WARTS_TRACE_ADDR_SRC, trace.src, &mut addrs);
+    params.addr(WARTS_TRACE_ADDR_DST, trace.dst, &mut addrs);
+    params.write(&mut buf);
+    let hop_count = u16::try_from(trace.hops.len()).unwrap_or(u16::MAX);
+    buf.extend(hop_count.to_be_bytes());
+    for hop in trace.hops.iter().take(usize::from(hop_count)) {
+        hop_params(hop, &mut addrs).write(&mut buf);
+    }
+    buf.extend(WARTS_TRACE_ATTR_EOF.to_be_bytes());
+    buf
+}
+
+fn hop_params(hop: &WartsHop, addrs: &mut AddrTable) -> Params {
+    let icmp = hop
+        .icmp_packet_type
+        .and_then(|icmp_packet_type| icmp_type_code(icmp_packet_type, hop.addr));
+    let mut params = Params::default();
+    params.u8(WARTS_TRACE_HOP_PROBE_TTL, hop.probe_ttl);
+    if hop.icmp_packet_type == Some(IcmpPacketType::NotApplicable) {
+        params.u8(WARTS_TRACE_HOP_FLAGS, SCAMPER_TRACE_HOP_FLAG_TCP);
+    }
+    params.u8(WARTS_TRACE_HOP_PROBE_ID, hop.probe_id);
+    params.u32(
+        WARTS_TRACE_HOP_RTT,
+        u32::try_from(hop.rtt.as_micros()).unwrap_or(u32::MAX),
+    );
+    if let Some((icmp_type, icmp_code)) = icmp {
+        params.u16(
+            WARTS_TRACE_HOP_ICMP_TC,
+            u16::from(icmp_type) << 8 | u16::from(icmp_code),
+        );
+    }
+    params.u16(WARTS_TRACE_HOP_PROBE_SIZE, hop.probe_size);
+    params.addr(WARTS_TRACE_HOP_ADDR, hop.addr, addrs);
+    params
+}
+
+fn timestamp_secs(time: DateTime<Utc>) -> u32 {
+    u32::try_from(time.timestamp()).unwrap_or_default()
+}
+
+/// Write a null terminated string.
+fn put_string(buf: &mut Vec<u8>, s: &str) {
+    buf.extend(s.as_bytes());
+    buf.push(0);
+}
+
+/// The optional parameters of a record.
+///
+/// Parameters are identified by a numbered flag and must be added in flag order.  The flags are written first, seven
+/// to a byte with the high bit of every byte but the last set, followed by the total length of the parameters and
+/// then the parameters themselves.  A record without parameters is written as a single zero byte.
+#[derive(Debug, Default)]
+struct Params {
+    ids: Vec<u8>,
+    data: Vec<u8>,
+}
+
+impl Params {
+    fn u8(&mut self, id: u8, value: u8) {
+        self.add(id, &[value]);
+    }
+
+    fn u16(&mut self, id: u8, value: u16) {
+        self.add(id, &value.to_be_bytes());
+    }
+
+    fn u32(&mut self, id: u8, value: u32) {
+        self.add(id, &value.to_be_bytes());
+    }
+
+    fn string(&mut self, id: u8, value: &str) {
+        let mut bytes = Vec::new();
+        put_string(&mut bytes, value);
+        self.add(id, &bytes);
+    }
+
+    /// A timestamp, written as seconds and microseconds.
+    fn timeval(&mut self, id: u8, time: DateTime<Utc>) {
+        let mut bytes = timestamp_secs(time).to_be_bytes().to_vec();
+        bytes.extend(time.timestamp_subsec_micros().to_be_bytes());
+        self.add(id, &bytes);
+    }
+
+    fn addr(&mut self, id: u8, addr: IpAddr, addrs: &mut AddrTable) {
+        self.add(id, &addrs.encode(addr));
+    }
+
+    fn add(&mut self, id: u8, bytes: &[u8]) {
+        debug_assert!(id > 0 && self.ids.last() < Some(&id));
+        self.ids.push(id);
+        self.data.extend(bytes);
+    }
+
+    fn write(&self, buf: &mut Vec<u8>) {
+        match self.ids.last() {
+            None => buf.push(0),
+            Some(max_id) => {
+                let mut flags = vec![0_u8; usize::from(max_id - 1) / 7 + 1];
+                for id in &self.ids {
+                    flags[usize::from(id - 1) / 7] |= 1 << ((id - 1) % 7);
+                }
+                let last = flags.len() - 1;
+                for flag in &mut flags[..last] {
+                    *flag |= 0x80;
+                }
+                buf.extend(flags);
+                let len = u16::try_from(self.data.len()).unwrap_or(u16::MAX);
+                buf.extend(len.to_be_bytes());
+                buf.extend(&self.data);
+            }
+        }
+    }
+}
+
+/// The addresses written in a record, in the order they were first written.
+#[derive(Debug, Default)]
+struct AddrTable(Vec<IpAddr>);
+
+impl AddrTable {
+    /// Encode `addr` as a reference to an earlier occurrence in the record if there is one, or as the address length,
+    /// type and octets otherwise.
+    fn encode(&mut self, addr: IpAddr) -> Vec<u8> {
+        if let Some(index) = self.0.iter().position(|known| *known == addr) {
+            let mut bytes = vec![0];
+            bytes.extend(u32::try_from(index).unwrap_or_default().to_be_bytes());
+            return bytes;
+        }
+        self.0.push(addr);
+        match addr {
+            IpAddr::V4(addr) => [&[4, SCAMPER_ADDR_TYPE_IPV4][..], &addr.octets()].concat(),
+            IpAddr::V6(addr) => [&[16, SCAMPER_ADDR_TYPE_IPV6][..], &addr.octets()].concat(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::testing::add_round;
+    use crate::report::tests::make_metadata;
+    use chrono::TimeZone;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    const ROUTER: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    const TARGET: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3));
+
+    fn time(secs: i64, micros: u32) -> DateTime<Utc> {
+        Utc.timestamp_opt(secs, micros * 1000).unwrap()
+    }
+
+    fn make_hop(addr: IpAddr, ttl: u8, rtt_us: u64, icmp: IcmpPacketType) -> WartsHop {
+        WartsHop {
+            addr,
+            probe_ttl: ttl,
+            probe_id: 1,
+            rtt: Duration::from_micros(rtt_us),
+            icmp_packet_type: Some(icmp),
+            probe_size: 84,
+        }
+    }
+
+    #[test]
+    fn test_params_empty() {
+        let mut buf = vec![];
+        Params::default().write(&mut buf);
+        assert_eq!(vec![0x00], buf);
+    }
+
+    #[test]
+    fn test_params_flags() {
+        let mut params = Params::default();
+        params.u8(1, 0xaa);
+        params.u8(7, 0xbb);
+        params.u16(8, 0xccdd);
+        let mut buf = vec![];
+        params.write(&mut buf);
+        assert_eq!(vec![0xc1, 0x01, 0x00, 0x04, 0xaa, 0xbb, 0xcc, 0xdd], buf);
+        let mut params = Params::default();
+        params.u8(15, 0x01);
+        let mut buf = vec![];
+        params.write(&mut buf);
+        assert_eq!(vec![0x80, 0x80, 0x01, 0x00, 0x01, 0x01], buf);
+    }
+
+    #[test]
+    fn test_addr_table() {
+        let mut addrs = AddrTable::default();
+        assert_eq!(vec![4, 1, 10, 0, 0, 1], addrs.encode(ROUTER));
+        assert_eq!(vec![4, 1, 10, 0, 0, 3], addrs.encode(TARGET));
+        assert_eq!(vec![0, 0, 0, 0, 1], addrs.encode(TARGET));
+        let mut expected = vec![16, 2];
+        expected.extend(Ipv6Addr::LOCALHOST.octets());
+        assert_eq!(expected, addrs.encode(IpAddr::V6(Ipv6Addr::LOCALHOST)));
+    }
+
+    #[test]
+    fn test_record_header() {
+        let mut out = vec![];
+        write_record(
+            &mut out,
+            WARTS_TYPE_CYCLE_STOP,
+            &cycle_stop_record(time(1_672_531_210, 0)),
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                0x12, 0x05, 0x00, 0x04, 0x00, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00, 0x01, 0x63, 0xb0,
+                0xcd, 0x0a, 0x00
+            ],
+            out
+        );
+    }
+
+    #[test]
+    fn test_list_and_cycle_records() {
+        let metadata = make_metadata(TARGET, None);
+        let mut expected = vec![0, 0, 0, 1, 0, 0, 0, 0];
+        expected.extend(b"trippy\0");
+        expected.extend([0x03, 0x00, 0x18]);
+        expected.extend(b"trippy 0.7.0-dev\0myhost\0");
+        assert_eq!(expected, list_record(&metadata));
+        let mut expected = vec![0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0x63, 0xb0, 0xcd, 0x00];
+        expected.extend([0x02, 0x00, 0x07]);
+        expected.extend(b"myhost\0");
+        assert_eq!(
+            expected,
+            cycle_start_record(&metadata, time(1_672_531_200, 0))
+        );
+    }
+
+    #[test]
+    fn test_hop_record() {
+        let mut addrs = AddrTable::default();
+        let mut buf = vec![];
+        hop_params(
+            &make_hop(ROUTER, 1, 10_250, IcmpPacketType::TimeExceeded),
+            &mut addrs,
+        )
+        .write(&mut buf);
+        assert_eq!(
+            vec![
+                0xf2, 0x81, 0x08, 0x00, 0x10, 0x01, 0x01, 0x00, 0x00, 0x28, 0x0a, 0x0b, 0x00, 0x00,
+                0x54, 0x04, 0x01, 0x0a, 0x00, 0x00, 0x01
+            ],
+            buf
+        );
+        let mut buf = vec![];
+        hop_params(
+            &make_hop(ROUTER, 2, 1, IcmpPacketType::NotApplicable),
+            &mut addrs,
+        )
+        .write(&mut buf);
+        assert_eq!(
+            vec![
+                0xba, 0x81, 0x08, 0x00, 0x0e, 0x02, 0x20, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x54,
+                0x00, 0x00, 0x00, 0x00, 0x00
+            ],
+            buf
+        );
+    }
+
+    #[test]
+    fn test_trace_record() {
+        let trace = WartsTrace {
+            start: time(1_672_531_200, 500),
+            src: ROUTER,
+            dst: TARGET,
+            method: SCAMPER_TRACE_TYPE_ICMP_ECHO,
+            stop_reason: SCAMPER_TRACE_STOP_COMPLETED,
+            attempts: 1,
+            hoplimit: 64,
+            firsthop: 1,
+            probe_size: 84,
+            hop_count: 1,
+            probe_count: 1,
+            hops: vec![make_hop(TARGET, 1, 20_000, IcmpPacketType::EchoReply)],
+        };
+        let record = trace_record(&trace);
+        let mut expected = vec![0xb3, 0x9e, 0x91, 0x32, 0x00, 0x27];
+        expected.extend([0, 0, 0, 1, 0, 0, 0, 1]);
+        expected.extend([0x63, 0xb0, 0xcd, 0x00, 0x00, 0x00, 0x01, 0xf4]);
+        expected.extend([
+            0x01, 0x01, 0x40, 0x01, 0x00, 0x54, 0x01, 0x00, 0x01, 0x00, 0x01,
+        ]);
+        expected.extend([4, 1, 10, 0, 0, 1, 4, 1, 10, 0, 0, 3]);
+        expected.extend([0x00, 0x01]);
+        expected.extend([
+            0xf2, 0x81, 0x08, 0x00, 0x0f, 0x01, 0x01, 0x00, 0x00, 0x4e, 0x20,
+        ]);
+        expected.extend([0x00, 0x00, 0x00, 0x54, 0x00, 0x00, 0x00, 0x00, 0x01]);
+        expected.extend([0x00, 0x00]);
+        assert_eq!(expected, record);
+    }
+
+    #[test]
+    fn test_probes() {
+        let mut trace = Trace::new(256);
+        let mut probes = WartsProbes::new(2, 84);
+        let time_exceeded = Some(IcmpPacketType::TimeExceeded);
+        let echo_reply = Some(IcmpPacketType::EchoReply);
+        add_round(
+            &mut trace,
+            0,
+            &[
+                (Some(ROUTER), 10, time_exceeded),
+                (Some(TARGET), 20, echo_reply),
+            ],
+        );
+        probes.update(Arc::new(trace.clone()));
+        probes.update(Arc::new(trace.clone()));
+        assert!(!probes.is_finished());
+        add_round(
+            &mut trace,
+            1,
+            &[(None, 0, None), (Some(TARGET), 30, echo_reply)],
+        );
+        probes.update(Arc::new(trace.clone()));
+        assert!(probes.is_finished());
+        let summary: Vec<_> = probes
+            .hops
+            .iter()
+            .map(|hop| (hop.addr, hop.probe_ttl, hop.probe_id, hop.rtt.as_millis()))
+            .collect();
+        assert_eq!(
+            vec![(ROUTER, 1, 1, 10), (TARGET, 2, 1, 20), (TARGET, 2, 2, 30)],
+            summary
+        );
+        assert_eq!(SCAMPER_TRACE_STOP_COMPLETED, stop_reason(&trace, TARGET));
+    }
+
+    #[test]
+    fn test_icmp_type_code() {
+        let v6 = IpAddr::V6(Ipv6Addr::LOCALHOST);
+        assert_eq!(
+            Some((11, 0)),
+            icmp_type_code(IcmpPacketType::TimeExceeded, ROUTER)
+        );
+        assert_eq!(
+            Some((3, 0)),
+            icmp_type_code(IcmpPacketType::TimeExceeded, v6)
+        );
+        assert_eq!(
+            Some((129, 0)),
+            icmp_type_code(IcmpPacketType::EchoReply, v6)
+        );
+        assert_eq!(
+            Some((3, 3)),
+            icmp_type_code(IcmpPacketType::Unreachable(3), ROUTER)
+        );
+        assert_eq!(
+            Some((1, 4)),
+            icmp_type_code(IcmpPacketType::Unreachable(4), v6)
+        );
+        assert_eq!(None, icmp_type_code(IcmpPacketType::NotApplicable, ROUTER));
+    }
+}
@@ -0,0 +1,96 @@
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use std::ops::Range;
+
+/// The range of the trace identifiers drawn, which lies below the ephemeral ports of most systems as the identifier
+/// of a trace is also the default source port of UDP probes.
+const TRACE_IDENTIFIERS: Range<u16> = 1024..32768;
+
+/// The source of randomness of a run, from which everything which varies between runs is drawn, such that a run may
+/// be repeated with the same identifiers, ports and payloads by seeding it with the seed of the run.
+#[derive(Debug, Clone)]
+pub struct TraceRng {
+    seed: u64,
+    rng: SmallRng,
+}
+
+impl TraceRng {
+    /// A generator seeded with `seed`, or with a seed drawn from entropy if there is none.
+    pub fn new(seed: Option<u64>) -> Self {
+        let seed = seed.unwrap_or_else(rand::random);
+        Self {
+            seed,
+            rng: SmallRng::seed_from_u64(seed),
+        }
+    }
+
+    /// The seed of the generator, with which the run may be repeated.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Draw the identifier of a trace, which is the ICMP identifier of its probes and, unless one is given, the
+    /// source port of its UDP probes.
+    pub fn trace_identifier(&mut self) -> u16 {
+        self.rng.gen_range(TRACE_IDENTIFIERS)
+    }
+
+    /// Draw the seed from which the offsets of the start of each round are drawn, should the start be jittered.
+    pub fn round_jitter_seed(&mut self) -> u64 {
+        self.rng.gen()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn identifiers(rng: &mut TraceRng) -> Vec<u16> {
+        (0..8).map(|_| rng.trace_identifier()).collect()
+    }
+
+    #[test]
+    fn test_same_seed() {
+        let mut rng = TraceRng::new(Some(42));
+        let mut other = TraceRng::new(Some(42));
+        assert_eq!(42, rng.seed());
+        assert_eq!(identifiers(&mut rng), identifiers(&mut other));
+    }
+
+    #[test]
+    fn test_different_seeds() {
+        let first: HashSet<_> = (0..64)
+            .map(|seed| TraceRng::new(Some(seed)).trace_identifier())
+            .collect();
+        assert!(first.len() > 60);
+    }
+
+    #[test]
+    fn test_seed_from_entropy() {
+        let mut rng = TraceRng::new(None);
+        let mut repeated = TraceRng::new(Some(rng.seed()));
+        assert_eq!(identifiers(&mut rng), identifiers(&mut repeated));
+    }
+
+    #[test]
+    fn test_round_jitter_seed() {
+        let mut rng = TraceRng::new(Some(42));
+        let mut other = TraceRng::new(Some(42));
+        rng.trace_identifier();
+        other.trace_identifier();
+        assert_eq!(rng.round_jitter_seed(), other.round_jitter_seed());
+        assert_ne!(
+            TraceRng::new(Some(42)).round_jitter_seed(),
+            TraceRng::new(Some(43)).round_jitter_seed()
+        );
+    }
+
+    #[test]
+    fn test_trace_identifier_range() {
+        let mut rng = TraceRng::new(Some(7));
+        assert!((0..1000)
+            .map(|_| rng.trace_identifier())
+            .all(|identifier| TRACE_IDENTIFIERS.contains(&identifier)));
+    }
+}
@@ -0,0 +1,117 @@
+use crate::backend::Hop;
+
+/// The estimated latency of the segment of the path which ends at a hop.
+///
+/// The RTT of a hop includes the return path from the hop, which may differ from that of the hop before it, and the
+/// time the hop takes to generate the ICMP response, so the difference between the RTT of adjacent hops is only an
+/// estimate of the latency between them and may even be negative.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Segment {
+    /// The average RTT of the hop less that of the previous hop which responded, in milliseconds.
+    delta_ms: f64,
+}
+
+impl Segment {
+    /// The estimated latency of the segment in milliseconds, which is never less than zero.
+    pub fn latency_ms(self) -> f64 {
+        self.delta_ms.max(0_f64)
+    }
+
+    /// Is the hop faster than the hop before it, which is an artifact of an asymmetric return path or a slow ICMP
+    /// response from the earlier hop rather than of the segment itself?
+    pub fn is_negative(self) -> bool {
+        self.delta_ms < 0_f64
+    }
+}
+
+/// The segment which ends at each of `hops`, `None` for a hop which has not responded.
+///
+/// The segment of a hop is from the last hop before it which has responded, skipping any which have not, or from the
+/// source for the first hop to respond.
+pub fn segments(hops: &[Hop]) -> Vec<Option<Segment>> {
+    let mut previous_ms = 0_f64;
+    hops.iter()
+        .map(|hop| {
+            (hop.total_recv() > 0).then(|| {
+                let avg_ms = hop.avg_ms();
+                let segment = Segment {
+                    delta_ms: avg_ms - previous_ms,
+                };
+                previous_ms = avg_ms;
+                segment
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod tests {
+    use super::*;
+    use crate::backend::testing::make_trace;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    const HOP1: Option<IpAddr> = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+    const HOP2: Option<IpAddr> = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)));
+    const HOP3: Option<IpAddr> = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3)));
+    const HOP4: Option<IpAddr> = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 4)));
+
+    /// The latency and whether it is negative of each segment.
+    fn latencies(rounds: &[&[(Option<IpAddr>, u64)]]) -> Vec<Option<(f64, bool)>> {
+        segments(make_trace(rounds).hops())
+            .into_iter()
+            .map(|segment| segment.map(|segment| (segment.latency_ms(), segment.is_negative())))
+            .collect()
+    }
+
+    #[test]
+    fn test_segments() {
+        assert_eq!(
+            vec![
+                Some((10_f64, false)),
+                Some((5_f64, false)),
+                Some((20_f64, false))
+            ],
+            latencies(&[
+                &[(HOP1, 10), (HOP2, 15), (HOP3, 35)],
+                &[(HOP1, 10), (HOP2, 15), (HOP3, 35)],
+            ])
+        );
+    }
+
+    #[test]
+    fn test_segments_skip_silent_middle() {
+        assert_eq!(
+            vec![Some((10_f64, false)), None, None, Some((30_f64, false))],
+            latencies(&[&[(HOP1, 10), (None, 0), (None, 0), (HOP4, 40)]])
+        );
+    }
+
+    #[test]
+    fn test_segments_leading_silent_hop_from_source() {
+        assert_eq!(
+            vec![None, Some((12_f64, false))],
+            latencies(&[&[(None, 0), (HOP2, 12)]])
+        );
+    }
+
+    #[test]
+    fn test_segments_negative_floored() {
+        assert_eq!(
+            vec![
+                Some((30_f64, false)),
+                Some((0_f64, true)),
+                Some((5_f64, false))
+            ],
+            latencies(&[&[(HOP1, 30), (HOP2, 20), (HOP3, 25)]])
+        );
+    }
+
+    #[test]
+    fn test_segments_averages() {
+        assert_eq!(
+            vec![Some((15_f64, false)), Some((15_f64, false))],
+            latencies(&[&[(HOP1, 10), (HOP2, 30)], &[(HOP1, 20), (None, 0)],])
+        );
+    }
+}
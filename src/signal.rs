@@ -0,0 +1,29 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A flag which is set when the user requests that trippy terminates (i.e. `Ctrl-C`).
+#[derive(Debug, Clone, Default)]
+pub struct Interrupt(Arc<AtomicBool>);
+
+impl Interrupt {
+    /// Install a handler for the termination signal.
+    ///
+    /// This may only be called once.
+    pub fn install() -> anyhow::Result<Self> {
+        let interrupt = Self::default();
+        let flag = interrupt.0.clone();
+        ctrlc::set_handler(move || flag.store(true, Ordering::SeqCst))?;
+        Ok(interrupt)
+    }
+
+    /// Has termination been requested?
+    pub fn is_set(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Request termination.
+    #[cfg(test)]
+    pub fn set(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,251 @@
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use trippy_core::{Trace, TracerProtocol};
+
+/// The version of the state file written, to be incremented should the serialized form of a `Trace` change.
+const SCHEMA_VERSION: u32 = 1;
+
+/// The most samples of each hop saved, such that the state file is kept small however many samples are kept.
+const MAX_SAVED_SAMPLES: usize = 64;
+
+/// The header of a state file, read before the trace such that the trace of another target, or of another schema
+/// version, is refused with an error saying so rather than one of parsing it.
+#[derive(Debug, Deserialize)]
+struct Header {
+    schema: u32,
+    target: String,
+    protocol: String,
+}
+
+/// The state saved to a state file.
+#[derive(Debug, Serialize)]
+struct SavedState<'a> {
+    schema: u32,
+    target: &'a str,
+    protocol: String,
+    trace: &'a Trace,
+}
+
+/// The state loaded from a state file, once its header has been checked.
+#[derive(Debug, Deserialize)]
+struct LoadedState {
+    trace: Trace,
+}
+
+/// The file to which the trace of a target is saved, every `save_rounds` rounds and on exit, and from which it is
+/// resumed on the next run against the same target with the same protocol.
+#[derive(Debug, Clone)]
+pub struct StateFile {
+    path: PathBuf,
+    target: String,
+    protocol: TracerProtocol,
+    save_rounds: usize,
+}
+
+impl StateFile {
+    pub fn new(
+        path: PathBuf,
+        target: String,
+        protocol: TracerProtocol,
+        save_rounds: usize,
+    ) -> Self {
+        Self {
+            path,
+            target,
+            protocol,
+            save_rounds,
+        }
+    }
+
+    /// The number of rounds between saves of the trace whilst tracing.
+    pub fn save_rounds(&self) -> usize {
+        self.save_rounds
+    }
+
+    /// Load the trace saved to the file, resumed keeping at most `max_samples` samples of each hop, or `None` if there
+    /// is no such file.
+    ///
+    /// The trace is refused if it was saved for another target or protocol, or with another schema version.
+    pub fn load(&self, max_samples: usize) -> anyhow::Result<Option<Trace>> {
+        match fs::read_to_string(&self.path) {
+            Ok(json) => {
+                let mut trace = self.parse(&json).map_err(|err| {
+                    anyhow!(
+                        "refusing to resume from state file {}: {err}",
+                        self.path.display()
+                    )
+                })?;
+                trace.resume(max_samples);
+                Ok(Some(trace))
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(anyhow!(
+                "failed to read state file {}: {err}",
+                self.path.display()
+            )),
+        }
+    }
+
+    /// Save the `trace` to the file, replacing it atomically such that a save which is interrupted leaves the last.
+    pub fn save(&self, trace: &Trace) -> anyhow::Result<()> {
+        let json = self.to_json(trace)?;
+        let mut temp = OsString::from(self.path.as_os_str());
+        temp.push(".tmp");
+        let temp = PathBuf::from(temp);
+        let write = || -> io::Result<()> {
+            let mut file = File::create(&temp)?;
+            file.write_all(json.as_bytes())?;
+            file.sync_all()?;
+            fs::rename(&temp, &self.path)
+        };
+        write().map_err(|err| anyhow!("failed to save state file {}: {err}", self.path.display()))
+    }
+
+    /// The json of the state of `trace`, less all but the latest samples of each hop.
+    fn to_json(&self, trace: &Trace) -> anyhow::Result<String> {
+        let mut trace = trace.clone();
+        trace.truncate_samples(MAX_SAVED_SAMPLES);
+        Ok(serde_json::to_string(&SavedState {
+            schema: SCHEMA_VERSION,
+            target: &self.target,
+            protocol: self.protocol.to_string(),
+            trace: &trace,
+        })?)
+    }
+
+    /// Parse the trace from the json of a state file, if it was saved for the target and protocol of this file.
+    fn parse(&self, json: &str) -> anyhow::Result<Trace> {
+        let header: Header = serde_json::from_str(json)?;
+        if header.schema != SCHEMA_VERSION {
+            return Err(anyhow!(
+                "it was written with schema version {}, not {SCHEMA_VERSION}, remove it to start afresh",
+                header.schema
+            ));
+        }
+        if header.target != self.target {
+            return Err(anyhow!(
+                "it is of target {}, not {}",
+                header.target,
+                self.target
+            ));
+        }
+        let protocol = self.protocol.to_string();
+        if header.protocol != protocol {
+            return Err(anyhow!(
+                "it is of protocol {}, not {protocol}",
+                header.protocol
+            ));
+        }
+        let state: LoadedState = serde_json::from_str(json)?;
+        Ok(state.trace)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod tests {
+    use super::*;
+    use crate::backend::testing::make_trace;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    #[allow(clippy::unnecessary_wraps)]
+    fn addr(last: u8) -> Option<IpAddr> {
+        Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, last)))
+    }
+
+    fn state_file(name: &str, target: &str, protocol: TracerProtocol) -> StateFile {
+        let path =
+            std::env::temp_dir().join(format!("trippy-state-{name}-{}.json", std::process::id()));
+        StateFile::new(path, target.to_string(), protocol, 10)
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let file = state_file("round-trip", "example.com", TracerProtocol::Icmp);
+        let trace = make_trace(&[&[(addr(1), 10), (addr(2), 20)], &[(addr(1), 30), (None, 0)]]);
+        file.save(&trace).unwrap();
+        let loaded = file.load(256).unwrap().unwrap();
+        fs::remove_file(&file.path).unwrap();
+        assert_eq!(Some(1), loaded.round());
+        assert_eq!(2, loaded.hops().len());
+        assert_eq!(2, loaded.hops()[1].total_sent());
+        assert_eq!(1, loaded.hops()[1].total_recv());
+        assert_eq!(20_f64, loaded.hops()[0].avg_ms());
+        assert_eq!(trace.hops()[0].samples(), loaded.hops()[0].samples());
+        assert_eq!(
+            vec![(&addr(2).unwrap(), &1)],
+            loaded.hops()[1].addrs_with_counts().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_load_missing() {
+        let file = state_file("missing", "example.com", TracerProtocol::Icmp);
+        assert!(file.load(256).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_saved_samples_are_truncated() {
+        let file = state_file("truncated", "example.com", TracerProtocol::Icmp);
+        let rounds: Vec<_> = (0..100).map(|_| [(addr(1), 10)]).collect();
+        let rounds: Vec<_> = rounds.iter().map(<[_; 1]>::as_slice).collect();
+        let trace = make_trace(&rounds);
+        let loaded = file.parse(&file.to_json(&trace).unwrap()).unwrap();
+        assert_eq!(MAX_SAVED_SAMPLES, loaded.hops()[0].samples().len());
+        assert_eq!(100, loaded.hops()[0].total_sent());
+    }
+
+    #[test]
+    fn test_schema_version_mismatch() {
+        let file = state_file("schema", "example.com", TracerProtocol::Icmp);
+        let json = file.to_json(&make_trace(&[&[(addr(1), 10)]])).unwrap();
+        let json = json.replace(&format!("\"schema\":{SCHEMA_VERSION}"), "\"schema\":0");
+        let err = file.parse(&json).unwrap_err();
+        assert_eq!(
+            "it was written with schema version 0, not 1, remove it to start afresh",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_target_mismatch() {
+        let file = state_file("target", "example.com", TracerProtocol::Icmp);
+        let json = file.to_json(&make_trace(&[&[(addr(1), 10)]])).unwrap();
+        let other = state_file("target", "example.org", TracerProtocol::Icmp);
+        let err = other.parse(&json).unwrap_err();
+        assert_eq!(
+            "it is of target example.com, not example.org",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_protocol_mismatch() {
+        let file = state_file("protocol", "example.com", TracerProtocol::Icmp);
+        let json = file.to_json(&make_trace(&[&[(addr(1), 10)]])).unwrap();
+        let other = state_file("protocol", "example.com", TracerProtocol::Udp);
+        let err = other.parse(&json).unwrap_err();
+        assert_eq!("it is of protocol icmp, not udp", err.to_string());
+    }
+
+    #[test]
+    fn test_load_refuses_mismatch() {
+        let file = state_file("refused", "example.com", TracerProtocol::Icmp);
+        file.save(&make_trace(&[&[(addr(1), 10)]])).unwrap();
+        let other = StateFile::new(
+            file.path.clone(),
+            String::from("example.org"),
+            TracerProtocol::Icmp,
+            10,
+        );
+        let err = other.load(256).unwrap_err();
+        fs::remove_file(&file.path).unwrap();
+        assert!(err
+            .to_string()
+            .starts_with("refusing to resume from state file"));
+    }
+}
@@ -1,19 +0,0 @@
-mod config;
-mod error;
-mod net;
-mod probe;
-mod tracer;
-mod types;
-mod util;
-
-/// Packet wire formats.
-pub mod packet;
-
-pub use config::{
-    MultipathStrategy, PortDirection, TracerAddrFamily, TracerChannelConfig, TracerConfig,
-    TracerProtocol,
-};
-pub use net::channel::TracerChannel;
-pub use net::source::SourceAddr;
-pub use probe::{IcmpPacketType, Probe, ProbeStatus};
-pub use tracer::{Tracer, TracerRound};
@@ -0,0 +1,163 @@
+use anyhow::anyhow;
+use std::net::{IpAddr, Ipv6Addr};
+
+/// Split the zone from a target, `fe80::1%eth0` being the address `fe80::1` within the zone `eth0`.
+///
+/// Only an IPv6 address may be given with a zone, which may be the name or the index of an interface.
+pub fn split_zone(target: &str) -> anyhow::Result<(&str, Option<&str>)> {
+    match target.split_once('%') {
+        None => Ok((target, None)),
+        Some((_, "")) => Err(anyhow!("empty zone given for target {target}")),
+        Some((addr, zone)) if addr.parse::<Ipv6Addr>().is_ok() => Ok((addr, Some(zone))),
+        Some((addr, _)) => Err(anyhow!(
+            "zone given for target {addr} which is not an IPv6 address"
+        )),
+    }
+}
+
+/// Is `addr` a link-local IPv6 address, within `fe80::/10`, which is only meaningful within a zone?
+pub fn is_link_local(addr: IpAddr) -> bool {
+    match addr {
+        IpAddr::V6(addr) => addr.segments()[0] & 0xffc0 == 0xfe80,
+        IpAddr::V4(_) => false,
+    }
+}
+
+/// The zone within which the `target_addrs` are traced, that given with the target or else the `interface`, should
+/// any of them be link-local.
+///
+/// A link-local target is refused if there is no zone, nor interface, through which to reach it, or if the zone given
+/// is not the interface given.
+pub fn target_zone(
+    target_addrs: &[IpAddr],
+    zone: Option<&str>,
+    interface: Option<&str>,
+) -> anyhow::Result<Option<String>> {
+    let target_addr = target_addrs[0];
+    let link_local = target_addrs.iter().any(|addr| is_link_local(*addr));
+    match (link_local, zone, interface) {
+        (false, None, _) => Ok(None),
+        (false, Some(zone), _) => Err(anyhow!(
+            "zone {zone} given for target {target_addr} which is not a link-local address"
+        )),
+        (true, Some(zone), Some(interface)) if zone != interface => Err(anyhow!(
+            "zone {zone} of target {target_addr} is not the interface {interface}"
+        )),
+        (true, Some(zone), _) => Ok(Some(zone.to_string())),
+        (true, None, Some(interface)) => Ok(Some(interface.to_string())),
+        (true, None, None) => Err(anyhow!(
+            "link-local target {target_addr} requires a zone, such as {target_addr}%eth0, or an --interface"
+        )),
+    }
+}
+
+/// The scope id of a `zone`, which is either the index of an interface or its name.
+pub fn scope_id(zone: &str) -> anyhow::Result<u32> {
+    match zone.parse() {
+        Ok(index) => Ok(index),
+        Err(_) => Ok(trippy_core::interface_index(zone)?),
+    }
+}
+
+/// Format `addr` with its `zone`, if any, as `fe80::1%eth0`.
+pub fn format_addr(addr: IpAddr, zone: Option<&str>) -> String {
+    match zone {
+        Some(zone) => format!("{addr}%{zone}"),
+        None => addr.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn link_local() -> IpAddr {
+        IpAddr::V6("fe80::1".parse().unwrap())
+    }
+
+    #[test]
+    fn test_split_zone() {
+        assert_eq!(("example.com", None), split_zone("example.com").unwrap());
+        assert_eq!(("fe80::1", None), split_zone("fe80::1").unwrap());
+        assert_eq!(
+            ("fe80::1", Some("eth0")),
+            split_zone("fe80::1%eth0").unwrap()
+        );
+        assert_eq!(("fe80::1", Some("2")), split_zone("fe80::1%2").unwrap());
+    }
+
+    #[test]
+    fn test_split_zone_invalid() {
+        assert_eq!(
+            "empty zone given for target fe80::1%",
+            split_zone("fe80::1%").unwrap_err().to_string()
+        );
+        assert_eq!(
+            "zone given for target example.com which is not an IPv6 address",
+            split_zone("example.com%eth0").unwrap_err().to_string()
+        );
+        assert!(split_zone("10.0.0.1%eth0").is_err());
+    }
+
+    #[test]
+    fn test_is_link_local() {
+        assert!(is_link_local(link_local()));
+        assert!(is_link_local(IpAddr::V6("febf::1".parse().unwrap())));
+        assert!(!is_link_local(IpAddr::V6("fec0::1".parse().unwrap())));
+        assert!(!is_link_local(IpAddr::V6("2001:db8::1".parse().unwrap())));
+        assert!(!is_link_local(IpAddr::V4(Ipv4Addr::new(169, 254, 0, 1))));
+    }
+
+    #[test]
+    fn test_target_zone() {
+        let global = IpAddr::V6("2001:db8::1".parse().unwrap());
+        assert_eq!(None, target_zone(&[global], None, Some("eth0")).unwrap());
+        assert_eq!(
+            Some(String::from("eth0")),
+            target_zone(&[link_local()], Some("eth0"), None).unwrap()
+        );
+        assert_eq!(
+            Some(String::from("eth0")),
+            target_zone(&[link_local()], Some("eth0"), Some("eth0")).unwrap()
+        );
+        assert_eq!(
+            Some(String::from("eth1")),
+            target_zone(&[global, link_local()], None, Some("eth1")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_target_zone_invalid() {
+        assert_eq!(
+            "link-local target fe80::1 requires a zone, such as fe80::1%eth0, or an --interface",
+            target_zone(&[link_local()], None, None)
+                .unwrap_err()
+                .to_string()
+        );
+        assert_eq!(
+            "zone eth0 of target fe80::1 is not the interface eth1",
+            target_zone(&[link_local()], Some("eth0"), Some("eth1"))
+                .unwrap_err()
+                .to_string()
+        );
+        assert_eq!(
+            "zone eth0 given for target 2001:db8::1 which is not a link-local address",
+            target_zone(&["2001:db8::1".parse().unwrap()], Some("eth0"), None)
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn test_scope_id() {
+        assert_eq!(2, scope_id("2").unwrap());
+        assert!(scope_id("no-such-interface").is_err());
+    }
+
+    #[test]
+    fn test_format_addr() {
+        assert_eq!("fe80::1%eth0", format_addr(link_local(), Some("eth0")));
+        assert_eq!("fe80::1", format_addr(link_local(), None));
+    }
+}
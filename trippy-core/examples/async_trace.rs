@@ -0,0 +1,114 @@
+//! Trace several targets concurrently on a single tokio runtime.
+//!
+//! ```shell
+//! cargo run --example async_trace --features tokio -- 1.1.1.1 8.8.8.8 2606:4700:4700::1111
+//! ```
+//!
+//! This requires the `CAP_NET_RAW` capability on Linux, or root on other platforms.
+use anyhow::anyhow;
+use std::net::IpAddr;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use trippy_core::{
+    AsyncTracer, AsyncTracerChannel, MultipathStrategy, PortDirection, ProbeStatus, SourceAddr,
+    TracerAddrFamily, TracerChannelConfig, TracerConfig, TracerProtocol,
+};
+
+/// The number of rounds traced of each target.
+const ROUNDS: usize = 5;
+
+fn main() -> anyhow::Result<()> {
+    let targets = std::env::args()
+        .skip(1)
+        .map(|target| target.parse::<IpAddr>())
+        .collect::<Result<Vec<_>, _>>()?;
+    if targets.is_empty() {
+        return Err(anyhow!("usage: async_trace <addr>..."));
+    }
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    runtime.block_on(async {
+        let (tx, mut rx) = mpsc::channel(16);
+        let mut traces = vec![];
+        for (i, target) in targets.into_iter().enumerate() {
+            let identifier = 4000 + u16::try_from(i)?;
+            let channel = AsyncTracerChannel::connect(&channel_config(target, identifier)?)?;
+            let tracer = AsyncTracer::new(&tracer_config(target, identifier)?);
+            let (round_tx, mut round_rx) = mpsc::channel(1);
+            let tx = tx.clone();
+            traces.push(tokio::spawn(tracer.trace(channel, round_tx)));
+            tokio::spawn(async move {
+                while let Some(round) = round_rx.recv().await {
+                    let _ = tx.send((target, round)).await;
+                }
+            });
+        }
+        drop(tx);
+        while let Some((target, round)) = rx.recv().await {
+            let hops = round
+                .probes
+                .iter()
+                .filter(|probe| probe.ttl <= round.largest_ttl)
+                .map(|probe| match probe.status {
+                    ProbeStatus::Complete => format!(
+                        "{} ({:.1}ms)",
+                        probe.host.map_or(String::new(), |host| host.to_string()),
+                        probe.duration().as_secs_f64() * 1000.0
+                    ),
+                    _ => String::from("*"),
+                })
+                .collect::<Vec<_>>();
+            println!("{target}: {}", hops.join(" -> "));
+        }
+        for trace in traces {
+            trace.await??;
+        }
+        Ok(())
+    })
+}
+
+fn channel_config(target: IpAddr, identifier: u16) -> anyhow::Result<TracerChannelConfig> {
+    let addr_family = match target {
+        IpAddr::V4(_) => TracerAddrFamily::Ipv4,
+        IpAddr::V6(_) => TracerAddrFamily::Ipv6,
+    };
+    let source_addr = SourceAddr::discover(target, PortDirection::None, None, 0)?;
+    Ok(TracerChannelConfig::new(
+        TracerProtocol::Icmp,
+        addr_family,
+        source_addr,
+        target,
+        identifier,
+        84,
+        0,
+        0,
+        33000,
+        MultipathStrategy::Classic,
+        PortDirection::None,
+        Duration::from_millis(10),
+        Duration::from_secs(1),
+        None,
+        None,
+        None,
+    ))
+}
+
+fn tracer_config(target: IpAddr, identifier: u16) -> anyhow::Result<TracerConfig> {
+    Ok(TracerConfig::new(
+        target,
+        TracerProtocol::Icmp,
+        Some(ROUNDS - 1),
+        identifier,
+        1,
+        64,
+        Duration::from_millis(100),
+        24,
+        33000,
+        Duration::from_millis(10),
+        Duration::from_secs(1),
+        Duration::from_secs(1),
+        84,
+        0,
+    )?)
+}
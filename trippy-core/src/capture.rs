@@ -0,0 +1,41 @@
+use std::sync::mpsc::SyncSender;
+use std::time::SystemTime;
+
+/// A raw IP packet sent or received by the tracer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapturedPacket {
+    /// Timestamp when the packet was sent or received.
+    pub timestamp: SystemTime,
+    /// The packet, including the IP header.
+    pub data: Vec<u8>,
+}
+
+impl CapturedPacket {
+    #[must_use]
+    pub fn new(timestamp: SystemTime, data: Vec<u8>) -> Self {
+        Self { timestamp, data }
+    }
+}
+
+/// A sink for the raw IP packets sent and received by the tracer.
+///
+/// Packets are handed off to the receiver of a bounded channel without blocking, any packets which cannot be accepted
+/// because the receiver has fallen behind are dropped such that capturing never delays tracing.
+#[derive(Debug, Clone)]
+pub struct PacketCapture {
+    sender: SyncSender<CapturedPacket>,
+}
+
+impl PacketCapture {
+    #[must_use]
+    pub fn new(sender: SyncSender<CapturedPacket>) -> Self {
+        Self { sender }
+    }
+
+    /// Capture a packet which was sent or received at `timestamp`.
+    pub fn capture(&self, timestamp: SystemTime, data: &[u8]) {
+        self.sender
+            .try_send(CapturedPacket::new(timestamp, data.to_vec()))
+            .unwrap_or_default();
+    }
+}
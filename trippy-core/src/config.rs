@@ -0,0 +1,519 @@
+use crate::error::{TraceResult, TracerError};
+use crate::types::{
+    MaxInflight, MaxRounds, PacketSize, PayloadPattern, Port, Sequence, TimeToLive, TraceId,
+    TypeOfService,
+};
+use crate::{PacketCapture, PayloadStamp};
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// The maximum time-to-live value allowed.
+pub const MAX_TTL: u8 = 254;
+
+/// The maximum _starting_ sequence number allowed.
+///
+/// This ensures that there are sufficient sequence numbers available for at least one round.
+const MAX_SEQUENCE: u16 = u16::MAX - MAX_TTL as u16 - 1;
+
+/// The default number of ttl probed beyond that of the target each round.
+pub const DEFAULT_TTL_MARGIN: u8 = 1;
+
+/// The default size of the largest IP packet sent or received, which is raised to the packet size should it be larger.
+pub const DEFAULT_MAX_PACKET_SIZE: u16 = 1024;
+
+/// The largest IP packet allowed, such that an ICMP error quoting it in full fits within the largest IPv4 packet.
+pub const MAX_PACKET_SIZE: u16 = u16::MAX - 28;
+
+/// The default number of TCP probes awaiting connection which a channel holds at once.
+pub const DEFAULT_MAX_TCP_PROBES: usize = 256;
+
+/// The address family.
+#[derive(Debug, Copy, Clone)]
+pub enum TracerAddrFamily {
+    /// Internet Protocol V4
+    Ipv4,
+    /// Internet Protocol V6
+    Ipv6,
+}
+
+impl Display for TracerAddrFamily {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ipv4 => write!(f, "v4"),
+            Self::Ipv6 => write!(f, "v6"),
+        }
+    }
+}
+
+/// The tracing protocol.
+#[derive(Debug, Copy, Clone)]
+pub enum TracerProtocol {
+    /// Internet Control Message Protocol
+    Icmp,
+    /// User Datagram Protocol
+    Udp,
+    /// Transmission Control Protocol
+    Tcp,
+}
+
+impl Display for TracerProtocol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Icmp => write!(f, "icmp"),
+            Self::Udp => write!(f, "udp"),
+            Self::Tcp => write!(f, "tcp"),
+        }
+    }
+}
+
+/// The type of ICMP message sent as a probe by the ICMP protocol.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum IcmpProbeType {
+    /// An `EchoRequest`, answered by the target with an `EchoReply`.
+    Echo,
+    /// A `TimestampRequest`, answered by the target with a `TimestampReply`.
+    ///
+    /// This is only defined for IPv4, there being no timestamp messages for IPv6.
+    Timestamp,
+}
+
+impl Display for IcmpProbeType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Echo => write!(f, "echo"),
+            Self::Timestamp => write!(f, "timestamp"),
+        }
+    }
+}
+
+/// The [Equal-cost Multi-Path](https://en.wikipedia.org/wiki/Equal-cost_multi-path_routing) routing strategy.
+#[derive(Debug, Copy, Clone)]
+pub enum MultipathStrategy {
+    /// The src or dest port is used to store the sequence number.
+    ///
+    /// This does _not_ allow fixing both the src and dest port and so `PortDirection::Both` and `SequenceField::Port`
+    /// are mutually exclusive.
+    Classic,
+    /// The UDP `checksum` field is used to store the sequence number.
+    ///
+    /// a.k.a [`paris`](https://github.com/libparistraceroute/libparistraceroute/wiki/Checksum) traceroute approach.
+    ///
+    /// This requires that the UDP payload contains a well chosen value to ensure the UDP checksum remains valid for
+    /// the packet and therefore this cannot be used along with a custom payload pattern.
+    Paris,
+    /// The IP `identifier` field is used to store the sequence number.
+    ///
+    /// a.k.a [`dublin](https://github.com/insomniacslk/dublin-traceroute) traceroute approach.
+    ///
+    /// The allow either the src or dest or both ports to be fixed.
+    ///
+    /// If either of the src or dest port may vary (i.e. `PortDirection::FixedSrc` or `PortDirection::FixedDest`) then
+    /// the port number is set to be the `initial_sequence` plus the round number to ensure that there is a fixed
+    /// `flowid` (protocol, src ip/port, dest ip/port) for all packets in a given tracing round.  Each round may
+    /// therefore discover different paths.
+    ///
+    /// If both src and dest ports are fixed (i.e. `PortDirection::FixedBoth`) then every packet in every round will
+    /// share the same `flowid` and thus only a single path will be discovered.
+    Dublin,
+}
+
+impl Display for MultipathStrategy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Classic => write!(f, "classic"),
+            Self::Paris => write!(f, "paris"),
+            Self::Dublin => write!(f, "dublin"),
+        }
+    }
+}
+
+/// Whether to fix the src, dest or both ports for a trace.
+#[derive(Debug, Copy, Clone)]
+pub enum PortDirection {
+    /// Trace without any source or destination port (i.e. for ICMP tracing).
+    None,
+    /// Trace from a fixed source port to a variable destination port (i.e. 5000 -> *).
+    ///
+    /// This is the default direction for UDP tracing.
+    FixedSrc(Port),
+    /// Trace from a variable source port to a fixed destination port (i.e. * -> 80).
+    ///
+    /// This is the default direction for TCP tracing.
+    FixedDest(Port),
+    /// Trace from a fixed source port to a fixed destination port (i.e. 5000 -> 80).
+    ///
+    /// When both ports are fixed another element of the IP header is required to vary per probe such that probes can
+    /// be identified.  Typically this is only used for UDP, whereby the checksum is manipulated by adjusting the
+    /// payload and therefore used as the identifier.
+    ///
+    /// Note that this case is not currently implemented.
+    FixedBoth(Port, Port),
+}
+
+impl PortDirection {
+    #[must_use]
+    pub fn new_fixed_src(src: u16) -> Self {
+        Self::FixedSrc(Port(src))
+    }
+
+    #[must_use]
+    pub fn new_fixed_dest(dest: u16) -> Self {
+        Self::FixedDest(Port(dest))
+    }
+
+    #[must_use]
+    pub fn new_fixed_both(src: u16, dest: u16) -> Self {
+        Self::FixedBoth(Port(src), Port(dest))
+    }
+
+    #[must_use]
+    pub fn src(&self) -> Option<Port> {
+        match *self {
+            Self::FixedSrc(src) | Self::FixedBoth(src, _) => Some(src),
+            _ => None,
+        }
+    }
+    #[must_use]
+    pub fn dest(&self) -> Option<Port> {
+        match *self {
+            Self::FixedDest(dest) | Self::FixedBoth(_, dest) => Some(dest),
+            _ => None,
+        }
+    }
+}
+
+/// Tracer network channel configuration.
+#[derive(Debug, Clone)]
+pub struct TracerChannelConfig {
+    pub protocol: TracerProtocol,
+    pub addr_family: TracerAddrFamily,
+    pub source_addr: IpAddr,
+    pub target_addr: IpAddr,
+    /// The scope of an IPv6 target, the index of the interface through which a link-local target is reached, or zero.
+    pub scope_id: u32,
+    pub identifier: TraceId,
+    pub packet_size: PacketSize,
+    pub payload_pattern: PayloadPattern,
+    pub tos: TypeOfService,
+    pub initial_sequence: Sequence,
+    pub multipath_strategy: MultipathStrategy,
+    pub port_direction: PortDirection,
+    pub read_timeout: Duration,
+    /// The longest a TCP probe awaits connection, after which it is abandoned and so lost.
+    pub tcp_connect_timeout: Duration,
+    /// The most TCP probes awaiting connection at once, the oldest being abandoned, and so lost, to make room for
+    /// another.
+    ///
+    /// Probes of every round await connection until `tcp_connect_timeout`, and so this should be no fewer than the
+    /// `max_inflight` of the tracer, and more should the connect timeout outlast a round.
+    pub max_tcp_probes: usize,
+    pub capture: Option<PacketCapture>,
+    /// The network interface to which the sockets are bound, if any.
+    pub interface: Option<String>,
+    /// The firewall mark set on the sockets which send probes, if any.
+    pub fwmark: Option<u32>,
+    /// The type of ICMP message sent as a probe by the ICMP protocol.
+    pub icmp_probe_type: IcmpProbeType,
+    /// The size of the largest IP packet sent or received, from which the buffers of the channel are sized.
+    pub max_packet_size: PacketSize,
+    /// Write a `PayloadStamp` at the start of the payload of every probe.
+    pub payload_stamp: bool,
+}
+
+impl TracerChannelConfig {
+    #[allow(clippy::too_many_arguments)]
+    #[must_use]
+    pub fn new(
+        protocol: TracerProtocol,
+        addr_family: TracerAddrFamily,
+        source_addr: IpAddr,
+        target_addr: IpAddr,
+        identifier: u16,
+        packet_size: u16,
+        payload_pattern: u8,
+        tos: u8,
+        initial_sequence: u16,
+        multipath_strategy: MultipathStrategy,
+        port_direction: PortDirection,
+        read_timeout: Duration,
+        tcp_connect_timeout: Duration,
+        capture: Option<PacketCapture>,
+        interface: Option<String>,
+        fwmark: Option<u32>,
+    ) -> Self {
+        Self {
+            protocol,
+            addr_family,
+            source_addr,
+            target_addr,
+            scope_id: 0,
+            identifier: TraceId(identifier),
+            packet_size: PacketSize(packet_size),
+            payload_pattern: PayloadPattern(payload_pattern),
+            tos: TypeOfService(tos),
+            initial_sequence: Sequence(initial_sequence),
+            multipath_strategy,
+            port_direction,
+            read_timeout,
+            tcp_connect_timeout,
+            max_tcp_probes: DEFAULT_MAX_TCP_PROBES,
+            capture,
+            interface,
+            fwmark,
+            icmp_probe_type: IcmpProbeType::Echo,
+            max_packet_size: PacketSize(packet_size.max(DEFAULT_MAX_PACKET_SIZE)),
+            payload_stamp: false,
+        }
+    }
+
+    /// Reach the target within the scope `scope_id`, the index of the interface through which it is reached, as a
+    /// link-local IPv6 target may be reached through any interface.
+    #[must_use]
+    pub fn with_scope_id(self, scope_id: u32) -> Self {
+        Self { scope_id, ..self }
+    }
+
+    /// Hold no more than `max_tcp_probes` TCP probes awaiting connection at once, which must be at least one.
+    pub fn with_max_tcp_probes(self, max_tcp_probes: usize) -> TraceResult<Self> {
+        if max_tcp_probes == 0 {
+            return Err(TracerError::BadConfig(String::from(
+                "max_tcp_probes must be greater than zero",
+            )));
+        }
+        Ok(Self {
+            max_tcp_probes,
+            ..self
+        })
+    }
+
+    /// Send ICMP probes of `icmp_probe_type`, which must be `Echo` unless tracing IPv4 with the ICMP protocol.
+    pub fn with_icmp_probe_type(self, icmp_probe_type: IcmpProbeType) -> TraceResult<Self> {
+        match (icmp_probe_type, self.protocol, self.addr_family) {
+            (IcmpProbeType::Timestamp, TracerProtocol::Icmp, TracerAddrFamily::Ipv4)
+            | (IcmpProbeType::Echo, _, _) => Ok(Self {
+                icmp_probe_type,
+                ..self
+            }),
+            (IcmpProbeType::Timestamp, TracerProtocol::Icmp, TracerAddrFamily::Ipv6) => Err(
+                TracerError::BadConfig(String::from("ICMPv6 has no timestamp messages")),
+            ),
+            (IcmpProbeType::Timestamp, protocol, _) => Err(TracerError::BadConfig(format!(
+                "timestamp probes require the icmp protocol, not {protocol}"
+            ))),
+        }
+    }
+
+    /// Write a `PayloadStamp` at the start of the payload of every probe, should `payload_stamp` be set.
+    ///
+    /// Only `icmp` echo requests and `udp` datagrams have a payload, which must be large enough to hold the stamp.
+    pub fn with_payload_stamp(self, payload_stamp: bool) -> TraceResult<Self> {
+        if payload_stamp {
+            match (self.protocol, self.icmp_probe_type) {
+                (TracerProtocol::Tcp, _) => {
+                    return Err(TracerError::BadConfig(String::from(
+                        "tcp probes have no payload to stamp",
+                    )))
+                }
+                (TracerProtocol::Icmp, IcmpProbeType::Timestamp) => {
+                    return Err(TracerError::BadConfig(String::from(
+                        "timestamp probes have no payload to stamp",
+                    )))
+                }
+                _ => {}
+            }
+            let min_packet_size = PayloadStamp::min_packet_size(self.addr_family);
+            if usize::from(self.packet_size.0) < min_packet_size {
+                return Err(TracerError::BadConfig(format!(
+                    "packet_size ({}) must be at least {min_packet_size} to hold the payload stamp",
+                    self.packet_size.0
+                )));
+            }
+        }
+        Ok(Self {
+            payload_stamp,
+            ..self
+        })
+    }
+}
+
+/// Tracing algorithm configuration.
+#[derive(Debug, Clone)]
+pub struct TracerConfig {
+    pub target_addr: IpAddr,
+    /// The addresses of the target, one of which is probed in each round in turn, the first being `target_addr`.
+    pub target_addrs: Vec<IpAddr>,
+    pub protocol: TracerProtocol,
+    pub trace_identifier: TraceId,
+    pub max_rounds: Option<MaxRounds>,
+    pub first_ttl: TimeToLive,
+    pub max_ttl: TimeToLive,
+    /// Send every probe with the ttl `first_ttl`, which is also `max_ttl`, ending each round once it is answered.
+    pub fixed_ttl: bool,
+    pub grace_duration: Duration,
+    pub max_inflight: MaxInflight,
+    /// Adapt the number of probes in flight each round, between one and `max_inflight`, to the loss of the last.
+    pub adaptive_inflight: bool,
+    /// The number of ttl beyond that of the target, as of the last round, to probe each round, so as to notice should
+    /// the path to the target grow.
+    pub ttl_margin: TimeToLive,
+    pub initial_sequence: Sequence,
+    pub read_timeout: Duration,
+    pub min_round_duration: Duration,
+    pub max_round_duration: Duration,
+    /// The most by which the start of each round is offset, at random, such that rounds do not fall into step with
+    /// other periodic traffic.
+    pub round_jitter: Duration,
+    /// The seed from which the offsets of the start of each round are drawn.
+    pub round_jitter_seed: u64,
+    pub packet_size: PacketSize,
+    pub payload_pattern: PayloadPattern,
+}
+
+impl TracerConfig {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        target_addr: IpAddr,
+        protocol: TracerProtocol,
+        max_rounds: Option<usize>,
+        trace_identifier: u16,
+        first_ttl: u8,
+        max_ttl: u8,
+        grace_duration: Duration,
+        max_inflight: u8,
+        initial_sequence: u16,
+        read_timeout: Duration,
+        min_round_duration: Duration,
+        max_round_duration: Duration,
+        packet_size: u16,
+        payload_pattern: u8,
+    ) -> TraceResult<Self> {
+        if first_ttl > MAX_TTL {
+            return Err(TracerError::BadConfig(format!(
+                "first_ttl ({first_ttl}) > {MAX_TTL}"
+            )));
+        }
+        if max_ttl > MAX_TTL {
+            return Err(TracerError::BadConfig(format!(
+                "max_ttl ({first_ttl}) > {MAX_TTL}"
+            )));
+        }
+        if initial_sequence > MAX_SEQUENCE {
+            return Err(TracerError::BadConfig(format!(
+                "initial_sequence ({initial_sequence}) > {MAX_SEQUENCE}"
+            )));
+        }
+        Ok(Self {
+            target_addr,
+            target_addrs: vec![target_addr],
+            protocol,
+            trace_identifier: TraceId(trace_identifier),
+            max_rounds: max_rounds.map(MaxRounds),
+            first_ttl: TimeToLive(first_ttl),
+            max_ttl: TimeToLive(max_ttl),
+            fixed_ttl: false,
+            grace_duration,
+            max_inflight: MaxInflight(max_inflight),
+            adaptive_inflight: false,
+            ttl_margin: TimeToLive(DEFAULT_TTL_MARGIN),
+            initial_sequence: Sequence(initial_sequence),
+            read_timeout,
+            min_round_duration,
+            max_round_duration,
+            round_jitter: Duration::ZERO,
+            round_jitter_seed: 0,
+            packet_size: PacketSize(packet_size),
+            payload_pattern: PayloadPattern(payload_pattern),
+        })
+    }
+
+    /// Probe each of `target_addrs` in turn, one in each round, rather than only `target_addr`.
+    ///
+    /// The addresses must all be of the family of `target_addr`, as they are probed from the same sockets.
+    pub fn with_round_robin(self, target_addrs: Vec<IpAddr>) -> TraceResult<Self> {
+        let Some(&target_addr) = target_addrs.first() else {
+            return Err(TracerError::BadConfig(String::from(
+                "round robin requires at least one target address",
+            )));
+        };
+        if let Some(addr) = target_addrs
+            .iter()
+            .find(|addr| addr.is_ipv4() != target_addr.is_ipv4())
+        {
+            return Err(TracerError::BadConfig(format!(
+                "round robin target addresses must share a family, {addr} is not of the family of {target_addr}"
+            )));
+        }
+        Ok(Self {
+            target_addr,
+            target_addrs,
+            ..self
+        })
+    }
+
+    /// Adapt the number of probes in flight each round, between one and `max_inflight`, to the loss of the last.
+    ///
+    /// The window is halved after a round in which too many of the hops which have responded before did not respond
+    /// and grows by one after a round in which all of them did, such that the probes of a path which rate limits ICMP
+    /// are spread out whilst those of a clean path are sent as fast as `max_inflight` allows.
+    #[must_use]
+    pub fn with_adaptive_inflight(self) -> Self {
+        Self {
+            adaptive_inflight: true,
+            ..self
+        }
+    }
+
+    /// Offset the start of each round by a random duration of up to `round_jitter`, drawn from a generator seeded with
+    /// `seed`.
+    ///
+    /// Rounds which start at fixed intervals may fall into step with other periodic traffic or with the rate limiting
+    /// of a hop, showing loss which is an artefact of the timing of the probes.  The offset of a round does not count
+    /// towards its duration and so may be no longer than `max_round_duration`, such that a round and its offset
+    /// together last at most twice as long.
+    pub fn with_round_jitter(self, round_jitter: Duration, seed: u64) -> TraceResult<Self> {
+        if round_jitter > self.max_round_duration {
+            return Err(TracerError::BadConfig(format!(
+                "round_jitter ({round_jitter:?}) > max_round_duration ({:?})",
+                self.max_round_duration
+            )));
+        }
+        Ok(Self {
+            round_jitter,
+            round_jitter_seed: seed,
+            ..self
+        })
+    }
+
+    /// Send every probe with the ttl `ttl`, rather than probing each ttl from `first_ttl` to `max_ttl`.
+    ///
+    /// Each round sends a single probe to the hop at `ttl` and ends as soon as it is answered, once the minimum round
+    /// duration has elapsed, rather than awaiting the target until the maximum round duration, such that the hop is
+    /// sampled as often as `min_round_duration` allows.
+    pub fn with_fixed_ttl(self, ttl: u8) -> TraceResult<Self> {
+        if ttl == 0 || ttl > MAX_TTL {
+            return Err(TracerError::BadConfig(format!(
+                "fixed_ttl ({ttl}) must be in the range 1..={MAX_TTL}"
+            )));
+        }
+        Ok(Self {
+            first_ttl: TimeToLive(ttl),
+            max_ttl: TimeToLive(ttl),
+            fixed_ttl: true,
+            ..self
+        })
+    }
+
+    /// Probe `ttl_margin` ttl beyond that of the target each round, rather than `DEFAULT_TTL_MARGIN`.
+    ///
+    /// Once the target has responded no deeper ttl is probed in that round, and in the rounds which follow only those up
+    /// to the ttl of the target plus this margin are probed, such that a path which grows is noticed without sending
+    /// probes to every ttl up to `max_ttl` each round.
+    #[must_use]
+    pub fn with_ttl_margin(self, ttl_margin: u8) -> Self {
+        Self {
+            ttl_margin: TimeToLive(ttl_margin),
+            ..self
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use crate::net::platform;
+use crate::priority::TracerPriority;
+use crate::util::RequiredError;
+use std::error::Error;
+use std::fmt::Write;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use thiserror::Error;
+
+pub type TraceResult<T> = Result<T, TracerError>;
+
+#[derive(Error, Debug)]
+pub enum TracerError {
+    #[error("invalid packet size: {0}")]
+    InvalidPacketSize(usize),
+    #[error("packet of size {0} is too big to send, it exceeds the MTU of the interface and is not fragmented on this platform")]
+    PacketTooBig(usize),
+    #[error("unknown interface: {0}")]
+    UnknownInterface(String),
+    #[error("invalid config: {0}")]
+    BadConfig(String),
+    #[error("missing required field: {0}")]
+    Required(#[from] RequiredError),
+    #[error("IO error: {0}")]
+    IoError(#[from] io::Error),
+    #[error("insufficient buffer capacity")]
+    InsufficientCapacity,
+    #[error("address not available: {0}")]
+    AddressNotAvailable(SocketAddr),
+    #[error("invalid source IP address: {0}")]
+    InvalidSourceAddr(IpAddr),
+    #[error("failed to bind to interface {0}: {1}")]
+    BindInterface(String, io::Error),
+    #[error("setting fwmark {0} requires CAP_NET_ADMIN")]
+    FwmarkPermission(u32),
+    #[error("failed to set fwmark {0}: {1}")]
+    SetFwmark(u32, io::Error),
+    #[error("malformed packet: {0}")]
+    MalformedPacket(#[from] ParseError),
+    #[error("{0} is not supported on {1}")]
+    Unsupported(&'static str, &'static str),
+    #[error("failed to raise the priority of the tracer to {0}: {1}")]
+    SetPriority(TracerPriority, io::Error),
+    #[error("permission denied by {0}, tracing requires elevated privileges such as root or CAP_NET_RAW")]
+    PermissionDenied(&'static str, #[source] io::Error),
+    #[error("address {0} is already in use")]
+    AddressInUse(SocketAddr, #[source] io::Error),
+    #[error("network unreachable for {0}")]
+    NetworkUnreachable(IpAddr, #[source] io::Error),
+    #[error("failed to send probe to {0}")]
+    ProbeSendFailed(SocketAddr, #[source] io::Error),
+}
+
+impl TracerError {
+    /// Classify the `err` of the `syscall` made on a socket for `addr`, such that the common failures are told apart
+    /// rather than reported as a bare IO error.
+    #[must_use]
+    pub fn from_socket_error(syscall: &'static str, addr: SocketAddr, err: io::Error) -> Self {
+        match err.raw_os_error() {
+            Some(code) if platform::is_permission_denied_error(code) => {
+                Self::PermissionDenied(syscall, err)
+            }
+            Some(code) if platform::is_addr_in_use_error(code) => Self::AddressInUse(addr, err),
+            Some(code) if platform::is_net_unreachable_error(code) => {
+                Self::NetworkUnreachable(addr.ip(), err)
+            }
+            _ => Self::IoError(err),
+        }
+    }
+
+    /// May the trace continue past this error?
+    ///
+    /// A probe which could not be sent, as the network is briefly unreachable or out of buffers, or a read which was
+    /// interrupted, is no different to a probe lost in the network and so is retryable.  Any other error is fatal.
+    #[must_use]
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::NetworkUnreachable(_, _) => true,
+            Self::ProbeSendFailed(_, err) | Self::IoError(err) => is_transient(err),
+            _ => false,
+        }
+    }
+
+    /// Render the error for the user, followed by the error of the OS which caused it, if it is not already given.
+    #[must_use]
+    pub fn render(&self) -> String {
+        let mut rendered = self.to_string();
+        let mut source = self.source();
+        while let Some(err) = source {
+            let cause = err.to_string();
+            if !rendered.contains(&cause) {
+                let _ = write!(rendered, ": {cause}");
+            }
+            source = err.source();
+        }
+        rendered
+    }
+}
+
+/// Is `err` a failure which may not recur, such as an interrupted call or a lack of buffer space?
+fn is_transient(err: &io::Error) -> bool {
+    match err.kind() {
+        io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => true,
+        _ => err
+            .raw_os_error()
+            .is_some_and(platform::is_no_buffer_space_error),
+    }
+}
+
+/// Why a packet received could not be parsed.
+#[derive(Error, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The packet ended before the named header, or the fields of it which are read.
+    #[error("truncated {0}")]
+    Truncated(&'static str),
+    /// The named field holds a value which is not possible.
+    #[error("invalid {0}")]
+    Invalid(&'static str),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    const ADDR: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)), 33434);
+
+    #[cfg(unix)]
+    fn os_error(errno: nix::Error) -> io::Error {
+        io::Error::from_raw_os_error(errno as i32)
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_from_socket_error() {
+        let classify = |errno| TracerError::from_socket_error("sendto", ADDR, os_error(errno));
+        assert!(matches!(
+            classify(nix::Error::EPERM),
+            TracerError::PermissionDenied("sendto", _)
+        ));
+        assert!(matches!(
+            classify(nix::Error::EACCES),
+            TracerError::PermissionDenied(_, _)
+        ));
+        assert!(matches!(
+            classify(nix::Error::EADDRINUSE),
+            TracerError::AddressInUse(addr, _) if addr == ADDR
+        ));
+        assert!(matches!(
+            classify(nix::Error::ENETUNREACH),
+            TracerError::NetworkUnreachable(addr, _) if addr == ADDR.ip()
+        ));
+        assert!(matches!(
+            classify(nix::Error::EINVAL),
+            TracerError::IoError(_)
+        ));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_is_retryable() {
+        let classify = |errno| TracerError::from_socket_error("sendto", ADDR, os_error(errno));
+        assert!(classify(nix::Error::ENETUNREACH).is_retryable());
+        assert!(classify(nix::Error::EHOSTUNREACH).is_retryable());
+        assert!(classify(nix::Error::ENOBUFS).is_retryable());
+        assert!(classify(nix::Error::EINTR).is_retryable());
+        assert!(!classify(nix::Error::EPERM).is_retryable());
+        assert!(!classify(nix::Error::EADDRINUSE).is_retryable());
+        assert!(!classify(nix::Error::EINVAL).is_retryable());
+        assert!(TracerError::ProbeSendFailed(ADDR, os_error(nix::Error::ENOBUFS)).is_retryable());
+        assert!(!TracerError::ProbeSendFailed(ADDR, os_error(nix::Error::EINVAL)).is_retryable());
+        assert!(!TracerError::PacketTooBig(1500).is_retryable());
+        assert!(!TracerError::InsufficientCapacity.is_retryable());
+    }
+
+    #[test]
+    fn test_render() {
+        let err = TracerError::ProbeSendFailed(ADDR, io::Error::other("no route"));
+        assert_eq!("failed to send probe to 192.0.2.1:33434", err.to_string());
+        assert_eq!(
+            "failed to send probe to 192.0.2.1:33434: no route",
+            err.render()
+        );
+        let err = TracerError::PermissionDenied("socket", io::Error::other("not permitted"));
+        assert_eq!(
+            "permission denied by socket, tracing requires elevated privileges such as root or CAP_NET_RAW: not permitted",
+            err.render()
+        );
+        let err = TracerError::IoError(io::Error::other("broken pipe"));
+        assert_eq!("IO error: broken pipe", err.render());
+        assert_eq!(
+            "invalid packet size: 9",
+            TracerError::InvalidPacketSize(9).render()
+        );
+    }
+}
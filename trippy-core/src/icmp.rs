@@ -0,0 +1,192 @@
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+
+/// An ICMP destination unreachable response received for a probe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Unreachable {
+    /// Was the response `ICMPv6`, for which the codes have a different meaning to `ICMPv4`?
+    ipv6: bool,
+    /// The ICMP code.
+    code: u8,
+    /// The next-hop MTU of a fragmentation needed response, if given.
+    next_hop_mtu: Option<u16>,
+}
+
+impl Unreachable {
+    /// A response with ICMP `code` from `addr`, the family of which determines the meaning of the code.
+    #[must_use]
+    pub fn new(addr: IpAddr, code: u8, next_hop_mtu: Option<u16>) -> Self {
+        Self {
+            ipv6: addr.is_ipv6(),
+            code,
+            next_hop_mtu,
+        }
+    }
+
+    /// The `traceroute` style annotation of the response, i.e. `!H` for host unreachable.
+    ///
+    /// No annotation is given for port unreachable, which is the expected response from the target for `UDP` probes,
+    /// and codes without a conventional annotation are shown as `!<code>`.
+    #[must_use]
+    pub fn annotation(self) -> Option<String> {
+        let annotation = match (self.ipv6, self.code) {
+            (false, 3) | (true, 4) => return None,
+            (false, 0 | 6) | (true, 0) => "!N",
+            (false, 1 | 7) | (true, 3) => "!H",
+            (false, 2) => "!P",
+            (false, 4) => {
+                return Some(
+                    self.next_hop_mtu
+                        .map_or_else(|| String::from("!F"), |mtu| format!("!F {mtu}")),
+                )
+            }
+            (false, 5) => "!S",
+            (false, 9 | 10 | 13) | (true, 1) => "!X",
+            (false, 14) => "!V",
+            (false, 15) => "!C",
+            (_, code) => return Some(format!("!<{code}>")),
+        };
+        Some(String::from(annotation))
+    }
+
+    /// The description of the code of the response from RFC 792 and RFC 1812 for `ICMPv4` and RFC 4443 for `ICMPv6`.
+    #[must_use]
+    pub fn description(self) -> &'static str {
+        match (self.ipv6, self.code) {
+            (false, 0) => "network unreachable",
+            (false, 1) => "host unreachable",
+            (false, 2) => "protocol unreachable",
+            (false, 3) | (true, 4) => "port unreachable",
+            (false, 4) => "fragmentation needed",
+            (false, 5) => "source route failed",
+            (false, 6) => "destination network unknown",
+            (false, 7) => "destination host unknown",
+            (false, 8) => "source host isolated",
+            (false, 9) => "network administratively prohibited",
+            (false, 10) => "host administratively prohibited",
+            (false, 11) => "network unreachable for type of service",
+            (false, 12) => "host unreachable for type of service",
+            (false, 13) | (true, 1) => "communication administratively prohibited",
+            (false, 14) => "host precedence violation",
+            (false, 15) => "precedence cutoff in effect",
+            (true, 0) => "no route to destination",
+            (true, 2) => "beyond scope of source address",
+            (true, 3) => "address unreachable",
+            (true, 5) => "source address failed ingress/egress policy",
+            (true, 6) => "reject route to destination",
+            (true, 7) => "error in source routing header",
+            _ => "unknown code",
+        }
+    }
+}
+
+impl Display for Unreachable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (code {})", self.description(), self.code)?;
+        if let Some(mtu) = self.next_hop_mtu {
+            write!(f, " mtu {mtu}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    const V4: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    const V6: IpAddr = IpAddr::V6(Ipv6Addr::LOCALHOST);
+
+    fn annotations(addr: IpAddr, codes: std::ops::RangeInclusive<u8>) -> Vec<Option<String>> {
+        codes
+            .map(|code| Unreachable::new(addr, code, None).annotation())
+            .collect()
+    }
+
+    fn descriptions(addr: IpAddr, codes: std::ops::RangeInclusive<u8>) -> Vec<&'static str> {
+        codes
+            .map(|code| Unreachable::new(addr, code, None).description())
+            .collect()
+    }
+
+    #[test]
+    fn test_annotation_ipv4() {
+        let expected = [
+            "!N", "!H", "!P", "", "!F", "!S", "!N", "!H", "!<8>", "!X", "!X", "!<11>", "!<12>",
+            "!X", "!V", "!C", "!<16>",
+        ];
+        let expected: Vec<_> = expected
+            .iter()
+            .map(|a| (!a.is_empty()).then(|| String::from(*a)))
+            .collect();
+        assert_eq!(expected, annotations(V4, 0..=16));
+    }
+
+    #[test]
+    fn test_annotation_ipv6() {
+        let expected = ["!N", "!X", "!<2>", "!H", "", "!<5>", "!<6>", "!<7>", "!<8>"];
+        let expected: Vec<_> = expected
+            .iter()
+            .map(|a| (!a.is_empty()).then(|| String::from(*a)))
+            .collect();
+        assert_eq!(expected, annotations(V6, 0..=8));
+    }
+
+    #[test]
+    fn test_annotation_fragmentation_needed() {
+        assert_eq!(
+            Some(String::from("!F 1400")),
+            Unreachable::new(V4, 4, Some(1400)).annotation()
+        );
+        assert_eq!(
+            "fragmentation needed (code 4) mtu 1400",
+            Unreachable::new(V4, 4, Some(1400)).to_string()
+        );
+    }
+
+    #[test]
+    fn test_description_ipv4() {
+        assert_eq!(
+            vec![
+                "network unreachable",
+                "host unreachable",
+                "protocol unreachable",
+                "port unreachable",
+                "fragmentation needed",
+                "source route failed",
+                "destination network unknown",
+                "destination host unknown",
+                "source host isolated",
+                "network administratively prohibited",
+                "host administratively prohibited",
+                "network unreachable for type of service",
+                "host unreachable for type of service",
+                "communication administratively prohibited",
+                "host precedence violation",
+                "precedence cutoff in effect",
+                "unknown code",
+            ],
+            descriptions(V4, 0..=16)
+        );
+    }
+
+    #[test]
+    fn test_description_ipv6() {
+        assert_eq!(
+            vec![
+                "no route to destination",
+                "communication administratively prohibited",
+                "beyond scope of source address",
+                "address unreachable",
+                "port unreachable",
+                "source address failed ingress/egress policy",
+                "reject route to destination",
+                "error in source routing header",
+                "unknown code",
+            ],
+            descriptions(V6, 0..=8)
+        );
+    }
+}
@@ -0,0 +1,61 @@
+#![doc = include_str!("../README.md")]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, rust_2018_idioms)]
+#![allow(
+    clippy::module_name_repetitions,
+    clippy::option_if_let_else,
+    clippy::missing_const_for_fn,
+    clippy::cast_possible_truncation,
+    clippy::missing_errors_doc
+)]
+#![deny(unsafe_code)]
+
+mod capture;
+mod config;
+mod error;
+mod icmp;
+mod net;
+mod priority;
+mod probe;
+mod trace;
+mod tracer;
+mod types;
+mod util;
+
+/// Packet wire formats.
+pub mod packet;
+
+pub use capture::{CapturedPacket, PacketCapture};
+pub use config::{
+    IcmpProbeType, MultipathStrategy, PortDirection, TracerAddrFamily, TracerChannelConfig,
+    TracerConfig, TracerProtocol, DEFAULT_MAX_PACKET_SIZE, DEFAULT_MAX_TCP_PROBES,
+    DEFAULT_TTL_MARGIN, MAX_PACKET_SIZE,
+};
+pub use error::{ParseError, TraceResult, TracerError};
+pub use icmp::Unreachable;
+#[cfg(all(feature = "tokio", unix))]
+pub use net::async_channel::AsyncTracerChannel;
+pub use net::channel::TracerChannel;
+pub use net::source::{interface_addr, interface_index, SourceAddr};
+#[cfg(feature = "tokio")]
+pub use net::AsyncNetwork;
+pub use net::{
+    capabilities, parse_ipv4_icmp_probe, parse_ipv6_icmp_probe, Attribution, Capabilities,
+    ChannelMetrics, Network, PayloadStamp, Support,
+};
+pub use priority::{set_thread_priority, TracerPriority};
+pub use probe::{
+    FlowKey, IcmpPacketType, IcmpQuote, IcmpTimestamps, Probe, ProbeResponse, ProbeResponseData,
+    ProbeStatus, ResponderPorts, SendFailure,
+};
+pub use trace::{
+    Convergence, Endpoint, Flow, Hop, HopAddr, HopDiff, HopLink, PathChange, PathDiff,
+    PathStability, RateLimitHeuristic, SharedTrace, Trace, MAX_FLOWS, MAX_FLOW_KEYS, MAX_HOPS,
+    SHORTER_PATH_ROUNDS,
+};
+#[cfg(feature = "tokio")]
+pub use tracer::{AsyncTracer, OwnedTracerRound};
+pub use tracer::{CancellationToken, CompletionReason, RoundParameters, Tracer, TracerRound};
+pub use types::{MaxInflight, Round, Sequence, TimeToLive};
+
+/// The target of the `tracing` events which record the value of a counter, named by the field of the event.
+pub const COUNTER_TARGET: &str = "trippy::counter";
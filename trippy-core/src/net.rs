@@ -0,0 +1,110 @@
+use crate::error::TraceResult;
+use crate::probe::{FlowKey, ProbeResponse};
+use crate::Probe;
+#[cfg(feature = "tokio")]
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// IPv4 implementation.
+mod ipv4;
+
+/// IPv6 implementation.
+mod ipv6;
+
+/// Platform specific network code.
+pub mod platform;
+
+/// A network socket.
+mod socket;
+
+/// Reading the datagrams quoted in ICMP errors.
+mod quoted;
+
+/// The counts of the packets received by a channel.
+mod metrics;
+
+/// The stamp written at the start of the payload of a probe.
+mod stamp;
+
+/// Reference implementations against which the packets built are checked.
+#[cfg(test)]
+mod reference;
+
+/// A channel for sending and receiving probes.
+pub mod channel;
+
+/// A channel for sending and receiving probes on a tokio runtime.
+#[cfg(all(feature = "tokio", unix))]
+pub mod async_channel;
+
+/// Determine the source address.
+pub mod source;
+
+pub use ipv4::parse_icmp_probe as parse_ipv4_icmp_probe;
+pub use ipv6::parse_icmp_probe as parse_ipv6_icmp_probe;
+pub use metrics::{Attribution, ChannelMetrics};
+pub use platform::{capabilities, Capabilities, Support};
+pub use stamp::PayloadStamp;
+
+/// An abstraction over a network interface for tracing.
+pub trait Network {
+    /// Send a `Probe`.
+    fn send_probe(&mut self, probe: Probe) -> TraceResult<()>;
+
+    /// Receive the next Icmp packet and return a `ProbeResponse`.
+    ///
+    /// Returns `None` if the read times out or the packet read is not one of the types expected.
+    fn recv_probe(&mut self) -> TraceResult<Option<ProbeResponse>>;
+
+    /// Receive the next Icmp packet and return a `ProbeResponse`, waiting no longer than `timeout` for one.
+    ///
+    /// The tracer waits only until its next probe is due, such that the probe is not delayed by the wait.  By default
+    /// the wait is that of `recv_probe`.
+    fn recv_probe_within(&mut self, _timeout: Duration) -> TraceResult<Option<ProbeResponse>> {
+        self.recv_probe()
+    }
+
+    /// Record whether the tracer attributed the last response received to a probe.
+    ///
+    /// By default the attribution is not recorded.
+    fn record_attribution(&mut self, _attribution: Attribution) {}
+
+    /// The counts of the packets received, if they are recorded.
+    fn metrics(&self) -> Option<ChannelMetrics> {
+        None
+    }
+
+    /// The flow key of the first probe sent in the latest round, if it is recorded.
+    fn flow_key(&self) -> Option<FlowKey> {
+        None
+    }
+}
+
+/// An abstraction over a network interface for tracing on a tokio runtime.
+#[cfg(feature = "tokio")]
+pub trait AsyncNetwork {
+    /// Send a `Probe`, without blocking.
+    fn send_probe(&mut self, probe: Probe) -> TraceResult<()>;
+
+    /// Poll for the next Icmp packet and return a `ProbeResponse`.
+    ///
+    /// Returns `None` if the packet read is not one of the types expected, or `Poll::Pending` if no packet has been
+    /// received, in which case the task of `cx` may be woken once one has.
+    fn poll_recv_probe(&mut self, cx: &mut Context<'_>)
+        -> Poll<TraceResult<Option<ProbeResponse>>>;
+
+    /// Record whether the tracer attributed the last response received to a probe.
+    ///
+    /// By default the attribution is not recorded.
+    fn record_attribution(&mut self, _attribution: Attribution) {}
+
+    /// The counts of the packets received, if they are recorded.
+    fn metrics(&self) -> Option<ChannelMetrics> {
+        None
+    }
+
+    /// The flow key of the first probe sent in the latest round, if it is recorded.
+    fn flow_key(&self) -> Option<FlowKey> {
+        None
+    }
+}
@@ -0,0 +1,95 @@
+use crate::error::TraceResult;
+use crate::net::channel::TracerChannel;
+use crate::net::socket::TracerSocket as _;
+use crate::net::{AsyncNetwork, Attribution, ChannelMetrics, Network};
+use crate::probe::{FlowKey, ProbeResponse};
+use crate::{Probe, TracerChannelConfig};
+use std::future::Future;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::unix::AsyncFd;
+use tokio::io::Interest;
+use tokio::time::{Instant, Sleep};
+
+/// How often the sockets of the `tcp` probes are checked for a connection whilst any are connecting.
+const TCP_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// A channel for sending and receiving `Probe` packets on a tokio runtime.
+///
+/// The socket on which responses are received is registered with the runtime, such that the trace is woken once a
+/// response arrives.  The sockets of `tcp` probes are not registered, as they are opened and closed by the channel
+/// for every probe, and so are checked every `TCP_POLL_INTERVAL` whilst any are connecting, which may add as much to
+/// the round trip time of a `tcp` probe.
+pub struct AsyncTracerChannel {
+    /// The registration of the receive socket, which is dropped before the channel that owns the socket.
+    recv: AsyncFd<RawFd>,
+    tcp_poll: Pin<Box<Sleep>>,
+    channel: TracerChannel,
+}
+
+impl AsyncTracerChannel {
+    /// Create an `AsyncTracerChannel`, which must be within the context of a tokio runtime.
+    ///
+    /// This operation requires the `CAP_NET_RAW` capability on Linux.
+    pub fn connect(config: &TracerChannelConfig) -> TraceResult<Self> {
+        let channel = TracerChannel::connect(config)?;
+        let recv = AsyncFd::with_interest(channel.recv_socket().as_raw_fd(), Interest::READABLE)?;
+        Ok(Self {
+            recv,
+            tcp_poll: Box::pin(tokio::time::sleep(Duration::ZERO)),
+            channel,
+        })
+    }
+
+    /// The number of ICMP packets received which were too short, or too mangled, to be matched to a probe.
+    #[must_use]
+    pub fn malformed_packets(&self) -> u64 {
+        self.channel.malformed_packets()
+    }
+}
+
+impl AsyncNetwork for AsyncTracerChannel {
+    fn send_probe(&mut self, probe: Probe) -> TraceResult<()> {
+        self.channel.send_probe(probe)
+    }
+
+    fn record_attribution(&mut self, attribution: Attribution) {
+        self.channel.record_attribution(attribution);
+    }
+
+    fn metrics(&self) -> Option<ChannelMetrics> {
+        self.channel.metrics()
+    }
+
+    fn flow_key(&self) -> Option<FlowKey> {
+        self.channel.flow_key()
+    }
+
+    fn poll_recv_probe(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<TraceResult<Option<ProbeResponse>>> {
+        if self.channel.tcp_connecting() {
+            if let Some(response) = self.channel.recv_tcp_sockets()? {
+                return Poll::Ready(Ok(Some(response)));
+            }
+        }
+        while let Poll::Ready(guard) = self.recv.poll_read_ready(cx) {
+            let mut guard = guard?;
+            if self.channel.recv_socket().is_readable(Duration::ZERO)? {
+                return Poll::Ready(self.channel.recv_icmp_probe(true));
+            }
+            guard.clear_ready();
+        }
+        if self.channel.tcp_connecting() {
+            while self.tcp_poll.as_mut().poll(cx).is_ready() {
+                self.tcp_poll
+                    .as_mut()
+                    .reset(Instant::now() + TCP_POLL_INTERVAL);
+            }
+        }
+        Poll::Pending
+    }
+}
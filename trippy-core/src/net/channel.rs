@@ -0,0 +1,781 @@
+use crate::error::{TraceResult, TracerError};
+use crate::net::platform::Socket;
+use crate::net::socket::{bind_interface, set_fwmark, TracerSocket as _};
+use crate::net::{ipv4, ipv6, platform, Attribution, ChannelMetrics, Network};
+use crate::probe::{FlowKey, ProbeResponse};
+use crate::types::{PacketSize, PayloadPattern, Round, Sequence, TraceId, TypeOfService};
+use crate::{
+    IcmpProbeType, MultipathStrategy, PacketCapture, PortDirection, Probe, TracerAddrFamily,
+    TracerChannelConfig, TracerProtocol, COUNTER_TARGET, MAX_PACKET_SIZE,
+};
+use ::tracing::{debug, trace, trace_span};
+use itertools::Itertools;
+use std::collections::VecDeque;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+/// The most bytes of an ICMP error received ahead of the probe it quotes, an IPv4 header with options and an ICMP
+/// header.
+const MAX_ERROR_HEADER_SIZE: usize = 60 + 8;
+
+/// A channel for sending and receiving `Probe` packets.
+pub struct TracerChannel {
+    protocol: TracerProtocol,
+    src_addr: IpAddr,
+    ipv4_length_order: platform::PlatformIpv4FieldByteOrder,
+    dest_addr: IpAddr,
+    scope_id: u32,
+    identifier: TraceId,
+    packet_size: PacketSize,
+    payload_pattern: PayloadPattern,
+    payload_stamp: bool,
+    tos: TypeOfService,
+    initial_sequence: Sequence,
+    multipath_strategy: MultipathStrategy,
+    port_direction: PortDirection,
+    read_timeout: Duration,
+    tcp_connect_timeout: Duration,
+    capture: Option<PacketCapture>,
+    interface: Option<String>,
+    fwmark: Option<u32>,
+    icmp_probe_type: IcmpProbeType,
+    icmp_send_socket: Socket,
+    udp_send_socket: Socket,
+    recv_socket: Socket,
+    tcp_probes: TcpProbes,
+    buffers: PacketBuffers,
+    metrics: ChannelMetrics,
+    /// The flow key of the first probe sent in the latest round.
+    flow_key: Option<(Round, FlowKey)>,
+}
+
+/// The buffers in which probes are built and responses are received.
+///
+/// These are sized from the maximum packet size of the channel, rather than a constant, and so are allocated on the
+/// heap, once, as the channel is connected.
+#[derive(Debug)]
+pub struct PacketBuffers {
+    /// The IP packet of a probe.
+    pub ip: Vec<u8>,
+    /// The ICMP or UDP datagram of a probe.
+    pub datagram: Vec<u8>,
+    /// A packet received, which may be an ICMP error quoting a probe of the maximum size in full.
+    pub recv: Vec<u8>,
+}
+
+impl PacketBuffers {
+    pub fn new(max_packet_size: usize) -> Self {
+        Self {
+            ip: vec![0; max_packet_size],
+            datagram: vec![0; max_packet_size],
+            recv: vec![0; max_packet_size + MAX_ERROR_HEADER_SIZE],
+        }
+    }
+}
+
+impl TracerChannel {
+    /// Create an `IcmpChannel`.
+    ///
+    /// This operation requires the `CAP_NET_RAW` capability on Linux.
+    pub fn connect(config: &TracerChannelConfig) -> TraceResult<Self> {
+        if config.packet_size.0 > config.max_packet_size.0 {
+            return Err(TracerError::InvalidPacketSize(usize::from(
+                config.packet_size.0,
+            )));
+        }
+        if config.max_packet_size.0 > MAX_PACKET_SIZE {
+            return Err(TracerError::InvalidPacketSize(usize::from(
+                config.max_packet_size.0,
+            )));
+        }
+        platform::startup()?;
+        let ipv4_length_order =
+            platform::PlatformIpv4FieldByteOrder::for_address(config.source_addr)?;
+        let interface = config.interface.as_deref();
+        let icmp_send_socket = make_icmp_send_socket(config.source_addr, interface, config.fwmark)?;
+        let udp_send_socket = make_udp_send_socket(config.source_addr, interface, config.fwmark)?;
+        let recv_socket = make_recv_socket(config.source_addr, interface)?;
+        Ok(Self {
+            protocol: config.protocol,
+            src_addr: config.source_addr,
+            ipv4_length_order,
+            dest_addr: config.target_addr,
+            scope_id: config.scope_id,
+            identifier: config.identifier,
+            packet_size: config.packet_size,
+            payload_pattern: config.payload_pattern,
+            payload_stamp: config.payload_stamp,
+            tos: config.tos,
+            initial_sequence: config.initial_sequence,
+            multipath_strategy: config.multipath_strategy,
+            port_direction: config.port_direction,
+            read_timeout: config.read_timeout,
+            tcp_connect_timeout: config.tcp_connect_timeout,
+            capture: config.capture.clone(),
+            interface: config.interface.clone(),
+            fwmark: config.fwmark,
+            icmp_probe_type: config.icmp_probe_type,
+            icmp_send_socket,
+            udp_send_socket,
+            recv_socket,
+            tcp_probes: TcpProbes::new(config.max_tcp_probes),
+            buffers: PacketBuffers::new(usize::from(config.max_packet_size.0)),
+            metrics: ChannelMetrics::default(),
+            flow_key: None,
+        })
+    }
+
+    /// The number of ICMP packets received which were too short, or too mangled, to be matched to a probe.
+    #[must_use]
+    pub fn malformed_packets(&self) -> u64 {
+        self.metrics.malformed
+    }
+
+    /// The socket on which the `ICMP` responses are received.
+    #[cfg(all(feature = "tokio", unix))]
+    pub(super) fn recv_socket(&self) -> &Socket {
+        &self.recv_socket
+    }
+
+    /// Are any `TCP` probes awaiting a connection?
+    #[cfg(all(feature = "tokio", unix))]
+    pub(super) fn tcp_connecting(&self) -> bool {
+        !self.tcp_probes.is_empty()
+    }
+}
+
+impl Network for TracerChannel {
+    fn send_probe(&mut self, probe: Probe) -> TraceResult<()> {
+        let _span = trace_span!(
+            "channel.send_probe",
+            ttl = probe.ttl.0,
+            sequence = probe.sequence.0
+        )
+        .entered();
+        let sent = match self.protocol {
+            TracerProtocol::Icmp => self.dispatch_icmp_probe(probe),
+            TracerProtocol::Udp => self.dispatch_udp_probe(probe),
+            TracerProtocol::Tcp => self.dispatch_tcp_probe(probe),
+        };
+        sent.map(|flow_key| self.record_flow_key(probe.round, flow_key))
+    }
+
+    fn recv_probe(&mut self) -> TraceResult<Option<ProbeResponse>> {
+        self.recv_probe_within(self.read_timeout)
+    }
+
+    fn record_attribution(&mut self, attribution: Attribution) {
+        self.metrics.count_attribution(attribution);
+    }
+
+    fn metrics(&self) -> Option<ChannelMetrics> {
+        Some(self.metrics)
+    }
+
+    fn flow_key(&self) -> Option<FlowKey> {
+        self.flow_key.map(|(_, flow_key)| flow_key)
+    }
+
+    /// Wait for a response no longer than `timeout`, nor the read timeout of the channel, waking as soon as the
+    /// receive socket is readable or, for `tcp`, as soon as any probe has connected or been refused.
+    fn recv_probe_within(&mut self, timeout: Duration) -> TraceResult<Option<ProbeResponse>> {
+        let _span = trace_span!("channel.recv_probe").entered();
+        let timeout = timeout.min(self.read_timeout);
+        match self.protocol {
+            TracerProtocol::Icmp | TracerProtocol::Udp => {
+                let readable = trace_span!("channel.poll")
+                    .in_scope(|| self.recv_socket.is_readable(timeout))?;
+                self.recv_icmp_probe(readable)
+            }
+            TracerProtocol::Tcp => {
+                let ready = trace_span!("channel.poll").in_scope(|| {
+                    Socket::wait_ready(
+                        &self.recv_socket,
+                        self.tcp_probes.iter().map(|probe| &probe.socket),
+                        timeout,
+                    )
+                })?;
+                if let Some(response) = self.recv_tcp_sockets()? {
+                    return Ok(Some(response));
+                }
+                let readable = ready && self.recv_socket.is_readable(Duration::ZERO)?;
+                self.recv_icmp_probe(readable)
+            }
+        }
+    }
+}
+
+impl TracerChannel {
+    /// Record the `flow_key` of a probe sent in `round`, should it be the first of the round.
+    fn record_flow_key(&mut self, round: Round, flow_key: FlowKey) {
+        if !matches!(self.flow_key, Some((recorded, _)) if recorded == round) {
+            self.flow_key = Some((round, flow_key));
+        }
+    }
+
+    /// Dispatch a ICMP probe.
+    fn dispatch_icmp_probe(&mut self, probe: Probe) -> TraceResult<FlowKey> {
+        match (self.src_addr, probe.target.unwrap_or(self.dest_addr)) {
+            (IpAddr::V4(src_addr), IpAddr::V4(dest_addr)) => ipv4::dispatch_icmp_probe(
+                &mut self.icmp_send_socket,
+                &mut self.buffers,
+                probe,
+                src_addr,
+                dest_addr,
+                self.identifier,
+                self.icmp_probe_type,
+                self.packet_size,
+                self.payload_pattern,
+                self.payload_stamp,
+                self.ipv4_length_order,
+                self.capture.as_ref(),
+            ),
+            (IpAddr::V6(src_addr), IpAddr::V6(dest_addr)) => ipv6::dispatch_icmp_probe(
+                &mut self.icmp_send_socket,
+                &mut self.buffers,
+                probe,
+                src_addr,
+                dest_addr,
+                self.scope_id,
+                self.identifier,
+                self.packet_size,
+                self.payload_pattern,
+                self.payload_stamp,
+                self.capture.as_ref(),
+            ),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Dispatch a UDP probe.
+    fn dispatch_udp_probe(&mut self, probe: Probe) -> TraceResult<FlowKey> {
+        match (self.src_addr, probe.target.unwrap_or(self.dest_addr)) {
+            (IpAddr::V4(src_addr), IpAddr::V4(dest_addr)) => ipv4::dispatch_udp_probe(
+                &mut self.udp_send_socket,
+                &mut self.buffers,
+                probe,
+                src_addr,
+                dest_addr,
+                self.initial_sequence,
+                self.multipath_strategy,
+                self.port_direction,
+                self.packet_size,
+                self.payload_pattern,
+                self.payload_stamp,
+                self.ipv4_length_order,
+                self.capture.as_ref(),
+            ),
+            (IpAddr::V6(src_addr), IpAddr::V6(dest_addr)) => ipv6::dispatch_udp_probe(
+                &mut self.udp_send_socket,
+                &mut self.buffers,
+                probe,
+                src_addr,
+                dest_addr,
+                self.scope_id,
+                self.port_direction,
+                self.packet_size,
+                self.payload_pattern,
+                self.payload_stamp,
+                self.capture.as_ref(),
+            ),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Dispatch a TCP probe.
+    fn dispatch_tcp_probe(&mut self, probe: Probe) -> TraceResult<FlowKey> {
+        let dest_addr = probe.target.unwrap_or(self.dest_addr);
+        let (socket, flow_key) = match (self.src_addr, dest_addr) {
+            (IpAddr::V4(src_addr), IpAddr::V4(dest_addr)) => ipv4::dispatch_tcp_probe(
+                probe,
+                src_addr,
+                dest_addr,
+                self.port_direction,
+                self.tos,
+                self.interface.as_deref(),
+                self.fwmark,
+            ),
+            (IpAddr::V6(src_addr), IpAddr::V6(dest_addr)) => ipv6::dispatch_tcp_probe(
+                probe,
+                src_addr,
+                dest_addr,
+                self.scope_id,
+                self.port_direction,
+                self.interface.as_deref(),
+                self.fwmark,
+            ),
+            _ => unreachable!(),
+        }?;
+        // The port probed, as chosen by `dispatch_tcp_probe`, against which the port of the peer is recorded.
+        let dest_port = match self.port_direction {
+            PortDirection::FixedDest(dest_port) => dest_port.0,
+            _ => probe.sequence.0,
+        };
+        let evicted = self.tcp_probes.push(TcpProbe::new(
+            socket,
+            probe.sequence,
+            SocketAddr::new(dest_addr, dest_port),
+            Instant::now(),
+        ));
+        if let Some(evicted) = evicted {
+            self.metrics.tcp_evicted += 1;
+            debug!(
+                sequence = evicted.sequence.0,
+                max_tcp_probes = self.tcp_probes.capacity(),
+                "evicting the oldest tcp probe awaiting connection"
+            );
+            trace!(target: COUNTER_TARGET, tcp_evicted = self.metrics.tcp_evicted);
+        }
+        Ok(flow_key)
+    }
+
+    /// Generate a `ProbeResponse` for the next available ICMP packet, if the receive socket is `readable`.
+    ///
+    /// Every packet read is counted, and a packet which is malformed is otherwise ignored.
+    pub(super) fn recv_icmp_probe(&mut self, readable: bool) -> TraceResult<Option<ProbeResponse>> {
+        if readable {
+            let response = match self.src_addr {
+                IpAddr::V4(_) => ipv4::recv_icmp_probe(
+                    &mut self.recv_socket,
+                    &mut self.buffers.recv,
+                    self.protocol,
+                    self.multipath_strategy,
+                    self.port_direction,
+                    self.capture.as_ref(),
+                ),
+                IpAddr::V6(src_addr) => ipv6::recv_icmp_probe(
+                    &mut self.recv_socket,
+                    &mut self.buffers.recv,
+                    self.protocol,
+                    self.port_direction,
+                    src_addr,
+                    self.capture.as_ref(),
+                ),
+            };
+            self.metrics.count_packet(&response);
+            match response {
+                Err(TracerError::MalformedPacket(_)) => {
+                    trace!(target: COUNTER_TARGET, malformed_packets = self.metrics.malformed);
+                    Ok(None)
+                }
+                response => skip_retryable(response, None),
+            }
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Generate synthetic `ProbeResponse` if a TCP socket is connected or if the connection was refused.
+    ///
+    /// Any TCP socket which has not connected or failed after a timeout will be removed.
+    pub(super) fn recv_tcp_sockets(&mut self) -> TraceResult<Option<ProbeResponse>> {
+        self.tcp_probes
+            .expire(Instant::now(), self.tcp_connect_timeout);
+        let found = self
+            .tcp_probes
+            .take_first(|socket| socket.is_writable().unwrap_or_default());
+        if let Some(probe) = found {
+            let response = match probe.dest_addr.ip() {
+                IpAddr::V4(_) => {
+                    ipv4::recv_tcp_socket(&probe.socket, probe.sequence, probe.dest_addr)
+                }
+                IpAddr::V6(_) => {
+                    ipv6::recv_tcp_socket(&probe.socket, probe.sequence, probe.dest_addr)
+                }
+            };
+            if matches!(response, Ok(Some(_))) {
+                self.metrics.count_packet(&response);
+            }
+            response
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// The TCP probes awaiting connection, oldest first, of which there are at most `capacity`.
+///
+/// The probes of every round await connection until the connect timeout, not only those in flight, and so should the
+/// table be smaller than `max_inflight`, or the connect timeout outlast a round, a probe may be evicted to make room
+/// for another, and so be lost, before it could connect.
+#[derive(Debug)]
+struct TcpProbes<S = Socket> {
+    probes: VecDeque<TcpProbe<S>>,
+    capacity: usize,
+}
+
+impl<S> TcpProbes<S> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            probes: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    const fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    #[cfg(all(feature = "tokio", unix))]
+    fn is_empty(&self) -> bool {
+        self.probes.is_empty()
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &TcpProbe<S>> {
+        self.probes.iter()
+    }
+
+    /// Add `probe`, returning the oldest probe if it had to be evicted to make room.
+    fn push(&mut self, probe: TcpProbe<S>) -> Option<TcpProbe<S>> {
+        let evicted = if self.probes.len() >= self.capacity {
+            self.probes.pop_front()
+        } else {
+            None
+        };
+        self.probes.push_back(probe);
+        evicted
+    }
+
+    /// Remove every probe which has awaited connection for `timeout` or longer as of `now`.
+    ///
+    /// The time awaited is measured with the monotonic clock, such that a step of the system clock can neither expire
+    /// every probe at once nor keep them all from expiring.
+    fn expire(&mut self, now: Instant, timeout: Duration) {
+        self.probes
+            .retain(|probe| now.saturating_duration_since(probe.start) < timeout);
+    }
+
+    /// Remove and return the oldest probe whose socket is `ready`.
+    fn take_first(&mut self, ready: impl Fn(&S) -> bool) -> Option<TcpProbe<S>> {
+        let (i, _) = self
+            .probes
+            .iter()
+            .find_position(|probe| ready(&probe.socket))?;
+        self.probes.remove(i)
+    }
+}
+
+/// An entry in the TCP probes table.
+#[derive(Debug)]
+struct TcpProbe<S = Socket> {
+    socket: S,
+    sequence: Sequence,
+    dest_addr: SocketAddr,
+    start: Instant,
+}
+
+impl<S> TcpProbe<S> {
+    pub const fn new(socket: S, sequence: Sequence, dest_addr: SocketAddr, start: Instant) -> Self {
+        Self {
+            socket,
+            sequence,
+            dest_addr,
+            start,
+        }
+    }
+}
+
+/// Continue past a retryable error, as though the packet read was lost, with `lost` in place of what would have been
+/// returned.
+///
+/// A probe which could not be sent is not skipped here but returned to the tracer, which records the failure.
+fn skip_retryable<T>(result: TraceResult<T>, lost: T) -> TraceResult<T> {
+    match result {
+        Err(err) if err.is_retryable() => {
+            debug!(error = err.render(), "skipping retryable error");
+            Ok(lost)
+        }
+        result => result,
+    }
+}
+
+/// The error of creating a socket for `addr`, which is refused without elevated privileges.
+fn socket_error(addr: IpAddr) -> impl FnOnce(io::Error) -> TracerError {
+    move |err| TracerError::from_socket_error("socket", SocketAddr::new(addr, 0), err)
+}
+
+/// Make a socket for sending raw `ICMP` packets.
+fn make_icmp_send_socket(
+    addr: IpAddr,
+    interface: Option<&str>,
+    fwmark: Option<u32>,
+) -> TraceResult<Socket> {
+    let socket = match addr {
+        IpAddr::V4(_) => Socket::new_icmp_send_socket_ipv4(),
+        IpAddr::V6(_) => Socket::new_icmp_send_socket_ipv6(),
+    }
+    .map_err(socket_error(addr))?;
+    bind_interface(&socket, interface, addr_family(addr))?;
+    set_fwmark(&socket, fwmark)?;
+    Ok(socket)
+}
+
+/// Make a socket for sending `UDP` packets.
+fn make_udp_send_socket(
+    addr: IpAddr,
+    interface: Option<&str>,
+    fwmark: Option<u32>,
+) -> TraceResult<Socket> {
+    let socket = match addr {
+        IpAddr::V4(_) => Socket::new_udp_send_socket_ipv4(),
+        IpAddr::V6(_) => Socket::new_udp_send_socket_ipv6(),
+    }
+    .map_err(socket_error(addr))?;
+    bind_interface(&socket, interface, addr_family(addr))?;
+    set_fwmark(&socket, fwmark)?;
+    Ok(socket)
+}
+
+/// Make a socket for receiving raw `ICMP` packets.
+fn make_recv_socket(addr: IpAddr, interface: Option<&str>) -> TraceResult<Socket> {
+    let socket = match addr {
+        IpAddr::V4(ipv4addr) => Socket::new_recv_socket_ipv4(ipv4addr),
+        IpAddr::V6(ipv6addr) => Socket::new_recv_socket_ipv6(ipv6addr),
+    }
+    .map_err(socket_error(addr))?;
+    bind_interface(&socket, interface, addr_family(addr))?;
+    Ok(socket)
+}
+
+/// The address family of `addr`.
+fn addr_family(addr: IpAddr) -> TracerAddrFamily {
+    match addr {
+        IpAddr::V4(_) => TracerAddrFamily::Ipv4,
+        IpAddr::V6(_) => TracerAddrFamily::Ipv6,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Port;
+    use crate::{ProbeStatus, Tracer, TracerConfig};
+    use std::net::{Ipv4Addr, TcpListener};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    /// Trace with the sockets bound to the interface named by `TRIPPY_TEST_INTERFACE`, on a host with several
+    /// interfaces, to the address given by `TRIPPY_TEST_TARGET`, or else 1.1.1.1, and validate that responses are
+    /// still received.
+    #[test]
+    #[ignore = "requires CAP_NET_RAW and a host with several interfaces"]
+    fn test_bound_to_interface() {
+        let interface = std::env::var("TRIPPY_TEST_INTERFACE").unwrap();
+        let target_addr = std::env::var("TRIPPY_TEST_TARGET")
+            .map_or(IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1)), |target| {
+                target.parse().unwrap()
+            });
+        let source_addr = platform::lookup_interface_addr_ipv4(&interface).unwrap();
+        let channel_config = TracerChannelConfig::new(
+            TracerProtocol::Icmp,
+            TracerAddrFamily::Ipv4,
+            source_addr,
+            target_addr,
+            4343,
+            84,
+            0,
+            0,
+            33000,
+            MultipathStrategy::Classic,
+            PortDirection::None,
+            Duration::from_millis(10),
+            Duration::from_secs(1),
+            None,
+            Some(interface),
+            None,
+        );
+        let tracer_config = TracerConfig::new(
+            target_addr,
+            TracerProtocol::Icmp,
+            Some(3),
+            4343,
+            1,
+            64,
+            Duration::from_millis(100),
+            24,
+            33000,
+            Duration::from_millis(10),
+            Duration::from_millis(500),
+            Duration::from_secs(1),
+            84,
+            0,
+        )
+        .unwrap();
+        let complete = AtomicUsize::new(0);
+        let channel = TracerChannel::connect(&channel_config).unwrap();
+        Tracer::new(&tracer_config, |round| {
+            let responses = round
+                .probes
+                .iter()
+                .filter(|probe| probe.status == ProbeStatus::Complete)
+                .count();
+            complete.fetch_add(responses, Ordering::SeqCst);
+        })
+        .trace(channel)
+        .unwrap();
+        assert!(complete.into_inner() > 0);
+    }
+
+    /// Measure the round trip times, and their jitter, of probes traced with `icmp` and with `tcp` to a listener on the
+    /// loopback interface along with the time taken by each round, all of which should be well under the read timeout
+    /// of the channel.
+    ///
+    /// Run with `cargo test --release bench_loopback_rtt -- --ignored --nocapture` with `CAP_NET_RAW`.
+    #[test]
+    #[ignore = "requires CAP_NET_RAW"]
+    fn bench_loopback_rtt() {
+        const ROUNDS: u32 = 100;
+        for protocol in [TracerProtocol::Icmp, TracerProtocol::Tcp] {
+            let start = std::time::Instant::now();
+            let rtts = loopback_rtts(protocol, ROUNDS as usize);
+            let round = start.elapsed() / ROUNDS;
+            let micros: Vec<_> = rtts.iter().map(|rtt| rtt.as_secs_f64() * 1e6).collect();
+            let count = f64::from(u32::try_from(micros.len()).unwrap());
+            let mean = micros.iter().sum::<f64>() / count;
+            let variance = micros.iter().map(|rtt| (rtt - mean).powi(2)).sum::<f64>() / count;
+            let max = micros.iter().copied().fold(0_f64, f64::max);
+            println!(
+                "{protocol:?}: {} probes, mean {mean:.1}us, stddev {:.1}us, max {max:.1}us, {round:?} per round",
+                micros.len(),
+                variance.sqrt()
+            );
+            assert!(max < 5_000_f64);
+            assert!(round < Duration::from_millis(50));
+        }
+    }
+
+    /// The round trip time of each probe which is answered in `rounds` of tracing 127.0.0.1 over `protocol`, with a
+    /// read timeout of 100ms.
+    fn loopback_rtts(protocol: TracerProtocol, rounds: usize) -> Vec<Duration> {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let addr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let port_direction = match protocol {
+            TracerProtocol::Tcp => PortDirection::FixedDest(Port(port)),
+            _ => PortDirection::None,
+        };
+        let channel_config = TracerChannelConfig::new(
+            protocol,
+            TracerAddrFamily::Ipv4,
+            addr,
+            addr,
+            4343,
+            84,
+            0,
+            0,
+            33000,
+            MultipathStrategy::Classic,
+            port_direction,
+            Duration::from_millis(100),
+            Duration::from_secs(1),
+            None,
+            None,
+            None,
+        );
+        let tracer_config = TracerConfig::new(
+            addr,
+            protocol,
+            Some(rounds),
+            4343,
+            1,
+            64,
+            Duration::from_millis(1),
+            24,
+            33000,
+            Duration::from_millis(100),
+            Duration::from_millis(5),
+            Duration::from_secs(1),
+            84,
+            0,
+        )
+        .unwrap();
+        let rtts = Mutex::new(Vec::new());
+        let channel = TracerChannel::connect(&channel_config).unwrap();
+        Tracer::new(&tracer_config, |round| {
+            rtts.lock()
+                .unwrap()
+                .extend(round.probes.iter().filter_map(Probe::checked_duration));
+        })
+        .trace(channel)
+        .unwrap();
+        drop(listener);
+        rtts.into_inner().unwrap()
+    }
+
+    /// A socket which connects once `writable` is set.
+    #[derive(Debug)]
+    struct MockSocket {
+        writable: bool,
+    }
+
+    fn mock_probe(sequence: u16, writable: bool, start: Instant) -> TcpProbe<MockSocket> {
+        TcpProbe::new(
+            MockSocket { writable },
+            Sequence(sequence),
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 80),
+            start,
+        )
+    }
+
+    fn sequences(probes: &TcpProbes<MockSocket>) -> Vec<u16> {
+        probes.iter().map(|probe| probe.sequence.0).collect()
+    }
+
+    #[test]
+    fn test_tcp_probes_overflow() {
+        let start = Instant::now();
+        let mut probes = TcpProbes::new(3);
+        for sequence in 33000..33003 {
+            assert!(probes.push(mock_probe(sequence, false, start)).is_none());
+        }
+        let evicted = probes.push(mock_probe(33003, false, start)).unwrap();
+        assert_eq!(Sequence(33000), evicted.sequence);
+        let evicted = probes.push(mock_probe(33004, false, start)).unwrap();
+        assert_eq!(Sequence(33001), evicted.sequence);
+        assert_eq!(vec![33002, 33003, 33004], sequences(&probes));
+    }
+
+    #[test]
+    fn test_tcp_probes_single() {
+        let start = Instant::now();
+        let mut probes = TcpProbes::new(1);
+        assert!(probes.push(mock_probe(33000, true, start)).is_none());
+        let evicted = probes.push(mock_probe(33001, true, start)).unwrap();
+        assert_eq!(Sequence(33000), evicted.sequence);
+        assert_eq!(vec![33001], sequences(&probes));
+    }
+
+    #[test]
+    fn test_tcp_probes_take_first() {
+        let start = Instant::now();
+        let mut probes = TcpProbes::new(4);
+        probes.push(mock_probe(33000, false, start));
+        probes.push(mock_probe(33001, true, start));
+        probes.push(mock_probe(33002, true, start));
+        let taken = probes.take_first(|socket| socket.writable).unwrap();
+        assert_eq!(Sequence(33001), taken.sequence);
+        assert_eq!(vec![33000, 33002], sequences(&probes));
+        let taken = probes.take_first(|socket| socket.writable).unwrap();
+        assert_eq!(Sequence(33002), taken.sequence);
+        assert!(probes.take_first(|socket| socket.writable).is_none());
+        assert_eq!(vec![33000], sequences(&probes));
+    }
+
+    /// A probe expires once it has awaited connection for the connect timeout, and not before.
+    #[test]
+    fn test_tcp_probes_expire() {
+        let timeout = Duration::from_millis(100);
+        let start = Instant::now();
+        let now = start + Duration::from_millis(200);
+        let mut probes = TcpProbes::new(4);
+        probes.push(mock_probe(33000, false, start + Duration::from_millis(99)));
+        probes.push(mock_probe(33001, false, start + Duration::from_millis(100)));
+        probes.push(mock_probe(33002, false, start + Duration::from_millis(101)));
+        probes.push(mock_probe(33003, false, start + Duration::from_millis(201)));
+        probes.expire(now, timeout);
+        assert_eq!(vec![33002, 33003], sequences(&probes));
+        probes.expire(now, Duration::ZERO);
+        assert!(sequences(&probes).is_empty());
+    }
+}
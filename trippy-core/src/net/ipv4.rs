@@ -0,0 +1,1230 @@
+use crate::error::TracerError::AddressNotAvailable;
+use crate::error::{ParseError, TraceResult, TracerError};
+use crate::net::channel::PacketBuffers;
+use crate::net::platform;
+use crate::net::platform::Socket;
+use crate::net::quoted;
+use crate::net::socket::{bind_interface, send_probe, set_fwmark, TracerSocket as _};
+use crate::net::stamp::{self, PayloadStamp};
+use crate::packet::checksum::{icmp_ipv4_checksum, udp_ipv4_checksum};
+use crate::packet::icmpv4::destination_unreachable::DestinationUnreachablePacket;
+use crate::packet::icmpv4::echo_reply::EchoReplyPacket;
+use crate::packet::icmpv4::echo_request::EchoRequestPacket;
+use crate::packet::icmpv4::time_exceeded::TimeExceededPacket;
+use crate::packet::icmpv4::timestamp::TimestampPacket;
+use crate::packet::icmpv4::{IcmpCode, IcmpPacket, IcmpType};
+use crate::packet::ipv4::Ipv4Packet;
+use crate::packet::udp::UdpPacket;
+use crate::packet::IpProtocol;
+use crate::probe::{
+    millis_since_midnight, FlowKey, IcmpQuote, IcmpTimestamps, ProbeResponse, ProbeResponseData,
+    ResponderPorts,
+};
+use crate::types::{PacketSize, PayloadPattern, Sequence, TraceId, TypeOfService};
+use crate::util::Required;
+use crate::{
+    IcmpProbeType, MultipathStrategy, PacketCapture, PortDirection, Probe, TracerAddrFamily,
+    TracerProtocol,
+};
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv4Addr, Shutdown, SocketAddr};
+use std::time::SystemTime;
+
+/// The value for the IPv4 `flags_and_fragment_offset` field to set the `Don't fragment` bit.
+///
+/// 0100 0000 0000 0000
+const DONT_FRAGMENT: u16 = 0x4000;
+
+/// The ICMP `DestinationUnreachable` code for `Fragmentation needed and Don't fragment was set`.
+const FRAGMENTATION_NEEDED_CODE: u8 = 4;
+
+#[allow(clippy::too_many_arguments)]
+pub fn dispatch_icmp_probe(
+    icmp_send_socket: &mut Socket,
+    buffers: &mut PacketBuffers,
+    probe: Probe,
+    src_addr: Ipv4Addr,
+    dest_addr: Ipv4Addr,
+    identifier: TraceId,
+    icmp_probe_type: IcmpProbeType,
+    packet_size: PacketSize,
+    payload_pattern: PayloadPattern,
+    payload_stamp: bool,
+    ipv4_byte_order: platform::PlatformIpv4FieldByteOrder,
+    capture: Option<&PacketCapture>,
+) -> TraceResult<FlowKey> {
+    let PacketBuffers {
+        ip: ipv4_buf,
+        datagram: icmp_buf,
+        ..
+    } = buffers;
+    let packet_size = usize::from(packet_size.0);
+    if packet_size > ipv4_buf.len() {
+        return Err(TracerError::InvalidPacketSize(packet_size));
+    }
+    let (icmp_len, checksum) = match icmp_probe_type {
+        IcmpProbeType::Echo => {
+            let echo_request = make_echo_request_icmp_packet(
+                icmp_buf,
+                identifier,
+                probe.sequence,
+                icmp_payload_size(packet_size),
+                payload_pattern,
+                payload_stamp.then(|| PayloadStamp::new(&probe)),
+            )?;
+            (echo_request.packet().len(), echo_request.get_checksum())
+        }
+        IcmpProbeType::Timestamp => {
+            let timestamp = make_timestamp_request_icmp_packet(
+                icmp_buf,
+                identifier,
+                probe.sequence,
+                SystemTime::now(),
+            )?;
+            (timestamp.packet().len(), timestamp.get_checksum())
+        }
+    };
+    let ipv4 = make_ipv4_packet(
+        ipv4_buf,
+        ipv4_byte_order,
+        IpProtocol::Icmp,
+        src_addr,
+        dest_addr,
+        probe.ttl.0,
+        0,
+        &icmp_buf[..icmp_len],
+    )?;
+    let remote_addr = SocketAddr::new(IpAddr::V4(dest_addr), 0);
+    send_probe(
+        icmp_send_socket,
+        ipv4.packet(),
+        ipv4.packet().len(),
+        remote_addr,
+    )?;
+    if let Some(capture) = capture {
+        capture.capture(SystemTime::now(), ipv4.packet());
+    }
+    Ok(FlowKey::icmp(identifier.0, checksum))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn dispatch_udp_probe(
+    raw_send_socket: &mut Socket,
+    buffers: &mut PacketBuffers,
+    probe: Probe,
+    src_addr: Ipv4Addr,
+    dest_addr: Ipv4Addr,
+    initial_sequence: Sequence,
+    multipath_strategy: MultipathStrategy,
+    port_direction: PortDirection,
+    packet_size: PacketSize,
+    payload_pattern: PayloadPattern,
+    payload_stamp: bool,
+    ipv4_byte_order: platform::PlatformIpv4FieldByteOrder,
+    capture: Option<&PacketCapture>,
+) -> TraceResult<FlowKey> {
+    let PacketBuffers {
+        ip: ipv4_buf,
+        datagram: udp_buf,
+        ..
+    } = buffers;
+    let packet_size = usize::from(packet_size.0);
+    if packet_size > ipv4_buf.len() {
+        return Err(TracerError::InvalidPacketSize(packet_size));
+    }
+    let (src_port, dest_port, identifier) =
+        udp_ports(&probe, initial_sequence, multipath_strategy, port_direction);
+    let udp = make_udp_packet(
+        udp_buf,
+        src_addr,
+        dest_addr,
+        src_port,
+        dest_port,
+        udp_payload_size(packet_size),
+        payload_pattern,
+        payload_stamp.then(|| PayloadStamp::new(&probe)),
+    )?;
+    let checksum = udp.get_checksum();
+    let ipv4 = make_ipv4_packet(
+        ipv4_buf,
+        ipv4_byte_order,
+        IpProtocol::Udp,
+        src_addr,
+        dest_addr,
+        probe.ttl.0,
+        identifier,
+        udp.packet(),
+    )?;
+    let remote_addr = SocketAddr::new(IpAddr::V4(dest_addr), dest_port);
+    send_probe(
+        raw_send_socket,
+        ipv4.packet(),
+        ipv4.packet().len(),
+        remote_addr,
+    )?;
+    if let Some(capture) = capture {
+        capture.capture(SystemTime::now(), ipv4.packet());
+    }
+    Ok(FlowKey::udp(src_port, dest_port, checksum).with_ip_id(identifier))
+}
+
+/// The source port, destination port and IPv4 identification of a UDP `probe`, which between them encode its
+/// sequence as required by the `multipath_strategy`.
+fn udp_ports(
+    probe: &Probe,
+    initial_sequence: Sequence,
+    multipath_strategy: MultipathStrategy,
+    port_direction: PortDirection,
+) -> (u16, u16, u16) {
+    match multipath_strategy {
+        MultipathStrategy::Classic => match port_direction {
+            PortDirection::FixedSrc(src_port) => (src_port.0, probe.sequence.0, 0),
+            PortDirection::FixedDest(dest_port) => (probe.sequence.0, dest_port.0, 0),
+            PortDirection::FixedBoth(_, _) | PortDirection::None => {
+                unimplemented!()
+            }
+        },
+        MultipathStrategy::Paris => unimplemented!(),
+        MultipathStrategy::Dublin => {
+            let round_port =
+                ((initial_sequence.0 as usize + probe.round.0) % usize::from(u16::MAX)) as u16;
+            match port_direction {
+                PortDirection::FixedSrc(src_port) => (src_port.0, round_port, probe.sequence.0),
+                PortDirection::FixedDest(dest_port) => (round_port, dest_port.0, probe.sequence.0),
+                PortDirection::FixedBoth(src_port, dest_port) => {
+                    (src_port.0, dest_port.0, probe.sequence.0)
+                }
+                PortDirection::None => unimplemented!(),
+            }
+        }
+    }
+}
+
+pub fn dispatch_tcp_probe(
+    probe: Probe,
+    src_addr: Ipv4Addr,
+    dest_addr: Ipv4Addr,
+    port_direction: PortDirection,
+    tos: TypeOfService,
+    interface: Option<&str>,
+    fwmark: Option<u32>,
+) -> TraceResult<(Socket, FlowKey)> {
+    let (src_port, dest_port) = match port_direction {
+        PortDirection::FixedSrc(src_port) => (src_port.0, probe.sequence.0),
+        PortDirection::FixedDest(dest_port) => (probe.sequence.0, dest_port.0),
+        PortDirection::FixedBoth(_, _) | PortDirection::None => unimplemented!(),
+    };
+    let mut socket = Socket::new_stream_socket_ipv4()?;
+    bind_interface(&socket, interface, TracerAddrFamily::Ipv4)?;
+    set_fwmark(&socket, fwmark)?;
+    let local_addr = SocketAddr::new(IpAddr::V4(src_addr), src_port);
+    socket
+        .bind(local_addr)
+        .map_err(|err| TracerError::from_socket_error("bind", local_addr, err))?;
+    socket.set_ttl(u32::from(probe.ttl.0))?;
+    socket.set_tos(u32::from(tos.0))?;
+    let remote_addr = SocketAddr::new(IpAddr::V4(dest_addr), dest_port);
+    match socket.connect(remote_addr) {
+        Ok(_) => {}
+        Err(err) => {
+            if let Some(code) = err.raw_os_error() {
+                if platform::is_not_in_progress_error(code) {
+                    return match err.kind() {
+                        ErrorKind::AddrInUse | ErrorKind::AddrNotAvailable => {
+                            Err(AddressNotAvailable(local_addr))
+                        }
+                        _ => Err(TracerError::IoError(err)),
+                    };
+                }
+            } else {
+                return Err(TracerError::IoError(err));
+            }
+        }
+    }
+    Ok((socket, FlowKey::tcp(src_port, dest_port)))
+}
+
+pub fn recv_icmp_probe(
+    recv_socket: &mut Socket,
+    buf: &mut [u8],
+    protocol: TracerProtocol,
+    multipath_strategy: MultipathStrategy,
+    direction: PortDirection,
+    capture: Option<&PacketCapture>,
+) -> TraceResult<Option<ProbeResponse>> {
+    match recv_socket.read(buf) {
+        Ok(bytes_read) => {
+            if let Some(capture) = capture {
+                capture.capture(SystemTime::now(), &buf[..bytes_read]);
+            }
+            Ok(parse_icmp_probe(
+                &buf[..bytes_read],
+                protocol,
+                multipath_strategy,
+                direction,
+            )?)
+        }
+        Err(err) => match err.kind() {
+            ErrorKind::WouldBlock => Ok(None),
+            _ => Err(TracerError::IoError(err)),
+        },
+    }
+}
+
+/// Generate a response for a TCP probe to `dest_addr` whose socket has connected or failed.
+///
+/// The port of the peer of a connected socket is recorded against the port probed, as the reply came from it.
+pub fn recv_tcp_socket(
+    tcp_socket: &Socket,
+    sequence: Sequence,
+    dest_addr: SocketAddr,
+) -> TraceResult<Option<ProbeResponse>> {
+    match tcp_socket.take_error()? {
+        None => {
+            let peer_addr = tcp_socket.peer_addr()?.req()?;
+            tcp_socket.shutdown(Shutdown::Both)?;
+            let ports = ResponderPorts::new(dest_addr.port(), peer_addr.port());
+            return Ok(Some(ProbeResponse::TcpReply(
+                ProbeResponseData::new(SystemTime::now(), peer_addr.ip(), 0, sequence.0)
+                    .with_responder_ports(ports),
+            )));
+        }
+        Some(err) => {
+            if let Some(code) = err.raw_os_error() {
+                if platform::is_conn_refused_error(code) {
+                    return Ok(Some(ProbeResponse::TcpRefused(ProbeResponseData::new(
+                        SystemTime::now(),
+                        dest_addr.ip(),
+                        0,
+                        sequence.0,
+                    ))));
+                }
+                if platform::is_host_unreachable_error(code) {
+                    let error_addr = tcp_socket.icmp_error_info()?;
+                    return Ok(Some(ProbeResponse::TimeExceeded(ProbeResponseData::new(
+                        SystemTime::now(),
+                        error_addr,
+                        0,
+                        sequence.0,
+                    ))));
+                }
+            }
+        }
+    };
+    Ok(None)
+}
+
+/// Create an ICMP `EchoRequest` packet.
+fn make_echo_request_icmp_packet(
+    icmp_buf: &mut [u8],
+    identifier: TraceId,
+    sequence: Sequence,
+    payload_size: usize,
+    payload_pattern: PayloadPattern,
+    stamp: Option<PayloadStamp>,
+) -> TraceResult<EchoRequestPacket<'_>> {
+    let packet_size = IcmpPacket::minimum_packet_size() + payload_size;
+    let icmp_buf = icmp_buf.get_mut(..packet_size).req()?;
+    icmp_buf[IcmpPacket::minimum_packet_size()..].fill(payload_pattern.0);
+    if let Some(stamp) = stamp {
+        stamp.write(&mut icmp_buf[IcmpPacket::minimum_packet_size()..])?;
+    }
+    let mut icmp = EchoRequestPacket::new(icmp_buf).req()?;
+    icmp.set_icmp_type(IcmpType::EchoRequest);
+    icmp.set_icmp_code(IcmpCode(0));
+    icmp.set_identifier(identifier.0);
+    icmp.set_sequence(sequence.0);
+    icmp.set_checksum(icmp_ipv4_checksum(icmp.packet()));
+    Ok(icmp)
+}
+
+/// Create an ICMP `TimestampRequest` packet originated at `now`.
+///
+/// A timestamp message has no payload and so is of a fixed size whatever the packet size.
+fn make_timestamp_request_icmp_packet(
+    icmp_buf: &mut [u8],
+    identifier: TraceId,
+    sequence: Sequence,
+    now: SystemTime,
+) -> TraceResult<TimestampPacket<'_>> {
+    let icmp_buf = icmp_buf
+        .get_mut(..TimestampPacket::minimum_packet_size())
+        .req()?;
+    icmp_buf.fill(0);
+    let mut icmp = TimestampPacket::new(icmp_buf).req()?;
+    icmp.set_icmp_type(IcmpType::TimestampRequest);
+    icmp.set_icmp_code(IcmpCode(0));
+    icmp.set_identifier(identifier.0);
+    icmp.set_sequence(sequence.0);
+    icmp.set_originate(millis_since_midnight(now));
+    icmp.set_checksum(icmp_ipv4_checksum(icmp.packet()));
+    Ok(icmp)
+}
+
+/// Create a `UdpPacket`
+#[allow(clippy::too_many_arguments)]
+fn make_udp_packet(
+    udp_buf: &mut [u8],
+    src_addr: Ipv4Addr,
+    dest_addr: Ipv4Addr,
+    src_port: u16,
+    dest_port: u16,
+    payload_size: usize,
+    payload_pattern: PayloadPattern,
+    stamp: Option<PayloadStamp>,
+) -> TraceResult<UdpPacket<'_>> {
+    let udp_packet_size = UdpPacket::minimum_packet_size() + payload_size;
+    let udp_buf = udp_buf.get_mut(..udp_packet_size).req()?;
+    udp_buf[UdpPacket::minimum_packet_size()..].fill(payload_pattern.0);
+    if let Some(stamp) = stamp {
+        stamp.write(&mut udp_buf[UdpPacket::minimum_packet_size()..])?;
+    }
+    let mut udp = UdpPacket::new(udp_buf).req()?;
+    udp.set_source(src_port);
+    udp.set_destination(dest_port);
+    udp.set_length(udp_packet_size as u16);
+    udp.set_checksum(udp_ipv4_checksum(udp.packet(), src_addr, dest_addr));
+    Ok(udp)
+}
+
+/// Create an `Ipv4Packet`.
+#[allow(clippy::too_many_arguments)]
+fn make_ipv4_packet<'a>(
+    ipv4_buf: &'a mut [u8],
+    ipv4_byte_order: platform::PlatformIpv4FieldByteOrder,
+    protocol: IpProtocol,
+    src_addr: Ipv4Addr,
+    dest_addr: Ipv4Addr,
+    ttl: u8,
+    identification: u16,
+    payload: &[u8],
+) -> TraceResult<Ipv4Packet<'a>> {
+    let ipv4_total_length = (Ipv4Packet::minimum_packet_size() + payload.len()) as u16;
+    let ipv4_total_length_header = ipv4_byte_order.adjust_length(ipv4_total_length);
+    let ipv4_flags_and_fragment_offset_header = ipv4_byte_order.adjust_length(DONT_FRAGMENT);
+    let mut ipv4 = Ipv4Packet::new(&mut ipv4_buf[..ipv4_total_length as usize]).req()?;
+    ipv4.set_version(4);
+    ipv4.set_header_length(5);
+    ipv4.set_total_length(ipv4_total_length);
+    ipv4.set_ttl(ttl);
+    ipv4.set_protocol(protocol);
+    ipv4.set_source(src_addr);
+    ipv4.set_destination(dest_addr);
+    ipv4.set_payload(payload);
+    // The payload is bounded by the total length and so is set before the total length is put in the byte order of
+    // the platform.
+    ipv4.set_total_length(ipv4_total_length_header);
+    ipv4.set_identification(identification);
+    ipv4.set_flags_and_fragment_offset(ipv4_flags_and_fragment_offset_header);
+    Ok(ipv4)
+}
+
+fn icmp_payload_size(packet_size: usize) -> usize {
+    let ip_header_size = Ipv4Packet::minimum_packet_size();
+    let icmp_header_size = IcmpPacket::minimum_packet_size();
+    packet_size - icmp_header_size - ip_header_size
+}
+
+fn udp_payload_size(packet_size: usize) -> usize {
+    let ip_header_size = Ipv4Packet::minimum_packet_size();
+    let udp_header_size = UdpPacket::minimum_packet_size();
+    packet_size - udp_header_size - ip_header_size
+}
+
+/// Parse the response to a probe from an IPv4 `packet` received on the ICMP socket, if it is one.
+///
+/// A raw IPv4 socket delivers the IPv4 header along with the ICMP message, on every platform, and so the `packet`
+/// starts with the IPv4 header and the responder is its source, unlike the `ICMPv6` packets of `ipv6::parse_icmp_probe`.
+///
+/// This is the pure step of receiving a probe response and so is fed arbitrary bytes when fuzzed, it must return an
+/// error for any packet which cannot be parsed rather than panic.
+pub fn parse_icmp_probe(
+    packet: &[u8],
+    protocol: TracerProtocol,
+    multipath_strategy: MultipathStrategy,
+    direction: PortDirection,
+) -> Result<Option<ProbeResponse>, ParseError> {
+    let recv = SystemTime::now();
+    let ipv4 = Ipv4Packet::new_view(packet).ok_or(ParseError::Truncated("IPv4 header"))?;
+    let src = IpAddr::V4(ipv4.get_source());
+    let data = |id, seq| ProbeResponseData::new(recv, src, id, seq).with_recv_ttl(ipv4.get_ttl());
+    let icmp_v4 =
+        IcmpPacket::new_view(ipv4.payload()).ok_or(ParseError::Truncated("ICMP header"))?;
+    Ok(match icmp_v4.get_icmp_type() {
+        IcmpType::TimeExceeded => {
+            let packet = TimeExceededPacket::new_view(icmp_v4.packet())
+                .ok_or(ParseError::Truncated("ICMP time exceeded"))?;
+            extract_quoted(packet.payload(), protocol, multipath_strategy, direction)?.map(
+                |(id, seq, quote)| ProbeResponse::TimeExceeded(data(id, seq).with_quote(quote)),
+            )
+        }
+        IcmpType::DestinationUnreachable => {
+            let packet = DestinationUnreachablePacket::new_view(icmp_v4.packet())
+                .ok_or(ParseError::Truncated("ICMP destination unreachable"))?;
+            let code = icmp_v4.get_icmp_code().0;
+            let mtu = packet.get_next_hop_mtu();
+            let next_hop_mtu = (code == FRAGMENTATION_NEEDED_CODE && mtu > 0).then_some(mtu);
+            extract_quoted(packet.payload(), protocol, multipath_strategy, direction)?.map(
+                |(id, seq, quote)| {
+                    ProbeResponse::DestinationUnreachable(
+                        data(id, seq).with_quote(quote),
+                        code,
+                        next_hop_mtu,
+                    )
+                },
+            )
+        }
+        IcmpType::TimestampReply => match protocol {
+            TracerProtocol::Icmp => {
+                let packet = TimestampPacket::new_view(icmp_v4.packet())
+                    .ok_or(ParseError::Truncated("ICMP timestamp reply"))?;
+                let id = packet.get_identifier();
+                let seq = packet.get_sequence();
+                let timestamps = IcmpTimestamps::new(
+                    packet.get_originate(),
+                    packet.get_receive(),
+                    packet.get_transmit(),
+                );
+                Some(ProbeResponse::TimestampReply(data(id, seq), timestamps))
+            }
+            TracerProtocol::Udp | TracerProtocol::Tcp => None,
+        },
+        IcmpType::EchoReply => match protocol {
+            TracerProtocol::Icmp => {
+                let packet = EchoReplyPacket::new_view(icmp_v4.packet())
+                    .ok_or(ParseError::Truncated("ICMP echo reply"))?;
+                let id = packet.get_identifier();
+                let seq = packet.get_sequence();
+                Some(ProbeResponse::EchoReply(data(id, seq)))
+            }
+            TracerProtocol::Udp | TracerProtocol::Tcp => None,
+        },
+        _ => None,
+    })
+}
+
+/// Extract the identifier and sequence of the probe quoted in the `payload` of an ICMP error, along with the size of
+/// the payload and of the quote, if it is one of ours.
+///
+/// The quote must hold the IPv4 header of the probe and enough of the datagram it heads to read the fields used to
+/// match the probe, otherwise it is malformed.  A quote of a datagram of another protocol is not one of ours.
+fn extract_quoted(
+    payload: &[u8],
+    protocol: TracerProtocol,
+    multipath_strategy: MultipathStrategy,
+    direction: PortDirection,
+) -> Result<Option<(u16, u16, IcmpQuote)>, ParseError> {
+    let ip_protocol = match protocol {
+        TracerProtocol::Icmp => IpProtocol::Icmp,
+        TracerProtocol::Udp => IpProtocol::Udp,
+        TracerProtocol::Tcp => IpProtocol::Tcp,
+    };
+    let Some((ipv4, datagram, full)) = extract_quoted_datagram(payload, ip_protocol)? else {
+        return Ok(None);
+    };
+    let header_len = usize::from(ipv4.get_header_length()) * 4;
+    let quote = IcmpQuote::new(payload.len(), header_len + datagram.len(), full);
+    let extracted = match protocol {
+        TracerProtocol::Icmp => extract_echo_request(datagram)?,
+        TracerProtocol::Udp => {
+            let header = quoted::udp_header(datagram, full)?;
+            let sequence = match (multipath_strategy, direction) {
+                (MultipathStrategy::Classic, PortDirection::FixedDest(_)) => header.src_port,
+                (MultipathStrategy::Classic, _) => header.dest_port,
+                (MultipathStrategy::Paris, _) => header
+                    .checksum
+                    .ok_or(ParseError::Truncated("quoted UDP checksum"))?,
+                (MultipathStrategy::Dublin, _) => ipv4.get_identification(),
+            };
+            Some((0, sequence))
+        }
+        TracerProtocol::Tcp => {
+            let header = quoted::tcp_header(datagram)?;
+            let sequence = match direction {
+                PortDirection::FixedSrc(_) => header.dest_port,
+                _ => header.src_port,
+            };
+            Some((0, sequence))
+        }
+    };
+    Ok(extracted
+        .filter(|(_, seq)| {
+            matches!(protocol, TracerProtocol::Tcp) || stamp::is_consistent(datagram, *seq)
+        })
+        .map(|(id, seq)| (id, seq, quote)))
+}
+
+/// The IPv4 header of a quoted datagram, the datagram it heads and whether that datagram was quoted in full.
+type QuotedDatagram<'a> = (Ipv4Packet<'a>, &'a [u8], bool);
+
+/// The IPv4 header quoted in the `payload` of an ICMP error, the datagram it heads and whether that datagram was
+/// quoted in full, if it is of `protocol`.
+///
+/// A quote with a mangled IPv4 header, one which is not IPv4 or whose header length is impossible, is malformed.  Any
+/// bytes quoted beyond the total length of the original datagram are padding and are dropped.  The total length is
+/// only relied upon if it is consistent with the quote, as some platforms send and quote it in host byte order.
+fn extract_quoted_datagram(
+    payload: &[u8],
+    protocol: IpProtocol,
+) -> Result<Option<QuotedDatagram<'_>>, ParseError> {
+    let ipv4 = Ipv4Packet::new_view(payload).ok_or(ParseError::Truncated("quoted IPv4 header"))?;
+    let header_len = usize::from(ipv4.get_header_length()) * 4;
+    if ipv4.get_version() != 4 {
+        return Err(ParseError::Invalid("quoted IP version"));
+    }
+    if header_len < Ipv4Packet::minimum_packet_size() {
+        return Err(ParseError::Invalid("quoted IPv4 header length"));
+    }
+    if header_len > payload.len() {
+        return Err(ParseError::Truncated("quoted IPv4 header"));
+    }
+    if ipv4.get_protocol() != protocol {
+        return Ok(None);
+    }
+    let total_len = usize::from(ipv4.get_total_length());
+    let full = (header_len..=payload.len()).contains(&total_len);
+    let end = if full { total_len } else { payload.len() };
+    Ok(Some((ipv4, &payload[header_len..end], full)))
+}
+
+/// Get the identifier and sequence of the quoted `EchoRequest` or `TimestampRequest`, if it is one.
+///
+/// Only the first 8 bytes of the request are read, which hold its identifier and sequence for both, as that is all
+/// an ICMP error is required to quote.
+fn extract_echo_request(datagram: &[u8]) -> Result<Option<(u16, u16)>, ParseError> {
+    let echo_request = EchoRequestPacket::new_view(datagram)
+        .ok_or(ParseError::Truncated("quoted echo request"))?;
+    Ok(matches!(
+        echo_request.get_icmp_type(),
+        IcmpType::EchoRequest | IcmpType::TimestampRequest
+    )
+    .then(|| (echo_request.get_identifier(), echo_request.get_sequence())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::reference::{internet_checksum, ipv4_pseudo_header};
+    use crate::types::{Round, TimeToLive};
+    use crate::{DEFAULT_MAX_PACKET_SIZE, MAX_PACKET_SIZE};
+    use platform::PlatformIpv4FieldByteOrder;
+    use proptest::prelude::*;
+    use rand::Rng;
+
+    /// The IPv4 header of a UDP probe from 192.168.1.2 to 8.8.8.8 with a ttl of 1, of total length 56.
+    const UDP_HEADER: [u8; 20] = [
+        0x45, 0x00, 0x00, 0x38, 0xab, 0xcd, 0x40, 0x00, 0x01, 0x11, 0x00, 0x00, 0xc0, 0xa8, 0x01,
+        0x02, 0x08, 0x08, 0x08, 0x08,
+    ];
+
+    /// The UDP header of the probe, from port 33434 to port 33435 of length 36.
+    const UDP: [u8; 8] = [0x82, 0x9a, 0x82, 0x9b, 0x00, 0x24, 0xbe, 0xef];
+
+    /// The IPv4 header of an ICMP probe, of total length 84.
+    const ICMP_HEADER: [u8; 20] = [
+        0x45, 0x00, 0x00, 0x54, 0xab, 0xce, 0x40, 0x00, 0x01, 0x01, 0x00, 0x00, 0xc0, 0xa8, 0x01,
+        0x02, 0x08, 0x08, 0x08, 0x08,
+    ];
+
+    /// The `EchoRequest` of the probe, with identifier 0x1234 and sequence 33000.
+    const ECHO_REQUEST: [u8; 8] = [0x08, 0x00, 0x00, 0x00, 0x12, 0x34, 0x80, 0xe8];
+
+    /// The IPv4 header of a TCP probe, of total length 40.
+    const TCP_HEADER: [u8; 20] = [
+        0x45, 0x00, 0x00, 0x28, 0xab, 0xcf, 0x40, 0x00, 0x01, 0x06, 0x00, 0x00, 0xc0, 0xa8, 0x01,
+        0x02, 0x08, 0x08, 0x08, 0x08,
+    ];
+
+    /// The first 8 bytes of the TCP header of the probe, from port 33434 to port 80.
+    const TCP: [u8; 8] = [0x82, 0x9a, 0x00, 0x50, 0x12, 0x34, 0x56, 0x78];
+
+    /// A `TimeExceeded` from 10.0.0.1 quoting `quote`.
+    fn time_exceeded(quote: &[&[u8]]) -> Vec<u8> {
+        let quote = quote.concat();
+        let total_len = (28 + quote.len()) as u16;
+        let mut packet = vec![0x45, 0x00];
+        packet.extend(total_len.to_be_bytes());
+        packet.extend([0, 0, 0, 0, 0xfe, 0x01, 0, 0, 10, 0, 0, 1, 192, 168, 1, 2]);
+        packet.extend([0x0b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        packet.extend(quote);
+        packet
+    }
+
+    fn extract(
+        packet: &[u8],
+        protocol: TracerProtocol,
+        direction: PortDirection,
+    ) -> Result<Option<(u16, u16)>, ParseError> {
+        Ok(
+            match parse_icmp_probe(packet, protocol, MultipathStrategy::Classic, direction)? {
+                Some(ProbeResponse::TimeExceeded(data)) => Some((data.identifier, data.sequence)),
+                _ => None,
+            },
+        )
+    }
+
+    fn extract_udp(packet: &[u8]) -> Result<Option<(u16, u16)>, ParseError> {
+        extract(
+            packet,
+            TracerProtocol::Udp,
+            PortDirection::new_fixed_src(33434),
+        )
+    }
+
+    fn is_malformed(result: &Result<Option<(u16, u16)>, ParseError>) -> bool {
+        result.is_err()
+    }
+
+    #[test]
+    fn test_udp_rfc792_quote() {
+        let packet = time_exceeded(&[&UDP_HEADER, &UDP]);
+        assert_eq!(Some((0, 33435)), extract_udp(&packet).unwrap());
+    }
+
+    #[test]
+    fn test_udp_full_quote() {
+        let packet = time_exceeded(&[&UDP_HEADER, &UDP, &[0xaa; 28]]);
+        assert_eq!(Some((0, 33435)), extract_udp(&packet).unwrap());
+    }
+
+    #[test]
+    fn test_udp_full_quote_padded() {
+        let packet = time_exceeded(&[&UDP_HEADER, &UDP, &[0xaa; 28], &[0; 44]]);
+        assert_eq!(Some((0, 33435)), extract_udp(&packet).unwrap());
+    }
+
+    #[test]
+    fn test_udp_full_quote_mangled_length() {
+        let mut udp = UDP;
+        udp[5] = 0x30;
+        let packet = time_exceeded(&[&UDP_HEADER, &udp, &[0xaa; 28]]);
+        assert!(is_malformed(&extract_udp(&packet)));
+    }
+
+    #[test]
+    fn test_udp_short_quote() {
+        let packet = time_exceeded(&[&UDP_HEADER, &UDP[..4]]);
+        assert_eq!(Some((0, 33435)), extract_udp(&packet).unwrap());
+        let packet = time_exceeded(&[&UDP_HEADER, &UDP[..2]]);
+        assert!(is_malformed(&extract_udp(&packet)));
+        let packet = time_exceeded(&[&UDP_HEADER[..15]]);
+        assert!(is_malformed(&extract_udp(&packet)));
+        let packet = time_exceeded(&[]);
+        assert!(is_malformed(&extract_udp(&packet)));
+    }
+
+    fn extract_quote(packet: &[u8]) -> Option<IcmpQuote> {
+        let direction = PortDirection::new_fixed_src(33434);
+        match parse_icmp_probe(
+            packet,
+            TracerProtocol::Udp,
+            MultipathStrategy::Classic,
+            direction,
+        ) {
+            Ok(Some(ProbeResponse::TimeExceeded(data))) => data.quote,
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_quote_size_rfc792() {
+        let packet = time_exceeded(&[&UDP_HEADER, &UDP]);
+        assert_eq!(Some(IcmpQuote::new(28, 28, false)), extract_quote(&packet));
+    }
+
+    #[test]
+    fn test_quote_size_full() {
+        let packet = time_exceeded(&[&UDP_HEADER, &UDP, &[0xaa; 28]]);
+        assert_eq!(Some(IcmpQuote::new(56, 56, true)), extract_quote(&packet));
+    }
+
+    #[test]
+    fn test_quote_size_padded() {
+        let packet = time_exceeded(&[&UDP_HEADER, &UDP, &[0xaa; 28], &[0; 44]]);
+        assert_eq!(Some(IcmpQuote::new(100, 56, true)), extract_quote(&packet));
+    }
+
+    #[test]
+    fn test_mangled_header_length() {
+        let mut header = UDP_HEADER;
+        header[0] = 0x4f;
+        let packet = time_exceeded(&[&header, &UDP]);
+        assert!(is_malformed(&extract_udp(&packet)));
+        header[0] = 0x42;
+        let packet = time_exceeded(&[&header, &UDP]);
+        assert!(is_malformed(&extract_udp(&packet)));
+        header[0] = 0x65;
+        let packet = time_exceeded(&[&header, &UDP]);
+        assert!(is_malformed(&extract_udp(&packet)));
+    }
+
+    #[test]
+    fn test_other_protocol() {
+        let packet = time_exceeded(&[&TCP_HEADER, &TCP]);
+        assert_eq!(None, extract_udp(&packet).unwrap());
+    }
+
+    #[test]
+    fn test_icmp_quote() {
+        let packet = time_exceeded(&[&ICMP_HEADER, &ECHO_REQUEST]);
+        let extracted = extract(&packet, TracerProtocol::Icmp, PortDirection::None).unwrap();
+        assert_eq!(Some((0x1234, 33000)), extracted);
+        let packet = time_exceeded(&[&ICMP_HEADER, &ECHO_REQUEST[..6]]);
+        let extracted = extract(&packet, TracerProtocol::Icmp, PortDirection::None);
+        assert!(is_malformed(&extracted));
+        let mut echo_reply = ECHO_REQUEST;
+        echo_reply[0] = 0x00;
+        let packet = time_exceeded(&[&ICMP_HEADER, &echo_reply]);
+        let extracted = extract(&packet, TracerProtocol::Icmp, PortDirection::None).unwrap();
+        assert_eq!(None, extracted);
+    }
+
+    #[test]
+    fn test_timestamp_request_quote() {
+        let mut timestamp_request = ECHO_REQUEST;
+        timestamp_request[0] = 0x0d;
+        let packet = time_exceeded(&[&ICMP_HEADER, &timestamp_request]);
+        let extracted = extract(&packet, TracerProtocol::Icmp, PortDirection::None).unwrap();
+        assert_eq!(Some((0x1234, 33000)), extracted);
+    }
+
+    #[test]
+    fn test_timestamp_reply() {
+        let mut packet = vec![
+            0x45, 0x00, 0x00, 0x28, 0, 0, 0, 0, 0x40, 0x01, 0, 0, 8, 8, 8, 8, 192, 168, 1, 2,
+        ];
+        packet.extend([0x0e, 0x00, 0x00, 0x00, 0x12, 0x34, 0x80, 0xe8]);
+        packet.extend(1000_u32.to_be_bytes());
+        packet.extend(1250_u32.to_be_bytes());
+        packet.extend(1251_u32.to_be_bytes());
+        let response = parse_icmp_probe(
+            &packet,
+            TracerProtocol::Icmp,
+            MultipathStrategy::Classic,
+            PortDirection::None,
+        )
+        .unwrap();
+        let Some(ProbeResponse::TimestampReply(data, timestamps)) = response else {
+            panic!("expected a timestamp reply, got {response:?}");
+        };
+        assert_eq!((0x1234, 33000), (data.identifier, data.sequence));
+        assert_eq!(IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)), data.addr);
+        assert_eq!(Some(64), data.recv_ttl);
+        assert_eq!(IcmpTimestamps::new(1000, 1250, 1251), timestamps);
+        let response = parse_icmp_probe(
+            &packet,
+            TracerProtocol::Udp,
+            MultipathStrategy::Classic,
+            PortDirection::new_fixed_src(33434),
+        )
+        .unwrap();
+        assert!(response.is_none());
+    }
+
+    #[test]
+    fn test_tcp_quote() {
+        let direction = PortDirection::new_fixed_dest(80);
+        let packet = time_exceeded(&[&TCP_HEADER, &TCP]);
+        let extracted = extract(&packet, TracerProtocol::Tcp, direction).unwrap();
+        assert_eq!(Some((0, 33434)), extracted);
+        let packet = time_exceeded(&[&TCP_HEADER, &TCP[..3]]);
+        assert!(is_malformed(&extract(
+            &packet,
+            TracerProtocol::Tcp,
+            direction
+        )));
+    }
+
+    /// The packet received on the raw IPv4 socket holds the IPv4 header, the responder being its source.
+    #[test]
+    fn test_header_present() {
+        let packet = time_exceeded(&[&UDP_HEADER, &UDP]);
+        let direction = PortDirection::new_fixed_src(33434);
+        let parsed = parse_icmp_probe(
+            &packet,
+            TracerProtocol::Udp,
+            MultipathStrategy::Classic,
+            direction,
+        );
+        match parsed.unwrap() {
+            Some(ProbeResponse::TimeExceeded(data)) => {
+                assert_eq!(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), data.addr);
+                assert_eq!((0, 33435), (data.identifier, data.sequence));
+            }
+            response => panic!("expected a time exceeded, got {response:?}"),
+        }
+    }
+
+    /// A packet which starts with the ICMP header, as a raw `ICMPv6` socket would deliver it, is not read as a response.
+    #[test]
+    fn test_header_absent() {
+        let packet = time_exceeded(&[&UDP_HEADER, &UDP]);
+        let headerless = &packet[20..];
+        assert!(!matches!(extract_udp(headerless), Ok(Some(_))));
+        let echo_reply = [0x00, 0x00, 0x00, 0x00, 0x12, 0x34, 0x80, 0xe8];
+        let parsed = parse_icmp_probe(
+            &echo_reply,
+            TracerProtocol::Icmp,
+            MultipathStrategy::Classic,
+            PortDirection::None,
+        );
+        assert!(!matches!(parsed, Ok(Some(_))));
+    }
+
+    /// Packets which were once mishandled, with the error each must be rejected with.
+    #[test]
+    fn test_regression_corpus() {
+        let corpus: [(&[u8], ParseError); 6] = [
+            (&[], ParseError::Truncated("IPv4 header")),
+            (
+                &[
+                    0x45, 0x00, 0x00, 0x14, 0, 0, 0, 0, 0xfe, 0x01, 0, 0, 10, 0, 0, 1,
+                ],
+                ParseError::Truncated("IPv4 header"),
+            ),
+            (
+                &time_exceeded(&[&[0x4f, 0x00, 0x00, 0x38, 0xab, 0xcd, 0x40, 0x00]]),
+                ParseError::Truncated("quoted IPv4 header"),
+            ),
+            (
+                &time_exceeded(&[&[0x4f], &UDP_HEADER[1..], &UDP]),
+                ParseError::Truncated("quoted IPv4 header"),
+            ),
+            (
+                &time_exceeded(&[&[0x41], &UDP_HEADER[1..], &UDP]),
+                ParseError::Invalid("quoted IPv4 header length"),
+            ),
+            (
+                &time_exceeded(&[&[0x05], &UDP_HEADER[1..], &UDP]),
+                ParseError::Invalid("quoted IP version"),
+            ),
+        ];
+        for (packet, err) in corpus {
+            assert_eq!(Err(err), extract_udp(packet), "{packet:02x?}");
+        }
+        let packet = time_exceeded(&[&UDP_HEADER, &UDP[..4]]);
+        let paris = parse_icmp_probe(
+            &packet,
+            TracerProtocol::Udp,
+            MultipathStrategy::Paris,
+            PortDirection::new_fixed_src(33434),
+        );
+        assert_eq!(
+            Some(ParseError::Truncated("quoted UDP checksum")),
+            paris.err()
+        );
+    }
+
+    #[test]
+    fn test_fuzz() {
+        let mut rng = rand::thread_rng();
+        let corpus = [
+            time_exceeded(&[&UDP_HEADER, &UDP, &[0xaa; 28]]),
+            time_exceeded(&[&ICMP_HEADER, &ECHO_REQUEST]),
+            time_exceeded(&[&TCP_HEADER, &TCP]),
+        ];
+        for _ in 0..10000 {
+            let mut packet = if rng.gen_bool(0.5) {
+                let len = rng.gen_range(0..128);
+                (0..len).map(|_| rng.gen()).collect()
+            } else {
+                let mut packet = corpus[rng.gen_range(0..corpus.len())].clone();
+                packet.truncate(rng.gen_range(0..=packet.len()));
+                packet
+            };
+            if !packet.is_empty() {
+                let i = rng.gen_range(0..packet.len());
+                packet[i] = rng.gen();
+            }
+            for protocol in [
+                TracerProtocol::Icmp,
+                TracerProtocol::Udp,
+                TracerProtocol::Tcp,
+            ] {
+                for strategy in [
+                    MultipathStrategy::Classic,
+                    MultipathStrategy::Paris,
+                    MultipathStrategy::Dublin,
+                ] {
+                    let direction = PortDirection::new_fixed_src(33434);
+                    let _ = parse_icmp_probe(&packet, protocol, strategy, direction);
+                }
+            }
+        }
+    }
+
+    const SRC: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 2);
+    const DEST: Ipv4Addr = Ipv4Addr::new(8, 8, 8, 8);
+
+    /// The number of packets built by each property test.
+    const CASES: u32 = 1000;
+
+    prop_compose! {
+        fn probe()(
+            sequence in any::<u16>(),
+            ttl in 1..=u8::MAX,
+            round in 0..usize::from(u16::MAX) * 2,
+        ) -> Probe {
+            Probe::new(Sequence(sequence), TimeToLive(ttl), Round(round), SystemTime::now())
+        }
+    }
+
+    fn packet_size() -> impl Strategy<Value = usize> {
+        28..=usize::from(DEFAULT_MAX_PACKET_SIZE)
+    }
+
+    fn byte_order() -> impl Strategy<Value = PlatformIpv4FieldByteOrder> {
+        prop_oneof![
+            Just(PlatformIpv4FieldByteOrder::Host),
+            Just(PlatformIpv4FieldByteOrder::Network),
+        ]
+    }
+
+    /// The multipath strategies supported for UDP, with each of the port directions they allow.
+    fn udp_ports_strategy() -> impl Strategy<Value = (MultipathStrategy, PortDirection)> {
+        prop_oneof![
+            any::<u16>().prop_map(|port| (
+                MultipathStrategy::Classic,
+                PortDirection::new_fixed_src(port)
+            )),
+            any::<u16>().prop_map(|port| (
+                MultipathStrategy::Classic,
+                PortDirection::new_fixed_dest(port)
+            )),
+            any::<u16>().prop_map(|port| (
+                MultipathStrategy::Dublin,
+                PortDirection::new_fixed_src(port)
+            )),
+            any::<u16>().prop_map(|port| (
+                MultipathStrategy::Dublin,
+                PortDirection::new_fixed_dest(port)
+            )),
+            any::<(u16, u16)>().prop_map(|(src, dest)| (
+                MultipathStrategy::Dublin,
+                PortDirection::new_fixed_both(src, dest)
+            )),
+        ]
+    }
+
+    /// Check the IPv4 header of a packet built, reading the fields from the bytes rather than with `Ipv4Packet`.
+    ///
+    /// The header checksum is left for the kernel to fill in, as it does for sockets with `IP_HDRINCL`.
+    fn check_ipv4_header(
+        packet: &[u8],
+        byte_order: PlatformIpv4FieldByteOrder,
+        protocol: u8,
+        ttl: u8,
+        identification: u16,
+    ) {
+        let field = |i: usize| [packet[i], packet[i + 1]];
+        let ordered_field = |i: usize| match byte_order {
+            PlatformIpv4FieldByteOrder::Network => u16::from_be_bytes(field(i)),
+            PlatformIpv4FieldByteOrder::Host => u16::from_le_bytes(field(i)),
+        };
+        assert_eq!(0x45, packet[0]);
+        assert_eq!(packet.len(), usize::from(ordered_field(2)));
+        assert_eq!(identification, u16::from_be_bytes(field(4)));
+        assert_eq!(DONT_FRAGMENT, ordered_field(6));
+        assert_eq!(ttl, packet[8]);
+        assert_eq!(protocol, packet[9]);
+        assert_eq!(0, u16::from_be_bytes(field(10)));
+        assert_eq!(SRC.octets(), packet[12..16]);
+        assert_eq!(DEST.octets(), packet[16..20]);
+    }
+
+    /// The packet as sent on the wire by the kernel, which encodes the fields given in host byte order in network byte
+    /// order, and so as quoted in an ICMP error.
+    fn on_the_wire(packet: &[u8], byte_order: PlatformIpv4FieldByteOrder) -> Vec<u8> {
+        let mut packet = packet.to_vec();
+        if byte_order == PlatformIpv4FieldByteOrder::Host {
+            packet[2..4].reverse();
+            packet[6..8].reverse();
+        }
+        packet
+    }
+
+    /// Build an ICMP probe of `packet_size` in `buffers` and check it, and that it is matched from an ICMP error which
+    /// quotes it in full.
+    fn check_icmp_probe(
+        buffers: &mut PacketBuffers,
+        packet_size: usize,
+        probe: Probe,
+        pattern: PayloadPattern,
+        identifier: TraceId,
+        byte_order: PlatformIpv4FieldByteOrder,
+    ) {
+        let echo_request = make_echo_request_icmp_packet(
+            &mut buffers.datagram,
+            identifier,
+            probe.sequence,
+            icmp_payload_size(packet_size),
+            pattern,
+            None,
+        )
+        .unwrap();
+        let ipv4 = make_ipv4_packet(
+            &mut buffers.ip,
+            byte_order,
+            IpProtocol::Icmp,
+            SRC,
+            DEST,
+            probe.ttl.0,
+            0,
+            echo_request.packet(),
+        )
+        .unwrap();
+        let packet = ipv4.packet();
+        assert_eq!(packet_size, packet.len());
+        check_ipv4_header(packet, byte_order, 1, probe.ttl.0, 0);
+        let icmp = &packet[20..];
+        assert_eq!([0x08, 0x00], icmp[..2]);
+        assert_eq!(0, internet_checksum(icmp));
+        assert!(icmp[8..].iter().all(|&b| b == pattern.0));
+        let quote = time_exceeded(&[&on_the_wire(packet, byte_order)]);
+        assert!(quote.len() <= buffers.recv.len());
+        assert_eq!(
+            Some((identifier.0, probe.sequence.0)),
+            extract(&quote, TracerProtocol::Icmp, PortDirection::None).unwrap()
+        );
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(CASES))]
+
+        #[test]
+        fn test_build_icmp_probe(
+            packet_size in packet_size(),
+            probe in probe(),
+            pattern in any::<u8>(),
+            identifier in any::<u16>(),
+            byte_order in byte_order(),
+        ) {
+            let mut buffers = PacketBuffers::new(usize::from(DEFAULT_MAX_PACKET_SIZE));
+            check_icmp_probe(
+                &mut buffers,
+                packet_size,
+                probe,
+                PayloadPattern(pattern),
+                TraceId(identifier),
+                byte_order,
+            );
+        }
+
+        #[test]
+        fn test_build_udp_probe(
+            packet_size in packet_size(),
+            probe in probe(),
+            pattern in any::<u8>(),
+            initial_sequence in any::<u16>(),
+            (multipath_strategy, direction) in udp_ports_strategy(),
+            byte_order in byte_order(),
+        ) {
+            let mut buffers = PacketBuffers::new(usize::from(DEFAULT_MAX_PACKET_SIZE));
+            check_udp_probe(
+                &mut buffers,
+                packet_size,
+                probe,
+                PayloadPattern(pattern),
+                Sequence(initial_sequence),
+                multipath_strategy,
+                direction,
+                byte_order,
+            );
+        }
+    }
+
+    #[test]
+    fn test_build_icmp_probe_large() {
+        let probe = Probe::new(Sequence(33000), TimeToLive(64), Round(0), SystemTime::now());
+        for packet_size in [1500, 9000, usize::from(MAX_PACKET_SIZE)] {
+            let mut buffers = PacketBuffers::new(packet_size);
+            for byte_order in [
+                PlatformIpv4FieldByteOrder::Host,
+                PlatformIpv4FieldByteOrder::Network,
+            ] {
+                for size in [packet_size, 84] {
+                    check_icmp_probe(
+                        &mut buffers,
+                        size,
+                        probe,
+                        PayloadPattern(0xaa),
+                        TraceId(0x1234),
+                        byte_order,
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_build_icmp_probe_beyond_buffers() {
+        let mut buffers = PacketBuffers::new(1500);
+        let result = make_echo_request_icmp_packet(
+            &mut buffers.datagram,
+            TraceId(1),
+            Sequence(33000),
+            1500 - IcmpPacket::minimum_packet_size() + 1,
+            PayloadPattern(0),
+            None,
+        );
+        assert!(matches!(result, Err(TracerError::Required(_))));
+    }
+
+    /// Build a UDP probe of `packet_size` and check it, and that it is matched from an ICMP error which quotes it in
+    /// full.
+    #[allow(clippy::too_many_arguments)]
+    fn check_udp_probe(
+        buffers: &mut PacketBuffers,
+        packet_size: usize,
+        probe: Probe,
+        pattern: PayloadPattern,
+        initial_sequence: Sequence,
+        multipath_strategy: MultipathStrategy,
+        direction: PortDirection,
+        byte_order: PlatformIpv4FieldByteOrder,
+    ) {
+        let (src_port, dest_port, identification) =
+            udp_ports(&probe, initial_sequence, multipath_strategy, direction);
+        let udp = make_udp_packet(
+            &mut buffers.datagram,
+            SRC,
+            DEST,
+            src_port,
+            dest_port,
+            udp_payload_size(packet_size),
+            pattern,
+            None,
+        )
+        .unwrap();
+        let ipv4 = make_ipv4_packet(
+            &mut buffers.ip,
+            byte_order,
+            IpProtocol::Udp,
+            SRC,
+            DEST,
+            probe.ttl.0,
+            identification,
+            udp.packet(),
+        )
+        .unwrap();
+        let packet = ipv4.packet();
+        assert_eq!(packet_size, packet.len());
+        check_ipv4_header(packet, byte_order, 17, probe.ttl.0, identification);
+        let udp = &packet[20..];
+        assert_eq!(
+            [src_port, dest_port],
+            [0, 2].map(|i| u16::from_be_bytes([udp[i], udp[i + 1]]))
+        );
+        assert_eq!(udp.len(), usize::from(u16::from_be_bytes([udp[4], udp[5]])));
+        let pseudo_header = ipv4_pseudo_header(SRC, DEST, 17, udp.len());
+        assert_eq!(0, internet_checksum(&[&pseudo_header, udp].concat()));
+        assert!(udp[8..].iter().all(|&b| b == pattern.0));
+        let quote = time_exceeded(&[&on_the_wire(packet, byte_order)]);
+        let response =
+            parse_icmp_probe(&quote, TracerProtocol::Udp, multipath_strategy, direction).unwrap();
+        assert!(matches!(
+            response,
+            Some(ProbeResponse::TimeExceeded(data)) if data.sequence == probe.sequence.0
+        ));
+    }
+}
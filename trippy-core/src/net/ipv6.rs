@@ -0,0 +1,864 @@
+use crate::error::TracerError::AddressNotAvailable;
+use crate::error::{ParseError, TraceResult, TracerError};
+use crate::net::channel::PacketBuffers;
+use crate::net::platform;
+use crate::net::platform::Socket;
+use crate::net::quoted;
+use crate::net::socket::{bind_interface, send_probe, set_fwmark, TracerSocket as _};
+use crate::net::stamp::{self, PayloadStamp};
+use crate::packet::checksum::{icmp_ipv6_checksum, udp_ipv6_checksum};
+use crate::packet::icmpv6::destination_unreachable::DestinationUnreachablePacket;
+use crate::packet::icmpv6::echo_reply::EchoReplyPacket;
+use crate::packet::icmpv6::echo_request::EchoRequestPacket;
+use crate::packet::icmpv6::time_exceeded::TimeExceededPacket;
+use crate::packet::icmpv6::{IcmpCode, IcmpPacket, IcmpType};
+use crate::packet::ipv6::Ipv6Packet;
+use crate::packet::udp::UdpPacket;
+use crate::packet::IpProtocol;
+use crate::probe::{FlowKey, IcmpQuote, ProbeResponse, ProbeResponseData, ResponderPorts};
+use crate::types::{PacketSize, PayloadPattern, Sequence, TraceId};
+use crate::util::Required;
+use crate::{PacketCapture, PortDirection, Probe, TracerAddrFamily, TracerProtocol};
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv6Addr, Shutdown, SocketAddr, SocketAddrV6};
+use std::time::SystemTime;
+
+#[allow(clippy::too_many_arguments)]
+pub fn dispatch_icmp_probe(
+    icmp_send_socket: &mut Socket,
+    buffers: &mut PacketBuffers,
+    probe: Probe,
+    src_addr: Ipv6Addr,
+    dest_addr: Ipv6Addr,
+    scope_id: u32,
+    identifier: TraceId,
+    packet_size: PacketSize,
+    payload_pattern: PayloadPattern,
+    payload_stamp: bool,
+    capture: Option<&PacketCapture>,
+) -> TraceResult<FlowKey> {
+    let packet_size = usize::from(packet_size.0);
+    if packet_size > buffers.ip.len() {
+        return Err(TracerError::InvalidPacketSize(packet_size));
+    }
+    let echo_request = make_echo_request_icmp_packet(
+        &mut buffers.datagram,
+        src_addr,
+        dest_addr,
+        identifier,
+        probe.sequence,
+        icmp_payload_size(packet_size),
+        payload_pattern,
+        payload_stamp.then(|| PayloadStamp::new(&probe)),
+    )?;
+    icmp_send_socket.set_unicast_hops_v6(probe.ttl.0)?;
+    let remote_addr = scoped_addr(dest_addr, 0, scope_id);
+    send_probe(
+        icmp_send_socket,
+        echo_request.packet(),
+        packet_size,
+        remote_addr,
+    )?;
+    if let Some(capture) = capture {
+        capture_packet(
+            capture,
+            src_addr,
+            dest_addr,
+            IpProtocol::IcmpV6,
+            probe.ttl.0,
+            echo_request.packet(),
+        )?;
+    }
+    Ok(FlowKey::icmp(identifier.0, echo_request.get_checksum()))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn dispatch_udp_probe(
+    udp_send_socket: &mut Socket,
+    buffers: &mut PacketBuffers,
+    probe: Probe,
+    src_addr: Ipv6Addr,
+    dest_addr: Ipv6Addr,
+    scope_id: u32,
+    port_direction: PortDirection,
+    packet_size: PacketSize,
+    payload_pattern: PayloadPattern,
+    payload_stamp: bool,
+    capture: Option<&PacketCapture>,
+) -> TraceResult<FlowKey> {
+    let packet_size = usize::from(packet_size.0);
+    if packet_size > buffers.ip.len() {
+        return Err(TracerError::InvalidPacketSize(packet_size));
+    }
+    let (src_port, dest_port) = udp_ports(&probe, port_direction);
+    let udp = make_udp_packet(
+        &mut buffers.datagram,
+        src_addr,
+        dest_addr,
+        src_port,
+        dest_port,
+        udp_payload_size(packet_size),
+        payload_pattern,
+        payload_stamp.then(|| PayloadStamp::new(&probe)),
+    )?;
+    udp_send_socket.set_unicast_hops_v6(probe.ttl.0)?;
+    // Note that we set the port to be 0 in the remote `SocketAddr` as the target port is encoded in the `UDP`
+    // packet.  If we (redundantly) set the target port here then the send will fail with `EINVAL`.
+    let remote_addr = scoped_addr(dest_addr, 0, scope_id);
+    send_probe(udp_send_socket, udp.packet(), packet_size, remote_addr)?;
+    if let Some(capture) = capture {
+        capture_packet(
+            capture,
+            src_addr,
+            dest_addr,
+            IpProtocol::Udp,
+            probe.ttl.0,
+            udp.packet(),
+        )?;
+    }
+    Ok(FlowKey::udp(src_port, dest_port, udp.get_checksum()))
+}
+
+/// The socket address of `addr` and `port` within the scope `scope_id`, the index of the interface through which a
+/// link-local address is reached, or zero for any other address.
+fn scoped_addr(addr: Ipv6Addr, port: u16, scope_id: u32) -> SocketAddr {
+    SocketAddr::V6(SocketAddrV6::new(addr, port, 0, scope_id))
+}
+
+/// The source and destination port of a UDP `probe`, one of which is its sequence.
+fn udp_ports(probe: &Probe, port_direction: PortDirection) -> (u16, u16) {
+    match port_direction {
+        PortDirection::FixedSrc(src_port) => (src_port.0, probe.sequence.0),
+        PortDirection::FixedDest(dest_port) => (probe.sequence.0, dest_port.0),
+        PortDirection::FixedBoth(_, _) | PortDirection::None => unimplemented!(),
+    }
+}
+
+pub fn dispatch_tcp_probe(
+    probe: Probe,
+    src_addr: Ipv6Addr,
+    dest_addr: Ipv6Addr,
+    scope_id: u32,
+    port_direction: PortDirection,
+    interface: Option<&str>,
+    fwmark: Option<u32>,
+) -> TraceResult<(Socket, FlowKey)> {
+    let (src_port, dest_port) = match port_direction {
+        PortDirection::FixedSrc(src_port) => (src_port.0, probe.sequence.0),
+        PortDirection::FixedDest(dest_port) => (probe.sequence.0, dest_port.0),
+        PortDirection::FixedBoth(_, _) | PortDirection::None => unimplemented!(),
+    };
+    let mut socket = Socket::new_stream_socket_ipv6()?;
+    bind_interface(&socket, interface, TracerAddrFamily::Ipv6)?;
+    set_fwmark(&socket, fwmark)?;
+    let local_addr = scoped_addr(src_addr, src_port, scope_id);
+    socket
+        .bind(local_addr)
+        .map_err(|err| TracerError::from_socket_error("bind", local_addr, err))?;
+    socket.set_unicast_hops_v6(probe.ttl.0)?;
+    let remote_addr = scoped_addr(dest_addr, dest_port, scope_id);
+    match socket.connect(remote_addr) {
+        Ok(_) => {}
+        Err(err) => {
+            if let Some(code) = err.raw_os_error() {
+                if platform::is_not_in_progress_error(code) {
+                    return match err.kind() {
+                        ErrorKind::AddrInUse | ErrorKind::AddrNotAvailable => {
+                            Err(AddressNotAvailable(local_addr))
+                        }
+                        _ => Err(TracerError::IoError(err)),
+                    };
+                }
+            } else {
+                return Err(TracerError::IoError(err));
+            }
+        }
+    }
+    Ok((socket, FlowKey::tcp(src_port, dest_port)))
+}
+
+pub fn recv_icmp_probe(
+    recv_socket: &mut Socket,
+    buf: &mut [u8],
+    protocol: TracerProtocol,
+    direction: PortDirection,
+    local_addr: Ipv6Addr,
+    capture: Option<&PacketCapture>,
+) -> TraceResult<Option<ProbeResponse>> {
+    match recv_socket.recv_from(buf) {
+        Ok((bytes_read, addr)) => {
+            let src_addr = match addr.as_ref().req()? {
+                SocketAddr::V6(addr) => addr.ip(),
+                SocketAddr::V4(_) => {
+                    return Err(TracerError::MalformedPacket(ParseError::Invalid(
+                        "source address family",
+                    )))
+                }
+            };
+
+            if let Some(capture) = capture {
+                capture_packet(
+                    capture,
+                    *src_addr,
+                    local_addr,
+                    IpProtocol::IcmpV6,
+                    0,
+                    &buf[..bytes_read],
+                )?;
+            }
+
+            Ok(parse_icmp_probe(
+                &buf[..bytes_read],
+                protocol,
+                direction,
+                *src_addr,
+            )?)
+        }
+        Err(err) => match err.kind() {
+            ErrorKind::WouldBlock => Ok(None),
+            _ => Err(TracerError::IoError(err)),
+        },
+    }
+}
+
+/// Generate a response for a TCP probe to `dest_addr` whose socket has connected or failed.
+///
+/// The port of the peer of a connected socket is recorded against the port probed, as the reply came from it.
+pub fn recv_tcp_socket(
+    tcp_socket: &Socket,
+    sequence: Sequence,
+    dest_addr: SocketAddr,
+) -> TraceResult<Option<ProbeResponse>> {
+    match tcp_socket.take_error()? {
+        None => {
+            let peer_addr = tcp_socket.peer_addr()?.req()?;
+            tcp_socket.shutdown(Shutdown::Both)?;
+            let ports = ResponderPorts::new(dest_addr.port(), peer_addr.port());
+            return Ok(Some(ProbeResponse::TcpReply(
+                ProbeResponseData::new(SystemTime::now(), peer_addr.ip(), 0, sequence.0)
+                    .with_responder_ports(ports),
+            )));
+        }
+        Some(err) => {
+            if let Some(code) = err.raw_os_error() {
+                if platform::is_conn_refused_error(code) {
+                    return Ok(Some(ProbeResponse::TcpRefused(ProbeResponseData::new(
+                        SystemTime::now(),
+                        dest_addr.ip(),
+                        0,
+                        sequence.0,
+                    ))));
+                }
+                if platform::is_host_unreachable_error(code) {
+                    let error_addr = tcp_socket.icmp_error_info()?;
+                    return Ok(Some(ProbeResponse::TimeExceeded(ProbeResponseData::new(
+                        SystemTime::now(),
+                        error_addr,
+                        0,
+                        sequence.0,
+                    ))));
+                }
+            }
+        }
+    };
+    Ok(None)
+}
+
+/// Create a `UdpPacket`
+#[allow(clippy::too_many_arguments)]
+fn make_udp_packet(
+    udp_buf: &mut [u8],
+    src_addr: Ipv6Addr,
+    dest_addr: Ipv6Addr,
+    src_port: u16,
+    dest_port: u16,
+    payload_size: usize,
+    payload_pattern: PayloadPattern,
+    stamp: Option<PayloadStamp>,
+) -> TraceResult<UdpPacket<'_>> {
+    let udp_packet_size = UdpPacket::minimum_packet_size() + payload_size;
+    let udp_buf = udp_buf.get_mut(..udp_packet_size).req()?;
+    udp_buf[UdpPacket::minimum_packet_size()..].fill(payload_pattern.0);
+    if let Some(stamp) = stamp {
+        stamp.write(&mut udp_buf[UdpPacket::minimum_packet_size()..])?;
+    }
+    let mut udp = UdpPacket::new(udp_buf).req()?;
+    udp.set_source(src_port);
+    udp.set_destination(dest_port);
+    udp.set_length(udp_packet_size as u16);
+    udp.set_checksum(udp_ipv6_checksum(udp.packet(), src_addr, dest_addr));
+    Ok(udp)
+}
+
+/// Create an ICMP `EchoRequest` packet.
+#[allow(clippy::too_many_arguments)]
+fn make_echo_request_icmp_packet(
+    icmp_buf: &mut [u8],
+    src_addr: Ipv6Addr,
+    dest_addr: Ipv6Addr,
+    identifier: TraceId,
+    sequence: Sequence,
+    payload_size: usize,
+    payload_pattern: PayloadPattern,
+    stamp: Option<PayloadStamp>,
+) -> TraceResult<EchoRequestPacket<'_>> {
+    let packet_size = IcmpPacket::minimum_packet_size() + payload_size;
+    let icmp_buf = icmp_buf.get_mut(..packet_size).req()?;
+    icmp_buf[IcmpPacket::minimum_packet_size()..].fill(payload_pattern.0);
+    if let Some(stamp) = stamp {
+        stamp.write(&mut icmp_buf[IcmpPacket::minimum_packet_size()..])?;
+    }
+    let mut icmp = EchoRequestPacket::new(icmp_buf).req()?;
+    icmp.set_icmp_type(IcmpType::EchoRequest);
+    icmp.set_icmp_code(IcmpCode(0));
+    icmp.set_identifier(identifier.0);
+    icmp.set_sequence(sequence.0);
+    icmp.set_checksum(icmp_ipv6_checksum(icmp.packet(), src_addr, dest_addr));
+    Ok(icmp)
+}
+
+fn icmp_payload_size(packet_size: usize) -> usize {
+    let ip_header_size = Ipv6Packet::minimum_packet_size();
+    let icmp_header_size = IcmpPacket::minimum_packet_size();
+    packet_size - icmp_header_size - ip_header_size
+}
+
+fn udp_payload_size(packet_size: usize) -> usize {
+    let ip_header_size = Ipv6Packet::minimum_packet_size();
+    let udp_header_size = UdpPacket::minimum_packet_size();
+    packet_size - udp_header_size - ip_header_size
+}
+
+/// Capture an IPv6 packet.
+///
+/// Packets are sent and received without the `IPv6` header and so one is constructed here from the addresses and
+/// `hop_limit` given.  The hop limit of received packets is not available and is recorded as zero.
+fn capture_packet(
+    capture: &PacketCapture,
+    src_addr: Ipv6Addr,
+    dest_addr: Ipv6Addr,
+    next_header: IpProtocol,
+    hop_limit: u8,
+    payload: &[u8],
+) -> TraceResult<()> {
+    let timestamp = SystemTime::now();
+    let mut ipv6_buf = vec![0_u8; Ipv6Packet::minimum_packet_size() + payload.len()];
+    let mut ipv6 = Ipv6Packet::new(&mut ipv6_buf).req()?;
+    ipv6.set_version(6);
+    ipv6.set_payload_length(payload.len() as u16);
+    ipv6.set_next_header(next_header);
+    ipv6.set_hop_limit(hop_limit);
+    ipv6.set_source_address(src_addr);
+    ipv6.set_destination_address(dest_addr);
+    ipv6.set_payload(payload);
+    capture.capture(timestamp, ipv6.packet());
+    Ok(())
+}
+
+/// Parse the response to a probe from an `ICMPv6` `packet` received from `src` on the ICMP socket, if it is one.
+///
+/// Unlike a raw IPv4 socket, a raw `ICMPv6` socket delivers the `ICMPv6` message without the IPv6 header which carried
+/// it, on every platform (see [rfc3542] section 3), and so the `packet` starts with the `ICMPv6` header and the `src`
+/// must be taken from the address the packet was received from.
+///
+/// This is the pure step of receiving a probe response and so is fed arbitrary bytes when fuzzed, it must return an
+/// error for any packet which cannot be parsed rather than panic.
+///
+/// [rfc3542]: https://datatracker.ietf.org/doc/html/rfc3542#section-3
+pub fn parse_icmp_probe(
+    packet: &[u8],
+    protocol: TracerProtocol,
+    direction: PortDirection,
+    src: Ipv6Addr,
+) -> Result<Option<ProbeResponse>, ParseError> {
+    let recv = SystemTime::now();
+    let ip = IpAddr::V6(src);
+    let icmp_v6 = IcmpPacket::new_view(packet).ok_or(ParseError::Truncated("ICMPv6 header"))?;
+    Ok(match icmp_v6.get_icmp_type() {
+        IcmpType::TimeExceeded => {
+            let packet = TimeExceededPacket::new_view(icmp_v6.packet())
+                .ok_or(ParseError::Truncated("ICMPv6 time exceeded"))?;
+            extract_quoted(packet.payload(), protocol, direction)?.map(|(id, seq, quote)| {
+                ProbeResponse::TimeExceeded(
+                    ProbeResponseData::new(recv, ip, id, seq).with_quote(quote),
+                )
+            })
+        }
+        IcmpType::DestinationUnreachable => {
+            let packet = DestinationUnreachablePacket::new_view(icmp_v6.packet())
+                .ok_or(ParseError::Truncated("ICMPv6 destination unreachable"))?;
+            let code = icmp_v6.get_icmp_code().0;
+            extract_quoted(packet.payload(), protocol, direction)?.map(|(id, seq, quote)| {
+                ProbeResponse::DestinationUnreachable(
+                    ProbeResponseData::new(recv, ip, id, seq).with_quote(quote),
+                    code,
+                    None,
+                )
+            })
+        }
+        IcmpType::EchoReply => match protocol {
+            TracerProtocol::Icmp => {
+                let packet = EchoReplyPacket::new_view(icmp_v6.packet())
+                    .ok_or(ParseError::Truncated("ICMPv6 echo reply"))?;
+                let id = packet.get_identifier();
+                let seq = packet.get_sequence();
+                Some(ProbeResponse::EchoReply(ProbeResponseData::new(
+                    recv, ip, id, seq,
+                )))
+            }
+            TracerProtocol::Udp | TracerProtocol::Tcp => None,
+        },
+        _ => None,
+    })
+}
+
+/// Extract the identifier and sequence of the probe quoted in the `payload` of an `ICMPv6` error, along with the size
+/// of the payload and of the quote, if it is one of ours.
+///
+/// From [rfc4443] (section 2.4, point c):
+///
+///    "Every ICMPv6 error message (type < 128) MUST include as much of
+///    the IPv6 offending (invoking) packet (the packet that caused the
+///    error) as possible without making the error message packet exceed
+///    the minimum IPv6 MTU"
+///
+/// The probes are no larger than the minimum IPv6 MTU and so should be quoted in full, however some devices quote
+/// less.  The quote must hold the IPv6 header of the probe and enough of the datagram it heads to read the fields used
+/// to match the probe, otherwise it is malformed.  A quote of a datagram of another protocol is not one of ours.
+///
+/// [rfc4443]: https://datatracker.ietf.org/doc/html/rfc4443#section-2.4
+fn extract_quoted(
+    payload: &[u8],
+    protocol: TracerProtocol,
+    direction: PortDirection,
+) -> Result<Option<(u16, u16, IcmpQuote)>, ParseError> {
+    let ip_protocol = match protocol {
+        TracerProtocol::Icmp => IpProtocol::IcmpV6,
+        TracerProtocol::Udp => IpProtocol::Udp,
+        TracerProtocol::Tcp => IpProtocol::Tcp,
+    };
+    let ipv6 = Ipv6Packet::new_view(payload).ok_or(ParseError::Truncated("quoted IPv6 header"))?;
+    if ipv6.get_version() != 6 {
+        return Err(ParseError::Invalid("quoted IP version"));
+    }
+    if ipv6.get_next_header() != ip_protocol {
+        return Ok(None);
+    }
+    let datagram = ipv6.payload();
+    let full = datagram.len() == usize::from(ipv6.get_payload_length());
+    let quote = IcmpQuote::new(
+        payload.len(),
+        Ipv6Packet::minimum_packet_size() + datagram.len(),
+        full,
+    );
+    let extracted = match protocol {
+        TracerProtocol::Icmp => extract_echo_request(datagram)?,
+        TracerProtocol::Udp => {
+            let header = quoted::udp_header(datagram, full)?;
+            let sequence = match direction {
+                PortDirection::FixedDest(_) => header.src_port,
+                _ => header.dest_port,
+            };
+            Some((0, sequence))
+        }
+        TracerProtocol::Tcp => {
+            let header = quoted::tcp_header(datagram)?;
+            let sequence = match direction {
+                PortDirection::FixedSrc(_) => header.dest_port,
+                _ => header.src_port,
+            };
+            Some((0, sequence))
+        }
+    };
+    Ok(extracted
+        .filter(|(_, seq)| {
+            matches!(protocol, TracerProtocol::Tcp) || stamp::is_consistent(datagram, *seq)
+        })
+        .map(|(id, seq)| (id, seq, quote)))
+}
+
+/// Get the identifier and sequence of the quoted `EchoRequest`, if it is one.
+fn extract_echo_request(datagram: &[u8]) -> Result<Option<(u16, u16)>, ParseError> {
+    let echo_request = EchoRequestPacket::new_view(datagram)
+        .ok_or(ParseError::Truncated("quoted echo request"))?;
+    Ok((echo_request.get_icmp_type() == IcmpType::EchoRequest)
+        .then(|| (echo_request.get_identifier(), echo_request.get_sequence())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::reference::{internet_checksum, ipv6_pseudo_header};
+    use crate::types::{Round, TimeToLive};
+    use crate::{DEFAULT_MAX_PACKET_SIZE, MAX_PACKET_SIZE};
+    use proptest::prelude::*;
+    use rand::Rng;
+
+    /// The UDP header of a probe from port 33434 to port 33435 of length 36.
+    const UDP: [u8; 8] = [0x82, 0x9a, 0x82, 0x9b, 0x00, 0x24, 0xbe, 0xef];
+
+    /// The `EchoRequest` of a probe, with identifier 0x1234 and sequence 33000.
+    const ECHO_REQUEST: [u8; 8] = [0x80, 0x00, 0x00, 0x00, 0x12, 0x34, 0x80, 0xe8];
+
+    /// The IPv6 header of a probe within the documentation prefix with a hop limit of 1.
+    fn ipv6_header(next_header: u8, payload_len: u16) -> Vec<u8> {
+        let mut header = vec![0x60, 0x00, 0x00, 0x00];
+        header.extend(payload_len.to_be_bytes());
+        header.extend([next_header, 0x01]);
+        header.extend([
+            0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x02,
+        ]);
+        header.extend([
+            0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x08,
+        ]);
+        header
+    }
+
+    /// A `TimeExceeded` quoting `quote`.
+    fn time_exceeded(quote: &[&[u8]]) -> Vec<u8> {
+        let mut packet = vec![0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        packet.extend(quote.concat());
+        packet
+    }
+
+    fn extract(
+        packet: &[u8],
+        protocol: TracerProtocol,
+        direction: PortDirection,
+    ) -> Result<Option<(u16, u16)>, ParseError> {
+        let src = Ipv6Addr::LOCALHOST;
+        Ok(match parse_icmp_probe(packet, protocol, direction, src)? {
+            Some(ProbeResponse::TimeExceeded(data)) => Some((data.identifier, data.sequence)),
+            _ => None,
+        })
+    }
+
+    fn extract_udp(packet: &[u8]) -> Result<Option<(u16, u16)>, ParseError> {
+        extract(
+            packet,
+            TracerProtocol::Udp,
+            PortDirection::new_fixed_src(33434),
+        )
+    }
+
+    fn is_malformed(result: &Result<Option<(u16, u16)>, ParseError>) -> bool {
+        result.is_err()
+    }
+
+    #[test]
+    fn test_udp_full_quote() {
+        let packet = time_exceeded(&[&ipv6_header(17, 36), &UDP, &[0xaa; 28]]);
+        assert_eq!(Some((0, 33435)), extract_udp(&packet).unwrap());
+    }
+
+    #[test]
+    fn test_udp_short_quote() {
+        let packet = time_exceeded(&[&ipv6_header(17, 36), &UDP]);
+        assert_eq!(Some((0, 33435)), extract_udp(&packet).unwrap());
+        let packet = time_exceeded(&[&ipv6_header(17, 36), &UDP[..4]]);
+        assert_eq!(Some((0, 33435)), extract_udp(&packet).unwrap());
+        let packet = time_exceeded(&[&ipv6_header(17, 36), &UDP[..3]]);
+        assert!(is_malformed(&extract_udp(&packet)));
+        let packet = time_exceeded(&[&ipv6_header(17, 36)[..39]]);
+        assert!(is_malformed(&extract_udp(&packet)));
+    }
+
+    fn extract_quote(packet: &[u8]) -> Option<IcmpQuote> {
+        let direction = PortDirection::new_fixed_src(33434);
+        let src = Ipv6Addr::LOCALHOST;
+        match parse_icmp_probe(packet, TracerProtocol::Udp, direction, src) {
+            Ok(Some(ProbeResponse::TimeExceeded(data))) => data.quote,
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_quote_size_short() {
+        let packet = time_exceeded(&[&ipv6_header(17, 36), &UDP]);
+        assert_eq!(Some(IcmpQuote::new(48, 48, false)), extract_quote(&packet));
+    }
+
+    #[test]
+    fn test_quote_size_full() {
+        let packet = time_exceeded(&[&ipv6_header(17, 36), &UDP, &[0xaa; 28]]);
+        assert_eq!(Some(IcmpQuote::new(76, 76, true)), extract_quote(&packet));
+    }
+
+    #[test]
+    fn test_udp_full_quote_mangled_length() {
+        let mut udp = UDP;
+        udp[5] = 0x30;
+        let packet = time_exceeded(&[&ipv6_header(17, 36), &udp, &[0xaa; 28]]);
+        assert!(is_malformed(&extract_udp(&packet)));
+    }
+
+    #[test]
+    fn test_mangled_header() {
+        let mut header = ipv6_header(17, 36);
+        header[0] = 0x40;
+        let packet = time_exceeded(&[&header, &UDP]);
+        assert!(is_malformed(&extract_udp(&packet)));
+        let packet = time_exceeded(&[&ipv6_header(17, 0), &UDP]);
+        assert!(is_malformed(&extract_udp(&packet)));
+    }
+
+    #[test]
+    fn test_other_protocol() {
+        let packet = time_exceeded(&[&ipv6_header(6, 20), &UDP]);
+        assert_eq!(None, extract_udp(&packet).unwrap());
+    }
+
+    #[test]
+    fn test_icmp_quote() {
+        let packet = time_exceeded(&[&ipv6_header(58, 8), &ECHO_REQUEST]);
+        let extracted = extract(&packet, TracerProtocol::Icmp, PortDirection::None).unwrap();
+        assert_eq!(Some((0x1234, 33000)), extracted);
+        let packet = time_exceeded(&[&ipv6_header(58, 8), &ECHO_REQUEST[..6]]);
+        let extracted = extract(&packet, TracerProtocol::Icmp, PortDirection::None);
+        assert!(is_malformed(&extracted));
+    }
+
+    /// An `EchoReply` to the probe of `ECHO_REQUEST`.
+    const ECHO_REPLY: [u8; 8] = [0x81, 0x00, 0x00, 0x00, 0x12, 0x34, 0x80, 0xe8];
+
+    /// The packet received on the raw `ICMPv6` socket holds no IPv6 header, the responder being the address it was
+    /// received from rather than any address within the packet.
+    #[test]
+    fn test_headerless_time_exceeded() {
+        let from = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+        let packet = time_exceeded(&[&ipv6_header(17, 36), &UDP, &[0xaa; 28]]);
+        let direction = PortDirection::new_fixed_src(33434);
+        match parse_icmp_probe(&packet, TracerProtocol::Udp, direction, from).unwrap() {
+            Some(ProbeResponse::TimeExceeded(data)) => {
+                assert_eq!(IpAddr::V6(from), data.addr);
+                assert_eq!((0, 33435), (data.identifier, data.sequence));
+            }
+            response => panic!("expected a time exceeded, got {response:?}"),
+        }
+    }
+
+    #[test]
+    fn test_headerless_echo_reply() {
+        let from = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 8);
+        let packet = parse_icmp_probe(&ECHO_REPLY, TracerProtocol::Icmp, PortDirection::None, from);
+        match packet.unwrap() {
+            Some(ProbeResponse::EchoReply(data)) => {
+                assert_eq!(IpAddr::V6(from), data.addr);
+                assert_eq!((0x1234, 33000), (data.identifier, data.sequence));
+            }
+            response => panic!("expected an echo reply, got {response:?}"),
+        }
+    }
+
+    /// A packet which starts with an IPv6 header, as a raw IPv4 socket would deliver it, is not read as `ICMPv6`.
+    #[test]
+    fn test_header_not_expected() {
+        let packet = [&ipv6_header(58, 8)[..], &ECHO_REPLY].concat();
+        let parsed = parse_icmp_probe(
+            &packet,
+            TracerProtocol::Icmp,
+            PortDirection::None,
+            Ipv6Addr::LOCALHOST,
+        );
+        assert_eq!(Ok(None), parsed.map(|response| response.map(|_| ())));
+    }
+
+    /// Packets which were once mishandled, with the error each must be rejected with.
+    #[test]
+    fn test_regression_corpus() {
+        let corpus: [(&[u8], ParseError); 5] = [
+            (&[], ParseError::Truncated("ICMPv6 header")),
+            (&[0x03], ParseError::Truncated("ICMPv6 header")),
+            (
+                &time_exceeded(&[&ipv6_header(17, 36)[..39]]),
+                ParseError::Truncated("quoted IPv6 header"),
+            ),
+            (
+                &time_exceeded(&[&[0x40], &ipv6_header(17, 36)[1..], &UDP]),
+                ParseError::Invalid("quoted IP version"),
+            ),
+            (
+                &time_exceeded(&[&ipv6_header(17, 36), &UDP[..3]]),
+                ParseError::Truncated("quoted UDP header"),
+            ),
+        ];
+        for (packet, err) in corpus {
+            assert_eq!(Err(err), extract_udp(packet), "{packet:02x?}");
+        }
+    }
+
+    #[test]
+    fn test_fuzz() {
+        let mut rng = rand::thread_rng();
+        let corpus = [
+            time_exceeded(&[&ipv6_header(17, 36), &UDP, &[0xaa; 28]]),
+            time_exceeded(&[&ipv6_header(58, 8), &ECHO_REQUEST]),
+        ];
+        for _ in 0..10000 {
+            let mut packet = if rng.gen_bool(0.5) {
+                let len = rng.gen_range(0..128);
+                (0..len).map(|_| rng.gen()).collect()
+            } else {
+                let mut packet = corpus[rng.gen_range(0..corpus.len())].clone();
+                packet.truncate(rng.gen_range(0..=packet.len()));
+                packet
+            };
+            if !packet.is_empty() {
+                let i = rng.gen_range(0..packet.len());
+                packet[i] = rng.gen();
+            }
+            for protocol in [
+                TracerProtocol::Icmp,
+                TracerProtocol::Udp,
+                TracerProtocol::Tcp,
+            ] {
+                let _ = extract(&packet, protocol, PortDirection::new_fixed_src(33434));
+            }
+        }
+    }
+
+    /// The addresses of the probes of `ipv6_header`.
+    const SRC: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2);
+    const DEST: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 8);
+
+    /// The number of packets built by each property test.
+    const CASES: u32 = 1000;
+
+    prop_compose! {
+        fn probe()(sequence in any::<u16>(), ttl in 1..=u8::MAX, round in any::<usize>()) -> Probe {
+            Probe::new(Sequence(sequence), TimeToLive(ttl), Round(round), SystemTime::now())
+        }
+    }
+
+    fn packet_size() -> impl Strategy<Value = usize> {
+        Ipv6Packet::minimum_packet_size() + 8..=usize::from(DEFAULT_MAX_PACKET_SIZE)
+    }
+
+    fn port_direction() -> impl Strategy<Value = PortDirection> {
+        prop_oneof![
+            any::<u16>().prop_map(PortDirection::new_fixed_src),
+            any::<u16>().prop_map(PortDirection::new_fixed_dest),
+        ]
+    }
+
+    /// Build an ICMP probe of `packet_size` in `buffers` and check it, and that it is matched from an ICMP error which
+    /// quotes it in full.
+    fn check_icmp_probe(
+        buffers: &mut PacketBuffers,
+        packet_size: usize,
+        probe: Probe,
+        pattern: PayloadPattern,
+        identifier: TraceId,
+    ) {
+        let echo_request = make_echo_request_icmp_packet(
+            &mut buffers.datagram,
+            SRC,
+            DEST,
+            identifier,
+            probe.sequence,
+            icmp_payload_size(packet_size),
+            pattern,
+            None,
+        )
+        .unwrap();
+        let icmp = echo_request.packet();
+        assert_eq!(packet_size, Ipv6Packet::minimum_packet_size() + icmp.len());
+        assert_eq!([0x80, 0x00], icmp[..2]);
+        let pseudo_header = ipv6_pseudo_header(SRC, DEST, 58, icmp.len());
+        assert_eq!(0, internet_checksum(&[&pseudo_header, icmp].concat()));
+        assert!(icmp[8..].iter().all(|&b| b == pattern.0));
+        let quote = time_exceeded(&[&ipv6_header(58, icmp.len() as u16), icmp]);
+        assert!(quote.len() <= buffers.recv.len());
+        assert_eq!(
+            Some((identifier.0, probe.sequence.0)),
+            extract(&quote, TracerProtocol::Icmp, PortDirection::None).unwrap()
+        );
+    }
+
+    /// Build a UDP probe of `packet_size` and check it, and that it is matched from an ICMP error which quotes it in
+    /// full.
+    fn check_udp_probe(
+        buffers: &mut PacketBuffers,
+        packet_size: usize,
+        probe: Probe,
+        pattern: PayloadPattern,
+        direction: PortDirection,
+    ) {
+        let (src_port, dest_port) = udp_ports(&probe, direction);
+        let udp = make_udp_packet(
+            &mut buffers.datagram,
+            SRC,
+            DEST,
+            src_port,
+            dest_port,
+            udp_payload_size(packet_size),
+            pattern,
+            None,
+        )
+        .unwrap();
+        let udp = udp.packet();
+        assert_eq!(packet_size, Ipv6Packet::minimum_packet_size() + udp.len());
+        assert_eq!(
+            [src_port, dest_port],
+            [0, 2].map(|i| u16::from_be_bytes([udp[i], udp[i + 1]]))
+        );
+        assert_eq!(udp.len(), usize::from(u16::from_be_bytes([udp[4], udp[5]])));
+        let pseudo_header = ipv6_pseudo_header(SRC, DEST, 17, udp.len());
+        assert_eq!(0, internet_checksum(&[&pseudo_header, udp].concat()));
+        assert!(udp[8..].iter().all(|&b| b == pattern.0));
+        let quote = time_exceeded(&[&ipv6_header(17, udp.len() as u16), udp]);
+        assert_eq!(
+            Some((0, probe.sequence.0)),
+            extract(&quote, TracerProtocol::Udp, direction).unwrap()
+        );
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(CASES))]
+
+        #[test]
+        fn test_build_icmp_probe(
+            packet_size in packet_size(),
+            probe in probe(),
+            pattern in any::<u8>(),
+            identifier in any::<u16>(),
+        ) {
+            let mut buffers = PacketBuffers::new(usize::from(DEFAULT_MAX_PACKET_SIZE));
+            check_icmp_probe(
+                &mut buffers,
+                packet_size,
+                probe,
+                PayloadPattern(pattern),
+                TraceId(identifier),
+            );
+        }
+
+        #[test]
+        fn test_build_udp_probe(
+            packet_size in packet_size(),
+            probe in probe(),
+            pattern in any::<u8>(),
+            direction in port_direction(),
+        ) {
+            let mut buffers = PacketBuffers::new(usize::from(DEFAULT_MAX_PACKET_SIZE));
+            check_udp_probe(&mut buffers, packet_size, probe, PayloadPattern(pattern), direction);
+        }
+    }
+
+    #[test]
+    fn test_build_icmp_probe_large() {
+        let probe = Probe::new(Sequence(33000), TimeToLive(64), Round(0), SystemTime::now());
+        for packet_size in [1500, 9000, usize::from(MAX_PACKET_SIZE)] {
+            let mut buffers = PacketBuffers::new(packet_size);
+            for size in [packet_size, 84] {
+                check_icmp_probe(
+                    &mut buffers,
+                    size,
+                    probe,
+                    PayloadPattern(0xaa),
+                    TraceId(0x1234),
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use crate::error::{TraceResult, TracerError};
+use crate::probe::ProbeResponse;
+use std::fmt::{Display, Formatter};
+
+/// The counts of the packets received by a channel, by what became of them.
+///
+/// Every packet received is either matched to a probe or counted as the reason it was not, such that loss which is
+/// not loss of the network, replies which were received but not attributed to a probe, may be told apart from it.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChannelMetrics {
+    /// The packets received.
+    pub received: u64,
+    /// The packets matched to a probe of the current round.
+    pub matched: u64,
+    /// The packets which were not of a probe of this trace, such as those of another trace or of another host.
+    pub ignored: u64,
+    /// The packets which were too short, or too mangled, to be matched to a probe.
+    pub malformed: u64,
+    /// The packets of a probe which had already been answered.
+    pub duplicates: u64,
+    /// The packets of a probe of a round which had already completed.
+    pub late: u64,
+    /// The TCP probes abandoned, and so lost, to make room for another as too many awaited connection.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub tcp_evicted: u64,
+}
+
+impl ChannelMetrics {
+    /// Count a packet received, and whether it could be read as the response to a probe.
+    ///
+    /// A packet which was read as a response is not counted further until it is attributed to a probe, or not, by the
+    /// tracer.
+    pub fn count_packet(&mut self, parsed: &TraceResult<Option<ProbeResponse>>) {
+        self.received += 1;
+        match parsed {
+            Err(TracerError::MalformedPacket(_)) => self.malformed += 1,
+            Ok(None) => self.ignored += 1,
+            Ok(Some(_)) | Err(_) => {}
+        }
+    }
+
+    /// Count a response which the tracer did, or did not, attribute to a probe.
+    pub fn count_attribution(&mut self, attribution: Attribution) {
+        match attribution {
+            Attribution::Matched => self.matched += 1,
+            Attribution::Ignored => self.ignored += 1,
+            Attribution::Duplicate => self.duplicates += 1,
+            Attribution::Late => self.late += 1,
+        }
+    }
+}
+
+impl Display for ChannelMetrics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "received={} matched={} ignored={} malformed={} duplicates={} late={}",
+            self.received, self.matched, self.ignored, self.malformed, self.duplicates, self.late
+        )?;
+        if self.tcp_evicted > 0 {
+            write!(f, " tcp_evicted={}", self.tcp_evicted)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether the tracer attributed a response to a probe, and if not why not.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Attribution {
+    /// The response is of a probe of the current round which had not yet been answered.
+    Matched,
+    /// The response is not of a probe of this trace, its identifier being that of another.
+    Ignored,
+    /// The response is of a probe which had already been answered.
+    Duplicate,
+    /// The response is of a probe of a round which has already completed.
+    Late,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseError;
+    use crate::probe::ProbeResponseData;
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::time::SystemTime;
+
+    #[test]
+    fn test_count_packet() {
+        let mut metrics = ChannelMetrics::default();
+        let data = ProbeResponseData::new(SystemTime::now(), IpAddr::V4(Ipv4Addr::LOCALHOST), 1, 1);
+        metrics.count_packet(&Ok(Some(ProbeResponse::EchoReply(data))));
+        metrics.count_packet(&Ok(None));
+        metrics.count_packet(&Err(TracerError::MalformedPacket(ParseError::Truncated(
+            "ICMP header",
+        ))));
+        metrics.count_attribution(Attribution::Matched);
+        assert_eq!(
+            ChannelMetrics {
+                received: 3,
+                matched: 1,
+                ignored: 1,
+                malformed: 1,
+                duplicates: 0,
+                late: 0,
+                tcp_evicted: 0,
+            },
+            metrics
+        );
+        assert_eq!(
+            "received=3 matched=1 ignored=1 malformed=1 duplicates=0 late=0",
+            metrics.to_string()
+        );
+        metrics.tcp_evicted = 2;
+        assert_eq!(
+            "received=3 matched=1 ignored=1 malformed=1 duplicates=0 late=0 tcp_evicted=2",
+            metrics.to_string()
+        );
+    }
+}
@@ -0,0 +1,141 @@
+use crate::error::{TraceResult, TracerError};
+use crate::net::platform::for_address;
+use std::net::IpAddr;
+
+/// The byte order to encode the `total_length`, `flags` and `fragment_offset` fields of the IPv4 header.
+///
+/// To quote directly from the `mtr` source code (from `check_length_order` in `probe_unix.c`):
+///
+/// "Nearly all fields in the IP header should be encoded in network byte
+/// order prior to passing to send().  However, the required byte order of
+/// the length field of the IP header is inconsistent between operating
+/// systems and operating system versions.  FreeBSD 11 requires the length
+/// field in network byte order, but some older versions of FreeBSD
+/// require host byte order.  OS X requires the length field in host
+/// byte order.  Linux will accept either byte order."
+///
+/// The BSDs have changed their requirement between versions and so, as on macOS, the byte order is discovered rather
+/// than assumed on FreeBSD, OpenBSD and NetBSD.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PlatformIpv4FieldByteOrder {
+    Host,
+    Network,
+}
+
+impl PlatformIpv4FieldByteOrder {
+    /// Discover the required byte ordering for the IPv4 header fields `total_length`, `flags` and `fragment_offset`.
+    ///
+    /// This is achieved by creating a raw socket and attempting to send an `IPv4` packet to localhost with the
+    /// `total_length` set in either host byte order or network byte order. The OS will return an `InvalidInput` error
+    /// if the buffer provided is smaller than the `total_length` indicated, which will be the case when the byte order
+    /// is set incorrectly.
+    ///
+    /// This is a little confusing as `Ipv4Packet::set_total_length` method will _always_ convert from host byte order
+    /// to network byte order (which will be a no-op on big-endian system) and so to test the host byte order case
+    /// we must try both the normal and the swapped byte order.
+    ///
+    /// For example, for a packet of length 4660 bytes (dec):
+    ///
+    /// For a little-endian architecture:
+    ///
+    /// Try        Host (LE)    Wire (BE)   Order (if succeeds)
+    /// normal     34 12        12 34       `PlatformIpv4FieldByteOrder::Network`
+    /// swapped    12 34        34 12       `PlatformIpv4FieldByteOrder::Host`
+    ///
+    /// For a big-endian architecture:
+    ///
+    /// Try        Host (BE)    Wire (BE)   Order (if succeeds)
+    /// normal     12 34        12 34       `Ipv4TotalLengthByteOrder::Host`
+    /// swapped    34 12        34 12       `Ipv4TotalLengthByteOrder::Network`
+    pub fn for_address(addr: IpAddr) -> TraceResult<Self> {
+        for_address(addr)
+    }
+
+    /// Select the byte order from the outcome of sending a test packet of `length` bytes with the `total_length` set
+    /// by `probe`, first in the normal and then, if rejected as invalid, in the swapped byte order.
+    #[cfg_attr(any(target_os = "linux", windows), allow(dead_code))]
+    pub(super) fn from_probes(
+        length: u16,
+        probe: impl Fn(u16) -> TraceResult<()>,
+    ) -> TraceResult<Self> {
+        match probe(length) {
+            Ok(()) => Ok(Self::Network),
+            Err(TracerError::IoError(io)) if io.kind() == std::io::ErrorKind::InvalidInput => {
+                probe(length.swap_bytes()).map(|()| Self::Host)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Adjust the IPv4 `total_length` header.
+    #[must_use]
+    pub fn adjust_length(self, ipv4_total_length: u16) -> u16 {
+        match self {
+            Self::Host => ipv4_total_length.swap_bytes(),
+            Self::Network => ipv4_total_length,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    const LENGTH: u16 = 256;
+
+    fn invalid() -> TraceResult<()> {
+        Err(TracerError::IoError(io::Error::from(
+            io::ErrorKind::InvalidInput,
+        )))
+    }
+
+    #[test]
+    fn test_network_order() {
+        let order = PlatformIpv4FieldByteOrder::from_probes(LENGTH, |length| {
+            if length == LENGTH {
+                Ok(())
+            } else {
+                invalid()
+            }
+        });
+        assert_eq!(PlatformIpv4FieldByteOrder::Network, order.unwrap());
+    }
+
+    #[test]
+    fn test_host_order() {
+        let order = PlatformIpv4FieldByteOrder::from_probes(LENGTH, |length| {
+            if length == LENGTH {
+                invalid()
+            } else {
+                Ok(())
+            }
+        });
+        assert_eq!(PlatformIpv4FieldByteOrder::Host, order.unwrap());
+    }
+
+    #[test]
+    fn test_probe_errors() {
+        assert!(PlatformIpv4FieldByteOrder::from_probes(LENGTH, |_| invalid()).is_err());
+        let denied = PlatformIpv4FieldByteOrder::from_probes(LENGTH, |_| {
+            Err(TracerError::IoError(io::Error::from(
+                io::ErrorKind::PermissionDenied,
+            )))
+        });
+        assert!(
+            matches!(denied, Err(TracerError::IoError(io)) if io.kind() == io::ErrorKind::PermissionDenied)
+        );
+    }
+
+    #[test]
+    fn test_adjust_length() {
+        assert_eq!(
+            0x1234,
+            PlatformIpv4FieldByteOrder::Network.adjust_length(0x1234)
+        );
+        assert_eq!(
+            0x3412,
+            PlatformIpv4FieldByteOrder::Host.adjust_length(0x1234)
+        );
+    }
+}
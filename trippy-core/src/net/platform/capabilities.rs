@@ -0,0 +1,336 @@
+use crate::error::{TraceResult, TracerError};
+use crate::{TracerAddrFamily, TracerProtocol};
+use std::fmt::{Display, Formatter};
+use std::sync::OnceLock;
+
+/// The IP versions for which tracing with a protocol works.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Support {
+    pub ipv4: bool,
+    pub ipv6: bool,
+}
+
+impl Support {
+    const ALL: Self = Self {
+        ipv4: true,
+        ipv6: true,
+    };
+
+    #[cfg_attr(not(any(windows, target_os = "macos")), allow(dead_code))]
+    const IPV4: Self = Self {
+        ipv4: true,
+        ipv6: false,
+    };
+
+    #[cfg_attr(
+        not(any(
+            windows,
+            target_os = "freebsd",
+            target_os = "openbsd",
+            target_os = "netbsd"
+        )),
+        allow(dead_code)
+    )]
+    const NONE: Self = Self {
+        ipv4: false,
+        ipv6: false,
+    };
+
+    /// Does it work for `addr_family`?
+    #[must_use]
+    pub fn supports(self, addr_family: TracerAddrFamily) -> bool {
+        match addr_family {
+            TracerAddrFamily::Ipv4 => self.ipv4,
+            TracerAddrFamily::Ipv6 => self.ipv6,
+        }
+    }
+}
+
+impl Display for Support {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match (self.ipv4, self.ipv6) {
+            (true, true) => write!(f, "v4,v6"),
+            (true, false) => write!(f, "v4"),
+            (false, true) => write!(f, "v6"),
+            (false, false) => write!(f, "none"),
+        }
+    }
+}
+
+/// What works on the platform for which Trippy was built, and on the host on which it runs.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Capabilities {
+    /// The name of the platform.
+    pub platform: &'static str,
+    pub icmp: Support,
+    pub udp: Support,
+    pub tcp: Support,
+    /// Can ICMP be sent and received with raw sockets, given the privileges to create them?
+    pub raw_icmp: bool,
+    /// Can ICMP be sent and received with unprivileged datagram sockets by this user?
+    pub dgram_icmp: bool,
+    /// Can the kernel timestamp the packets received?
+    pub kernel_timestamps: bool,
+    /// Can the IPv4 header of the probes be written by Trippy, i.e. `IP_HDRINCL`?
+    pub header_included: bool,
+    /// Can the source address be looked up from the name of a network interface?
+    pub interface_lookup: bool,
+    /// The IP versions for which the probe sockets can be bound to a network interface.
+    pub bind_device: Support,
+    /// Can the firewall mark of the probes be set?
+    pub fwmark: bool,
+}
+
+impl Capabilities {
+    /// Check that tracing with `protocol` over `addr_family` works, and that the probes can be bound to an `interface`
+    /// and given a `fwmark`, if they are to be.
+    pub fn check(
+        &self,
+        protocol: TracerProtocol,
+        addr_family: TracerAddrFamily,
+        interface: bool,
+        fwmark: bool,
+    ) -> TraceResult<()> {
+        let support = match protocol {
+            TracerProtocol::Icmp => self.icmp,
+            TracerProtocol::Udp => self.udp,
+            TracerProtocol::Tcp => self.tcp,
+        };
+        if !support.supports(addr_family) {
+            return Err(TracerError::Unsupported(
+                tracing_name(protocol, addr_family),
+                self.platform,
+            ));
+        }
+        if interface && !self.interface_lookup {
+            return Err(TracerError::Unsupported(
+                "looking up the address of an interface",
+                self.platform,
+            ));
+        }
+        if interface && !self.bind_device.supports(addr_family) {
+            let capability = match addr_family {
+                TracerAddrFamily::Ipv4 => "binding IPv4 probes to an interface",
+                TracerAddrFamily::Ipv6 => "binding IPv6 probes to an interface",
+            };
+            return Err(TracerError::Unsupported(capability, self.platform));
+        }
+        if fwmark && !self.fwmark {
+            return Err(TracerError::Unsupported(
+                "setting the fwmark of the probes",
+                self.platform,
+            ));
+        }
+        Ok(())
+    }
+
+    /// The capabilities other than the protocols, for display.
+    #[must_use]
+    pub fn features(&self) -> String {
+        let yes_no = |supported| if supported { "yes" } else { "no" };
+        format!(
+            "raw-icmp={} dgram-icmp={} kernel-timestamps={} header-included={} interface={} bind={} fwmark={}",
+            yes_no(self.raw_icmp),
+            yes_no(self.dgram_icmp),
+            yes_no(self.kernel_timestamps),
+            yes_no(self.header_included),
+            yes_no(self.interface_lookup),
+            self.bind_device,
+            yes_no(self.fwmark)
+        )
+    }
+}
+
+impl Display for Capabilities {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "platform={} icmp={} udp={} tcp={}",
+            self.platform, self.icmp, self.udp, self.tcp
+        )
+    }
+}
+
+/// The name of tracing with `protocol` over `addr_family`, as given in the error if it does not work.
+fn tracing_name(protocol: TracerProtocol, addr_family: TracerAddrFamily) -> &'static str {
+    match (protocol, addr_family) {
+        (TracerProtocol::Icmp, TracerAddrFamily::Ipv4) => "tracing with icmp over IPv4",
+        (TracerProtocol::Icmp, TracerAddrFamily::Ipv6) => "tracing with icmp over IPv6",
+        (TracerProtocol::Udp, TracerAddrFamily::Ipv4) => "tracing with udp over IPv4",
+        (TracerProtocol::Udp, TracerAddrFamily::Ipv6) => "tracing with udp over IPv6",
+        (TracerProtocol::Tcp, TracerAddrFamily::Ipv4) => "tracing with tcp over IPv4",
+        (TracerProtocol::Tcp, TracerAddrFamily::Ipv6) => "tracing with tcp over IPv6",
+    }
+}
+
+/// What works on the platform for which Trippy was built, and on the host on which it runs.
+///
+/// Linux, macOS, FreeBSD, OpenBSD and NetBSD support every protocol, with the byte order of the IPv4 header discovered
+/// at startup on all but Linux.  Windows is not yet supported.  Whether unprivileged ICMP sockets may be created is
+/// checked once, when first asked, as it depends on the configuration of the host and the user.
+#[must_use]
+pub fn capabilities() -> Capabilities {
+    static CAPABILITIES: OnceLock<Capabilities> = OnceLock::new();
+    *CAPABILITIES.get_or_init(|| Capabilities {
+        dgram_icmp: dgram_icmp(),
+        ..built()
+    })
+}
+
+/// What works on the platform for which Trippy was built, as far as is known without asking the host.
+fn built() -> Capabilities {
+    #[cfg(unix)]
+    let (icmp, udp, tcp, raw_icmp, header_included, interface_lookup) =
+        (Support::ALL, Support::ALL, Support::ALL, true, true, true);
+    #[cfg(windows)]
+    let (icmp, udp, tcp, raw_icmp, header_included, interface_lookup) = (
+        Support::NONE,
+        Support::NONE,
+        Support::NONE,
+        false,
+        false,
+        false,
+    );
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    let (bind_device, fwmark) = (Support::ALL, true);
+    #[cfg(target_os = "macos")]
+    let (bind_device, fwmark) = (Support::IPV4, false);
+    #[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos")))]
+    let (bind_device, fwmark) = (Support::NONE, false);
+    Capabilities {
+        platform: std::env::consts::OS,
+        icmp,
+        udp,
+        tcp,
+        raw_icmp,
+        dgram_icmp: false,
+        kernel_timestamps: cfg!(any(target_os = "linux", target_os = "android")),
+        header_included,
+        interface_lookup,
+        bind_device,
+        fwmark,
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+fn dgram_icmp() -> bool {
+    super::is_dgram_icmp_available()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos")))]
+fn dgram_icmp() -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOWS: Capabilities = Capabilities {
+        platform: "windows",
+        icmp: Support::NONE,
+        udp: Support::NONE,
+        tcp: Support::NONE,
+        raw_icmp: false,
+        dgram_icmp: false,
+        kernel_timestamps: false,
+        header_included: false,
+        interface_lookup: false,
+        bind_device: Support::NONE,
+        fwmark: false,
+    };
+
+    #[test]
+    fn test_capabilities() {
+        let capabilities = capabilities();
+        assert_eq!(std::env::consts::OS, capabilities.platform);
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "macos",
+            target_os = "freebsd",
+            target_os = "openbsd",
+            target_os = "netbsd"
+        ))]
+        {
+            assert_eq!(Support::ALL, capabilities.icmp);
+            assert_eq!(Support::ALL, capabilities.udp);
+            assert_eq!(Support::ALL, capabilities.tcp);
+            assert!(capabilities.raw_icmp);
+            assert!(capabilities.header_included);
+            assert!(capabilities.interface_lookup);
+        }
+        #[cfg(target_os = "linux")]
+        {
+            assert!(capabilities.kernel_timestamps);
+            assert_eq!(Support::ALL, capabilities.bind_device);
+            assert!(capabilities.fwmark);
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        let linux = Capabilities {
+            platform: "linux",
+            icmp: Support::ALL,
+            udp: Support::ALL,
+            tcp: Support::ALL,
+            interface_lookup: true,
+            bind_device: Support::ALL,
+            fwmark: true,
+            ..WINDOWS
+        };
+        let macos = Capabilities {
+            platform: "macos",
+            bind_device: Support::IPV4,
+            fwmark: false,
+            ..linux
+        };
+        assert!(linux
+            .check(TracerProtocol::Tcp, TracerAddrFamily::Ipv6, true, true)
+            .is_ok());
+        assert!(macos
+            .check(TracerProtocol::Udp, TracerAddrFamily::Ipv4, true, false)
+            .is_ok());
+        let unsupported = |result: TraceResult<()>| match result {
+            Err(TracerError::Unsupported(capability, _)) => capability,
+            _ => panic!("expected unsupported"),
+        };
+        assert_eq!(
+            "binding IPv6 probes to an interface",
+            unsupported(macos.check(TracerProtocol::Udp, TracerAddrFamily::Ipv6, true, false))
+        );
+        assert_eq!(
+            "setting the fwmark of the probes",
+            unsupported(macos.check(TracerProtocol::Icmp, TracerAddrFamily::Ipv4, false, true))
+        );
+        assert_eq!(
+            "tracing with tcp over IPv4 is not supported on windows",
+            WINDOWS
+                .check(TracerProtocol::Tcp, TracerAddrFamily::Ipv4, false, false)
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        let capabilities = Capabilities {
+            platform: "freebsd",
+            icmp: Support::ALL,
+            udp: Support::IPV4,
+            raw_icmp: true,
+            header_included: true,
+            interface_lookup: true,
+            ..WINDOWS
+        };
+        assert_eq!(
+            "platform=freebsd icmp=v4,v6 udp=v4 tcp=none",
+            capabilities.to_string()
+        );
+        assert_eq!(
+            "raw-icmp=yes dgram-icmp=no kernel-timestamps=no header-included=yes interface=yes bind=none fwmark=no",
+            capabilities.features()
+        );
+    }
+}
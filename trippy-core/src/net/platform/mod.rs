@@ -1,6 +1,9 @@
 pub mod byte_order;
 pub use byte_order::PlatformIpv4FieldByteOrder;
 
+mod capabilities;
+pub use capabilities::{capabilities, Capabilities, Support};
+
 #[cfg(unix)]
 mod unix;
 
@@ -1,8 +1,10 @@
 use super::byte_order::PlatformIpv4FieldByteOrder;
-use crate::tracing::error::{TraceResult, TracerError};
-use crate::tracing::net::socket::TracerSocket;
-use crate::tracing::util::Required;
+use crate::error::{TraceResult, TracerError};
+use crate::net::socket::TracerSocket;
+use crate::util::Required;
+use crate::TracerAddrFamily;
 use nix::{
+    poll::{PollFd, PollFlags},
     sys::select::FdSet,
     sys::socket::{AddressFamily, SockaddrLike},
     sys::time::{TimeVal, TimeValLike},
@@ -11,8 +13,8 @@ use socket2::{Domain, Protocol, SockAddr, Type};
 use std::io;
 use std::io::Read;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
-use std::net::{Shutdown, SocketAddr};
-use std::os::unix::io::AsRawFd;
+use std::net::{Shutdown, SocketAddr, SocketAddrV6};
+use std::os::unix::io::{AsRawFd, RawFd};
 use std::time::Duration;
 
 /// The size of the test packet to use for discovering the `total_length` byte order.
@@ -29,21 +31,41 @@ pub fn for_address(_src_addr: IpAddr) -> TraceResult<PlatformIpv4FieldByteOrder>
     Ok(PlatformIpv4FieldByteOrder::Network)
 }
 
-#[cfg(not(target_os = "linux"))]
+/// Discover the required byte ordering for the IPv4 header fields `total_length`, `flags` and `fragment_offset`.
+///
+/// macOS requires host byte order whereas FreeBSD, OpenBSD and NetBSD have each required either, depending on their
+/// version, and so the byte order is discovered by sending a test packet.
+#[cfg(any(
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd"
+))]
 pub fn for_address(addr: IpAddr) -> TraceResult<PlatformIpv4FieldByteOrder> {
-    let addr = match addr {
-        IpAddr::V4(addr) => addr,
-        IpAddr::V6(_) => return Ok(PlatformIpv4FieldByteOrder::Network),
-    };
-    match test_send_local_ip4_packet(addr, TEST_PACKET_LENGTH) {
-        Ok(_) => Ok(PlatformIpv4FieldByteOrder::Network),
-        Err(TracerError::IoError(io)) if io.kind() == std::io::ErrorKind::InvalidInput => {
-            match test_send_local_ip4_packet(addr, TEST_PACKET_LENGTH.swap_bytes()) {
-                Ok(_) => Ok(PlatformIpv4FieldByteOrder::Host),
-                Err(err) => Err(err),
-            }
-        }
-        Err(err) => Err(err),
+    discover_byte_order(addr)
+}
+
+/// Discover the required byte ordering for the IPv4 header fields `total_length`, `flags` and `fragment_offset`.
+///
+/// The byte order required by other unix platforms is not known and so is discovered by sending a test packet.
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd"
+)))]
+pub fn for_address(addr: IpAddr) -> TraceResult<PlatformIpv4FieldByteOrder> {
+    discover_byte_order(addr)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn discover_byte_order(addr: IpAddr) -> TraceResult<PlatformIpv4FieldByteOrder> {
+    match addr {
+        IpAddr::V4(addr) => PlatformIpv4FieldByteOrder::from_probes(TEST_PACKET_LENGTH, |length| {
+            test_send_local_ip4_packet(addr, length)
+        }),
+        IpAddr::V6(_) => Ok(PlatformIpv4FieldByteOrder::Network),
     }
 }
 
@@ -54,10 +76,10 @@ pub fn for_address(addr: IpAddr) -> TraceResult<PlatformIpv4FieldByteOrder> {
 #[cfg(not(target_os = "linux"))]
 fn test_send_local_ip4_packet(src_addr: Ipv4Addr, total_length: u16) -> TraceResult<()> {
     let mut buf = [0_u8; TEST_PACKET_LENGTH as usize];
-    let mut ipv4 = crate::tracing::packet::ipv4::Ipv4Packet::new(&mut buf).req()?;
+    let mut ipv4 = crate::packet::ipv4::Ipv4Packet::new(&mut buf).req()?;
     ipv4.set_version(4);
     ipv4.set_header_length(5);
-    ipv4.set_protocol(crate::tracing::packet::IpProtocol::Icmp);
+    ipv4.set_protocol(crate::packet::IpProtocol::Icmp);
     ipv4.set_ttl(255);
     ipv4.set_source(src_addr);
     ipv4.set_destination(Ipv4Addr::LOCALHOST);
@@ -101,11 +123,23 @@ pub fn lookup_interface_addr_ipv6(name: &str) -> TraceResult<IpAddr> {
         .ok_or_else(|| TracerError::UnknownInterface(name.to_string()))
 }
 
+/// Lookup the index of a named interface, the scope of the link-local addresses reached through it.
+pub fn lookup_interface_index(name: &str) -> TraceResult<u32> {
+    nix::net::if_::if_nametoindex(name).map_err(|_| TracerError::UnknownInterface(name.to_string()))
+}
+
 #[allow(clippy::unnecessary_wraps)]
 pub fn startup() -> TraceResult<()> {
     Ok(())
 }
 
+/// Can an unprivileged ICMP socket, of type `SOCK_DGRAM`, be created?
+///
+/// Such sockets exist on Linux, where the group of the user must be within `net.ipv4.ping_group_range`, and on macOS.
+pub fn is_dgram_icmp_available() -> bool {
+    socket2::Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::ICMPV4)).is_ok()
+}
+
 pub fn is_not_in_progress_error(code: i32) -> bool {
     nix::Error::from_i32(code) != nix::Error::EINPROGRESS
 }
@@ -114,20 +148,56 @@ pub fn is_conn_refused_error(code: i32) -> bool {
     nix::Error::from_i32(code) == nix::Error::ECONNREFUSED
 }
 
+/// Was a packet refused as it is larger than the MTU of the interface, and the platform will not fragment it?
+pub fn is_msg_too_big_error(code: i32) -> bool {
+    nix::Error::from_i32(code) == nix::Error::EMSGSIZE
+}
+
 #[must_use]
 pub fn is_host_unreachable_error(_code: i32) -> bool {
     false
 }
 
-/// Discover the local `IpAddr` that will be used to communicate with the given target `IpAddr`.
+/// Was an operation refused as the tracer lacks the privileges for it?
+pub fn is_permission_denied_error(code: i32) -> bool {
+    matches!(
+        nix::Error::from_i32(code),
+        nix::Error::EACCES | nix::Error::EPERM
+    )
+}
+
+pub fn is_addr_in_use_error(code: i32) -> bool {
+    nix::Error::from_i32(code) == nix::Error::EADDRINUSE
+}
+
+/// Was a packet refused as there is no route to the network, or host, it is sent to?
+pub fn is_net_unreachable_error(code: i32) -> bool {
+    matches!(
+        nix::Error::from_i32(code),
+        nix::Error::ENETUNREACH | nix::Error::EHOSTUNREACH
+    )
+}
+
+pub fn is_no_buffer_space_error(code: i32) -> bool {
+    nix::Error::from_i32(code) == nix::Error::ENOBUFS
+}
+
+/// Discover the local `IpAddr` that will be used to communicate with the given target `IpAddr`, within the scope
+/// `scope_id` should it be a link-local IPv6 address.
 ///
 /// Note that no packets are transmitted by this method.
-pub fn discover_local_addr(target_addr: IpAddr, port: u16) -> TraceResult<IpAddr> {
-    let socket = match target_addr {
-        IpAddr::V4(_) => Socket::new_udp_dgram_socket_ipv4(),
-        IpAddr::V6(_) => Socket::new_udp_dgram_socket_ipv6(),
-    }?;
-    socket.connect(SocketAddr::new(target_addr, port))?;
+pub fn discover_local_addr(target_addr: IpAddr, port: u16, scope_id: u32) -> TraceResult<IpAddr> {
+    let (socket, remote_addr) = match target_addr {
+        IpAddr::V4(_) => (
+            Socket::new_udp_dgram_socket_ipv4()?,
+            SocketAddr::new(target_addr, port),
+        ),
+        IpAddr::V6(addr) => (
+            Socket::new_udp_dgram_socket_ipv6()?,
+            SocketAddr::V6(SocketAddrV6::new(addr, port, 0, scope_id)),
+        ),
+    };
+    socket.connect(remote_addr)?;
     Ok(socket.local_addr()?.req()?.ip())
 }
 
@@ -165,6 +235,12 @@ impl Socket {
     }
 }
 
+impl AsRawFd for Socket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
 impl TracerSocket for Socket {
     fn new_icmp_send_socket_ipv4() -> io::Result<Self> {
         let socket = Self::new_raw_ipv4(Protocol::from(nix::libc::IPPROTO_RAW))?;
@@ -220,12 +296,53 @@ impl TracerSocket for Socket {
     fn bind(&mut self, address: SocketAddr) -> io::Result<()> {
         self.inner.bind(&SockAddr::from(address))
     }
+    /// Bind to the interface with `SO_BINDTODEVICE`, which may require the `CAP_NET_RAW` capability.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn bind_device(&self, name: &str, _addr_family: TracerAddrFamily) -> io::Result<()> {
+        self.inner.bind_device(Some(name.as_bytes()))
+    }
+    /// Bind to the interface with `IP_BOUND_IF`.
+    ///
+    /// The `IPV6_BOUND_IF` option required for IPv6 is not available and so binding an IPv6 socket is not supported.
+    #[cfg(target_os = "macos")]
+    fn bind_device(&self, name: &str, addr_family: TracerAddrFamily) -> io::Result<()> {
+        match addr_family {
+            TracerAddrFamily::Ipv4 => {
+                let index = nix::net::if_::if_nametoindex(name)?;
+                self.inner
+                    .bind_device_by_index(std::num::NonZeroU32::new(index))
+            }
+            TracerAddrFamily::Ipv6 => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "binding an IPv6 socket to an interface is not supported on this platform",
+            )),
+        }
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos")))]
+    fn bind_device(&self, _name: &str, _addr_family: TracerAddrFamily) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "binding a socket to an interface is not supported on this platform",
+        ))
+    }
     fn set_tos(&self, tos: u32) -> io::Result<()> {
         self.inner.set_tos(tos)
     }
     fn set_ttl(&self, ttl: u32) -> io::Result<()> {
         self.inner.set_ttl(ttl)
     }
+    /// Set the firewall mark with `SO_MARK`, which requires the `CAP_NET_ADMIN` capability.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn set_mark(&self, mark: u32) -> io::Result<()> {
+        self.inner.set_mark(mark)
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    fn set_mark(&self, _mark: u32) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "setting the firewall mark is not supported on this platform",
+        ))
+    }
     fn set_reuse_port(&self, reuse: bool) -> io::Result<()> {
         self.inner.set_reuse_port(reuse)
     }
@@ -250,7 +367,7 @@ impl TracerSocket for Socket {
             Some(&mut read),
             None,
             None,
-            Some(&mut TimeVal::milliseconds(timeout.as_millis() as i64)),
+            Some(&mut TimeVal::microseconds(timeout.as_micros() as i64)),
         )?;
         Ok(readable == 1)
     }
@@ -266,6 +383,21 @@ impl TracerSocket for Socket {
         )?;
         Ok(writable == 1)
     }
+    fn wait_ready<'a>(
+        recv: &'a Self,
+        connecting: impl Iterator<Item = &'a Self>,
+        timeout: Duration,
+    ) -> io::Result<bool> {
+        let mut fds: Vec<_> =
+            std::iter::once(PollFd::new(recv.inner.as_raw_fd(), PollFlags::POLLIN))
+                .chain(
+                    connecting
+                        .map(|socket| PollFd::new(socket.inner.as_raw_fd(), PollFlags::POLLOUT)),
+                )
+                .collect();
+        let ready = nix::poll::poll(&mut fds, poll_timeout(timeout))?;
+        Ok(ready > 0)
+    }
     fn recv_from(&mut self, buf: &mut [u8]) -> io::Result<(usize, Option<SocketAddr>)> {
         self.inner.recv_from_into_buf(buf)
     }
@@ -316,3 +448,67 @@ impl RecvFrom for socket2::Socket {
             .map(|(size, addr)| (size, addr.as_socket()))
     }
 }
+
+/// The `timeout` of `poll` in milliseconds, rounded up such that a wait of under a millisecond does not spin.
+fn poll_timeout(timeout: Duration) -> i32 {
+    i32::try_from(timeout.as_micros().div_ceil(1000)).unwrap_or(i32::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The name of the loopback interface.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const LOOPBACK: &str = "lo";
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    const LOOPBACK: &str = "lo0";
+
+    #[test]
+    fn test_lookup_interface_addr_ipv4() {
+        assert_eq!(
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+            lookup_interface_addr_ipv4(LOOPBACK).unwrap()
+        );
+    }
+
+    /// Set the firewall mark of a send socket and read it back with `getsockopt`.
+    #[test]
+    #[cfg(all(feature = "privileged-tests", target_os = "linux"))]
+    fn test_set_mark() {
+        let socket = Socket::new_udp_send_socket_ipv4().unwrap();
+        socket.set_mark(42).unwrap();
+        assert_eq!(42, socket.inner.mark().unwrap());
+    }
+
+    #[test]
+    fn test_classify_os_errors() {
+        assert!(is_permission_denied_error(nix::Error::EACCES as i32));
+        assert!(is_permission_denied_error(nix::Error::EPERM as i32));
+        assert!(is_addr_in_use_error(nix::Error::EADDRINUSE as i32));
+        assert!(is_net_unreachable_error(nix::Error::ENETUNREACH as i32));
+        assert!(is_net_unreachable_error(nix::Error::EHOSTUNREACH as i32));
+        assert!(is_no_buffer_space_error(nix::Error::ENOBUFS as i32));
+        assert!(!is_permission_denied_error(nix::Error::EADDRINUSE as i32));
+        assert!(!is_addr_in_use_error(nix::Error::EADDRNOTAVAIL as i32));
+        assert!(!is_net_unreachable_error(nix::Error::ECONNREFUSED as i32));
+        assert!(!is_no_buffer_space_error(nix::Error::ENOMEM as i32));
+    }
+
+    #[test]
+    fn test_lookup_unknown_interface() {
+        assert!(matches!(
+            lookup_interface_addr_ipv4("no-such-interface"),
+            Err(TracerError::UnknownInterface(name)) if name == "no-such-interface"
+        ));
+        assert!(matches!(
+            lookup_interface_addr_ipv6("no-such-interface"),
+            Err(TracerError::UnknownInterface(_))
+        ));
+        assert!(matches!(
+            lookup_interface_index("no-such-interface"),
+            Err(TracerError::UnknownInterface(_))
+        ));
+    }
+}
@@ -0,0 +1,270 @@
+use super::byte_order::PlatformIpv4FieldByteOrder;
+use crate::error::{TraceResult, TracerError};
+use crate::net::socket::TracerSocket;
+use crate::TracerAddrFamily;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::net::{Shutdown, SocketAddr};
+use std::time::Duration;
+
+/// The name of the platform given in the error of an operation which is not supported.
+const PLATFORM: &str = "windows";
+
+/// The Winsock error codes checked for, from `winerror.h`.
+const WSAEACCES: i32 = 10013;
+const WSAEINPROGRESS: i32 = 10036;
+const WSAEMSGSIZE: i32 = 10040;
+const WSAEADDRINUSE: i32 = 10048;
+const WSAENETUNREACH: i32 = 10051;
+const WSAENOBUFS: i32 = 10055;
+const WSAECONNREFUSED: i32 = 10061;
+const WSAEHOSTUNREACH: i32 = 10065;
+
+/// The error of a socket operation which is not yet supported on Windows.
+fn unsupported(operation: &'static str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        TracerError::Unsupported(operation, PLATFORM),
+    )
+}
+
+/// Windows accepts the IPv4 header fields in network byte order.
+#[allow(clippy::unnecessary_wraps)]
+pub fn for_address(_src_addr: IpAddr) -> TraceResult<PlatformIpv4FieldByteOrder> {
+    Ok(PlatformIpv4FieldByteOrder::Network)
+}
+
+#[allow(clippy::unnecessary_wraps)]
+pub fn startup() -> TraceResult<()> {
+    Ok(())
+}
+
+pub fn lookup_interface_addr_ipv4(_name: &str) -> TraceResult<IpAddr> {
+    Err(TracerError::Unsupported(
+        "looking up the address of an interface",
+        PLATFORM,
+    ))
+}
+
+pub fn lookup_interface_addr_ipv6(_name: &str) -> TraceResult<IpAddr> {
+    Err(TracerError::Unsupported(
+        "looking up the address of an interface",
+        PLATFORM,
+    ))
+}
+
+pub fn lookup_interface_index(_name: &str) -> TraceResult<u32> {
+    Err(TracerError::Unsupported(
+        "looking up the index of an interface",
+        PLATFORM,
+    ))
+}
+
+pub fn is_not_in_progress_error(code: i32) -> bool {
+    code != WSAEINPROGRESS
+}
+
+pub fn is_conn_refused_error(code: i32) -> bool {
+    code == WSAECONNREFUSED
+}
+
+/// Was a packet refused as it is larger than the MTU of the interface, and the platform will not fragment it?
+pub fn is_msg_too_big_error(code: i32) -> bool {
+    code == WSAEMSGSIZE
+}
+
+#[must_use]
+pub fn is_host_unreachable_error(code: i32) -> bool {
+    code == WSAEHOSTUNREACH
+}
+
+/// Was an operation refused as the tracer lacks the privileges for it?
+pub fn is_permission_denied_error(code: i32) -> bool {
+    code == WSAEACCES
+}
+
+pub fn is_addr_in_use_error(code: i32) -> bool {
+    code == WSAEADDRINUSE
+}
+
+/// Was a packet refused as there is no route to the network, or host, it is sent to?
+pub fn is_net_unreachable_error(code: i32) -> bool {
+    code == WSAENETUNREACH || code == WSAEHOSTUNREACH
+}
+
+pub fn is_no_buffer_space_error(code: i32) -> bool {
+    code == WSAENOBUFS
+}
+
+pub fn discover_local_addr(
+    _target_addr: IpAddr,
+    _port: u16,
+    _scope_id: u32,
+) -> TraceResult<IpAddr> {
+    Err(TracerError::Unsupported(
+        "discovering the source address",
+        PLATFORM,
+    ))
+}
+
+/// A network socket.
+///
+/// Raw sockets are not yet supported on Windows and so every operation fails with an unsupported error, which
+/// `capabilities` reports such that tracing is refused before any socket is created.
+#[derive(Debug)]
+pub struct Socket {}
+
+#[allow(clippy::unused_self)]
+impl TracerSocket for Socket {
+    fn new_icmp_send_socket_ipv4() -> io::Result<Self> {
+        Err(unsupported("raw ICMP sockets"))
+    }
+
+    fn new_icmp_send_socket_ipv6() -> io::Result<Self> {
+        Err(unsupported("raw ICMPv6 sockets"))
+    }
+
+    fn new_udp_send_socket_ipv4() -> io::Result<Self> {
+        Err(unsupported("raw UDP sockets"))
+    }
+
+    fn new_udp_send_socket_ipv6() -> io::Result<Self> {
+        Err(unsupported("raw UDP sockets"))
+    }
+
+    fn new_recv_socket_ipv4(_addr: Ipv4Addr) -> io::Result<Self> {
+        Err(unsupported("raw receive sockets"))
+    }
+
+    fn new_recv_socket_ipv6(_addr: Ipv6Addr) -> io::Result<Self> {
+        Err(unsupported("raw receive sockets"))
+    }
+
+    fn new_stream_socket_ipv4() -> io::Result<Self> {
+        Err(unsupported("TCP probe sockets"))
+    }
+
+    fn new_stream_socket_ipv6() -> io::Result<Self> {
+        Err(unsupported("TCP probe sockets"))
+    }
+
+    fn new_udp_dgram_socket_ipv4() -> io::Result<Self> {
+        Err(unsupported("UDP datagram sockets"))
+    }
+
+    fn new_udp_dgram_socket_ipv6() -> io::Result<Self> {
+        Err(unsupported("UDP datagram sockets"))
+    }
+
+    fn bind(&mut self, _address: SocketAddr) -> io::Result<()> {
+        Err(unsupported("binding a socket"))
+    }
+
+    fn bind_device(&self, _name: &str, _addr_family: TracerAddrFamily) -> io::Result<()> {
+        Err(unsupported("binding a socket to an interface"))
+    }
+
+    fn set_tos(&self, _tos: u32) -> io::Result<()> {
+        Err(unsupported("setting the type of service"))
+    }
+
+    fn set_ttl(&self, _ttl: u32) -> io::Result<()> {
+        Err(unsupported("setting the time-to-live"))
+    }
+
+    fn set_mark(&self, _mark: u32) -> io::Result<()> {
+        Err(unsupported("setting the fwmark"))
+    }
+
+    #[allow(dead_code)]
+    fn set_reuse_port(&self, _reuse: bool) -> io::Result<()> {
+        Err(unsupported("reusing a port"))
+    }
+
+    #[allow(dead_code)]
+    fn set_header_included(&self, _included: bool) -> io::Result<()> {
+        Err(unsupported("including the IP header"))
+    }
+
+    fn set_unicast_hops_v6(&self, _hops: u8) -> io::Result<()> {
+        Err(unsupported("setting the unicast hops"))
+    }
+
+    fn connect(&self, _address: SocketAddr) -> io::Result<()> {
+        Err(unsupported("connecting a socket"))
+    }
+
+    fn send_to(&self, _buf: &[u8], _addr: SocketAddr) -> io::Result<()> {
+        Err(unsupported("sending on a socket"))
+    }
+
+    fn is_readable(&self, _timeout: Duration) -> io::Result<bool> {
+        Err(unsupported("polling a socket"))
+    }
+
+    fn is_writable(&self) -> io::Result<bool> {
+        Err(unsupported("polling a socket"))
+    }
+
+    fn wait_ready<'a>(
+        _recv: &'a Self,
+        _connecting: impl Iterator<Item = &'a Self>,
+        _timeout: Duration,
+    ) -> io::Result<bool> {
+        Err(unsupported("polling a socket"))
+    }
+
+    fn recv_from(&mut self, _buf: &mut [u8]) -> io::Result<(usize, Option<SocketAddr>)> {
+        Err(unsupported("receiving on a socket"))
+    }
+
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        Err(unsupported("reading a socket"))
+    }
+
+    fn shutdown(&self, _how: Shutdown) -> io::Result<()> {
+        Err(unsupported("shutting down a socket"))
+    }
+
+    fn peer_addr(&self) -> io::Result<Option<SocketAddr>> {
+        Err(unsupported("the peer address of a socket"))
+    }
+
+    fn take_error(&self) -> io::Result<Option<io::Error>> {
+        Err(unsupported("the error of a socket"))
+    }
+
+    #[allow(clippy::unused_self)]
+    fn icmp_error_info(&self) -> io::Result<IpAddr> {
+        Err(unsupported("the ICMP error of a socket"))
+    }
+
+    #[allow(clippy::unused_self, clippy::unnecessary_wraps)]
+    fn close(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl io::Read for Socket {
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        Err(unsupported("reading a socket"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_os_errors() {
+        assert!(is_permission_denied_error(WSAEACCES));
+        assert!(is_addr_in_use_error(WSAEADDRINUSE));
+        assert!(is_net_unreachable_error(WSAENETUNREACH));
+        assert!(is_net_unreachable_error(WSAEHOSTUNREACH));
+        assert!(is_no_buffer_space_error(WSAENOBUFS));
+        assert!(!is_permission_denied_error(WSAEADDRINUSE));
+        assert!(!is_addr_in_use_error(WSAEACCES));
+        assert!(!is_net_unreachable_error(WSAECONNREFUSED));
+        assert!(!is_no_buffer_space_error(WSAEMSGSIZE));
+    }
+}
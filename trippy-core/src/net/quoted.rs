@@ -0,0 +1,113 @@
+use crate::error::ParseError;
+use crate::packet::tcp::TcpPacket;
+use crate::packet::udp::UdpPacket;
+
+/// The fewest bytes of a quoted `UDP` or `TCP` header from which the ports can be read.
+const MIN_PORTS_LEN: usize = 4;
+
+/// The transport header of the datagram quoted in an ICMP error.
+///
+/// RFC 792 only requires the first 8 bytes of the original datagram to be quoted, though some devices quote fewer and
+/// some the full datagram.  A quote which is too short to read the fields required from is malformed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct QuotedHeader {
+    pub src_port: u16,
+    pub dest_port: u16,
+    /// The checksum of a `UDP` header, if quoted.
+    pub checksum: Option<u16>,
+}
+
+/// Read the quoted `UDP` header from `datagram`.
+///
+/// If the datagram is quoted in `full` then the length of the `UDP` header must be that of the datagram quoted, such
+/// that a quote of a datagram which is not one of ours, or which was mangled, is not matched.
+pub fn udp_header(datagram: &[u8], full: bool) -> Result<QuotedHeader, ParseError> {
+    let buf = padded::<{ UdpPacket::minimum_packet_size() }>(datagram, "quoted UDP header")?;
+    let udp = UdpPacket::new_view(&buf).ok_or(ParseError::Truncated("quoted UDP header"))?;
+    let quoted_header = datagram.len() >= UdpPacket::minimum_packet_size();
+    if quoted_header {
+        let length = usize::from(udp.get_length());
+        if length < UdpPacket::minimum_packet_size() || (full && length != datagram.len()) {
+            return Err(ParseError::Invalid("quoted UDP length"));
+        }
+    }
+    Ok(QuotedHeader {
+        src_port: udp.get_source(),
+        dest_port: udp.get_destination(),
+        checksum: quoted_header.then(|| udp.get_checksum()),
+    })
+}
+
+/// Read the quoted `TCP` header from `datagram`.
+///
+/// The `TCP` header is a minimum of 20 bytes and so is seldom quoted in full, only the ports are read.
+pub fn tcp_header(datagram: &[u8]) -> Result<QuotedHeader, ParseError> {
+    let buf = padded::<{ TcpPacket::minimum_packet_size() }>(datagram, "quoted TCP header")?;
+    let tcp = TcpPacket::new_view(&buf).ok_or(ParseError::Truncated("quoted TCP header"))?;
+    Ok(QuotedHeader {
+        src_port: tcp.get_source(),
+        dest_port: tcp.get_destination(),
+        checksum: None,
+    })
+}
+
+/// The first `N` bytes of the `header` quoted in `datagram`, zero padded if fewer were quoted, which must include the
+/// ports.
+fn padded<const N: usize>(datagram: &[u8], header: &'static str) -> Result<[u8; N], ParseError> {
+    if datagram.len() < MIN_PORTS_LEN {
+        return Err(ParseError::Truncated(header));
+    }
+    let mut buf = [0_u8; N];
+    let len = datagram.len().min(N);
+    buf[..len].copy_from_slice(&datagram[..len]);
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `UDP` header from port 33434 to port 33435 of length 36 with checksum 0xbeef.
+    const UDP: [u8; 8] = [0x82, 0x9a, 0x82, 0x9b, 0x00, 0x24, 0xbe, 0xef];
+
+    #[test]
+    fn test_udp_header() {
+        let header = udp_header(&UDP, false).unwrap();
+        assert_eq!((33434, 33435), (header.src_port, header.dest_port));
+        assert_eq!(Some(0xbeef), header.checksum);
+    }
+
+    #[test]
+    fn test_udp_header_truncated() {
+        let header = udp_header(&UDP[..4], false).unwrap();
+        assert_eq!((33434, 33435), (header.src_port, header.dest_port));
+        assert_eq!(None, header.checksum);
+        assert_eq!(
+            Err(ParseError::Truncated("quoted UDP header")),
+            udp_header(&UDP[..3], false)
+        );
+        assert!(udp_header(&[], false).is_err());
+    }
+
+    #[test]
+    fn test_udp_header_full() {
+        let mut datagram = [0xaa_u8; 36];
+        datagram[..8].copy_from_slice(&UDP);
+        assert!(udp_header(&datagram, true).is_ok());
+        assert!(udp_header(&datagram[..20], true).is_err());
+        assert!(udp_header(&datagram[..20], false).is_ok());
+        let mut mangled = UDP;
+        mangled[5] = 0x04;
+        assert_eq!(
+            Err(ParseError::Invalid("quoted UDP length")),
+            udp_header(&mangled, false)
+        );
+    }
+
+    #[test]
+    fn test_tcp_header() {
+        let header = tcp_header(&[0x82, 0x9a, 0x00, 0x50, 0x12, 0x34, 0x56, 0x78]).unwrap();
+        assert_eq!((33434, 80), (header.src_port, header.dest_port));
+        assert!(tcp_header(&[0x82, 0x9a, 0x00]).is_err());
+    }
+}
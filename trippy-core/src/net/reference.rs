@@ -0,0 +1,40 @@
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// The internet checksum of `data`, as defined by RFC 1071 and written independently of `packet::checksum` such that
+/// the checksums of the packets built may be checked against it.
+///
+/// The checksum of data which includes a correct checksum is zero.
+pub fn internet_checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|word| u32::from(word[0]) << 8 | u32::from(word.get(1).copied().unwrap_or(0)))
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// The IPv4 pseudo-header of a datagram of `protocol` and `length` bytes, over which with the datagram the checksum
+/// is calculated.
+pub fn ipv4_pseudo_header(src: Ipv4Addr, dest: Ipv4Addr, protocol: u8, length: usize) -> Vec<u8> {
+    let mut header = Vec::from(src.octets());
+    header.extend(dest.octets());
+    header.extend([0, protocol]);
+    header.extend((length as u16).to_be_bytes());
+    header
+}
+
+/// The IPv6 pseudo-header of a datagram of `next_header` and `length` bytes, as defined by RFC 8200 section 8.1.
+pub fn ipv6_pseudo_header(
+    src: Ipv6Addr,
+    dest: Ipv6Addr,
+    next_header: u8,
+    length: usize,
+) -> Vec<u8> {
+    let mut header = Vec::from(src.octets());
+    header.extend(dest.octets());
+    header.extend((length as u32).to_be_bytes());
+    header.extend([0, 0, 0, next_header]);
+    header
+}
@@ -0,0 +1,111 @@
+use crate::error::{TraceResult, TracerError};
+use crate::net::platform;
+use crate::TracerAddrFamily;
+use std::io::{Error, ErrorKind, Result};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr};
+use std::time::Duration;
+
+pub trait TracerSocket
+where
+    Self: Sized,
+{
+    /// Create an IPv4 socket for sending ICMP probes.
+    fn new_icmp_send_socket_ipv4() -> Result<Self>;
+    /// Create an IPv6 socket for sending ICMP probes.
+    fn new_icmp_send_socket_ipv6() -> Result<Self>;
+    /// Create an IPv4 socket for sending UDP probes.
+    fn new_udp_send_socket_ipv4() -> Result<Self>;
+    /// Create an IPv6 socket for sending UDP probes.
+    fn new_udp_send_socket_ipv6() -> Result<Self>;
+    /// Create an IPv4 socket for receiving UDP probe responses.
+    fn new_recv_socket_ipv4(addr: Ipv4Addr) -> Result<Self>;
+    /// Create an IPv6 socket for receiving UDP probe responses.
+    fn new_recv_socket_ipv6(addr: Ipv6Addr) -> Result<Self>;
+    /// Create a IPv4/TCP socket for sending TCP probes.
+    fn new_stream_socket_ipv4() -> Result<Self>;
+    /// Create a IPv6/TCP socket for sending TCP probes.
+    fn new_stream_socket_ipv6() -> Result<Self>;
+    /// Create (non-raw) IPv4/UDP socket for local address validation.
+    fn new_udp_dgram_socket_ipv4() -> Result<Self>;
+    /// Create (non-raw) IPv6/UDP socket for local address validation.
+    fn new_udp_dgram_socket_ipv6() -> Result<Self>;
+    fn bind(&mut self, address: SocketAddr) -> Result<()>;
+    /// Bind the socket, of `addr_family`, to the network interface `name` such that packets egress no other interface.
+    fn bind_device(&self, name: &str, addr_family: TracerAddrFamily) -> Result<()>;
+    fn set_tos(&self, tos: u32) -> Result<()>;
+    fn set_ttl(&self, ttl: u32) -> Result<()>;
+    /// Set the firewall mark of the packets sent from the socket, used to select a routing policy.
+    fn set_mark(&self, mark: u32) -> Result<()>;
+    fn set_reuse_port(&self, reuse: bool) -> Result<()>;
+    fn set_header_included(&self, included: bool) -> Result<()>;
+    fn set_unicast_hops_v6(&self, hops: u8) -> Result<()>;
+    fn connect(&self, address: SocketAddr) -> Result<()>;
+    fn send_to(&self, buf: &[u8], addr: SocketAddr) -> Result<()>;
+    /// Returns true if the socket becomes readable before the timeout, false otherwise.
+    fn is_readable(&self, timeout: Duration) -> Result<bool>;
+    /// Returns true if the socket is currently writeable, false otherwise.
+    fn is_writable(&self) -> Result<bool>;
+    /// Returns true if `recv` becomes readable, or any of the `connecting` sockets becomes writable, before the timeout,
+    /// false otherwise.
+    fn wait_ready<'a>(
+        recv: &'a Self,
+        connecting: impl Iterator<Item = &'a Self>,
+        timeout: Duration,
+    ) -> Result<bool>;
+    fn recv_from(&mut self, buf: &mut [u8]) -> Result<(usize, Option<SocketAddr>)>;
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+    fn shutdown(&self, how: Shutdown) -> Result<()>;
+    fn peer_addr(&self) -> Result<Option<SocketAddr>>;
+    fn take_error(&self) -> Result<Option<Error>>;
+    fn icmp_error_info(&self) -> Result<IpAddr>;
+    fn close(&self) -> Result<()>;
+}
+
+/// Bind `socket` to the network `interface`, if given.
+pub fn bind_interface<S: TracerSocket>(
+    socket: &S,
+    interface: Option<&str>,
+    addr_family: TracerAddrFamily,
+) -> TraceResult<()> {
+    if let Some(name) = interface {
+        socket
+            .bind_device(name, addr_family)
+            .map_err(|err| TracerError::BindInterface(name.to_string(), err))?;
+    }
+    Ok(())
+}
+
+/// Send the `packet` of a probe, of IP packet size `packet_size`, on `socket` to `addr`.
+///
+/// A packet larger than the MTU of the interface is fragmented by some platforms and refused by others, in which case
+/// this fails with `PacketTooBig`.  Any other failure is classified as by `TracerError::from_socket_error`, or else is
+/// a `ProbeSendFailed`.
+pub fn send_probe<S: TracerSocket>(
+    socket: &S,
+    packet: &[u8],
+    packet_size: usize,
+    addr: SocketAddr,
+) -> TraceResult<()> {
+    socket
+        .send_to(packet, addr)
+        .map_err(|err| match err.raw_os_error() {
+            Some(code) if platform::is_msg_too_big_error(code) => {
+                TracerError::PacketTooBig(packet_size)
+            }
+            _ => match TracerError::from_socket_error("sendto", addr, err) {
+                TracerError::IoError(err) => TracerError::ProbeSendFailed(addr, err),
+                err => err,
+            },
+        })
+}
+
+/// Set the firewall mark `fwmark`, if given, on `socket`.
+pub fn set_fwmark<S: TracerSocket>(socket: &S, fwmark: Option<u32>) -> TraceResult<()> {
+    if let Some(mark) = fwmark {
+        socket.set_mark(mark).map_err(|err| match err.kind() {
+            ErrorKind::PermissionDenied => TracerError::FwmarkPermission(mark),
+            _ => TracerError::SetFwmark(mark, err),
+        })?;
+    }
+    Ok(())
+}
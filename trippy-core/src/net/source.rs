@@ -1,10 +1,10 @@
-use crate::tracing::error::TraceResult;
-use crate::tracing::error::TracerError::InvalidSourceAddr;
-use crate::tracing::net::platform;
-use crate::tracing::net::platform::Socket;
-use crate::tracing::net::socket::TracerSocket as _;
-use crate::tracing::types::Port;
-use crate::tracing::PortDirection;
+use crate::error::TraceResult;
+use crate::error::TracerError::InvalidSourceAddr;
+use crate::net::platform;
+use crate::net::platform::Socket;
+use crate::net::socket::TracerSocket as _;
+use crate::types::Port;
+use crate::{PortDirection, TracerAddrFamily};
 use std::net::{IpAddr, SocketAddr};
 
 /// The port used for local address discovery if not dest port is available.
@@ -15,15 +15,20 @@ pub struct SourceAddr;
 
 impl SourceAddr {
     /// Discover the source `IpAddr`.
+    ///
+    /// The source of a target within a scope, a link-local IPv6 target reached through the interface `scope_id`, is
+    /// that which the platform would use to reach it, the link-local address of that interface, whether or not an
+    /// interface is given.
     pub fn discover(
         target_addr: IpAddr,
         port_direction: PortDirection,
         interface: Option<&str>,
+        scope_id: u32,
     ) -> TraceResult<IpAddr> {
         let port = port_direction.dest().unwrap_or(DISCOVERY_PORT).0;
         match interface.as_ref() {
-            Some(interface) => lookup_interface_addr(target_addr, interface),
-            None => platform::discover_local_addr(target_addr, port),
+            Some(interface) if scope_id == 0 => lookup_interface_addr(target_addr, interface),
+            _ => platform::discover_local_addr(target_addr, port, scope_id),
         }
     }
 
@@ -56,3 +61,16 @@ fn lookup_interface_addr(addr: IpAddr, name: &str) -> TraceResult<IpAddr> {
         IpAddr::V6(_) => platform::lookup_interface_addr_ipv6(name),
     }
 }
+
+/// Lookup the address of `addr_family` of a named interface, which is the source of probes sent through it.
+pub fn interface_addr(name: &str, addr_family: TracerAddrFamily) -> TraceResult<IpAddr> {
+    match addr_family {
+        TracerAddrFamily::Ipv4 => platform::lookup_interface_addr_ipv4(name),
+        TracerAddrFamily::Ipv6 => platform::lookup_interface_addr_ipv6(name),
+    }
+}
+
+/// Lookup the index of a named interface, the scope of a link-local IPv6 target reached through it.
+pub fn interface_index(name: &str) -> TraceResult<u32> {
+    platform::lookup_interface_index(name)
+}
@@ -0,0 +1,166 @@
+use crate::error::TraceResult;
+use crate::util::Required;
+use crate::{Probe, TracerAddrFamily};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The size of the `ICMP` echo request and `UDP` headers which precede the payload of a probe.
+const TRANSPORT_HEADER_LEN: usize = 8;
+
+/// A stamp written at the start of the payload of a probe, identifying it such that it may be matched to the frames of
+/// a capture taken anywhere on its path.
+///
+/// The stamp is 18 bytes long and its fields are in network byte order, the remainder of the payload being filled
+/// with the payload pattern as usual:
+///
+/// | offset | length | field                                                      |
+/// |--------|--------|------------------------------------------------------------|
+/// | 0      | 4      | the magic bytes `TRPY`                                     |
+/// | 4      | 4      | the round of the probe, modulo 2^32                        |
+/// | 8      | 2      | the sequence of the probe                                  |
+/// | 10     | 8      | the time the probe was sent, in microseconds since the epoch |
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PayloadStamp {
+    /// The round of the probe, modulo 2^32.
+    pub round: u32,
+    /// The sequence of the probe.
+    pub sequence: u16,
+    /// The time the probe was sent, in microseconds since the Unix epoch.
+    pub sent_micros: u64,
+}
+
+impl PayloadStamp {
+    /// The magic bytes with which every stamp starts.
+    pub const MAGIC: [u8; 4] = *b"TRPY";
+
+    /// The length of a stamp, which is the smallest payload a stamped probe may have.
+    pub const LEN: usize = 18;
+
+    /// The smallest packet of `addr_family` whose payload can hold a stamp, the `ICMP` echo request and `UDP` headers
+    /// being of the same size.
+    #[must_use]
+    pub fn min_packet_size(addr_family: TracerAddrFamily) -> usize {
+        let ip_header_len = match addr_family {
+            TracerAddrFamily::Ipv4 => 20,
+            TracerAddrFamily::Ipv6 => 40,
+        };
+        ip_header_len + TRANSPORT_HEADER_LEN + Self::LEN
+    }
+
+    /// The stamp of `probe`.
+    #[must_use]
+    pub fn new(probe: &Probe) -> Self {
+        let sent_micros = probe
+            .sent
+            .and_then(|sent| sent.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |since| since.as_micros() as u64);
+        Self {
+            round: probe.round.0 as u32,
+            sequence: probe.sequence.0,
+            sent_micros,
+        }
+    }
+
+    /// The time the probe was sent.
+    #[must_use]
+    pub fn sent(&self) -> SystemTime {
+        UNIX_EPOCH + std::time::Duration::from_micros(self.sent_micros)
+    }
+
+    /// Write the stamp to the start of `payload`, which must be at least `LEN` bytes long.
+    pub fn write(&self, payload: &mut [u8]) -> TraceResult<()> {
+        let buf = payload.get_mut(..Self::LEN).req()?;
+        buf[..4].copy_from_slice(&Self::MAGIC);
+        buf[4..8].copy_from_slice(&self.round.to_be_bytes());
+        buf[8..10].copy_from_slice(&self.sequence.to_be_bytes());
+        buf[10..18].copy_from_slice(&self.sent_micros.to_be_bytes());
+        Ok(())
+    }
+
+    /// Read the stamp from the start of `payload`, if it has one.
+    #[must_use]
+    pub fn read(payload: &[u8]) -> Option<Self> {
+        let buf = payload.get(..Self::LEN)?;
+        if buf[..4] != Self::MAGIC {
+            return None;
+        }
+        Some(Self {
+            round: u32::from_be_bytes(buf[4..8].try_into().ok()?),
+            sequence: u16::from_be_bytes(buf[8..10].try_into().ok()?),
+            sent_micros: u64::from_be_bytes(buf[10..18].try_into().ok()?),
+        })
+    }
+}
+
+/// Is the `ICMP` echo request or `UDP` `datagram` quoted in an ICMP error consistent with the `sequence` read from its
+/// headers?
+///
+/// A datagram whose stamp is quoted must carry the stamp of the probe of that sequence, such that a quote whose headers
+/// happen to match a probe, but which is not of that probe, is not matched.  A datagram without a stamp, or whose stamp
+/// was not quoted, is consistent with any sequence.
+pub fn is_consistent(datagram: &[u8], sequence: u16) -> bool {
+    !matches!(
+        datagram.get(TRANSPORT_HEADER_LEN..).and_then(PayloadStamp::read),
+        Some(stamp) if stamp.sequence != sequence
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Round, Sequence, TimeToLive};
+    use std::time::Duration;
+
+    fn stamp() -> PayloadStamp {
+        let sent = UNIX_EPOCH + Duration::from_micros(1_672_531_200_123_456);
+        PayloadStamp::new(&Probe::new(Sequence(33000), TimeToLive(4), Round(7), sent))
+    }
+
+    #[test]
+    fn test_stamp_round_trip() {
+        let mut payload = [0xaa_u8; 40];
+        stamp().write(&mut payload).unwrap();
+        assert_eq!(b"TRPY", &payload[..4]);
+        assert_eq!([0, 0, 0, 7], payload[4..8]);
+        assert_eq!(33000_u16.to_be_bytes(), payload[8..10]);
+        assert_eq!(1_672_531_200_123_456_u64.to_be_bytes(), payload[10..18]);
+        assert!(payload[PayloadStamp::LEN..]
+            .iter()
+            .all(|byte| *byte == 0xaa));
+        let read = PayloadStamp::read(&payload).unwrap();
+        assert_eq!(stamp(), read);
+        assert_eq!(
+            UNIX_EPOCH + Duration::from_micros(1_672_531_200_123_456),
+            read.sent()
+        );
+    }
+
+    #[test]
+    fn test_stamp_too_short() {
+        let mut payload = [0_u8; PayloadStamp::LEN - 1];
+        assert!(stamp().write(&mut payload).is_err());
+        assert_eq!(None, PayloadStamp::read(&payload));
+    }
+
+    #[test]
+    fn test_min_packet_size() {
+        assert_eq!(46, PayloadStamp::min_packet_size(TracerAddrFamily::Ipv4));
+        assert_eq!(66, PayloadStamp::min_packet_size(TracerAddrFamily::Ipv6));
+    }
+
+    #[test]
+    fn test_read_without_stamp() {
+        assert_eq!(None, PayloadStamp::read(&[0_u8; 40]));
+    }
+
+    #[test]
+    fn test_is_consistent() {
+        let mut datagram = [0_u8; TRANSPORT_HEADER_LEN + 40];
+        assert!(is_consistent(&datagram, 1));
+        stamp()
+            .write(&mut datagram[TRANSPORT_HEADER_LEN..])
+            .unwrap();
+        assert!(is_consistent(&datagram, 33000));
+        assert!(!is_consistent(&datagram, 33001));
+        assert!(is_consistent(&datagram[..TRANSPORT_HEADER_LEN + 10], 33001));
+    }
+}
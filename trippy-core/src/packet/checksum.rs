@@ -4,7 +4,7 @@
 //!
 //! [`libpnet`]: https://github.com/libpnet/libpnet
 
-use crate::tracing::packet::IpProtocol;
+use crate::packet::IpProtocol;
 use std::net::{Ipv4Addr, Ipv6Addr};
 
 /// Calculate the checksum for an `Ipv4` `ICMP` packet.
@@ -92,7 +92,7 @@ fn sum_be_words(data: &[u8], ignore_word: usize) -> u32 {
     let mut i = 0;
     while cur_data.len() >= 2 {
         if i != ignore_word {
-            sum += u32::from(u16::from_be_bytes(cur_data[0..2].try_into().unwrap()));
+            sum += u32::from(u16::from_be_bytes([cur_data[0], cur_data[1]]));
         }
         cur_data = &cur_data[2..];
         i += 1;
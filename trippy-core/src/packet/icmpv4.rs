@@ -1,4 +1,4 @@
-use crate::tracing::packet::buffer::Buffer;
+use crate::packet::buffer::Buffer;
 use std::fmt::{Debug, Formatter};
 
 /// The type of ICMP packet.
@@ -8,6 +8,8 @@ pub enum IcmpType {
     EchoReply,
     DestinationUnreachable,
     TimeExceeded,
+    TimestampRequest,
+    TimestampReply,
     Other(u8),
 }
 
@@ -19,6 +21,8 @@ impl IcmpType {
             Self::EchoReply => 0,
             Self::DestinationUnreachable => 3,
             Self::TimeExceeded => 11,
+            Self::TimestampRequest => 13,
+            Self::TimestampReply => 14,
             Self::Other(id) => *id,
         }
     }
@@ -31,6 +35,8 @@ impl From<u8> for IcmpType {
             0 => Self::EchoReply,
             3 => Self::DestinationUnreachable,
             11 => Self::TimeExceeded,
+            13 => Self::TimestampRequest,
+            14 => Self::TimestampReply,
             id => Self::Other(id),
         }
     }
@@ -148,6 +154,12 @@ mod tests {
         packet.set_icmp_type(IcmpType::TimeExceeded);
         assert_eq!(IcmpType::TimeExceeded, packet.get_icmp_type());
         assert_eq!([0x0B], packet.packet()[0..1]);
+        packet.set_icmp_type(IcmpType::TimestampRequest);
+        assert_eq!(IcmpType::TimestampRequest, packet.get_icmp_type());
+        assert_eq!([0x0D], packet.packet()[0..1]);
+        packet.set_icmp_type(IcmpType::TimestampReply);
+        assert_eq!(IcmpType::TimestampReply, packet.get_icmp_type());
+        assert_eq!([0x0E], packet.packet()[0..1]);
         packet.set_icmp_type(IcmpType::Other(255));
         assert_eq!(IcmpType::Other(255), packet.get_icmp_type());
         assert_eq!([0xFF], packet.packet()[0..1]);
@@ -185,9 +197,9 @@ mod tests {
 }
 
 pub mod echo_request {
-    use crate::tracing::packet::buffer::Buffer;
-    use crate::tracing::packet::fmt_payload;
-    use crate::tracing::packet::icmpv4::{IcmpCode, IcmpType};
+    use crate::packet::buffer::Buffer;
+    use crate::packet::fmt_payload;
+    use crate::packet::icmpv4::{IcmpCode, IcmpType};
     use std::fmt::{Debug, Formatter};
 
     const TYPE_OFFSET: usize = 0;
@@ -406,9 +418,9 @@ pub mod echo_request {
 }
 
 pub mod echo_reply {
-    use crate::tracing::packet::buffer::Buffer;
-    use crate::tracing::packet::fmt_payload;
-    use crate::tracing::packet::icmpv4::{IcmpCode, IcmpType};
+    use crate::packet::buffer::Buffer;
+    use crate::packet::fmt_payload;
+    use crate::packet::icmpv4::{IcmpCode, IcmpType};
     use std::fmt::{Debug, Formatter};
 
     const TYPE_OFFSET: usize = 0;
@@ -626,10 +638,203 @@ pub mod echo_reply {
     }
 }
 
+pub mod timestamp {
+    use crate::packet::buffer::Buffer;
+    use crate::packet::icmpv4::{IcmpCode, IcmpType};
+    use std::fmt::{Debug, Formatter};
+
+    const TYPE_OFFSET: usize = 0;
+    const CODE_OFFSET: usize = 1;
+    const CHECKSUM_OFFSET: usize = 2;
+    const IDENTIFIER_OFFSET: usize = 4;
+    const SEQUENCE_OFFSET: usize = 6;
+    const ORIGINATE_OFFSET: usize = 8;
+    const RECEIVE_OFFSET: usize = 12;
+    const TRANSMIT_OFFSET: usize = 16;
+
+    /// Represents an ICMP `TimestampRequest` or `TimestampReply` packet, which share a format.
+    ///
+    /// Each timestamp is the milliseconds since midnight UT, with the high bit set if it is not.
+    ///
+    /// The internal representation is held in network byte order (big-endian) and all accessor methods take and return
+    /// data in host byte order, converting as necessary for the given architecture.
+    pub struct TimestampPacket<'a> {
+        buf: Buffer<'a>,
+    }
+
+    impl<'a> TimestampPacket<'a> {
+        pub fn new(packet: &'a mut [u8]) -> Option<Self> {
+            if packet.len() >= Self::minimum_packet_size() {
+                Some(Self {
+                    buf: Buffer::Mutable(packet),
+                })
+            } else {
+                None
+            }
+        }
+
+        #[must_use]
+        pub fn new_view(packet: &'a [u8]) -> Option<Self> {
+            if packet.len() >= Self::minimum_packet_size() {
+                Some(Self {
+                    buf: Buffer::Immutable(packet),
+                })
+            } else {
+                None
+            }
+        }
+
+        #[must_use]
+        pub const fn minimum_packet_size() -> usize {
+            20
+        }
+
+        #[must_use]
+        pub fn get_icmp_type(&self) -> IcmpType {
+            IcmpType::from(self.buf.read(TYPE_OFFSET))
+        }
+
+        #[must_use]
+        pub fn get_icmp_code(&self) -> IcmpCode {
+            IcmpCode::from(self.buf.read(CODE_OFFSET))
+        }
+
+        #[must_use]
+        pub fn get_checksum(&self) -> u16 {
+            u16::from_be_bytes(self.buf.get_bytes(CHECKSUM_OFFSET))
+        }
+
+        #[must_use]
+        pub fn get_identifier(&self) -> u16 {
+            u16::from_be_bytes(self.buf.get_bytes(IDENTIFIER_OFFSET))
+        }
+
+        #[must_use]
+        pub fn get_sequence(&self) -> u16 {
+            u16::from_be_bytes(self.buf.get_bytes(SEQUENCE_OFFSET))
+        }
+
+        #[must_use]
+        pub fn get_originate(&self) -> u32 {
+            u32::from_be_bytes(self.buf.get_bytes(ORIGINATE_OFFSET))
+        }
+
+        #[must_use]
+        pub fn get_receive(&self) -> u32 {
+            u32::from_be_bytes(self.buf.get_bytes(RECEIVE_OFFSET))
+        }
+
+        #[must_use]
+        pub fn get_transmit(&self) -> u32 {
+            u32::from_be_bytes(self.buf.get_bytes(TRANSMIT_OFFSET))
+        }
+
+        pub fn set_icmp_type(&mut self, val: IcmpType) {
+            *self.buf.write(TYPE_OFFSET) = val.id();
+        }
+
+        pub fn set_icmp_code(&mut self, val: IcmpCode) {
+            *self.buf.write(CODE_OFFSET) = val.0;
+        }
+
+        pub fn set_checksum(&mut self, val: u16) {
+            self.buf.set_bytes(CHECKSUM_OFFSET, val.to_be_bytes());
+        }
+
+        pub fn set_identifier(&mut self, val: u16) {
+            self.buf.set_bytes(IDENTIFIER_OFFSET, val.to_be_bytes());
+        }
+
+        pub fn set_sequence(&mut self, val: u16) {
+            self.buf.set_bytes(SEQUENCE_OFFSET, val.to_be_bytes());
+        }
+
+        pub fn set_originate(&mut self, val: u32) {
+            self.buf.set_bytes(ORIGINATE_OFFSET, val.to_be_bytes());
+        }
+
+        pub fn set_receive(&mut self, val: u32) {
+            self.buf.set_bytes(RECEIVE_OFFSET, val.to_be_bytes());
+        }
+
+        pub fn set_transmit(&mut self, val: u32) {
+            self.buf.set_bytes(TRANSMIT_OFFSET, val.to_be_bytes());
+        }
+
+        #[must_use]
+        pub fn packet(&self) -> &[u8] {
+            self.buf.as_slice()
+        }
+    }
+
+    impl Debug for TimestampPacket<'_> {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("TimestampPacket")
+                .field("icmp_type", &self.get_icmp_type())
+                .field("icmp_code", &self.get_icmp_code())
+                .field("checksum", &self.get_checksum())
+                .field("identifier", &self.get_identifier())
+                .field("sequence", &self.get_sequence())
+                .field("originate", &self.get_originate())
+                .field("receive", &self.get_receive())
+                .field("transmit", &self.get_transmit())
+                .finish()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_identifier_and_sequence() {
+            let mut buf = [0_u8; TimestampPacket::minimum_packet_size()];
+            let mut packet = TimestampPacket::new(&mut buf).unwrap();
+            packet.set_identifier(1999);
+            packet.set_sequence(u16::MAX);
+            assert_eq!(1999, packet.get_identifier());
+            assert_eq!(u16::MAX, packet.get_sequence());
+            assert_eq!([0x07, 0xCF, 0xFF, 0xFF], packet.packet()[4..=7]);
+        }
+
+        #[test]
+        fn test_timestamps() {
+            let mut buf = [0_u8; TimestampPacket::minimum_packet_size()];
+            let mut packet = TimestampPacket::new(&mut buf).unwrap();
+            packet.set_originate(0);
+            packet.set_receive(86_399_999);
+            packet.set_transmit(u32::MAX);
+            assert_eq!(0, packet.get_originate());
+            assert_eq!(86_399_999, packet.get_receive());
+            assert_eq!(u32::MAX, packet.get_transmit());
+            assert_eq!([0x00, 0x00, 0x00, 0x00], packet.packet()[8..=11]);
+            assert_eq!([0x05, 0x26, 0x5B, 0xFF], packet.packet()[12..=15]);
+            assert_eq!([0xFF, 0xFF, 0xFF, 0xFF], packet.packet()[16..=19]);
+        }
+
+        #[test]
+        fn test_view() {
+            let buf = [
+                0x0e, 0x00, 0x6c, 0x2b, 0x60, 0x9b, 0x80, 0xe8, 0x02, 0x93, 0x4f, 0x70, 0x02, 0x93,
+                0x4f, 0x82, 0x02, 0x93, 0x4f, 0x83,
+            ];
+            let packet = TimestampPacket::new_view(&buf).unwrap();
+            assert_eq!(IcmpType::TimestampReply, packet.get_icmp_type());
+            assert_eq!(IcmpCode(0), packet.get_icmp_code());
+            assert_eq!(27691, packet.get_checksum());
+            assert_eq!(24731, packet.get_identifier());
+            assert_eq!(33000, packet.get_sequence());
+            assert_eq!(43_208_560, packet.get_originate());
+            assert_eq!(43_208_578, packet.get_receive());
+            assert_eq!(43_208_579, packet.get_transmit());
+        }
+    }
+}
+
 pub mod time_exceeded {
-    use crate::tracing::packet::buffer::Buffer;
-    use crate::tracing::packet::fmt_payload;
-    use crate::tracing::packet::icmpv4::{IcmpCode, IcmpType};
+    use crate::packet::buffer::Buffer;
+    use crate::packet::fmt_payload;
+    use crate::packet::icmpv4::{IcmpCode, IcmpType};
     use std::fmt::{Debug, Formatter};
 
     const TYPE_OFFSET: usize = 0;
@@ -794,9 +999,9 @@ pub mod time_exceeded {
 }
 
 pub mod destination_unreachable {
-    use crate::tracing::packet::buffer::Buffer;
-    use crate::tracing::packet::fmt_payload;
-    use crate::tracing::packet::icmpv4::{IcmpCode, IcmpType};
+    use crate::packet::buffer::Buffer;
+    use crate::packet::fmt_payload;
+    use crate::packet::icmpv4::{IcmpCode, IcmpType};
     use std::fmt::{Debug, Formatter};
 
     const TYPE_OFFSET: usize = 0;
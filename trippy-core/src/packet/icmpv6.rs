@@ -1,4 +1,4 @@
-use crate::tracing::packet::buffer::Buffer;
+use crate::packet::buffer::Buffer;
 use std::fmt::{Debug, Formatter};
 
 /// The type of `ICMPv6` packet.
@@ -185,9 +185,9 @@ mod tests {
 }
 
 pub mod echo_request {
-    use crate::tracing::packet::buffer::Buffer;
-    use crate::tracing::packet::fmt_payload;
-    use crate::tracing::packet::icmpv6::{IcmpCode, IcmpType};
+    use crate::packet::buffer::Buffer;
+    use crate::packet::fmt_payload;
+    use crate::packet::icmpv6::{IcmpCode, IcmpType};
     use std::fmt::{Debug, Formatter};
 
     const TYPE_OFFSET: usize = 0;
@@ -406,9 +406,9 @@ pub mod echo_request {
 }
 
 pub mod echo_reply {
-    use crate::tracing::packet::buffer::Buffer;
-    use crate::tracing::packet::fmt_payload;
-    use crate::tracing::packet::icmpv6::{IcmpCode, IcmpType};
+    use crate::packet::buffer::Buffer;
+    use crate::packet::fmt_payload;
+    use crate::packet::icmpv6::{IcmpCode, IcmpType};
     use std::fmt::{Debug, Formatter};
 
     const TYPE_OFFSET: usize = 0;
@@ -627,9 +627,9 @@ pub mod echo_reply {
 }
 
 pub mod time_exceeded {
-    use crate::tracing::packet::buffer::Buffer;
-    use crate::tracing::packet::fmt_payload;
-    use crate::tracing::packet::icmpv6::{IcmpCode, IcmpType};
+    use crate::packet::buffer::Buffer;
+    use crate::packet::fmt_payload;
+    use crate::packet::icmpv6::{IcmpCode, IcmpType};
     use std::fmt::{Debug, Formatter};
 
     const TYPE_OFFSET: usize = 0;
@@ -794,9 +794,9 @@ pub mod time_exceeded {
 }
 
 pub mod destination_unreachable {
-    use crate::tracing::packet::buffer::Buffer;
-    use crate::tracing::packet::fmt_payload;
-    use crate::tracing::packet::icmpv6::{IcmpCode, IcmpType};
+    use crate::packet::buffer::Buffer;
+    use crate::packet::fmt_payload;
+    use crate::packet::icmpv6::{IcmpCode, IcmpType};
     use std::fmt::{Debug, Formatter};
 
     const TYPE_OFFSET: usize = 0;
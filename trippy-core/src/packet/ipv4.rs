@@ -1,5 +1,5 @@
-use crate::tracing::packet::buffer::Buffer;
-use crate::tracing::packet::{fmt_payload, IpProtocol};
+use crate::packet::buffer::Buffer;
+use crate::packet::{fmt_payload, IpProtocol};
 use std::fmt::{Debug, Formatter};
 use std::net::Ipv4Addr;
 
@@ -1,5 +1,5 @@
-use crate::tracing::packet::buffer::Buffer;
-use crate::tracing::packet::fmt_payload;
+use crate::packet::buffer::Buffer;
+use crate::packet::fmt_payload;
 use std::fmt::{Debug, Formatter};
 
 const SOURCE_PORT_OFFSET: usize = 0;
@@ -0,0 +1,147 @@
+use crate::error::{TraceResult, TracerError};
+use std::fmt::{Display, Formatter};
+use std::io;
+
+/// The scheduling priority of the thread which sends and receives the probes.
+///
+/// A tracer which is preempted by other threads, such as those of the TUI or of other processes on a loaded host,
+/// timestamps the responses it receives late, which is indistinguishable from network jitter.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TracerPriority {
+    /// A real-time priority, `SCHED_FIFO` on unix and time critical on Windows.
+    Realtime,
+    /// A raised priority, a nice of -10 on unix and highest on Windows.
+    High,
+    /// The priority of the thread is not changed.
+    Normal,
+}
+
+impl Display for TracerPriority {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Realtime => write!(f, "realtime"),
+            Self::High => write!(f, "high"),
+            Self::Normal => write!(f, "normal"),
+        }
+    }
+}
+
+/// Raise the scheduling priority of the current thread to `priority`.
+///
+/// Raising the priority requires privileges on most platforms, i.e. `CAP_SYS_NICE` on Linux, without which this fails
+/// and the priority of the thread is unchanged.
+pub fn set_thread_priority(priority: TracerPriority) -> TraceResult<()> {
+    match priority {
+        TracerPriority::Realtime => set_realtime(),
+        TracerPriority::High => set_high(),
+        TracerPriority::Normal => Ok(()),
+    }
+    .map_err(|err| TracerError::SetPriority(priority, err))
+}
+
+/// The nice of a thread of high priority.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const HIGH_NICE: libc::c_int = -10;
+
+/// Schedule the current thread first-in first-out at the lowest real-time priority, which is above that of every
+/// thread which is not real-time.
+#[cfg(unix)]
+#[allow(unsafe_code)]
+fn set_realtime() -> io::Result<()> {
+    // Safety: the `sched_param` is zeroed, which is valid for every platform, before the priority is set, and the
+    // thread is the current thread.
+    unsafe {
+        let mut param: libc::sched_param = std::mem::zeroed();
+        param.sched_priority = libc::sched_get_priority_min(libc::SCHED_FIFO);
+        match libc::pthread_setschedparam(
+            libc::pthread_self(),
+            libc::SCHED_FIFO,
+            std::ptr::addr_of!(param),
+        ) {
+            0 => Ok(()),
+            code => Err(io::Error::from_raw_os_error(code)),
+        }
+    }
+}
+
+/// Lower the nice of the current thread, which on Linux is per thread rather than per process.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[allow(unsafe_code, clippy::cast_sign_loss)]
+fn set_high() -> io::Result<()> {
+    // Safety: `setpriority` is given the id of the current thread and reports failure with `errno`.
+    unsafe {
+        let tid = libc::gettid() as libc::id_t;
+        match libc::setpriority(libc::PRIO_PROCESS, tid, HIGH_NICE) {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+}
+
+/// Raise the current thread to the highest priority of its scheduling policy.
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+#[allow(unsafe_code)]
+fn set_high() -> io::Result<()> {
+    // Safety: the policy and `sched_param` are written by `pthread_getschedparam` before they are read, and the thread
+    // is the current thread.
+    unsafe {
+        let mut policy = 0;
+        let mut param: libc::sched_param = std::mem::zeroed();
+        let thread = libc::pthread_self();
+        match libc::pthread_getschedparam(
+            thread,
+            std::ptr::addr_of_mut!(policy),
+            std::ptr::addr_of_mut!(param),
+        ) {
+            0 => {}
+            code => return Err(io::Error::from_raw_os_error(code)),
+        }
+        param.sched_priority = libc::sched_get_priority_max(policy);
+        match libc::pthread_setschedparam(thread, policy, std::ptr::addr_of!(param)) {
+            0 => Ok(()),
+            code => Err(io::Error::from_raw_os_error(code)),
+        }
+    }
+}
+
+#[cfg(windows)]
+fn set_realtime() -> io::Result<()> {
+    set_windows_priority(windows::Win32::System::Threading::THREAD_PRIORITY_TIME_CRITICAL)
+}
+
+#[cfg(windows)]
+fn set_high() -> io::Result<()> {
+    set_windows_priority(windows::Win32::System::Threading::THREAD_PRIORITY_HIGHEST)
+}
+
+/// Set the priority of the current thread.
+#[cfg(windows)]
+#[allow(unsafe_code)]
+fn set_windows_priority(
+    priority: windows::Win32::System::Threading::THREAD_PRIORITY,
+) -> io::Result<()> {
+    use windows::Win32::System::Threading::{GetCurrentThread, SetThreadPriority};
+    // Safety: the pseudo handle of the current thread is always valid.
+    if unsafe { SetThreadPriority(GetCurrentThread(), priority) }.as_bool() {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normal() {
+        assert!(set_thread_priority(TracerPriority::Normal).is_ok());
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!("realtime", TracerPriority::Realtime.to_string());
+        assert_eq!("high", TracerPriority::High.to_string());
+        assert_eq!("normal", TracerPriority::Normal.to_string());
+    }
+}
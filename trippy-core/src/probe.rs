@@ -0,0 +1,678 @@
+use crate::error::TracerError;
+use crate::types::{Round, Sequence, TimeToLive};
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::net::IpAddr;
+use std::num::NonZeroI32;
+use std::time::{Duration, Instant, SystemTime};
+
+/// The state of an ICMP echo request/response
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Probe {
+    /// The sequence of the probe.
+    pub sequence: Sequence,
+    /// The TTL of the probe.
+    pub ttl: TimeToLive,
+    /// Which round the probe belongs to.
+    pub round: Round,
+    /// Timestamp when the probe was sent.
+    pub sent: Option<SystemTime>,
+    /// The instant at which the probe was sent, by the monotonic clock, if it was timed by it.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub sent_at: Option<Instant>,
+    /// The status of the probe.
+    pub status: ProbeStatus,
+    /// The host which responded to the probe.
+    pub host: Option<IpAddr>,
+    /// Timestamp when the response to the probe was received.
+    pub received: Option<SystemTime>,
+    /// The instant at which the response to the probe was received, by the monotonic clock, if it was timed by it.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub received_at: Option<Instant>,
+    /// The type of ICMP response packet received for the probe.
+    pub icmp_packet_type: Option<IcmpPacketType>,
+    /// The next-hop MTU of an ICMP fragmentation needed response to the probe, if given.
+    pub next_hop_mtu: Option<u16>,
+    /// The address of the target to which the probe was sent, which varies by round if the target has several.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub target: Option<IpAddr>,
+    /// The timestamps of an ICMP timestamp reply to the probe, if given.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub timestamps: Option<IcmpTimestamps>,
+    /// The time-to-live of the response to the probe, as received, if known.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub recv_ttl: Option<u8>,
+    /// The size of the ICMP error in response to the probe and of the probe quoted in it, if the response is one.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub quote: Option<IcmpQuote>,
+    /// The port probed and the port from which the target replied, if it replied at the transport layer.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub responder_ports: Option<ResponderPorts>,
+}
+
+impl Probe {
+    #[must_use]
+    pub const fn new(sequence: Sequence, ttl: TimeToLive, round: Round, sent: SystemTime) -> Self {
+        Self {
+            sequence,
+            ttl,
+            round,
+            sent: Some(sent),
+            sent_at: None,
+            status: ProbeStatus::Awaited,
+            host: None,
+            received: None,
+            received_at: None,
+            icmp_packet_type: None,
+            next_hop_mtu: None,
+            target: None,
+            timestamps: None,
+            recv_ttl: None,
+            quote: None,
+            responder_ports: None,
+        }
+    }
+
+    /// The duration of a probe which has been answered.
+    ///
+    /// The duration is measured by the monotonic clock if the probe was timed by it, and otherwise, as for a probe
+    /// restored from a saved trace, by the wall clock, in which case it is `None` if the wall clock was set back such
+    /// that the response appears to have been received before the probe was sent.
+    #[must_use]
+    pub fn checked_duration(&self) -> Option<Duration> {
+        match (self.sent_at, self.received_at, self.sent, self.received) {
+            (Some(sent_at), Some(received_at), _, _) => {
+                Some(received_at.saturating_duration_since(sent_at))
+            }
+            (_, _, Some(sent), Some(recv)) => recv.duration_since(sent).ok(),
+            _ => None,
+        }
+    }
+
+    /// The duration of this probe.
+    #[must_use]
+    pub fn duration(&self) -> Duration {
+        match (self.sent_at, self.sent, self.received) {
+            (_, _, Some(_)) => self.checked_duration().unwrap_or_default(),
+            (Some(sent_at), _, None) => sent_at.elapsed(),
+            (None, Some(sent), None) => sent.elapsed().unwrap_or_default(),
+            (None, None, None) => Duration::default(),
+        }
+    }
+
+    #[must_use]
+    pub const fn with_status(self, status: ProbeStatus) -> Self {
+        Self { status, ..self }
+    }
+
+    #[must_use]
+    pub const fn with_icmp_packet_type(self, icmp_packet_type: IcmpPacketType) -> Self {
+        Self {
+            icmp_packet_type: Some(icmp_packet_type),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub const fn with_next_hop_mtu(self, next_hop_mtu: Option<u16>) -> Self {
+        Self {
+            next_hop_mtu,
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub const fn with_timestamps(self, timestamps: IcmpTimestamps) -> Self {
+        Self {
+            timestamps: Some(timestamps),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub const fn with_recv_ttl(self, recv_ttl: Option<u8>) -> Self {
+        Self { recv_ttl, ..self }
+    }
+
+    #[must_use]
+    pub const fn with_quote(self, quote: Option<IcmpQuote>) -> Self {
+        Self { quote, ..self }
+    }
+
+    #[must_use]
+    pub const fn with_responder_ports(self, responder_ports: Option<ResponderPorts>) -> Self {
+        Self {
+            responder_ports,
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub const fn with_target(self, target: IpAddr) -> Self {
+        Self {
+            target: Some(target),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub const fn with_host(self, host: IpAddr) -> Self {
+        Self {
+            host: Some(host),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub const fn with_received(self, received: SystemTime) -> Self {
+        Self {
+            received: Some(received),
+            ..self
+        }
+    }
+
+    /// The probe sent at the `sent_at` instant of the monotonic clock, from which its duration is measured.
+    #[must_use]
+    pub const fn with_sent_at(self, sent_at: Instant) -> Self {
+        Self {
+            sent_at: Some(sent_at),
+            ..self
+        }
+    }
+
+    /// The probe answered at the `received_at` instant of the monotonic clock, from which its duration is measured.
+    #[must_use]
+    pub const fn with_received_at(self, received_at: Instant) -> Self {
+        Self {
+            received_at: Some(received_at),
+            ..self
+        }
+    }
+
+    /// The number of hops by which the return path of the response to this probe is longer than its forward path,
+    /// negative if shorter, or `None` if the time-to-live of the response is unknown or its initial value is in doubt.
+    #[must_use]
+    pub fn asymmetry(&self) -> Option<i16> {
+        let return_hops = return_hops(self.recv_ttl?)?;
+        Some(i16::from(return_hops) - i16::from(self.ttl.0))
+    }
+}
+
+/// The status of a `Echo` for a single TTL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ProbeStatus {
+    /// The probe has not been sent.
+    NotSent,
+    /// The probe has been sent and we are awaiting the response.
+    Awaited,
+    /// The probe has been sent and a response (`EchoReply`, `DestinationUnreachable` or `TimeExceeded`) has
+    /// been received.
+    Complete,
+    /// The probe could not be sent, for a reason local to this host rather than the path.
+    Failed(SendFailure),
+}
+
+impl Default for ProbeStatus {
+    fn default() -> Self {
+        Self::NotSent
+    }
+}
+
+/// Why a probe could not be sent, such as a lack of buffer space or a local firewall refusing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SendFailure {
+    /// The error of the OS, if known, which is never zero.
+    pub os_error: Option<NonZeroI32>,
+}
+
+impl SendFailure {
+    /// The failure of a probe to send with `err`.
+    #[must_use]
+    pub fn new(err: &TracerError) -> Self {
+        let os_error = match err {
+            TracerError::IoError(err)
+            | TracerError::NetworkUnreachable(_, err)
+            | TracerError::PermissionDenied(_, err)
+            | TracerError::ProbeSendFailed(_, err) => err.raw_os_error().and_then(NonZeroI32::new),
+            _ => None,
+        };
+        Self { os_error }
+    }
+}
+
+impl Display for SendFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.os_error {
+            Some(code) => write!(f, "{}", io::Error::from_raw_os_error(code.get())),
+            None => write!(f, "unknown error"),
+        }
+    }
+}
+
+/// The type of ICMP packet received.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IcmpPacketType {
+    /// TimeExceeded packet.
+    TimeExceeded,
+    /// EchoReply packet.
+    EchoReply,
+    /// `TimestampReply` packet.
+    TimestampReply,
+    /// Unreachable packet with the given ICMP code.
+    Unreachable(u8),
+    /// Non-ICMP response (i.e. for some `UDP` & `TCP` probes).
+    NotApplicable,
+}
+
+/// The response to a probe.
+#[derive(Debug, Copy, Clone)]
+pub enum ProbeResponse {
+    TimeExceeded(ProbeResponseData),
+    /// A destination unreachable response with the ICMP code and the next-hop MTU of a fragmentation needed response.
+    DestinationUnreachable(ProbeResponseData, u8, Option<u16>),
+    EchoReply(ProbeResponseData),
+    /// A timestamp reply response with the timestamps it carries.
+    TimestampReply(ProbeResponseData, IcmpTimestamps),
+    TcpReply(ProbeResponseData),
+    TcpRefused(ProbeResponseData),
+}
+
+impl ProbeResponse {
+    /// The data of the response, whatever its type.
+    #[must_use]
+    pub const fn data(&self) -> &ProbeResponseData {
+        match self {
+            Self::TimeExceeded(data)
+            | Self::DestinationUnreachable(data, _, _)
+            | Self::EchoReply(data)
+            | Self::TimestampReply(data, _)
+            | Self::TcpReply(data)
+            | Self::TcpRefused(data) => data,
+        }
+    }
+}
+
+/// The data in the probe response.
+#[derive(Debug, Copy, Clone)]
+pub struct ProbeResponseData {
+    pub recv: SystemTime,
+    /// The instant at which the response was received, by the monotonic clock, from which its round trip time is
+    /// measured.
+    pub recv_at: Instant,
+    pub addr: IpAddr,
+    pub identifier: u16,
+    pub sequence: u16,
+    /// The time-to-live of the response as received, if the IP header of the response was read.
+    pub recv_ttl: Option<u8>,
+    /// The size of the ICMP error and of the probe quoted in it, if the response is one.
+    pub quote: Option<IcmpQuote>,
+    /// The port probed and the port from which the target replied, if the response is a reply of the transport.
+    pub responder_ports: Option<ResponderPorts>,
+}
+
+impl ProbeResponseData {
+    #[must_use]
+    pub fn new(recv: SystemTime, addr: IpAddr, identifier: u16, sequence: u16) -> Self {
+        Self {
+            recv,
+            recv_at: Instant::now(),
+            addr,
+            identifier,
+            sequence,
+            recv_ttl: None,
+            quote: None,
+            responder_ports: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_recv_ttl(self, recv_ttl: u8) -> Self {
+        Self {
+            recv_ttl: Some(recv_ttl),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn with_quote(self, quote: IcmpQuote) -> Self {
+        Self {
+            quote: Some(quote),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn with_responder_ports(self, responder_ports: ResponderPorts) -> Self {
+        Self {
+            responder_ports: Some(responder_ports),
+            ..self
+        }
+    }
+}
+
+/// The destination port of a probe which the target answered at the transport layer, such as with a TCP SYN-ACK, and
+/// the source port of its reply.
+///
+/// The ports differ if the reply came not from the service probed but from something answering on its behalf, such
+/// as a transparent proxy.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ResponderPorts {
+    /// The destination port of the probe.
+    pub probed: u16,
+    /// The source port of the reply.
+    pub responder: u16,
+}
+
+impl ResponderPorts {
+    #[must_use]
+    pub fn new(probed: u16, responder: u16) -> Self {
+        Self { probed, responder }
+    }
+
+    /// Did the reply come from a port other than the one probed?
+    #[must_use]
+    pub fn is_mismatch(&self) -> bool {
+        self.probed != self.responder
+    }
+}
+
+/// The fields of a probe, as sent, which the routers on its path may hash to choose between equal cost paths.
+///
+/// Only the fields which were set by the tracer are recorded, such that the IPv4 identification of a probe is not
+/// recorded unless it encodes the sequence, as for the `dublin` strategy, and the flow label of an IPv6 probe, which
+/// is left to the platform, is not recorded at all.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FlowKey {
+    /// The source port of a `udp` or `tcp` probe.
+    pub src_port: Option<u16>,
+    /// The destination port of a `udp` or `tcp` probe.
+    pub dest_port: Option<u16>,
+    /// The identifier of an `icmp` probe.
+    pub icmp_identifier: Option<u16>,
+    /// The checksum of an `icmp` or `udp` probe, which varies with its sequence and payload.
+    pub checksum: Option<u16>,
+    /// The IPv4 identification of the probe, if set by the tracer.
+    pub ip_id: Option<u16>,
+}
+
+impl FlowKey {
+    #[must_use]
+    pub fn icmp(identifier: u16, checksum: u16) -> Self {
+        Self {
+            icmp_identifier: Some(identifier),
+            checksum: Some(checksum),
+            ..Self::default()
+        }
+    }
+
+    #[must_use]
+    pub fn udp(src_port: u16, dest_port: u16, checksum: u16) -> Self {
+        Self {
+            src_port: Some(src_port),
+            dest_port: Some(dest_port),
+            checksum: Some(checksum),
+            ..Self::default()
+        }
+    }
+
+    #[must_use]
+    pub fn tcp(src_port: u16, dest_port: u16) -> Self {
+        Self {
+            src_port: Some(src_port),
+            dest_port: Some(dest_port),
+            ..Self::default()
+        }
+    }
+
+    /// The key with the IPv4 identification `ip_id`, should it have been set by the tracer rather than left as zero
+    /// for the platform to fill.
+    #[must_use]
+    pub fn with_ip_id(self, ip_id: u16) -> Self {
+        Self {
+            ip_id: (ip_id != 0).then_some(ip_id),
+            ..self
+        }
+    }
+}
+
+impl Display for FlowKey {
+    /// Format the fields recorded, i.e. `src_port=33000 dest_port=33434 checksum=0xbeef`.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let fields = [
+            ("src_port", self.src_port.map(|port| port.to_string())),
+            ("dest_port", self.dest_port.map(|port| port.to_string())),
+            ("icmp_id", self.icmp_identifier.map(|id| id.to_string())),
+            ("checksum", self.checksum.map(|sum| format!("{sum:#06x}"))),
+            ("ip_id", self.ip_id.map(|id| id.to_string())),
+        ];
+        let mut sep = "";
+        for (name, value) in fields {
+            if let Some(value) = value {
+                write!(f, "{sep}{name}={value}")?;
+                sep = " ";
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The size of the payload of an ICMP error and of the probe quoted in it.
+///
+/// An ICMP error need only quote the IP header of the probe and the first 8 bytes of the datagram it heads, though
+/// many hosts quote the probe in full, and some pad the quote or truncate it further.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IcmpQuote {
+    /// The size of the payload of the ICMP error, in bytes, including any padding.
+    pub payload_len: u16,
+    /// The size of the probe quoted, in bytes, less any padding.
+    pub quoted_len: u16,
+    /// Whether the probe was quoted in full.
+    pub full: bool,
+}
+
+impl IcmpQuote {
+    #[must_use]
+    pub fn new(payload_len: usize, quoted_len: usize, full: bool) -> Self {
+        Self {
+            payload_len: u16::try_from(payload_len).unwrap_or(u16::MAX),
+            quoted_len: u16::try_from(quoted_len).unwrap_or(u16::MAX),
+            full,
+        }
+    }
+}
+
+/// The initial time-to-live of the responses of almost all hosts, one of which a response is inferred to be sent with.
+const INITIAL_TTLS: [u8; 3] = [64, 128, 255];
+
+/// The most hops a response is inferred to have travelled, beyond which its initial time-to-live is in doubt.
+const MAX_RETURN_HOPS: u8 = 32;
+
+/// The number of hops of the return path of a response received with time-to-live `recv_ttl`, counting the host
+/// which sent it, as is the forward path of a probe.
+///
+/// The response is inferred to have been sent with the least of the common initial time-to-live values of 64, 128 and
+/// 255 which is no less than `recv_ttl`.  There are no hops if this inference is in doubt, as the response would have
+/// travelled more than `MAX_RETURN_HOPS`, such as when a host sends with another initial value.
+#[must_use]
+pub fn return_hops(recv_ttl: u8) -> Option<u8> {
+    let initial = INITIAL_TTLS
+        .into_iter()
+        .find(|initial| *initial >= recv_ttl)?;
+    let decrements = initial - recv_ttl;
+    (decrements < MAX_RETURN_HOPS).then_some(decrements + 1)
+}
+
+/// The milliseconds in a day, through which the timestamps of ICMP timestamp messages count from midnight UT.
+const MILLIS_PER_DAY: i64 = 86_400_000;
+
+/// The timestamps of an ICMP timestamp reply, each in milliseconds since midnight UT.
+///
+/// The `originate` timestamp is ours, echoed back, whereas the `receive` and `transmit` timestamps are of the clock of
+/// the remote host when it received the request and sent the reply.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IcmpTimestamps {
+    pub originate: u32,
+    pub receive: u32,
+    pub transmit: u32,
+}
+
+impl IcmpTimestamps {
+    #[must_use]
+    pub fn new(originate: u32, receive: u32, transmit: u32) -> Self {
+        Self {
+            originate,
+            receive,
+            transmit,
+        }
+    }
+
+    /// The offset of the remote clock from ours, in milliseconds, given the reply was received at `received`.
+    ///
+    /// This is estimated as for NTP, assuming the delay is the same in both directions, and so is experimental: any
+    /// asymmetry of the path is indistinguishable from an offset.  There is no offset if the remote host did not give
+    /// standard timestamps, i.e. it set the high bit.
+    #[must_use]
+    pub fn clock_offset_ms(&self, received: SystemTime) -> Option<i64> {
+        const NON_STANDARD: u32 = 1 << 31;
+        if (self.receive | self.transmit) & NON_STANDARD != 0 {
+            return None;
+        }
+        let received = i64::from(millis_since_midnight(received));
+        let diff = |later: i64, earlier: i64| {
+            (later - earlier + MILLIS_PER_DAY / 2).rem_euclid(MILLIS_PER_DAY) - MILLIS_PER_DAY / 2
+        };
+        let outbound = diff(i64::from(self.receive), i64::from(self.originate));
+        let inbound = diff(i64::from(self.transmit), received);
+        let round_trip_offset = outbound + inbound;
+        Some(round_trip_offset / 2)
+    }
+}
+
+/// The milliseconds since midnight UT of `time`, as given in the timestamps of ICMP timestamp messages.
+#[must_use]
+pub fn millis_since_midnight(time: SystemTime) -> u32 {
+    let millis = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    (millis % MILLIS_PER_DAY as u128) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flow_key_display() {
+        assert_eq!(
+            "src_port=33000 dest_port=33434 checksum=0x0bef ip_id=7",
+            FlowKey::udp(33000, 33434, 0xbef).with_ip_id(7).to_string()
+        );
+        assert_eq!(
+            "icmp_id=4660 checksum=0xbeef",
+            FlowKey::icmp(0x1234, 0xbeef).with_ip_id(0).to_string()
+        );
+        assert_eq!(
+            "src_port=33000 dest_port=80",
+            FlowKey::tcp(33000, 80).to_string()
+        );
+    }
+
+    #[test]
+    fn test_send_failure() {
+        let addr = "192.0.2.1:33434".parse().unwrap();
+        let err = io::Error::from_raw_os_error(105);
+        let failure = SendFailure::new(&TracerError::ProbeSendFailed(addr, err));
+        assert_eq!(NonZeroI32::new(105), failure.os_error);
+        assert_eq!(
+            io::Error::from_raw_os_error(105).to_string(),
+            failure.to_string()
+        );
+        let failure = SendFailure::new(&TracerError::InsufficientCapacity);
+        assert_eq!("unknown error", failure.to_string());
+    }
+
+    #[test]
+    fn test_checked_duration() {
+        let sent = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+        let probe = Probe::new(Sequence(1), TimeToLive(1), Round(0), sent);
+        assert_eq!(None, probe.checked_duration());
+        let answered = probe.with_received(sent + Duration::from_millis(20));
+        assert_eq!(Some(Duration::from_millis(20)), answered.checked_duration());
+        let set_back = probe.with_received(sent - Duration::from_millis(20));
+        assert_eq!(None, set_back.checked_duration());
+        assert_eq!(Duration::ZERO, set_back.duration());
+    }
+
+    #[test]
+    fn test_monotonic_duration() {
+        let sent = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+        let sent_at = Instant::now();
+        let probe = Probe::new(Sequence(1), TimeToLive(1), Round(0), sent).with_sent_at(sent_at);
+        assert_eq!(None, probe.checked_duration());
+        let set_back = probe
+            .with_received(sent - Duration::from_millis(500))
+            .with_received_at(sent_at + Duration::from_millis(20));
+        assert_eq!(Some(Duration::from_millis(20)), set_back.checked_duration());
+        assert_eq!(Duration::from_millis(20), set_back.duration());
+    }
+
+    #[test]
+    fn test_clock_offset() {
+        let received = SystemTime::UNIX_EPOCH + Duration::from_millis(1_000_040);
+        let timestamps = IcmpTimestamps::new(1_000_000, 1_000_520, 1_000_530);
+        assert_eq!(Some(505), timestamps.clock_offset_ms(received));
+    }
+
+    #[test]
+    fn test_clock_offset_across_midnight() {
+        let received = SystemTime::UNIX_EPOCH + Duration::from_millis(MILLIS_PER_DAY as u64 + 10);
+        let timestamps = IcmpTimestamps::new(86_399_990, 86_399_995, 86_399_996);
+        assert_eq!(Some(-4), timestamps.clock_offset_ms(received));
+    }
+
+    #[test]
+    fn test_return_hops() {
+        assert_eq!(Some(1), return_hops(64));
+        assert_eq!(Some(9), return_hops(56));
+        assert_eq!(Some(1), return_hops(128));
+        assert_eq!(Some(14), return_hops(115));
+        assert_eq!(Some(1), return_hops(255));
+        assert_eq!(Some(6), return_hops(250));
+    }
+
+    #[test]
+    fn test_return_hops_in_doubt() {
+        assert_eq!(Some(32), return_hops(33));
+        assert_eq!(None, return_hops(32));
+        assert_eq!(None, return_hops(0));
+        assert_eq!(None, return_hops(80));
+        assert_eq!(Some(32), return_hops(97));
+        assert_eq!(None, return_hops(96));
+        assert_eq!(None, return_hops(200));
+    }
+
+    #[test]
+    fn test_asymmetry() {
+        let probe = Probe::new(Sequence(0), TimeToLive(5), Round(0), SystemTime::UNIX_EPOCH);
+        assert_eq!(None, probe.asymmetry());
+        assert_eq!(Some(0), probe.with_recv_ttl(Some(60)).asymmetry());
+        assert_eq!(Some(3), probe.with_recv_ttl(Some(121)).asymmetry());
+        assert_eq!(Some(-2), probe.with_recv_ttl(Some(253)).asymmetry());
+        assert_eq!(None, probe.with_recv_ttl(Some(180)).asymmetry());
+    }
+
+    #[test]
+    fn test_non_standard_timestamps() {
+        let timestamps = IcmpTimestamps::new(0, 1 << 31, 1 << 31);
+        assert_eq!(None, timestamps.clock_offset_ms(SystemTime::UNIX_EPOCH));
+    }
+}
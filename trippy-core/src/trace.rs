@@ -0,0 +1,2178 @@
+use crate::icmp::Unreachable;
+use crate::net::ChannelMetrics;
+use crate::{
+    CompletionReason, FlowKey, IcmpPacketType, IcmpQuote, Probe, ProbeStatus, ResponderPorts,
+    SendFailure, TimeToLive, TracerRound,
+};
+use itertools::Itertools;
+use parking_lot::RwLock;
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+mod stability;
+
+pub use stability::{HopDiff, PathDiff, PathStability};
+
+/// The maximum number of hops we allow.
+///
+/// The IP `ttl` is a u8 (0..255) but since a `ttl` of zero isn't useful we only allow 255 distinct hops.
+pub const MAX_HOPS: usize = u8::MAX as usize;
+
+/// The most flows recorded for a trace, the rounds of any further flows are only recorded in the merged trace.
+pub const MAX_FLOWS: usize = 32;
+
+/// The consecutive rounds in which the target must respond at a lower ttl before the path is shortened.
+///
+/// A target which alternates between paths of different lengths, such as an anycast target, keeps the longer.
+pub const SHORTER_PATH_ROUNDS: usize = 3;
+
+/// The most recent flow keys recorded for a trace.
+pub const MAX_FLOW_KEYS: usize = 8;
+
+/// The state of all hops in a trace.
+///
+/// With the `serde` feature a trace may be serialized, such as to be resumed in a later session, less the error with
+/// which tracing stopped.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Trace {
+    max_samples: usize,
+    lowest_ttl: u8,
+    highest_ttl: u8,
+    highest_ttl_for_round: u8,
+    /// The ttl of the last hop of the current path, which is lower than `highest_ttl` once the target has responded
+    /// at a lower ttl for `SHORTER_PATH_ROUNDS` consecutive rounds, the deeper hops being kept as historic hops.
+    #[cfg_attr(feature = "serde", serde(default))]
+    path_ttl: u8,
+    /// The consecutive rounds in which the target responded at a lower ttl than `path_ttl`, and the highest such ttl.
+    #[cfg_attr(feature = "serde", serde(default))]
+    shorter_path: (usize, u8),
+    /// The consecutive rounds, up to and including the latest, in which the ttl of the last hop of the path was
+    /// unchanged.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    path_ttl_rounds: usize,
+    /// The round at which the path converged, if it has.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    converged: Option<usize>,
+    /// The latest change of the length of the path to the target.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    path_change: Option<PathChange>,
+    /// The stability of the path to the target over the latest `max_samples` rounds.
+    #[cfg_attr(feature = "serde", serde(default))]
+    path_stability: PathStability,
+    round: Option<usize>,
+    /// The number added to the round of each probe, such that a resumed trace numbers its rounds on from the last.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    round_offset: usize,
+    in_flight: usize,
+    /// The adaptive in-flight window of the latest round, if it is adaptive.
+    #[cfg_attr(feature = "serde", serde(default))]
+    inflight_window: Option<u8>,
+    /// When the latest round started, after any jitter of its start.
+    #[cfg_attr(feature = "serde", serde(default))]
+    round_started: Option<SystemTime>,
+    /// The number of responses discarded as their round trip time was impossible, as the wall clock was adjusted
+    /// whilst the probe was in flight.
+    #[cfg_attr(feature = "serde", serde(default))]
+    clock_anomalies: usize,
+    /// The number of probes discarded as their ttl was not that of any hop, being outside `1..=MAX_HOPS`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    invalid_ttls: usize,
+    /// The counts of the packets received by the channel as of the latest round, if the channel counts them.
+    ///
+    /// These are of the channel of this run and so are not saved with the trace.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    channel_metrics: Option<ChannelMetrics>,
+    /// The flow key of the first probe of each of the latest `MAX_FLOW_KEYS` rounds, by round, if the channel records
+    /// them.
+    ///
+    /// These are of the channel of this run and so are not saved with the trace.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    flow_keys: VecDeque<(usize, FlowKey)>,
+    #[cfg_attr(feature = "serde", serde(with = "serialize::elapsed"))]
+    started: Instant,
+    #[cfg_attr(feature = "serde", serde(with = "serialize::hops"))]
+    hops: Vec<Hop>,
+    #[cfg_attr(feature = "serde", serde(with = "serialize::entries"))]
+    links: HashMap<HopLink, usize>,
+    flows: Vec<Flow>,
+    /// The rounds of each address of the target, if its addresses are probed round robin.
+    #[cfg_attr(feature = "serde", serde(default))]
+    endpoints: Vec<Endpoint>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    error: Option<String>,
+}
+
+impl Trace {
+    #[must_use]
+    pub fn new(max_samples: usize) -> Self {
+        Self {
+            max_samples,
+            lowest_ttl: 0,
+            highest_ttl: 0,
+            highest_ttl_for_round: 0,
+            path_ttl: 0,
+            shorter_path: (0, 0),
+            path_ttl_rounds: 0,
+            converged: None,
+            path_change: None,
+            path_stability: PathStability::default(),
+            round: None,
+            round_offset: 0,
+            in_flight: 0,
+            inflight_window: None,
+            round_started: None,
+            clock_anomalies: 0,
+            invalid_ttls: 0,
+            channel_metrics: None,
+            flow_keys: VecDeque::new(),
+            started: Instant::now(),
+            hops: (0..MAX_HOPS).map(|_| Hop::default()).collect(),
+            links: HashMap::new(),
+            flows: vec![],
+            endpoints: vec![],
+            error: None,
+        }
+    }
+
+    /// The current round of tracing.
+    #[must_use]
+    pub fn round(&self) -> Option<usize> {
+        self.round
+    }
+
+    /// The number of rounds traced before the trace was resumed, zero if it was not.
+    #[must_use]
+    pub fn resumed_rounds(&self) -> usize {
+        self.round_offset
+    }
+
+    /// The number of probes of the latest round which were awaiting a response when the round was published.
+    #[must_use]
+    pub fn in_flight(&self) -> usize {
+        self.in_flight
+    }
+
+    /// The number of probes which could be in flight at once in the latest round, if the window is adaptive.
+    #[must_use]
+    pub fn inflight_window(&self) -> Option<u8> {
+        self.inflight_window
+    }
+
+    /// When the latest round started, after any jitter of its start, if known.
+    #[must_use]
+    pub fn round_started(&self) -> Option<SystemTime> {
+        self.round_started
+    }
+
+    /// The number of responses whose round trip time was discarded as impossible, being negative or longer than the
+    /// round in which the probe was sent, as the wall clock was adjusted whilst the probe was in flight.
+    #[must_use]
+    pub fn clock_anomalies(&self) -> usize {
+        self.clock_anomalies
+    }
+
+    /// The number of probes discarded as their ttl was not that of any hop, such as those of a corrupted response.
+    #[must_use]
+    pub fn invalid_ttls(&self) -> usize {
+        self.invalid_ttls
+    }
+
+    /// The counts of the packets received by the channel, by what became of them, as of the latest round.
+    ///
+    /// These are of the trace as a whole, and are not recorded against its flows or the addresses of its target.
+    #[must_use]
+    pub fn channel_metrics(&self) -> Option<ChannelMetrics> {
+        self.channel_metrics
+    }
+
+    /// The flow key of the first probe of the latest round, being the ports, checksum and identifiers the probes of the
+    /// round were sent with, if the channel records it.
+    #[must_use]
+    pub fn flow_key(&self) -> Option<FlowKey> {
+        self.flow_keys.back().map(|(_, flow_key)| *flow_key)
+    }
+
+    /// The flow keys of the latest `MAX_FLOW_KEYS` rounds, oldest first, along with the round of each.
+    pub fn flow_keys(&self) -> impl Iterator<Item = (usize, FlowKey)> + '_ {
+        self.flow_keys.iter().copied()
+    }
+
+    /// The time elapsed since the trace was started, or since its statistics were last reset.
+    #[must_use]
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Information about each hop of the current path of the trace.
+    #[must_use]
+    pub fn hops(&self) -> &[Hop] {
+        self.hops_to(self.path_ttl())
+    }
+
+    /// Information about each hop in the trace, including the historic hops beyond the current path.
+    #[must_use]
+    pub fn all_hops(&self) -> &[Hop] {
+        self.hops_to(self.highest_ttl)
+    }
+
+    /// Information about the hops beyond the current path, which were of a longer path to the target.
+    #[must_use]
+    pub fn historic_hops(&self) -> &[Hop] {
+        let all_hops = self.all_hops();
+        &all_hops[self.hops().len()..]
+    }
+
+    fn hops_to(&self, ttl: u8) -> &[Hop] {
+        if self.lowest_ttl == 0 || ttl < self.lowest_ttl {
+            &[]
+        } else {
+            let start = (self.lowest_ttl as usize) - 1;
+            let end = ttl as usize;
+            &self.hops[start..end]
+        }
+    }
+
+    /// The ttl of the last hop of the current path.
+    ///
+    /// This is the highest `ttl` observed, unless the target has since responded at a lower `ttl` for
+    /// `SHORTER_PATH_ROUNDS` consecutive rounds.
+    fn path_ttl(&self) -> u8 {
+        if self.path_ttl == 0 {
+            self.highest_ttl
+        } else {
+            self.path_ttl
+        }
+    }
+
+    /// The latest change of the length of the path to the target, if it has changed.
+    #[must_use]
+    pub fn path_change(&self) -> Option<PathChange> {
+        self.path_change
+    }
+
+    /// The stability of the path to the target over the latest rounds, and the changes of path observed.
+    #[must_use]
+    pub fn path_stability(&self) -> &PathStability {
+        &self.path_stability
+    }
+
+    /// The round at which the path converged, as judged by the `Convergence` with which the trace was last updated,
+    /// if it has.
+    ///
+    /// A path which has converged remains so, such that the round is that at which it first converged.
+    #[must_use]
+    pub fn converged(&self) -> Option<usize> {
+        self.converged
+    }
+
+    /// Is a given `Hop` the target hop?
+    ///
+    /// A `Hop` is considered to be the target if it is the last hop of the current path.
+    ///
+    /// Note that if the target host does not respond to probes then the the highest `ttl` observed will be one greater
+    /// than the `ttl` of the last host which did respond.
+    #[must_use]
+    pub fn is_target(&self, hop: &Hop) -> bool {
+        self.path_ttl() == hop.ttl
+    }
+
+    /// Is a given `Hop` in the current round?
+    #[must_use]
+    pub fn is_in_round(&self, hop: &Hop) -> bool {
+        hop.ttl <= self.highest_ttl_for_round
+    }
+
+    /// Return the target `Hop`.
+    ///
+    /// TODO Do we guarantee there is always a target hop?
+    #[must_use]
+    pub fn target_hop(&self) -> &Hop {
+        let path_ttl = self.path_ttl();
+        if path_ttl > 0 {
+            &self.hops[usize::from(path_ttl) - 1]
+        } else {
+            &self.hops[0]
+        }
+    }
+
+    /// The links observed between the hosts of consecutive hops along with the number of rounds in which each was seen.
+    pub fn links(&self) -> impl Iterator<Item = (&HopLink, &usize)> {
+        self.links.iter()
+    }
+
+    /// The flows observed, in the order in which they were first observed.
+    #[must_use]
+    pub fn flows(&self) -> &[Flow] {
+        &self.flows
+    }
+
+    /// The rounds of each address of the target, in the order in which they were first probed, if its addresses are
+    /// probed round robin.
+    #[must_use]
+    pub fn endpoints(&self) -> &[Endpoint] {
+        &self.endpoints
+    }
+
+    /// The error with which tracing stopped, if it has.
+    #[must_use]
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Record that tracing stopped with `error`.
+    pub fn set_error(&mut self, error: String) {
+        self.error = Some(error);
+    }
+
+    /// Continue the trace, such as one restored from a previous session, keeping at most `max_samples` samples of each
+    /// hop.
+    ///
+    /// The rounds traced from now on are numbered on from the last round of the trace, as the rounds of a new tracer
+    /// are numbered from zero.
+    pub fn resume(&mut self, max_samples: usize) {
+        let round_offset = self.round.map_or(0, |round| round + 1);
+        self.resume_from(max_samples, round_offset);
+    }
+
+    fn resume_from(&mut self, max_samples: usize, round_offset: usize) {
+        self.max_samples = max_samples;
+        self.round_offset = round_offset;
+        self.error = None;
+        self.truncate_samples(max_samples);
+        for flow in &mut self.flows {
+            flow.trace.resume_from(max_samples, round_offset);
+        }
+        for endpoint in &mut self.endpoints {
+            endpoint.trace.resume_from(max_samples, round_offset);
+        }
+    }
+
+    /// Flag the hops of the current path which are likely rate limiting the ICMP responses they generate, as judged by
+    /// `heuristic`, and likewise the hops of each flow and of each address of the target.
+    pub fn update_rate_limited(&mut self, heuristic: &RateLimitHeuristic) {
+        let rate_limited = heuristic.classify(self.hops());
+        let start = usize::from(self.lowest_ttl.max(1)) - 1;
+        for hop in &mut self.hops {
+            hop.rate_limited_likely = false;
+        }
+        for (hop, rate_limited) in self.hops[start..].iter_mut().zip(rate_limited) {
+            hop.rate_limited_likely = rate_limited;
+        }
+        for flow in &mut self.flows {
+            flow.trace.update_rate_limited(heuristic);
+        }
+        for endpoint in &mut self.endpoints {
+            endpoint.trace.update_rate_limited(heuristic);
+        }
+    }
+
+    /// Record the round at which the path converged, as judged by `convergence`, should it have done so in the
+    /// latest round.
+    pub fn update_converged(&mut self, convergence: &Convergence) {
+        if self.converged.is_none() && convergence.is_converged(self.path_ttl_rounds, self.hops()) {
+            self.converged = self.round;
+        }
+    }
+
+    /// Keep only the latest `len` samples of each hop, such as to limit the size of a serialized trace.
+    pub fn truncate_samples(&mut self, len: usize) {
+        for hop in &mut self.hops {
+            hop.samples.truncate(len);
+            hop.recent_unreachable.truncate(len);
+        }
+        for flow in &mut self.flows {
+            flow.trace.truncate_samples(len);
+        }
+        for endpoint in &mut self.endpoints {
+            endpoint.trace.truncate_samples(len);
+        }
+    }
+
+    /// Update the tracing state from a `TracerRound`.
+    pub fn update_from_round(&mut self, round: &TracerRound<'_>) {
+        if round.channel_metrics.is_some() {
+            self.channel_metrics = round.channel_metrics;
+        }
+        self.update_hops(round);
+        if let Some(flow_key) = round.flow_key {
+            if self.flow_keys.len() == MAX_FLOW_KEYS {
+                self.flow_keys.pop_front();
+            }
+            self.flow_keys
+                .push_back((self.round.unwrap_or_default(), flow_key));
+        }
+        self.path_stability
+            .update(round, self.round.unwrap_or_default(), self.max_samples);
+        self.update_flows(round);
+        self.update_endpoints(round);
+    }
+
+    /// Record the round against the address of the target which it probed, if the target is probed round robin.
+    fn update_endpoints(&mut self, round: &TracerRound<'_>) {
+        if let Some(addr) = round.target() {
+            if let Some(endpoint) = self
+                .endpoints
+                .iter_mut()
+                .find(|endpoint| endpoint.addr == addr)
+            {
+                endpoint.update_from_round(round);
+            } else {
+                let mut endpoint = Endpoint {
+                    addr,
+                    rounds: 0,
+                    trace: Self {
+                        round_offset: self.round_offset,
+                        ..Self::new(self.max_samples)
+                    },
+                };
+                endpoint.update_from_round(round);
+                self.endpoints.push(endpoint);
+            }
+        }
+    }
+
+    /// Record the round against the flow whose path it follows, starting a new flow if it follows none.
+    fn update_flows(&mut self, round: &TracerRound<'_>) {
+        let path = round.path();
+        if let Some(flow) = self.flows.iter_mut().find(|flow| flow.follows(&path)) {
+            flow.update_from_round(round, &path);
+        } else if self.flows.len() < MAX_FLOWS {
+            let mut flow = Flow {
+                id: self.flows.len() + 1,
+                path: vec![],
+                rounds: 0,
+                trace: Self {
+                    round_offset: self.round_offset,
+                    ..Self::new(self.max_samples)
+                },
+            };
+            flow.update_from_round(round, &path);
+            self.flows.push(flow);
+        }
+    }
+
+    /// Update the hops from a `TracerRound`.
+    fn update_hops(&mut self, round: &TracerRound<'_>) {
+        self.highest_ttl = std::cmp::max(self.highest_ttl, round.largest_ttl.0);
+        self.highest_ttl_for_round = round.largest_ttl.0;
+        self.update_path(round);
+        self.in_flight = round
+            .probes
+            .iter()
+            .filter(|probe| probe.status == ProbeStatus::Awaited)
+            .count();
+        self.inflight_window = round.inflight_window.map(|window| window.0);
+        self.round_started = round.started;
+        // The probes beyond the target of a round in which it responded, those sent before it did or to notice should
+        // the path grow, are of no hop and so are not counted, lest they accumulate as phantom hops beyond the target.
+        let target_found = matches!(round.reason, CompletionReason::TargetFound);
+        for probe in round.probes {
+            if probe.status != ProbeStatus::NotSent && hop_index(probe.ttl).is_none() {
+                self.invalid_ttls += 1;
+            } else if target_found && probe.ttl > round.largest_ttl {
+                self.update_round(probe);
+            } else if is_clock_anomaly(probe, round) {
+                self.update_lowest_ttl(probe);
+                self.update_round(probe);
+                self.clock_anomalies += 1;
+            } else {
+                self.update_from_probe(probe);
+            }
+        }
+        self.update_links(round);
+    }
+
+    /// Update the length of the current path from a `TracerRound`.
+    ///
+    /// The path is lengthened as soon as a round reaches beyond it but is only shortened once the target has responded
+    /// at a lower ttl for `SHORTER_PATH_ROUNDS` consecutive rounds, to the highest ttl at which it did so.  A round in
+    /// which the target did not respond is not evidence of a shorter path and so neither shortens the path nor
+    /// interrupts the consecutive rounds.
+    fn update_path(&mut self, round: &TracerRound<'_>) {
+        let previous_ttl = self.path_ttl();
+        self.update_path_ttl(round);
+        self.path_ttl_rounds = if self.path_ttl() == previous_ttl {
+            self.path_ttl_rounds + 1
+        } else {
+            1
+        };
+    }
+
+    fn update_path_ttl(&mut self, round: &TracerRound<'_>) {
+        let target_found = matches!(round.reason, CompletionReason::TargetFound);
+        let ttl = round.largest_ttl.0;
+        if ttl >= self.path_ttl {
+            self.set_path_ttl(ttl, target_found);
+            self.shorter_path = (0, 0);
+        } else if target_found {
+            let (rounds, shorter_ttl) = self.shorter_path;
+            self.shorter_path = (rounds + 1, shorter_ttl.max(ttl));
+            if self.shorter_path.0 >= SHORTER_PATH_ROUNDS {
+                self.set_path_ttl(self.shorter_path.1, true);
+                self.shorter_path = (0, 0);
+            }
+        }
+    }
+
+    /// Set the ttl of the last hop of the path, recording the change of its length if `record`, i.e. if the change is
+    /// to the ttl at which the target responded.
+    fn set_path_ttl(&mut self, ttl: u8, record: bool) {
+        if record && self.path_ttl != 0 && self.path_ttl != ttl {
+            self.path_change = Some(PathChange {
+                from: self.path_ttl,
+                to: ttl,
+                at: Instant::now(),
+            });
+        }
+        self.path_ttl = ttl;
+    }
+
+    /// Record the links between the hosts of consecutive hops in the round.
+    fn update_links(&mut self, round: &TracerRound<'_>) {
+        let path = round
+            .probes
+            .iter()
+            .filter(|probe| probe.ttl <= round.largest_ttl)
+            .filter_map(|probe| match probe.status {
+                ProbeStatus::Complete => Some((probe.ttl.0, probe.host)),
+                ProbeStatus::Awaited => Some((probe.ttl.0, None)),
+                ProbeStatus::NotSent | ProbeStatus::Failed(_) => None,
+            });
+        for ((ttl, from), (next_ttl, to)) in path.tuple_windows() {
+            if next_ttl == ttl + 1 {
+                *self.links.entry(HopLink { ttl, from, to }).or_default() += 1;
+            }
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn update_from_probe(&mut self, probe: &Probe) {
+        let index = hop_index(probe.ttl);
+        debug_assert!(
+            index.is_some() || probe.status == ProbeStatus::NotSent,
+            "probe of ttl {} is of no hop",
+            probe.ttl.0
+        );
+        let Some(index) = index else {
+            return;
+        };
+        self.update_lowest_ttl(probe);
+        self.update_round(probe);
+        match probe.status {
+            ProbeStatus::Complete => {
+                let hop = &mut self.hops[index];
+                hop.ttl = probe.ttl.0;
+                hop.total_sent += 1;
+                hop.total_recv += 1;
+                let dur = probe.duration();
+                let dur_ms = dur.as_secs_f64() * 1000_f64;
+                hop.total_time += dur;
+                if let Some(last) = hop.last {
+                    hop.total_jitter += dur.saturating_sub(last) + last.saturating_sub(dur);
+                }
+                hop.last = Some(dur);
+                hop.last_icmp_packet_type = probe.icmp_packet_type;
+                hop.last_sent_time = hop.last_sent_time.max(probe.sent);
+                hop.last_recv_time = hop.last_recv_time.max(probe.received);
+                if let (Some(timestamps), Some(received)) = (probe.timestamps, probe.received) {
+                    hop.clock_offset_ms =
+                        timestamps.clock_offset_ms(received).or(hop.clock_offset_ms);
+                }
+                hop.asymmetry = probe.asymmetry().or(hop.asymmetry);
+                hop.samples.insert(0, dur);
+                let unreachable = match (probe.icmp_packet_type, probe.host) {
+                    (Some(IcmpPacketType::Unreachable(code)), Some(host)) => {
+                        Some(Unreachable::new(host, code, probe.next_hop_mtu))
+                    }
+                    _ => None,
+                };
+                if let Some(unreachable) = unreachable {
+                    *hop.unreachable.entry(unreachable).or_default() += 1;
+                }
+                if let Some(ports) = probe.responder_ports {
+                    *hop.responder_ports.entry(ports).or_default() += 1;
+                }
+                hop.recent_unreachable.insert(0, unreachable);
+                hop.best = hop.best.map_or(Some(dur), |d| Some(d.min(dur)));
+                hop.worst = hop.worst.map_or(Some(dur), |d| Some(d.max(dur)));
+                hop.mean += (dur_ms - hop.mean) / hop.total_recv as f64;
+                hop.m2 += (dur_ms - hop.mean) * (dur_ms - hop.mean);
+                if hop.samples.len() > self.max_samples {
+                    hop.samples.pop();
+                    hop.recent_unreachable.pop();
+                }
+                let host = probe.host.unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+                let addr = hop.addrs.entry(host).or_default();
+                addr.count += 1;
+                addr.total_time += dur;
+                addr.last = Some(dur);
+                addr.best = addr.best.map_or(Some(dur), |d| Some(d.min(dur)));
+                addr.worst = addr.worst.map_or(Some(dur), |d| Some(d.max(dur)));
+                addr.quote = probe.quote.or(addr.quote);
+            }
+            ProbeStatus::Awaited => {
+                self.hops[index].total_sent += 1;
+                self.hops[index].ttl = probe.ttl.0;
+                self.hops[index].last_sent_time = self.hops[index].last_sent_time.max(probe.sent);
+                self.hops[index].samples.insert(0, Duration::default());
+                self.hops[index].recent_unreachable.insert(0, None);
+                if self.hops[index].samples.len() > self.max_samples {
+                    self.hops[index].samples.pop();
+                    self.hops[index].recent_unreachable.pop();
+                }
+            }
+            ProbeStatus::Failed(failure) => {
+                self.hops[index].ttl = probe.ttl.0;
+                self.hops[index].total_failed += 1;
+                self.hops[index].last_failure = Some(failure);
+            }
+            ProbeStatus::NotSent => {}
+        }
+    }
+
+    /// Update `lowest_ttl` for valid probes.
+    fn update_lowest_ttl(&mut self, probe: &Probe) {
+        if probe.status != ProbeStatus::NotSent {
+            if self.lowest_ttl == 0 {
+                self.lowest_ttl = probe.ttl.0;
+            } else {
+                self.lowest_ttl = self.lowest_ttl.min(probe.ttl.0);
+            }
+        }
+    }
+
+    /// Update `round` for valid probes.
+    fn update_round(&mut self, probe: &Probe) {
+        if probe.status != ProbeStatus::NotSent {
+            let round = probe.round.0 + self.round_offset;
+            self.round = match self.round {
+                None => Some(round),
+                Some(r) => Some(r.max(round)),
+            }
+        }
+    }
+}
+
+/// The index within the hops of a trace of the hop of `ttl`, if there is one, `ttl` being within `1..=MAX_HOPS`.
+fn hop_index(ttl: TimeToLive) -> Option<usize> {
+    usize::from(ttl.0)
+        .checked_sub(1)
+        .filter(|index| *index < MAX_HOPS)
+}
+
+/// Is the round trip time of a response to `probe` impossible, being negative or longer than the `round` in which it
+/// was sent, such that the wall clock by which it was timed must have been adjusted whilst it was in flight?
+///
+/// Only a probe not timed by the monotonic clock, such as one restored from a saved trace, is timed by the wall clock.
+fn is_clock_anomaly(probe: &Probe, round: &TracerRound<'_>) -> bool {
+    probe.status == ProbeStatus::Complete
+        && match (probe.checked_duration(), round.duration) {
+            (None, _) => true,
+            (Some(dur), Some(round_duration)) => dur > round_duration,
+            (Some(_), None) => false,
+        }
+}
+
+/// The thresholds of the heuristic by which a hop is judged likely to be rate limiting the ICMP responses it generates.
+///
+/// A router which rate limits the responses it generates shows loss whilst the probes which pass through it, to the
+/// deeper hops, are not lost.  A hop is therefore judged to be rate limiting if its response rate over the last N
+/// samples is much lower than the best response rate of any deeper hop, and that deeper hop is healthy.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RateLimitHeuristic {
+    /// How many percentage points the response rate of the hop must be below the best response rate of a deeper hop.
+    pub gap_pct: f64,
+    /// The lowest response rate, as a percentage, of the best deeper hop for the path beyond the hop to be healthy.
+    pub healthy_pct: f64,
+    /// The fewest samples of a hop for its response rate to be judged.
+    pub min_samples: usize,
+}
+
+impl Default for RateLimitHeuristic {
+    fn default() -> Self {
+        Self {
+            gap_pct: 20_f64,
+            healthy_pct: 90_f64,
+            min_samples: 10,
+        }
+    }
+}
+
+impl RateLimitHeuristic {
+    /// Whether each of `hops`, those of a path in order of ttl, is likely rate limiting the responses it generates.
+    ///
+    /// A hop which has not responded at all over the last N samples is not judged rate limiting, as it is more likely
+    /// not to respond at all.
+    #[must_use]
+    pub fn classify(&self, hops: &[Hop]) -> Vec<bool> {
+        let mut rate_limited = vec![false; hops.len()];
+        let mut best_deeper: Option<f64> = None;
+        for (index, hop) in hops.iter().enumerate().rev() {
+            let Some(response_pct) = self.response_pct(hop) else {
+                continue;
+            };
+            if let Some(best_deeper) = best_deeper {
+                rate_limited[index] = response_pct > 0_f64
+                    && best_deeper >= self.healthy_pct
+                    && best_deeper - response_pct > self.gap_pct;
+            }
+            best_deeper = Some(best_deeper.map_or(response_pct, |best| best.max(response_pct)));
+        }
+        rate_limited
+    }
+
+    /// The percentage of the last N samples of `hop` which were responded to, if there are enough to judge.
+    #[allow(clippy::cast_precision_loss)]
+    fn response_pct(&self, hop: &Hop) -> Option<f64> {
+        let samples = hop.samples();
+        (!samples.is_empty() && samples.len() >= self.min_samples).then(|| {
+            let responded = samples
+                .iter()
+                .filter(|sample| **sample != Duration::ZERO)
+                .count();
+            responded as f64 / samples.len() as f64 * 100_f64
+        })
+    }
+}
+
+/// The thresholds by which the path of a trace is judged to have converged, such that further rounds are unlikely to
+/// reveal more of it.
+///
+/// The path has converged once the ttl of the target has been unchanged for `rounds` consecutive rounds and every hop
+/// of the path has either responded at least `samples` times or not responded to any of at least `samples` probes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Convergence {
+    /// The fewest consecutive rounds in which the ttl of the target must be unchanged.
+    pub rounds: usize,
+    /// The fewest responses of each hop, or probes of each hop which has never responded.
+    pub samples: usize,
+}
+
+impl Default for Convergence {
+    fn default() -> Self {
+        Self {
+            rounds: 5,
+            samples: 5,
+        }
+    }
+}
+
+impl Convergence {
+    /// Has a path whose target ttl has been unchanged for `rounds` consecutive rounds, and whose hops are `hops`,
+    /// converged?
+    #[must_use]
+    pub fn is_converged(&self, rounds: usize, hops: &[Hop]) -> bool {
+        rounds >= self.rounds
+            && !hops.is_empty()
+            && hops.iter().all(|hop| {
+                hop.total_recv() >= self.samples
+                    || (hop.total_recv() == 0 && hop.total_sent() >= self.samples)
+            })
+    }
+}
+
+/// A change of the length of the path to the target, such as when an anycast target is reached by a shorter path.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PathChange {
+    from: u8,
+    to: u8,
+    at: Instant,
+}
+
+impl PathChange {
+    /// The ttl of the last hop of the path before the change.
+    #[must_use]
+    pub fn from(&self) -> u8 {
+        self.from
+    }
+
+    /// The ttl of the last hop of the path after the change.
+    #[must_use]
+    pub fn to(&self) -> u8 {
+        self.to
+    }
+
+    /// The time elapsed since the change.
+    #[must_use]
+    pub fn elapsed(&self) -> Duration {
+        self.at.elapsed()
+    }
+}
+
+/// The rounds of a trace which followed the same path.
+///
+/// A round follows the path of a flow if every hop which responded in both did so from the same host, such that a
+/// hop which did not respond in a round does not start a new flow.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Flow {
+    id: usize,
+    path: Vec<Option<IpAddr>>,
+    rounds: usize,
+    trace: Trace,
+}
+
+impl Flow {
+    /// The number of the flow, from 1 in the order in which the flows were first observed.
+    #[must_use]
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// The host which responded at each ttl, from 1, in any round of the flow.
+    #[must_use]
+    pub fn path(&self) -> &[Option<IpAddr>] {
+        &self.path
+    }
+
+    /// The number of rounds which followed the flow.
+    #[must_use]
+    pub fn rounds(&self) -> usize {
+        self.rounds
+    }
+
+    /// The state of the hops of the rounds of the flow.
+    #[must_use]
+    pub fn trace(&self) -> &Trace {
+        &self.trace
+    }
+
+    /// The lowest ttl at which the path of the flow differs from the path of `other`, if any.
+    #[must_use]
+    pub fn diverges_from(&self, other: &Self) -> Option<u8> {
+        self.path
+            .iter()
+            .zip(&other.path)
+            .position(|(host, other)| host.is_some() && other.is_some() && host != other)
+            .map(|index| index as u8 + 1)
+    }
+
+    fn follows(&self, path: &[Option<IpAddr>]) -> bool {
+        self.path
+            .iter()
+            .zip(path)
+            .all(|(host, other)| host.is_none() || other.is_none() || host == other)
+    }
+
+    fn update_from_round(&mut self, round: &TracerRound<'_>, path: &[Option<IpAddr>]) {
+        if self.path.len() < path.len() {
+            self.path.resize(path.len(), None);
+        }
+        for (host, other) in self.path.iter_mut().zip(path) {
+            if host.is_none() {
+                *host = *other;
+            }
+        }
+        self.rounds += 1;
+        self.trace.update_hops(round);
+    }
+}
+
+/// The rounds of a trace which probed one of the addresses of its target, if its addresses are probed round robin.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Endpoint {
+    addr: IpAddr,
+    rounds: usize,
+    trace: Trace,
+}
+
+impl Endpoint {
+    /// The address of the target probed.
+    #[must_use]
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    /// The number of rounds which probed the address.
+    #[must_use]
+    pub fn rounds(&self) -> usize {
+        self.rounds
+    }
+
+    /// The state of the hops of the rounds which probed the address.
+    #[must_use]
+    pub fn trace(&self) -> &Trace {
+        &self.trace
+    }
+
+    fn update_from_round(&mut self, round: &TracerRound<'_>) {
+        self.rounds += 1;
+        self.trace.update_hops(round);
+    }
+}
+
+/// Information about a single `Hop` within a `Trace`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Hop {
+    ttl: u8,
+    addrs: HashMap<IpAddr, HopAddr>,
+    total_sent: usize,
+    total_recv: usize,
+    total_time: Duration,
+    total_jitter: Duration,
+    last: Option<Duration>,
+    last_icmp_packet_type: Option<IcmpPacketType>,
+    best: Option<Duration>,
+    worst: Option<Duration>,
+    mean: f64,
+    m2: f64,
+    samples: Vec<Duration>,
+    /// The destination unreachable response, if any, to the probe of each of the `samples`.
+    recent_unreachable: Vec<Option<Unreachable>>,
+    /// The number of each destination unreachable response received.
+    #[cfg_attr(feature = "serde", serde(with = "serialize::entries"))]
+    unreachable: HashMap<Unreachable, usize>,
+    /// The number of each pair of the port probed and the port from which the target replied at the transport layer.
+    #[cfg_attr(feature = "serde", serde(default, with = "serialize::entries"))]
+    responder_ports: HashMap<ResponderPorts, usize>,
+    /// The offset of the clock of the host from ours, in milliseconds, as of its last ICMP timestamp reply.
+    #[cfg_attr(feature = "serde", serde(default))]
+    clock_offset_ms: Option<i64>,
+    /// The hops by which the return path from the host is longer than the forward path, as of its last response from
+    /// which this could be inferred.
+    #[cfg_attr(feature = "serde", serde(default))]
+    asymmetry: Option<i16>,
+    /// Whether the hop is likely rate limiting the ICMP responses it generates, as of the latest round.
+    #[cfg_attr(feature = "serde", serde(default))]
+    rate_limited_likely: bool,
+    /// The time at which the last probe of the hop was sent, answered or not.
+    #[cfg_attr(feature = "serde", serde(default))]
+    last_sent_time: Option<SystemTime>,
+    /// The time at which the last response of the hop was received.
+    #[cfg_attr(feature = "serde", serde(default))]
+    last_recv_time: Option<SystemTime>,
+    /// The number of probes of the hop which could not be sent, which are not counted as sent.
+    #[cfg_attr(feature = "serde", serde(default))]
+    total_failed: usize,
+    /// Why the last probe of the hop which could not be sent failed.
+    #[cfg_attr(feature = "serde", serde(default))]
+    last_failure: Option<SendFailure>,
+}
+
+impl Hop {
+    /// The time-to-live of this hop.
+    #[must_use]
+    pub fn ttl(&self) -> u8 {
+        self.ttl
+    }
+
+    /// The set of addresses that have responded for this time-to-live.
+    pub fn addrs(&self) -> impl Iterator<Item = &IpAddr> {
+        self.addrs.keys()
+    }
+
+    pub fn addrs_with_counts(&self) -> impl Iterator<Item = (&IpAddr, &usize)> {
+        self.addrs.iter().map(|(addr, info)| (addr, &info.count))
+    }
+
+    /// The set of addresses that have responded for this time-to-live along with the per-address details.
+    pub fn addrs_with_details(&self) -> impl Iterator<Item = (&IpAddr, &HopAddr)> {
+        self.addrs.iter()
+    }
+
+    /// The address which has responded most frequently for this time-to-live, if any.
+    #[must_use]
+    pub fn primary_addr(&self) -> Option<IpAddr> {
+        self.addrs
+            .iter()
+            .max_by_key(|(addr, info)| (info.count, **addr))
+            .map(|(addr, _)| *addr)
+    }
+
+    /// The number of unique address observed for this time-to-live.
+    #[must_use]
+    pub fn addr_count(&self) -> usize {
+        self.addrs.len()
+    }
+
+    /// The total number of probes sent.
+    #[must_use]
+    pub fn total_sent(&self) -> usize {
+        self.total_sent
+    }
+
+    /// The total number of probes responses received.
+    #[must_use]
+    pub fn total_recv(&self) -> usize {
+        self.total_recv
+    }
+
+    /// The total number of probes which could not be sent.
+    #[must_use]
+    pub fn total_failed(&self) -> usize {
+        self.total_failed
+    }
+
+    /// Why the last probe which could not be sent failed, if any has.
+    #[must_use]
+    pub fn last_failure(&self) -> Option<SendFailure> {
+        self.last_failure
+    }
+
+    /// The % of packets that are lost.
+    ///
+    /// The probes which could not be sent are not lost, as their failure is local rather than of the path.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn loss_pct(&self) -> f64 {
+        if self.total_sent > 0 {
+            let lost = self.total_sent - self.total_recv;
+            lost as f64 / self.total_sent as f64 * 100f64
+        } else {
+            0_f64
+        }
+    }
+
+    /// The duration of the last probe.
+    #[must_use]
+    pub fn last_ms(&self) -> Option<f64> {
+        self.last.map(|last| last.as_secs_f64() * 1000_f64)
+    }
+
+    /// The type of ICMP packet received in response to the last probe.
+    #[must_use]
+    pub fn last_icmp_packet_type(&self) -> Option<IcmpPacketType> {
+        self.last_icmp_packet_type
+    }
+
+    /// The offset of the clock of the host from ours, in milliseconds, as of its last ICMP timestamp reply, if any.
+    ///
+    /// This is experimental, as it is estimated assuming the delay to the host is the same in both directions.
+    #[must_use]
+    pub fn clock_offset_ms(&self) -> Option<i64> {
+        self.clock_offset_ms
+    }
+
+    /// The number of hops by which the return path from the host is longer than the forward path to it, negative if
+    /// shorter, as of its last response from which this could be inferred.
+    ///
+    /// The return path is inferred from the time-to-live of the response, which is only known for ICMP responses over
+    /// IPv4, assuming the host sent it with one of the common initial values.  A hop of the forward path which does not
+    /// respond is not counted, whereas one of the return path is.
+    #[must_use]
+    pub fn asymmetry(&self) -> Option<i16> {
+        self.asymmetry
+    }
+
+    /// Whether the hop is likely rate limiting the ICMP responses it generates, such that its loss is not loss of the
+    /// path, as judged by a `RateLimitHeuristic`.
+    #[must_use]
+    pub fn rate_limited_likely(&self) -> bool {
+        self.rate_limited_likely
+    }
+
+    /// The time at which the last probe of the hop was sent, whether or not it was answered.
+    #[must_use]
+    pub fn last_sent_time(&self) -> Option<SystemTime> {
+        self.last_sent_time
+    }
+
+    /// The time at which the last response of the hop was received.
+    #[must_use]
+    pub fn last_recv_time(&self) -> Option<SystemTime> {
+        self.last_recv_time
+    }
+
+    /// How long before `now` the last response of the hop was received, zero should the clock have since gone back.
+    #[must_use]
+    pub fn since_last_recv(&self, now: SystemTime) -> Option<Duration> {
+        self.last_recv_time
+            .map(|time| now.duration_since(time).unwrap_or_default())
+    }
+
+    /// The duration of the best probe observed.
+    #[must_use]
+    pub fn best_ms(&self) -> Option<f64> {
+        self.best.map(|last| last.as_secs_f64() * 1000_f64)
+    }
+
+    /// The duration of the worst probe observed.
+    #[must_use]
+    pub fn worst_ms(&self) -> Option<f64> {
+        self.worst.map(|last| last.as_secs_f64() * 1000_f64)
+    }
+
+    /// The average duration of all probes.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn avg_ms(&self) -> f64 {
+        if self.total_recv() > 0 {
+            (self.total_time.as_secs_f64() * 1000_f64) / self.total_recv as f64
+        } else {
+            0_f64
+        }
+    }
+
+    /// The standard deviation of all probes.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn stddev_ms(&self) -> f64 {
+        if self.total_recv > 1 {
+            (self.m2 / (self.total_recv - 1) as f64).sqrt()
+        } else {
+            0_f64
+        }
+    }
+
+    /// The average jitter, the mean difference between the durations of consecutive probes.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn jitter_ms(&self) -> f64 {
+        if self.total_recv > 1 {
+            (self.total_jitter.as_secs_f64() * 1000_f64) / (self.total_recv - 1) as f64
+        } else {
+            0_f64
+        }
+    }
+
+    /// The last N samples.
+    #[must_use]
+    pub fn samples(&self) -> &[Duration] {
+        &self.samples
+    }
+
+    /// The destination unreachable responses received for the probes of the last N samples, with the number of each.
+    #[must_use]
+    pub fn recent_unreachable(&self) -> Vec<(Unreachable, usize)> {
+        self.recent_unreachable
+            .iter()
+            .flatten()
+            .copied()
+            .counts()
+            .into_iter()
+            .sorted()
+            .collect()
+    }
+
+    /// The destination unreachable responses received for all probes, with the number of each.
+    #[must_use]
+    pub fn unreachable(&self) -> Vec<(Unreachable, usize)> {
+        self.unreachable
+            .iter()
+            .map(|(unreachable, count)| (*unreachable, *count))
+            .sorted()
+            .collect()
+    }
+
+    /// The ports probed and the ports from which the target replied at the transport layer, with the number of each.
+    #[must_use]
+    pub fn responder_ports(&self) -> Vec<(ResponderPorts, usize)> {
+        self.responder_ports
+            .iter()
+            .map(|(ports, count)| (*ports, *count))
+            .sorted()
+            .collect()
+    }
+
+    /// Did the target ever reply from a port other than the one probed, as if something answered on its behalf?
+    #[must_use]
+    pub fn port_mismatch(&self) -> bool {
+        self.responder_ports.keys().any(ResponderPorts::is_mismatch)
+    }
+}
+
+/// Information about a single address which responded for a `Hop`.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HopAddr {
+    count: usize,
+    total_time: Duration,
+    last: Option<Duration>,
+    best: Option<Duration>,
+    worst: Option<Duration>,
+    /// The size of the last ICMP error from this address and of the probe quoted in it.
+    #[cfg_attr(feature = "serde", serde(default))]
+    quote: Option<IcmpQuote>,
+}
+
+impl HopAddr {
+    /// The number of probe responses received from this address.
+    #[must_use]
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// The duration of the last probe response from this address.
+    #[must_use]
+    pub fn last_ms(&self) -> Option<f64> {
+        self.last.map(|last| last.as_secs_f64() * 1000_f64)
+    }
+
+    /// The average duration of all probe responses from this address.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn avg_ms(&self) -> f64 {
+        if self.count > 0 {
+            (self.total_time.as_secs_f64() * 1000_f64) / self.count as f64
+        } else {
+            0_f64
+        }
+    }
+
+    /// The duration of the best probe response from this address.
+    #[must_use]
+    pub fn best_ms(&self) -> Option<f64> {
+        self.best.map(|best| best.as_secs_f64() * 1000_f64)
+    }
+
+    /// The duration of the worst probe response from this address.
+    #[must_use]
+    pub fn worst_ms(&self) -> Option<f64> {
+        self.worst.map(|worst| worst.as_secs_f64() * 1000_f64)
+    }
+
+    /// The size of the payload of the last ICMP error from this address and of the probe quoted in it, if any.
+    #[must_use]
+    pub fn quote(&self) -> Option<IcmpQuote> {
+        self.quote
+    }
+}
+
+/// A link between the host which responded at `ttl` and the host which responded at the next `ttl` in a round.
+///
+/// A host of `None` indicates that no response was received for that `ttl`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HopLink {
+    pub ttl: u8,
+    pub from: Option<IpAddr>,
+    pub to: Option<IpAddr>,
+}
+
+impl Default for Hop {
+    fn default() -> Self {
+        Self {
+            ttl: 0,
+            addrs: HashMap::default(),
+            total_sent: 0,
+            total_recv: 0,
+            total_time: Duration::default(),
+            total_jitter: Duration::default(),
+            last: None,
+            last_icmp_packet_type: None,
+            best: None,
+            worst: None,
+            mean: 0f64,
+            m2: 0f64,
+            samples: Vec::default(),
+            recent_unreachable: Vec::default(),
+            unreachable: HashMap::default(),
+            responder_ports: HashMap::default(),
+            clock_offset_ms: None,
+            asymmetry: None,
+            rate_limited_likely: false,
+            last_sent_time: None,
+            last_recv_time: None,
+            total_failed: 0,
+            last_failure: None,
+        }
+    }
+}
+
+/// The data of a trace, shared between the backend which updates it after every round and the readers of it.
+///
+/// The readers are given an immutable snapshot of the latest `Trace` which is cheap to take, and which is not copied
+/// unless it is still held when the backend next updates it, such that a frame of the TUI neither copies the trace nor
+/// holds the lock whilst it is drawn.
+#[derive(Debug, Clone)]
+pub struct SharedTrace(Arc<RwLock<Arc<Trace>>>);
+
+impl SharedTrace {
+    #[must_use]
+    pub fn new(max_samples: usize) -> Self {
+        Self::from(Trace::new(max_samples))
+    }
+
+    /// A snapshot of the latest data of the trace.
+    #[must_use]
+    pub fn snapshot(&self) -> Arc<Trace> {
+        self.0.read().clone()
+    }
+
+    /// A snapshot of the latest data of the trace, if it is not being updated.
+    #[must_use]
+    pub fn try_snapshot(&self) -> Option<Arc<Trace>> {
+        self.0.try_read().map(|trace| trace.clone())
+    }
+
+    /// Update the data of the trace, copying it first if a snapshot of it is still held.
+    pub fn update(&self, f: impl FnOnce(&mut Trace)) {
+        f(Arc::make_mut(&mut self.0.write()));
+    }
+
+    /// Replace the data of the trace, such as to reset its statistics.
+    pub fn replace(&self, trace: Trace) {
+        *self.0.write() = Arc::new(trace);
+    }
+}
+
+impl From<Trace> for SharedTrace {
+    fn from(trace: Trace) -> Self {
+        Self(Arc::new(RwLock::new(Arc::new(trace))))
+    }
+}
+
+/// The serialization of the fields of a `Trace` which cannot be serialized as they are.
+#[cfg(feature = "serde")]
+mod serialize {
+    /// An `Instant` as the time elapsed since, such that it is deserialized as the same time before the present.
+    pub mod elapsed {
+        use serde::{Deserialize, Deserializer, Serialize, Serializer};
+        use std::time::{Duration, Instant};
+
+        pub fn serialize<S: Serializer>(
+            instant: &Instant,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            instant.elapsed().serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Instant, D::Error> {
+            let elapsed = Duration::deserialize(deserializer)?;
+            let now = Instant::now();
+            Ok(now.checked_sub(elapsed).unwrap_or(now))
+        }
+    }
+
+    /// The hops which have been probed, each of which is deserialized to the index of its ttl.
+    pub mod hops {
+        use crate::trace::{Hop, MAX_HOPS};
+        use serde::de::Error;
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(hops: &[Hop], serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(hops.iter().filter(|hop| hop.ttl != 0))
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Vec<Hop>, D::Error> {
+            let mut hops: Vec<_> = (0..MAX_HOPS).map(|_| Hop::default()).collect();
+            for hop in Vec::<Hop>::deserialize(deserializer)? {
+                let index = usize::from(hop.ttl)
+                    .checked_sub(1)
+                    .ok_or_else(|| D::Error::custom("hop with a ttl of zero"))?;
+                hops[index] = hop;
+            }
+            Ok(hops)
+        }
+    }
+
+    /// A map as a sequence of its entries, as the keys of some maps cannot be serialized as strings.
+    pub mod entries {
+        use serde::{Deserialize, Deserializer, Serialize, Serializer};
+        use std::collections::HashMap;
+        use std::hash::Hash;
+
+        pub fn serialize<K: Serialize, V: Serialize, S: Serializer>(
+            map: &HashMap<K, V>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(map)
+        }
+
+        pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<HashMap<K, V>, D::Error>
+        where
+            K: Deserialize<'de> + Eq + Hash,
+            V: Deserialize<'de>,
+            D: Deserializer<'de>,
+        {
+            Ok(Vec::<(K, V)>::deserialize(deserializer)?
+                .into_iter()
+                .collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CompletionReason, MaxInflight, Round, Sequence, TimeToLive};
+    use std::time::SystemTime;
+
+    /// Build a `Trace` from a sequence of rounds.
+    ///
+    /// Each round is a list of `(host, rtt_ms)` for consecutive `ttl` starting from 1, where a `host` of `None`
+    /// indicates that no response was received.
+    fn make_trace(rounds: &[&[(Option<IpAddr>, u64)]]) -> Trace {
+        let mut trace = Trace::new(256);
+        for (round, path) in rounds.iter().enumerate() {
+            let path: Vec<_> = path.iter().map(|(host, rtt)| (*host, *rtt, None)).collect();
+            add_round(&mut trace, round, &path);
+        }
+        trace
+    }
+
+    /// Add a round to a `Trace`.
+    ///
+    /// The round is a list of `(host, rtt_ms, icmp_packet_type)` for consecutive `ttl` starting from 1, where a `host`
+    /// of `None` indicates that no response was received.
+    fn add_round(
+        trace: &mut Trace,
+        round: usize,
+        path: &[(Option<IpAddr>, u64, Option<IcmpPacketType>)],
+    ) {
+        let sent = SystemTime::UNIX_EPOCH;
+        let probes: Vec<_> = path
+            .iter()
+            .enumerate()
+            .map(|(i, (host, rtt, icmp_packet_type))| {
+                let probe = Probe::new(
+                    Sequence(33000 + i as u16),
+                    TimeToLive(i as u8 + 1),
+                    Round(round),
+                    sent,
+                );
+                match host {
+                    Some(host) => {
+                        let probe = probe
+                            .with_status(ProbeStatus::Complete)
+                            .with_host(*host)
+                            .with_received(sent + Duration::from_millis(*rtt));
+                        match icmp_packet_type {
+                            Some(icmp_packet_type) => {
+                                probe.with_icmp_packet_type(*icmp_packet_type)
+                            }
+                            None => probe,
+                        }
+                    }
+                    None => probe,
+                }
+            })
+            .collect();
+        let largest_ttl = TimeToLive(path.len() as u8);
+        trace.update_from_round(&TracerRound::new(
+            &probes,
+            largest_ttl,
+            CompletionReason::TargetFound,
+        ));
+    }
+
+    #[allow(clippy::unnecessary_wraps)]
+    fn addr(last: u8) -> Option<IpAddr> {
+        Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, last)))
+    }
+
+    #[test]
+    fn test_rounds_on_the_same_path_are_one_flow() {
+        let trace = make_trace(&[
+            &[(addr(1), 10), (addr(2), 10), (addr(3), 10)],
+            &[(addr(1), 10), (None, 0), (addr(3), 10)],
+            &[(None, 0), (addr(2), 10), (addr(3), 10)],
+        ]);
+        assert_eq!(1, trace.flows().len());
+        let flow = &trace.flows()[0];
+        assert_eq!(1, flow.id());
+        assert_eq!(3, flow.rounds());
+        assert_eq!(&[addr(1), addr(2), addr(3)], flow.path());
+        assert_eq!(3, flow.trace().hops()[0].total_sent());
+        assert_eq!(2, flow.trace().hops()[0].total_recv());
+    }
+
+    #[test]
+    fn test_rounds_on_different_paths_are_different_flows() {
+        let trace = make_trace(&[
+            &[(addr(1), 10), (addr(2), 10), (addr(3), 10)],
+            &[(addr(1), 10), (addr(4), 20), (addr(3), 10)],
+            &[(addr(1), 10), (addr(2), 10), (addr(3), 10)],
+            &[(None, 0), (addr(4), 20), (None, 0)],
+        ]);
+        let flows = trace.flows();
+        assert_eq!(2, flows.len());
+        assert_eq!((1, 2), (flows[0].id(), flows[0].rounds()));
+        assert_eq!((2, 2), (flows[1].id(), flows[1].rounds()));
+        assert_eq!(Some(2), flows[1].diverges_from(&flows[0]));
+        assert_eq!(None, flows[0].diverges_from(&flows[0]));
+        assert_eq!(4, trace.hops()[1].total_sent());
+        assert_eq!(2, flows[1].trace().hops()[1].total_sent());
+        assert_eq!(Some(20_f64), flows[1].trace().hops()[1].last_ms());
+    }
+
+    #[test]
+    fn test_flow_keys() {
+        let mut trace = make_trace(&[&path(3)]);
+        assert_eq!(None, trace.flow_key());
+        let probes = [Probe::new(
+            Sequence(33000),
+            TimeToLive(1),
+            Round(1),
+            SystemTime::UNIX_EPOCH,
+        )];
+        for dest_port in 0..10 {
+            let flow_key = FlowKey::udp(33000, 33434 + dest_port, 0xbeef);
+            let round = TracerRound::new(
+                &probes,
+                TimeToLive(1),
+                CompletionReason::RoundTimeLimitExceeded,
+            )
+            .with_flow_key(flow_key);
+            trace.update_from_round(&round);
+        }
+        assert_eq!(Some(FlowKey::udp(33000, 33443, 0xbeef)), trace.flow_key());
+        let flow_keys: Vec<_> = trace.flow_keys().collect();
+        assert_eq!(MAX_FLOW_KEYS, flow_keys.len());
+        assert_eq!((1, FlowKey::udp(33000, 33436, 0xbeef)), flow_keys[0]);
+    }
+
+    #[test]
+    fn test_path_stability() {
+        let trace = make_trace(&[
+            &[(addr(1), 10), (addr(2), 10), (addr(3), 10)],
+            &[(addr(1), 10), (None, 0), (addr(3), 10)],
+            &[(addr(1), 10), (addr(4), 10), (addr(3), 10)],
+        ]);
+        let stability = trace.path_stability();
+        assert_eq!(2, stability.distinct_paths());
+        assert_eq!(1, stability.changes());
+        let change = stability.last_change().unwrap();
+        assert_eq!(2, change.round());
+        assert_eq!(
+            &[HopDiff {
+                ttl: 2,
+                from: addr(2),
+                to: addr(4)
+            }],
+            change.hops()
+        );
+        assert!(stability.score().unwrap() < 100_f64);
+        assert_eq!(
+            Some(100_f64),
+            make_trace(&[&path(3), &path(3)]).path_stability().score()
+        );
+    }
+
+    /// The round at which the path converged, as judged by `convergence` after each of `rounds`.
+    fn converged(convergence: &Convergence, rounds: &[&[(Option<IpAddr>, u64)]]) -> Option<usize> {
+        let mut trace = Trace::new(256);
+        for (round, path) in rounds.iter().enumerate() {
+            let path: Vec<_> = path.iter().map(|(host, rtt)| (*host, *rtt, None)).collect();
+            add_round(&mut trace, round, &path);
+            trace.update_converged(convergence);
+        }
+        trace.converged()
+    }
+
+    #[test]
+    fn test_converged() {
+        let convergence = Convergence {
+            rounds: 3,
+            samples: 3,
+        };
+        assert_eq!(None, converged(&convergence, &[&path(3), &path(3)]));
+        assert_eq!(
+            Some(2),
+            converged(&convergence, &[&path(3), &path(3), &path(3), &path(3)])
+        );
+        let silent = [(addr(1), 10), (None, 0), (addr(3), 10)];
+        assert_eq!(
+            Some(2),
+            converged(&convergence, &[&silent, &silent, &silent])
+        );
+    }
+
+    #[test]
+    fn test_not_converged_intermittent_hop() {
+        let convergence = Convergence {
+            rounds: 3,
+            samples: 3,
+        };
+        let lost = [(addr(1), 10), (None, 0), (addr(3), 10)];
+        assert_eq!(
+            None,
+            converged(&convergence, &[&path(3), &path(3), &lost, &lost, &lost])
+        );
+        assert_eq!(
+            Some(5),
+            converged(
+                &convergence,
+                &[&path(3), &path(3), &lost, &lost, &lost, &path(3)]
+            )
+        );
+    }
+
+    #[test]
+    fn test_converged_after_path_ttl_change() {
+        let convergence = Convergence {
+            rounds: 3,
+            samples: 2,
+        };
+        assert_eq!(
+            Some(4),
+            converged(
+                &convergence,
+                &[&path(3), &path(3), &path(4), &path(4), &path(4), &path(4)]
+            )
+        );
+    }
+
+    #[test]
+    fn test_converged_is_sticky() {
+        let convergence = Convergence {
+            rounds: 2,
+            samples: 2,
+        };
+        assert_eq!(
+            Some(1),
+            converged(&convergence, &[&path(2), &path(2), &path(5)])
+        );
+    }
+
+    #[test]
+    fn test_flows_are_bounded() {
+        let rounds: Vec<_> = (0..=MAX_FLOWS as u8)
+            .map(|i| vec![(addr(i), 10), (addr(200), 10)])
+            .collect();
+        let rounds: Vec<_> = rounds.iter().map(Vec::as_slice).collect();
+        let trace = make_trace(&rounds);
+        assert_eq!(MAX_FLOWS, trace.flows().len());
+        assert_eq!(MAX_FLOWS + 1, trace.hops()[1].total_sent());
+    }
+
+    /// A path of `len` hops, each of which responds.
+    fn path(len: u8) -> Vec<(Option<IpAddr>, u64)> {
+        (1..=len).map(|ttl| (addr(ttl), 10)).collect()
+    }
+
+    #[test]
+    fn test_round_robin_endpoints() {
+        let mut trace = Trace::new(256);
+        for round in 0..5 {
+            let target = if round % 2 == 0 { addr(3) } else { addr(4) }.unwrap();
+            let probes: Vec<_> = (0..2)
+                .map(|i| {
+                    Probe::new(
+                        Sequence(33000 + i),
+                        TimeToLive(i as u8 + 1),
+                        Round(round),
+                        SystemTime::UNIX_EPOCH,
+                    )
+                    .with_target(target)
+                    .with_status(ProbeStatus::Complete)
+                    .with_host(if i == 0 { addr(1).unwrap() } else { target })
+                    .with_received(SystemTime::UNIX_EPOCH + Duration::from_millis(10))
+                })
+                .collect();
+            trace.update_from_round(&TracerRound::new(
+                &probes,
+                TimeToLive(2),
+                CompletionReason::TargetFound,
+            ));
+        }
+        let endpoints = trace.endpoints();
+        assert_eq!(2, endpoints.len());
+        assert_eq!(
+            (addr(3).unwrap(), 3),
+            (endpoints[0].addr(), endpoints[0].rounds())
+        );
+        assert_eq!(
+            (addr(4).unwrap(), 2),
+            (endpoints[1].addr(), endpoints[1].rounds())
+        );
+        assert_eq!(5, trace.hops()[0].total_sent());
+        assert_eq!(2, endpoints[1].trace().hops()[1].total_sent());
+        assert_eq!(addr(4), endpoints[1].trace().hops()[1].primary_addr());
+    }
+
+    /// A trace of `rounds` rounds over a path of four hops, the target being the last, in which the hop of ttl `ttl`
+    /// does not respond in round `round` if `lost(round, ttl)`.
+    fn make_lossy_trace(rounds: usize, lost: impl Fn(usize, u8) -> bool) -> Trace {
+        let rounds: Vec<Vec<_>> = (0..rounds)
+            .map(|round| {
+                (1..=4)
+                    .map(|ttl| {
+                        if lost(round, ttl) {
+                            (None, 0)
+                        } else {
+                            (addr(ttl), 10)
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        let rounds: Vec<_> = rounds.iter().map(Vec::as_slice).collect();
+        make_trace(&rounds)
+    }
+
+    fn rate_limited_ttls(trace: &Trace) -> Vec<u8> {
+        RateLimitHeuristic::default()
+            .classify(trace.hops())
+            .into_iter()
+            .zip(trace.hops())
+            .filter(|&(rate_limited, _)| rate_limited)
+            .map(|(_, hop)| hop.ttl())
+            .collect()
+    }
+
+    #[test]
+    fn test_rate_limited_middle_hop() {
+        let trace = make_lossy_trace(20, |round, ttl| ttl == 2 && round % 2 == 0);
+        assert_eq!(vec![2], rate_limited_ttls(&trace));
+    }
+
+    #[test]
+    fn test_tail_loss_is_not_rate_limited() {
+        let trace = make_lossy_trace(20, |round, ttl| ttl >= 2 && round % 2 == 0);
+        assert!(rate_limited_ttls(&trace).is_empty());
+    }
+
+    #[test]
+    fn test_rate_limited_with_unhealthy_deeper_hops() {
+        let trace = make_lossy_trace(20, |round, ttl| {
+            (ttl == 2 && round % 2 == 0) || (ttl >= 3 && round % 5 == 0)
+        });
+        assert!(rate_limited_ttls(&trace).is_empty());
+    }
+
+    #[test]
+    fn test_small_loss_is_not_rate_limited() {
+        let trace = make_lossy_trace(20, |round, ttl| ttl == 2 && round % 10 == 0);
+        assert!(rate_limited_ttls(&trace).is_empty());
+    }
+
+    #[test]
+    fn test_unresponsive_hop_is_not_rate_limited() {
+        let trace = make_lossy_trace(20, |_, ttl| ttl == 2);
+        assert!(rate_limited_ttls(&trace).is_empty());
+    }
+
+    #[test]
+    fn test_rate_limited_needs_samples() {
+        let trace = make_lossy_trace(9, |round, ttl| ttl == 2 && round % 2 == 0);
+        assert!(rate_limited_ttls(&trace).is_empty());
+    }
+
+    #[test]
+    fn test_update_rate_limited() {
+        let mut trace = make_lossy_trace(20, |round, ttl| ttl == 3 && round % 3 != 0);
+        trace.update_rate_limited(&RateLimitHeuristic::default());
+        let flags: Vec<_> = trace.hops().iter().map(Hop::rate_limited_likely).collect();
+        assert_eq!(vec![false, false, true, false], flags);
+        assert!(trace.flows()[0].trace().hops()[2].rate_limited_likely());
+        let strict = RateLimitHeuristic {
+            gap_pct: 80_f64,
+            ..RateLimitHeuristic::default()
+        };
+        trace.update_rate_limited(&strict);
+        assert!(!trace.hops()[2].rate_limited_likely());
+    }
+
+    #[test]
+    fn test_inflight_window() {
+        let mut trace = make_trace(&[&[(addr(1), 10)]]);
+        assert_eq!(None, trace.inflight_window());
+        let probes = [
+            Probe::new(Sequence(33001), TimeToLive(1), Round(1), SystemTime::now())
+                .with_status(ProbeStatus::Awaited),
+        ];
+        let round = TracerRound::new(&probes, TimeToLive(1), CompletionReason::TargetFound)
+            .with_inflight_window(MaxInflight(3));
+        trace.update_from_round(&round);
+        assert_eq!(Some(3), trace.inflight_window());
+    }
+
+    #[test]
+    fn test_no_endpoints_without_targets() {
+        let trace = make_trace(&[&[(addr(1), 10)], &[(addr(1), 10)]]);
+        assert!(trace.endpoints().is_empty());
+    }
+
+    #[test]
+    fn test_alternating_path_keeps_longer() {
+        let (short, long) = (path(10), path(12));
+        let trace = make_trace(&[&long, &short, &long, &short, &long, &short]);
+        assert_eq!(12, trace.hops().len());
+        assert!(trace.historic_hops().is_empty());
+        assert_eq!(12, trace.target_hop().ttl());
+        assert_eq!(None, trace.path_change());
+    }
+
+    #[test]
+    fn test_shorter_path() {
+        let (short, long) = (path(10), path(12));
+        let mut rounds = vec![long.as_slice()];
+        rounds.extend([short.as_slice(); SHORTER_PATH_ROUNDS - 1]);
+        assert_eq!(12, make_trace(&rounds).hops().len());
+        rounds.push(&short);
+        let trace = make_trace(&rounds);
+        assert_eq!(10, trace.hops().len());
+        assert_eq!(12, trace.all_hops().len());
+        let historic: Vec<_> = trace.historic_hops().iter().map(Hop::ttl).collect();
+        assert_eq!(vec![11, 12], historic);
+        assert_eq!(10, trace.target_hop().ttl());
+        assert!(trace.is_target(&trace.hops()[9]));
+        let change = trace.path_change().unwrap();
+        assert_eq!((12, 10), (change.from(), change.to()));
+    }
+
+    #[test]
+    fn test_longer_path() {
+        let (short, long) = (path(10), path(12));
+        let trace = make_trace(&[&short, &long]);
+        assert_eq!(12, trace.target_hop().ttl());
+        let change = trace.path_change().unwrap();
+        assert_eq!((10, 12), (change.from(), change.to()));
+    }
+
+    #[test]
+    fn test_recent_unreachable() {
+        let unreachable = Some(IcmpPacketType::Unreachable(1));
+        let mut trace = Trace::new(2);
+        add_round(&mut trace, 0, &[(addr(1), 10, unreachable)]);
+        add_round(&mut trace, 1, &[(addr(1), 10, unreachable)]);
+        let host_unreachable = Unreachable::new(addr(1).unwrap(), 1, None);
+        assert_eq!(
+            vec![(host_unreachable, 2)],
+            trace.hops()[0].recent_unreachable()
+        );
+        add_round(&mut trace, 2, &[(addr(1), 10, None)]);
+        add_round(&mut trace, 3, &[(None, 0, None)]);
+        assert!(trace.hops()[0].recent_unreachable().is_empty());
+        assert_eq!(vec![(host_unreachable, 2)], trace.hops()[0].unreachable());
+    }
+
+    #[test]
+    fn test_asymmetry() {
+        let mut trace = Trace::new(256);
+        let sent = SystemTime::UNIX_EPOCH;
+        let mut update = |round: usize, recv_ttl: Option<u8>| {
+            let probe = Probe::new(Sequence(33000), TimeToLive(3), Round(round), sent)
+                .with_status(ProbeStatus::Complete)
+                .with_host(addr(3).unwrap())
+                .with_received(sent + Duration::from_millis(10))
+                .with_recv_ttl(recv_ttl);
+            let probes = [probe];
+            trace.update_from_round(&TracerRound::new(
+                &probes,
+                TimeToLive(3),
+                CompletionReason::TargetFound,
+            ));
+            trace.target_hop().asymmetry()
+        };
+        assert_eq!(None, update(0, None));
+        assert_eq!(Some(3), update(1, Some(250)));
+        assert_eq!(Some(3), update(2, Some(90)));
+        assert_eq!(Some(-1), update(3, Some(63)));
+    }
+
+    #[test]
+    fn test_addr_quote() {
+        let mut trace = Trace::new(256);
+        let sent = SystemTime::UNIX_EPOCH;
+        let mut update = |round: usize, quote: Option<IcmpQuote>| {
+            let probe = Probe::new(Sequence(33000), TimeToLive(1), Round(round), sent)
+                .with_status(ProbeStatus::Complete)
+                .with_host(addr(1).unwrap())
+                .with_received(sent + Duration::from_millis(10))
+                .with_quote(quote);
+            let probes = [probe];
+            trace.update_from_round(&TracerRound::new(
+                &probes,
+                TimeToLive(1),
+                CompletionReason::TargetFound,
+            ));
+            let (_, details) = trace.target_hop().addrs_with_details().next().unwrap();
+            details.quote()
+        };
+        let short = IcmpQuote::new(28, 28, false);
+        let full = IcmpQuote::new(100, 56, true);
+        assert_eq!(None, update(0, None));
+        assert_eq!(Some(short), update(1, Some(short)));
+        assert_eq!(Some(short), update(2, None));
+        assert_eq!(Some(full), update(3, Some(full)));
+    }
+
+    #[test]
+    fn test_responder_ports() {
+        let mut trace = Trace::new(256);
+        let sent = SystemTime::UNIX_EPOCH;
+        let update = |trace: &mut Trace, round: usize, ports: ResponderPorts| {
+            let probe = Probe::new(Sequence(33000), TimeToLive(1), Round(round), sent)
+                .with_status(ProbeStatus::Complete)
+                .with_host(addr(1).unwrap())
+                .with_received(sent + Duration::from_millis(10))
+                .with_responder_ports(Some(ports));
+            trace.update_from_round(&TracerRound::new(
+                &[probe],
+                TimeToLive(1),
+                CompletionReason::TargetFound,
+            ));
+        };
+        let matched = ResponderPorts::new(443, 443);
+        let proxied = ResponderPorts::new(443, 8443);
+        update(&mut trace, 0, matched);
+        update(&mut trace, 1, matched);
+        assert_eq!(vec![(matched, 2)], trace.target_hop().responder_ports());
+        assert!(!trace.target_hop().port_mismatch());
+        update(&mut trace, 2, proxied);
+        assert_eq!(
+            vec![(matched, 2), (proxied, 1)],
+            trace.target_hop().responder_ports()
+        );
+        assert!(trace.target_hop().port_mismatch());
+    }
+
+    #[test]
+    fn test_last_sent_and_recv_time() {
+        let mut trace = Trace::new(256);
+        let sent = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+        let received = sent + Duration::from_millis(10);
+        let answered = Probe::new(Sequence(33000), TimeToLive(1), Round(0), sent)
+            .with_status(ProbeStatus::Complete)
+            .with_host(addr(1).unwrap())
+            .with_received(received);
+        trace.update_from_round(&TracerRound::new(
+            &[answered],
+            TimeToLive(1),
+            CompletionReason::TargetFound,
+        ));
+        assert_eq!(Some(sent), trace.hops()[0].last_sent_time());
+        assert_eq!(Some(received), trace.hops()[0].last_recv_time());
+        let later = sent + Duration::from_secs(90);
+        let awaited = Probe::new(Sequence(33001), TimeToLive(1), Round(1), later)
+            .with_status(ProbeStatus::Awaited);
+        trace.update_from_round(&TracerRound::new(
+            &[awaited],
+            TimeToLive(1),
+            CompletionReason::RoundTimeLimitExceeded,
+        ));
+        assert_eq!(Some(later), trace.hops()[0].last_sent_time());
+        assert_eq!(Some(received), trace.hops()[0].last_recv_time());
+        assert_eq!(
+            Some(Duration::from_secs(252)),
+            trace.hops()[0].since_last_recv(received + Duration::from_secs(252))
+        );
+        assert_eq!(
+            Some(Duration::ZERO),
+            trace.hops()[0].since_last_recv(SystemTime::UNIX_EPOCH)
+        );
+        assert_eq!(None, Hop::default().since_last_recv(later));
+    }
+
+    /// Update a `Trace` from a round of a single probe to ttl 1 received `rtt_ms` after it was sent, which may be
+    /// negative, in a round which lasted `round_ms`.
+    fn add_timed_round(trace: &mut Trace, round: usize, rtt_ms: i64, round_ms: u64) {
+        let sent = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+        let received = if rtt_ms < 0 {
+            sent - Duration::from_millis(rtt_ms.unsigned_abs())
+        } else {
+            sent + Duration::from_millis(rtt_ms.unsigned_abs())
+        };
+        let probes = [
+            Probe::new(Sequence(33000), TimeToLive(1), Round(round), sent)
+                .with_status(ProbeStatus::Complete)
+                .with_host(addr(1).unwrap())
+                .with_received(received),
+        ];
+        let round = TracerRound::new(&probes, TimeToLive(1), CompletionReason::TargetFound)
+            .with_duration(Duration::from_millis(round_ms));
+        trace.update_from_round(&round);
+    }
+
+    #[test]
+    fn test_clock_set_back() {
+        let mut trace = Trace::new(256);
+        add_timed_round(&mut trace, 0, 10, 1000);
+        add_timed_round(&mut trace, 1, -500, 1000);
+        let hop = &trace.hops()[0];
+        assert_eq!(1, trace.clock_anomalies());
+        assert_eq!(Some(1), trace.round());
+        assert_eq!(1, hop.total_sent());
+        assert_eq!(1, hop.total_recv());
+        assert_eq!(Some(10_f64), hop.best_ms());
+        assert_eq!(&[Duration::from_millis(10)], hop.samples());
+    }
+
+    #[test]
+    fn test_clock_set_forward() {
+        let mut trace = Trace::new(256);
+        add_timed_round(&mut trace, 0, 3_600_000, 1000);
+        add_timed_round(&mut trace, 1, 20, 1000);
+        let hop = &trace.hops()[0];
+        assert_eq!(1, trace.clock_anomalies());
+        assert_eq!(1, hop.total_recv());
+        assert_eq!(Some(20_f64), hop.worst_ms());
+    }
+
+    #[test]
+    fn test_clock_set_back_monotonic() {
+        let mut trace = Trace::new(256);
+        let sent = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+        let sent_at = Instant::now();
+        let probes = [Probe::new(Sequence(33000), TimeToLive(1), Round(0), sent)
+            .with_sent_at(sent_at)
+            .with_status(ProbeStatus::Complete)
+            .with_host(addr(1).unwrap())
+            .with_received(sent - Duration::from_millis(500))
+            .with_received_at(sent_at + Duration::from_millis(20))];
+        let round = TracerRound::new(&probes, TimeToLive(1), CompletionReason::TargetFound)
+            .with_duration(Duration::from_secs(1));
+        trace.update_from_round(&round);
+        let hop = &trace.hops()[0];
+        assert_eq!(0, trace.clock_anomalies());
+        assert_eq!(1, hop.total_recv());
+        assert_eq!(&[Duration::from_millis(20)], hop.samples());
+    }
+
+    /// Update a `Trace` from a round of a probe to ttl 1 and a probe to `ttl`, both of which were answered.
+    fn add_round_with_ttl(trace: &mut Trace, round: usize, ttl: u8) {
+        let sent = SystemTime::UNIX_EPOCH;
+        let probe = |sequence, ttl| {
+            Probe::new(Sequence(sequence), TimeToLive(ttl), Round(round), sent)
+                .with_status(ProbeStatus::Complete)
+                .with_host(addr(ttl).unwrap())
+                .with_received(sent + Duration::from_millis(10))
+        };
+        let probes = [probe(33000, 1), probe(33001, ttl)];
+        trace.update_from_round(&TracerRound::new(
+            &probes,
+            TimeToLive(ttl.max(1)),
+            CompletionReason::RoundTimeLimitExceeded,
+        ));
+    }
+
+    #[test]
+    fn test_ttl_zero() {
+        let mut trace = Trace::new(256);
+        add_round_with_ttl(&mut trace, 0, 0);
+        add_round_with_ttl(&mut trace, 1, 0);
+        assert_eq!(2, trace.invalid_ttls());
+        assert_eq!(1, trace.hops().len());
+        assert_eq!(2, trace.hops()[0].total_recv());
+        assert_eq!(Some(1), trace.round());
+    }
+
+    #[test]
+    fn test_ttl_max_hops() {
+        // A ttl of `MAX_HOPS + 1` cannot be represented, the ttl being a `u8`, and so `MAX_HOPS` is the highest.
+        let mut trace = Trace::new(256);
+        add_round_with_ttl(&mut trace, 0, MAX_HOPS as u8);
+        assert_eq!(0, trace.invalid_ttls());
+        assert_eq!(MAX_HOPS, trace.hops().len());
+        assert_eq!(MAX_HOPS as u8, trace.target_hop().ttl());
+        assert_eq!(1, trace.target_hop().total_recv());
+    }
+
+    #[test]
+    fn test_hop_index() {
+        assert_eq!(None, hop_index(TimeToLive(0)));
+        assert_eq!(Some(0), hop_index(TimeToLive(1)));
+        assert_eq!(Some(MAX_HOPS - 1), hop_index(TimeToLive(u8::MAX)));
+    }
+
+    #[test]
+    fn test_shared_trace_snapshot() {
+        let shared = SharedTrace::new(256);
+        let before = shared.snapshot();
+        assert!(Arc::ptr_eq(&before, &shared.snapshot()));
+        shared.update(|trace| add_round(trace, 0, &[(addr(1), 10, None)]));
+        let after = shared.snapshot();
+        assert_eq!(None, before.round());
+        assert_eq!(Some(0), after.round());
+        let unshared = Arc::as_ptr(&after);
+        drop((before, after));
+        shared.update(|trace| add_round(trace, 1, &[(addr(1), 10, None)]));
+        assert_eq!(unshared, Arc::as_ptr(&shared.snapshot()));
+        assert_eq!(Some(1), shared.snapshot().round());
+        shared.replace(Trace::new(256));
+        assert_eq!(None, shared.snapshot().round());
+    }
+
+    #[test]
+    fn test_resume() {
+        let mut trace = make_trace(&[
+            &[(addr(1), 10), (addr(2), 20)],
+            &[(addr(1), 10), (addr(2), 20)],
+            &[(addr(1), 10), (addr(2), 20)],
+        ]);
+        trace.set_error(String::from("failed"));
+        trace.resume(2);
+        assert_eq!(None, trace.error());
+        assert_eq!(2, trace.hops()[1].samples().len());
+        assert_eq!(2, trace.flows()[0].trace().hops()[1].samples().len());
+        assert_eq!(3, trace.resumed_rounds());
+        add_round(&mut trace, 0, &[(addr(1), 10, None), (addr(3), 30, None)]);
+        assert_eq!(Some(3), trace.round());
+        assert_eq!(4, trace.hops()[1].total_sent());
+        assert_eq!(2, trace.hops()[1].samples().len());
+        assert_eq!(Some(3), trace.flows()[1].trace().round());
+        add_round(&mut trace, 1, &[(addr(1), 10, None), (addr(2), 20, None)]);
+        assert_eq!(Some(4), trace.flows()[0].trace().round());
+    }
+
+    #[test]
+    fn test_truncate_samples() {
+        let mut trace = make_trace(&[&[(addr(1), 10)], &[(addr(1), 20)], &[(addr(1), 30)]]);
+        trace.truncate_samples(1);
+        assert_eq!(&[Duration::from_millis(30)], trace.hops()[0].samples());
+        assert_eq!(3, trace.hops()[0].total_recv());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn test_serialize() {
+        let unreachable = Some(IcmpPacketType::Unreachable(1));
+        let mut trace = make_trace(&[
+            &[(addr(1), 10), (addr(2), 20)],
+            &[(addr(1), 10), (addr(3), 30)],
+        ]);
+        add_round(
+            &mut trace,
+            2,
+            &[(addr(1), 10, None), (addr(2), 20, unreachable)],
+        );
+        let json = serde_json::to_string(&trace).unwrap();
+        let restored: Trace = serde_json::from_str(&json).unwrap();
+        assert_eq!(Some(2), restored.round());
+        assert_eq!(trace.hops().len(), restored.hops().len());
+        assert_eq!(MAX_HOPS, restored.hops.len());
+        let (hop, restored_hop) = (&trace.hops()[1], &restored.hops()[1]);
+        assert_eq!(2, restored_hop.ttl());
+        assert_eq!(hop.total_sent(), restored_hop.total_sent());
+        assert_eq!(hop.avg_ms(), restored_hop.avg_ms());
+        assert_eq!(hop.stddev_ms(), restored_hop.stddev_ms());
+        assert_eq!(hop.samples(), restored_hop.samples());
+        assert_eq!(hop.unreachable(), restored_hop.unreachable());
+        assert_eq!(Some(20_f64), restored_hop.best_ms());
+        assert_eq!(
+            trace.links().collect::<HashMap<_, _>>(),
+            restored.links().collect::<HashMap<_, _>>()
+        );
+        assert_eq!(2, restored.flows().len());
+        assert_eq!(&[addr(1), addr(3)], restored.flows()[1].path());
+    }
+
+    /// Compare the cost of preparing a frame of the TUI by copying a fully populated 30 hop trace with that of taking
+    /// a snapshot of it.
+    ///
+    /// Run with `cargo test --release bench_frame_snapshot -- --ignored --nocapture`.
+    #[test]
+    #[ignore = "benchmark"]
+    fn bench_frame_snapshot() {
+        const FRAMES: u32 = 1000;
+        let path: Vec<_> = (1..=30).map(|ttl| (addr(ttl), 10, None)).collect();
+        let shared = SharedTrace::new(256);
+        for round in 0..256 {
+            shared.update(|trace| add_round(trace, round, &path));
+        }
+        let start = std::time::Instant::now();
+        for _ in 0..FRAMES {
+            std::hint::black_box(shared.0.read().as_ref().clone());
+        }
+        let copy = start.elapsed() / FRAMES;
+        let start = std::time::Instant::now();
+        for _ in 0..FRAMES {
+            std::hint::black_box(shared.snapshot());
+        }
+        let snapshot = start.elapsed() / FRAMES;
+        println!("copy: {copy:?} per frame, snapshot: {snapshot:?} per frame");
+        assert!(snapshot < copy);
+    }
+}
@@ -0,0 +1,384 @@
+use crate::{CompletionReason, TracerRound};
+use itertools::Itertools;
+use std::collections::{HashSet, VecDeque};
+use std::net::IpAddr;
+use std::time::SystemTime;
+
+/// The stability of the path to the target over the latest rounds of a trace.
+///
+/// The path of each round is the host which responded at each ttl, those which did not respond being assumed to be
+/// the hosts which last did, such that loss is not taken for a change of path.  The path of a round in which the
+/// target did not respond keeps the length of the path of the previous round.
+///
+/// The path of each round is hashed and compared to that of the previous round, a round whose hash differs being a
+/// change of path, and the hashes of the paths observed are kept to count the distinct paths.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PathStability {
+    /// The paths of the latest rounds, the latest last.
+    window: VecDeque<RoundPath>,
+    /// The hashes of the distinct paths observed.
+    paths: HashSet<u64>,
+    /// The number of changes of path observed.
+    changes: usize,
+    /// The latest change of path.
+    last_change: Option<PathDiff>,
+}
+
+impl PathStability {
+    /// The stability of the path over the latest rounds, as a score from 0 to 100, if any round has been traced.
+    ///
+    /// The score is the mean of the fraction of rounds which followed the most common path, the inverse of one plus
+    /// the variance of the length of the path, and the fraction of rounds in which each hop responded from the same
+    /// host as in the previous round, such that a path which never changes scores 100.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn score(&self) -> Option<f64> {
+        if self.window.is_empty() {
+            return None;
+        }
+        let rounds = self.window.len() as f64;
+        let same_path = self
+            .window
+            .iter()
+            .counts_by(|path| path.hash)
+            .into_values()
+            .max()
+            .unwrap_or_default() as f64
+            / rounds;
+        let mean_len = self
+            .window
+            .iter()
+            .map(|path| path.hosts.len() as f64)
+            .sum::<f64>()
+            / rounds;
+        let len_variance = self
+            .window
+            .iter()
+            .map(|path| (path.hosts.len() as f64 - mean_len).powi(2))
+            .sum::<f64>()
+            / rounds;
+        let same_len = 1_f64 / (1_f64 + len_variance);
+        let hops = self
+            .window
+            .iter()
+            .map(|path| path.hosts.len())
+            .max()
+            .unwrap_or_default();
+        let same_hosts = if self.window.len() < 2 || hops == 0 {
+            1_f64
+        } else {
+            let hop_changes = self
+                .window
+                .iter()
+                .tuple_windows()
+                .map(|(previous, path)| diff(&previous.hosts, &path.hosts).len())
+                .sum::<usize>();
+            1_f64 - hop_changes as f64 / ((rounds - 1_f64) * hops as f64)
+        };
+        Some((same_path + same_len + same_hosts) / 3_f64 * 100_f64)
+    }
+
+    /// The number of distinct paths observed.
+    #[must_use]
+    pub fn distinct_paths(&self) -> usize {
+        self.paths.len()
+    }
+
+    /// The number of changes of path observed.
+    #[must_use]
+    pub fn changes(&self) -> usize {
+        self.changes
+    }
+
+    /// The latest change of path, if the path has changed.
+    #[must_use]
+    pub fn last_change(&self) -> Option<&PathDiff> {
+        self.last_change.as_ref()
+    }
+
+    /// When the path last changed, if it has.
+    #[must_use]
+    pub fn changed_at(&self) -> Option<SystemTime> {
+        self.last_change.as_ref().map(PathDiff::at)
+    }
+
+    /// Record the path of `round`, the round numbered `round_number` of the trace, keeping the paths of at most
+    /// `window` rounds.
+    pub fn update(&mut self, round: &TracerRound<'_>, round_number: usize, window: usize) {
+        let observed = round.path();
+        let previous = self.window.back();
+        let target_found = matches!(round.reason, CompletionReason::TargetFound);
+        let len = match previous {
+            Some(previous) if !target_found => previous.hosts.len().max(
+                observed
+                    .iter()
+                    .rposition(Option::is_some)
+                    .map_or(0, |index| index + 1),
+            ),
+            _ => observed.len(),
+        };
+        let hosts: Vec<_> = (0..len)
+            .map(|index| {
+                observed.get(index).copied().flatten().or_else(|| {
+                    previous.and_then(|previous| previous.hosts.get(index).copied().flatten())
+                })
+            })
+            .collect();
+        let path = RoundPath {
+            hash: path_hash(&hosts),
+            hosts,
+        };
+        if let Some(previous) = previous {
+            if previous.hash != path.hash {
+                let hops = diff(&previous.hosts, &path.hosts);
+                if hops.is_empty() {
+                    // A hop which had not yet responded has done so, which completes the path rather than changes it.
+                    let previous_hash = previous.hash;
+                    self.paths.remove(&previous_hash);
+                    for completed in self
+                        .window
+                        .iter_mut()
+                        .filter(|other| other.hash == previous_hash)
+                    {
+                        *completed = path.clone();
+                    }
+                } else {
+                    self.changes += 1;
+                    self.last_change = Some(PathDiff {
+                        round: round_number,
+                        at: SystemTime::now(),
+                        hops,
+                    });
+                }
+            }
+        }
+        self.paths.insert(path.hash);
+        self.window.push_back(path);
+        while self.window.len() > window.max(1) {
+            self.window.pop_front();
+        }
+    }
+}
+
+/// A change of the path to the target between consecutive rounds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PathDiff {
+    round: usize,
+    at: SystemTime,
+    hops: Vec<HopDiff>,
+}
+
+impl PathDiff {
+    /// The round in which the path changed.
+    #[must_use]
+    pub fn round(&self) -> usize {
+        self.round
+    }
+
+    /// When the path changed.
+    #[must_use]
+    pub fn at(&self) -> SystemTime {
+        self.at
+    }
+
+    /// The hops which differ between the paths, in order of ttl.
+    #[must_use]
+    pub fn hops(&self) -> &[HopDiff] {
+        &self.hops
+    }
+}
+
+/// A hop which differs between the paths before and after a change of path.
+///
+/// A host of `None` is of a hop beyond the end of the shorter path, or of a hop which had not yet responded.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HopDiff {
+    pub ttl: u8,
+    pub from: Option<IpAddr>,
+    pub to: Option<IpAddr>,
+}
+
+/// The path of a round and its hash.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct RoundPath {
+    hash: u64,
+    hosts: Vec<Option<IpAddr>>,
+}
+
+/// The hops which differ between the paths `from` and `to`, being those of a ttl at which both responded from
+/// different hosts, or which are beyond the end of one of the paths.
+fn diff(from: &[Option<IpAddr>], to: &[Option<IpAddr>]) -> Vec<HopDiff> {
+    (0..from.len().max(to.len()))
+        .filter_map(|index| {
+            let hop = HopDiff {
+                ttl: index as u8 + 1,
+                from: from.get(index).copied().flatten(),
+                to: to.get(index).copied().flatten(),
+            };
+            let differs = match (from.get(index), to.get(index)) {
+                (Some(Some(from)), Some(Some(to))) => from != to,
+                (Some(_), Some(_)) | (None, None) => false,
+                (Some(_), None) | (None, Some(_)) => true,
+            };
+            differs.then_some(hop)
+        })
+        .collect()
+}
+
+/// The `FNV-1a` hash of `hosts`, which unlike the `Hash` of the standard library is stable between builds such that
+/// the hashes of a saved trace remain valid once it is resumed.
+fn path_hash(hosts: &[Option<IpAddr>]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+    let bytes = hosts.iter().flat_map(|host| {
+        let (tag, octets) = match host {
+            None => (0_u8, vec![]),
+            Some(IpAddr::V4(addr)) => (4, addr.octets().to_vec()),
+            Some(IpAddr::V6(addr)) => (6, addr.octets().to_vec()),
+        };
+        std::iter::once(tag).chain(octets)
+    });
+    bytes.fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Probe, ProbeStatus, Round, Sequence, TimeToLive};
+    use std::net::Ipv4Addr;
+
+    #[allow(clippy::unnecessary_wraps)]
+    fn addr(last: u8) -> Option<IpAddr> {
+        Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, last)))
+    }
+
+    /// Record a round in which the hop of each ttl, from 1, responded from `hosts`, or did not respond if `None`.
+    fn update(stability: &mut PathStability, round: usize, hosts: &[Option<IpAddr>], found: bool) {
+        let probes: Vec<_> = hosts
+            .iter()
+            .enumerate()
+            .map(|(i, host)| {
+                let probe = Probe::new(
+                    Sequence(33000 + i as u16),
+                    TimeToLive(i as u8 + 1),
+                    Round(round),
+                    SystemTime::UNIX_EPOCH,
+                );
+                match host {
+                    Some(host) => probe.with_status(ProbeStatus::Complete).with_host(*host),
+                    None => probe,
+                }
+            })
+            .collect();
+        let reason = if found {
+            CompletionReason::TargetFound
+        } else {
+            CompletionReason::RoundTimeLimitExceeded
+        };
+        let largest_ttl = TimeToLive(hosts.len() as u8);
+        stability.update(&TracerRound::new(&probes, largest_ttl, reason), round, 10);
+    }
+
+    #[test]
+    fn test_stable_path() {
+        let mut stability = PathStability::default();
+        assert_eq!(None, stability.score());
+        for round in 0..5 {
+            update(&mut stability, round, &[addr(1), addr(2), addr(3)], true);
+        }
+        assert_eq!(Some(100_f64), stability.score());
+        assert_eq!(1, stability.distinct_paths());
+        assert_eq!(0, stability.changes());
+        assert_eq!(None, stability.last_change());
+        assert_eq!(None, stability.changed_at());
+    }
+
+    #[test]
+    fn test_loss_is_not_a_change() {
+        let mut stability = PathStability::default();
+        update(&mut stability, 0, &[addr(1), None, addr(3)], true);
+        update(&mut stability, 1, &[addr(1), addr(2), addr(3)], true);
+        update(&mut stability, 2, &[None, addr(2), addr(3)], true);
+        update(&mut stability, 3, &[addr(1), addr(2), None], false);
+        assert_eq!(Some(100_f64), stability.score());
+        assert_eq!(1, stability.distinct_paths());
+        assert_eq!(0, stability.changes());
+    }
+
+    #[test]
+    fn test_changed_hop() {
+        let mut stability = PathStability::default();
+        update(&mut stability, 0, &[addr(1), addr(2), addr(3)], true);
+        update(&mut stability, 1, &[addr(1), addr(4), addr(3)], true);
+        update(&mut stability, 2, &[addr(1), addr(4), addr(3)], true);
+        update(&mut stability, 3, &[addr(1), addr(2), addr(3)], true);
+        assert_eq!(2, stability.distinct_paths());
+        assert_eq!(2, stability.changes());
+        let change = stability.last_change().unwrap();
+        assert_eq!(3, change.round());
+        assert_eq!(
+            &[HopDiff {
+                ttl: 2,
+                from: addr(4),
+                to: addr(2)
+            }],
+            change.hops()
+        );
+        assert!(stability.changed_at().is_some());
+        // Half of the rounds followed the most common path, the length never changed and two of the nine hops of
+        // consecutive rounds changed.
+        let score = stability.score().unwrap();
+        let expected = (0.5 + 1.0 + (1.0 - 2.0 / 9.0)) / 3.0 * 100.0;
+        assert!((score - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_changed_length() {
+        let mut stability = PathStability::default();
+        update(&mut stability, 0, &[addr(1), addr(2), addr(3)], true);
+        update(&mut stability, 1, &[addr(1), addr(3)], true);
+        assert_eq!(2, stability.distinct_paths());
+        assert_eq!(1, stability.changes());
+        assert_eq!(
+            &[
+                HopDiff {
+                    ttl: 2,
+                    from: addr(2),
+                    to: addr(3)
+                },
+                HopDiff {
+                    ttl: 3,
+                    from: addr(3),
+                    to: None
+                }
+            ],
+            stability.last_change().unwrap().hops()
+        );
+        assert!(stability.score().unwrap() < 100_f64);
+    }
+
+    #[test]
+    fn test_window() {
+        let mut stability = PathStability::default();
+        update(&mut stability, 0, &[addr(1), addr(2)], true);
+        for round in 1..=10 {
+            update(&mut stability, round, &[addr(1), addr(4)], true);
+        }
+        assert_eq!(Some(100_f64), stability.score());
+        assert_eq!(2, stability.distinct_paths());
+        assert_eq!(1, stability.changes());
+    }
+
+    #[test]
+    fn test_path_hash() {
+        assert_eq!(path_hash(&[addr(1), None]), path_hash(&[addr(1), None]));
+        assert_ne!(path_hash(&[addr(1), None]), path_hash(&[None, addr(1)]));
+        assert_ne!(path_hash(&[addr(1)]), path_hash(&[addr(2)]));
+    }
+}
@@ -0,0 +1,1855 @@
+use self::state::TracerState;
+use crate::config::MAX_TTL;
+use crate::error::{TraceResult, TracerError};
+use crate::net::{Attribution, ChannelMetrics, Network};
+use crate::probe::{FlowKey, ProbeResponse};
+use crate::types::{MaxInflight, MaxRounds, Sequence, TimeToLive, TraceId};
+use crate::TracerProtocol;
+use crate::{Probe, ProbeStatus, SendFailure, TracerConfig, COUNTER_TARGET};
+use ::tracing::{debug, debug_span, trace, trace_span};
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Tracing on a tokio runtime.
+#[cfg(feature = "tokio")]
+mod asynchronous;
+
+#[cfg(feature = "tokio")]
+pub use asynchronous::{AsyncTracer, OwnedTracerRound};
+
+/// The shortest wait for an incoming packet when no probe can be sent.
+const MIN_RECV_TIMEOUT: Duration = Duration::from_millis(1);
+
+/// The percentage of the probes of a round to hops which have responded before that must go unanswered for an
+/// adaptive in-flight window to be halved.
+const ADAPTIVE_INFLIGHT_LOSS_PCT: usize = 20;
+
+/// The output from a round of tracing.
+#[derive(Debug, Clone)]
+pub struct TracerRound<'a> {
+    /// The state of all `Probe` that were sent in the round.
+    pub probes: &'a [Probe],
+    /// The largest time-to-live (ttl) for which we received a reply in the round.
+    pub largest_ttl: TimeToLive,
+    /// Indicates what triggered the completion of the tracing round.
+    pub reason: CompletionReason,
+    /// The number of probes which could be in flight at once in the round, if it is adaptive.
+    pub inflight_window: Option<MaxInflight>,
+    /// How long the round lasted, as measured by the monotonic clock, if known.
+    pub duration: Option<Duration>,
+    /// When the round started, after any jitter of its start, if known.
+    pub started: Option<SystemTime>,
+    /// The counts of the packets received by the channel as of the completion of the round, if they are recorded.
+    pub channel_metrics: Option<ChannelMetrics>,
+    /// The flow key of the first probe sent in the round, if it is recorded.
+    pub flow_key: Option<FlowKey>,
+}
+
+impl<'a> TracerRound<'a> {
+    #[must_use]
+    pub fn new(probes: &'a [Probe], largest_ttl: TimeToLive, reason: CompletionReason) -> Self {
+        Self {
+            probes,
+            largest_ttl,
+            reason,
+            inflight_window: None,
+            duration: None,
+            started: None,
+            channel_metrics: None,
+            flow_key: None,
+        }
+    }
+
+    /// The round which started at `started`.
+    #[must_use]
+    pub fn with_started(self, started: SystemTime) -> Self {
+        Self {
+            started: Some(started),
+            ..self
+        }
+    }
+
+    /// The round which lasted for `duration`.
+    #[must_use]
+    pub fn with_duration(self, duration: Duration) -> Self {
+        Self {
+            duration: Some(duration),
+            ..self
+        }
+    }
+
+    /// The round with the counts of the packets received by the channel as of its completion.
+    #[must_use]
+    pub fn with_channel_metrics(self, channel_metrics: ChannelMetrics) -> Self {
+        Self {
+            channel_metrics: Some(channel_metrics),
+            ..self
+        }
+    }
+
+    /// The round with the `flow_key` of the first probe sent in it.
+    #[must_use]
+    pub fn with_flow_key(self, flow_key: FlowKey) -> Self {
+        Self {
+            flow_key: Some(flow_key),
+            ..self
+        }
+    }
+
+    /// The round with the adaptive in-flight window in which it was traced.
+    #[must_use]
+    pub fn with_inflight_window(self, inflight_window: MaxInflight) -> Self {
+        Self {
+            inflight_window: Some(inflight_window),
+            ..self
+        }
+    }
+
+    /// The hosts which responded in the round, by ttl from 1, where `None` indicates that no response was received.
+    #[must_use]
+    pub fn path(&self) -> Vec<Option<IpAddr>> {
+        let mut path = vec![None; usize::from(self.largest_ttl.0)];
+        for probe in self.probes {
+            if probe.status == ProbeStatus::Complete && probe.ttl <= self.largest_ttl {
+                if let Some(host) = usize::from(probe.ttl.0)
+                    .checked_sub(1)
+                    .and_then(|index| path.get_mut(index))
+                {
+                    *host = probe.host;
+                }
+            }
+        }
+        path
+    }
+
+    /// The address of the target probed in the round, if known.
+    #[must_use]
+    pub fn target(&self) -> Option<IpAddr> {
+        self.probes.iter().find_map(|probe| probe.target)
+    }
+}
+
+/// Indicates what triggered the completion of the tracing round.
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CompletionReason {
+    /// The round ended because the target was found.
+    TargetFound,
+    /// The round ended because the time exceeded the configured maximum round time.
+    RoundTimeLimitExceeded,
+    /// The round ended because the probe of the fixed ttl, short of the target, was answered.
+    FixedTtlAnswered,
+}
+
+/// A token with which to cancel a trace from another thread.
+///
+/// A cancelled trace ends after the probe being sent and the response being awaited, if any, and so within the read
+/// timeout of the channel.  A cancelled `AsyncTracer` is woken and ends at once.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<Cancellation>);
+
+#[derive(Debug, Default)]
+struct Cancellation {
+    cancelled: AtomicBool,
+    #[cfg(feature = "tokio")]
+    notify: ::tokio::sync::Notify,
+}
+
+impl CancellationToken {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel the trace.
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::Relaxed);
+        #[cfg(feature = "tokio")]
+        self.0.notify.notify_waiters();
+    }
+
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Wait until the trace is cancelled.
+    #[cfg(feature = "tokio")]
+    pub async fn cancelled(&self) {
+        // The waiter is registered before the token is checked, such that a cancellation in between is not missed.
+        let notified = self.0.notify.notified();
+        if !self.is_cancelled() {
+            notified.await;
+        }
+    }
+}
+
+/// The parameters of a trace which may be changed whilst it runs, such as from a UI, by another thread.
+///
+/// The tracer reads the parameters at the start of each round and so a change takes effect from the next round, the
+/// round in progress being completed with the parameters it started with.  Only the range of ttl probed may be
+/// changed at present.
+#[derive(Debug, Clone)]
+pub struct RoundParameters(Arc<Parameters>);
+
+#[derive(Debug)]
+struct Parameters {
+    /// The first and max ttl, packed into the high and low bytes such that they are always read and written together.
+    ttl_range: AtomicU16,
+}
+
+impl RoundParameters {
+    /// The parameters of a trace which probes each ttl from `first_ttl` to `max_ttl`.
+    pub fn new(first_ttl: TimeToLive, max_ttl: TimeToLive) -> TraceResult<Self> {
+        validate_ttl_range(first_ttl, max_ttl)?;
+        Ok(Self(Arc::new(Parameters {
+            ttl_range: AtomicU16::new(pack_ttl_range(first_ttl, max_ttl)),
+        })))
+    }
+
+    /// The first and max ttl probed each round.
+    #[must_use]
+    pub fn ttl_range(&self) -> (TimeToLive, TimeToLive) {
+        let [first_ttl, max_ttl] = self.0.ttl_range.load(Ordering::Relaxed).to_be_bytes();
+        (TimeToLive(first_ttl), TimeToLive(max_ttl))
+    }
+
+    /// Probe each ttl from `first_ttl` to `max_ttl` from the next round.
+    ///
+    /// The range must not be empty and must be within `1..=254`, otherwise it is refused and the range is unchanged.
+    pub fn set_ttl_range(&self, first_ttl: TimeToLive, max_ttl: TimeToLive) -> TraceResult<()> {
+        validate_ttl_range(first_ttl, max_ttl)?;
+        self.0
+            .ttl_range
+            .store(pack_ttl_range(first_ttl, max_ttl), Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+fn validate_ttl_range(first_ttl: TimeToLive, max_ttl: TimeToLive) -> TraceResult<()> {
+    if first_ttl.0 == 0 || max_ttl.0 > MAX_TTL {
+        Err(TracerError::BadConfig(format!(
+            "ttl range ({}..={}) must be within 1..={MAX_TTL}",
+            first_ttl.0, max_ttl.0
+        )))
+    } else if first_ttl > max_ttl {
+        Err(TracerError::BadConfig(format!(
+            "first_ttl ({}) > max_ttl ({})",
+            first_ttl.0, max_ttl.0
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+const fn pack_ttl_range(first_ttl: TimeToLive, max_ttl: TimeToLive) -> u16 {
+    u16::from_be_bytes([first_ttl.0, max_ttl.0])
+}
+
+/// The random offsets of the start of each round, drawn uniformly from zero up to `max`, such that the rounds of a
+/// trace do not fall into step with other periodic traffic or with the rate limiting of a hop.
+///
+/// The offsets are drawn from a generator seeded with `seed`, such that a trace with the same seed is offset alike.
+#[derive(Debug, Clone)]
+struct RoundJitter {
+    max: Duration,
+    rng: SmallRng,
+}
+
+impl RoundJitter {
+    fn new(max: Duration, seed: u64) -> Self {
+        Self {
+            max,
+            rng: SmallRng::seed_from_u64(seed),
+        }
+    }
+
+    /// The offset of the start of the next round.
+    fn next_offset(&mut self) -> Duration {
+        if self.max.is_zero() {
+            Duration::ZERO
+        } else {
+            self.rng.gen_range(Duration::ZERO..=self.max)
+        }
+    }
+}
+
+/// Trace a path to a target.
+#[derive(Debug, Clone)]
+pub struct Tracer<F> {
+    target_addrs: Vec<IpAddr>,
+    protocol: TracerProtocol,
+    trace_identifier: TraceId,
+    max_rounds: Option<MaxRounds>,
+    first_ttl: TimeToLive,
+    max_ttl: TimeToLive,
+    fixed_ttl: bool,
+    grace_duration: Duration,
+    max_inflight: MaxInflight,
+    adaptive_inflight: bool,
+    ttl_margin: TimeToLive,
+    initial_sequence: Sequence,
+    min_round_duration: Duration,
+    max_round_duration: Duration,
+    round_jitter: Duration,
+    round_jitter_seed: u64,
+    cancellation: Option<CancellationToken>,
+    parameters: Option<RoundParameters>,
+    publish: F,
+}
+
+impl<F: Fn(&TracerRound<'_>)> Tracer<F> {
+    pub fn new(config: &TracerConfig, publish: F) -> Self {
+        Self::from_config(config, publish)
+    }
+
+    /// Run a continuous trace and publish results.
+    ///
+    /// TODO describe algorithm
+    pub fn trace<N: Network>(self, mut network: N) -> TraceResult<()> {
+        let mut state = self.new_state();
+        while !state.finished(self.max_rounds) && !self.is_cancelled() {
+            self.send_request(&mut state, |probe| network.send_probe(probe))?;
+            self.recv_response(&mut network, &mut state)?;
+            self.update_round(&mut state, network.metrics(), network.flow_key());
+        }
+        Ok(())
+    }
+
+    /// Read and process the next incoming `ICMP` packet.
+    fn recv_response<N: Network>(&self, network: &mut N, st: &mut TracerState) -> TraceResult<()> {
+        let _span = trace_span!("tracer.recv_response").entered();
+        let next = network.recv_probe_within(self.recv_timeout(st))?;
+        if let Some(attribution) = self.process_response(st, next) {
+            network.record_attribution(attribution);
+        }
+        Ok(())
+    }
+
+    /// Check if the round is complete and publish the results, along with the `channel_metrics` as of its completion
+    /// and the `flow_key` of its first probe.
+    fn update_round(
+        &self,
+        st: &mut TracerState,
+        channel_metrics: Option<ChannelMetrics>,
+        flow_key: Option<FlowKey>,
+    ) {
+        if self.is_round_complete(st) {
+            self.publish_trace(st, channel_metrics, flow_key);
+            self.advance_round(st);
+        }
+    }
+
+    /// Publish details of all `Probe` in the completed round.
+    fn publish_trace(
+        &self,
+        state: &TracerState,
+        channel_metrics: Option<ChannelMetrics>,
+        flow_key: Option<FlowKey>,
+    ) {
+        (self.publish)(&self.round(state, channel_metrics, flow_key));
+    }
+}
+
+impl<F> Tracer<F> {
+    fn from_config(config: &TracerConfig, publish: F) -> Self {
+        Self {
+            target_addrs: config.target_addrs.clone(),
+            protocol: config.protocol,
+            trace_identifier: config.trace_identifier,
+            max_rounds: config.max_rounds,
+            first_ttl: config.first_ttl,
+            max_ttl: config.max_ttl,
+            fixed_ttl: config.fixed_ttl,
+            grace_duration: config.grace_duration,
+            max_inflight: config.max_inflight,
+            adaptive_inflight: config.adaptive_inflight,
+            ttl_margin: config.ttl_margin,
+            initial_sequence: config.initial_sequence,
+            min_round_duration: config.min_round_duration,
+            max_round_duration: config.max_round_duration,
+            round_jitter: config.round_jitter,
+            round_jitter_seed: config.round_jitter_seed,
+            cancellation: None,
+            parameters: None,
+            publish,
+        }
+    }
+
+    /// End the trace once `cancellation` is cancelled.
+    #[must_use]
+    pub fn with_cancellation(self, cancellation: CancellationToken) -> Self {
+        Self {
+            cancellation: Some(cancellation),
+            ..self
+        }
+    }
+
+    /// Read the range of ttl probed from `parameters` at the start of each round, rather than from the config.
+    #[must_use]
+    pub fn with_parameters(self, parameters: RoundParameters) -> Self {
+        Self {
+            parameters: Some(parameters),
+            ..self
+        }
+    }
+
+    /// The first and max ttl of the next round.
+    fn ttl_range(&self) -> (TimeToLive, TimeToLive) {
+        self.parameters
+            .as_ref()
+            .map_or((self.first_ttl, self.max_ttl), RoundParameters::ttl_range)
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+    }
+
+    /// The state of a trace which has not yet sent a probe.
+    fn new_state(&self) -> TracerState {
+        let (first_ttl, max_ttl) = self.ttl_range();
+        TracerState::new(first_ttl, max_ttl, self.initial_sequence)
+            .with_targets(&self.target_addrs)
+            .with_inflight_window(self.max_inflight)
+            .with_round_jitter(RoundJitter::new(self.round_jitter, self.round_jitter_seed))
+    }
+
+    /// Advance to the next round, adapting the in-flight window to the round which just completed if it is adaptive.
+    fn advance_round(&self, st: &mut TracerState) {
+        if self.adaptive_inflight {
+            st.adapt_inflight_window(self.max_inflight, ADAPTIVE_INFLIGHT_LOSS_PCT);
+        }
+        let (first_ttl, max_ttl) = self.ttl_range();
+        st.advance_round(first_ttl, max_ttl);
+    }
+
+    /// Send the next probe if required.
+    ///
+    /// Send a `Probe` for the next time-to-live (ttl) if all of the following are true:
+    ///
+    /// 1 - the target host has not been found in this round
+    /// 2 - the next ttl is not greater than the maximum allowed ttl
+    /// 3 - if the target ttl of the target is known:
+    ///       - the next ttl is not greater than the ttl of the target host observed from the prior round plus the ttl
+    ///         margin, such that a path which has grown is noticed
+    ///     otherwise:
+    ///       - the number of unknown-in-flight probes is lower than the in-flight window, which is the maximum
+    ///         allowed unless it is adaptive
+    fn send_request(
+        &self,
+        st: &mut TracerState,
+        mut send_probe: impl FnMut(Probe) -> TraceResult<()>,
+    ) -> TraceResult<()> {
+        let _span = trace_span!("tracer.send_request").entered();
+        if self.can_send(st) {
+            match self.protocol {
+                TracerProtocol::Icmp | TracerProtocol::Udp => {
+                    let probe = st.next_probe();
+                    if let Err(err) = send_probe(probe) {
+                        Self::fail_probe(st, probe, err)?;
+                    }
+                }
+                TracerProtocol::Tcp => {
+                    let mut probe = if st.round_has_capacity() {
+                        st.next_probe()
+                    } else {
+                        return Err(TracerError::InsufficientCapacity);
+                    };
+                    while let Err(err) = send_probe(probe) {
+                        match err {
+                            TracerError::AddressNotAvailable(_) => {
+                                if st.round_has_capacity() {
+                                    probe = st.reissue_probe();
+                                } else {
+                                    return Err(TracerError::InsufficientCapacity);
+                                }
+                            }
+                            other => return Self::fail_probe(st, probe, other),
+                        }
+                    }
+                }
+            };
+        }
+        Ok(())
+    }
+
+    /// Mark `probe` as failed to send with `err`, if the trace may continue past it, such that the failure is counted
+    /// against its hop rather than taken to be loss.
+    fn fail_probe(st: &mut TracerState, probe: Probe, err: TracerError) -> TraceResult<()> {
+        if !err.is_retryable() {
+            return Err(err);
+        }
+        debug!(
+            ttl = probe.ttl.0,
+            error = err.render(),
+            "probe failed to send"
+        );
+        st.fail_probe(probe.sequence, SendFailure::new(&err));
+        Ok(())
+    }
+
+    /// Can the probe of the next ttl be sent now?
+    fn can_send(&self, st: &TracerState) -> bool {
+        let can_send_ttl = if let Some(target_ttl) = st.target_ttl() {
+            st.ttl().0 <= target_ttl.0.saturating_add(self.ttl_margin.0)
+        } else {
+            st.ttl() - st.max_received_ttl().unwrap_or_default()
+                < TimeToLive(st.inflight_window().0)
+        };
+        st.round_started() && !st.target_found() && st.ttl() <= st.max_ttl() && can_send_ttl
+    }
+
+    /// How long to wait for the next incoming packet.
+    ///
+    /// There is no wait if the next probe can be sent now, otherwise the wait ends once the round starts, if its start
+    /// is jittered and it has yet to, or once the round could be complete, such that neither the next probe nor the
+    /// next round is delayed by the wait, though it is never shorter than `MIN_RECV_TIMEOUT` such that the tracer does
+    /// not spin whilst the round completes.
+    fn recv_timeout(&self, st: &TracerState) -> Duration {
+        if self.can_send(st) {
+            return Duration::ZERO;
+        }
+        let now = Instant::now();
+        if now < st.round_start() {
+            return (st.round_start() - now).max(MIN_RECV_TIMEOUT);
+        }
+        let elapsed = |since: Instant| now.saturating_duration_since(since);
+        let round_max = self
+            .max_round_duration
+            .saturating_sub(elapsed(st.round_start()));
+        if self.is_answered(st) {
+            let round_min = self
+                .min_round_duration
+                .saturating_sub(elapsed(st.round_start()));
+            let grace = st.received_time().map_or(self.grace_duration, |received| {
+                self.grace_duration.saturating_sub(elapsed(received))
+            });
+            round_max.min(round_min.max(grace))
+        } else {
+            round_max
+        }
+        .max(MIN_RECV_TIMEOUT)
+    }
+
+    /// Process the next incoming `ICMP` packet, if any.
+    ///
+    /// We allow multiple probes to be in-flight at any time and we cannot guaranteed that responses will be
+    /// received in-order.  We therefore maintain a buffer which holds details of each `Probe` which is
+    /// indexed by the offset of the sequence number from the sequence number at the beginning of the round.  The
+    /// sequence number is set in the outgoing `ICMP` `EchoRequest` (or `UDP` / `TCP`) packet and returned in both
+    /// the `TimeExceeded` and `EchoReply` responses.
+    ///
+    /// Each incoming `ICMP` packet contains the original `ICMP` `EchoRequest` packet from which we can read the
+    /// `identifier` that we set which we can now validate to ensure we only process responses which correspond to
+    /// packets sent from this process.  For The `UDP` and `TCP` protocols, only packets destined for our src port will
+    /// be delivered to us by the OS and so no other `identifier` is needed and so we allow the special case value of 0.
+    ///
+    /// When we process an `EchoReply` from the target host we extract the time-to-live from the corresponding
+    /// original `EchoRequest`.  Note that this may not be the greatest time-to-live that was sent in the round as
+    /// the algorithm will send `EchoRequest` with larger time-to-live values before the `EchoReply` is received.
+    ///
+    /// A response is attributed to its probe only if it is of this trace, of the current round and the first to the
+    /// probe, otherwise it is discarded and the reason returned such that the channel may count it.
+    fn process_response(
+        &self,
+        st: &mut TracerState,
+        next: Option<ProbeResponse>,
+    ) -> Option<Attribution> {
+        let next = next?;
+        let data = *next.data();
+        let sequence = Sequence(data.sequence);
+        let attribution = if !self.check_trace_id(TraceId(data.identifier)) {
+            Attribution::Ignored
+        } else if !st.in_round(sequence) {
+            Attribution::Late
+        } else if st.probe_at(sequence).status == ProbeStatus::Complete {
+            Attribution::Duplicate
+        } else {
+            Attribution::Matched
+        };
+        if attribution != Attribution::Matched {
+            return Some(attribution);
+        }
+        let (received, received_at) = (data.recv, data.recv_at);
+        let host = data.addr;
+        match next {
+            ProbeResponse::TimeExceeded(_) => {
+                let is_target = st.target() == Some(host);
+                st.complete_probe_time_exceeded(sequence, host, received, received_at, is_target);
+            }
+            ProbeResponse::DestinationUnreachable(_, code, next_hop_mtu) => {
+                st.complete_probe_unreachable(
+                    sequence,
+                    host,
+                    received,
+                    received_at,
+                    code,
+                    next_hop_mtu,
+                );
+            }
+            ProbeResponse::EchoReply(_) => {
+                st.complete_probe_echo_reply(sequence, host, received, received_at);
+            }
+            ProbeResponse::TimestampReply(_, timestamps) => {
+                st.complete_probe_timestamp_reply(
+                    sequence,
+                    host,
+                    received,
+                    received_at,
+                    timestamps,
+                );
+            }
+            ProbeResponse::TcpReply(_) | ProbeResponse::TcpRefused(_) => {
+                st.complete_probe_other(sequence, host, received, received_at);
+            }
+        }
+        st.set_response_data(sequence, &data);
+        Some(attribution)
+    }
+
+    /// Is the round complete?
+    ///
+    /// A round is considered to be complete when:
+    ///
+    /// 1 - the round has exceed the minimum round duration AND
+    /// 2 - the duration since the last packet was received exceeds the grace period AND
+    /// 3 - either:
+    ///     A - the target has been found, or the probe of the fixed ttl has been answered, OR
+    ///     B - the round has exceeded the maximum round duration
+    fn is_round_complete(&self, st: &TracerState) -> bool {
+        let now = Instant::now();
+        let round_duration = now.saturating_duration_since(st.round_start());
+        let round_min = round_duration > self.min_round_duration;
+        let grace_exceeded = exceeds(st.received_time(), now, self.grace_duration);
+        let round_max = round_duration > self.max_round_duration;
+        round_min && grace_exceeded && self.is_answered(st) || round_max
+    }
+
+    /// Has the round been answered, i.e. has the target been found or, if every probe is sent with a fixed ttl, has
+    /// the probe of the round been sent and answered?
+    fn is_answered(&self, st: &TracerState) -> bool {
+        st.target_found() || self.fixed_ttl && st.ttl() > st.max_ttl() && st.all_answered()
+    }
+
+    /// The details of all `Probe` in the completed round.
+    ///
+    /// If the round completed without receiving an `EchoReply` from the target host then we also publish the next
+    /// `Probe` which is assumed to represent the TTL of the target host.
+    fn round<'a>(
+        &self,
+        state: &'a TracerState,
+        channel_metrics: Option<ChannelMetrics>,
+        flow_key: Option<FlowKey>,
+    ) -> TracerRound<'a> {
+        let max_received_ttl = if let Some(target_ttl) = state.target_ttl() {
+            target_ttl
+        } else if self.fixed_ttl {
+            state.max_ttl()
+        } else {
+            state
+                .max_received_ttl()
+                .map_or(TimeToLive(0), |max_received_ttl| {
+                    let max_sent_ttl = state.ttl() - TimeToLive(1);
+                    max_sent_ttl.min(max_received_ttl + TimeToLive(1))
+                })
+        };
+        let probes = state.probes();
+        let largest_ttl = max_received_ttl;
+        let _span = debug_span!(
+            "tracer.publish_round",
+            probes = probes.len(),
+            largest_ttl = largest_ttl.0
+        )
+        .entered();
+        trace!(target: COUNTER_TARGET, round_probes = probes.len());
+        let reason = if state.target_found() {
+            CompletionReason::TargetFound
+        } else if self.is_answered(state) {
+            CompletionReason::FixedTtlAnswered
+        } else {
+            CompletionReason::RoundTimeLimitExceeded
+        };
+        let duration = state.round_start().elapsed();
+        let mut round = TracerRound::new(probes, largest_ttl, reason).with_duration(duration);
+        if let Some(started) = SystemTime::now().checked_sub(duration) {
+            round = round.with_started(started);
+        }
+        if let Some(channel_metrics) = channel_metrics {
+            round = round.with_channel_metrics(channel_metrics);
+        }
+        if let Some(flow_key) = flow_key {
+            round = round.with_flow_key(flow_key);
+        }
+        if self.adaptive_inflight {
+            round.with_inflight_window(state.inflight_window())
+        } else {
+            round
+        }
+    }
+
+    /// Check if the `TraceId` matches the expected value for this tracer.
+    ///
+    /// A special value of `0` is accepted for `udp` and `tcp` which do not have an identifier.
+    fn check_trace_id(&self, trace_id: TraceId) -> bool {
+        self.trace_identifier == trace_id || trace_id == TraceId(0)
+    }
+}
+
+/// Mutable state needed for the tracing algorithm.
+///
+/// This is contained within a sub-module to ensure that mutations are only performed via methods on the
+/// `TracerState` struct.
+mod state {
+    use super::RoundJitter;
+    use crate::types::{MaxInflight, MaxRounds, Round, Sequence, TimeToLive};
+    use crate::{
+        IcmpPacketType, IcmpTimestamps, Probe, ProbeResponseData, ProbeStatus, SendFailure,
+    };
+    use std::net::IpAddr;
+    use std::time::{Duration, Instant, SystemTime};
+
+    /// The maximum number of `Probe` entries in the buffer.
+    ///
+    /// This is larger than maximum number of time-to-live (TTL) we can support to allow for skipped sequences.
+    const BUFFER_SIZE: u16 = 1024;
+
+    /// The maximum sequence number.
+    ///
+    /// The sequence number is only ever wrapped between rounds and so we need to ensure that there are enough sequence
+    /// numbers for a complete round.
+    ///
+    /// A sequence number can be skipped if, for example, the port for that sequence number cannot be bound as it is
+    /// already in use.
+    ///
+    /// To ensure each `Probe` is in the correct place in the buffer (i.e. the index into the buffer is always
+    /// `Probe.sequence - round_sequence`), when we skip a sequence we leave the skipped `Probe` in-place and use the
+    /// next slot for the next sequence.
+    ///
+    /// We cap the number of sequences that can potentially be skipped in a round to ensure that sequence number does
+    /// not even need to wrap around during a round.
+    ///
+    /// We only ever send `ttl` in the range 1..255 and so we may use all buffer capacity, except the minimum needed to
+    /// send up to a max `ttl` of 255 (a `ttl` of 0 is never sent).
+    const MAX_SEQUENCE: Sequence = Sequence(u16::MAX - BUFFER_SIZE);
+
+    /// Mutable state needed for the tracing algorithm.
+    #[derive(Debug)]
+    pub struct TracerState {
+        /// The state of all `Probe` requests and responses, which is boxed as it is too large to be moved cheaply.
+        buffer: Box<[Probe; BUFFER_SIZE as usize]>,
+        /// The initial sequence number configuration, used to reset sequence when it wraps around.
+        initial_sequence: Sequence,
+        /// An increasing sequence number for every `EchoRequest`.
+        sequence: Sequence,
+        /// The starting sequence number of the current round.
+        round_sequence: Sequence,
+        /// The time-to-live for the _next_ `EchoRequest` packet to be sent.
+        ttl: TimeToLive,
+        /// The largest time-to-live probed in the current round.
+        max_ttl: TimeToLive,
+        /// The current round.
+        round: Round,
+        /// The instant at which the current round started, or will start if its start is jittered.
+        round_start: Instant,
+        /// Did we receive an `EchoReply` from the target host in this round?
+        target_found: bool,
+        /// The maximum time-to-live echo response packet we have received.
+        max_received_ttl: Option<TimeToLive>,
+        /// The observed time-to-live of the `EchoReply` from the target host.
+        ///
+        /// Note that this is _not_ reset each round and that it can also _change_ over time, including going _down_ as
+        /// responses can be are received out-of-order.
+        target_ttl: Option<TimeToLive>,
+        /// The instant at which the latest echo response packet of the round was received.
+        received_time: Option<Instant>,
+        /// The addresses of the target, one of which is probed in each round in turn, along with the `target_ttl` of
+        /// each as of the last round in which it was probed.
+        targets: Vec<(IpAddr, Option<TimeToLive>)>,
+        /// The number of probes of unknown ttl which may be in flight at once.
+        inflight_window: MaxInflight,
+        /// Whether a response has been received from each ttl in any prior round.
+        responsive: [bool; 256],
+        /// The random offsets of the start of each round.
+        jitter: RoundJitter,
+    }
+
+    impl TracerState {
+        pub fn new(first_ttl: TimeToLive, max_ttl: TimeToLive, initial_sequence: Sequence) -> Self {
+            Self {
+                buffer: Box::new([Probe::default(); BUFFER_SIZE as usize]),
+                initial_sequence,
+                sequence: initial_sequence,
+                round_sequence: initial_sequence,
+                ttl: first_ttl,
+                max_ttl,
+                round: Round(0),
+                round_start: Instant::now(),
+                target_found: false,
+                max_received_ttl: None,
+                target_ttl: None,
+                received_time: None,
+                targets: vec![],
+                inflight_window: MaxInflight(1),
+                responsive: [false; 256],
+                jitter: RoundJitter::new(Duration::ZERO, 0),
+            }
+        }
+
+        /// Probe the `targets` in turn, one in each round, from the first.
+        pub fn with_targets(self, targets: &[IpAddr]) -> Self {
+            Self {
+                targets: targets.iter().map(|&target| (target, None)).collect(),
+                ..self
+            }
+        }
+
+        /// Offset the start of each round, including the first, by `jitter`.
+        pub fn with_round_jitter(self, mut jitter: RoundJitter) -> Self {
+            Self {
+                round_start: self.round_start + jitter.next_offset(),
+                jitter,
+                ..self
+            }
+        }
+
+        /// Start with an in-flight window of `inflight_window`.
+        pub fn with_inflight_window(self, inflight_window: MaxInflight) -> Self {
+            Self {
+                inflight_window,
+                ..self
+            }
+        }
+
+        /// The address of the target probed in the current round, if known.
+        pub fn target(&self) -> Option<IpAddr> {
+            self.target_index().map(|index| self.targets[index].0)
+        }
+
+        fn target_index(&self) -> Option<usize> {
+            (!self.targets.is_empty()).then(|| self.round.0 % self.targets.len())
+        }
+
+        /// Get a slice of `Probe` for the current round.
+        pub fn probes(&self) -> &[Probe] {
+            let round_size = self.sequence - self.round_sequence;
+            &self.buffer[..round_size.0 as usize]
+        }
+
+        /// Get the `Probe` for `sequence`
+        pub fn probe_at(&self, sequence: Sequence) -> Probe {
+            self.buffer[usize::from(sequence - self.round_sequence)]
+        }
+
+        pub const fn ttl(&self) -> TimeToLive {
+            self.ttl
+        }
+
+        pub const fn max_ttl(&self) -> TimeToLive {
+            self.max_ttl
+        }
+
+        pub const fn round_start(&self) -> Instant {
+            self.round_start
+        }
+
+        /// Has the current round started, i.e. has any jitter of its start elapsed?
+        pub fn round_started(&self) -> bool {
+            Instant::now() >= self.round_start
+        }
+
+        pub const fn target_found(&self) -> bool {
+            self.target_found
+        }
+
+        pub const fn max_received_ttl(&self) -> Option<TimeToLive> {
+            self.max_received_ttl
+        }
+
+        pub const fn target_ttl(&self) -> Option<TimeToLive> {
+            self.target_ttl
+        }
+
+        pub const fn received_time(&self) -> Option<Instant> {
+            self.received_time
+        }
+
+        pub const fn inflight_window(&self) -> MaxInflight {
+            self.inflight_window
+        }
+
+        /// Have all the probes sent in the current round been answered?
+        pub fn all_answered(&self) -> bool {
+            let probes = self.probes();
+            !probes.is_empty()
+                && probes
+                    .iter()
+                    .all(|probe| probe.status != ProbeStatus::Awaited)
+        }
+
+        /// Is `sequence` in the current round?
+        pub fn in_round(&self, sequence: Sequence) -> bool {
+            sequence >= self.round_sequence && sequence.0 - self.round_sequence.0 < BUFFER_SIZE
+        }
+
+        /// Do we have capacity in the current round for another sequence?
+        pub fn round_has_capacity(&self) -> bool {
+            let round_size = self.sequence - self.round_sequence;
+            round_size.0 < BUFFER_SIZE
+        }
+
+        /// Have all round completed?
+        pub fn finished(&self, max_rounds: Option<MaxRounds>) -> bool {
+            match max_rounds {
+                None => false,
+                Some(max_rounds) => self.round.0 > max_rounds.0,
+            }
+        }
+
+        /// Create and return the next `Probe` at the current `sequence` and `ttl`.
+        ///
+        /// We post-increment `ttl` here and so in practice we only allow `ttl` values in the range `1..254` to allow
+        /// us to use a `u8`.
+        pub fn next_probe(&mut self) -> Probe {
+            let probe = self.new_probe(self.ttl);
+            self.buffer[usize::from(self.sequence - self.round_sequence)] = probe;
+            debug_assert!(self.ttl < TimeToLive(u8::MAX));
+            self.ttl += TimeToLive(1);
+            debug_assert!(self.sequence < Sequence(u16::MAX));
+            self.sequence += Sequence(1);
+            probe
+        }
+
+        /// Re-issue the `Probe` with the next sequence number.
+        ///
+        /// This will mark the `Probe` at the previous `sequence` as skipped and re-create it with the previous `ttl`
+        /// and the current `sequence`.
+        ///
+        /// For example, if the sequence is `4` and the `ttl` is `5` prior to calling this method then afterwards:
+        /// - The `Probe` at sequence `3` will be reset to default values (i.e. `NotSent` status)
+        /// - A new `Probe` will be created at sequence `4` with a `ttl` of `5`
+        pub fn reissue_probe(&mut self) -> Probe {
+            self.buffer[usize::from(self.sequence - self.round_sequence) - 1] = Probe::default();
+            let probe = self.new_probe(self.ttl - TimeToLive(1));
+            self.buffer[usize::from(self.sequence - self.round_sequence)] = probe;
+            debug_assert!(self.sequence < Sequence(u16::MAX));
+            self.sequence += Sequence(1);
+            probe
+        }
+
+        /// A `Probe` of `ttl` at the current `sequence`, to the target of the current round if there are several.
+        fn new_probe(&self, ttl: TimeToLive) -> Probe {
+            debug_assert!(ttl > TimeToLive(0));
+            let probe = Probe::new(self.sequence, ttl, self.round, SystemTime::now())
+                .with_sent_at(Instant::now());
+            match self.target() {
+                Some(target) if self.targets.len() > 1 => probe.with_target(target),
+                _ => probe,
+            }
+        }
+
+        /// Mark the `Probe` at `sequence` as failed to send for the reason `failure`.
+        pub fn fail_probe(&mut self, sequence: Sequence, failure: SendFailure) {
+            let index = usize::from(sequence - self.round_sequence);
+            self.buffer[index] = self.buffer[index].with_status(ProbeStatus::Failed(failure));
+        }
+
+        /// Mark the `Probe` at `sequence` completed as `TimeExceeded` and update the round state.
+        pub fn complete_probe_time_exceeded(
+            &mut self,
+            sequence: Sequence,
+            host: IpAddr,
+            received: SystemTime,
+            received_at: Instant,
+            is_target: bool,
+        ) {
+            self.complete_probe(
+                sequence,
+                IcmpPacketType::TimeExceeded,
+                host,
+                received,
+                received_at,
+                is_target,
+            );
+        }
+
+        /// Mark the `Probe` at `sequence` completed as `Unreachable` with ICMP `code` and update the round state.
+        pub fn complete_probe_unreachable(
+            &mut self,
+            sequence: Sequence,
+            host: IpAddr,
+            received: SystemTime,
+            received_at: Instant,
+            code: u8,
+            next_hop_mtu: Option<u16>,
+        ) {
+            self.complete_probe(
+                sequence,
+                IcmpPacketType::Unreachable(code),
+                host,
+                received,
+                received_at,
+                true,
+            );
+            let index = usize::from(sequence - self.round_sequence);
+            self.buffer[index] = self.buffer[index].with_next_hop_mtu(next_hop_mtu);
+        }
+
+        /// Mark the `Probe` at `sequence` completed as `EchoReply` and update the round state.
+        pub fn complete_probe_echo_reply(
+            &mut self,
+            sequence: Sequence,
+            host: IpAddr,
+            received: SystemTime,
+            received_at: Instant,
+        ) {
+            self.complete_probe(
+                sequence,
+                IcmpPacketType::EchoReply,
+                host,
+                received,
+                received_at,
+                true,
+            );
+        }
+
+        /// Mark the `Probe` at `sequence` completed as `TimestampReply` with the `timestamps` it carries and update the
+        /// round state.
+        pub fn complete_probe_timestamp_reply(
+            &mut self,
+            sequence: Sequence,
+            host: IpAddr,
+            received: SystemTime,
+            received_at: Instant,
+            timestamps: IcmpTimestamps,
+        ) {
+            self.complete_probe(
+                sequence,
+                IcmpPacketType::TimestampReply,
+                host,
+                received,
+                received_at,
+                true,
+            );
+            let index = usize::from(sequence - self.round_sequence);
+            self.buffer[index] = self.buffer[index].with_timestamps(timestamps);
+        }
+
+        /// Mark the `Probe` at `sequence` completed as `NotApplicable` and update the round state.
+        pub fn complete_probe_other(
+            &mut self,
+            sequence: Sequence,
+            host: IpAddr,
+            received: SystemTime,
+            received_at: Instant,
+        ) {
+            self.complete_probe(
+                sequence,
+                IcmpPacketType::NotApplicable,
+                host,
+                received,
+                received_at,
+                true,
+            );
+        }
+
+        /// Record the time-to-live with which the response to the `Probe` at `sequence` was received and the size of
+        /// the ICMP error and quote it carried, if known.
+        pub fn set_response_data(&mut self, sequence: Sequence, data: &ProbeResponseData) {
+            let index = usize::from(sequence - self.round_sequence);
+            self.buffer[index] = self.buffer[index]
+                .with_recv_ttl(data.recv_ttl)
+                .with_quote(data.quote)
+                .with_responder_ports(data.responder_ports);
+        }
+
+        /// Update the state of a `Probe` and the trace.
+        ///
+        /// We want to update:
+        ///
+        /// - the `target_ttl` to be the time-to-live of the `Probe` request from the target
+        /// - the `max_received_ttl` we have observed this round
+        /// - the latest packet `received_time` in this round
+        /// - whether the target has been found in this round
+        ///
+        /// The ICMP replies may arrive out-of-order and so we must be careful here to avoid overwriting the state with
+        /// stale values.  We may also receive multiple replies from the target host with differing time-to-live values
+        /// and so must ensure we use the time-to-live with the lowest sequence number.
+        fn complete_probe(
+            &mut self,
+            sequence: Sequence,
+            icmp_packet_type: IcmpPacketType,
+            host: IpAddr,
+            received: SystemTime,
+            received_at: Instant,
+            is_target: bool,
+        ) {
+            // Retrieve and update the `Probe` at `sequence`.
+            let probe = self
+                .probe_at(sequence)
+                .with_status(ProbeStatus::Complete)
+                .with_icmp_packet_type(icmp_packet_type)
+                .with_host(host)
+                .with_received(received)
+                .with_received_at(received_at);
+            self.buffer[usize::from(sequence - self.round_sequence)] = probe;
+
+            // If this `Probe` found the target then we set the `target_tll` if not already set, being careful to
+            // account for `Probes` being received out-of-order.
+            //
+            // If this `Probe` did not find the target but has a ttl that is greater or equal to the target ttl (if
+            // known) then we reset the target ttl to None.  This is to support Equal Cost Multi-path Routing (ECMP)
+            // cases where the number of hops to the target will vary over the lifetime of the trace.
+            self.target_ttl = if is_target {
+                match self.target_ttl {
+                    None => Some(probe.ttl),
+                    Some(target_ttl) if probe.ttl < target_ttl => Some(probe.ttl),
+                    Some(target_ttl) => Some(target_ttl),
+                }
+            } else {
+                match self.target_ttl {
+                    Some(target_ttl) if probe.ttl >= target_ttl => None,
+                    Some(target_ttl) => Some(target_ttl),
+                    None => None,
+                }
+            };
+
+            self.max_received_ttl = match self.max_received_ttl {
+                None => Some(probe.ttl),
+                Some(max_received_ttl) => Some(max_received_ttl.max(probe.ttl)),
+            };
+
+            self.received_time = Some(received_at);
+            self.target_found |= is_target;
+        }
+
+        /// Adapt the in-flight window to the loss of the round which just completed, additive increase multiplicative
+        /// decrease.
+        ///
+        /// Only the probes to hops short of the target which have responded in a prior round are counted, such that
+        /// neither a hop which never responds nor the probes sent beyond the target are taken to be loss.  The window is
+        /// halved, to no fewer than one, if more than `loss_pct` percent of these probes were not answered, and grows by
+        /// one, to no more than `max_inflight`, if all of them were.
+        pub fn adapt_inflight_window(&mut self, max_inflight: MaxInflight, loss_pct: usize) {
+            let horizon = match (self.target_found, self.target_ttl, self.max_received_ttl) {
+                (true, Some(target_ttl), _) => target_ttl,
+                (_, _, Some(max_received_ttl)) => max_received_ttl + TimeToLive(1),
+                (_, _, None) => TimeToLive(0),
+            };
+            let (sent, lost) = self
+                .probes()
+                .iter()
+                .filter(|probe| {
+                    matches!(probe.status, ProbeStatus::Awaited | ProbeStatus::Complete)
+                        && probe.ttl < horizon
+                })
+                .filter(|probe| self.responsive[usize::from(probe.ttl.0)])
+                .fold((0, 0), |(sent, lost), probe| {
+                    (
+                        sent + 1,
+                        lost + usize::from(probe.status == ProbeStatus::Awaited),
+                    )
+                });
+            let window = self.inflight_window.0;
+            self.inflight_window = if lost * 100 > sent * loss_pct {
+                MaxInflight((window / 2).max(1))
+            } else if lost == 0 {
+                MaxInflight(window.saturating_add(1).min(max_inflight.0))
+            } else {
+                self.inflight_window
+            };
+            for probe in &self.buffer[..usize::from(self.sequence - self.round_sequence)] {
+                if probe.status == ProbeStatus::Complete {
+                    self.responsive[usize::from(probe.ttl.0)] = true;
+                }
+            }
+        }
+
+        /// Advance to the next round.
+        ///
+        /// If, during the rond which just completed, we went above the max sequence number then we reset it here.
+        /// We do this here to avoid having to deal with the sequence number wrapping during a round, which is more
+        /// problematic.
+        pub fn advance_round(&mut self, first_ttl: TimeToLive, max_ttl: TimeToLive) {
+            if self.sequence >= MAX_SEQUENCE {
+                self.sequence = self.initial_sequence;
+            }
+            self.target_found = false;
+            self.round_sequence = self.sequence;
+            self.received_time = None;
+            self.round_start = Instant::now() + self.jitter.next_offset();
+            self.max_received_ttl = None;
+            if let Some(index) = self.target_index() {
+                self.targets[index].1 = self.target_ttl;
+            }
+            self.round += Round(1);
+            if let Some(index) = self.target_index() {
+                self.target_ttl = self.targets[index].1;
+            }
+            self.ttl = first_ttl;
+            self.max_ttl = max_ttl;
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::config::MAX_TTL;
+        use crate::probe::IcmpPacketType;
+        use crate::ProbeStatus;
+        use rand::Rng;
+        use std::net::{IpAddr, Ipv4Addr};
+
+        #[allow(
+            clippy::cognitive_complexity,
+            clippy::too_many_lines,
+            clippy::bool_assert_comparison
+        )]
+        #[test]
+        fn test_state() {
+            let mut state = TracerState::new(TimeToLive(1), TimeToLive(MAX_TTL), Sequence(33000));
+
+            // Validate the initial TracerState
+            assert_eq!(state.round, Round(0));
+            assert_eq!(state.sequence, Sequence(33000));
+            assert_eq!(state.round_sequence, Sequence(33000));
+            assert_eq!(state.ttl, TimeToLive(1));
+            assert_eq!(state.max_received_ttl, None);
+            assert_eq!(state.received_time, None);
+            assert_eq!(state.target_ttl, None);
+            assert_eq!(state.target_found, false);
+
+            // The initial state of the probe before sending
+            let prob_init = state.probe_at(Sequence(33000));
+            assert_eq!(prob_init.sequence, Sequence(0));
+            assert_eq!(prob_init.ttl, TimeToLive(0));
+            assert_eq!(prob_init.round, Round(0));
+            assert_eq!(prob_init.received, None);
+            assert_eq!(prob_init.host, None);
+            assert_eq!(prob_init.sent.is_some(), false);
+            assert_eq!(prob_init.status, ProbeStatus::NotSent);
+            assert_eq!(prob_init.icmp_packet_type, None);
+
+            // Prepare probe 1 (round 0, sequence 33000, ttl 1) for sending
+            let probe_1 = state.next_probe();
+            assert_eq!(probe_1.sequence, Sequence(33000));
+            assert_eq!(probe_1.ttl, TimeToLive(1));
+            assert_eq!(probe_1.round, Round(0));
+            assert_eq!(probe_1.received, None);
+            assert_eq!(probe_1.received_at, None);
+            assert_eq!(probe_1.host, None);
+            assert_eq!(probe_1.sent.is_some(), true);
+            assert_eq!(probe_1.sent_at.is_some(), true);
+            assert_eq!(probe_1.status, ProbeStatus::Awaited);
+            assert_eq!(probe_1.icmp_packet_type, None);
+
+            // Update the state of the probe 1 after receiving a TimeExceeded
+            let received_1 = SystemTime::now();
+            let received_at_1 = Instant::now();
+            let host = IpAddr::V4(Ipv4Addr::LOCALHOST);
+            state.complete_probe_time_exceeded(
+                Sequence(33000),
+                host,
+                received_1,
+                received_at_1,
+                false,
+            );
+
+            // Validate the state of the probe 1 after the update
+            let probe_1_fetch = state.probe_at(Sequence(33000));
+            assert_eq!(probe_1_fetch.sequence, Sequence(33000));
+            assert_eq!(probe_1_fetch.ttl, TimeToLive(1));
+            assert_eq!(probe_1_fetch.round, Round(0));
+            assert_eq!(probe_1_fetch.received, Some(received_1));
+            assert_eq!(probe_1_fetch.received_at, Some(received_at_1));
+            assert_eq!(probe_1_fetch.host, Some(host));
+            assert_eq!(probe_1_fetch.sent.is_some(), true);
+            assert_eq!(probe_1_fetch.status, ProbeStatus::Complete);
+            assert_eq!(
+                probe_1_fetch.icmp_packet_type,
+                Some(IcmpPacketType::TimeExceeded)
+            );
+
+            // Validate the TracerState after the update
+            assert_eq!(state.round, Round(0));
+            assert_eq!(state.sequence, Sequence(33001));
+            assert_eq!(state.round_sequence, Sequence(33000));
+            assert_eq!(state.ttl, TimeToLive(2));
+            assert_eq!(state.max_received_ttl, Some(TimeToLive(1)));
+            assert_eq!(state.received_time, Some(received_at_1));
+            assert_eq!(state.target_ttl, None);
+            assert_eq!(state.target_found, false);
+
+            // Validate the probes() iterator returns returns only a single probe
+            {
+                let mut probe_iter = state.probes().iter();
+                let probe_next1 = *probe_iter.next().unwrap();
+                assert_eq!(probe_1_fetch, probe_next1);
+                assert_eq!(None, probe_iter.next());
+            }
+
+            // Advance to the next round
+            state.advance_round(TimeToLive(1), TimeToLive(MAX_TTL));
+
+            // Validate the TracerState after the round update
+            assert_eq!(state.round, Round(1));
+            assert_eq!(state.sequence, Sequence(33001));
+            assert_eq!(state.round_sequence, Sequence(33001));
+            assert_eq!(state.ttl, TimeToLive(1));
+            assert_eq!(state.max_received_ttl, None);
+            assert_eq!(state.received_time, None);
+            assert_eq!(state.target_ttl, None);
+            assert_eq!(state.target_found, false);
+
+            // Prepare probe 2 (round 1, sequence 33001, ttl 1) for sending
+            let probe_2 = state.next_probe();
+            assert_eq!(probe_2.sequence, Sequence(33001));
+            assert_eq!(probe_2.ttl, TimeToLive(1));
+            assert_eq!(probe_2.round, Round(1));
+            assert_eq!(probe_2.received, None);
+            assert_eq!(probe_2.host, None);
+            assert_eq!(probe_2.sent.is_some(), true);
+            assert_eq!(probe_2.status, ProbeStatus::Awaited);
+            assert_eq!(probe_2.icmp_packet_type, None);
+
+            // Prepare probe 3 (round 1, sequence 33002, ttl 2) for sending
+            let probe_3 = state.next_probe();
+            assert_eq!(probe_3.sequence, Sequence(33002));
+            assert_eq!(probe_3.ttl, TimeToLive(2));
+            assert_eq!(probe_3.round, Round(1));
+            assert_eq!(probe_3.received, None);
+            assert_eq!(probe_3.host, None);
+            assert_eq!(probe_3.sent.is_some(), true);
+            assert_eq!(probe_3.status, ProbeStatus::Awaited);
+            assert_eq!(probe_3.icmp_packet_type, None);
+
+            // Update the state of probe 2 after receiving a TimeExceeded
+            let received_2 = SystemTime::now();
+            let received_at_2 = Instant::now();
+            let host = IpAddr::V4(Ipv4Addr::LOCALHOST);
+            state.complete_probe_time_exceeded(
+                Sequence(33001),
+                host,
+                received_2,
+                received_at_2,
+                false,
+            );
+            let probe_2_recv = state.probe_at(Sequence(33001));
+
+            // Validate the TracerState after the update to probe 2
+            assert_eq!(state.round, Round(1));
+            assert_eq!(state.sequence, Sequence(33003));
+            assert_eq!(state.round_sequence, Sequence(33001));
+            assert_eq!(state.ttl, TimeToLive(3));
+            assert_eq!(state.max_received_ttl, Some(TimeToLive(1)));
+            assert_eq!(state.received_time, Some(received_at_2));
+            assert_eq!(state.target_ttl, None);
+            assert_eq!(state.target_found, false);
+
+            // Validate the probes() iterator returns the two probes in the states we expect
+            {
+                let mut probe_iter = state.probes().iter();
+                let probe_next1 = *probe_iter.next().unwrap();
+                assert_eq!(probe_2_recv, probe_next1);
+                let probe_next2 = *probe_iter.next().unwrap();
+                assert_eq!(probe_3, probe_next2);
+            }
+
+            // Update the state of probe 3 after receiving a EchoReply
+            let received_3 = SystemTime::now();
+            let received_at_3 = Instant::now();
+            let host = IpAddr::V4(Ipv4Addr::LOCALHOST);
+            state.complete_probe_echo_reply(Sequence(33002), host, received_3, received_at_3);
+            let probe_3_recv = state.probe_at(Sequence(33002));
+
+            // Validate the TracerState after the update to probe 3
+            assert_eq!(state.round, Round(1));
+            assert_eq!(state.sequence, Sequence(33003));
+            assert_eq!(state.round_sequence, Sequence(33001));
+            assert_eq!(state.ttl, TimeToLive(3));
+            assert_eq!(state.max_received_ttl, Some(TimeToLive(2)));
+            assert_eq!(state.received_time, Some(received_at_3));
+            assert_eq!(state.target_ttl, Some(TimeToLive(2)));
+            assert_eq!(state.target_found, true);
+
+            // Validate the probes() iterator returns the two probes in the states we expect
+            {
+                let mut probe_iter = state.probes().iter();
+                let probe_next1 = *probe_iter.next().unwrap();
+                assert_eq!(probe_2_recv, probe_next1);
+                let probe_next2 = *probe_iter.next().unwrap();
+                assert_eq!(probe_3_recv, probe_next2);
+            }
+        }
+
+        #[test]
+        fn test_sequence_wrap1() {
+            // Start from MAX_SEQUENCE - 1 which is (65279 - 1) == 65278
+            let initial_sequence = Sequence(65278);
+            let mut state = TracerState::new(TimeToLive(1), TimeToLive(MAX_TTL), initial_sequence);
+            assert_eq!(state.round, Round(0));
+            assert_eq!(state.sequence, initial_sequence);
+            assert_eq!(state.round_sequence, initial_sequence);
+
+            // Create a probe at seq 65278
+            assert_eq!(state.next_probe().sequence, Sequence(65278));
+            assert_eq!(state.sequence, Sequence(65279));
+
+            // Validate the probes()
+            {
+                let mut iter = state.probes().iter();
+                assert_eq!(iter.next().unwrap().sequence, Sequence(65278));
+                iter.take(BUFFER_SIZE as usize - 1)
+                    .for_each(|p| assert_eq!(p.sequence, Sequence(0)));
+            }
+
+            // Advance the round, which will wrap the sequence back to initial_sequence
+            state.advance_round(TimeToLive(1), TimeToLive(MAX_TTL));
+            assert_eq!(state.round, Round(1));
+            assert_eq!(state.sequence, initial_sequence);
+            assert_eq!(state.round_sequence, initial_sequence);
+
+            // Create a probe at seq 65278
+            assert_eq!(state.next_probe().sequence, Sequence(65278));
+            assert_eq!(state.sequence, Sequence(65279));
+
+            // Validate the probes() again
+            {
+                let mut iter = state.probes().iter();
+                assert_eq!(iter.next().unwrap().sequence, Sequence(65278));
+                iter.take(BUFFER_SIZE as usize - 1)
+                    .for_each(|p| assert_eq!(p.sequence, Sequence(0)));
+            }
+        }
+
+        #[test]
+        fn test_sequence_wrap2() {
+            let total_rounds = 2000;
+            let max_probe_per_round = 254;
+            let mut state = TracerState::new(TimeToLive(1), TimeToLive(MAX_TTL), Sequence(33000));
+            for _ in 0..total_rounds {
+                for _ in 0..max_probe_per_round {
+                    let _probe = state.next_probe();
+                }
+                state.advance_round(TimeToLive(1), TimeToLive(MAX_TTL));
+            }
+            assert_eq!(state.round, Round(2000));
+            assert_eq!(state.round_sequence, Sequence(33000));
+            assert_eq!(state.sequence, Sequence(33000));
+        }
+
+        #[test]
+        fn test_sequence_wrap3() {
+            let total_rounds = 2000;
+            let max_probe_per_round = 20;
+            let mut state = TracerState::new(TimeToLive(1), TimeToLive(MAX_TTL), Sequence(33000));
+            let mut rng = rand::thread_rng();
+            for _ in 0..total_rounds {
+                for _ in 0..rng.gen_range(0..max_probe_per_round) {
+                    state.next_probe();
+                }
+                state.advance_round(TimeToLive(1), TimeToLive(MAX_TTL));
+            }
+        }
+
+        #[test]
+        fn test_sequence_wrap_with_skip() {
+            let total_rounds = 2000;
+            let max_probe_per_round = 254;
+            let mut state = TracerState::new(TimeToLive(1), TimeToLive(MAX_TTL), Sequence(33000));
+            for _ in 0..total_rounds {
+                for _ in 0..max_probe_per_round {
+                    let _ = state.next_probe();
+                    let _ = state.reissue_probe();
+                }
+                state.advance_round(TimeToLive(1), TimeToLive(MAX_TTL));
+            }
+            assert_eq!(state.round, Round(2000));
+            assert_eq!(state.round_sequence, Sequence(56876));
+            assert_eq!(state.sequence, Sequence(56876));
+        }
+
+        #[test]
+        fn test_in_round() {
+            let state = TracerState::new(TimeToLive(1), TimeToLive(MAX_TTL), Sequence(33000));
+            assert!(state.in_round(Sequence(33000)));
+            assert!(state.in_round(Sequence(34023)));
+            assert!(!state.in_round(Sequence(34024)));
+        }
+
+        #[test]
+        fn test_round_robin_targets() {
+            let first = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+            let second = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+            let mut state = TracerState::new(TimeToLive(1), TimeToLive(MAX_TTL), Sequence(33000))
+                .with_targets(&[first, second]);
+            assert_eq!(Some(first), state.next_probe().target);
+            state.target_ttl = Some(TimeToLive(10));
+            state.advance_round(TimeToLive(1), TimeToLive(MAX_TTL));
+            assert_eq!(Some(second), state.next_probe().target);
+            assert_eq!(None, state.target_ttl);
+            state.target_ttl = Some(TimeToLive(12));
+            state.advance_round(TimeToLive(1), TimeToLive(MAX_TTL));
+            assert_eq!(Some(first), state.next_probe().target);
+            assert_eq!(Some(TimeToLive(10)), state.target_ttl);
+        }
+
+        #[test]
+        fn test_single_target_is_not_set_on_probes() {
+            let target = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+            let mut state = TracerState::new(TimeToLive(1), TimeToLive(MAX_TTL), Sequence(33000))
+                .with_targets(&[target]);
+            assert_eq!(Some(target), state.target());
+            assert_eq!(None, state.next_probe().target);
+        }
+
+        /// Trace a round of a path of five hops, the target being the last, in which the hops of `lost` ttls do not
+        /// respond, and adapt the in-flight window to it.
+        fn adapt_round(state: &mut TracerState, lost: &[u8]) -> MaxInflight {
+            let target = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 5));
+            for ttl in 1..=5 {
+                let probe = state.next_probe();
+                if !lost.contains(&ttl) {
+                    let host = IpAddr::V4(Ipv4Addr::new(10, 0, 0, ttl));
+                    let (received, received_at) = (SystemTime::now(), Instant::now());
+                    if ttl == 5 {
+                        state.complete_probe_echo_reply(
+                            probe.sequence,
+                            target,
+                            received,
+                            received_at,
+                        );
+                    } else {
+                        state.complete_probe_time_exceeded(
+                            probe.sequence,
+                            host,
+                            received,
+                            received_at,
+                            false,
+                        );
+                    }
+                }
+            }
+            state.adapt_inflight_window(MaxInflight(8), 20);
+            state.advance_round(TimeToLive(1), TimeToLive(MAX_TTL));
+            state.inflight_window()
+        }
+
+        #[test]
+        fn test_adapt_inflight_window() {
+            let mut state = TracerState::new(TimeToLive(1), TimeToLive(MAX_TTL), Sequence(33000))
+                .with_inflight_window(MaxInflight(8));
+            let windows: Vec<_> = [
+                &[2, 3][..],
+                &[],
+                &[1, 2],
+                &[1, 2, 3],
+                &[4],
+                &[],
+                &[],
+                &[],
+                &[],
+                &[],
+            ]
+            .into_iter()
+            .map(|lost| adapt_round(&mut state, lost).0)
+            .collect();
+            assert_eq!(vec![8, 8, 4, 2, 1, 2, 3, 4, 5, 6], windows);
+        }
+
+        #[test]
+        fn test_adapt_inflight_window_ignores_unresponsive_hops() {
+            let mut state = TracerState::new(TimeToLive(1), TimeToLive(MAX_TTL), Sequence(33000))
+                .with_inflight_window(MaxInflight(2));
+            for expected in 3..=6 {
+                assert_eq!(MaxInflight(expected), adapt_round(&mut state, &[2, 3]));
+            }
+        }
+    }
+}
+
+/// Returns true if the duration between start and end is grater than a duration, false otherwise.
+fn exceeds(start: Option<Instant>, end: Instant, dur: Duration) -> bool {
+    start.map_or(false, |start| end.saturating_duration_since(start) > dur)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+    use std::net::Ipv4Addr;
+    use std::rc::Rc;
+
+    /// A network which sends every probe into the void, cancelling the trace after `limit` probes.
+    struct CancellingNetwork {
+        sent: Rc<Cell<usize>>,
+        limit: usize,
+        cancellation: CancellationToken,
+    }
+
+    impl Network for CancellingNetwork {
+        fn send_probe(&mut self, _probe: Probe) -> TraceResult<()> {
+            self.sent.set(self.sent.get() + 1);
+            if self.sent.get() == self.limit {
+                self.cancellation.cancel();
+            }
+            Ok(())
+        }
+
+        fn recv_probe(&mut self) -> TraceResult<Option<ProbeResponse>> {
+            Ok(None)
+        }
+    }
+
+    #[test]
+    fn test_cancellation() {
+        let config = TracerConfig::new(
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+            TracerProtocol::Icmp,
+            None,
+            1,
+            1,
+            64,
+            Duration::from_millis(100),
+            24,
+            33000,
+            Duration::from_millis(10),
+            Duration::from_millis(1),
+            Duration::from_millis(1),
+            84,
+            0,
+        )
+        .unwrap();
+        let cancellation = CancellationToken::new();
+        let sent = Rc::new(Cell::new(0));
+        let network = CancellingNetwork {
+            sent: sent.clone(),
+            limit: 30,
+            cancellation: cancellation.clone(),
+        };
+        let rounds = Cell::new(0);
+        let tracer = Tracer::new(&config, |_| rounds.set(rounds.get() + 1))
+            .with_cancellation(cancellation.clone());
+        tracer.trace(network).unwrap();
+        assert!(cancellation.is_cancelled());
+        assert_eq!(30, sent.get());
+        assert!(rounds.get() > 0);
+    }
+
+    #[test]
+    fn test_round_parameters() {
+        let parameters = RoundParameters::new(TimeToLive(1), TimeToLive(64)).unwrap();
+        assert_eq!((TimeToLive(1), TimeToLive(64)), parameters.ttl_range());
+        let shared = parameters.clone();
+        shared.set_ttl_range(TimeToLive(5), TimeToLive(5)).unwrap();
+        assert_eq!((TimeToLive(5), TimeToLive(5)), parameters.ttl_range());
+        let error = |first_ttl, max_ttl| {
+            shared
+                .set_ttl_range(TimeToLive(first_ttl), TimeToLive(max_ttl))
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!("invalid config: first_ttl (6) > max_ttl (5)", error(6, 5));
+        assert_eq!(
+            "invalid config: ttl range (0..=5) must be within 1..=254",
+            error(0, 5)
+        );
+        assert_eq!(
+            "invalid config: ttl range (1..=255) must be within 1..=254",
+            error(1, 255)
+        );
+        assert_eq!((TimeToLive(5), TimeToLive(5)), parameters.ttl_range());
+        assert!(RoundParameters::new(TimeToLive(2), TimeToLive(1)).is_err());
+    }
+
+    /// A network which receives nothing, recording the number of probes sent and the timeout of each wait to receive.
+    struct WaitingNetwork {
+        sent: usize,
+        waits: Rc<RefCell<Vec<(usize, Duration)>>>,
+        cancellation: CancellationToken,
+    }
+
+    impl Network for WaitingNetwork {
+        fn send_probe(&mut self, _probe: Probe) -> TraceResult<()> {
+            self.sent += 1;
+            Ok(())
+        }
+
+        fn recv_probe(&mut self) -> TraceResult<Option<ProbeResponse>> {
+            unreachable!()
+        }
+
+        fn recv_probe_within(&mut self, timeout: Duration) -> TraceResult<Option<ProbeResponse>> {
+            let mut waits = self.waits.borrow_mut();
+            waits.push((self.sent, timeout));
+            if waits.len() == 3 {
+                self.cancellation.cancel();
+            }
+            Ok(None)
+        }
+    }
+
+    #[test]
+    fn test_recv_timeout() {
+        let config = TracerConfig::new(
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+            TracerProtocol::Icmp,
+            None,
+            1,
+            1,
+            64,
+            Duration::from_millis(100),
+            3,
+            33000,
+            Duration::from_millis(10),
+            Duration::from_millis(1),
+            Duration::from_secs(30),
+            84,
+            0,
+        )
+        .unwrap();
+        let cancellation = CancellationToken::new();
+        let waits = Rc::new(RefCell::new(Vec::new()));
+        let network = WaitingNetwork {
+            sent: 0,
+            waits: waits.clone(),
+            cancellation: cancellation.clone(),
+        };
+        Tracer::new(&config, |_| {})
+            .with_cancellation(cancellation)
+            .trace(network)
+            .unwrap();
+        let waits = waits.take();
+        assert_eq!((1, Duration::ZERO), waits[0]);
+        assert_eq!(2, waits[1].0);
+        assert!(waits[1].1 > Duration::from_secs(29) && waits[1].1 <= Duration::from_secs(30));
+        assert_eq!(2, waits[2].0);
+    }
+
+    #[test]
+    fn test_round_jitter() {
+        let max = Duration::from_millis(250);
+        let offsets = |seed| {
+            let mut jitter = RoundJitter::new(max, seed);
+            (0..100).map(|_| jitter.next_offset()).collect::<Vec<_>>()
+        };
+        let first = offsets(42);
+        assert!(first.iter().all(|offset| *offset <= max));
+        assert!(first.iter().any(|offset| *offset > max / 2));
+        assert!(first.iter().any(|offset| *offset < max / 2));
+        assert_eq!(first, offsets(42));
+        assert_ne!(first, offsets(43));
+    }
+
+    #[test]
+    fn test_round_jitter_zero() {
+        let mut jitter = RoundJitter::new(Duration::ZERO, 42);
+        assert!((0..10).all(|_| jitter.next_offset().is_zero()));
+    }
+
+    #[test]
+    fn test_recv_timeout_round_jitter() {
+        let round_jitter = Duration::from_secs(20);
+        let config = TracerConfig::new(
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+            TracerProtocol::Icmp,
+            None,
+            1,
+            1,
+            64,
+            Duration::from_millis(100),
+            3,
+            33000,
+            Duration::from_millis(10),
+            Duration::from_millis(1),
+            Duration::from_secs(30),
+            84,
+            0,
+        )
+        .unwrap()
+        .with_round_jitter(round_jitter, 7)
+        .unwrap();
+        let offset = RoundJitter::new(round_jitter, 7).next_offset();
+        let cancellation = CancellationToken::new();
+        let waits = Rc::new(RefCell::new(Vec::new()));
+        let network = WaitingNetwork {
+            sent: 0,
+            waits: waits.clone(),
+            cancellation: cancellation.clone(),
+        };
+        Tracer::new(&config, |_| {})
+            .with_cancellation(cancellation)
+            .trace(network)
+            .unwrap();
+        let waits = waits.take();
+        assert_eq!(0, waits[0].0);
+        assert!(waits[0].1 <= offset.max(MIN_RECV_TIMEOUT));
+        assert!(waits[0].1 + Duration::from_secs(1) > offset);
+    }
+
+    #[test]
+    fn test_round_jitter_beyond_max_round_duration() {
+        let config = TracerConfig::new(
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+            TracerProtocol::Icmp,
+            None,
+            1,
+            1,
+            64,
+            Duration::from_millis(100),
+            3,
+            33000,
+            Duration::from_millis(10),
+            Duration::from_millis(1),
+            Duration::from_secs(1),
+            84,
+            0,
+        )
+        .unwrap();
+        let err = config
+            .with_round_jitter(Duration::from_secs(2), 7)
+            .unwrap_err();
+        assert_eq!(
+            "invalid config: round_jitter (2s) > max_round_duration (1s)",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_fixed_ttl_out_of_range() {
+        let config = TracerConfig::new(
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+            TracerProtocol::Icmp,
+            None,
+            1,
+            1,
+            64,
+            Duration::from_millis(100),
+            3,
+            33000,
+            Duration::from_millis(10),
+            Duration::from_millis(1),
+            Duration::from_secs(1),
+            84,
+            0,
+        )
+        .unwrap();
+        let fixed = config.clone().with_fixed_ttl(7).unwrap();
+        assert!(fixed.fixed_ttl);
+        assert_eq!(
+            (TimeToLive(7), TimeToLive(7)),
+            (fixed.first_ttl, fixed.max_ttl)
+        );
+        let err = config.clone().with_fixed_ttl(0).unwrap_err();
+        assert_eq!(
+            "invalid config: fixed_ttl (0) must be in the range 1..=254",
+            err.to_string()
+        );
+        assert!(config.with_fixed_ttl(255).is_err());
+    }
+}
@@ -0,0 +1,943 @@
+use super::{CancellationToken, CompletionReason, RoundParameters, Tracer, TracerRound};
+use crate::error::TraceResult;
+use crate::net::{AsyncNetwork, ChannelMetrics};
+use crate::probe::{FlowKey, ProbeResponse};
+use crate::types::{MaxInflight, TimeToLive};
+use crate::{Probe, TracerConfig};
+use ::tracing::{trace_span, Instrument};
+use std::future::{self, Future};
+use std::pin::pin;
+use std::task::Poll;
+use std::time::{Duration, SystemTime};
+use tokio::sync::mpsc;
+
+/// The output from a round of tracing, which owns the probes of the round such that it may be sent to another task.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedTracerRound {
+    /// The state of all `Probe` that were sent in the round.
+    pub probes: Vec<Probe>,
+    /// The largest time-to-live (ttl) for which we received a reply in the round.
+    pub largest_ttl: TimeToLive,
+    /// Indicates what triggered the completion of the tracing round.
+    pub reason: CompletionReason,
+    /// The number of probes which could be in flight at once in the round, if it is adaptive.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub inflight_window: Option<MaxInflight>,
+    /// How long the round lasted, as measured by the monotonic clock, if known.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub duration: Option<Duration>,
+    /// When the round started, after any jitter of its start, if known.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub started: Option<SystemTime>,
+    /// The counts of the packets received by the channel as of the completion of the round, if they are recorded.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub channel_metrics: Option<ChannelMetrics>,
+    /// The flow key of the first probe sent in the round, if it is recorded.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub flow_key: Option<FlowKey>,
+}
+
+impl OwnedTracerRound {
+    /// Borrow the round as a `TracerRound`, such as to update a trace from it.
+    #[must_use]
+    pub fn as_round(&self) -> TracerRound<'_> {
+        TracerRound {
+            inflight_window: self.inflight_window,
+            duration: self.duration,
+            started: self.started,
+            channel_metrics: self.channel_metrics,
+            flow_key: self.flow_key,
+            ..TracerRound::new(&self.probes, self.largest_ttl, self.reason)
+        }
+    }
+}
+
+impl From<&TracerRound<'_>> for OwnedTracerRound {
+    fn from(round: &TracerRound<'_>) -> Self {
+        Self {
+            probes: round.probes.to_vec(),
+            largest_ttl: round.largest_ttl,
+            reason: round.reason,
+            inflight_window: round.inflight_window,
+            duration: round.duration,
+            started: round.started,
+            channel_metrics: round.channel_metrics,
+            flow_key: round.flow_key,
+        }
+    }
+}
+
+/// Trace a path to a target on a tokio runtime.
+///
+/// The algorithm is that of the `Tracer`, though rather than blocking a thread whilst awaiting a response the trace
+/// yields to the runtime, such that many targets may be traced concurrently on a single runtime, and each round is
+/// sent to an `mpsc` channel rather than published to a callback.
+#[derive(Debug, Clone)]
+pub struct AsyncTracer(Tracer<()>);
+
+impl AsyncTracer {
+    #[must_use]
+    pub fn new(config: &TracerConfig) -> Self {
+        Self(Tracer::from_config(config, ()))
+    }
+
+    /// End the trace once `cancellation` is cancelled.
+    #[must_use]
+    pub fn with_cancellation(self, cancellation: CancellationToken) -> Self {
+        Self(self.0.with_cancellation(cancellation))
+    }
+
+    /// Read the range of ttl probed from `parameters` at the start of each round, rather than from the config.
+    #[must_use]
+    pub fn with_parameters(self, parameters: RoundParameters) -> Self {
+        Self(self.0.with_parameters(parameters))
+    }
+
+    /// Run a trace and send each round to `rounds`.
+    ///
+    /// The trace ends once the maximum number of rounds have been traced, `rounds` has been closed or the trace has
+    /// been cancelled.  The trace may also be ended by dropping the future, in which case the round in progress is
+    /// not sent.
+    pub async fn trace<N: AsyncNetwork>(
+        self,
+        mut network: N,
+        rounds: mpsc::Sender<OwnedTracerRound>,
+    ) -> TraceResult<()> {
+        let tracer = self.0;
+        // The state holds a buffer of every probe of the round and so is boxed rather than held in the future.
+        let mut state = Box::new(tracer.new_state());
+        while !state.finished(tracer.max_rounds) && !tracer.is_cancelled() {
+            tracer.send_request(&mut state, |probe| network.send_probe(probe))?;
+            let timeout = tracer.recv_timeout(&state);
+            let recv = recv_response(&mut network, timeout, tracer.cancellation.as_ref())
+                .instrument(trace_span!("tracer.recv_response"));
+            match recv.await {
+                Some(next) => {
+                    if let Some(attribution) = tracer.process_response(&mut state, next?) {
+                        network.record_attribution(attribution);
+                    }
+                }
+                None => break,
+            }
+            if tracer.is_round_complete(&state) {
+                let round = OwnedTracerRound::from(&tracer.round(
+                    &state,
+                    network.metrics(),
+                    network.flow_key(),
+                ));
+                if rounds.send(round).await.is_err() {
+                    break;
+                }
+                tracer.advance_round(&mut state);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Receive the next incoming packet, waiting no longer than `timeout` for one.
+///
+/// Returns `None` if the trace is cancelled whilst waiting.
+async fn recv_response<N: AsyncNetwork>(
+    network: &mut N,
+    timeout: Duration,
+    cancellation: Option<&CancellationToken>,
+) -> Option<TraceResult<Option<ProbeResponse>>> {
+    let mut recv = pin!(tokio::time::timeout(
+        timeout,
+        future::poll_fn(|cx| network.poll_recv_probe(cx))
+    ));
+    let mut cancelled = pin!(cancelled(cancellation));
+    future::poll_fn(|cx| {
+        if cancelled.as_mut().poll(cx).is_ready() {
+            return Poll::Ready(None);
+        }
+        recv.as_mut()
+            .poll(cx)
+            .map(|recv| Some(recv.unwrap_or(Ok(None))))
+    })
+    .await
+}
+
+/// Wait until the trace is cancelled, which is never if there is no `cancellation`.
+async fn cancelled(cancellation: Option<&CancellationToken>) {
+    match cancellation {
+        Some(cancellation) => cancellation.cancelled().await,
+        None => future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::Attribution;
+    use crate::{
+        Hop, ParseError, ProbeResponseData, ProbeStatus, ResponderPorts, SendFailure, Trace,
+        TracerError, TracerProtocol, SHORTER_PATH_ROUNDS,
+    };
+    use itertools::Itertools;
+    use std::collections::VecDeque;
+    use std::io;
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::num::NonZeroI32;
+    use std::pin::Pin;
+    use std::task::Context;
+    use std::time::SystemTime;
+    use tokio::time::{Instant, Sleep};
+
+    /// A simulated network of `hops` hops, each of which responds after 1ms per hop, to the target at the last hop.
+    ///
+    /// A network of no hops never responds.  The path of a network of several paths is chosen by the round, such as to
+    /// simulate an anycast target which is reached by paths of different lengths.
+    ///
+    /// The network counts the packets it delivers as a channel would, and may be scripted to deliver packets which are
+    /// not attributed to a probe: the responses to the probes of another trace, garbage, duplicates of every response
+    /// and responses which are delayed.
+    struct SimulatedNetwork {
+        target: IpAddr,
+        paths: Vec<u8>,
+        /// The rounds in which the hops short of the target do not respond, as if they were rate limiting ICMP.
+        rate_limited: Vec<bool>,
+        /// Whether every response is accompanied by one to a probe of another trace.
+        cross_talk: bool,
+        /// Whether every probe is followed by a packet too short to be read.
+        garbage: bool,
+        /// Whether every response is delivered twice.
+        duplicates: bool,
+        /// The ttl of a hop which responds only after the delay given.
+        delayed: Option<(u8, Duration)>,
+        /// The ports of the TCP replies of the target, if it replies over TCP rather than ICMP.
+        tcp_ports: Option<ResponderPorts>,
+        /// The ttl of the probes which fail to send, with the error of the OS given.
+        failing: Option<(u8, i32)>,
+        /// The parameters of the trace, the ttl range of which is set to each of the `ttl_ranges`, as if from a UI,
+        /// whilst the round given is in progress.
+        parameters: Option<RoundParameters>,
+        ttl_ranges: Vec<(usize, u8, u8)>,
+        deliveries: VecDeque<(Instant, Delivery)>,
+        delay: Pin<Box<Sleep>>,
+        metrics: ChannelMetrics,
+    }
+
+    /// A packet delivered by a `SimulatedNetwork`.
+    enum Delivery {
+        Response(ProbeResponse),
+        Garbage,
+    }
+
+    impl SimulatedNetwork {
+        fn new(target: IpAddr, hops: u8) -> Self {
+            Self::with_paths(target, &[hops])
+        }
+
+        /// A network the path of which in each round is the next of `paths`, in turn.
+        fn with_paths(target: IpAddr, paths: &[u8]) -> Self {
+            Self {
+                target,
+                paths: paths.to_vec(),
+                rate_limited: vec![],
+                cross_talk: false,
+                garbage: false,
+                duplicates: false,
+                delayed: None,
+                tcp_ports: None,
+                failing: None,
+                parameters: None,
+                ttl_ranges: vec![],
+                deliveries: VecDeque::new(),
+                delay: Box::pin(tokio::time::sleep(Duration::ZERO)),
+                metrics: ChannelMetrics::default(),
+            }
+        }
+
+        /// A network in which the hops short of the target do not respond in each round for which `rate_limited` is
+        /// true.
+        fn with_rate_limited_rounds(self, rate_limited: &[bool]) -> Self {
+            Self {
+                rate_limited: rate_limited.to_vec(),
+                ..self
+            }
+        }
+
+        /// A network which also delivers a response to a probe of another trace for every response, and a garbage
+        /// packet for every probe.
+        fn with_cross_talk_and_garbage(self) -> Self {
+            Self {
+                cross_talk: true,
+                garbage: true,
+                ..self
+            }
+        }
+
+        /// A network which delivers every response twice.
+        fn with_duplicates(self) -> Self {
+            Self {
+                duplicates: true,
+                ..self
+            }
+        }
+
+        /// A network in which the hop at `ttl` responds only after `delay`.
+        fn with_delayed_hop(self, ttl: u8, delay: Duration) -> Self {
+            Self {
+                delayed: Some((ttl, delay)),
+                ..self
+            }
+        }
+
+        /// A network in which the target replies over TCP, with the `ports` given.
+        fn with_tcp_target(self, ports: ResponderPorts) -> Self {
+            Self {
+                tcp_ports: Some(ports),
+                ..self
+            }
+        }
+
+        /// A network to which every probe of `ttl` fails to send with the error of the OS `os_error`, as if refused
+        /// by a local firewall.
+        fn with_failing_ttl(self, ttl: u8, os_error: i32) -> Self {
+            Self {
+                failing: Some((ttl, os_error)),
+                ..self
+            }
+        }
+
+        /// A network which sets each of the `ttl_ranges` of `parameters`, the first and max ttl, upon the first
+        /// probe of the round given.
+        fn with_ttl_ranges(
+            self,
+            parameters: &RoundParameters,
+            ttl_ranges: &[(usize, u8, u8)],
+        ) -> Self {
+            Self {
+                parameters: Some(parameters.clone()),
+                ttl_ranges: ttl_ranges.to_vec(),
+                ..self
+            }
+        }
+
+        fn deliver(&mut self, due: Instant, delivery: Delivery) {
+            let at = self.deliveries.partition_point(|(at, _)| *at <= due);
+            self.deliveries.insert(at, (due, delivery));
+        }
+    }
+
+    impl AsyncNetwork for SimulatedNetwork {
+        fn send_probe(&mut self, probe: Probe) -> TraceResult<()> {
+            if let Some(parameters) = &self.parameters {
+                for (_, first_ttl, max_ttl) in self
+                    .ttl_ranges
+                    .iter()
+                    .filter(|(round, ..)| *round == probe.round.0)
+                {
+                    parameters.set_ttl_range(TimeToLive(*first_ttl), TimeToLive(*max_ttl))?;
+                }
+            }
+            if let Some((ttl, os_error)) = self.failing.filter(|(ttl, _)| *ttl == probe.ttl.0) {
+                let err = io::Error::from_raw_os_error(os_error);
+                let addr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, ttl));
+                return Err(TracerError::NetworkUnreachable(addr, err));
+            }
+            if self.garbage {
+                self.deliver(Instant::now(), Delivery::Garbage);
+            }
+            let hops = self.paths[probe.round.0 % self.paths.len()];
+            if hops == 0 {
+                return Ok(());
+            }
+            let ttl = probe.ttl.0.min(hops);
+            if ttl < hops && self.rate_limited.get(probe.round.0) == Some(&true) {
+                return Ok(());
+            }
+            let delay = match self.delayed {
+                Some((delayed_ttl, delay)) if delayed_ttl == ttl => delay,
+                _ => Duration::from_millis(u64::from(ttl)),
+            };
+            let due = Instant::now() + delay;
+            let tcp_ports = self.tcp_ports;
+            let response = |addr, identifier| {
+                let data =
+                    ProbeResponseData::new(SystemTime::now(), addr, identifier, probe.sequence.0);
+                match tcp_ports {
+                    _ if ttl < hops => ProbeResponse::TimeExceeded(data),
+                    Some(ports) => ProbeResponse::TcpReply(data.with_responder_ports(ports)),
+                    None => ProbeResponse::EchoReply(data),
+                }
+            };
+            let addr = if ttl == hops {
+                self.target
+            } else {
+                IpAddr::V4(Ipv4Addr::new(10, 0, 0, ttl))
+            };
+            self.deliver(due, Delivery::Response(response(addr, 0)));
+            if self.duplicates {
+                self.deliver(due, Delivery::Response(response(addr, 0)));
+            }
+            if self.cross_talk {
+                self.deliver(due, Delivery::Response(response(addr, 4321)));
+            }
+            Ok(())
+        }
+
+        fn poll_recv_probe(
+            &mut self,
+            cx: &mut Context<'_>,
+        ) -> Poll<TraceResult<Option<ProbeResponse>>> {
+            let Some(&(due, _)) = self.deliveries.front() else {
+                return Poll::Pending;
+            };
+            self.delay.as_mut().reset(due);
+            if self.delay.as_mut().poll(cx).is_ready() {
+                let response = match self.deliveries.pop_front().unwrap().1 {
+                    Delivery::Response(mut response) => {
+                        if let ProbeResponse::TimeExceeded(data)
+                        | ProbeResponse::EchoReply(data)
+                        | ProbeResponse::TcpReply(data) = &mut response
+                        {
+                            data.recv = SystemTime::now();
+                            data.recv_at = std::time::Instant::now();
+                        }
+                        Ok(Some(response))
+                    }
+                    Delivery::Garbage => Err(TracerError::MalformedPacket(ParseError::Truncated(
+                        "ICMP header",
+                    ))),
+                };
+                self.metrics.count_packet(&response);
+                Poll::Ready(Ok(response.unwrap_or(None)))
+            } else {
+                Poll::Pending
+            }
+        }
+
+        fn record_attribution(&mut self, attribution: Attribution) {
+            self.metrics.count_attribution(attribution);
+        }
+
+        fn metrics(&self) -> Option<ChannelMetrics> {
+            Some(self.metrics)
+        }
+    }
+
+    fn config(target: IpAddr, max_rounds: usize, max_round_duration: Duration) -> TracerConfig {
+        TracerConfig::new(
+            target,
+            TracerProtocol::Icmp,
+            Some(max_rounds),
+            1234,
+            1,
+            64,
+            Duration::from_millis(10),
+            24,
+            33000,
+            Duration::from_millis(10),
+            Duration::from_millis(20),
+            max_round_duration,
+            84,
+            0,
+        )
+        .unwrap()
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    /// Trace `target` over a simulated network of `hops` hops, returning the rounds traced, which are those numbered
+    /// from 0 to `max_rounds`.
+    async fn trace(target: IpAddr, hops: u8, max_rounds: usize) -> Vec<OwnedTracerRound> {
+        trace_network(SimulatedNetwork::new(target, hops), max_rounds).await
+    }
+
+    /// Trace over a simulated `network`, returning the rounds traced, which are those numbered from 0 to `max_rounds`.
+    async fn trace_network(network: SimulatedNetwork, max_rounds: usize) -> Vec<OwnedTracerRound> {
+        let config = config(network.target, max_rounds, Duration::from_secs(1));
+        trace_with_config(network, &config).await
+    }
+
+    /// Trace over a simulated `network` with `config`, returning the rounds traced.
+    async fn trace_with_config(
+        network: SimulatedNetwork,
+        config: &TracerConfig,
+    ) -> Vec<OwnedTracerRound> {
+        let (tx, mut rx) = mpsc::channel(8);
+        let trace = tokio::spawn(AsyncTracer::new(config).trace(network, tx));
+        let mut rounds = vec![];
+        while let Some(round) = rx.recv().await {
+            rounds.push(round);
+        }
+        trace.await.unwrap().unwrap();
+        rounds
+    }
+
+    #[test]
+    fn test_trace() {
+        let target = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let rounds = block_on(trace(target, 4, 2));
+        assert_eq!(3, rounds.len());
+        for round in &rounds {
+            assert!(matches!(round.reason, CompletionReason::TargetFound));
+            assert_eq!(TimeToLive(4), round.largest_ttl);
+            assert_eq!(None, round.inflight_window);
+            let round = round.as_round();
+            let hosts: Vec<_> = round
+                .probes
+                .iter()
+                .filter(|probe| probe.status == ProbeStatus::Complete)
+                .map(|probe| (probe.ttl.0, probe.host.unwrap()))
+                .collect();
+            assert_eq!(
+                vec![
+                    (1, IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))),
+                    (2, IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2))),
+                    (3, IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3))),
+                    (4, target),
+                ],
+                hosts[..4]
+            );
+        }
+    }
+
+    #[test]
+    fn test_trace_alternating_paths() {
+        let target = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let mut paths = vec![10, 12, 10, 12];
+        paths.extend([10; SHORTER_PATH_ROUNDS]);
+        let rounds = block_on(async {
+            let network = SimulatedNetwork::with_paths(target, &paths);
+            trace_network(network, paths.len() - 1).await
+        });
+        let largest_ttls: Vec<_> = rounds.iter().map(|round| round.largest_ttl.0).collect();
+        assert_eq!(paths, largest_ttls);
+        let mut trace = Trace::new(256);
+        for round in &rounds[..4] {
+            trace.update_from_round(&round.as_round());
+        }
+        assert_eq!(12, trace.target_hop().ttl());
+        assert_eq!(12, trace.hops().len());
+        for round in &rounds[4..] {
+            trace.update_from_round(&round.as_round());
+        }
+        assert_eq!(10, trace.target_hop().ttl());
+        assert_eq!(10, trace.hops().len());
+        assert_eq!(2, trace.historic_hops().len());
+        assert_eq!(Some(target), trace.target_hop().primary_addr());
+        let change = trace.path_change().unwrap();
+        assert_eq!((12, 10), (change.from(), change.to()));
+    }
+
+    /// The number of probes sent in each of `rounds`.
+    fn probes_sent(rounds: &[OwnedTracerRound]) -> Vec<usize> {
+        rounds
+            .iter()
+            .map(|round| {
+                round
+                    .probes
+                    .iter()
+                    .filter(|probe| probe.status != ProbeStatus::NotSent)
+                    .count()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_trace_probes_to_target_and_margin() {
+        let target = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let rounds = block_on(trace(target, 4, 3));
+        let sent = probes_sent(&rounds);
+        assert!(sent[0] > 5);
+        assert_eq!(vec![5, 5, 5], sent[1..]);
+        for round in &rounds {
+            let found = round
+                .probes
+                .iter()
+                .filter(|probe| probe.host == Some(target))
+                .filter_map(|probe| probe.received)
+                .min()
+                .unwrap();
+            assert!(round
+                .probes
+                .iter()
+                .filter_map(|probe| probe.sent)
+                .all(|sent| sent <= found));
+        }
+        let rounds = block_on(async {
+            let network = SimulatedNetwork::new(target, 4);
+            let config = config(target, 3, Duration::from_secs(1)).with_ttl_margin(0);
+            trace_with_config(network, &config).await
+        });
+        assert_eq!(vec![4, 4, 4], probes_sent(&rounds)[1..]);
+    }
+
+    #[test]
+    fn test_trace_send_failures() {
+        let target = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let rounds = block_on(async {
+            let network = SimulatedNetwork::new(target, 4).with_failing_ttl(2, 101);
+            trace_network(network, 2).await
+        });
+        let failure = SendFailure {
+            os_error: NonZeroI32::new(101),
+        };
+        for round in &rounds {
+            assert!(matches!(round.reason, CompletionReason::TargetFound));
+            let probe = round.probes.iter().find(|probe| probe.ttl.0 == 2).unwrap();
+            assert_eq!(ProbeStatus::Failed(failure), probe.status);
+        }
+        let mut trace = Trace::new(256);
+        for round in &rounds {
+            trace.update_from_round(&round.as_round());
+        }
+        let hop = &trace.hops()[1];
+        assert_eq!((0, 0), (hop.total_sent(), hop.total_recv()));
+        assert_eq!(3, hop.total_failed());
+        assert_eq!(Some(failure), hop.last_failure());
+        assert!(trace.hops().iter().all(|hop| hop.loss_pct() == 0.0));
+        assert!(trace
+            .hops()
+            .iter()
+            .filter(|hop| hop.ttl() != 2)
+            .all(|hop| hop.total_failed() == 0 && hop.total_recv() == 3));
+    }
+
+    #[test]
+    fn test_trace_no_phantom_hops() {
+        let target = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let paths = [4, 4, 6];
+        let rounds = block_on(async {
+            let network = SimulatedNetwork::with_paths(target, &paths);
+            trace_network(network, paths.len() - 1).await
+        });
+        assert_eq!(
+            vec![4, 4, 6],
+            rounds
+                .iter()
+                .map(|round| round.largest_ttl.0)
+                .collect::<Vec<_>>()
+        );
+        let mut trace = Trace::new(256);
+        for round in &rounds[..2] {
+            trace.update_from_round(&round.as_round());
+        }
+        assert_eq!(4, trace.all_hops().len());
+        trace.update_from_round(&rounds[2].as_round());
+        assert_eq!(6, trace.hops().len());
+        let hop = &trace.hops()[4];
+        assert_eq!(1, hop.total_sent());
+        assert_eq!(
+            vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 5))],
+            hop.addrs().copied().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_trace_adaptive_inflight() {
+        let target = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let rate_limited = [false, true, true, true, false, false, false];
+        let rounds = block_on(async {
+            let network = SimulatedNetwork::new(target, 6).with_rate_limited_rounds(&rate_limited);
+            let config = config(target, rate_limited.len() - 1, Duration::from_secs(1))
+                .with_adaptive_inflight();
+            trace_with_config(network, &config).await
+        });
+        let windows: Vec<_> = rounds
+            .iter()
+            .map(|round| round.inflight_window.unwrap().0)
+            .collect();
+        assert_eq!(vec![24, 24, 12, 6, 3, 4, 5], windows);
+        assert!(rounds
+            .iter()
+            .all(|round| matches!(round.reason, CompletionReason::TargetFound)));
+    }
+
+    #[test]
+    fn test_trace_round_jitter() {
+        let target = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let round_jitter = Duration::from_millis(30);
+        let rounds = block_on(async {
+            let network = SimulatedNetwork::new(target, 3);
+            let config = config(target, 4, Duration::from_secs(1))
+                .with_round_jitter(round_jitter, 42)
+                .unwrap();
+            trace_with_config(network, &config).await
+        });
+        assert_eq!(5, rounds.len());
+        for (round, next) in rounds.iter().tuple_windows() {
+            assert!(matches!(round.reason, CompletionReason::TargetFound));
+            let duration = round.duration.unwrap();
+            assert!(duration > Duration::from_millis(20));
+            let gap = next
+                .started
+                .unwrap()
+                .duration_since(round.started.unwrap())
+                .unwrap();
+            assert!(gap >= duration);
+        }
+    }
+
+    #[test]
+    fn test_trace_fixed_ttl() {
+        let target = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let rounds = block_on(async {
+            let network = SimulatedNetwork::new(target, 4);
+            let config = config(target, 4, Duration::from_secs(1))
+                .with_fixed_ttl(2)
+                .unwrap();
+            trace_with_config(network, &config).await
+        });
+        assert_eq!(5, rounds.len());
+        for round in &rounds {
+            assert!(matches!(round.reason, CompletionReason::FixedTtlAnswered));
+            assert_eq!(TimeToLive(2), round.largest_ttl);
+            assert!(round.duration.unwrap() < Duration::from_millis(500));
+        }
+        assert_eq!(vec![1; 5], probes_sent(&rounds));
+        let mut trace = Trace::new(256);
+        for round in &rounds {
+            trace.update_from_round(&round.as_round());
+        }
+        assert_eq!(1, trace.hops().len());
+        let hop = &trace.hops()[0];
+        assert_eq!(2, hop.ttl());
+        assert_eq!((5, 5), (hop.total_sent(), hop.total_recv()));
+        assert_eq!(
+            vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2))],
+            hop.addrs().copied().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_trace_fixed_ttl_beyond_target() {
+        let target = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let rounds = block_on(async {
+            let network = SimulatedNetwork::new(target, 4);
+            let config = config(target, 2, Duration::from_secs(1))
+                .with_fixed_ttl(6)
+                .unwrap();
+            trace_with_config(network, &config).await
+        });
+        assert!(rounds
+            .iter()
+            .all(|round| matches!(round.reason, CompletionReason::TargetFound)));
+        assert_eq!(vec![1; 3], probes_sent(&rounds));
+        let mut trace = Trace::new(256);
+        for round in &rounds {
+            trace.update_from_round(&round.as_round());
+        }
+        assert_eq!(1, trace.hops().len());
+        assert_eq!(6, trace.target_hop().ttl());
+        assert_eq!(Some(target), trace.target_hop().primary_addr());
+    }
+
+    #[test]
+    fn test_trace_ttl_range_changed() {
+        let target = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let parameters = RoundParameters::new(TimeToLive(1), TimeToLive(64)).unwrap();
+        let tracer = AsyncTracer::new(&config(target, 4, Duration::from_millis(100)))
+            .with_parameters(parameters.clone());
+        let (tx, mut rx) = mpsc::channel(8);
+        let rounds = block_on(async {
+            let network = SimulatedNetwork::new(target, 6)
+                .with_ttl_ranges(&parameters, &[(1, 3, 4), (3, 1, 64)]);
+            let trace = tokio::spawn(tracer.trace(network, tx));
+            let mut rounds = vec![];
+            while let Some(round) = rx.recv().await {
+                rounds.push(round);
+            }
+            trace.await.unwrap().unwrap();
+            rounds
+        });
+        assert_eq!((TimeToLive(1), TimeToLive(64)), parameters.ttl_range());
+        let ttls: Vec<Vec<_>> = rounds
+            .iter()
+            .map(|round| {
+                round
+                    .probes
+                    .iter()
+                    .filter(|probe| probe.status != ProbeStatus::NotSent)
+                    .map(|probe| probe.ttl.0)
+                    .collect()
+            })
+            .collect();
+        // Each range takes effect from the round after that in progress when it was set, the first round probing
+        // beyond the target as its ttl is not yet known.
+        assert_eq!(
+            vec![
+                (1..=7).collect::<Vec<_>>(),
+                vec![3, 4],
+                vec![3, 4],
+                (1..=7).collect(),
+            ],
+            ttls[1..]
+        );
+        let reasons: Vec<_> = rounds
+            .iter()
+            .map(|round| matches!(round.reason, CompletionReason::TargetFound))
+            .collect();
+        assert_eq!(vec![true, true, false, false, true], reasons);
+        let mut trace = Trace::new(256);
+        for round in &rounds {
+            trace.update_from_round(&round.as_round());
+        }
+        let sent: Vec<_> = trace.hops().iter().map(Hop::total_sent).collect();
+        assert_eq!(vec![3, 3, 5, 5, 3, 3], sent);
+    }
+
+    /// Trace over a simulated network in which the target replies over TCP with `ports`, returning the trace.
+    fn trace_tcp_target(ports: ResponderPorts) -> Trace {
+        let target = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let rounds = block_on(async {
+            let network = SimulatedNetwork::new(target, 3).with_tcp_target(ports);
+            trace_network(network, 2).await
+        });
+        let mut trace = Trace::new(256);
+        for round in &rounds {
+            assert!(matches!(round.reason, CompletionReason::TargetFound));
+            trace.update_from_round(&round.as_round());
+        }
+        trace
+    }
+
+    #[test]
+    fn test_trace_responder_ports_matched() {
+        let ports = ResponderPorts::new(443, 443);
+        let trace = trace_tcp_target(ports);
+        let hop = trace.target_hop();
+        assert_eq!(vec![(ports, 3)], hop.responder_ports());
+        assert!(!hop.port_mismatch());
+        assert!(trace.hops()[..2]
+            .iter()
+            .all(|hop| hop.responder_ports().is_empty()));
+    }
+
+    #[test]
+    fn test_trace_responder_ports_mismatched() {
+        let ports = ResponderPorts::new(443, 3128);
+        let trace = trace_tcp_target(ports);
+        let hop = trace.target_hop();
+        assert_eq!(vec![(ports, 3)], hop.responder_ports());
+        assert!(hop.port_mismatch());
+    }
+
+    /// The counts of the packets received by the channel as of the last of `rounds`, which must account for every
+    /// packet received.
+    fn last_channel_metrics(rounds: &[OwnedTracerRound]) -> ChannelMetrics {
+        let metrics = rounds.last().unwrap().channel_metrics.unwrap();
+        assert_eq!(
+            metrics.received,
+            metrics.matched
+                + metrics.ignored
+                + metrics.malformed
+                + metrics.duplicates
+                + metrics.late
+        );
+        metrics
+    }
+
+    #[test]
+    fn test_trace_channel_metrics_cross_talk_and_garbage() {
+        let target = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let rounds = block_on(async {
+            let network = SimulatedNetwork::new(target, 4).with_cross_talk_and_garbage();
+            trace_network(network, 3).await
+        });
+        let metrics = last_channel_metrics(&rounds);
+        assert!(metrics.matched >= 16);
+        assert_eq!(metrics.matched + metrics.late, metrics.ignored);
+        let sent: usize = probes_sent(&rounds).iter().sum();
+        assert_eq!(sent as u64, metrics.malformed);
+        assert_eq!(0, metrics.duplicates);
+    }
+
+    #[test]
+    fn test_trace_channel_metrics_duplicates() {
+        let target = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let rounds = block_on(async {
+            let network = SimulatedNetwork::new(target, 4).with_duplicates();
+            trace_network(network, 3).await
+        });
+        let metrics = last_channel_metrics(&rounds);
+        assert!(metrics.matched >= 16);
+        assert_eq!(metrics.matched, metrics.duplicates);
+        assert_eq!(0, metrics.ignored + metrics.malformed);
+        let mut trace = Trace::new(256);
+        for round in &rounds {
+            trace.update_from_round(&round.as_round());
+        }
+        assert_eq!(Some(metrics), trace.channel_metrics());
+        assert!(trace.hops().iter().all(|hop| hop.total_recv() == 4));
+    }
+
+    #[test]
+    fn test_trace_channel_metrics_late() {
+        let target = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let rounds = block_on(async {
+            let network =
+                SimulatedNetwork::new(target, 4).with_delayed_hop(1, Duration::from_millis(30));
+            trace_network(network, 3).await
+        });
+        let metrics = last_channel_metrics(&rounds);
+        assert!(metrics.late > 0);
+        assert_eq!(0, metrics.ignored + metrics.malformed + metrics.duplicates);
+    }
+
+    #[test]
+    fn test_trace_concurrently() {
+        let targets: Vec<_> = (1..=16)
+            .map(|i| (IpAddr::V4(Ipv4Addr::new(192, 0, 2, i)), 2 + i % 5))
+            .collect();
+        let traces = block_on(async {
+            let traces: Vec<_> = targets
+                .iter()
+                .map(|&(target, hops)| tokio::spawn(trace(target, hops, 2)))
+                .collect();
+            let mut rounds = vec![];
+            for trace in traces {
+                rounds.push(trace.await.unwrap());
+            }
+            rounds
+        });
+        for ((target, hops), rounds) in targets.into_iter().zip(traces) {
+            assert_eq!(3, rounds.len());
+            for round in rounds {
+                assert_eq!(TimeToLive(hops), round.largest_ttl);
+                assert!(round.probes.iter().any(|probe| probe.host == Some(target)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_cancellation() {
+        let target = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let cancellation = CancellationToken::new();
+        let tracer = AsyncTracer::new(&config(target, 10, Duration::from_secs(30)))
+            .with_cancellation(cancellation.clone());
+        let (tx, _rx) = mpsc::channel(8);
+        let result = block_on(async {
+            let trace = tokio::spawn(tracer.trace(SimulatedNetwork::new(target, 0), tx));
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            cancellation.cancel();
+            tokio::time::timeout(Duration::from_secs(1), trace).await
+        });
+        assert!(result.unwrap().unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_rounds_closed() {
+        let target = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let tracer = AsyncTracer::new(&config(target, usize::MAX, Duration::from_secs(1)));
+        let (tx, mut rx) = mpsc::channel(1);
+        let result = block_on(async {
+            let trace = tokio::spawn(tracer.trace(SimulatedNetwork::new(target, 3), tx));
+            assert!(rx.recv().await.is_some());
+            drop(rx);
+            tokio::time::timeout(Duration::from_secs(1), trace).await
+        });
+        assert!(result.unwrap().unwrap().is_ok());
+    }
+}
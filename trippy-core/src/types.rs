@@ -2,6 +2,7 @@ use derive_more::{Add, AddAssign, Rem, Sub};
 
 /// `Round` newtype.
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Ord, PartialOrd, AddAssign)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Round(pub usize);
 
 /// `MaxRound` newtype.
@@ -10,10 +11,12 @@ pub struct MaxRounds(pub usize);
 
 /// `TimeToLive` (ttl) newtype.
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Ord, PartialOrd, Add, Sub, AddAssign)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TimeToLive(pub u8);
 
 /// `Sequence` number newtype.
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Ord, PartialOrd, Add, Sub, AddAssign, Rem)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Sequence(pub u16);
 
 /// `TraceId` newtype.
@@ -22,6 +25,7 @@ pub struct TraceId(pub u16);
 
 /// `MaxInflight` newtype.
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MaxInflight(pub u8);
 
 /// `PacketSize` newtype.